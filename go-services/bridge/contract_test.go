@@ -0,0 +1,95 @@
+package main
+
+import (
+	"bytes"
+	"encoding/json"
+	"os"
+	"path/filepath"
+	"testing"
+
+	"github.com/nats-io/nats.go"
+)
+
+// Golden payloads produced by rust-core (rust-core/src/bridge_contract.rs)
+const fixtureDir = "../../rust-core/fixtures/bridge/v1"
+
+func decodeStrict(t *testing.T, name string, target interface{}) {
+	t.Helper()
+	data, err := os.ReadFile(filepath.Join(fixtureDir, name))
+	if err != nil {
+		t.Fatalf("failed to read fixture %s: %v", name, err)
+	}
+	decoder := json.NewDecoder(bytes.NewReader(data))
+	decoder.DisallowUnknownFields()
+	if err := decoder.Decode(target); err != nil {
+		t.Fatalf("fixture %s does not match Go struct: %v", name, err)
+	}
+}
+
+func TestThreatEventContract(t *testing.T) {
+	var event ThreatEvent
+	decodeStrict(t, "threat_event.json", &event)
+	if event.SourceIP == "" || event.ThreatType == "" || event.Severity == 0 {
+		t.Errorf("threat fixture decoded with empty fields: %+v", event)
+	}
+}
+
+func TestSystemEventContract(t *testing.T) {
+	var event SystemEvent
+	decodeStrict(t, "system_event.json", &event)
+	if event.EventType == "" || event.Message == "" {
+		t.Errorf("system event fixture decoded with empty fields: %+v", event)
+	}
+}
+
+// Every subject must carry the payload its handler decodes
+func TestSubjectFixturesMatchHandlers(t *testing.T) {
+	data, err := os.ReadFile(filepath.Join(fixtureDir, "subjects.json"))
+	if err != nil {
+		t.Fatalf("failed to read subjects.json: %v", err)
+	}
+	var fixtures map[string]string
+	if err := json.Unmarshal(data, &fixtures); err != nil {
+		t.Fatalf("invalid subjects.json: %v", err)
+	}
+
+	handlers := map[string]func() interface{}{
+		subjectThreats: func() interface{} { return &ThreatEvent{} },
+		subjectEvents:  func() interface{} { return &UltraSIEMEvent{} },
+		subjectSystem:  func() interface{} { return &SystemEvent{} },
+	}
+	for subject := range fixtures {
+		if _, ok := handlers[subject]; !ok {
+			t.Errorf("rust-core publishes on %s but the bridge has no handler for it", subject)
+		}
+	}
+	for subject, target := range handlers {
+		name, ok := fixtures[subject]
+		if !ok {
+			t.Errorf("no golden payload for %s", subject)
+			continue
+		}
+		decodeStrict(t, name, target())
+	}
+
+	var event UltraSIEMEvent
+	decodeStrict(t, fixtures[subjectEvents], &event)
+	if event.ID == "" || event.EventType == "" {
+		t.Errorf("event fixture decoded with empty fields: %+v", event)
+	}
+}
+
+func TestContractVersionHeader(t *testing.T) {
+	msg := nats.NewMsg("ultra_siem.threats")
+	if !checkContractVersion(msg) {
+		t.Error("messages without headers must be accepted")
+	}
+	msg.Header.Set(contractVersionHeader, ContractVersion)
+	if !checkContractVersion(msg) {
+		t.Error("current contract version must be accepted")
+	}
+	msg.Header.Set(contractVersionHeader, "999")
+	if checkContractVersion(msg) {
+		t.Error("unknown contract version must be rejected")
+	}
+}
@@ -20,6 +20,34 @@ import (
 	"github.com/nats-io/nats.go"
 )
 
+// ContractVersion is the rust-core bridge contract version this bridge understands
+// (see rust-core/src/bridge_contract.rs and rust-core/fixtures/bridge).
+const ContractVersion = "1"
+
+const contractVersionHeader = "Ultra-Siem-Contract-Version"
+
+// Subjects published by rust-core (rust-core/src/bridge_contract.rs); the golden
+// payload of each is listed in rust-core/fixtures/bridge/v1/subjects.json.
+const (
+	subjectThreats = "ultra_siem.threats"
+	subjectEvents  = "ultra_siem.events"
+	subjectSystem  = "ultra_siem.system"
+)
+
+// checkContractVersion rejects messages produced for an incompatible contract.
+// Messages without the header come from legacy producers and are accepted.
+func checkContractVersion(msg *nats.Msg) bool {
+	if msg.Header == nil {
+		return true
+	}
+	version := msg.Header.Get(contractVersionHeader)
+	if version == "" || version == ContractVersion {
+		return true
+	}
+	log.Printf("❌ Unsupported contract version %q on %s (expected %s)", version, msg.Subject, ContractVersion)
+	return false
+}
+
 // ThreatEvent represents the threat event format from Rust core
 type ThreatEvent struct {
 	Timestamp   int64   `json:"timestamp"`
@@ -537,24 +565,31 @@ func (b *SimpleBridge) Start() error {
 	log.Println("🚀 Starting Ultra SIEM Enhanced Bridge...")
 
 	// Subscribe to threats with timeout
-	threatsSub, err := b.js.Subscribe("ultra_siem.threats", b.handleThreatEvent)
+	threatsSub, err := b.js.Subscribe(subjectThreats, b.handleThreatEvent)
 	if err != nil {
-		return fmt.Errorf("failed to subscribe to ultra_siem.threats: %w", err)
+		return fmt.Errorf("failed to subscribe to %s: %w", subjectThreats, err)
 	}
 	defer threatsSub.Unsubscribe()
 
 	// Subscribe to events with timeout
-	eventsSub, err := b.js.Subscribe("ultra_siem.events", b.handleUltraSIEMEvent)
+	eventsSub, err := b.js.Subscribe(subjectEvents, b.handleUltraSIEMEvent)
 	if err != nil {
-		return fmt.Errorf("failed to subscribe to ultra_siem.events: %w", err)
+		return fmt.Errorf("failed to subscribe to %s: %w", subjectEvents, err)
 	}
 	defer eventsSub.Unsubscribe()
 
+	// Subscribe to rust-core system events
+	systemSub, err := b.js.Subscribe(subjectSystem, b.handleSystemEvent)
+	if err != nil {
+		return fmt.Errorf("failed to subscribe to %s: %w", subjectSystem, err)
+	}
+	defer systemSub.Unsubscribe()
+
 	// Start statistics reporting
 	go b.reportStats()
 
 	log.Println("✅ Ultra SIEM Enhanced Bridge started successfully")
-	log.Println("📡 Listening for events on: ultra_siem.threats, ultra_siem.events")
+	log.Printf("📡 Listening for events on: %s, %s, %s", subjectThreats, subjectEvents, subjectSystem)
 
 	// Keep the service running
 	select {
@@ -564,6 +599,11 @@ func (b *SimpleBridge) Start() error {
 }
 
 func (b *SimpleBridge) handleThreatEvent(msg *nats.Msg) {
+	if !checkContractVersion(msg) {
+		b.updateErrorStats()
+		return
+	}
+
 	var event ThreatEvent
 	if err := json.Unmarshal(msg.Data, &event); err != nil {
 		log.Printf("❌ Error unmarshaling threat event: %v", err)
@@ -587,6 +627,11 @@ func (b *SimpleBridge) handleThreatEvent(msg *nats.Msg) {
 }
 
 func (b *SimpleBridge) handleSystemEvent(msg *nats.Msg) {
+	if !checkContractVersion(msg) {
+		b.updateErrorStats()
+		return
+	}
+
 	var event SystemEvent
 	if err := json.Unmarshal(msg.Data, &event); err != nil {
 		log.Printf("❌ Error unmarshaling system event: %v", err)
@@ -609,6 +654,11 @@ func (b *SimpleBridge) handleSystemEvent(msg *nats.Msg) {
 }
 
 func (b *SimpleBridge) handleUltraSIEMEvent(msg *nats.Msg) {
+	if !checkContractVersion(msg) {
+		b.updateErrorStats()
+		return
+	}
+
 	var event UltraSIEMEvent
 	if err := json.Unmarshal(msg.Data, &event); err != nil {
 		log.Printf("❌ Error unmarshaling UltraSIEM event: %v", err)
@@ -0,0 +1,14 @@
+fn main() {
+    // gRPC stubs are only generated for builds with the `api` feature
+    #[cfg(feature = "api")]
+    {
+        if std::env::var_os("PROTOC").is_none() {
+            let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc unavailable");
+            std::env::set_var("PROTOC", protoc);
+        }
+        println!("cargo:rerun-if-changed=proto/ultra_siem.proto");
+        tonic_build::configure()
+            .compile(&["proto/ultra_siem.proto"], &["proto"])
+            .expect("failed to compile proto/ultra_siem.proto");
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+#[cfg(feature = "response")]
+use crate::incident_response::Incident;
+
+/// Rows a role may read; an empty list leaves that dimension unrestricted.
+///
+/// Query tables carry the dimensions as `tenant`, `tags` (array) and `classification`
+/// columns; incidents as the `tenant` and `classification` threat details and their tags.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DataScope {
+    pub tenants: Vec<String>,
+    /// Any one shared tag grants access
+    pub tags: Vec<String>,
+    pub classifications: Vec<String>,
+}
+
+impl DataScope {
+    pub fn is_unrestricted(&self) -> bool {
+        self.tenants.is_empty() && self.tags.is_empty() && self.classifications.is_empty()
+    }
+
+    /// Union of several roles; a role unrestricted in a dimension lifts it for all
+    pub fn union<'a>(scopes: impl IntoIterator<Item = &'a DataScope>) -> DataScope {
+        let mut merged: [Option<BTreeSet<String>>; 3] = [Some(BTreeSet::new()), Some(BTreeSet::new()), Some(BTreeSet::new())];
+        for scope in scopes {
+            for (slot, values) in merged.iter_mut().zip([&scope.tenants, &scope.tags, &scope.classifications]) {
+                match slot {
+                    Some(_) if values.is_empty() => *slot = None,
+                    Some(set) => set.extend(values.iter().cloned()),
+                    None => {}
+                }
+            }
+        }
+        let [tenants, tags, classifications] = merged.map(|set| set.map(|s| s.into_iter().collect()).unwrap_or_default());
+        DataScope { tenants, tags, classifications }
+    }
+
+    pub fn permits(&self, tenant: Option<&str>, mut tags: impl Iterator<Item = impl AsRef<str>>, classification: Option<&str>) -> bool {
+        let allowed = |list: &[String], value: Option<&str>| list.is_empty() || value.is_some_and(|v| list.iter().any(|l| l == v));
+        allowed(&self.tenants, tenant)
+            && allowed(&self.classifications, classification)
+            && (self.tags.is_empty() || tags.any(|t| self.tags.iter().any(|l| l == t.as_ref())))
+    }
+
+    #[cfg(feature = "response")]
+    pub fn permits_incident(&self, incident: &Incident) -> bool {
+        let details = &incident.threat_result.details;
+        self.permits(
+            details.get("tenant").map(String::as_str),
+            incident.tags.iter(),
+            details.get("classification").map(String::as_str),
+        )
+    }
+
+    /// Same test as the injected query predicates, for rows already in memory
+    pub fn permits_row(&self, row: &serde_json::Map<String, serde_json::Value>) -> bool {
+        let text = |column: &str| row.get(column).and_then(|v| v.as_str());
+        let tags = row.get("tags").and_then(|v| v.as_array()).into_iter().flatten().filter_map(|v| v.as_str());
+        self.permits(text("tenant"), tags, text("classification"))
+    }
+}
+
+/// API caller bound to a token and the roles that scope its reads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Principal {
+    pub name: String,
+    pub token: String,
+    pub roles: Vec<String>,
+}
+
+/// Row-level scoping of the query and incident APIs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DataAccessSettings {
+    pub enabled: bool,
+    pub roles: BTreeMap<String, DataScope>,
+    /// Scoped callers; `rest.auth_tokens` stay unrestricted service tokens
+    pub principals: Vec<Principal>,
+    /// Denied attempts kept for `GET /api/v1/access/denials`
+    pub max_denials: usize,
+}
+
+impl Default for DataAccessSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            roles: BTreeMap::new(),
+            principals: Vec::new(),
+            max_denials: 1000,
+        }
+    }
+}
+
+impl DataAccessSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let mut tokens = BTreeSet::new();
+        for principal in &self.principals {
+            if principal.name.trim().is_empty() || principal.token.len() < 16 {
+                return Err(SIEMError::Validation(format!(
+                    "data_access principal '{}' needs a name and a token of at least 16 characters",
+                    principal.name
+                )));
+            }
+            if !tokens.insert(principal.token.as_str()) {
+                return Err(SIEMError::Validation(format!("data_access principal '{}' reuses another token", principal.name)));
+            }
+            if principal.roles.is_empty() {
+                return Err(SIEMError::Validation(format!("data_access principal '{}' has no roles", principal.name)));
+            }
+            if let Some(role) = principal.roles.iter().find(|r| !self.roles.contains_key(*r)) {
+                return Err(SIEMError::Validation(format!("data_access principal '{}' has unknown role '{}'", principal.name, role)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Who is calling and what they may read; `scope` is `None` for unrestricted callers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caller {
+    pub name: String,
+    pub scope: Option<DataScope>,
+}
+
+impl Caller {
+    pub fn unrestricted() -> Self {
+        Self { name: "service".to_string(), scope: None }
+    }
+
+    #[cfg(feature = "response")]
+    pub fn permits_incident(&self, incident: &Incident) -> bool {
+        self.scope.as_ref().is_none_or(|s| s.permits_incident(incident))
+    }
+}
+
+/// One refused request, kept for review
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessDenial {
+    pub at: DateTime<Utc>,
+    pub principal: String,
+    pub action: String,
+    pub resource: String,
+}
+
+/// Resolves bearer tokens to scoped callers and audits denied access
+pub struct DataAccess {
+    callers: HashMap<String, Caller>,
+    max_denials: usize,
+    denials: Mutex<VecDeque<AccessDenial>>,
+    denied_total: AtomicU64,
+}
+
+impl DataAccess {
+    pub fn new(settings: &DataAccessSettings) -> SIEMResult<Self> {
+        settings.validate()?;
+        let callers = if settings.enabled {
+            settings
+                .principals
+                .iter()
+                .map(|p| {
+                    let scope = DataScope::union(p.roles.iter().filter_map(|r| settings.roles.get(r)));
+                    let scope = (!scope.is_unrestricted()).then_some(scope);
+                    (p.token.clone(), Caller { name: p.name.clone(), scope })
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            callers,
+            max_denials: settings.max_denials,
+            denials: Mutex::new(VecDeque::new()),
+            denied_total: AtomicU64::new(0),
+        })
+    }
+
+    /// Principal tokens the REST layer must accept besides its service tokens
+    pub fn tokens(&self) -> Vec<String> {
+        self.callers.keys().cloned().collect()
+    }
+
+    pub fn caller(&self, token: Option<&str>) -> Caller {
+        token.and_then(|t| self.callers.get(t)).cloned().unwrap_or_else(Caller::unrestricted)
+    }
+
+    pub fn deny(&self, caller: &Caller, action: &str, resource: &str) {
+        warn!("🚫 Denied {} {} to {}", action, resource, caller.name);
+        self.denied_total.fetch_add(1, Ordering::Relaxed);
+        let mut denials = self.denials.lock().unwrap();
+        denials.push_back(AccessDenial {
+            at: Utc::now(),
+            principal: caller.name.clone(),
+            action: action.to_string(),
+            resource: resource.to_string(),
+        });
+        while denials.len() > self.max_denials {
+            denials.pop_front();
+        }
+    }
+
+    /// Denied attempts, newest last
+    pub fn denials(&self) -> Vec<AccessDenial> {
+        self.denials.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        HashMap::from([
+            ("access_scoped_principals".to_string(), self.callers.values().filter(|c| c.scope.is_some()).count() as f64),
+            ("access_denied_total".to_string(), self.denied_total.load(Ordering::Relaxed) as f64),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(tenants: &[&str], tags: &[&str]) -> DataScope {
+        DataScope {
+            tenants: tenants.iter().map(|s| s.to_string()).collect(),
+            tags: tags.iter().map(|s| s.to_string()).collect(),
+            classifications: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_union_lifts_dimensions_left_open_by_any_role() {
+        let merged = DataScope::union([&scope(&["acme"], &["pci"]), &scope(&["globex"], &[])]);
+        assert_eq!(merged.tenants, vec!["acme", "globex"]);
+        assert!(merged.tags.is_empty());
+        assert!(merged.permits(Some("globex"), std::iter::empty::<&str>(), None));
+        assert!(!merged.permits(Some("initech"), ["pci"].iter(), None));
+        assert!(!merged.permits(None, ["pci"].iter(), None));
+    }
+
+    #[test]
+    fn test_rows_need_the_tenant_and_a_shared_tag() {
+        let acme_pci = scope(&["acme"], &["pci"]);
+        let row = serde_json::json!({ "tenant": "acme", "tags": ["hr", "pci"] });
+        assert!(acme_pci.permits_row(row.as_object().unwrap()));
+        let row = serde_json::json!({ "tenant": "acme", "tags": ["hr"] });
+        assert!(!acme_pci.permits_row(row.as_object().unwrap()));
+
+    }
+
+    #[test]
+    fn test_principals_resolve_to_scoped_callers_and_denials_are_kept() {
+        let settings = DataAccessSettings {
+            enabled: true,
+            roles: BTreeMap::from([("acme_analyst".to_string(), scope(&["acme"], &[])), ("admin".to_string(), DataScope::default())]),
+            principals: vec![
+                Principal { name: "alice".to_string(), token: "alice-token-0123456789".to_string(), roles: vec!["acme_analyst".to_string()] },
+                Principal { name: "root".to_string(), token: "root-token-0123456789".to_string(), roles: vec!["admin".to_string()] },
+            ],
+            max_denials: 1,
+        };
+        let access = DataAccess::new(&settings).unwrap();
+        let alice = access.caller(Some("alice-token-0123456789"));
+        assert_eq!(alice.scope.as_ref().unwrap().tenants, vec!["acme"]);
+        assert_eq!(access.caller(Some("root-token-0123456789")).scope, None);
+        assert_eq!(access.caller(Some("service-token")), Caller::unrestricted());
+
+        access.deny(&alice, "read", "incident a");
+        access.deny(&alice, "read", "incident b");
+        assert_eq!(access.denials().len(), 1);
+        assert_eq!(access.get_metrics()["access_denied_total"], 2.0);
+
+        let mut bad = settings;
+        bad.principals[0].roles = vec!["missing".to_string()];
+        assert!(bad.validate().is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, RwLock, Mutex};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
 use serde::{Deserialize, Serialize};
 use log::{info, warn, error, debug};
@@ -8,11 +9,48 @@ use uuid::Uuid;
 use regex::Regex;
 use rayon::prelude::*;
 use dashmap::DashMap;
+use sha2::{Digest, Sha256};
 
+use crate::aggregation::TopNAggregator;
+use crate::anomaly_tuning::AnomalyTuner;
+use crate::attack::{self, DetectionEntry};
+use crate::brute_force::{BruteForceDetector, BruteForceSettings};
+use crate::cardinality::{CardinalitySettings, CardinalityTracker, FanoutAlert};
+use crate::cert_monitor::{CertMonitor, CertMonitorSettings};
+use crate::config_change::{ConfigChangeDetector, ConfigChangeSettings};
+#[cfg(feature = "chaos")]
+use crate::chaos::FaultInjector;
+use crate::clock_skew::{ClockSkewSettings, TimestampNormalizer};
+use crate::detection_profile::{ProfileRegistry, PROFILE_DETAIL};
+use crate::domain_baseline::DomainBaseline;
+use crate::dedup::{DedupMode, DedupSettings, DedupVerdict, EventDeduplicator};
+use crate::event_time::{Admission, EventTimeClock, EventTimeSettings, LateEvent};
+use crate::latency_budget::{EventBudget, LatencyBudget, LatencyBudgetSettings, SKIPPED_STAGES_DETAIL};
+use crate::memory_budget::{self, MemoryAccountant, StructureLimit};
 use crate::error_handling::SIEMResult;
-use crate::ml_engine::MLAnomalyEngine;
-use crate::quantum_detector::QuantumDetector;
+use crate::intel_fusion::IntelFusion;
+use crate::honeyport::AttackerStore;
+use crate::canary_asset::{self, CanaryRegistry};
+use crate::ip_net::{self, IpNetwork};
+use crate::parsing::ParsingPipelines;
+use crate::ml_engine::{AnomalyBaselines, MLAnomalyEngine};
+use crate::quantum_detector::{PatternOrigin, QuantumDetector, QuantumPattern};
+use crate::replay::{self, ContentVersions, PipelineTrace, ReplayRecorder, ReplaySettings, REPLAY_ID_DETAIL};
+use crate::secret_scan::{LeakingSystem, SecretScanSettings, SecretScanner};
+use crate::tagging::{TaggingEngine, TaggingSettings};
+use crate::text_normalization::{TextNormalizationSettings, TextNormalizer};
+use crate::decoder_chain::{DecodedVariant, DecoderChain, DecoderSettings};
+use crate::autoscaling::QueueSample;
+use crate::snapshot::EngineState;
+use crate::suppression::SuppressionList;
+#[cfg(feature = "response")]
+use crate::telemetry::LatencyHistogram;
+use crate::tls_fingerprint::{self, TlsFingerprintSettings, TlsFingerprinter};
 use crate::threat_detection::{ThreatEvent, ThreatSeverity, ThreatCategory, IOC, SignaturePattern};
+use crate::web_access::{WebAccessDetector, WebAccessSettings};
+
+/// Event fields copied into correlation event metadata for `fields` conditions
+const CORRELATION_FIELDS: [&str; 6] = ["url", "http_method", "http_status", "user_agent", "user_id", "host"];
 
 /// Signature match result
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -133,6 +171,16 @@ impl Default for AdvancedThreatResult {
     }
 }
 
+impl AdvancedThreatResult {
+    pub fn source_addr(&self) -> Option<std::net::IpAddr> {
+        ip_net::parse_ip(&self.source_ip)
+    }
+
+    pub fn destination_addr(&self) -> Option<std::net::IpAddr> {
+        ip_net::parse_ip(&self.destination_ip)
+    }
+}
+
 /// YARA-like signature engine
 #[derive(Debug)]
 pub struct YaraSignatureEngine {
@@ -158,6 +206,11 @@ impl YaraSignatureEngine {
         Ok(())
     }
 
+    pub fn remove_signature(&self, signature_id: &str) -> bool {
+        self.match_cache.clear();
+        self.compiled_signatures.remove(signature_id).is_some()
+    }
+
     pub fn match_signatures(&self, event: &str) -> Vec<SignatureMatch> {
         let mut matches = Vec::new();
         for refmulti in self.compiled_signatures.iter() {
@@ -182,11 +235,27 @@ impl YaraSignatureEngine {
         matches
     }
 
-    pub fn get_match_statistics(&self) -> HashMap<String, u64> {
-        self.match_cache.iter().map(|entry| (entry.key().clone(), *entry.value())).collect()
+    /// Bound the match counters, dropping the least matched signatures first
+    fn enforce_memory_limits(&self, memory: &MemoryAccountant) {
+        account_counters(memory, "signature.match_cache", &self.match_cache);
     }
 }
 
+/// Apply the limit of `structure` to a map whose entries know when they were last used, then report its size
+fn account_map<V: Serialize + Clone>(memory: &MemoryAccountant, structure: &str, map: &DashMap<String, V>, last_used: impl Fn(&V) -> u64) {
+    let eviction = memory_budget::evict_map(map, memory.limit(structure), last_used);
+    let sample: Vec<V> = map.iter().take(32).map(|e| e.value().clone()).collect();
+    memory.record(structure, map.len(), memory_budget::estimate_bytes(map.len(), sample.iter()), eviction);
+}
+
+/// Bound a map of hit counters; counts are no timestamps, so only the entry limit applies
+fn account_counters(memory: &MemoryAccountant, structure: &str, counters: &DashMap<String, u64>) {
+    let limit = StructureLimit { ttl_seconds: 0, ..memory.limit(structure) };
+    let eviction = memory_budget::evict_map(counters, limit, |count| *count);
+    let bytes = counters.iter().map(|e| e.key().len() + std::mem::size_of::<(String, u64)>()).sum::<usize>();
+    memory.record(structure, counters.len(), bytes as u64, eviction);
+}
+
 /// Behavioral analysis engine
 #[derive(Debug)]
 pub struct BehavioralAnalysisEngine {
@@ -197,7 +266,7 @@ pub struct BehavioralAnalysisEngine {
     risk_thresholds: Arc<RwLock<HashMap<String, f32>>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct UserProfile {
     user_id: String,
     login_patterns: VecDeque<u64>,
@@ -208,7 +277,7 @@ struct UserProfile {
     user_agents: HashSet<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct IPProfile {
     ip_address: String,
     connection_count: u32,
@@ -218,7 +287,7 @@ struct IPProfile {
     risk_score: f32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct SessionContext {
     session_id: String,
     user_id: String,
@@ -228,6 +297,21 @@ struct SessionContext {
     risk_score: f32,
 }
 
+/// Learned user baselines and open sessions of the behavioral engine
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BehavioralState {
+    user_profiles: HashMap<String, UserProfile>,
+    sessions: HashMap<String, SessionContext>,
+    baselines: AnomalyBaselines,
+}
+
+/// Per-IP risk profiles and false positive history
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntityRiskState {
+    ip_profiles: HashMap<String, IPProfile>,
+    false_positive_history: HashMap<String, u64>,
+}
+
 impl BehavioralAnalysisEngine {
     pub fn new() -> Self {
         Self {
@@ -239,6 +323,35 @@ impl BehavioralAnalysisEngine {
         }
     }
 
+    pub fn export_state(&self) -> BehavioralState {
+        BehavioralState {
+            user_profiles: self.user_profiles.iter().map(|e| (e.key().clone(), e.value().clone())).collect(),
+            sessions: self.session_tracker.iter().map(|e| (e.key().clone(), e.value().clone())).collect(),
+            baselines: self.anomaly_engine.export_baselines(),
+        }
+    }
+
+    pub fn restore_state(&self, state: BehavioralState) {
+        replace_entries(&self.user_profiles, state.user_profiles);
+        replace_entries(&self.session_tracker, state.sessions);
+        self.anomaly_engine.restore_baselines(state.baselines);
+    }
+
+    fn export_ip_profiles(&self) -> HashMap<String, IPProfile> {
+        self.ip_profiles.iter().map(|e| (e.key().clone(), e.value().clone())).collect()
+    }
+
+    fn restore_ip_profiles(&self, profiles: HashMap<String, IPProfile>) {
+        replace_entries(&self.ip_profiles, profiles);
+    }
+
+    /// Evict idle and least recently active users, addresses and sessions
+    fn enforce_memory_limits(&self, memory: &MemoryAccountant) {
+        account_map(memory, "behavioral.user_profiles", &self.user_profiles, |p| p.last_activity);
+        account_map(memory, "behavioral.ip_profiles", &self.ip_profiles, |p| p.last_seen);
+        account_map(memory, "behavioral.sessions", &self.session_tracker, |s| s.last_activity);
+    }
+
     pub fn analyze_behavior(&self, event: &serde_json::Value) -> Option<BehavioralContext> {
         let user_id = event.get("user_id")?.as_str()?;
         let source_ip = event.get("source_ip")?.as_str()?;
@@ -353,7 +466,7 @@ impl BehavioralAnalysisEngine {
         
         // Session duration analysis
         if let Some(session) = self.session_tracker.get(user_id) {
-            let session_duration = timestamp - session.start_time;
+            let session_duration = timestamp.saturating_sub(session.start_time);
             if session_duration > 3600 * 24 { // More than 24 hours
                 risk += 0.2;
             }
@@ -368,6 +481,56 @@ impl BehavioralAnalysisEngine {
     }
 }
 
+/// Append a stage to the trace when one is being collected
+fn note(trace: &mut Option<&mut PipelineTrace>, stage: &str, detections: usize, detail: impl FnOnce() -> String) {
+    if let Some(trace) = trace.as_deref_mut() {
+        trace.stage(stage, detections, detail());
+    }
+}
+
+/// Start timing `stage`; false when the latency budget sheds it for this event
+fn admit(budget: &mut Option<EventBudget<'_>>, stage: &'static str) -> bool {
+    budget.as_mut().is_none_or(|budget| budget.admit(stage))
+}
+
+/// Swap the contents of a shared map for restored entries
+fn replace_entries<V>(map: &DashMap<String, V>, entries: HashMap<String, V>) {
+    map.clear();
+    for (key, value) in entries {
+        map.insert(key, value);
+    }
+}
+
+/// Correlation source/target match: CIDR containment for address patterns, substring otherwise
+fn pattern_matches(pattern: &str, value: &str) -> bool {
+    match (pattern.parse::<IpNetwork>(), ip_net::parse_ip(value)) {
+        (Ok(network), Some(ip)) => network.contains(ip),
+        _ => value.contains(pattern),
+    }
+}
+
+fn correlation_threat(rule: &CorrelationRule, event: &CorrelationEvent, events: Vec<CorrelationEvent>) -> AdvancedThreatResult {
+    AdvancedThreatResult {
+        threat_id: Uuid::new_v4().to_string(),
+        timestamp: event.timestamp,
+        severity: rule.severity.clone(),
+        category: ThreatCategory::APT, // Multi-step attacks are typically APT
+        confidence: 0.9,
+        detection_method: "correlation".to_string(),
+        source_ip: event.source.clone(),
+        destination_ip: event.target.clone(),
+        user_id: "".to_string(),
+        description: format!("Multi-step attack detected: {}", rule.name),
+        iocs: Vec::new(),
+        signatures: Vec::new(),
+        behavioral_context: None,
+        correlation_events: events,
+        false_positive_probability: 0.1,
+        gpu_processing_time_ms: 0.0,
+        details: attack::technique_details(&rule.attack_techniques),
+    }
+}
+
 /// Correlation engine for multi-step attack detection
 #[derive(Debug)]
 pub struct CorrelationEngine {
@@ -375,29 +538,40 @@ pub struct CorrelationEngine {
     correlation_rules: Arc<DashMap<String, CorrelationRule>>,
     active_correlations: Arc<DashMap<String, ActiveCorrelation>>,
     quantum_detector: Arc<QuantumDetector>,
+    /// Events kept in the sliding window
+    window_size: AtomicUsize,
 }
 
-#[derive(Debug, Clone)]
-struct CorrelationRule {
-    id: String,
+/// Sliding window size unless the memory budget sets one
+const DEFAULT_CORRELATION_WINDOW: usize = 10_000;
+
+/// Multi-step correlation rule; shipped built in or loaded from content packs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrelationRule {
+    pub(crate) id: String,
     name: String,
     description: String,
     conditions: Vec<CorrelationCondition>,
     time_window: u64,
     severity: ThreatSeverity,
     enabled: bool,
+    #[serde(default)]
+    attack_techniques: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct CorrelationCondition {
     event_type: String,
     source_pattern: Option<String>,
     target_pattern: Option<String>,
     min_count: u32,
     max_count: Option<u32>,
+    /// Event field -> text the field must contain, e.g. `http_status = "404"`
+    #[serde(default)]
+    fields: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct ActiveCorrelation {
     rule_id: String,
     start_time: u64,
@@ -405,13 +579,20 @@ struct ActiveCorrelation {
     status: CorrelationStatus,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 enum CorrelationStatus {
     Active,
     Triggered,
     Expired,
 }
 
+/// Sliding event window and in-flight multi-step correlations
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CorrelationState {
+    events: Vec<CorrelationEvent>,
+    active_correlations: HashMap<String, ActiveCorrelation>,
+}
+
 impl CorrelationEngine {
     pub fn new() -> Self {
         Self {
@@ -419,6 +600,7 @@ impl CorrelationEngine {
             correlation_rules: Arc::new(DashMap::new()),
             active_correlations: Arc::new(DashMap::new()),
             quantum_detector: Arc::new(QuantumDetector::new()),
+            window_size: AtomicUsize::new(DEFAULT_CORRELATION_WINDOW),
         }
     }
 
@@ -428,6 +610,39 @@ impl CorrelationEngine {
         info!("✅ Added correlation rule: {}", rule_clone.name);
     }
 
+    pub fn remove_correlation_rule(&self, rule_id: &str) -> bool {
+        self.active_correlations.retain(|_, correlation| correlation.rule_id != rule_id);
+        self.correlation_rules.remove(rule_id).is_some()
+    }
+
+    pub fn export_state(&self) -> CorrelationState {
+        CorrelationState {
+            events: self.events.lock().unwrap().iter().cloned().collect(),
+            active_correlations: self.active_correlations.iter().map(|e| (e.key().clone(), e.value().clone())).collect(),
+        }
+    }
+
+    pub fn restore_state(&self, state: CorrelationState) {
+        *self.events.lock().unwrap() = state.events.into();
+        replace_entries(&self.active_correlations, state.active_correlations);
+    }
+
+    /// Resize the event window and evict the oldest in-flight correlations past the limits
+    fn enforce_memory_limits(&self, memory: &MemoryAccountant) {
+        let limit = memory.limit("correlation.events");
+        self.window_size.store(
+            if limit.max_entries > 0 { limit.max_entries } else { DEFAULT_CORRELATION_WINDOW },
+            Ordering::Relaxed,
+        );
+        {
+            let mut events = self.events.lock().unwrap();
+            let eviction = memory_budget::trim_window(&mut events, limit);
+            let bytes = memory_budget::estimate_bytes(events.len(), events.iter());
+            memory.record("correlation.events", events.len(), bytes, eviction);
+        }
+        account_map(memory, "correlation.active", &self.active_correlations, |c| c.start_time);
+    }
+
     pub fn process_event(&self, event: CorrelationEvent) -> Vec<AdvancedThreatResult> {
         let mut threats = Vec::new();
         
@@ -437,7 +652,7 @@ impl CorrelationEngine {
             events.push_back(event.clone());
             
             // Maintain window size
-            let window_size = 10000; // Keep last 10k events
+            let window_size = self.window_size.load(Ordering::Relaxed);
             while events.len() > window_size {
                 events.pop_front();
             }
@@ -465,31 +680,9 @@ impl CorrelationEngine {
             active_correlation.events.push(event.clone());
             
             // Check if correlation is triggered
-            if self.check_correlation_triggered(&rule, &active_correlation.events) {
+            if self.check_correlation_triggered(rule, &active_correlation.events) {
                 active_correlation.status = CorrelationStatus::Triggered;
-                
-                // Create threat result
-                let threat = AdvancedThreatResult {
-                    threat_id: Uuid::new_v4().to_string(),
-                    timestamp: event.timestamp,
-                    severity: rule.severity.clone(),
-                    category: ThreatCategory::APT, // Multi-step attacks are typically APT
-                    confidence: 0.9,
-                    detection_method: "correlation".to_string(),
-                    source_ip: event.source.clone(),
-                    destination_ip: event.target.clone(),
-                    user_id: "".to_string(),
-                    description: format!("Multi-step attack detected: {}", rule.name),
-                    iocs: Vec::new(),
-                    signatures: Vec::new(),
-                    behavioral_context: None,
-                    correlation_events: active_correlation.events.clone(),
-                    false_positive_probability: 0.1,
-                    gpu_processing_time_ms: 0.0,
-                    details: HashMap::new(),
-                };
-                
-                threats.push(threat);
+                threats.push(correlation_threat(rule, &event, active_correlation.events.clone()));
             }
         }
         
@@ -499,8 +692,62 @@ impl CorrelationEngine {
         threats
     }
 
+    /// Correlate by event timestamp: each rule sees tumbling windows of its `time_window`
+    /// cut by event time, fires at most once per window, and windows close once `clock`'s
+    /// watermark passes them. Late events follow the clock's policy.
+    pub fn process_event_time(&self, event: CorrelationEvent, clock: &EventTimeClock) -> Vec<AdvancedThreatResult> {
+        if clock.admit(&event) == Admission::Rejected {
+            return Vec::new();
+        }
+        {
+            let mut events = self.events.lock().unwrap();
+            let position = events.iter().rposition(|e| e.timestamp <= event.timestamp).map_or(0, |i| i + 1);
+            events.insert(position, event.clone());
+            let window_size = self.window_size.load(Ordering::Relaxed);
+            while events.len() > window_size {
+                events.pop_front();
+            }
+        }
+        
+        let mut threats = Vec::new();
+        for rule_entry in self.correlation_rules.iter() {
+            let rule = rule_entry.value();
+            if !rule.enabled {
+                continue;
+            }
+            let width = rule.time_window.max(1);
+            let window_start = event.timestamp - event.timestamp % width;
+            let window_events: Vec<CorrelationEvent> = self.events.lock().unwrap()
+                .iter()
+                .filter(|e| e.timestamp >= window_start && e.timestamp < window_start + width)
+                .cloned()
+                .collect();
+            
+            let mut window = self.active_correlations.entry(format!("{}@{}", rule.id, window_start))
+                .or_insert_with(|| ActiveCorrelation {
+                    rule_id: rule.id.clone(),
+                    start_time: window_start,
+                    events: Vec::new(),
+                    status: CorrelationStatus::Active,
+                });
+            window.events = window_events;
+            if !matches!(window.status, CorrelationStatus::Triggered) && self.check_correlation_triggered(rule, &window.events) {
+                window.status = CorrelationStatus::Triggered;
+                threats.push(correlation_threat(rule, &event, window.events.clone()));
+            }
+        }
+        
+        // Drop windows the watermark has passed
+        let watermark = clock.watermark();
+        self.active_correlations.retain(|_, window| {
+            let width = self.correlation_rules.get(&window.rule_id).map_or(1, |rule| rule.time_window.max(1));
+            window.start_time + width > watermark
+        });
+        threats
+    }
+
     fn check_correlation_triggered(&self, rule: &CorrelationRule, events: &[CorrelationEvent]) -> bool {
-        let window_start = events.last().unwrap().timestamp - rule.time_window;
+        let window_start = events.last().unwrap().timestamp.saturating_sub(rule.time_window);
         let window_events: Vec<&CorrelationEvent> = events.iter()
             .filter(|e| e.timestamp >= window_start)
             .collect();
@@ -509,8 +756,9 @@ impl CorrelationEngine {
             let matching_events: Vec<&CorrelationEvent> = window_events.iter()
                 .filter(|e| {
                     e.event_type == condition.event_type &&
-                    condition.source_pattern.as_ref().map_or(true, |p| e.source.contains(p)) &&
-                    condition.target_pattern.as_ref().map_or(true, |p| e.target.contains(p))
+                    condition.source_pattern.as_ref().is_none_or(|p| pattern_matches(p, &e.source)) &&
+                    condition.target_pattern.as_ref().is_none_or(|p| pattern_matches(p, &e.target)) &&
+                    condition.fields.iter().all(|(field, p)| e.metadata.get(field).is_some_and(|v| v.contains(p.as_str())))
                 })
                 .cloned()
                 .collect();
@@ -533,7 +781,7 @@ impl CorrelationEngine {
         let expired_keys: Vec<String> = self.active_correlations.iter()
             .filter(|entry| {
                 let correlation = entry.value();
-                current_time - correlation.start_time > 3600 // 1 hour
+                current_time.saturating_sub(correlation.start_time) > 3600 // 1 hour
             })
             .map(|entry| entry.key().clone())
             .collect();
@@ -557,6 +805,38 @@ pub struct AdvancedThreatDetectionEngine {
     performance_metrics: Arc<DashMap<String, f64>>,
     threat_tx: mpsc::Sender<AdvancedThreatResult>,
     threat_rx: mpsc::Receiver<AdvancedThreatResult>,
+    dedup: Option<EventDeduplicator>,
+    clock: Option<TimestampNormalizer>,
+    event_time: Option<EventTimeClock>,
+    pipelines: ParsingPipelines,
+    recorder: Option<ReplayRecorder>,
+    aggregator: Option<Arc<TopNAggregator>>,
+    cardinality: Option<CardinalityTracker>,
+    brute_force: Option<BruteForceDetector>,
+    config_changes: Option<ConfigChangeDetector>,
+    web_access: Option<WebAccessDetector>,
+    cert_monitor: Option<CertMonitor>,
+    tls_fingerprints: Option<TlsFingerprinter>,
+    secret_scanner: Option<SecretScanner>,
+    tagging: Option<TaggingEngine>,
+    text_normalizer: Option<TextNormalizer>,
+    decoder: Option<DecoderChain>,
+    /// Events inside `process_event` and events it has finished, for queue lag
+    in_flight: AtomicU64,
+    processed_events: AtomicU64,
+    suppressions: Option<Arc<SuppressionList>>,
+    intel: Option<Arc<IntelFusion>>,
+    domains: Option<Arc<DomainBaseline>>,
+    attackers: Option<Arc<AttackerStore>>,
+    canaries: Option<Arc<CanaryRegistry>>,
+    anomaly_tuner: Option<Arc<AnomalyTuner>>,
+    latency_budget: Option<LatencyBudget>,
+    profiles: Option<Arc<ProfileRegistry>>,
+    memory: Option<Arc<MemoryAccountant>>,
+    #[cfg(feature = "chaos")]
+    chaos: Option<FaultInjector>,
+    #[cfg(feature = "response")]
+    latency: Option<Arc<LatencyHistogram>>,
 }
 
 impl AdvancedThreatDetectionEngine {
@@ -574,9 +854,340 @@ impl AdvancedThreatDetectionEngine {
             performance_metrics: Arc::new(DashMap::new()),
             threat_tx,
             threat_rx,
+            dedup: None,
+            clock: None,
+            event_time: None,
+            pipelines: ParsingPipelines::default(),
+            recorder: None,
+            aggregator: None,
+            cardinality: None,
+            brute_force: None,
+            config_changes: None,
+            web_access: None,
+            cert_monitor: None,
+            tls_fingerprints: None,
+            secret_scanner: None,
+            tagging: None,
+            text_normalizer: None,
+            decoder: None,
+            in_flight: AtomicU64::new(0),
+            processed_events: AtomicU64::new(0),
+            suppressions: None,
+            intel: None,
+            domains: None,
+            attackers: None,
+            canaries: None,
+            anomaly_tuner: None,
+            latency_budget: None,
+            profiles: None,
+            memory: None,
+            #[cfg(feature = "chaos")]
+            chaos: None,
+            #[cfg(feature = "response")]
+            latency: None,
+        }
+    }
+
+    /// De-duplicate events before detection (no-op unless `settings.enabled`)
+    pub fn enable_dedup(&mut self, settings: DedupSettings) {
+        self.dedup = settings.enabled.then(|| EventDeduplicator::new(settings));
+    }
+
+    /// Correct per-source clock skew before detection (no-op unless `settings.enabled`)
+    pub fn enable_timestamp_normalization(&mut self, settings: ClockSkewSettings) {
+        self.clock = settings.enabled.then(|| TimestampNormalizer::new(settings));
+    }
+
+    /// Correlate in event-time windows behind a watermark (no-op unless `settings.enabled`)
+    pub fn enable_event_time(&mut self, settings: EventTimeSettings) {
+        self.event_time = settings.enabled.then(|| EventTimeClock::new(settings));
+    }
+
+    /// Late events set aside by event-time correlation, newest first
+    pub fn late_events(&self, limit: usize) -> Vec<LateEvent> {
+        self.event_time.as_ref().map(|clock| clock.late_events(limit)).unwrap_or_default()
+    }
+
+    /// Track unique sources, users and per-host fan-out with sketches (no-op unless `settings.enabled`)
+    pub fn enable_cardinality(&mut self, settings: CardinalitySettings) {
+        self.cardinality = settings.enabled.then(|| CardinalityTracker::new(settings));
+    }
+
+    /// Detect SSH and RDP brute force per source (no-op unless `settings.enabled`)
+    pub fn enable_brute_force(&mut self, settings: BruteForceSettings) {
+        self.brute_force = settings.enabled.then(|| BruteForceDetector::new(settings));
+    }
+
+    /// Detect bursts of GPO, sudoers and firewall changes and changes outside change windows (no-op unless `settings.enabled`)
+    pub fn enable_config_changes(&mut self, settings: ConfigChangeSettings) {
+        self.config_changes = settings.enabled.then(|| ConfigChangeDetector::new(settings));
+    }
+
+    /// Detect scanners, 404 bursts, credential stuffing and path traversal in access logs (no-op unless `settings.enabled`)
+    pub fn enable_web_access(&mut self, settings: WebAccessSettings) {
+        self.web_access = settings.enabled.then(|| WebAccessDetector::new(settings));
+    }
+
+    /// Check certificates in TLS handshake events for brand look-alikes, unexpected issuers and self-signed internal services
+    pub fn enable_cert_monitor(&mut self, settings: CertMonitorSettings) {
+        self.cert_monitor = settings.enabled.then(|| CertMonitor::new(settings));
+    }
+
+    /// Stamp JA3/JA3S on TLS events and report malicious or rare fingerprints (no-op unless `settings.enabled`)
+    pub fn enable_tls_fingerprints(&mut self, settings: TlsFingerprintSettings) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        self.tls_fingerprints = settings.enabled.then(|| TlsFingerprinter::new(settings, now));
+    }
+
+    /// Report and mask credentials leaked in event payloads (no-op unless `settings.enabled`)
+    pub fn enable_secret_scan(&mut self, settings: SecretScanSettings) -> SIEMResult<()> {
+        self.secret_scanner = if settings.enabled { Some(SecretScanner::new(settings)?) } else { None };
+        Ok(())
+    }
+
+    /// Add config-defined tags and derived fields to normalized events (no-op unless `settings.enabled`)
+    /// Events being detected and events finished, sampled as the `detection` queue
+    pub fn queue_sample(&self) -> QueueSample {
+        QueueSample {
+            depth: self.in_flight.load(Ordering::Relaxed),
+            processed: self.processed_events.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Fold Unicode evasion (look-alikes, invisible and compatibility characters) out of event text
+    pub fn enable_text_normalization(&mut self, settings: TextNormalizationSettings) {
+        self.text_normalizer = settings.enabled.then(|| TextNormalizer::new(settings));
+    }
+
+    /// Match signatures and intel against URL, base64, PowerShell and hex decoded payloads too
+    pub fn enable_decoder(&mut self, settings: DecoderSettings) -> SIEMResult<()> {
+        self.decoder = if settings.enabled { Some(DecoderChain::new(settings)?) } else { None };
+        Ok(())
+    }
+
+    pub fn enable_tagging(&mut self, settings: TaggingSettings) -> SIEMResult<()> {
+        self.tagging = if settings.enabled && !settings.rules.is_empty() { Some(TaggingEngine::new(settings)?) } else { None };
+        Ok(())
+    }
+
+    /// Systems that keep leaking credentials into their events
+    pub fn secret_leakers(&self) -> Vec<LeakingSystem> {
+        self.secret_scanner.as_ref().map(|scanner| scanner.repeat_leakers()).unwrap_or_default()
+    }
+
+    /// Time pipeline stages and shed optional ones over the per-event budget (no-op unless `settings.enabled`)
+    pub fn enable_latency_budget(&mut self, settings: LatencyBudgetSettings) {
+        self.latency_budget = settings.enabled.then(|| LatencyBudget::new(settings));
+    }
+
+    /// Replace the quantum detector, e.g. with one opened on a pattern store; call before `start`
+    pub fn set_quantum_detector(&mut self, detector: Arc<QuantumDetector>) {
+        self.quantum_detector = detector;
+    }
+
+    /// Quantum detector, for managing its patterns
+    pub fn quantum_detector(&self) -> &Arc<QuantumDetector> {
+        &self.quantum_detector
+    }
+
+    /// Bound profile maps, sessions and correlation windows, swept from event processing
+    pub fn set_memory_accountant(&mut self, memory: Arc<MemoryAccountant>) {
+        self.memory = Some(memory);
+    }
+
+    /// Sweep every bounded structure now and report its size to the memory accountant
+    pub fn enforce_memory_limits(&self) {
+        let Some(memory) = &self.memory else {
+            return;
+        };
+        self.signature_engine.enforce_memory_limits(memory);
+        self.behavioral_engine.enforce_memory_limits(memory);
+        self.correlation_engine.enforce_memory_limits(memory);
+        account_counters(memory, "detection.false_positive_history", &self.false_positive_history);
+    }
+
+    /// Tune categories and thresholds per source or asset with detection profiles
+    pub fn set_detection_profiles(&mut self, profiles: Arc<ProfileRegistry>) {
+        self.profiles = Some(profiles);
+    }
+
+    /// Drop detections covered by an active analyst suppression
+    pub fn set_suppressions(&mut self, suppressions: Arc<SuppressionList>) {
+        self.suppressions = Some(suppressions);
+    }
+
+    /// Match events against feed indicators, scored by their fused confidence
+    pub fn set_intel_fusion(&mut self, intel: Arc<IntelFusion>) {
+        self.intel = Some(intel);
+    }
+
+    /// Learn per-group domain allow-lists, then report contacts outside them
+    pub fn set_domain_baseline(&mut self, domains: Arc<DomainBaseline>) {
+        self.domains = Some(domains);
+    }
+
+    /// Carry the honeyport history and risk of remembered actors on their detections
+    pub fn set_attacker_store(&mut self, attackers: Arc<AttackerStore>) {
+        self.attackers = Some(attackers);
+    }
+
+    /// Raise a Critical detection for any use of a canary account or host
+    pub fn set_canary_assets(&mut self, canaries: Arc<CanaryRegistry>) {
+        self.canaries = Some(canaries);
+    }
+
+    /// Score anomalies against per-source thresholds tuned to an alert budget
+    pub fn set_anomaly_tuner(&mut self, tuner: Arc<AnomalyTuner>) {
+        self.anomaly_tuner = Some(tuner);
+    }
+
+    /// Parse raw events with the per-source pipelines before detection
+    pub fn set_parsing_pipelines(&mut self, pipelines: ParsingPipelines) {
+        self.pipelines = pipelines;
+    }
+
+    /// Record the inputs and engine state behind every detection (no-op unless `settings.enabled`)
+    pub fn enable_replay_recording(&mut self, settings: ReplaySettings) {
+        self.recorder = settings.enabled.then(|| ReplayRecorder::new(&settings.record_dir));
+    }
+
+    /// Feed ingested events and detections into the rolling top-N statistics
+    pub fn set_aggregator(&mut self, aggregator: Arc<TopNAggregator>) {
+        self.aggregator = Some(aggregator);
+    }
+
+    /// Record pipeline latencies for telemetry
+    #[cfg(feature = "response")]
+    pub fn set_latency_histogram(&mut self, latency: Arc<LatencyHistogram>) {
+        self.latency = Some(latency);
+    }
+
+    /// Fingerprints of the running config, rules and parsers
+    pub fn content_versions(&self) -> ContentVersions {
+        let digest = |bytes: Vec<u8>| format!("{:x}", Sha256::digest(bytes));
+        let mut signatures: Vec<SignaturePattern> = self.signature_engine.compiled_signatures.iter().map(|e| e.value().clone()).collect();
+        signatures.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut rules: Vec<CorrelationRule> = self.correlation_engine.correlation_rules.iter().map(|e| e.value().clone()).collect();
+        rules.sort_by(|a, b| a.id.cmp(&b.id));
+        ContentVersions {
+            engine: env!("CARGO_PKG_VERSION").to_string(),
+            config: digest(serde_json::to_vec(&self.config).unwrap_or_default()),
+            signatures: digest(serde_json::to_vec(&signatures).unwrap_or_default()),
+            correlation_rules: digest(serde_json::to_vec(&rules).unwrap_or_default()),
+            pipelines: self.pipelines.fingerprint().to_string(),
+        }
+    }
+
+    /// Enabled detections and the ATT&CK techniques each one covers
+    pub fn attack_inventory(&self) -> Vec<DetectionEntry> {
+        let techniques = |tags: &[String], category: &ThreatCategory| {
+            if tags.is_empty() {
+                attack::category_techniques(category).iter().map(|t| t.to_string()).collect()
+            } else {
+                tags.to_vec()
+            }
+        };
+        let mut inventory = Vec::new();
+        if self.config.signature_enabled {
+            for signature in self.signature_engine.compiled_signatures.iter().filter(|s| s.enabled) {
+                inventory.push(DetectionEntry {
+                    name: format!("signature:{}", signature.id),
+                    techniques: techniques(&signature.attack_techniques, &signature.category),
+                });
+            }
+        }
+        if self.config.correlation_enabled {
+            for rule in self.correlation_engine.correlation_rules.iter().filter(|r| r.enabled) {
+                inventory.push(DetectionEntry {
+                    name: format!("correlation:{}", rule.id),
+                    techniques: techniques(&rule.attack_techniques, &ThreatCategory::APT),
+                });
+            }
+        }
+        if self.config.behavioral_enabled {
+            inventory.push(DetectionEntry {
+                name: "behavioral:user_risk".to_string(),
+                techniques: techniques(&[], &ThreatCategory::InsiderThreat),
+            });
+        }
+        if self.cardinality.is_some() {
+            inventory.push(DetectionEntry {
+                name: "cardinality:fanout".to_string(),
+                techniques: vec!["T1021".to_string(), "T1046".to_string()],
+            });
+        }
+        if self.brute_force.is_some() {
+            inventory.push(DetectionEntry {
+                name: "brute_force:ssh_rdp".to_string(),
+                techniques: vec!["T1110.001".to_string(), "T1110.003".to_string()],
+            });
+        }
+        if self.config_changes.is_some() {
+            inventory.push(DetectionEntry {
+                name: "config_change:gpo_sudoers_firewall".to_string(),
+                techniques: ["T1484.001", "T1548.003", "T1562.004"].iter().map(|t| t.to_string()).collect(),
+            });
+        }
+        if self.web_access.is_some() {
+            inventory.push(DetectionEntry {
+                name: "web_access:access_logs".to_string(),
+                techniques: ["T1110.004", "T1190", "T1595.002", "T1595.003"].iter().map(|t| t.to_string()).collect(),
+            });
+        }
+        if self.cert_monitor.is_some() {
+            inventory.push(DetectionEntry {
+                name: "cert_monitor:tls_certificates".to_string(),
+                techniques: ["T1583.001", "T1587.003", "T1588.004"].iter().map(|t| t.to_string()).collect(),
+            });
+        }
+        if self.tls_fingerprints.is_some() {
+            inventory.push(DetectionEntry {
+                name: "tls_fingerprint:ja3_ja3s".to_string(),
+                techniques: vec!["T1071.001".to_string(), "T1573".to_string()],
+            });
+        }
+        if self.secret_scanner.is_some() {
+            inventory.push(DetectionEntry {
+                name: "secret_scan:event_payloads".to_string(),
+                techniques: vec!["T1552".to_string()],
+            });
+        }
+        if self.text_normalizer.as_ref().is_some_and(|n| n.settings().evasion_min_changes > 0 || n.settings().flag_mixed_scripts) {
+            inventory.push(DetectionEntry {
+                name: "text_normalization:unicode_evasion".to_string(),
+                techniques: vec!["T1027".to_string()],
+            });
+        }
+        inventory.sort_by(|a, b| a.name.cmp(&b.name));
+        inventory
+    }
+
+    /// Corrupt a share of ingested events for resilience testing
+    #[cfg(feature = "chaos")]
+    pub fn set_fault_injector(&mut self, injector: FaultInjector) {
+        self.chaos = injector.settings().enabled.then_some(injector);
+    }
+
+    /// Copy the accumulated behavioral, entity risk and correlation state
+    pub fn export_state(&self) -> EngineState {
+        EngineState {
+            behavioral: self.behavioral_engine.export_state(),
+            entity_risk: EntityRiskState {
+                ip_profiles: self.behavioral_engine.export_ip_profiles(),
+                false_positive_history: self.false_positive_history.iter().map(|e| (e.key().clone(), *e.value())).collect(),
+            },
+            correlation: self.correlation_engine.export_state(),
         }
     }
 
+    /// Replace the accumulated state, e.g. from a snapshot taken before a restart
+    pub fn restore_state(&self, state: EngineState) {
+        self.behavioral_engine.restore_state(state.behavioral);
+        self.behavioral_engine.restore_ip_profiles(state.entity_risk.ip_profiles);
+        replace_entries(&self.false_positive_history, state.entity_risk.false_positive_history);
+        self.correlation_engine.restore_state(state.correlation);
+    }
+
     pub async fn start(&mut self) -> SIEMResult<()> {
         info!("🚀 Starting Advanced Threat Detection Engine...");
         
@@ -594,75 +1205,410 @@ impl AdvancedThreatDetectionEngine {
     }
 
     pub async fn process_event(&self, event: serde_json::Value) -> SIEMResult<Vec<AdvancedThreatResult>> {
+        #[cfg(feature = "chaos")]
+        let event = {
+            let mut event = event;
+            if let Some(chaos) = &self.chaos {
+                chaos.corrupt_event(&mut event);
+            }
+            event
+        };
+        
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        let result = match &self.recorder {
+            Some(recorder) => self.process_recorded(recorder, event).await,
+            None => self.run_pipeline(event, None).await,
+        };
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        self.processed_events.fetch_add(1, Ordering::Relaxed);
+        if self.memory.as_ref().is_some_and(|memory| memory.sweep_due()) {
+            self.enforce_memory_limits();
+        }
+        let threats = result?;
+        
+        if let Some(aggregator) = &self.aggregator {
+            threats.iter().for_each(|threat| aggregator.record_detection(threat));
+        }
+        
+        // Publish threats (never stall detection on a full channel)
+        for threat in &threats {
+            let _ = self.threat_tx.try_send(threat.clone());
+        }
+        
+        Ok(threats)
+    }
+
+    /// Run one event through the pipeline without publishing, tracing every stage
+    pub async fn process_event_traced(&self, event: serde_json::Value) -> SIEMResult<(Vec<AdvancedThreatResult>, PipelineTrace)> {
+        let mut trace = PipelineTrace::default();
+        let threats = self.run_pipeline(event, Some(&mut trace)).await?;
+        Ok((threats, trace))
+    }
+
+    async fn process_recorded(&self, recorder: &ReplayRecorder, event: serde_json::Value) -> SIEMResult<Vec<AdvancedThreatResult>> {
+        let state = self.export_state();
+        let mut raw_event = event.clone();
+        if let Some(scanner) = &self.secret_scanner {
+            scanner.mask(&mut raw_event);
+        }
+        let (mut threats, trace) = self.process_event_traced(event).await?;
+        if threats.is_empty() {
+            return Ok(threats);
+        }
+        let record = replay::new_record(raw_event, trace, state, self.content_versions(), threats.clone())?;
+        match recorder.save(&record) {
+            Ok(_) => {
+                for threat in &mut threats {
+                    threat.details.insert(REPLAY_ID_DETAIL.to_string(), record.record_id.clone());
+                }
+            }
+            Err(e) => warn!("⚠️ Failed to write replay record {}: {}", record.record_id, e),
+        }
+        Ok(threats)
+    }
+
+    async fn run_pipeline(&self, mut event: serde_json::Value, mut trace: Option<&mut PipelineTrace>) -> SIEMResult<Vec<AdvancedThreatResult>> {
         let start_time = std::time::Instant::now();
         let mut threats = Vec::new();
+        let mut budget = self.latency_budget.as_ref().map(LatencyBudget::start_event);
+        admit(&mut budget, "preprocess");
+        // Mask leaked credentials before the event is traced, parsed or stored
+        let leak = self.secret_scanner.as_ref().and_then(|scanner| scanner.scan(&mut event));
+        note(&mut trace, "input", 0, || event.to_string());
+        
+        // Drop exact duplicates before any detection work
+        let verdict = self.dedup.as_ref().map(|dedup| dedup.check(&event));
+        note(&mut trace, "dedup", 0, || format!("{:?}", verdict));
+        if let (Some(dedup), Some(DedupVerdict::Duplicate { .. })) = (&self.dedup, &verdict) {
+            if dedup.mode() == DedupMode::Drop {
+                return Ok(threats);
+            }
+        }
+        
+        // Extract structured fields from unstructured logs
+        if !self.pipelines.is_empty() {
+            let pipeline = self.pipelines.process(&mut event);
+            note(&mut trace, "parsing", 0, || format!("pipeline={:?} event={}", pipeline, event));
+        }
+        
+        // Canonical addresses so equality, allow-lists and CIDR rules agree
+        ip_net::normalize_addresses(&mut event);
+        // Fingerprints are stored on the event so they can be hunted on
+        if let Some(tls_fingerprints) = &self.tls_fingerprints {
+            tls_fingerprints.annotate(&mut event);
+        }
+        
+        // Move event time onto the receive clock so correlation windows line up
+        if let Some(clock) = &self.clock {
+            clock.normalize(&mut event);
+            note(&mut trace, "timestamp", 0, || format!("timestamp={}", event.get("timestamp").cloned().unwrap_or_default()));
+        }
+        
+        // Unicode folding so signatures and rules see the text an analyst would read
+        let normalized = self.text_normalizer.as_ref().map(|normalizer| normalizer.apply(&mut event)).unwrap_or_default();
+        if self.text_normalizer.is_some() {
+            note(&mut trace, "text_normalization", 0, || format!("{:?}", normalized.fields));
+        }
+        
+        // Config-defined tags and derived fields, carried onto every detection below
+        let tagged = self.tagging.as_ref().map(|tagging| tagging.apply(&mut event)).unwrap_or_default();
+        if self.tagging.is_some() {
+            note(&mut trace, "tagging", 0, || format!("tags={:?} fields={:?}", tagged.tags, tagged.fields));
+        }
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.detection_input = Some(event.clone());
+        }
+        if let Some(aggregator) = &self.aggregator {
+            aggregator.record_event(&event);
+        }
+        
+        // Bait accounts and hosts: any use is a hit, even from allow-listed sources
+        if let Some(canaries) = &self.canaries {
+            let timestamp = event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+                SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+            });
+            threats.extend(canaries.detect(&event, timestamp));
+            note(&mut trace, "canary_asset", threats.len(), || format!("hits {:?}", threats.iter().map(|t| &t.signatures).collect::<Vec<_>>()));
+        }
         
         // Check whitelist first
         if self.is_whitelisted(&event) {
+            note(&mut trace, "whitelist", threats.len(), || "whitelisted; detection skipped".to_string());
+            for threat in &mut threats {
+                tagged.annotate(threat);
+            }
             return Ok(threats);
         }
         
+        // Layered encodings peeled off payload fields for the signature and intel matchers
+        let decoded = self.decoder.as_ref().map(|decoder| decoder.decode_event(&event)).unwrap_or_default();
+        if self.decoder.is_some() {
+            note(&mut trace, "decoder", 0, || format!("{:?}", decoded.iter().map(|v| (&v.field, v.chain(), &v.text)).collect::<Vec<_>>()));
+        }
+        
         // Signature-based detection
-        if self.config.signature_enabled {
-            let signature_threats = self.signature_detection(&event).await?;
+        if self.config.signature_enabled && admit(&mut budget, "signature") {
+            let signature_threats = self.signature_detection(&event, &decoded).await?;
             threats.extend(signature_threats);
+            note(&mut trace, "signature", threats.len(), || format!("matched {:?}", threats.iter().flat_map(|t| t.signatures.clone()).collect::<Vec<_>>()));
+        }
+        
+        // Text that needed heavy normalization was likely crafted to slip past signatures
+        if let Some(normalizer) = self.text_normalizer.as_ref().filter(|_| !normalized.is_empty() && admit(&mut budget, "unicode_evasion")) {
+            let timestamp = event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+                SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+            });
+            let evasion = normalizer.evasion(&event, &normalized, timestamp);
+            let found = evasion.is_some();
+            threats.extend(evasion);
+            note(&mut trace, "unicode_evasion", threats.len(), || format!("evasion={} changes={}", found, normalized.suspicious_changes()));
         }
         
         // Behavioral analysis
-        if self.config.behavioral_enabled {
-            if let Some(behavioral_context) = self.behavioral_engine.analyze_behavior(&event) {
+        if self.config.behavioral_enabled && admit(&mut budget, "behavioral") {
+            let context = self.behavioral_engine.analyze_behavior(&event);
+            let detail = context.as_ref().map(|c| format!("risk_score={:.3} deviation={:.3}", c.risk_score, c.baseline_deviation));
+            if let Some(behavioral_context) = context {
                 let behavioral_threat = self.create_behavioral_threat(&event, behavioral_context).await?;
                 threats.push(behavioral_threat);
             }
+            note(&mut trace, "behavioral", threats.len(), || detail.unwrap_or_else(|| "no anomalous behavior".to_string()));
         }
         
         // Anomaly detection
-        if self.config.anomaly_enabled {
+        if self.config.anomaly_enabled && admit(&mut budget, "anomaly") {
             let anomaly_threats = self.anomaly_detection(&event).await?;
+            let found = anomaly_threats.len();
             threats.extend(anomaly_threats);
+            note(&mut trace, "anomaly", threats.len(), || format!("{} anomalies", found));
         }
         
         // Correlation analysis
-        if self.config.correlation_enabled {
+        if self.config.correlation_enabled && admit(&mut budget, "correlation") {
             let correlation_event = self.create_correlation_event(&event)?;
-            let correlation_threats = self.correlation_engine.process_event(correlation_event);
+            let correlation_threats = match &self.event_time {
+                Some(clock) => self.correlation_engine.process_event_time(correlation_event, clock),
+                None => self.correlation_engine.process_event(correlation_event),
+            };
+            let rules: Vec<String> = correlation_threats.iter().map(|t| t.description.clone()).collect();
             threats.extend(correlation_threats);
+            note(&mut trace, "correlation", threats.len(), || format!("fired {:?}", rules));
+        }
+        
+        // Fan-out detection from bounded-memory distinct destination counts
+        if let Some(cardinality) = self.cardinality.as_ref().filter(|_| admit(&mut budget, "cardinality")) {
+            let alert = cardinality.observe(&event);
+            if let Some(alert) = &alert {
+                threats.push(self.create_fanout_threat(&event, alert));
+            }
+            note(&mut trace, "cardinality", threats.len(), || format!("{:?}", alert));
+        }
+        
+        // Per-source SSH and RDP logon failures
+        if let Some(brute_force) = self.brute_force.as_ref().filter(|_| admit(&mut budget, "brute_force")) {
+            let alert = brute_force.observe(&event);
+            if let Some(alert) = &alert {
+                let timestamp = event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+                    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+                });
+                threats.push(alert.to_threat(timestamp));
+            }
+            note(&mut trace, "brute_force", threats.len(), || format!("{:?}", alert));
+        }
+        
+        // Bursts of configuration changes and changes outside change windows
+        if let Some(config_changes) = self.config_changes.as_ref().filter(|_| admit(&mut budget, "config_change")) {
+            let timestamp = event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+                SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+            });
+            let alert = config_changes.observe_at(&event, timestamp);
+            if let Some(alert) = &alert {
+                threats.push(alert.to_threat(timestamp));
+            }
+            note(&mut trace, "config_change", threats.len(), || format!("{:?}", alert));
+        }
+        
+        // Scanners, 404 bursts, credential stuffing and path traversal in access logs
+        if let Some(web_access) = self.web_access.as_ref().filter(|_| admit(&mut budget, "web_access")) {
+            let alerts = web_access.observe(&event);
+            let timestamp = event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+                SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+            });
+            let window_seconds = web_access.settings().window_seconds;
+            threats.extend(alerts.iter().map(|alert| alert.to_threat(timestamp, window_seconds)));
+            note(&mut trace, "web_access", threats.len(), || format!("{:?}", alerts));
+        }
+        
+        // Look-alike, unexpected issuer and self-signed internal certificates in TLS handshakes
+        if let Some(cert_monitor) = self.cert_monitor.as_ref().filter(|_| admit(&mut budget, "cert_monitor")) {
+            let alerts = cert_monitor.observe(&event);
+            let timestamp = event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+                SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+            });
+            threats.extend(alerts.iter().map(|alert| alert.to_threat(timestamp)));
+            note(&mut trace, "cert_monitor", threats.len(), || format!("{:?}", alerts));
+        }
+        
+        // JA3/JA3S fingerprints known to be malicious, or rare ones reaching critical servers
+        if let Some(tls_fingerprints) = self.tls_fingerprints.as_ref().filter(|_| admit(&mut budget, "tls_fingerprint")) {
+            let timestamp = event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+                SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+            });
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+            let alerts = tls_fingerprints.observe(&event, self.intel.as_deref(), now);
+            threats.extend(alerts.iter().map(|alert| alert.to_threat(&event, timestamp)));
+            note(&mut trace, "tls_fingerprint", threats.len(), || format!("{:?}", alerts));
+        }
+        
+        // Domains outside the learned allow-list of the asset's group
+        if let Some(domains) = self.domains.as_ref().filter(|_| admit(&mut budget, "domain_baseline")) {
+            let alert = domains.observe(&event, chrono::Utc::now());
+            if let Some(alert) = &alert {
+                let timestamp = event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+                    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+                });
+                threats.push(alert.to_threat(&event, timestamp));
+            }
+            note(&mut trace, "domain_baseline", threats.len(), || format!("{:?}", alert));
+        }
+        
+        // Credentials found in the payload when it entered the pipeline
+        if self.secret_scanner.is_some() && admit(&mut budget, "secret_scan") {
+            if let Some(leak) = &leak {
+                let timestamp = event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+                    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+                });
+                threats.push(leak.to_threat(&event, timestamp));
+            }
+            note(&mut trace, "secret_scan", threats.len(), || format!("{:?}", leak));
+        }
+        
+        // Indicators reported by threat intel feeds
+        if let Some(intel) = self.intel.as_ref().filter(|_| admit(&mut budget, "intel")) {
+            let timestamp = event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+                SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+            });
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+            let mut matches = intel.match_event(&event, now);
+            // Fingerprint indicators are reported with their handshake by the tls_fingerprint stage
+            if self.tls_fingerprints.is_some() {
+                matches.retain(|indicator| !tls_fingerprint::is_fingerprint_indicator(&indicator.ioc_type));
+            }
+            threats.extend(matches.iter().map(|indicator| indicator.to_threat(&event, timestamp)));
+            // Indicators only visible once decoded, e.g. a C2 address inside an encoded command
+            if let Some(decoder) = self.decoder.as_ref().filter(|_| !decoded.is_empty()) {
+                for indicator in intel.match_event(&DecoderChain::as_event(&decoded), now) {
+                    if matches.iter().any(|m| m.value == indicator.value) {
+                        continue;
+                    }
+                    let mut threat = indicator.to_threat(&event, timestamp);
+                    if let Some(variant) = decoded.iter().find(|v| v.text.contains(indicator.value.as_str())) {
+                        decoder.annotate(&mut threat, variant);
+                    }
+                    threats.push(threat);
+                    matches.push(indicator);
+                }
+            }
+            note(&mut trace, "intel", threats.len(), || {
+                format!("matched {:?}", matches.iter().map(|m| (&m.value, m.confidence)).collect::<Vec<_>>())
+            });
         }
         
         // Quantum detection
-        if let Some(event_str) = event.get("message").and_then(|v| v.as_str()) {
+        if let Some(event_str) = event.get("message").and_then(|v| v.as_str()).filter(|_| admit(&mut budget, "quantum")) {
             self.quantum_detector.process_event(event_str);
             let quantum_matches = self.quantum_detector.get_matches();
+            let detail = format!("matched {:?}", quantum_matches);
             if !quantum_matches.is_empty() {
                 let quantum_threat = self.create_quantum_threat(&event, quantum_matches).await?;
                 threats.push(quantum_threat);
             }
+            note(&mut trace, "quantum", threats.len(), || detail);
+        }
+        
+        if !tagged.is_empty() {
+            for threat in &mut threats {
+                tagged.annotate(threat);
+            }
+        }
+        
+        // Actors already seen touching a honeyport
+        if let Some(attackers) = &self.attackers {
+            let enriched = threats.iter_mut().map(|threat| attackers.enrich(threat)).filter(|known| *known).count();
+            note(&mut trace, "honeyport_actor", threats.len(), || format!("enriched {}", enriched));
         }
         
         // Filter false positives
-        threats.retain(|threat| !self.is_false_positive(threat));
+        admit(&mut budget, "filter");
+        let profile = self.profiles.as_ref().and_then(|profiles| profiles.resolve(&event));
+        if let Some((name, profile)) = &profile {
+            let before = threats.len();
+            threats.retain(|threat| canary_asset::is_canary_hit(threat) || profile.enables(threat));
+            for threat in &mut threats {
+                threat.details.insert(PROFILE_DETAIL.to_string(), name.clone());
+            }
+            note(&mut trace, "profile", threats.len(), || format!("{}: disabled {}", name, before - threats.len()));
+        }
+        let before = threats.len();
+        threats.retain(|threat| {
+            let threshold = profile.as_ref().and_then(|(_, profile)| profile.min_confidence_for(&threat.category));
+            canary_asset::is_canary_hit(threat) || !self.is_false_positive(threat, threshold.unwrap_or(self.config.false_positive_threshold))
+        });
+        note(&mut trace, "false_positive", threats.len(), || format!("removed {}", before - threats.len()));
+        
+        // Analyst snoozes
+        if let Some(suppressions) = &self.suppressions {
+            let before = threats.len();
+            let now = chrono::Utc::now();
+            threats.retain(|threat| canary_asset::is_canary_hit(threat) || !suppressions.suppresses(threat, now));
+            note(&mut trace, "suppression", threats.len(), || format!("suppressed {}", before - threats.len()));
+        }
+        
+        // Tell analysts which optional stages these detections ran without
+        if let Some(budget) = budget.take() {
+            let skipped = budget.finish().join(",");
+            if !skipped.is_empty() {
+                note(&mut trace, "latency_budget", threats.len(), || format!("skipped {}", skipped));
+                for threat in &mut threats {
+                    threat.details.insert(SKIPPED_STAGES_DETAIL.to_string(), skipped.clone());
+                }
+            }
+        }
+        
+        // Tag detections raised by a kept duplicate
+        if let Some(DedupVerdict::Duplicate { first_seen, count }) = verdict {
+            for threat in &mut threats {
+                threat.details.insert("duplicate".to_string(), "true".to_string());
+                threat.details.insert("duplicate_first_seen".to_string(), first_seen.to_string());
+                threat.details.insert("duplicate_count".to_string(), count.to_string());
+            }
+        }
         
         // Record performance metrics
         let processing_time = start_time.elapsed().as_millis() as f64;
         self.performance_metrics.insert("avg_processing_time_ms".to_string(), processing_time);
-        
-        // Publish threats
-        for threat in &threats {
-            let _ = self.threat_tx.send(threat.clone()).await;
+        #[cfg(feature = "response")]
+        if let Some(latency) = &self.latency {
+            latency.observe(start_time.elapsed());
         }
         
         Ok(threats)
     }
 
-    async fn signature_detection(&self, event: &serde_json::Value) -> SIEMResult<Vec<AdvancedThreatResult>> {
-        let mut threats = Vec::new();
+    async fn signature_detection(&self, event: &serde_json::Value, decoded: &[DecodedVariant]) -> SIEMResult<Vec<AdvancedThreatResult>> {
+        let mut threats: Vec<AdvancedThreatResult> = Vec::new();
         
-        if let Some(message) = event.get("message").and_then(|v| v.as_str()) {
-            let matches = self.signature_engine.match_signatures(message);
+        // The raw message first, then decoded payloads; each signature fires once per event
+        let message = event.get("message").and_then(|v| v.as_str()).map(|message| (message, None));
+        for (text, variant) in message.into_iter().chain(decoded.iter().map(|v| (v.text.as_str(), Some(v)))) {
+            let matches = self.signature_engine.match_signatures(text);
             
             for match_result in matches {
+                if threats.iter().any(|t| t.signatures.contains(&match_result.signature_id)) {
+                    continue;
+                }
                 let signature = self.signature_engine.compiled_signatures.get(&match_result.signature_id).unwrap();
-                let threat = AdvancedThreatResult {
+                let mut threat = AdvancedThreatResult {
                     threat_id: Uuid::new_v4().to_string(),
                     timestamp: event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
                         SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
@@ -681,8 +1627,11 @@ impl AdvancedThreatDetectionEngine {
                     correlation_events: Vec::new(),
                     false_positive_probability: 0.2,
                     gpu_processing_time_ms: 0.0,
-                    details: HashMap::new(),
+                    details: attack::technique_details(&signature.attack_techniques),
                 };
+                if let (Some(decoder), Some(variant)) = (&self.decoder, variant) {
+                    decoder.annotate(&mut threat, variant);
+                }
                 
                 threats.push(threat);
             }
@@ -691,6 +1640,29 @@ impl AdvancedThreatDetectionEngine {
         Ok(threats)
     }
 
+    fn create_fanout_threat(&self, event: &serde_json::Value, alert: &FanoutAlert) -> AdvancedThreatResult {
+        let mut details = HashMap::new();
+        details.insert("distinct_destinations".to_string(), alert.distinct_destinations.to_string());
+        details.insert("window_seconds".to_string(), alert.window_seconds.to_string());
+        AdvancedThreatResult {
+            timestamp: event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+                SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+            }),
+            severity: ThreatSeverity::High,
+            category: ThreatCategory::LateralMovement,
+            confidence: 0.8,
+            detection_method: "cardinality".to_string(),
+            source_ip: alert.host.clone(),
+            user_id: event.get("user_id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            description: format!(
+                "Host {} contacted ~{} unique external IPs within {} seconds",
+                alert.host, alert.distinct_destinations, alert.window_seconds
+            ),
+            details,
+            ..Default::default()
+        }
+    }
+
     async fn create_behavioral_threat(&self, event: &serde_json::Value, context: BehavioralContext) -> SIEMResult<AdvancedThreatResult> {
         let severity = if context.risk_score > 0.8 {
             ThreatSeverity::Critical
@@ -739,16 +1711,29 @@ impl AdvancedThreatDetectionEngine {
             features.insert("user_activity".to_string(), user_hash);
         }
         
-        // Perform anomaly detection
-        let anomaly_results = self.behavioral_engine.anomaly_engine.batch_score(&features);
+        // Perform anomaly detection, against the source's tuned threshold when tuning is on
+        let timestamp = event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+        });
+        let source = event.get("source").and_then(|v| v.as_str()).filter(|s| !s.is_empty()).unwrap_or("unknown");
+        let anomaly_engine = &self.behavioral_engine.anomaly_engine;
+        let anomaly_results = features.iter().map(|(feature, value)| match &self.anomaly_tuner {
+            Some(tuner) => {
+                let sensitivity = tuner.sensitivity(source, feature);
+                let mut result = anomaly_engine.score_with_threshold(feature, *value, sensitivity);
+                tuner.observe(source, feature, result.is_anomaly, timestamp);
+                result.details.insert("anomaly_source".to_string(), source.to_string());
+                result.details.insert("anomaly_sensitivity".to_string(), format!("{:.2}", sensitivity));
+                (feature.clone(), result)
+            }
+            None => (feature.clone(), anomaly_engine.score(feature, *value)),
+        });
         
         for (feature, result) in anomaly_results {
             if result.is_anomaly {
                 let threat = AdvancedThreatResult {
                     threat_id: Uuid::new_v4().to_string(),
-                    timestamp: event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
-                        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
-                    }),
+                    timestamp,
                     severity: ThreatSeverity::Medium,
                     category: ThreatCategory::Other,
                     confidence: result.score.min(1.0),
@@ -776,7 +1761,7 @@ impl AdvancedThreatDetectionEngine {
     fn create_correlation_event(&self, event: &serde_json::Value) -> SIEMResult<CorrelationEvent> {
         Ok(CorrelationEvent {
             id: Uuid::new_v4().to_string(),
-            timestamp: event.get("timestamp").and_then(|v| v.as_u64()).unwrap_or_else(|| {
+            timestamp: event.get("timestamp").and_then(|v| v.as_u64()).map(crate::clock_skew::epoch_seconds).unwrap_or_else(|| {
                 SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
             }),
             event_type: event.get("event_type").and_then(|v| v.as_str()).unwrap_or("unknown").to_string(),
@@ -784,7 +1769,16 @@ impl AdvancedThreatDetectionEngine {
             target: event.get("destination_ip").and_then(|v| v.as_str()).unwrap_or("").to_string(),
             severity: ThreatSeverity::Low,
             confidence: 0.5,
-            metadata: HashMap::new(),
+            metadata: CORRELATION_FIELDS
+                .iter()
+                .filter_map(|field| {
+                    let value = match event.get(*field)? {
+                        serde_json::Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    Some((field.to_string(), value))
+                })
+                .collect(),
         })
     }
 
@@ -824,6 +1818,12 @@ impl AdvancedThreatDetectionEngine {
             if whitelist.contains(source_ip) {
                 return true;
             }
+            // Entries may also be CIDR ranges of either family
+            if let Some(ip) = ip_net::parse_ip(source_ip) {
+                if whitelist.iter().filter(|entry| entry.contains('/')).any(|entry| ip_net::network_contains(entry, ip)) {
+                    return true;
+                }
+            }
         }
         
         // Check user ID
@@ -836,7 +1836,7 @@ impl AdvancedThreatDetectionEngine {
         false
     }
 
-    fn is_false_positive(&self, threat: &AdvancedThreatResult) -> bool {
+    fn is_false_positive(&self, threat: &AdvancedThreatResult, confidence_threshold: f32) -> bool {
         // Check false positive history
         let history_key = format!("{}:{}", threat.detection_method, threat.source_ip);
         if let Some(count) = self.false_positive_history.get(&history_key) {
@@ -846,7 +1846,23 @@ impl AdvancedThreatDetectionEngine {
         }
         
         // Check confidence threshold
-        threat.confidence < self.config.false_positive_threshold
+        threat.confidence < confidence_threshold
+    }
+
+    pub fn add_signature(&self, signature: SignaturePattern) -> SIEMResult<()> {
+        self.signature_engine.add_signature(signature)
+    }
+
+    pub fn remove_signature(&self, signature_id: &str) -> bool {
+        self.signature_engine.remove_signature(signature_id)
+    }
+
+    pub fn add_correlation_rule(&self, rule: CorrelationRule) {
+        self.correlation_engine.add_correlation_rule(rule)
+    }
+
+    pub fn remove_correlation_rule(&self, rule_id: &str) -> bool {
+        self.correlation_engine.remove_correlation_rule(rule_id)
     }
 
     pub fn mark_false_positive(&self, threat_id: &str) -> SIEMResult<()> {
@@ -857,6 +1873,8 @@ impl AdvancedThreatDetectionEngine {
     }
 
     pub fn add_to_whitelist(&self, item: String) -> SIEMResult<()> {
+        // Store addresses and ranges in the spelling normalized events use
+        let item = item.parse::<IpNetwork>().map_or(item, |network| network.to_string());
         let mut whitelist = self.whitelist.write().unwrap();
         whitelist.insert(item.clone());
         info!("✅ Added {} to whitelist", item);
@@ -864,14 +1882,71 @@ impl AdvancedThreatDetectionEngine {
     }
 
     pub fn remove_from_whitelist(&self, item: &str) -> SIEMResult<()> {
+        let item = item.parse::<IpNetwork>().map_or(item.to_string(), |network| network.to_string());
         let mut whitelist = self.whitelist.write().unwrap();
-        whitelist.remove(item);
+        whitelist.remove(&item);
         info!("✅ Removed {} from whitelist", item);
         Ok(())
     }
 
     pub fn get_performance_metrics(&self) -> HashMap<String, f64> {
-        self.performance_metrics.iter().map(|entry| (entry.key().clone(), *entry.value())).collect()
+        let mut metrics: HashMap<String, f64> =
+            self.performance_metrics.iter().map(|entry| (entry.key().clone(), *entry.value())).collect();
+        if let Some(dedup) = &self.dedup {
+            metrics.extend(dedup.get_metrics());
+        }
+        if let Some(latency_budget) = &self.latency_budget {
+            metrics.extend(latency_budget.get_metrics());
+        }
+        if let Some(clock) = &self.clock {
+            metrics.extend(clock.get_metrics());
+        }
+        if let Some(event_time) = &self.event_time {
+            metrics.extend(event_time.get_metrics());
+        }
+        if let Some(cardinality) = &self.cardinality {
+            metrics.extend(cardinality.get_metrics());
+        }
+        if let Some(brute_force) = &self.brute_force {
+            metrics.extend(brute_force.get_metrics());
+        }
+        if let Some(config_changes) = &self.config_changes {
+            metrics.extend(config_changes.get_metrics());
+        }
+        if let Some(tagging) = &self.tagging {
+            metrics.extend(tagging.get_metrics());
+        }
+        if let Some(web_access) = &self.web_access {
+            metrics.extend(web_access.get_metrics());
+        }
+        if let Some(cert_monitor) = &self.cert_monitor {
+            metrics.extend(cert_monitor.get_metrics());
+        }
+        if let Some(attackers) = &self.attackers {
+            metrics.extend(attackers.get_metrics());
+        }
+        if let Some(canaries) = &self.canaries {
+            metrics.extend(canaries.get_metrics());
+        }
+        if let Some(tuner) = &self.anomaly_tuner {
+            metrics.extend(tuner.get_metrics());
+        }
+        if let Some(tls_fingerprints) = &self.tls_fingerprints {
+            metrics.extend(tls_fingerprints.get_metrics());
+        }
+        if let Some(secret_scanner) = &self.secret_scanner {
+            metrics.extend(secret_scanner.get_metrics());
+        }
+        if let Some(text_normalizer) = &self.text_normalizer {
+            metrics.extend(text_normalizer.get_metrics());
+        }
+        if let Some(decoder) = &self.decoder {
+            metrics.extend(decoder.get_metrics());
+        }
+        if let Some(memory) = &self.memory {
+            metrics.extend(memory.get_metrics());
+        }
+        metrics
     }
 
     fn initialize_default_signatures(&self) -> SIEMResult<()> {
@@ -885,6 +1960,7 @@ impl AdvancedThreatDetectionEngine {
                 description: "Detects SQL injection attempts".to_string(),
                 enabled: true,
                 confidence: 0.9,
+                attack_techniques: vec!["T1190".to_string()],
             },
             SignaturePattern {
                 id: "xss_1".to_string(),
@@ -895,6 +1971,7 @@ impl AdvancedThreatDetectionEngine {
                 description: "Detects XSS attempts".to_string(),
                 enabled: true,
                 confidence: 0.8,
+                attack_techniques: vec!["T1189".to_string()],
             },
             SignaturePattern {
                 id: "brute_force_1".to_string(),
@@ -905,6 +1982,7 @@ impl AdvancedThreatDetectionEngine {
                 description: "Detects brute force attacks".to_string(),
                 enabled: true,
                 confidence: 0.7,
+                attack_techniques: vec!["T1110".to_string()],
             },
             SignaturePattern {
                 id: "malware_1".to_string(),
@@ -915,6 +1993,7 @@ impl AdvancedThreatDetectionEngine {
                 description: "Detects malware-related activities".to_string(),
                 enabled: true,
                 confidence: 0.9,
+                attack_techniques: vec!["T1204".to_string()],
             },
         ];
         
@@ -938,11 +2017,13 @@ impl AdvancedThreatDetectionEngine {
                         target_pattern: None,
                         min_count: 5,
                         max_count: None,
+                        fields: HashMap::new(),
                     }
                 ],
                 time_window: 300, // 5 minutes
                 severity: ThreatSeverity::High,
                 enabled: true,
+                attack_techniques: vec!["T1110".to_string()],
             },
             CorrelationRule {
                 id: "data_exfiltration".to_string(),
@@ -955,11 +2036,13 @@ impl AdvancedThreatDetectionEngine {
                         target_pattern: None,
                         min_count: 10,
                         max_count: None,
+                        fields: HashMap::new(),
                     }
                 ],
                 time_window: 600, // 10 minutes
                 severity: ThreatSeverity::Critical,
                 enabled: true,
+                attack_techniques: vec!["T1041".to_string()],
             },
         ];
         
@@ -977,7 +2060,10 @@ impl AdvancedThreatDetectionEngine {
         ];
         
         for (name, pattern) in patterns {
-            self.quantum_detector.cache.add_pattern(name.to_string(), pattern.to_string());
+            let pattern = QuantumPattern { origin: PatternOrigin::Builtin, ..QuantumPattern::new(name, pattern) };
+            if let Err(e) = self.quantum_detector.install_pattern(pattern) {
+                warn!("⚠️ Built-in quantum pattern {} not loaded: {}", name, e);
+            }
         }
     }
 }
@@ -1011,6 +2097,139 @@ mod tests {
         assert_eq!(sql_threat.severity, ThreatSeverity::High);
     }
 
+    #[tokio::test]
+    async fn test_duplicate_events_are_dropped_before_detection() {
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        engine.enable_dedup(DedupSettings {
+            enabled: true,
+            ..Default::default()
+        });
+
+        let event = json!({ "source_ip": "192.168.1.100", "message": "UNION SELECT * FROM users" });
+        assert!(!engine.process_event(event.clone()).await.unwrap().is_empty());
+        assert!(engine.process_event(event).await.unwrap().is_empty());
+        assert_eq!(engine.get_performance_metrics()["dedup_duplicates"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_allow_list_ranges_cover_both_families() {
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        engine.add_to_whitelist("10.20.0.0/16".to_string()).unwrap();
+        engine.add_to_whitelist("2001:DB8:AB::/48".to_string()).unwrap();
+
+        let attack = |source: &str| json!({ "source_ip": source, "message": "UNION SELECT * FROM users" });
+        assert!(engine.process_event(attack("10.20.3.4")).await.unwrap().is_empty());
+        assert!(engine.process_event(attack("[2001:db8:ab::7]:443")).await.unwrap().is_empty());
+        assert!(engine.process_event(attack("::ffff:10.20.9.9")).await.unwrap().is_empty());
+        let threats = engine.process_event(attack("2001:db8:ac::7")).await.unwrap();
+        assert!(!threats.is_empty());
+        assert_eq!(threats[0].source_addr(), Some("2001:db8:ac::7".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_detections_record_stages_shed_over_budget() {
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        // A zero budget is spent before the first optional stage
+        engine.enable_latency_budget(LatencyBudgetSettings { enabled: true, budget_ms: 0, ..Default::default() });
+
+        let threats = engine.process_event(json!({ "source_ip": "192.168.1.100", "message": "UNION SELECT * FROM users" })).await.unwrap();
+        let signature = threats.iter().find(|t| t.detection_method == "signature").unwrap();
+        assert_eq!(signature.details[SKIPPED_STAGES_DETAIL], "behavioral,anomaly,correlation,quantum");
+        let metrics = engine.get_performance_metrics();
+        assert_eq!(metrics["stage_anomaly_skipped"], 1.0);
+        assert!(metrics.contains_key("stage_signature_mean_ms"));
+    }
+
+    #[tokio::test]
+    async fn test_memory_budget_evicts_least_recently_active_users() {
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        let memory = Arc::new(MemoryAccountant::new(crate::memory_budget::MemorySettings {
+            limits: [("behavioral.user_profiles".to_string(), StructureLimit { max_entries: 2, ttl_seconds: 0 })].into(),
+            ..Default::default()
+        }).unwrap());
+        engine.set_memory_accountant(memory.clone());
+
+        for (i, user) in ["alice", "bob", "carol", "dave"].iter().enumerate() {
+            let event = json!({ "timestamp": 1_700_000_000 + i as u64, "source_ip": "192.0.2.10", "user_id": user, "action": "login", "message": "login ok" });
+            engine.process_event(event).await.unwrap();
+        }
+        engine.enforce_memory_limits();
+
+        let mut users: Vec<String> = engine.behavioral_engine.user_profiles.iter().map(|e| e.key().clone()).collect();
+        users.sort();
+        assert_eq!(users, vec!["carol", "dave"]);
+        let report = memory.report();
+        let profiles = &report.subsystems["behavioral"].structures["user_profiles"];
+        assert_eq!(profiles.entries, 2);
+        assert!(profiles.evicted_over_limit_total >= 2);
+        assert!(report.total_estimated_bytes > 0);
+        assert_eq!(engine.get_performance_metrics()["memory_behavioral_user_profiles_entries"], 2.0);
+    }
+
+    #[test]
+    fn test_event_time_windows_survive_out_of_order_events() {
+        let engine = CorrelationEngine::new();
+        engine.add_correlation_rule(serde_json::from_value(json!({
+            "id": "burst", "name": "Login failure burst", "description": "",
+            "conditions": [{ "event_type": "login_failed", "min_count": 3 }],
+            "time_window": 60, "severity": "High", "enabled": true
+        })).unwrap());
+        let clock = EventTimeClock::new(EventTimeSettings {
+            enabled: true,
+            watermark_delay_seconds: 30,
+            late_events: crate::event_time::LateEventPolicy::Drop,
+            ..Default::default()
+        });
+        let event = |timestamp: u64| CorrelationEvent {
+            id: timestamp.to_string(),
+            timestamp,
+            event_type: "login_failed".to_string(),
+            source: "203.0.113.9".to_string(),
+            target: String::new(),
+            severity: ThreatSeverity::Low,
+            confidence: 0.5,
+            metadata: HashMap::new(),
+        };
+
+        assert!(engine.process_event_time(event(1_210), &clock).is_empty());
+        assert!(engine.process_event_time(event(1_230), &clock).is_empty());
+        // Arrives last but belongs first; the window is still open
+        let threats = engine.process_event_time(event(1_205), &clock);
+        assert_eq!(threats.len(), 1);
+        let order: Vec<u64> = threats[0].correlation_events.iter().map(|e| e.timestamp).collect();
+        assert_eq!(order, vec![1_205, 1_210, 1_230]);
+        // Fires once per window
+        assert!(engine.process_event_time(event(1_250), &clock).is_empty());
+
+        // The watermark moves past the window; a straggler for it is dropped
+        assert!(engine.process_event_time(event(1_400), &clock).is_empty());
+        assert!(!engine.active_correlations.contains_key("burst@1200"));
+        assert!(engine.process_event_time(event(1_215), &clock).is_empty());
+        assert_eq!(clock.get_metrics()["event_time_late_dropped"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_signatures_match_decoded_payloads() {
+        use base64ct::Encoding as _;
+
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        let payload = base64ct::Base64::encode_string(b"1%20%55NION%20%53ELECT%20password%20%46ROM%20users");
+        let event = json!({ "source_ip": "192.168.1.100", "message": format!("GET /search?q={}", payload) });
+        assert!(!engine.process_event(event.clone()).await.unwrap().iter().any(|t| t.detection_method == "signature"));
+
+        engine.enable_decoder(DecoderSettings::default()).unwrap();
+        let threats = engine.process_event(event).await.unwrap();
+        let signature = threats.iter().find(|t| t.detection_method == "signature").unwrap();
+        assert_eq!(signature.details["decoded_encodings"], "base64>url");
+        assert_eq!(signature.details["decoded_payload"], "1 UNION SELECT password FROM users");
+        assert_eq!(signature.details["deep_encoding"], "true");
+    }
+
     #[test]
     fn test_yara_signature_engine() {
         let engine = YaraSignatureEngine::new();
@@ -1024,6 +2243,7 @@ mod tests {
             description: "Test signature".to_string(),
             enabled: true,
             confidence: 0.9,
+            attack_techniques: Vec::new(),
         };
         
         engine.add_signature(signature).unwrap();
@@ -0,0 +1,488 @@
+//! # Remote Agent Tasking
+//!
+//! Response actions that must run on the affected endpoint (`RunOnAgent`) are
+//! sent to the collector agent of that host as a NATS request on
+//! `ultra_siem.agents.<host_id>.commands`. Commands are signed with the core's
+//! Ed25519 key; agents only execute commands addressed to them, signed by a
+//! trusted key, recent and not seen before. The reply carries the execution
+//! result, which the incident engine records on the incident.
+//!
+//! Only `BlockIP`, `KillProcess` and `QuarantineFile` run on agents, plus the
+//! playbook collections `CollectProcessList` and `SnapshotFirewallRules`, whose
+//! reply carries the collected data as `output`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use base64ct::{Base64, Encoding};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::{IncidentResponseEngine, ResponseAction};
+use crate::playbook;
+use crate::subjects::{validate, SUBJECT_AGENT_COMMANDS};
+
+/// Command ids an agent remembers to refuse replays
+const SEEN_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentTaskingSettings {
+    /// Dispatch `RunOnAgent` response actions from this core
+    pub enabled: bool,
+    /// File holding the base64 Ed25519 seed commands are signed with (`--agent keygen`)
+    pub signing_key_path: String,
+    pub key_id: String,
+    /// How long to wait for an agent's result
+    pub request_timeout_seconds: u64,
+    /// Agent side: id this agent answers to
+    pub host_id: String,
+    /// Agent side: key id -> base64 Ed25519 public key of cores allowed to task this agent
+    pub trusted_keys: HashMap<String, String>,
+    /// Agent side: older commands are refused
+    pub max_command_age_seconds: i64,
+}
+
+impl Default for AgentTaskingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            signing_key_path: "data/agent-signing.key".to_string(),
+            key_id: "core".to_string(),
+            request_timeout_seconds: 30,
+            host_id: String::new(),
+            trusted_keys: HashMap::new(),
+            max_command_age_seconds: 60,
+        }
+    }
+}
+
+/// Whether `action` can be executed by a remote agent
+pub fn runs_on_agent(action: &ResponseAction) -> bool {
+    matches!(
+        action,
+        ResponseAction::BlockIP { .. }
+            | ResponseAction::KillProcess { .. }
+            | ResponseAction::QuarantineFile { .. }
+            | ResponseAction::CollectProcessList
+            | ResponseAction::SnapshotFirewallRules
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentCommand {
+    pub command_id: String,
+    pub host_id: String,
+    pub incident_id: String,
+    pub action: ResponseAction,
+    pub issued_at: DateTime<Utc>,
+    pub key_id: String,
+}
+
+/// Wire form of a command; the signature covers the exact `command` bytes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedAgentCommand {
+    /// JSON encoded `AgentCommand`
+    pub command: String,
+    /// Base64 Ed25519 signature over `command`
+    pub signature: String,
+}
+
+/// Execution result an agent replies with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentCommandResult {
+    pub command_id: String,
+    pub host_id: String,
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub execution_time_ms: u64,
+    pub completed_at: DateTime<Utc>,
+    /// What a collection action gathered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+}
+
+impl AgentCommandResult {
+    fn failed(command_id: &str, host_id: &str, error: impl ToString) -> Self {
+        Self {
+            command_id: command_id.to_string(),
+            host_id: host_id.to_string(),
+            success: false,
+            error_message: Some(error.to_string()),
+            execution_time_ms: 0,
+            completed_at: Utc::now(),
+            output: None,
+        }
+    }
+}
+
+/// Delivers a signed command to the agent of `host_id` and waits for its result
+pub trait AgentTransport: Send + Sync {
+    fn request<'a>(&'a self, host_id: &'a str, command: &'a SignedAgentCommand) -> BoxFuture<'a, SIEMResult<AgentCommandResult>>;
+}
+
+/// NATS request/reply on `ultra_siem.agents.<host_id>.commands`
+pub struct NatsAgentTransport {
+    client: async_nats::Client,
+    timeout: Duration,
+}
+
+impl NatsAgentTransport {
+    pub fn new(client: async_nats::Client, timeout: Duration) -> Self {
+        Self { client, timeout }
+    }
+}
+
+impl AgentTransport for NatsAgentTransport {
+    fn request<'a>(&'a self, host_id: &'a str, command: &'a SignedAgentCommand) -> BoxFuture<'a, SIEMResult<AgentCommandResult>> {
+        Box::pin(async move {
+            let subject = agent_subject(host_id)?;
+            let payload = serde_json::to_value(command)?;
+            validate(&subject, &payload)?;
+            let reply = tokio::time::timeout(self.timeout, self.client.request(subject, serde_json::to_vec(&payload)?.into()))
+                .await
+                .map_err(|_| SIEMError::Other(format!("Agent {} did not answer within {:?}", host_id, self.timeout)))?
+                .map_err(|e| SIEMError::Other(format!("Agent {} unreachable: {}", host_id, e)))?;
+            Ok(serde_json::from_slice(&reply.payload)?)
+        })
+    }
+}
+
+/// Signs response actions and sends them to agents
+pub struct AgentDispatcher {
+    transport: Arc<dyn AgentTransport>,
+    signing_key: SigningKey,
+    key_id: String,
+}
+
+impl AgentDispatcher {
+    pub fn new(transport: Arc<dyn AgentTransport>, signing_key: SigningKey, key_id: &str) -> Self {
+        Self { transport, signing_key, key_id: key_id.to_string() }
+    }
+
+    /// Load the signing key and connect to NATS
+    pub async fn connect(settings: &AgentTaskingSettings, nats_url: &str) -> SIEMResult<Self> {
+        let seed = std::fs::read_to_string(&settings.signing_key_path)
+            .map_err(|e| SIEMError::Config(format!("Agent signing key {}: {}", settings.signing_key_path, e)))?;
+        let signing_key = SigningKey::from_bytes(&decode_key(&seed)?);
+        let client = async_nats::connect(nats_url)
+            .await
+            .map_err(|e| SIEMError::Other(format!("NATS {} unavailable: {}", nats_url, e)))?;
+        let timeout = Duration::from_secs(settings.request_timeout_seconds.max(1));
+        Ok(Self::new(Arc::new(NatsAgentTransport::new(client, timeout)), signing_key, &settings.key_id))
+    }
+
+    pub fn sign(&self, host_id: &str, incident_id: &str, action: &ResponseAction) -> SIEMResult<SignedAgentCommand> {
+        let command = AgentCommand {
+            command_id: uuid::Uuid::new_v4().to_string(),
+            host_id: host_id.to_string(),
+            incident_id: incident_id.to_string(),
+            action: action.clone(),
+            issued_at: Utc::now(),
+            key_id: self.key_id.clone(),
+        };
+        let command = serde_json::to_string(&command)?;
+        let signature = encode(&self.signing_key.sign(command.as_bytes()).to_bytes());
+        Ok(SignedAgentCommand { command, signature })
+    }
+
+    /// Run `action` on the agent of `host_id`; delivery failures come back as failed results
+    pub async fn dispatch(&self, host_id: &str, incident_id: &str, action: &ResponseAction) -> AgentCommandResult {
+        let signed = match self.sign(host_id, incident_id, action) {
+            Ok(signed) => signed,
+            Err(e) => return AgentCommandResult::failed("", host_id, e),
+        };
+        let command_id = serde_json::from_str::<AgentCommand>(&signed.command).map(|c| c.command_id).unwrap_or_default();
+        match self.transport.request(host_id, &signed).await {
+            Ok(result) if result.command_id == command_id => result,
+            Ok(result) => AgentCommandResult::failed(&command_id, host_id, format!("Agent answered for command {}", result.command_id)),
+            Err(e) => AgentCommandResult::failed(&command_id, host_id, e),
+        }
+    }
+}
+
+impl std::fmt::Debug for AgentDispatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AgentDispatcher").field("key_id", &self.key_id).finish_non_exhaustive()
+    }
+}
+
+/// Agent side: verifies commands from trusted cores and executes them locally
+pub struct AgentExecutor {
+    host_id: String,
+    trusted_keys: HashMap<String, VerifyingKey>,
+    max_age: chrono::Duration,
+    seen: Mutex<(HashSet<String>, VecDeque<String>)>,
+    engine: Arc<IncidentResponseEngine>,
+}
+
+impl AgentExecutor {
+    pub fn new(settings: &AgentTaskingSettings, engine: Arc<IncidentResponseEngine>) -> SIEMResult<Self> {
+        if settings.host_id.is_empty() {
+            return Err(SIEMError::Config("Agent host_id is required".to_string()));
+        }
+        agent_subject(&settings.host_id)?;
+        if settings.trusted_keys.is_empty() {
+            return Err(SIEMError::Config("Agent has no trusted_keys; it would refuse every command".to_string()));
+        }
+        let trusted_keys = settings
+            .trusted_keys
+            .iter()
+            .map(|(id, key)| {
+                let key = VerifyingKey::from_bytes(&decode_key(key)?)
+                    .map_err(|_| SIEMError::Config(format!("Malformed trusted key '{}'", id)))?;
+                Ok((id.clone(), key))
+            })
+            .collect::<SIEMResult<_>>()?;
+        Ok(Self {
+            host_id: settings.host_id.clone(),
+            trusted_keys,
+            max_age: chrono::Duration::seconds(settings.max_command_age_seconds),
+            seen: Mutex::new((HashSet::new(), VecDeque::new())),
+            engine,
+        })
+    }
+
+    /// Check signature, addressee, age and uniqueness of a command
+    pub fn verify(&self, signed: &SignedAgentCommand, now: DateTime<Utc>) -> SIEMResult<AgentCommand> {
+        let command: AgentCommand = serde_json::from_str(&signed.command)?;
+        let key = self
+            .trusted_keys
+            .get(&command.key_id)
+            .ok_or_else(|| SIEMError::Auth(format!("Command signed by untrusted key '{}'", command.key_id)))?;
+        let mut signature = [0u8; 64];
+        let invalid = || SIEMError::Auth("Command signature is invalid".to_string());
+        if Base64::decode(&signed.signature, &mut signature).map_err(|_| invalid())?.len() != signature.len() {
+            return Err(invalid());
+        }
+        key.verify_strict(signed.command.as_bytes(), &Signature::from_bytes(&signature)).map_err(|_| invalid())?;
+
+        if command.host_id != self.host_id {
+            return Err(SIEMError::Auth(format!("Command addressed to {}, not {}", command.host_id, self.host_id)));
+        }
+        if now - command.issued_at > self.max_age || command.issued_at - now > self.max_age {
+            return Err(SIEMError::Auth(format!("Command issued at {} is outside the accepted window", command.issued_at)));
+        }
+        let mut seen = self.seen.lock().unwrap();
+        if !seen.0.insert(command.command_id.clone()) {
+            return Err(SIEMError::Auth(format!("Command {} was already executed", command.command_id)));
+        }
+        seen.1.push_back(command.command_id.clone());
+        if seen.1.len() > SEEN_CAPACITY {
+            if let Some(oldest) = seen.1.pop_front() {
+                seen.0.remove(&oldest);
+            }
+        }
+        Ok(command)
+    }
+
+    /// Verify and execute one request payload
+    pub async fn handle(&self, payload: &[u8]) -> AgentCommandResult {
+        let started = Instant::now();
+        let command = match serde_json::from_slice::<SignedAgentCommand>(payload)
+            .map_err(SIEMError::from)
+            .and_then(|signed| self.verify(&signed, Utc::now()))
+        {
+            Ok(command) => command,
+            Err(e) => {
+                warn!("⚠️ Refused agent command: {}", e);
+                return AgentCommandResult::failed("", &self.host_id, e);
+            }
+        };
+        let executed = match &command.action {
+            action @ (ResponseAction::CollectProcessList | ResponseAction::SnapshotFirewallRules) => playbook::collect_local(action).await.map(Some),
+            action if runs_on_agent(action) => self.engine.execute_agent_action(action).await.map(|_| None),
+            action => Err(SIEMError::Validation(format!("{:?} cannot run on an agent", action))),
+        };
+        info!("🛰️ Agent command {} for incident {}: {:?} -> {}", command.command_id, command.incident_id, command.action, executed.is_ok());
+        AgentCommandResult {
+            command_id: command.command_id,
+            host_id: self.host_id.clone(),
+            success: executed.is_ok(),
+            execution_time_ms: started.elapsed().as_millis() as u64,
+            completed_at: Utc::now(),
+            output: executed.as_ref().ok().cloned().flatten(),
+            error_message: executed.err().map(|e| e.to_string()),
+        }
+    }
+
+    /// Answer command requests for this host until the connection closes
+    pub async fn serve(self: Arc<Self>, client: async_nats::Client) -> SIEMResult<()> {
+        let subject = agent_subject(&self.host_id)?;
+        let mut subscriber = client.subscribe(subject.clone()).await?;
+        info!("🛰️ Agent {} waiting for commands on {}", self.host_id, subject);
+        while let Some(message) = subscriber.next().await {
+            let Some(reply) = message.reply else {
+                warn!("⚠️ Ignoring agent command without a reply subject");
+                continue;
+            };
+            let result = self.handle(&message.payload).await;
+            client.publish(reply, serde_json::to_vec(&result)?.into()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Generate a signing key: (base64 seed for the core, base64 public key for agents)
+pub fn generate_signing_key() -> (String, String) {
+    let mut seed = [0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut seed);
+    let key = SigningKey::from_bytes(&seed);
+    (encode(&seed), encode(key.verifying_key().as_bytes()))
+}
+
+fn agent_subject(host_id: &str) -> SIEMResult<String> {
+    if host_id.is_empty() || !host_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(SIEMError::Validation(format!("Invalid agent host id '{}'", host_id)));
+    }
+    Ok(SUBJECT_AGENT_COMMANDS.replace('*', host_id))
+}
+
+fn encode(bytes: &[u8]) -> String {
+    let mut buf = vec![0u8; Base64::encoded_len(bytes)];
+    Base64::encode(bytes, &mut buf).map(str::to_string).unwrap_or_default()
+}
+
+fn decode_key(value: &str) -> SIEMResult<[u8; 32]> {
+    let mut key = [0u8; 32];
+    let len = Base64::decode(value.trim(), &mut key)
+        .map_err(|_| SIEMError::Config("Malformed base64 Ed25519 key".to_string()))?
+        .len();
+    if len != key.len() {
+        return Err(SIEMError::Config("Ed25519 keys are 32 bytes".to_string()));
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::incident_response::{AlertConfig, SOARConfig};
+
+    /// Hands commands straight to an in-process agent
+    struct LocalTransport(Arc<AgentExecutor>);
+
+    impl AgentTransport for LocalTransport {
+        fn request<'a>(&'a self, host_id: &'a str, command: &'a SignedAgentCommand) -> BoxFuture<'a, SIEMResult<AgentCommandResult>> {
+            Box::pin(async move {
+                if host_id != self.0.host_id {
+                    return Err(SIEMError::Other(format!("no responders for {}", host_id)));
+                }
+                Ok(self.0.handle(&serde_json::to_vec(command)?).await)
+            })
+        }
+    }
+
+    fn agent(host_id: &str, trusted: &SigningKey) -> Arc<AgentExecutor> {
+        let settings = AgentTaskingSettings {
+            host_id: host_id.to_string(),
+            trusted_keys: HashMap::from([("core".to_string(), encode(trusted.verifying_key().as_bytes()))]),
+            ..Default::default()
+        };
+        let engine = Arc::new(IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled()));
+        Arc::new(AgentExecutor::new(&settings, engine).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_signed_command_runs_on_addressed_agent() {
+        let key = SigningKey::from_bytes(&[3u8; 32]);
+        let dispatcher = AgentDispatcher::new(Arc::new(LocalTransport(agent("wks-0317", &key))), key.clone(), "core");
+
+        let dir = std::env::temp_dir().join(format!("agent-quarantine-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dropper = dir.join("dropper.exe");
+        std::fs::write(&dropper, b"MZ").unwrap();
+        let hash = format!("agent-test-{}", uuid::Uuid::new_v4());
+        let quarantine = ResponseAction::QuarantineFile { file_path: dropper.display().to_string(), hash: hash.clone() };
+        let result = dispatcher.dispatch("wks-0317", "inc-1", &quarantine).await;
+        assert!(result.success, "{:?}", result.error_message);
+        assert!(!dropper.exists());
+        let _ = std::fs::remove_file(std::path::Path::new("/tmp/ultra_siem_quarantine").join(hash));
+
+        let unreachable = dispatcher.dispatch("db-01", "inc-1", &quarantine).await;
+        assert!(!unreachable.success);
+        assert!(unreachable.error_message.unwrap().contains("no responders"));
+
+        let script = ResponseAction::CustomScript { script_path: "/bin/true".to_string(), args: Vec::new() };
+        assert!(dispatcher.dispatch("wks-0317", "inc-1", &script).await.error_message.unwrap().contains("cannot run on an agent"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_agent_result_is_reported_onto_incident() {
+        use crate::advanced_threat_detection::AdvancedThreatResult;
+        use crate::incident_response::ResponseRule;
+
+        let key = SigningKey::from_bytes(&[3u8; 32]);
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        engine.set_agent_dispatcher(Arc::new(AgentDispatcher::new(Arc::new(LocalTransport(agent("wks-0317", &key))), key, "core")));
+        engine.add_response_rule(ResponseRule {
+            id: "kill_on_host".to_string(),
+            name: "Kill on host".to_string(),
+            description: String::new(),
+            enabled: true,
+            conditions: Vec::new(),
+            actions: vec![ResponseAction::RunOnAgent {
+                host_id: None,
+                action: Box::new(ResponseAction::KillProcess { process_id: 4_000_000, reason: "ransomware".to_string() }),
+            }],
+            priority: 1,
+            cooldown_seconds: 0,
+            last_triggered: None,
+        });
+        let mut threat = AdvancedThreatResult::default();
+        threat.details.insert("host".to_string(), "wks-0317".to_string());
+
+        let incident = engine.process_threat(threat).await.unwrap();
+        let pending = &incident.response_actions[0];
+        assert_eq!(pending.metadata["agent_status"], "pending");
+        assert!(matches!(&pending.action_type, ResponseAction::RunOnAgent { host_id: Some(host), .. } if host == "wks-0317"));
+
+        let mut reported = None;
+        for _ in 0..100 {
+            let incident = engine.get_incident(&incident.id).unwrap();
+            if incident.timeline.iter().any(|entry| entry.event == "agent_action") {
+                reported = Some(incident);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let result = &reported.expect("agent never reported back").response_actions[0];
+        // No such process on the agent
+        assert_eq!(result.metadata["agent_status"], "failed");
+        assert!(!result.success && result.error_message.is_some());
+    }
+
+    #[test]
+    fn test_agent_refuses_forged_misaddressed_stale_and_replayed_commands() {
+        let key = SigningKey::from_bytes(&[3u8; 32]);
+        let executor = agent("wks-0317", &key);
+        let dispatcher = AgentDispatcher::new(Arc::new(LocalTransport(executor.clone())), key, "core");
+        let action = ResponseAction::KillProcess { process_id: 4242, reason: "ransomware".to_string() };
+        let now = Utc::now();
+
+        let signed = dispatcher.sign("wks-0317", "inc-1", &action).unwrap();
+        assert_eq!(executor.verify(&signed, now).unwrap().action, action);
+        assert!(executor.verify(&signed, now).unwrap_err().to_string().contains("already executed"));
+
+        let forged = AgentDispatcher::new(Arc::new(LocalTransport(executor.clone())), SigningKey::from_bytes(&[4u8; 32]), "core");
+        let forged = forged.sign("wks-0317", "inc-1", &action).unwrap();
+        assert!(matches!(executor.verify(&forged, now), Err(SIEMError::Auth(_))));
+
+        let mut tampered = dispatcher.sign("wks-0317", "inc-1", &action).unwrap();
+        tampered.command = tampered.command.replace("4242", "1");
+        assert!(executor.verify(&tampered, now).is_err());
+
+        let elsewhere = dispatcher.sign("db-01", "inc-1", &action).unwrap();
+        assert!(executor.verify(&elsewhere, now).unwrap_err().to_string().contains("addressed to db-01"));
+
+        let stale = dispatcher.sign("wks-0317", "inc-1", &action).unwrap();
+        assert!(executor.verify(&stale, now + chrono::Duration::minutes(5)).is_err());
+        assert!(agent_subject("wks.*").is_err());
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::error_handling::time;
+
+/// Bucket that collects values beyond `max_keys_per_dimension`
+pub const OTHER_KEY: &str = "__other__";
+
+const DETECTION_DIMENSIONS: [&str; 2] = ["category", "severity"];
+/// Ids counted per rayon task before the partial histograms are merged
+const COUNT_CHUNK: usize = 4096;
+
+/// Rolling top-N statistics settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AggregationSettings {
+    pub enabled: bool,
+    pub window_seconds: u64,
+    pub bucket_seconds: u64,
+    pub top_n: usize,
+    /// Event fields counted per value; detections are counted by category and severity
+    pub event_fields: Vec<String>,
+    /// Distinct values tracked per dimension before the rest fold into `__other__`
+    pub max_keys_per_dimension: usize,
+    pub refresh_interval_ms: u64,
+}
+
+impl Default for AggregationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_seconds: 300,
+            bucket_seconds: 5,
+            top_n: 10,
+            event_fields: vec!["source_ip".to_string(), "destination_ip".to_string(), "user_id".to_string(), "source".to_string()],
+            max_keys_per_dimension: 50_000,
+            refresh_interval_ms: 500,
+        }
+    }
+}
+
+/// How the histograms were counted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregationBackend {
+    /// Chunked scalar counting on the rayon pool
+    #[default]
+    #[serde(alias = "simd")]
+    Parallel,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopEntry {
+    pub value: String,
+    pub count: u64,
+}
+
+/// Snapshot served by the stats API
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TopNStats {
+    pub generated_at: u64,
+    pub window_seconds: u64,
+    pub backend: AggregationBackend,
+    pub total_events: u64,
+    /// Events per bucket over the window, oldest first
+    pub events_histogram: Vec<u64>,
+    pub top: BTreeMap<String, Vec<TopEntry>>,
+}
+
+type Counts = HashMap<String, HashMap<String, u64>>;
+
+#[derive(Debug, Default)]
+struct Bucket {
+    start: u64,
+    events: u64,
+    counts: Counts,
+}
+
+#[derive(Debug, Default)]
+struct Pending {
+    samples: Vec<(u64, usize, String)>,
+    event_times: Vec<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    buckets: VecDeque<Bucket>,
+    totals: Counts,
+    backend: AggregationBackend,
+}
+
+/// Rolling per-dimension counters, batched into histograms on refresh
+#[derive(Debug)]
+pub struct TopNAggregator {
+    settings: AggregationSettings,
+    dimensions: Vec<String>,
+    pending: Mutex<Pending>,
+    window: Mutex<Window>,
+    snapshot: RwLock<TopNStats>,
+}
+
+impl TopNAggregator {
+    pub fn new(settings: AggregationSettings) -> Self {
+        let dimensions = settings
+            .event_fields
+            .iter()
+            .cloned()
+            .chain(DETECTION_DIMENSIONS.iter().map(|d| d.to_string()))
+            .collect();
+        Self {
+            settings,
+            dimensions,
+            pending: Mutex::new(Pending::default()),
+            window: Mutex::new(Window::default()),
+            snapshot: RwLock::new(TopNStats::default()),
+        }
+    }
+
+    /// Queue an ingested event; counted on the next refresh
+    pub fn record_event(&self, event: &serde_json::Value) {
+        let now = self.now();
+        let mut pending = self.pending.lock().unwrap();
+        pending.event_times.push(now);
+        for (index, field) in self.settings.event_fields.iter().enumerate() {
+            let value = match event.get(field) {
+                Some(serde_json::Value::String(value)) if !value.is_empty() => value.clone(),
+                Some(serde_json::Value::Number(value)) => value.to_string(),
+                _ => continue,
+            };
+            pending.samples.push((now, index, value));
+        }
+    }
+
+    pub fn record_detection(&self, threat: &AdvancedThreatResult) {
+        let now = self.now();
+        let offset = self.settings.event_fields.len();
+        let mut pending = self.pending.lock().unwrap();
+        pending.samples.push((now, offset, format!("{:?}", threat.category)));
+        pending.samples.push((now, offset + 1, format!("{:?}", threat.severity)));
+    }
+
+    /// Latest snapshot; refreshed every `refresh_interval_ms` by the refresh task
+    pub fn stats(&self) -> TopNStats {
+        self.snapshot.read().unwrap().clone()
+    }
+
+    /// Fold queued samples into the window, expire old buckets and rebuild the snapshot
+    pub fn refresh(&self, now: u64) -> TopNStats {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        let mut guard = self.window.lock().unwrap();
+        let window = &mut *guard;
+        let bucket_seconds = self.settings.bucket_seconds.max(1);
+        let oldest = now.saturating_sub(self.settings.window_seconds);
+
+        // Group samples by bucket and dimension, then count each group with one kernel launch
+        let mut groups: BTreeMap<(u64, usize), Vec<String>> = BTreeMap::new();
+        for (at, dimension, value) in pending.samples {
+            if at >= oldest {
+                groups.entry((at / bucket_seconds * bucket_seconds, dimension)).or_default().push(value);
+            }
+        }
+        for ((start, dimension), values) in groups {
+            let (keys, counts, backend) = self.histogram(values);
+            window.backend = backend;
+            let name = &self.dimensions[dimension];
+            for (key, count) in keys.into_iter().zip(counts) {
+                let totals = window.totals.entry(name.clone()).or_default();
+                let key = if totals.contains_key(&key) || totals.len() < self.settings.max_keys_per_dimension {
+                    key
+                } else {
+                    OTHER_KEY.to_string()
+                };
+                *totals.entry(key.clone()).or_insert(0) += count;
+                *bucket_mut(&mut window.buckets, start).counts.entry(name.clone()).or_default().entry(key).or_insert(0) += count;
+            }
+        }
+        for at in pending.event_times.into_iter().filter(|at| *at >= oldest) {
+            bucket_mut(&mut window.buckets, at / bucket_seconds * bucket_seconds).events += 1;
+        }
+
+        // Expire buckets that left the window
+        while window.buckets.front().is_some_and(|bucket| bucket.start + bucket_seconds <= oldest) {
+            let expired = window.buckets.pop_front().unwrap();
+            for (dimension, counts) in expired.counts {
+                let Some(totals) = window.totals.get_mut(&dimension) else { continue };
+                for (key, count) in counts {
+                    if let Some(total) = totals.get_mut(&key) {
+                        *total = total.saturating_sub(count);
+                        if *total == 0 {
+                            totals.remove(&key);
+                        }
+                    }
+                }
+            }
+        }
+
+        let stats = self.build_stats(window, now);
+        *self.snapshot.write().unwrap() = stats.clone();
+        stats
+    }
+
+    /// Refresh the snapshot every `refresh_interval_ms`
+    pub fn spawn_refresh_task(self: Arc<Self>) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.settings.enabled {
+            return None;
+        }
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(self.settings.refresh_interval_ms.max(50)));
+            loop {
+                interval.tick().await;
+                self.refresh(self.now());
+            }
+        }))
+    }
+
+    fn build_stats(&self, window: &Window, now: u64) -> TopNStats {
+        let bucket_seconds = self.settings.bucket_seconds.max(1);
+        let newest = now / bucket_seconds * bucket_seconds;
+        let slots = (self.settings.window_seconds / bucket_seconds).max(1);
+        let events_histogram: Vec<u64> = (0..slots)
+            .rev()
+            .map(|i| newest.saturating_sub(i * bucket_seconds))
+            .map(|start| window.buckets.iter().find(|b| b.start == start).map_or(0, |b| b.events))
+            .collect();
+
+        let top = self
+            .dimensions
+            .iter()
+            .map(|dimension| {
+                let mut entries: Vec<TopEntry> = window
+                    .totals
+                    .get(dimension)
+                    .map(|totals| totals.iter().map(|(value, count)| TopEntry { value: value.clone(), count: *count }).collect())
+                    .unwrap_or_default();
+                entries.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+                entries.truncate(self.settings.top_n);
+                (dimension.clone(), entries)
+            })
+            .collect();
+
+        TopNStats {
+            generated_at: now,
+            window_seconds: self.settings.window_seconds,
+            backend: window.backend,
+            total_events: window.buckets.iter().map(|bucket| bucket.events).sum(),
+            events_histogram,
+            top,
+        }
+    }
+
+    /// Intern `values` and count them in parallel chunks
+    fn histogram(&self, values: Vec<String>) -> (Vec<String>, Vec<u64>, AggregationBackend) {
+        let mut index: HashMap<String, u32> = HashMap::new();
+        let mut keys = Vec::new();
+        let ids: Vec<u32> = values
+            .into_iter()
+            .map(|value| {
+                *index.entry(value).or_insert_with_key(|value| {
+                    keys.push(value.clone());
+                    (keys.len() - 1) as u32
+                })
+            })
+            .collect();
+
+        let counts = parallel_histogram(&ids, keys.len());
+        (keys, counts, AggregationBackend::Parallel)
+    }
+
+    fn now(&self) -> u64 {
+        time::current_timestamp().unwrap_or_default()
+    }
+}
+
+fn bucket_mut(buckets: &mut VecDeque<Bucket>, start: u64) -> &mut Bucket {
+    let position = match buckets.binary_search_by_key(&start, |bucket| bucket.start) {
+        Ok(position) => position,
+        Err(position) => {
+            buckets.insert(position, Bucket { start, ..Default::default() });
+            position
+        }
+    };
+    &mut buckets[position]
+}
+
+/// Per-chunk local histograms merged in parallel
+fn parallel_histogram(ids: &[u32], bins: usize) -> Vec<u64> {
+    ids.par_chunks(COUNT_CHUNK)
+        .map(|chunk| {
+            let mut counts = vec![0u64; bins];
+            for id in chunk {
+                counts[*id as usize] += 1;
+            }
+            counts
+        })
+        .reduce(
+            || vec![0u64; bins],
+            |mut total, counts| {
+                for (total, count) in total.iter_mut().zip(counts) {
+                    *total += count;
+                }
+                total
+            },
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::threat_detection::ThreatCategory;
+
+    #[test]
+    fn test_top_sources_and_categories_over_window() {
+        let aggregator = TopNAggregator::new(AggregationSettings { top_n: 2, ..Default::default() });
+        for (ip, times) in [("10.0.0.1", 5), ("10.0.0.2", 3), ("10.0.0.3", 1)] {
+            for _ in 0..times {
+                aggregator.record_event(&serde_json::json!({ "source_ip": ip, "message": "x" }));
+            }
+        }
+        aggregator.record_detection(&AdvancedThreatResult { category: ThreatCategory::Malware, ..Default::default() });
+
+        let stats = aggregator.refresh(aggregator.now());
+        assert_eq!(stats.total_events, 9);
+        assert_eq!(stats.backend, AggregationBackend::Parallel);
+        assert_eq!(
+            stats.top["source_ip"],
+            vec![
+                TopEntry { value: "10.0.0.1".to_string(), count: 5 },
+                TopEntry { value: "10.0.0.2".to_string(), count: 3 },
+            ]
+        );
+        assert_eq!(stats.top["category"][0].value, "Malware");
+        assert_eq!(stats.events_histogram.iter().sum::<u64>(), 9);
+        assert_eq!(aggregator.stats().total_events, 9);
+
+        // Everything expires once the window has passed
+        let later = aggregator.refresh(aggregator.now() + 310);
+        assert_eq!(later.total_events, 0);
+        assert!(later.top["source_ip"].is_empty());
+    }
+
+    #[test]
+    fn test_distinct_values_beyond_cap_fold_into_other() {
+        let settings = AggregationSettings { max_keys_per_dimension: 2, ..Default::default() };
+        let aggregator = TopNAggregator::new(settings);
+        for user in ["alice", "bob", "carol", "dave", "alice"] {
+            aggregator.record_event(&serde_json::json!({ "user_id": user }));
+        }
+        let stats = aggregator.refresh(aggregator.now());
+        let users: HashMap<String, u64> = stats.top["user_id"].iter().map(|e| (e.value.clone(), e.count)).collect();
+        assert_eq!(users.len(), 3);
+        assert_eq!(users[OTHER_KEY], 2);
+        assert_eq!(users["alice"], 2);
+    }
+}
@@ -0,0 +1,320 @@
+//! # Anomaly Sensitivity Tuning
+//!
+//! `anomaly_sensitivity` is one z-score threshold for every data source, which
+//! floods on chatty sources and stays silent on quiet ones. The tuner keeps a
+//! threshold per data source (the event's `source`) and metric, starting from
+//! `anomaly_sensitivity`, and nudges it by `step` at most once per
+//! `adjust_interval_minutes`: up while the projected daily anomaly alerts exceed
+//! the source's budget, down while they stay under half of it. Thresholds are
+//! persisted so tuning survives restarts.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+
+const DAY_SECONDS: u64 = 86_400;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnomalyTuningSettings {
+    pub enabled: bool,
+    pub store_path: String,
+    /// Anomaly alerts per source and metric per day the tuner aims for
+    pub daily_alert_budget: u32,
+    /// Budget overrides per data source
+    pub source_budgets: BTreeMap<String, u32>,
+    /// Bounds of the tuned z-score threshold
+    pub min_sensitivity: f32,
+    pub max_sensitivity: f32,
+    /// Relative threshold change per adjustment
+    pub step: f32,
+    /// Minimum time between two adjustments of one source and metric
+    pub adjust_interval_minutes: u64,
+}
+
+impl Default for AnomalyTuningSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            store_path: "data/anomaly_tuning.json".to_string(),
+            daily_alert_budget: 5,
+            source_budgets: BTreeMap::new(),
+            min_sensitivity: 1.5,
+            max_sensitivity: 8.0,
+            step: 0.1,
+            adjust_interval_minutes: 60,
+        }
+    }
+}
+
+impl AnomalyTuningSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if !(self.min_sensitivity > 0.0 && self.min_sensitivity <= self.max_sensitivity) {
+            return Err(SIEMError::Validation("anomaly_tuning needs 0 < min_sensitivity <= max_sensitivity".to_string()));
+        }
+        if !(self.step > 0.0 && self.step <= 1.0) {
+            return Err(SIEMError::Validation("anomaly_tuning.step must be in (0, 1]".to_string()));
+        }
+        if self.adjust_interval_minutes == 0 {
+            return Err(SIEMError::Validation("anomaly_tuning.adjust_interval_minutes must be positive".to_string()));
+        }
+        if self.daily_alert_budget == 0 || self.source_budgets.values().any(|budget| *budget == 0) {
+            return Err(SIEMError::Validation("anomaly_tuning alert budgets must be positive".to_string()));
+        }
+        Ok(())
+    }
+
+    fn budget(&self, source: &str) -> u32 {
+        self.source_budgets.get(source).copied().unwrap_or(self.daily_alert_budget)
+    }
+}
+
+/// Persisted threshold of one source and metric
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredSensitivity {
+    source: String,
+    metric: String,
+    sensitivity: f32,
+    last_adjusted: u64,
+}
+
+/// Current effective sensitivity of one source and metric
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensitivityReport {
+    pub source: String,
+    pub metric: String,
+    /// Z-score threshold anomalies must exceed
+    pub sensitivity: f32,
+    pub alerts_last_day: usize,
+    pub daily_alert_budget: u32,
+    pub last_adjusted: u64,
+}
+
+#[derive(Debug)]
+struct Tuned {
+    sensitivity: f32,
+    /// Anomaly alert timestamps of the last day
+    alerts: VecDeque<u64>,
+    first_seen: u64,
+    last_adjusted: u64,
+}
+
+/// Per-source, per-metric anomaly thresholds tuned towards an alert budget
+#[derive(Debug)]
+pub struct AnomalyTuner {
+    settings: AnomalyTuningSettings,
+    base: f32,
+    path: PathBuf,
+    state: Mutex<HashMap<(String, String), Tuned>>,
+    adjustments: AtomicU64,
+}
+
+impl AnomalyTuner {
+    /// Open the tuner; untuned sources start at `base`, the global `anomaly_sensitivity`
+    pub fn open(settings: AnomalyTuningSettings, base: f32) -> SIEMResult<Self> {
+        settings.validate()?;
+        let path = PathBuf::from(&settings.store_path);
+        let stored: Vec<StoredSensitivity> = match std::fs::read(&path) {
+            Ok(raw) => serde_json::from_slice(&raw)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let state = stored
+            .into_iter()
+            .map(|s| {
+                let tuned = Tuned { sensitivity: s.sensitivity, alerts: VecDeque::new(), first_seen: s.last_adjusted, last_adjusted: s.last_adjusted };
+                ((s.source, s.metric), tuned)
+            })
+            .collect::<HashMap<_, _>>();
+        if !state.is_empty() {
+            info!("🎚️ Restored {} tuned anomaly threshold(s)", state.len());
+        }
+        let base = base.clamp(settings.min_sensitivity, settings.max_sensitivity);
+        Ok(Self { settings, base, path, state: Mutex::new(state), adjustments: AtomicU64::new(0) })
+    }
+
+    /// Threshold `metric` of `source` is scored against
+    pub fn sensitivity(&self, source: &str, metric: &str) -> f32 {
+        self.state
+            .lock()
+            .unwrap()
+            .get(&(source.to_string(), metric.to_string()))
+            .map(|tuned| tuned.sensitivity)
+            .unwrap_or(self.base)
+    }
+
+    /// Record one scored value and adjust the threshold when its interval is due
+    pub fn observe(&self, source: &str, metric: &str, alerted: bool, now: u64) {
+        let adjusted = {
+            let mut state = self.state.lock().unwrap();
+            let tuned = state.entry((source.to_string(), metric.to_string())).or_insert_with(|| Tuned {
+                sensitivity: self.base,
+                alerts: VecDeque::new(),
+                first_seen: now,
+                last_adjusted: now,
+            });
+            if alerted {
+                tuned.alerts.push_back(now);
+            }
+            while tuned.alerts.front().is_some_and(|t| *t + DAY_SECONDS <= now) {
+                tuned.alerts.pop_front();
+            }
+            let interval = self.settings.adjust_interval_minutes * 60;
+            if now < tuned.last_adjusted + interval {
+                return;
+            }
+            // Project the alerts seen so far onto a full day
+            let covered = now.saturating_sub(tuned.first_seen).clamp(interval, DAY_SECONDS) as f64;
+            let projected = tuned.alerts.len() as f64 * DAY_SECONDS as f64 / covered;
+            let budget = self.settings.budget(source) as f64;
+            let previous = tuned.sensitivity;
+            if projected > budget {
+                tuned.sensitivity *= 1.0 + self.settings.step;
+            } else if projected < budget / 2.0 {
+                tuned.sensitivity /= 1.0 + self.settings.step;
+            }
+            tuned.sensitivity = tuned.sensitivity.clamp(self.settings.min_sensitivity, self.settings.max_sensitivity);
+            tuned.last_adjusted = now;
+            (tuned.sensitivity != previous).then_some((previous, tuned.sensitivity, projected))
+        };
+        if let Some((previous, current, projected)) = adjusted {
+            self.adjustments.fetch_add(1, Ordering::Relaxed);
+            info!("🎚️ Anomaly sensitivity of {}/{}: {:.2} -> {:.2} ({:.1} alerts/day projected)", source, metric, previous, current, projected);
+            if let Err(e) = self.save() {
+                warn!("⚠️ Failed to persist anomaly thresholds: {}", e);
+            }
+        }
+    }
+
+    /// Effective sensitivity of every tuned source and metric
+    pub fn report(&self, now: u64) -> Vec<SensitivityReport> {
+        let state = self.state.lock().unwrap();
+        let mut report: Vec<SensitivityReport> = state
+            .iter()
+            .map(|((source, metric), tuned)| SensitivityReport {
+                source: source.clone(),
+                metric: metric.clone(),
+                sensitivity: tuned.sensitivity,
+                alerts_last_day: tuned.alerts.iter().filter(|t| **t + DAY_SECONDS > now).count(),
+                daily_alert_budget: self.settings.budget(source),
+                last_adjusted: tuned.last_adjusted,
+            })
+            .collect();
+        report.sort_by(|a, b| (&a.source, &a.metric).cmp(&(&b.source, &b.metric)));
+        report
+    }
+
+    fn save(&self) -> SIEMResult<()> {
+        let stored: Vec<StoredSensitivity> = self
+            .state
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((source, metric), tuned)| StoredSensitivity {
+                source: source.clone(),
+                metric: metric.clone(),
+                sensitivity: tuned.sensitivity,
+                last_adjusted: tuned.last_adjusted,
+            })
+            .collect();
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&stored)?)?;
+        std::fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        let state = self.state.lock().unwrap();
+        let (low, high) = state
+            .values()
+            .fold((f64::MAX, 0.0f64), |(low, high), t| (low.min(t.sensitivity as f64), high.max(t.sensitivity as f64)));
+        HashMap::from([
+            ("anomaly_tuning_series".to_string(), state.len() as f64),
+            ("anomaly_tuning_adjustments_total".to_string(), self.adjustments.load(Ordering::Relaxed) as f64),
+            ("anomaly_sensitivity_min".to_string(), if state.is_empty() { self.base as f64 } else { low }),
+            ("anomaly_sensitivity_max".to_string(), if state.is_empty() { self.base as f64 } else { high }),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(dir: &std::path::Path) -> AnomalyTuningSettings {
+        AnomalyTuningSettings {
+            enabled: true,
+            store_path: dir.join("tuning.json").to_string_lossy().to_string(),
+            source_budgets: BTreeMap::from([("firewall".to_string(), 50)]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_noisy_source_is_desensitized_gradually_within_bounds() {
+        let dir = std::env::temp_dir().join(format!("anomaly-tuning-{}", uuid::Uuid::new_v4()));
+        let tuner = AnomalyTuner::open(settings(&dir), 2.0).unwrap();
+        // One alert a minute: far over a budget of 5, so one step up per interval
+        for minute in 0..=60 {
+            tuner.observe("windows", "user_activity", true, 1_000 + minute * 60);
+        }
+        assert!((tuner.sensitivity("windows", "user_activity") - 2.2).abs() < 1e-5);
+        // Untouched pairs still use the global sensitivity
+        assert_eq!(tuner.sensitivity("windows", "time_of_day"), 2.0);
+
+        for minute in 61..=24 * 60 {
+            tuner.observe("windows", "user_activity", true, 1_000 + minute * 60);
+        }
+        assert_eq!(tuner.sensitivity("windows", "user_activity"), 8.0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_quiet_source_gains_sensitivity_and_budgets_are_per_source() {
+        let dir = std::env::temp_dir().join(format!("anomaly-tuning-{}", uuid::Uuid::new_v4()));
+        let tuner = AnomalyTuner::open(settings(&dir), 3.0).unwrap();
+        for hour in 0..=48 {
+            tuner.observe("dns", "time_of_day", false, hour * 3600);
+            // 24 alerts a day: over the default budget, within the firewall's 50
+            tuner.observe("firewall", "time_of_day", true, hour * 3600);
+            tuner.observe("proxy", "time_of_day", true, hour * 3600);
+        }
+        assert_eq!(tuner.sensitivity("dns", "time_of_day"), 1.5);
+        assert!(tuner.sensitivity("firewall", "time_of_day") < 3.0);
+        assert!(tuner.sensitivity("proxy", "time_of_day") > 3.0);
+
+        let report = tuner.report(48 * 3600);
+        let firewall = report.iter().find(|r| r.source == "firewall").unwrap();
+        assert_eq!((firewall.daily_alert_budget, firewall.alerts_last_day), (50, 24));
+        assert_eq!(tuner.get_metrics()["anomaly_sensitivity_min"], 1.5);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_thresholds_survive_restart() {
+        let dir = std::env::temp_dir().join(format!("anomaly-tuning-{}", uuid::Uuid::new_v4()));
+        let tuner = AnomalyTuner::open(settings(&dir), 2.0).unwrap();
+        for hour in 0..=6 {
+            tuner.observe("linux", "user_activity", hour > 0, hour * 3600);
+        }
+        let tuned = tuner.sensitivity("linux", "user_activity");
+        assert!(tuned > 2.0);
+        drop(tuner);
+
+        let reopened = AnomalyTuner::open(settings(&dir), 2.0).unwrap();
+        assert_eq!(reopened.sensitivity("linux", "user_activity"), tuned);
+        assert_eq!(reopened.report(6 * 3600)[0].alerts_last_day, 0);
+        let mut invalid = settings(&dir);
+        invalid.step = 0.0;
+        assert!(AnomalyTuner::open(invalid, 2.0).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::threat_detection::ThreatCategory;
+
+/// Detail key carrying the comma-separated ATT&CK techniques of a detection
+pub const ATTACK_DETAIL: &str = "attack_techniques";
+
+/// ATT&CK Enterprise techniques the built-in detectors and category defaults refer to
+const CATALOG: &[(&str, &str, &[&str])] = &[
+    ("T1003", "OS Credential Dumping", &["credential-access"]),
+    ("T1021", "Remote Services", &["lateral-movement"]),
+    ("T1027", "Obfuscated Files or Information", &["defense-evasion"]),
+    ("T1041", "Exfiltration Over C2 Channel", &["exfiltration"]),
+    ("T1046", "Network Service Discovery", &["discovery"]),
+    ("T1048", "Exfiltration Over Alternative Protocol", &["exfiltration"]),
+    ("T1059", "Command and Scripting Interpreter", &["execution"]),
+    ("T1068", "Exploitation for Privilege Escalation", &["privilege-escalation"]),
+    ("T1070", "Indicator Removal", &["defense-evasion"]),
+    ("T1071", "Application Layer Protocol", &["command-and-control"]),
+    ("T1078", "Valid Accounts", &["defense-evasion", "persistence", "privilege-escalation", "initial-access"]),
+    ("T1110", "Brute Force", &["credential-access"]),
+    ("T1189", "Drive-by Compromise", &["initial-access"]),
+    ("T1190", "Exploit Public-Facing Application", &["initial-access"]),
+    ("T1204", "User Execution", &["execution"]),
+    ("T1484", "Domain or Tenant Policy Modification", &["defense-evasion", "privilege-escalation"]),
+    ("T1486", "Data Encrypted for Impact", &["impact"]),
+    ("T1498", "Network Denial of Service", &["impact"]),
+    ("T1547", "Boot or Logon Autostart Execution", &["persistence", "privilege-escalation"]),
+    ("T1548", "Abuse Elevation Control Mechanism", &["privilege-escalation", "defense-evasion"]),
+    ("T1552", "Unsecured Credentials", &["credential-access"]),
+    ("T1562", "Impair Defenses", &["defense-evasion"]),
+    ("T1566", "Phishing", &["initial-access"]),
+    ("T1573", "Encrypted Channel", &["command-and-control"]),
+    ("T1583", "Acquire Infrastructure", &["resource-development"]),
+    ("T1587", "Develop Capabilities", &["resource-development"]),
+    ("T1588", "Obtain Capabilities", &["resource-development"]),
+];
+
+/// Techniques assumed for detections that carry no explicit tags
+pub fn category_techniques(category: &ThreatCategory) -> &'static [&'static str] {
+    match category {
+        ThreatCategory::Malware => &["T1204"],
+        ThreatCategory::Network => &["T1046"],
+        ThreatCategory::Authentication | ThreatCategory::InsiderThreat => &["T1078"],
+        ThreatCategory::SQLInjection => &["T1190"],
+        ThreatCategory::XSS => &["T1189"],
+        ThreatCategory::BruteForce => &["T1110"],
+        ThreatCategory::APT => &["T1071"],
+        ThreatCategory::DDoS => &["T1498"],
+        ThreatCategory::DataExfiltration => &["T1041"],
+        ThreatCategory::PrivilegeEscalation => &["T1068"],
+        ThreatCategory::LateralMovement => &["T1021"],
+        ThreatCategory::Persistence => &["T1547"],
+        ThreatCategory::Evasion => &["T1562"],
+        ThreatCategory::Compliance | ThreatCategory::Other => &[],
+    }
+}
+
+/// Detection details recording `techniques`, empty when there are none
+pub(crate) fn technique_details(techniques: &[String]) -> HashMap<String, String> {
+    let mut details = HashMap::new();
+    if !techniques.is_empty() {
+        details.insert(ATTACK_DETAIL.to_string(), techniques.join(","));
+    }
+    details
+}
+
+/// Techniques of a detection: its explicit tags, else the category default
+pub fn techniques_of(threat: &AdvancedThreatResult) -> Vec<String> {
+    match threat.details.get(ATTACK_DETAIL) {
+        Some(tags) => tags.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect(),
+        None => category_techniques(&threat.category).iter().map(|t| t.to_string()).collect(),
+    }
+}
+
+/// An enabled detection and the techniques it covers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectionEntry {
+    /// `<kind>:<id>`, e.g. `signature:sql_injection_1`
+    pub name: String,
+    pub techniques: Vec<String>,
+}
+
+/// Sample events exercising one technique
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttackFixture {
+    pub technique: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub events: Vec<serde_json::Value>,
+}
+
+/// Load every `*.json` fixture in `dir`; a missing directory has no fixtures
+pub fn load_fixtures<P: AsRef<Path>>(dir: P) -> SIEMResult<Vec<AttackFixture>> {
+    let dir = dir.as_ref();
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| {
+            serde_json::from_slice(&fs::read(path)?)
+                .map_err(|e| SIEMError::Validation(format!("{}: {}", path.display(), e)))
+        })
+        .collect()
+}
+
+/// Coverage of one technique
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TechniqueCoverage {
+    pub technique_id: String,
+    pub name: String,
+    pub tactics: Vec<String>,
+    pub detections: Vec<String>,
+    pub fixtures: usize,
+    /// Detections attributed to the technique within the report window
+    pub fired: usize,
+}
+
+impl TechniqueCoverage {
+    pub fn covered(&self) -> bool {
+        !self.detections.is_empty()
+    }
+
+    /// Covered, but with no fixture to prove the detections work
+    pub fn untested(&self) -> bool {
+        self.covered() && self.fixtures == 0
+    }
+}
+
+/// ATT&CK coverage of the enabled detections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoverageReport {
+    pub generated_at: DateTime<Utc>,
+    pub days: u32,
+    pub techniques: Vec<TechniqueCoverage>,
+}
+
+impl CoverageReport {
+    /// Combine the detection inventory, fixtures, and detections fired in the last `days`
+    pub fn build(
+        inventory: &[DetectionEntry],
+        fixtures: &[AttackFixture],
+        fired: &[AdvancedThreatResult],
+        days: u32,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let mut techniques: BTreeMap<String, TechniqueCoverage> = CATALOG
+            .iter()
+            .map(|(id, name, tactics)| {
+                let coverage = TechniqueCoverage {
+                    technique_id: id.to_string(),
+                    name: name.to_string(),
+                    tactics: tactics.iter().map(|t| t.to_string()).collect(),
+                    detections: Vec::new(),
+                    fixtures: 0,
+                    fired: 0,
+                };
+                (id.to_string(), coverage)
+            })
+            .collect();
+
+        for detection in inventory {
+            for technique in &detection.techniques {
+                technique_entry(&mut techniques, technique).detections.push(detection.name.clone());
+            }
+        }
+        for fixture in fixtures {
+            technique_entry(&mut techniques, &fixture.technique).fixtures += 1;
+        }
+        let since = now.timestamp().saturating_sub(days as i64 * 86_400).max(0) as u64;
+        for threat in fired.iter().filter(|threat| threat.timestamp >= since) {
+            for technique in techniques_of(threat) {
+                technique_entry(&mut techniques, &technique).fired += 1;
+            }
+        }
+
+        Self {
+            generated_at: now,
+            days,
+            techniques: techniques.into_values().collect(),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let covered = self.techniques.iter().filter(|t| t.covered()).count();
+        let mut out = format!(
+            "ATT&CK coverage: {}/{} techniques have an enabled detection\n",
+            covered,
+            self.techniques.len()
+        );
+        for technique in &self.techniques {
+            let status = if !technique.covered() {
+                "GAP"
+            } else if technique.untested() {
+                "untested"
+            } else {
+                "tested"
+            };
+            out.push_str(&format!(
+                "{:<10} {:<40} {:<9} detections={} fixtures={} fired({}d)={}\n",
+                technique.technique_id,
+                technique.name,
+                status,
+                technique.detections.len(),
+                technique.fixtures,
+                self.days,
+                technique.fired
+            ));
+        }
+        out
+    }
+
+    /// ATT&CK Navigator layer; score 0 = gap, 1 = untested, 2 = tested, 3 = fired in the window
+    pub fn navigator_layer(&self) -> serde_json::Value {
+        let mut entries = Vec::new();
+        for technique in &self.techniques {
+            let score = if technique.fired > 0 && technique.covered() {
+                3
+            } else if !technique.covered() {
+                0
+            } else if technique.untested() {
+                1
+            } else {
+                2
+            };
+            let comment = if technique.covered() {
+                format!("Detections: {}", technique.detections.join(", "))
+            } else {
+                "No enabled detection".to_string()
+            };
+            for tactic in &technique.tactics {
+                entries.push(serde_json::json!({
+                    "techniqueID": technique.technique_id,
+                    "tactic": tactic,
+                    "score": score,
+                    "comment": comment,
+                    "enabled": true,
+                    "metadata": [
+                        { "name": "fixtures", "value": technique.fixtures.to_string() },
+                        { "name": format!("fired_last_{}d", self.days), "value": technique.fired.to_string() }
+                    ]
+                }));
+            }
+        }
+        serde_json::json!({
+            "name": "Ultra SIEM detection coverage",
+            "versions": { "attack": "14", "navigator": "4.9.1", "layer": "4.5" },
+            "domain": "enterprise-attack",
+            "description": format!("Generated {} covering the last {} days", self.generated_at.to_rfc3339(), self.days),
+            "sorting": 0,
+            "hideDisabled": false,
+            "techniques": entries,
+            "gradient": { "colors": ["#ff6666", "#ffe766", "#8ec843", "#3182bd"], "minValue": 0, "maxValue": 3 },
+            "legendItems": [
+                { "label": "No detection", "color": "#ff6666" },
+                { "label": "Detection without fixture", "color": "#ffe766" },
+                { "label": "Detection with fixture", "color": "#8ec843" },
+                { "label": "Fired in window", "color": "#3182bd" }
+            ]
+        })
+    }
+}
+
+/// Detections behind the incidents kept in the persistent store, for the fired counts
+#[cfg(feature = "response")]
+pub async fn stored_detections(storage: &dyn crate::storage::Storage) -> SIEMResult<Vec<AdvancedThreatResult>> {
+    storage.prepare().await?;
+    Ok(storage.load_incidents().await?.into_iter().map(|incident| incident.threat_result).collect())
+}
+
+fn technique_entry<'a>(techniques: &'a mut BTreeMap<String, TechniqueCoverage>, id: &str) -> &'a mut TechniqueCoverage {
+    techniques.entry(id.to_string()).or_insert_with(|| TechniqueCoverage {
+        technique_id: id.to_string(),
+        name: id.to_string(),
+        tactics: Vec::new(),
+        detections: Vec::new(),
+        fixtures: 0,
+        fired: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::{AdvancedThreatConfig, AdvancedThreatDetectionEngine};
+
+    #[tokio::test]
+    async fn test_signature_detections_carry_technique_tags() {
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        let threats = engine
+            .process_event(serde_json::json!({
+                "source_ip": "203.0.113.9", "timestamp": 1_700_000_000u64,
+                "message": "GET /item?id=1 UNION SELECT password FROM users"
+            }))
+            .await
+            .unwrap();
+        let sql = threats.iter().find(|t| t.signatures.contains(&"sql_injection_1".to_string())).unwrap();
+        assert_eq!(techniques_of(sql), vec!["T1190".to_string()]);
+
+        let inventory = engine.attack_inventory();
+        assert!(inventory.iter().any(|d| d.name == "signature:brute_force_1" && d.techniques == vec!["T1110"]));
+        assert!(inventory.iter().any(|d| d.name == "correlation:data_exfiltration"));
+
+        let fixtures = load_fixtures(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/attack")).unwrap();
+        assert!(fixtures.iter().any(|f| f.technique == "T1190"));
+    }
+
+    #[test]
+    fn test_report_classifies_gaps_untested_and_fired() {
+        let now = Utc::now();
+        let inventory = vec![
+            DetectionEntry { name: "signature:a".to_string(), techniques: vec!["T1110".to_string()] },
+            DetectionEntry { name: "signature:b".to_string(), techniques: vec!["T1190".to_string()] },
+        ];
+        let fixtures = vec![AttackFixture {
+            technique: "T1110".to_string(),
+            name: "ssh".to_string(),
+            description: String::new(),
+            events: Vec::new(),
+        }];
+        let recent = AdvancedThreatResult {
+            timestamp: now.timestamp() as u64 - 3600,
+            category: ThreatCategory::BruteForce,
+            ..Default::default()
+        };
+        let stale = AdvancedThreatResult {
+            timestamp: now.timestamp() as u64 - 40 * 86_400,
+            category: ThreatCategory::SQLInjection,
+            ..Default::default()
+        };
+        let report = CoverageReport::build(&inventory, &fixtures, &[recent, stale], 30, now);
+        let get = |id: &str| report.techniques.iter().find(|t| t.technique_id == id).unwrap();
+
+        assert!(!get("T1110").untested());
+        assert_eq!(get("T1110").fired, 1);
+        assert!(get("T1190").untested());
+        assert_eq!(get("T1190").fired, 0);
+        assert!(!get("T1486").covered());
+
+        let layer = report.navigator_layer();
+        assert_eq!(layer["domain"], "enterprise-attack");
+        let brute = layer["techniques"].as_array().unwrap().iter().find(|t| t["techniqueID"] == "T1110").unwrap();
+        assert_eq!(brute["score"], 3);
+    }
+
+    #[cfg(feature = "response")]
+    #[tokio::test]
+    async fn test_fired_counts_come_from_stored_incidents() {
+        use crate::incident_response::{AlertConfig, IncidentResponseEngine, SOARConfig};
+        use crate::storage::{IncidentStorage, MemoryStorage, StorageBatch};
+
+        let now = Utc::now();
+        let incidents = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let mut details = HashMap::new();
+        details.insert(ATTACK_DETAIL.to_string(), "T1190".to_string());
+        let threat = AdvancedThreatResult {
+            timestamp: now.timestamp() as u64 - 3600,
+            category: ThreatCategory::SQLInjection,
+            details,
+            ..Default::default()
+        };
+        let storage = MemoryStorage::default();
+        let batch = StorageBatch { upserts: vec![incidents.process_threat(threat).await.unwrap()], ..Default::default() };
+        storage.commit(&batch).await.unwrap();
+
+        let fired = stored_detections(&storage).await.unwrap();
+        let inventory = vec![DetectionEntry { name: "signature:b".to_string(), techniques: vec!["T1190".to_string()] }];
+        let report = CoverageReport::build(&inventory, &[], &fired, 30, now);
+        assert_eq!(report.techniques.iter().find(|t| t.technique_id == "T1190").unwrap().fired, 1);
+    }
+}
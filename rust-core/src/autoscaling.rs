@@ -0,0 +1,380 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use async_nats::Client;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::subjects::{SchemaMode, SubjectPublisher, SUBJECT_SCALING_HINTS};
+
+/// Queue sampling and lag-based scaling hints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoscalingSettings {
+    pub enabled: bool,
+    pub interval_seconds: u64,
+    /// Names this instance in published hints; empty uses the host name
+    pub instance_id: String,
+    /// Stages whose lag drives the hint, `*` suffix for prefixes; the rest are only reported
+    pub scaling_stages: Vec<String>,
+    pub scale_up_lag_seconds: f64,
+    pub scale_down_lag_seconds: f64,
+    /// Consecutive samples past a threshold before the hint changes
+    pub sustain_intervals: u32,
+    /// Worker instances currently deployed
+    pub instances: u32,
+    pub min_instances: u32,
+    pub max_instances: u32,
+    /// Weight of the newest sample in the smoothed processing rate
+    pub rate_smoothing: f64,
+    /// Publish each hint on `ultra_siem.scaling.hints`
+    pub publish: bool,
+}
+
+impl Default for AutoscalingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_seconds: 15,
+            instance_id: String::new(),
+            scaling_stages: vec!["detection".to_string()],
+            scale_up_lag_seconds: 30.0,
+            scale_down_lag_seconds: 2.0,
+            sustain_intervals: 3,
+            instances: 1,
+            min_instances: 1,
+            max_instances: 8,
+            rate_smoothing: 0.3,
+            publish: true,
+        }
+    }
+}
+
+impl AutoscalingSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.interval_seconds == 0 {
+            return Err(SIEMError::Validation("autoscaling.interval_seconds must be positive".to_string()));
+        }
+        if self.scale_down_lag_seconds < 0.0 || self.scale_down_lag_seconds >= self.scale_up_lag_seconds {
+            return Err(SIEMError::Validation("autoscaling.scale_down_lag_seconds must be below scale_up_lag_seconds".to_string()));
+        }
+        if self.min_instances == 0 || self.min_instances > self.max_instances {
+            return Err(SIEMError::Validation("autoscaling needs 1 <= min_instances <= max_instances".to_string()));
+        }
+        if !(self.min_instances..=self.max_instances).contains(&self.instances) {
+            return Err(SIEMError::Validation("autoscaling.instances must be within min_instances..max_instances".to_string()));
+        }
+        if !(self.rate_smoothing > 0.0 && self.rate_smoothing <= 1.0) {
+            return Err(SIEMError::Validation("autoscaling.rate_smoothing must be in (0, 1]".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Depth and cumulative throughput of one pipeline queue
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueSample {
+    /// Items waiting or in flight
+    pub depth: u64,
+    /// Items taken off the queue since start
+    pub processed: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleDirection {
+    Up,
+    Hold,
+    Down,
+}
+
+impl ScaleDirection {
+    fn metric(&self) -> f64 {
+        match self {
+            ScaleDirection::Up => 1.0,
+            ScaleDirection::Hold => 0.0,
+            ScaleDirection::Down => -1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageStatus {
+    pub stage: String,
+    pub depth: u64,
+    pub lag_seconds: f64,
+    pub rate_per_second: f64,
+    /// Whether this stage's lag drives the hint
+    pub scaling: bool,
+}
+
+/// Published on `ultra_siem.scaling.hints` for the supervisor or an HPA adapter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScalingHint {
+    pub timestamp: u64,
+    pub instance: String,
+    pub hint: ScaleDirection,
+    pub current_instances: u32,
+    pub desired_instances: u32,
+    /// Highest lag among the scaling stages
+    pub lag_seconds: f64,
+    pub reason: String,
+    pub stages: Vec<StageStatus>,
+}
+
+#[derive(Debug, Default)]
+struct StageTracker {
+    last: Option<(QueueSample, u64)>,
+    rate: Option<f64>,
+    /// When the queue last went from empty to non-empty
+    backlog_since_ms: Option<u64>,
+    status: Option<StageStatus>,
+}
+
+#[derive(Debug, Default)]
+struct MonitorState {
+    stages: BTreeMap<String, StageTracker>,
+    up_streak: u32,
+    down_streak: u32,
+    latest: Option<ScalingHint>,
+}
+
+type Probe = Box<dyn Fn() -> QueueSample + Send + Sync>;
+
+/// Samples registered pipeline queues and turns their lag into scale up/down hints
+pub struct QueueMonitor {
+    settings: AutoscalingSettings,
+    instance: String,
+    probes: RwLock<Vec<(String, Probe)>>,
+    state: Mutex<MonitorState>,
+}
+
+impl std::fmt::Debug for QueueMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueueMonitor")
+            .field("stages", &self.probes.read().unwrap().iter().map(|(name, _)| name.clone()).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+impl QueueMonitor {
+    pub fn new(settings: AutoscalingSettings) -> Self {
+        let instance = match settings.instance_id.trim() {
+            "" => std::env::var("HOSTNAME").unwrap_or_else(|_| "ultra-siem-core".to_string()),
+            id => id.to_string(),
+        };
+        Self { settings, instance, probes: RwLock::new(Vec::new()), state: Mutex::new(MonitorState::default()) }
+    }
+
+    pub fn settings(&self) -> &AutoscalingSettings {
+        &self.settings
+    }
+
+    /// Sample `probe` as `stage` from the next interval on
+    pub fn register(&self, stage: impl Into<String>, probe: impl Fn() -> QueueSample + Send + Sync + 'static) {
+        self.probes.write().unwrap().push((stage.into(), Box::new(probe)));
+    }
+
+    fn drives_scaling(&self, stage: &str) -> bool {
+        self.settings.scaling_stages.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => stage.starts_with(prefix),
+            None => stage == pattern,
+        })
+    }
+
+    /// Sample every queue at `now_ms` and derive the scaling hint
+    pub fn sample_at(&self, now_ms: u64) -> ScalingHint {
+        let samples: Vec<(String, QueueSample)> = self.probes.read().unwrap().iter().map(|(stage, probe)| (stage.clone(), probe())).collect();
+        let mut state = self.state.lock().unwrap();
+        let mut stages = Vec::with_capacity(samples.len());
+        for (stage, sample) in samples {
+            let tracker = state.stages.entry(stage.clone()).or_default();
+            if let Some((last, last_ms)) = tracker.last.filter(|(_, last_ms)| now_ms > *last_ms) {
+                let elapsed = (now_ms - last_ms) as f64 / 1000.0;
+                let rate = sample.processed.saturating_sub(last.processed) as f64 / elapsed;
+                let alpha = self.settings.rate_smoothing;
+                tracker.rate = Some(tracker.rate.map_or(rate, |smoothed| alpha * rate + (1.0 - alpha) * smoothed));
+            }
+            tracker.last = Some((sample, now_ms));
+            let lag_seconds = if sample.depth == 0 {
+                tracker.backlog_since_ms = None;
+                0.0
+            } else {
+                let since = *tracker.backlog_since_ms.get_or_insert(now_ms);
+                match tracker.rate.filter(|rate| *rate > 0.0) {
+                    Some(rate) => sample.depth as f64 / rate,
+                    // Nothing drained yet: the backlog is at least as old as its first sighting
+                    None => now_ms.saturating_sub(since) as f64 / 1000.0,
+                }
+            };
+            let status = StageStatus {
+                scaling: self.drives_scaling(&stage),
+                stage,
+                depth: sample.depth,
+                lag_seconds,
+                rate_per_second: tracker.rate.unwrap_or(0.0),
+            };
+            tracker.status = Some(status.clone());
+            stages.push(status);
+        }
+
+        let lagging = stages.iter().filter(|s| s.scaling).max_by(|a, b| a.lag_seconds.total_cmp(&b.lag_seconds));
+        let lag_seconds = lagging.map_or(0.0, |s| s.lag_seconds);
+        if lag_seconds >= self.settings.scale_up_lag_seconds {
+            state.up_streak += 1;
+            state.down_streak = 0;
+        } else if lag_seconds <= self.settings.scale_down_lag_seconds {
+            state.down_streak += 1;
+            state.up_streak = 0;
+        } else {
+            state.up_streak = 0;
+            state.down_streak = 0;
+        }
+        let current = self.settings.instances;
+        let sustain = self.settings.sustain_intervals.max(1);
+        let (hint, desired, reason) = if state.up_streak >= sustain && current < self.settings.max_instances {
+            // Enough instances to bring the lag back under the threshold at the current per-instance rate
+            let needed = (current as f64 * lag_seconds / self.settings.scale_up_lag_seconds).ceil() as u32;
+            let desired = needed.clamp(current + 1, self.settings.max_instances);
+            let stage = lagging.map(|s| s.stage.as_str()).unwrap_or_default();
+            (ScaleDirection::Up, desired, format!("{} lag {:.1}s over {:.0}s for {} samples", stage, lag_seconds, self.settings.scale_up_lag_seconds, state.up_streak))
+        } else if state.down_streak >= sustain && current > self.settings.min_instances {
+            (ScaleDirection::Down, current - 1, format!("lag under {:.0}s for {} samples", self.settings.scale_down_lag_seconds, state.down_streak))
+        } else {
+            (ScaleDirection::Hold, current, format!("lag {:.1}s", lag_seconds))
+        };
+        let hint = ScalingHint {
+            timestamp: now_ms / 1000,
+            instance: self.instance.clone(),
+            hint,
+            current_instances: current,
+            desired_instances: desired,
+            lag_seconds,
+            reason,
+            stages,
+        };
+        state.latest = Some(hint.clone());
+        hint
+    }
+
+    pub fn sample(&self) -> ScalingHint {
+        self.sample_at(now_ms())
+    }
+
+    /// The hint of the last sample
+    pub fn latest(&self) -> Option<ScalingHint> {
+        self.state.lock().unwrap().latest.clone()
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        let state = self.state.lock().unwrap();
+        let mut metrics = HashMap::new();
+        for status in state.stages.values().filter_map(|tracker| tracker.status.as_ref()) {
+            metrics.insert(format!("queue_{}_depth", status.stage), status.depth as f64);
+            metrics.insert(format!("queue_{}_lag_seconds", status.stage), status.lag_seconds);
+            metrics.insert(format!("queue_{}_rate_per_second", status.stage), status.rate_per_second);
+        }
+        if let Some(hint) = &state.latest {
+            metrics.insert("autoscaling_hint".to_string(), hint.hint.metric());
+            metrics.insert("autoscaling_desired_instances".to_string(), hint.desired_instances as f64);
+        }
+        metrics
+    }
+
+    /// Sample on the configured interval and publish hints when a NATS client is given
+    pub fn spawn(self: Arc<Self>, client: Option<Client>) -> tokio::task::JoinHandle<()> {
+        let publisher = client.filter(|_| self.settings.publish).map(|client| SubjectPublisher::new(client, SchemaMode::default()));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(self.settings.interval_seconds.max(1)));
+            let mut last = ScaleDirection::Hold;
+            loop {
+                interval.tick().await;
+                let hint = self.sample();
+                if hint.hint != last {
+                    info!("📈 Scaling hint {:?}: {} -> {} instances ({})", hint.hint, hint.current_instances, hint.desired_instances, hint.reason);
+                    last = hint.hint;
+                }
+                if let Some(publisher) = &publisher {
+                    if let Err(e) = publisher.publish(SUBJECT_SCALING_HINTS, &hint).await {
+                        warn!("⚠️ Scaling hint not published: {}", e);
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use crate::subjects;
+
+    fn monitor(settings: AutoscalingSettings) -> (QueueMonitor, Arc<(AtomicU64, AtomicU64)>) {
+        let queue = Arc::new((AtomicU64::new(0), AtomicU64::new(0)));
+        let monitor = QueueMonitor::new(AutoscalingSettings { instance_id: "core-1".to_string(), ..settings });
+        let probe = queue.clone();
+        monitor.register("detection", move || QueueSample { depth: probe.0.load(Ordering::Relaxed), processed: probe.1.load(Ordering::Relaxed) });
+        (monitor, queue)
+    }
+
+    #[test]
+    fn test_lag_from_depth_and_rate() {
+        let (monitor, queue) = monitor(AutoscalingSettings { rate_smoothing: 1.0, ..Default::default() });
+        monitor.register("sink_loki", || QueueSample { depth: 500, processed: 0 });
+        monitor.sample_at(0);
+        // 1000 events drained in 10s with 2000 waiting: 200s of lag
+        queue.0.store(2000, Ordering::Relaxed);
+        queue.1.store(1000, Ordering::Relaxed);
+        let hint = monitor.sample_at(10_000);
+        let detection = &hint.stages[0];
+        assert_eq!(detection.rate_per_second, 100.0);
+        assert_eq!(detection.lag_seconds, 20.0);
+        assert!(detection.scaling);
+        // A stalled queue lags by the age of its backlog and is reported without driving the hint
+        let sink = &hint.stages[1];
+        assert_eq!(sink.lag_seconds, 10.0);
+        assert!(!sink.scaling);
+        assert_eq!(monitor.get_metrics()["queue_detection_lag_seconds"], 20.0);
+        assert_eq!(subjects::validate(SUBJECT_SCALING_HINTS, &serde_json::to_value(&hint).unwrap()).unwrap().name, "scaling.hint");
+    }
+
+    #[test]
+    fn test_sustained_lag_scales_up_and_idle_scales_down() {
+        let settings = AutoscalingSettings { instances: 2, rate_smoothing: 1.0, sustain_intervals: 2, ..Default::default() };
+        let (monitor, queue) = monitor(settings);
+        monitor.sample_at(0);
+        // 10 events/s against 900 waiting: 90s lag, three times the threshold
+        queue.0.store(900, Ordering::Relaxed);
+        queue.1.store(100, Ordering::Relaxed);
+        assert_eq!(monitor.sample_at(10_000).hint, ScaleDirection::Hold);
+        queue.1.store(200, Ordering::Relaxed);
+        let hint = monitor.sample_at(20_000);
+        assert_eq!(hint.hint, ScaleDirection::Up);
+        assert_eq!(hint.desired_instances, 6);
+        assert!(hint.reason.starts_with("detection lag 90.0s"));
+
+        queue.0.store(0, Ordering::Relaxed);
+        assert_eq!(monitor.sample_at(30_000).hint, ScaleDirection::Hold);
+        let hint = monitor.sample_at(40_000);
+        assert_eq!(hint.hint, ScaleDirection::Down);
+        assert_eq!(hint.desired_instances, 1);
+        assert_eq!(monitor.get_metrics()["autoscaling_hint"], -1.0);
+    }
+
+    #[test]
+    fn test_settings_validation() {
+        assert!(AutoscalingSettings::default().validate().is_ok());
+        assert!(AutoscalingSettings { scale_down_lag_seconds: 60.0, ..Default::default() }.validate().is_err());
+        assert!(AutoscalingSettings { instances: 9, ..Default::default() }.validate().is_err());
+        assert!(AutoscalingSettings { min_instances: 0, ..Default::default() }.validate().is_err());
+    }
+}
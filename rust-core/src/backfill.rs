@@ -0,0 +1,443 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "chaos")]
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use async_nats::Client;
+use flate2::read::GzDecoder;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::{AdvancedThreatDetectionEngine, AdvancedThreatResult};
+#[cfg(feature = "chaos")]
+use crate::chaos::FaultInjector;
+use crate::bridge_contract::{self, BridgeThreatEvent};
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// Historical import settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackfillSettings {
+    /// Maximum replay rate; 0 disables throttling
+    pub events_per_second: u32,
+    pub recursive: bool,
+    /// Tag every detection with `details["backfill"] = "true"`
+    pub tag_detections: bool,
+}
+
+impl Default for BackfillSettings {
+    fn default() -> Self {
+        Self {
+            events_per_second: 5_000,
+            recursive: true,
+            tag_detections: true,
+        }
+    }
+}
+
+/// Archive layouts recognised by file extension
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    JsonLines,
+    GzipJsonLines,
+    Csv,
+    GzipCsv,
+    Evtx,
+}
+
+impl ArchiveFormat {
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        let format = if name.ends_with(".jsonl.gz") || name.ends_with(".ndjson.gz") || name.ends_with(".json.gz") {
+            ArchiveFormat::GzipJsonLines
+        } else if name.ends_with(".jsonl") || name.ends_with(".ndjson") || name.ends_with(".json") {
+            ArchiveFormat::JsonLines
+        } else if name.ends_with(".csv.gz") {
+            ArchiveFormat::GzipCsv
+        } else if name.ends_with(".csv") {
+            ArchiveFormat::Csv
+        } else if name.ends_with(".evtx") {
+            ArchiveFormat::Evtx
+        } else {
+            return None;
+        };
+        Some(format)
+    }
+}
+
+type Records = Box<dyn Iterator<Item = SIEMResult<serde_json::Value>> + Send>;
+
+/// Stream the records of an archive as JSON objects
+pub fn read_archive(path: &Path, format: ArchiveFormat) -> SIEMResult<Records> {
+    let file = File::open(path)?;
+    match format {
+        ArchiveFormat::JsonLines => Ok(read_json_lines(BufReader::new(file))),
+        ArchiveFormat::GzipJsonLines => Ok(read_json_lines(BufReader::new(GzDecoder::new(file)))),
+        ArchiveFormat::Csv => read_csv(file),
+        ArchiveFormat::GzipCsv => read_csv(GzDecoder::new(file)),
+        #[cfg(feature = "evtx")]
+        ArchiveFormat::Evtx => Ok(Box::new(crate::evtx_import::read_evtx(path)?.into_iter())),
+        #[cfg(not(feature = "evtx"))]
+        ArchiveFormat::Evtx => Err(SIEMError::Validation(format!(
+            "{}: built without the `evtx` feature; export the log to JSONL first",
+            path.display()
+        ))),
+    }
+}
+
+fn read_json_lines<R: BufRead + Send + 'static>(reader: R) -> Records {
+    Box::new(
+        reader
+            .lines()
+            .filter(|line| !matches!(line, Ok(text) if text.trim().is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?)),
+    )
+}
+
+fn read_csv<R: Read + Send + 'static>(reader: R) -> SIEMResult<Records> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader
+        .headers()
+        .map_err(|e| SIEMError::Validation(format!("Invalid CSV header: {}", e)))?
+        .clone();
+    Ok(Box::new(reader.into_records().map(move |record| {
+        let record = record.map_err(|e| SIEMError::Validation(format!("Invalid CSV row: {}", e)))?;
+        let mut fields = serde_json::Map::new();
+        for (name, value) in headers.iter().zip(record.iter()) {
+            // Keep numeric epoch timestamps numeric so detection sees the original time
+            let value = match (name, value.parse::<u64>()) {
+                ("timestamp", Ok(seconds)) => seconds.into(),
+                _ => value.into(),
+            };
+            fields.insert(name.to_string(), value);
+        }
+        Ok(serde_json::Value::Object(fields))
+    })))
+}
+
+/// Destination for detections raised during a backfill
+pub enum BackfillSink {
+    /// Publish on the bridge threat subject, where the Go bridge writes them to ClickHouse
+    Nats(Client),
+    /// Append detections as JSON lines
+    JsonLines(BufWriter<File>),
+    /// Count detections without writing them
+    DryRun,
+}
+
+impl BackfillSink {
+    pub fn json_lines<P: AsRef<Path>>(path: P) -> SIEMResult<Self> {
+        Ok(BackfillSink::JsonLines(BufWriter::new(File::create(path)?)))
+    }
+
+    async fn write(&mut self, threat: &AdvancedThreatResult) -> SIEMResult<()> {
+        match self {
+            BackfillSink::Nats(client) => bridge_contract::publish_threat(client, &BridgeThreatEvent::from(threat)).await,
+            BackfillSink::JsonLines(writer) => {
+                serde_json::to_writer(&mut *writer, threat)?;
+                writer.write_all(b"\n")?;
+                Ok(())
+            }
+            BackfillSink::DryRun => Ok(()),
+        }
+    }
+
+    async fn flush(&mut self) -> SIEMResult<()> {
+        match self {
+            BackfillSink::Nats(client) => client
+                .flush()
+                .await
+                .map_err(|e| SIEMError::Other(format!("NATS flush failed: {}", e))),
+            BackfillSink::JsonLines(writer) => Ok(writer.flush()?),
+            BackfillSink::DryRun => Ok(()),
+        }
+    }
+}
+
+/// Outcome of a backfill run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackfillReport {
+    pub files_processed: u64,
+    pub files_skipped: u64,
+    pub events_read: u64,
+    pub events_rejected: u64,
+    pub detections: u64,
+    pub elapsed_seconds: f64,
+}
+
+/// Position of a run, kept so a restarted run resumes instead of starting over
+#[derive(Debug, Default)]
+struct BackfillProgress {
+    report: BackfillReport,
+    files_done: usize,
+    records_done: usize,
+    started: Option<Instant>,
+}
+
+/// Replays historical archives through parsing and detection
+pub struct BackfillImporter {
+    settings: BackfillSettings,
+    engine: AdvancedThreatDetectionEngine,
+    sink: BackfillSink,
+    progress: BackfillProgress,
+    #[cfg(feature = "chaos")]
+    chaos: Option<FaultInjector>,
+}
+
+impl BackfillImporter {
+    /// `engine` should be built without timestamp normalization so original event times are kept
+    pub fn new(settings: BackfillSettings, engine: AdvancedThreatDetectionEngine, sink: BackfillSink) -> Self {
+        Self {
+            settings,
+            engine,
+            sink,
+            progress: BackfillProgress::default(),
+            #[cfg(feature = "chaos")]
+            chaos: None,
+        }
+    }
+
+    /// Delay sink writes, drop NATS publishes and panic between records for resilience testing
+    #[cfg(feature = "chaos")]
+    pub fn set_fault_injector(&mut self, injector: FaultInjector) {
+        self.chaos = injector.settings().enabled.then_some(injector);
+    }
+
+    /// Import every recognised archive below `directory`, oldest file name first
+    ///
+    /// A run interrupted by a panic resumes at the record it stopped on when called again.
+    pub async fn run<P: AsRef<Path>>(&mut self, directory: P) -> SIEMResult<BackfillReport> {
+        let start = *self.progress.started.get_or_insert_with(Instant::now);
+        let files = collect_files(directory.as_ref(), self.settings.recursive)?;
+        info!("📦 Backfilling {} archive(s) from {}", files.len(), directory.as_ref().display());
+
+        for path in files.into_iter().skip(self.progress.files_done) {
+            let Some(format) = ArchiveFormat::detect(&path) else {
+                self.progress.report.files_skipped += 1;
+                self.progress.files_done += 1;
+                continue;
+            };
+            let records = match read_archive(&path, format) {
+                Ok(records) => records,
+                Err(e) => {
+                    warn!("⚠️ Skipping {}: {}", path.display(), e);
+                    self.progress.report.files_skipped += 1;
+                    self.progress.files_done += 1;
+                    continue;
+                }
+            };
+
+            for record in records.skip(self.progress.records_done) {
+                #[cfg(feature = "chaos")]
+                if let Some(chaos) = &self.chaos {
+                    chaos.maybe_panic("backfill");
+                }
+                self.progress.report.events_read += 1;
+                match record {
+                    Ok(event) => {
+                        for mut threat in self.engine.process_event(event).await? {
+                            if self.settings.tag_detections {
+                                threat.details.insert("backfill".to_string(), "true".to_string());
+                            }
+                            self.deliver(&threat).await?;
+                            self.progress.report.detections += 1;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("⚠️ Rejected record in {}: {}", path.display(), e);
+                        self.progress.report.events_rejected += 1;
+                    }
+                }
+                self.progress.records_done += 1;
+                self.throttle(start, self.progress.report.events_read).await;
+            }
+            self.progress.report.files_processed += 1;
+            self.progress.files_done += 1;
+            self.progress.records_done = 0;
+        }
+
+        self.sink.flush().await?;
+        let mut report = std::mem::take(&mut self.progress).report;
+        report.elapsed_seconds = start.elapsed().as_secs_f64();
+        info!(
+            "✅ Backfill complete: {} events, {} detections in {:.1}s",
+            report.events_read, report.detections, report.elapsed_seconds
+        );
+        Ok(report)
+    }
+
+    /// Run as a supervised task that resumes after injected panics; plain `run` without fault injection
+    #[cfg(feature = "chaos")]
+    pub async fn run_supervised(mut self, directory: PathBuf) -> SIEMResult<BackfillReport> {
+        let Some(chaos) = self.chaos.clone() else {
+            return self.run(directory).await;
+        };
+        let importer = Arc::new(tokio::sync::Mutex::new(self));
+        let outcome = Arc::new(Mutex::new(None));
+        let (task_importer, task_outcome) = (importer.clone(), outcome.clone());
+        chaos
+            .spawn_supervised("backfill", move || {
+                let (importer, outcome, directory) = (task_importer.clone(), task_outcome.clone(), directory.clone());
+                async move {
+                    let result = importer.lock().await.run(&directory).await;
+                    *outcome.lock().unwrap() = Some(result);
+                }
+            })
+            .await
+            .map_err(|e| SIEMError::Other(format!("Backfill task failed: {}", e)))?;
+        let result = outcome.lock().unwrap().take();
+        result.unwrap_or_else(|| Err(SIEMError::Other("Backfill task ended without a report".to_string())))
+    }
+
+    #[cfg(not(feature = "chaos"))]
+    async fn deliver(&mut self, threat: &AdvancedThreatResult) -> SIEMResult<()> {
+        self.sink.write(threat).await
+    }
+
+    /// Write through injected faults, retrying publishes that failed on a dropped connection
+    #[cfg(feature = "chaos")]
+    async fn deliver(&mut self, threat: &AdvancedThreatResult) -> SIEMResult<()> {
+        let Some(chaos) = self.chaos.clone() else {
+            return self.sink.write(threat).await;
+        };
+        chaos.delay_sink_write().await;
+        let mut attempt = 0u64;
+        loop {
+            let injected = match self.sink {
+                BackfillSink::Nats(_) => chaos.nats_fault(),
+                _ => Ok(()),
+            };
+            match injected {
+                Ok(()) => return self.sink.write(threat).await,
+                Err(e) if attempt < 5 => {
+                    attempt += 1;
+                    warn!("⚠️ {}; retrying publish (attempt {})", e, attempt);
+                    tokio::time::sleep(Duration::from_millis(50 * attempt)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn throttle(&self, start: Instant, events: u64) {
+        if self.settings.events_per_second == 0 {
+            return;
+        }
+        let due = Duration::from_secs_f64(events as f64 / self.settings.events_per_second as f64);
+        if let Some(wait) = due.checked_sub(start.elapsed()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+fn collect_files(directory: &Path, recursive: bool) -> SIEMResult<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                files.extend(collect_files(&path, recursive)?);
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use crate::advanced_threat_detection::AdvancedThreatConfig;
+
+    #[test]
+    fn test_detect_archive_format() {
+        assert_eq!(ArchiveFormat::detect(Path::new("2023/app.jsonl.gz")), Some(ArchiveFormat::GzipJsonLines));
+        assert_eq!(ArchiveFormat::detect(Path::new("auth.CSV")), Some(ArchiveFormat::Csv));
+        assert_eq!(ArchiveFormat::detect(Path::new("Security.evtx")), Some(ArchiveFormat::Evtx));
+        assert_eq!(ArchiveFormat::detect(Path::new("README.md")), None);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_replays_archives_with_original_timestamps() {
+        let directory = std::env::temp_dir().join(format!("backfill-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let mut gzip = GzEncoder::new(File::create(directory.join("web.jsonl.gz")).unwrap(), Compression::default());
+        writeln!(gzip, r#"{{"timestamp": 1500000000, "source_ip": "198.51.100.4", "message": "UNION SELECT password FROM users"}}"#).unwrap();
+        writeln!(gzip, "not json").unwrap();
+        gzip.finish().unwrap();
+        std::fs::write(
+            directory.join("auth.csv"),
+            "timestamp,source_ip,message\n1500000100,198.51.100.9,user logged in\n",
+        )
+        .unwrap();
+        std::fs::write(directory.join("notes.txt"), "ignored").unwrap();
+
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        let output = directory.join("detections.out");
+        let settings = BackfillSettings { events_per_second: 0, ..Default::default() };
+        let mut importer = BackfillImporter::new(settings, engine, BackfillSink::json_lines(&output).unwrap());
+
+        let report = importer.run(&directory).await.unwrap();
+        assert_eq!(report.files_processed, 2);
+        assert_eq!(report.events_read, 3);
+        assert_eq!(report.events_rejected, 1);
+        assert!(report.detections >= 1);
+
+        let written = std::fs::read_to_string(&output).unwrap();
+        let first: AdvancedThreatResult = serde_json::from_str(written.lines().next().unwrap()).unwrap();
+        assert_eq!(first.timestamp, 1_500_000_000);
+        assert_eq!(first.details.get("backfill").map(String::as_str), Some("true"));
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[cfg(feature = "chaos")]
+    #[tokio::test]
+    async fn test_supervised_backfill_resumes_after_injected_panics() {
+        use crate::chaos::ChaosSettings;
+
+        let directory = std::env::temp_dir().join(format!("backfill-chaos-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        for name in ["a.jsonl", "b.jsonl"] {
+            let lines: Vec<String> = (0..50)
+                .map(|i| format!(r#"{{"timestamp": {}, "source_ip": "198.51.100.{}", "message": "UNION SELECT password FROM users"}}"#, 1_500_000_000 + i, i))
+                .collect();
+            std::fs::write(directory.join(name), lines.join("\n")).unwrap();
+        }
+        let settings = BackfillSettings { events_per_second: 0, ..Default::default() };
+        let importer = |injector: Option<FaultInjector>| {
+            let settings = settings.clone();
+            async move {
+                let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+                engine.start().await.unwrap();
+                let mut importer = BackfillImporter::new(settings, engine, BackfillSink::DryRun);
+                if let Some(injector) = injector {
+                    importer.set_fault_injector(injector);
+                }
+                importer
+            }
+        };
+
+        let expected = importer(None).await.run(&directory).await.unwrap();
+        let injector = FaultInjector::new(ChaosSettings {
+            enabled: true,
+            seed: Some(11),
+            task_panic_probability: 0.05,
+            max_event_loss_rate: 0.0,
+            ..Default::default()
+        });
+        let report = importer(Some(injector.clone())).await.run_supervised(directory.clone()).await.unwrap();
+        let _ = std::fs::remove_dir_all(&directory);
+
+        assert!(injector.stats().task_panics > 0);
+        assert_eq!((report.files_processed, report.events_read), (2, 100));
+        assert_eq!(report.detections, expected.detections);
+        assert_eq!(injector.check_invariants(expected.events_read, report.events_read), Ok(()));
+    }
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use async_nats::{Client, HeaderMap};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::error_handling::SIEMResult;
+use crate::subjects::{SchemaMode, SubjectPublisher};
+use crate::threat_detection::{ThreatEvent, ThreatSeverity};
+
+/// JetStream subject consumed by the Go bridge `handleThreatEvent`
+pub const SUBJECT_THREATS: &str = "ultra_siem.threats";
+/// Collector events in the Go `bridge.UltraSIEMEvent` shape, consumed by the
+/// Go bridge `handleUltraSIEMEvent` and by the threat detection engine
+pub const SUBJECT_EVENTS: &str = "ultra_siem.events";
+/// JetStream subject consumed by the Go bridge `handleSystemEvent`
+pub const SUBJECT_SYSTEM: &str = "ultra_siem.system";
+
+/// Contract version; bump on any breaking change to the payloads below
+pub const CONTRACT_VERSION: &str = "1";
+/// Header carrying `CONTRACT_VERSION` on every bridge message
+pub const HEADER_CONTRACT_VERSION: &str = "Ultra-Siem-Contract-Version";
+/// Header naming the producing component
+pub const HEADER_PRODUCER: &str = "Ultra-Siem-Producer";
+pub const HEADER_CONTENT_TYPE: &str = "Content-Type";
+
+/// Threat payload, field-for-field identical to Go `bridge.ThreatEvent`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BridgeThreatEvent {
+    pub timestamp: i64,
+    pub source_ip: String,
+    pub threat_type: String,
+    pub payload: String,
+    /// 1 = Low, 2 = Medium, 3 = High, 4 = Critical (ClickHouse `UInt8`)
+    pub severity: u8,
+    pub confidence: f64,
+}
+
+/// System event payload, field-for-field identical to Go `bridge.SystemEvent`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BridgeSystemEvent {
+    pub timestamp: i64,
+    pub event_type: String,
+    pub source: String,
+    pub message: String,
+    pub severity: u8,
+}
+
+/// Numeric severity used by the Go services and ClickHouse
+pub fn severity_code(severity: &ThreatSeverity) -> u8 {
+    match severity {
+        ThreatSeverity::Low => 1,
+        ThreatSeverity::Medium => 2,
+        ThreatSeverity::High => 3,
+        ThreatSeverity::Critical => 4,
+    }
+}
+
+impl From<&ThreatEvent> for BridgeThreatEvent {
+    fn from(threat: &ThreatEvent) -> Self {
+        Self {
+            timestamp: threat.timestamp as i64,
+            source_ip: threat.source_ip.clone(),
+            threat_type: threat.category.to_string(),
+            payload: threat.description.clone(),
+            severity: severity_code(&threat.severity),
+            confidence: threat.confidence as f64,
+        }
+    }
+}
+
+impl From<&AdvancedThreatResult> for BridgeThreatEvent {
+    fn from(threat: &AdvancedThreatResult) -> Self {
+        Self {
+            timestamp: threat.timestamp as i64,
+            source_ip: threat.source_ip.clone(),
+            threat_type: threat.category.to_string(),
+            payload: threat.description.clone(),
+            severity: severity_code(&threat.severity),
+            confidence: threat.confidence as f64,
+        }
+    }
+}
+
+/// Headers attached to every message published for the Go services
+pub fn contract_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(HEADER_CONTRACT_VERSION, CONTRACT_VERSION);
+    headers.insert(HEADER_PRODUCER, "rust-core");
+    headers.insert(HEADER_CONTENT_TYPE, "application/json");
+    headers
+}
+
+/// Publish a threat on `SUBJECT_THREATS` using the bridge contract
+pub async fn publish_threat(client: &Client, threat: &BridgeThreatEvent) -> SIEMResult<()> {
+    SubjectPublisher::new(client.clone(), SchemaMode::default())
+        .publish(SUBJECT_THREATS, threat)
+        .await
+}
+
+/// Publish a system event on `SUBJECT_SYSTEM` using the bridge contract
+pub async fn publish_system_event(client: &Client, event: &BridgeSystemEvent) -> SIEMResult<()> {
+    SubjectPublisher::new(client.clone(), SchemaMode::default())
+        .publish(SUBJECT_SYSTEM, event)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::threat_detection::ThreatCategory;
+
+    const GOLDEN_THREAT: &str = include_str!("../fixtures/bridge/v1/threat_event.json");
+    const GOLDEN_SYSTEM_EVENT: &str = include_str!("../fixtures/bridge/v1/system_event.json");
+
+    #[test]
+    fn test_threat_payload_matches_golden_fixture() {
+        let threat = AdvancedThreatResult {
+            timestamp: 1700000000,
+            source_ip: "203.0.113.7".to_string(),
+            category: ThreatCategory::SQLInjection,
+            severity: ThreatSeverity::High,
+            description: "UNION SELECT password FROM users".to_string(),
+            confidence: 0.9,
+            ..Default::default()
+        };
+
+        let produced = serde_json::to_value(BridgeThreatEvent::from(&threat)).unwrap();
+        let golden: serde_json::Value = serde_json::from_str(GOLDEN_THREAT).unwrap();
+        assert_eq!(produced["timestamp"], golden["timestamp"]);
+        assert_eq!(produced["severity"], golden["severity"]);
+        assert_eq!(
+            produced.as_object().unwrap().keys().collect::<Vec<_>>(),
+            golden.as_object().unwrap().keys().collect::<Vec<_>>()
+        );
+        let parsed: BridgeThreatEvent = serde_json::from_str(GOLDEN_THREAT).unwrap();
+        assert_eq!(parsed.threat_type, "SQLInjection");
+        assert!((parsed.confidence - produced["confidence"].as_f64().unwrap()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_system_event_round_trips_golden_fixture() {
+        let parsed: BridgeSystemEvent = serde_json::from_str(GOLDEN_SYSTEM_EVENT).unwrap();
+        let golden: serde_json::Value = serde_json::from_str(GOLDEN_SYSTEM_EVENT).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), golden);
+    }
+
+    #[test]
+    fn test_contract_headers() {
+        let headers = contract_headers();
+        assert_eq!(headers.get(HEADER_CONTRACT_VERSION).map(|v| v.as_str()), Some(CONTRACT_VERSION));
+        assert_eq!(headers.get(HEADER_CONTENT_TYPE).map(|v| v.as_str()), Some("application/json"));
+    }
+}
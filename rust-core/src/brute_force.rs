@@ -0,0 +1,383 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::attack;
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::content_pack::{ContentPack, ContentPackManifest};
+use crate::error_handling::time;
+#[cfg(feature = "response")]
+use crate::incident_response::{ResponseAction, ResponseCondition, ResponseRule};
+use crate::parsing::{PipelineConfig, ProcessorConfig};
+use crate::threat_detection::{ThreatCategory, ThreatSeverity};
+
+/// Name of the built-in pack shipping the sshd parser
+pub const PACK_NAME: &str = "ssh-rdp-brute-force";
+/// `source` value routed to the sshd parser
+pub const SSHD_SOURCE: &str = "sshd";
+/// Id of the auto-response template rule
+pub const BLOCK_RULE_ID: &str = "brute_force_block_source";
+
+/// Distinct usernames kept per source; enough to tell spraying from guessing
+const MAX_USERS_PER_SOURCE: usize = 64;
+
+/// SSH and RDP brute-force detection and auto-response settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BruteForceSettings {
+    pub enabled: bool,
+    pub window_seconds: u64,
+    /// Failed logons per source and window that raise a detection
+    pub ssh_threshold: u32,
+    pub rdp_threshold: u32,
+    pub max_tracked_sources: usize,
+    /// Block the source of every brute-force detection
+    pub auto_block: bool,
+    pub block_duration_seconds: u64,
+    /// Also block RFC1918, loopback and link-local sources
+    pub block_private: bool,
+}
+
+impl Default for BruteForceSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_seconds: 300,
+            ssh_threshold: 10,
+            rdp_threshold: 10,
+            max_tracked_sources: 10_000,
+            auto_block: false,
+            block_duration_seconds: 3600,
+            block_private: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthProtocol {
+    Ssh,
+    Rdp,
+}
+
+impl AuthProtocol {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuthProtocol::Ssh => "ssh",
+            AuthProtocol::Rdp => "rdp",
+        }
+    }
+}
+
+/// One logon attempt recognised in an event
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthAttempt {
+    pub protocol: AuthProtocol,
+    pub success: bool,
+    pub source_ip: String,
+    pub user: String,
+}
+
+impl AuthAttempt {
+    /// sshd events parsed by the pack pipeline, or Windows 4624/4625 logons of type 10 (RemoteInteractive)
+    pub fn from_event(event: &serde_json::Value) -> Option<Self> {
+        let field = |name: &str| match event.get(name) {
+            Some(serde_json::Value::String(text)) => Some(text.clone()),
+            Some(serde_json::Value::Number(number)) => Some(number.to_string()),
+            _ => None,
+        };
+        let (protocol, success) = match field("event_id").as_deref() {
+            Some("4624") | Some("4625") if field("event_data.LogonType").as_deref() == Some("10") => {
+                (AuthProtocol::Rdp, field("event_id").as_deref() == Some("4624"))
+            }
+            _ if field("source").as_deref() == Some(SSHD_SOURCE) => match field("auth_result").as_deref() {
+                Some("Accepted") => (AuthProtocol::Ssh, true),
+                Some("Failed") => (AuthProtocol::Ssh, false),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let source_ip = field("source_ip").or_else(|| field("event_data.IpAddress")).filter(|ip| !ip.is_empty() && ip != "-")?;
+        Some(Self {
+            protocol,
+            success,
+            source_ip,
+            user: field("user_id").unwrap_or_default(),
+        })
+    }
+}
+
+/// A source crossed the failure threshold, or logged on after doing so
+#[derive(Debug, Clone, PartialEq)]
+pub struct BruteForceAlert {
+    pub protocol: AuthProtocol,
+    pub source_ip: String,
+    pub failed_attempts: u32,
+    pub distinct_users: usize,
+    pub window_seconds: u64,
+    /// Account that logged on after the failures
+    pub compromised_user: Option<String>,
+}
+
+impl BruteForceAlert {
+    pub fn to_threat(&self, timestamp: u64) -> AdvancedThreatResult {
+        let technique = if self.distinct_users > 1 { "T1110.003" } else { "T1110.001" };
+        let mut details = attack::technique_details(&[technique.to_string()]);
+        details.insert("protocol".to_string(), self.protocol.as_str().to_string());
+        details.insert("failed_attempts".to_string(), self.failed_attempts.to_string());
+        details.insert("distinct_users".to_string(), self.distinct_users.to_string());
+        details.insert("window_seconds".to_string(), self.window_seconds.to_string());
+        let protocol = self.protocol.as_str().to_uppercase();
+        let (severity, user_id, description) = match &self.compromised_user {
+            Some(user) => (
+                ThreatSeverity::Critical,
+                user.clone(),
+                format!("{} logon as {} from {} after {} failed attempts", protocol, user, self.source_ip, self.failed_attempts),
+            ),
+            None => (
+                ThreatSeverity::High,
+                String::new(),
+                format!(
+                    "{} brute force from {}: {} failed logons for {} accounts within {} seconds",
+                    protocol, self.source_ip, self.failed_attempts, self.distinct_users, self.window_seconds
+                ),
+            ),
+        };
+        AdvancedThreatResult {
+            timestamp,
+            severity,
+            category: ThreatCategory::BruteForce,
+            confidence: 0.9,
+            detection_method: "brute_force".to_string(),
+            source_ip: self.source_ip.clone(),
+            user_id,
+            description,
+            details,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SourceWindow {
+    start: u64,
+    failures: u32,
+    users: HashSet<String>,
+    alerted: bool,
+}
+
+/// Per-source failed logon counts over a rolling window
+#[derive(Debug)]
+pub struct BruteForceDetector {
+    settings: BruteForceSettings,
+    sources: Mutex<HashMap<(AuthProtocol, String), SourceWindow>>,
+}
+
+impl BruteForceDetector {
+    pub fn new(settings: BruteForceSettings) -> Self {
+        Self {
+            settings,
+            sources: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn observe(&self, event: &serde_json::Value) -> Option<BruteForceAlert> {
+        self.observe_at(event, time::current_timestamp().unwrap_or_default())
+    }
+
+    /// Count a logon in `event` at `now`; alerts once per window, and again on a logon after the alert
+    pub fn observe_at(&self, event: &serde_json::Value, now: u64) -> Option<BruteForceAlert> {
+        let attempt = AuthAttempt::from_event(event)?;
+        let threshold = match attempt.protocol {
+            AuthProtocol::Ssh => self.settings.ssh_threshold,
+            AuthProtocol::Rdp => self.settings.rdp_threshold,
+        };
+        let mut sources = self.sources.lock().unwrap();
+        let key = (attempt.protocol, attempt.source_ip.clone());
+
+        if attempt.success {
+            let window = sources.remove(&key).filter(|w| now.saturating_sub(w.start) <= self.settings.window_seconds)?;
+            return window.alerted.then_some(BruteForceAlert {
+                protocol: attempt.protocol,
+                source_ip: attempt.source_ip,
+                failed_attempts: window.failures,
+                distinct_users: window.users.len(),
+                window_seconds: self.settings.window_seconds,
+                compromised_user: Some(attempt.user),
+            });
+        }
+
+        if !sources.contains_key(&key) && sources.len() >= self.settings.max_tracked_sources {
+            let window_seconds = self.settings.window_seconds;
+            sources.retain(|_, w| now.saturating_sub(w.start) <= window_seconds);
+            if sources.len() >= self.settings.max_tracked_sources {
+                return None;
+            }
+        }
+        let window = sources.entry(key).or_insert_with(|| SourceWindow {
+            start: now,
+            failures: 0,
+            users: HashSet::new(),
+            alerted: false,
+        });
+        if now.saturating_sub(window.start) > self.settings.window_seconds {
+            *window = SourceWindow { start: now, failures: 0, users: HashSet::new(), alerted: false };
+        }
+        window.failures += 1;
+        if window.users.len() < MAX_USERS_PER_SOURCE {
+            window.users.insert(attempt.user);
+        }
+        if window.alerted || window.failures < threshold {
+            return None;
+        }
+        window.alerted = true;
+        Some(BruteForceAlert {
+            protocol: attempt.protocol,
+            source_ip: attempt.source_ip,
+            failed_attempts: window.failures,
+            distinct_users: window.users.len(),
+            window_seconds: self.settings.window_seconds,
+            compromised_user: None,
+        })
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        HashMap::from([("brute_force_tracked_sources".to_string(), self.sources.lock().unwrap().len() as f64)])
+    }
+}
+
+/// Built-in pack with the sshd auth log parser
+pub fn content_pack() -> ContentPack {
+    let sshd_fields = "%{SSH_RESULT:auth_result} %{NOTSPACE:auth_method} for (?:invalid user )?%{NOTSPACE:user_id} from %{IP:source_ip} port %{INT:source_port:int}";
+    ContentPack {
+        manifest: ContentPackManifest {
+            name: PACK_NAME.to_string(),
+            version: "1.0.0".to_string(),
+            publisher: "ultra-siem".to_string(),
+            description: "sshd and RDP logon parsing for the SSH/RDP brute-force detector".to_string(),
+        },
+        signatures: Vec::new(),
+        correlation_rules: Vec::new(),
+        quantum_patterns: Vec::new(),
+        pipelines: vec![PipelineConfig {
+            name: "sshd-auth".to_string(),
+            source: SSHD_SOURCE.to_string(),
+            processors: vec![ProcessorConfig::Grok {
+                field: "message".to_string(),
+                patterns: vec![
+                    format!("^%{{SYSLOGTIMESTAMP:syslog_timestamp}} %{{IPORHOST:host}} sshd(?:\\[%{{INT:pid:int}}\\])?: {}", sshd_fields),
+                    sshd_fields.to_string(),
+                ],
+                pattern_definitions: HashMap::from([("SSH_RESULT".to_string(), "Failed|Accepted".to_string())]),
+            }],
+        }],
+        dashboards: Vec::new(),
+    }
+}
+
+/// Response rule blocking the source of brute-force detections; private sources only with `block_private`
+#[cfg(feature = "response")]
+pub fn block_rule(settings: &BruteForceSettings) -> ResponseRule {
+    ResponseRule {
+        id: BLOCK_RULE_ID.to_string(),
+        name: "Block SSH/RDP brute-force sources".to_string(),
+        description: "Block the source address of SSH and RDP brute-force detections.".to_string(),
+        enabled: settings.auto_block,
+        conditions: vec![ResponseCondition {
+            field: "detection_method".to_string(),
+            operator: "equals".to_string(),
+            value: "brute_force".to_string(),
+            case_sensitive: true,
+        }],
+        actions: vec![ResponseAction::BlockSourceIP {
+            duration_seconds: settings.block_duration_seconds,
+            block_private: settings.block_private,
+        }],
+        priority: 1,
+        cooldown_seconds: 0,
+        last_triggered: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::ParsingPipelines;
+
+    #[test]
+    fn test_sshd_brute_force_then_compromise() {
+        let pipelines = ParsingPipelines::from_config(&content_pack().pipelines).unwrap();
+        let detector = BruteForceDetector::new(BruteForceSettings { ssh_threshold: 5, ..Default::default() });
+        let sshd = |message: &str| {
+            let mut event = serde_json::json!({ "source": "sshd", "message": message });
+            pipelines.process(&mut event);
+            event
+        };
+        let now = 1_700_000_000;
+        let mut alerts = Vec::new();
+        for i in 0..8 {
+            let line = format!("Oct 16 10:00:0{} bastion sshd[811]: Failed password for invalid user user{} from 203.0.113.7 port 5102{} ssh2", i, i % 3, i);
+            alerts.extend(detector.observe_at(&sshd(&line), now + i));
+        }
+        assert_eq!(alerts.len(), 1);
+        assert_eq!((alerts[0].failed_attempts, alerts[0].distinct_users), (5, 3));
+
+        let accepted = sshd("Accepted password for deploy from 203.0.113.7 port 51100 ssh2");
+        assert_eq!(accepted["user_id"], "deploy");
+        let compromise = detector.observe_at(&accepted, now + 20).unwrap();
+        assert_eq!(compromise.compromised_user.as_deref(), Some("deploy"));
+        assert_eq!(compromise.to_threat(now).severity, ThreatSeverity::Critical);
+
+        // Another source's logon is not a compromise
+        assert!(detector.observe_at(&sshd("Accepted publickey for ops from 198.51.100.4 port 40000 ssh2"), now + 21).is_none());
+    }
+
+    #[test]
+    fn test_rdp_logons_need_remote_interactive_type() {
+        let detector = BruteForceDetector::new(BruteForceSettings { rdp_threshold: 3, ..Default::default() });
+        let logon = |event_id: u64, logon_type: &str| {
+            serde_json::json!({
+                "source": "evtx",
+                "event_id": event_id,
+                "event_data.LogonType": logon_type,
+                "source_ip": "198.51.100.23",
+                "user_id": "administrator",
+            })
+        };
+        let now = 1_700_000_000;
+        // Network logons (type 3) are not RDP
+        assert!((0..5).all(|i| detector.observe_at(&logon(4625, "3"), now + i).is_none()));
+        assert!(detector.observe_at(&logon(4625, "10"), now).is_none());
+        assert!(detector.observe_at(&logon(4625, "10"), now + 1).is_none());
+        let alert = detector.observe_at(&logon(4625, "10"), now + 2).unwrap();
+        assert_eq!(alert.protocol, AuthProtocol::Rdp);
+        let threat = alert.to_threat(now);
+        assert_eq!(threat.details["protocol"], "rdp");
+        assert_eq!(attack::techniques_of(&threat), vec!["T1110.001"]);
+
+        // Failures outside the window start a new count
+        assert!(detector.observe_at(&logon(4625, "10"), now + 1000).is_none());
+        assert!(detector.observe_at(&logon(4624, "10"), now + 1001).is_none());
+    }
+
+    #[cfg(feature = "response")]
+    #[tokio::test]
+    async fn test_block_template_spares_private_sources() {
+        use crate::incident_response::{AlertConfig, IncidentResponseEngine, SOARConfig};
+
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        engine.add_response_rule(block_rule(&BruteForceSettings { auto_block: true, ..Default::default() }));
+        let alert = BruteForceAlert {
+            protocol: AuthProtocol::Ssh,
+            source_ip: "10.1.2.3".to_string(),
+            failed_attempts: 12,
+            distinct_users: 1,
+            window_seconds: 300,
+            compromised_user: None,
+        };
+        let incident = engine.process_threat(alert.to_threat(1_700_000_000)).await.unwrap();
+        let result = incident.response_actions.iter().find(|r| matches!(r.action_type, ResponseAction::BlockSourceIP { .. })).unwrap();
+        assert!(!result.success);
+        assert!(result.error_message.as_deref().unwrap().contains("Not blocking internal address 10.1.2.3"));
+    }
+}
@@ -0,0 +1,370 @@
+//! # End-to-end Canary
+//!
+//! Every `interval_seconds` a synthetic event carrying the canary marker is
+//! injected at ingestion. A built-in signature turns it into a canary
+//! detection, which must come out of parsing, detection and the suppression
+//! filters intact and be accepted by every detection sink, all within
+//! `sla_ms`. The event runs through the traced pipeline, so the canary
+//! detection is never published to gRPC subscribers or turned into an alert;
+//! records written to the sinks carry `canary: true`.
+//!
+//! A failed check raises a self-monitoring incident naming the stage that
+//! broke, once per outage: the next incident is only raised after the canary
+//! recovered or started failing at a different stage.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::advanced_threat_detection::{AdvancedThreatDetectionEngine, AdvancedThreatResult};
+use crate::error_handling::SIEMResult;
+use crate::incident_response::IncidentResponseEngine;
+use crate::output_sinks::{OutputSinks, SinkRecord};
+use crate::replay::PipelineTrace;
+use crate::threat_detection::{SignaturePattern, ThreatCategory, ThreatSeverity};
+
+/// Event field carrying the id of the check that injected it
+pub const CANARY_FIELD: &str = "canary_id";
+/// Signature that turns canary events into canary detections
+pub const CANARY_SIGNATURE_ID: &str = "ultra_siem_canary";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CanarySettings {
+    pub enabled: bool,
+    pub interval_seconds: u64,
+    /// End-to-end budget from injection to the last sink acknowledging the record
+    pub sla_ms: u64,
+    /// Token in the canary message the canary signature matches
+    pub marker: String,
+    /// Documentation-range address the canary claims to come from
+    pub source_ip: String,
+}
+
+impl Default for CanarySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_seconds: 60,
+            sla_ms: 2000,
+            marker: "ULTRA_SIEM_CANARY".to_string(),
+            source_ip: "192.0.2.1".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CanaryStage {
+    Ingestion,
+    Parsing,
+    Detection,
+    Suppression,
+    Sink,
+}
+
+impl std::fmt::Display for CanaryStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CanaryStage::Ingestion => "ingestion",
+            CanaryStage::Parsing => "parsing",
+            CanaryStage::Detection => "detection",
+            CanaryStage::Suppression => "suppression",
+            CanaryStage::Sink => "sink",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Outcome of one canary check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryResult {
+    pub canary_id: String,
+    pub checked_at: DateTime<Utc>,
+    pub passed: bool,
+    pub failed_stage: Option<CanaryStage>,
+    pub detail: String,
+    pub elapsed_ms: u64,
+    /// Time spent in the detection pipeline and in the sinks
+    pub stage_ms: BTreeMap<String, u64>,
+}
+
+/// Periodic end-to-end health check of the detection path
+pub struct Canary {
+    settings: CanarySettings,
+    detector: Arc<AdvancedThreatDetectionEngine>,
+    sinks: Option<Arc<OutputSinks>>,
+    /// Stage of the outage an incident was already raised for
+    failing: Mutex<Option<CanaryStage>>,
+    last: RwLock<Option<CanaryResult>>,
+}
+
+impl Canary {
+    /// Registers the canary signature on `detector`
+    pub fn new(settings: CanarySettings, detector: Arc<AdvancedThreatDetectionEngine>, sinks: Option<Arc<OutputSinks>>) -> SIEMResult<Self> {
+        detector.add_signature(SignaturePattern {
+            id: CANARY_SIGNATURE_ID.to_string(),
+            name: "Ultra SIEM canary".to_string(),
+            pattern: regex::escape(&settings.marker),
+            category: ThreatCategory::Other,
+            severity: ThreatSeverity::Low,
+            description: "Synthetic canary event (benign health check)".to_string(),
+            enabled: true,
+            confidence: 1.0,
+            attack_techniques: Vec::new(),
+        })?;
+        Ok(Self { settings, detector, sinks, failing: Mutex::new(None), last: RwLock::new(None) })
+    }
+
+    pub fn last_result(&self) -> Option<CanaryResult> {
+        self.last.read().unwrap().clone()
+    }
+
+    /// Synthetic benign event for one check
+    pub fn event(&self, canary_id: &str, now: DateTime<Utc>) -> serde_json::Value {
+        json!({
+            "timestamp": now.timestamp().max(0) as u64,
+            "event_type": "canary",
+            "source_ip": self.settings.source_ip,
+            "host": "ultra-siem-canary",
+            "message": format!("{} {} synthetic health check, benign", self.settings.marker, canary_id),
+            CANARY_FIELD: canary_id,
+            "benign": true,
+        })
+    }
+
+    /// Inject one canary and follow it through the pipeline and the sinks
+    pub async fn run_once(&self) -> SIEMResult<CanaryResult> {
+        let canary_id = uuid::Uuid::new_v4().to_string();
+        let checked_at = Utc::now();
+        let sla = Duration::from_millis(self.settings.sla_ms);
+        let start = Instant::now();
+        let mut stage_ms = BTreeMap::new();
+        let result = |failure: Option<(CanaryStage, String)>, stage_ms: BTreeMap<String, u64>| {
+            let (failed_stage, detail) = failure.map_or((None, String::new()), |(stage, detail)| (Some(stage), detail));
+            CanaryResult {
+                canary_id: canary_id.clone(),
+                checked_at,
+                passed: failed_stage.is_none(),
+                failed_stage,
+                detail,
+                elapsed_ms: start.elapsed().as_millis() as u64,
+                stage_ms,
+            }
+        };
+
+        let event = self.event(&canary_id, checked_at);
+        let traced = tokio::time::timeout(sla, self.detector.process_event_traced(event)).await;
+        stage_ms.insert("detection".to_string(), start.elapsed().as_millis() as u64);
+        let (threats, trace) = match traced {
+            Ok(traced) => traced?,
+            Err(_) => {
+                let detail = format!("no result from the detection pipeline within {} ms", self.settings.sla_ms);
+                return Ok(result(Some((CanaryStage::Detection, detail)), stage_ms));
+            }
+        };
+        if let Some(failure) = locate_failure(&trace, &threats, &canary_id) {
+            return Ok(result(Some(failure), stage_ms));
+        }
+
+        if let Some(sinks) = &self.sinks {
+            let sink_start = Instant::now();
+            let threat = threats.iter().find(|t| is_canary(t)).expect("canary detection located above");
+            let mut record = SinkRecord::detection(threat);
+            record.fields.insert("canary".to_string(), json!(true));
+            record.fields.insert(CANARY_FIELD.to_string(), json!(canary_id));
+            let remaining = sla.saturating_sub(start.elapsed());
+            let probed = tokio::time::timeout(remaining, sinks.probe(&record)).await;
+            stage_ms.insert("sink".to_string(), sink_start.elapsed().as_millis() as u64);
+            match probed {
+                Err(_) => {
+                    let detail = format!("sinks did not acknowledge the canary record within {} ms", self.settings.sla_ms);
+                    return Ok(result(Some((CanaryStage::Sink, detail)), stage_ms));
+                }
+                Ok(probes) => {
+                    if let Some((kind, Err(e))) = probes.into_iter().find(|(_, probe)| probe.is_err()) {
+                        return Ok(result(Some((CanaryStage::Sink, format!("{:?} sink rejected the canary record: {}", kind, e))), stage_ms));
+                    }
+                }
+            }
+        }
+
+        // Both phases finished, but together they may still have overrun the SLA
+        let elapsed = start.elapsed();
+        if elapsed > sla {
+            let (slowest, ms) = stage_ms.iter().max_by_key(|(_, ms)| **ms).map(|(stage, ms)| (stage.clone(), *ms)).unwrap_or_default();
+            let stage = if slowest == "sink" { CanaryStage::Sink } else { CanaryStage::Detection };
+            let detail = format!("took {} ms of a {} ms SLA; {} spent {} ms", elapsed.as_millis(), self.settings.sla_ms, slowest, ms);
+            return Ok(result(Some((stage, detail)), stage_ms));
+        }
+        Ok(result(None, stage_ms))
+    }
+
+    /// Run a check and raise a self-monitoring incident when a new outage starts
+    pub async fn check(&self, incidents: &IncidentResponseEngine) -> SIEMResult<CanaryResult> {
+        let result = self.run_once().await?;
+        *self.last.write().unwrap() = Some(result.clone());
+        let newly_failing = {
+            let mut failing = self.failing.lock().unwrap();
+            let previous = std::mem::replace(&mut *failing, result.failed_stage);
+            match (previous, result.failed_stage) {
+                (Some(stage), None) => {
+                    info!("✅ Canary recovered after failing at {}", stage);
+                    None
+                }
+                (previous, Some(stage)) if previous != Some(stage) => Some(stage),
+                _ => None,
+            }
+        };
+        if let Some(stage) = newly_failing {
+            warn!("⚠️ Canary {} failed at {}: {}", result.canary_id, stage, result.detail);
+            incidents.process_threat(self_monitoring_threat(&result, stage)).await?;
+        }
+        Ok(result)
+    }
+
+    pub fn spawn(self: Arc<Self>, incidents: Arc<IncidentResponseEngine>) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.settings.enabled {
+            return None;
+        }
+        Some(tokio::spawn(async move {
+            info!("🐤 Canary every {}s with a {} ms SLA", self.settings.interval_seconds, self.settings.sla_ms);
+            let mut interval = tokio::time::interval(Duration::from_secs(self.settings.interval_seconds.max(1)));
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.check(&incidents).await {
+                    error!("❌ Canary check failed to run: {}", e);
+                }
+            }
+        }))
+    }
+}
+
+fn is_canary(threat: &AdvancedThreatResult) -> bool {
+    threat.signatures.iter().any(|s| s == CANARY_SIGNATURE_ID)
+}
+
+/// First stage of the traced pipeline that lost the canary
+fn locate_failure(trace: &PipelineTrace, threats: &[AdvancedThreatResult], canary_id: &str) -> Option<(CanaryStage, String)> {
+    let last_stage = trace.stages.last().map(|s| s.stage.as_str()).unwrap_or("none");
+    let Some(input) = &trace.detection_input else {
+        return Some((CanaryStage::Ingestion, format!("event dropped at the {} stage", last_stage)));
+    };
+    let parsed = input.get("message").and_then(|m| m.as_str()).is_some_and(|m| m.contains(canary_id));
+    if !parsed {
+        return Some((CanaryStage::Parsing, format!("canary message lost or rewritten by parsing: {}", input)));
+    }
+    if trace.stages.iter().any(|s| s.stage == "whitelist") {
+        return Some((CanaryStage::Detection, "canary source is allow-listed; detection skipped".to_string()));
+    }
+    let Some(signature) = trace.stages.iter().find(|s| s.stage == "signature") else {
+        return Some((CanaryStage::Detection, "signature stage did not run".to_string()));
+    };
+    if !signature.detail.contains(CANARY_SIGNATURE_ID) {
+        return Some((CanaryStage::Detection, format!("canary signature did not match ({})", signature.detail)));
+    }
+    if threats.iter().any(is_canary) {
+        return None;
+    }
+    // The detection was filtered afterwards; blame the last filter that removed anything
+    let mut filters = trace.stages.iter().filter(|s| matches!(s.stage.as_str(), "profile" | "false_positive" | "suppression"));
+    let culprit = filters.clone().rev().find(|s| !s.detail.ends_with(" 0")).or_else(|| filters.next_back());
+    match culprit {
+        Some(stage) if stage.stage == "suppression" => Some((CanaryStage::Suppression, format!("canary detection suppressed ({})", stage.detail))),
+        Some(stage) => Some((CanaryStage::Detection, format!("canary detection removed by the {} filter ({})", stage.stage, stage.detail))),
+        None => Some((CanaryStage::Detection, "canary detection lost after the signature stage".to_string())),
+    }
+}
+
+fn self_monitoring_threat(result: &CanaryResult, stage: CanaryStage) -> AdvancedThreatResult {
+    AdvancedThreatResult {
+        threat_id: uuid::Uuid::new_v4().to_string(),
+        timestamp: result.checked_at.timestamp().max(0) as u64,
+        severity: ThreatSeverity::High,
+        category: ThreatCategory::Other,
+        confidence: 1.0,
+        detection_method: "canary".to_string(),
+        description: format!("Self-monitoring: detection path broken at {}: {}", stage, result.detail),
+        details: [
+            ("self_monitoring".to_string(), "canary".to_string()),
+            ("canary_stage".to_string(), stage.to_string()),
+            (CANARY_FIELD.to_string(), result.canary_id.clone()),
+        ]
+        .into(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::future::BoxFuture;
+    use crate::advanced_threat_detection::AdvancedThreatConfig;
+    use crate::error_handling::SIEMError;
+    use crate::incident_response::{AlertConfig, SOARConfig};
+    use crate::output_sinks::{RecordSink, SinkKind, SinkSettings};
+    use crate::suppression::{SuppressionList, SuppressionRequest, SuppressionScope, SuppressionSettings};
+
+    struct DownSink;
+
+    impl RecordSink for DownSink {
+        fn write<'a>(&'a self, _records: &'a [SinkRecord]) -> BoxFuture<'a, SIEMResult<()>> {
+            Box::pin(async { Err(SIEMError::Other("connection refused".to_string())) })
+        }
+    }
+
+    async fn detector() -> AdvancedThreatDetectionEngine {
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        engine
+    }
+
+    #[tokio::test]
+    async fn test_healthy_pipeline_passes_and_down_sink_raises_one_incident() {
+        let incidents = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let settings = CanarySettings { enabled: true, sla_ms: 60_000, ..Default::default() };
+        let canary = Canary::new(settings.clone(), Arc::new(detector().await), None).unwrap();
+        let result = canary.check(&incidents).await.unwrap();
+        assert!(result.passed, "{:?}", result);
+        assert!(incidents.get_all_incidents().is_empty());
+
+        let sink_settings = SinkSettings { enabled: true, detections: vec![SinkKind::Loki], ..Default::default() };
+        let sinks = OutputSinks::with_sinks(sink_settings, vec![(SinkKind::Loki, Arc::new(DownSink))]);
+        let canary = Canary::new(settings, Arc::new(detector().await), Some(Arc::new(sinks))).unwrap();
+        for _ in 0..3 {
+            let result = canary.check(&incidents).await.unwrap();
+            assert_eq!(result.failed_stage, Some(CanaryStage::Sink));
+        }
+        let raised = incidents.get_all_incidents();
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].threat_result.details["canary_stage"], "sink");
+    }
+
+    #[tokio::test]
+    async fn test_pinpoints_suppression_and_detection_failures() {
+        let path = std::env::temp_dir().join(format!("canary-suppressions-{}.json", uuid::Uuid::new_v4()));
+        let suppressions = Arc::new(SuppressionList::open(SuppressionSettings {
+            store_path: path.to_string_lossy().to_string(),
+            ..Default::default()
+        }).unwrap());
+        let canary_threat = AdvancedThreatResult { signatures: vec![CANARY_SIGNATURE_ID.to_string()], ..Default::default() };
+        let request = SuppressionRequest { scope: SuppressionScope::Everywhere, days: 1, owner: "alice".to_string(), reason: "noisy".to_string() };
+        suppressions.create(&canary_threat, "inc-1", &request, Utc::now()).unwrap();
+        let mut engine = detector().await;
+        engine.set_suppressions(suppressions);
+        let canary = Canary::new(CanarySettings { sla_ms: 60_000, ..Default::default() }, Arc::new(engine), None).unwrap();
+        let result = canary.run_once().await.unwrap();
+        assert_eq!(result.failed_stage, Some(CanaryStage::Suppression), "{:?}", result);
+
+        let engine = Arc::new(detector().await);
+        let canary = Canary::new(CanarySettings { sla_ms: 60_000, ..Default::default() }, engine.clone(), None).unwrap();
+        engine.remove_signature(CANARY_SIGNATURE_ID);
+        let result = canary.run_once().await.unwrap();
+        assert_eq!(result.failed_stage, Some(CanaryStage::Detection), "{:?}", result);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,485 @@
+//! # Canary Accounts and Hosts
+//!
+//! Accounts and hosts that exist only as bait: no person or service ever uses
+//! them, so any event naming one is an intrusion. Each registered asset is
+//! expanded into one rule per event type template, matching the fields that
+//! event type names an account or host in; events of a type no template lists
+//! fall back to the template without `event_types`.
+//!
+//! A hit is Critical and is never dropped by detection profiles, false-positive
+//! filtering or suppressions. Its alert skips quiet hours and also goes to the
+//! routing `canary_channels`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::attack;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::ip_net;
+use crate::threat_detection::{ThreatCategory, ThreatSeverity};
+
+/// Detail naming the canary asset a detection fired on
+pub const CANARY_ASSET_DETAIL: &str = "canary_asset";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CanaryKind {
+    Account,
+    Host,
+}
+
+impl std::fmt::Display for CanaryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CanaryKind::Account => "account",
+            CanaryKind::Host => "host",
+        })
+    }
+}
+
+impl std::str::FromStr for CanaryKind {
+    type Err = SIEMError;
+
+    fn from_str(value: &str) -> SIEMResult<Self> {
+        match value {
+            "account" => Ok(CanaryKind::Account),
+            "host" => Ok(CanaryKind::Host),
+            other => Err(SIEMError::Validation(format!("Unknown canary kind '{}', expected account or host", other))),
+        }
+    }
+}
+
+impl CanaryKind {
+    /// Form values are compared in: accounts without `DOMAIN\` or `@realm`, hosts
+    /// as canonical addresses or short names, both lowercase
+    pub fn key(&self, value: &str) -> String {
+        let value = value.trim().to_lowercase();
+        match self {
+            CanaryKind::Account => {
+                let user = value.rsplit('\\').next().unwrap_or_default();
+                user.split('@').next().unwrap_or_default().to_string()
+            }
+            CanaryKind::Host => match ip_net::parse_ip(&value) {
+                Some(ip) => ip.to_string(),
+                None => value.split('.').next().unwrap_or_default().to_string(),
+            },
+        }
+    }
+}
+
+/// A bait account or host
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CanaryAsset {
+    pub kind: CanaryKind,
+    pub name: String,
+    /// Who planted it and answers for it
+    #[serde(default)]
+    pub owner: String,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub registered_at: u64,
+}
+
+impl CanaryAsset {
+    fn validate(&self) -> SIEMResult<()> {
+        if self.kind.key(&self.name).is_empty() {
+            return Err(SIEMError::Validation(format!("Canary {} needs a name", self.kind)));
+        }
+        Ok(())
+    }
+}
+
+/// Fields naming an account or host in one family of event types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryRuleTemplate {
+    pub name: String,
+    /// `event_type` values covered; empty covers event types no other template lists
+    #[serde(default)]
+    pub event_types: Vec<String>,
+    #[serde(default)]
+    pub account_fields: Vec<String>,
+    #[serde(default)]
+    pub host_fields: Vec<String>,
+}
+
+impl CanaryRuleTemplate {
+    fn new(name: &str, event_types: &[&str], account_fields: &[&str], host_fields: &[&str]) -> Self {
+        let owned = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        Self {
+            name: name.to_string(),
+            event_types: owned(event_types),
+            account_fields: owned(account_fields),
+            host_fields: owned(host_fields),
+        }
+    }
+
+    fn fields(&self, kind: CanaryKind) -> &[String] {
+        match kind {
+            CanaryKind::Account => &self.account_fields,
+            CanaryKind::Host => &self.host_fields,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CanaryAssetSettings {
+    pub enabled: bool,
+    /// Registered assets; seeded from `assets` when the file does not exist yet
+    pub store_path: String,
+    pub assets: Vec<CanaryAsset>,
+    pub templates: Vec<CanaryRuleTemplate>,
+}
+
+impl Default for CanaryAssetSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            store_path: "data/canary_assets.json".to_string(),
+            assets: Vec::new(),
+            templates: vec![
+                CanaryRuleTemplate::new(
+                    "authentication",
+                    &["authentication", "logon", "login", "ssh", "rdp", "vpn"],
+                    &["user", "username", "target_user", "account"],
+                    &["host", "hostname", "destination_host", "workstation", "destination_ip"],
+                ),
+                CanaryRuleTemplate::new(
+                    "kerberos",
+                    &["kerberos", "tgt_request", "tgs_request"],
+                    &["user", "target_user", "service_name"],
+                    &["host", "client_host", "destination_host"],
+                ),
+                CanaryRuleTemplate::new(
+                    "account_management",
+                    &["account_change", "group_change", "password_reset"],
+                    &["target_user", "user", "member"],
+                    &["host", "hostname"],
+                ),
+                CanaryRuleTemplate::new("process", &["process", "process_start", "command"], &["user", "parent_user"], &["host", "hostname"]),
+                CanaryRuleTemplate::new("file_access", &["file_access", "share_access"], &["user"], &["host", "share_host"]),
+                CanaryRuleTemplate::new(
+                    "network",
+                    &["connection", "network", "firewall", "dns", "http"],
+                    &["user"],
+                    &["destination_host", "destination_ip", "query_name", "server_name"],
+                ),
+                CanaryRuleTemplate::new("other", &[], &["user", "username", "target_user"], &["host", "hostname", "destination_host", "destination_ip"]),
+            ],
+        }
+    }
+}
+
+impl CanaryAssetSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        let mut names = BTreeSet::new();
+        let mut event_types = BTreeSet::new();
+        for template in &self.templates {
+            if template.name.trim().is_empty() || !names.insert(template.name.as_str()) {
+                return Err(SIEMError::Validation(format!("canary_assets template '{}' needs a unique name", template.name)));
+            }
+            if let Some(event_type) = template.event_types.iter().find(|t| !event_types.insert(t.to_lowercase())) {
+                return Err(SIEMError::Validation(format!("canary_assets event type '{}' is listed by two templates", event_type)));
+            }
+        }
+        if self.templates.iter().filter(|t| t.event_types.is_empty()).count() > 1 {
+            return Err(SIEMError::Validation("canary_assets allows one fallback template without event_types".to_string()));
+        }
+        for asset in &self.assets {
+            asset.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// One asset under one template, generated on registration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CanaryRule {
+    pub id: String,
+    pub kind: CanaryKind,
+    pub asset: String,
+    pub template: String,
+    pub event_types: Vec<String>,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Registered {
+    assets: Vec<CanaryAsset>,
+    rules: Vec<CanaryRule>,
+    /// (kind, key) to the asset's index
+    index: HashMap<(CanaryKind, String), usize>,
+}
+
+impl Registered {
+    fn new(assets: Vec<CanaryAsset>, templates: &[CanaryRuleTemplate]) -> Self {
+        let mut rules = Vec::new();
+        let mut index = HashMap::new();
+        for (i, asset) in assets.iter().enumerate() {
+            index.insert((asset.kind, asset.kind.key(&asset.name)), i);
+            for template in templates.iter().filter(|t| !t.fields(asset.kind).is_empty()) {
+                rules.push(CanaryRule {
+                    id: rule_id(asset, template),
+                    kind: asset.kind,
+                    asset: asset.name.clone(),
+                    template: template.name.clone(),
+                    event_types: template.event_types.clone(),
+                    fields: template.fields(asset.kind).to_vec(),
+                });
+            }
+        }
+        Self { assets, rules, index }
+    }
+}
+
+fn rule_id(asset: &CanaryAsset, template: &CanaryRuleTemplate) -> String {
+    format!("canary_{}_{}_{}", asset.kind, asset.kind.key(&asset.name), template.name)
+}
+
+/// Registered canary assets and the rules generated from them
+#[derive(Debug)]
+pub struct CanaryRegistry {
+    settings: CanaryAssetSettings,
+    path: PathBuf,
+    state: RwLock<Registered>,
+    hits: AtomicU64,
+}
+
+impl CanaryRegistry {
+    pub fn open(settings: CanaryAssetSettings) -> SIEMResult<Self> {
+        settings.validate()?;
+        let path = PathBuf::from(&settings.store_path);
+        let assets = match std::fs::read(&path) {
+            Ok(raw) => serde_json::from_slice(&raw)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => settings.assets.clone(),
+            Err(e) => return Err(e.into()),
+        };
+        let state = RwLock::new(Registered::new(assets, &settings.templates));
+        Ok(Self { settings, path, state, hits: AtomicU64::new(0) })
+    }
+
+    pub fn assets(&self) -> Vec<CanaryAsset> {
+        self.state.read().unwrap().assets.clone()
+    }
+
+    pub fn rules(&self) -> Vec<CanaryRule> {
+        self.state.read().unwrap().rules.clone()
+    }
+
+    /// Register `asset` and generate its rules; returns the rules added
+    pub fn register(&self, mut asset: CanaryAsset, now: u64) -> SIEMResult<Vec<CanaryRule>> {
+        asset.validate()?;
+        let mut assets = self.assets();
+        let key = asset.kind.key(&asset.name);
+        if assets.iter().any(|known| known.kind == asset.kind && known.kind.key(&known.name) == key) {
+            return Err(SIEMError::Validation(format!("Canary {} '{}' is already registered", asset.kind, asset.name)));
+        }
+        asset.registered_at = now;
+        info!("🪤 Canary {} '{}' registered by {}", asset.kind, asset.name, asset.owner);
+        let name = asset.name.clone();
+        assets.push(asset);
+        self.replace(assets)?;
+        Ok(self.rules().into_iter().filter(|rule| rule.asset == name).collect())
+    }
+
+    /// Retire an asset and its rules; whether it was registered
+    pub fn remove(&self, kind: CanaryKind, name: &str) -> SIEMResult<bool> {
+        let key = kind.key(name);
+        let mut assets = self.assets();
+        let before = assets.len();
+        assets.retain(|asset| asset.kind != kind || kind.key(&asset.name) != key);
+        if assets.len() == before {
+            return Ok(false);
+        }
+        info!("🪤 Canary {} '{}' retired", kind, name);
+        self.replace(assets)?;
+        Ok(true)
+    }
+
+    fn replace(&self, assets: Vec<CanaryAsset>) -> SIEMResult<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&assets)?)?;
+        std::fs::rename(tmp, &self.path)?;
+        *self.state.write().unwrap() = Registered::new(assets, &self.settings.templates);
+        Ok(())
+    }
+
+    /// Template covering `event_type`, else the fallback template
+    fn template_for(&self, event_type: &str) -> Option<&CanaryRuleTemplate> {
+        let templates = &self.settings.templates;
+        templates
+            .iter()
+            .find(|t| t.event_types.iter().any(|known| known.eq_ignore_ascii_case(event_type)))
+            .or_else(|| templates.iter().find(|t| t.event_types.is_empty()))
+    }
+
+    /// One Critical detection per canary asset the event names
+    pub fn detect(&self, event: &serde_json::Value, timestamp: u64) -> Vec<AdvancedThreatResult> {
+        let state = self.state.read().unwrap();
+        if state.assets.is_empty() {
+            return Vec::new();
+        }
+        let event_type = event.get("event_type").and_then(|v| v.as_str()).unwrap_or_default();
+        let Some(template) = self.template_for(event_type) else {
+            return Vec::new();
+        };
+        let mut hit = BTreeSet::new();
+        let mut threats = Vec::new();
+        for kind in [CanaryKind::Account, CanaryKind::Host] {
+            for field in template.fields(kind) {
+                let Some(value) = event.get(field).and_then(|v| v.as_str()) else { continue };
+                let Some(&index) = state.index.get(&(kind, kind.key(value))) else { continue };
+                if !hit.insert(index) {
+                    continue;
+                }
+                let asset = &state.assets[index];
+                threats.push(hit_threat(asset, &rule_id(asset, template), event_type, field, value, event, timestamp));
+            }
+        }
+        if !threats.is_empty() {
+            self.hits.fetch_add(threats.len() as u64, Ordering::Relaxed);
+            warn!("🪤 Canary use in {} event: {:?}", event_type, threats.iter().map(|t| &t.details[CANARY_ASSET_DETAIL]).collect::<Vec<_>>());
+        }
+        threats
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        let state = self.state.read().unwrap();
+        HashMap::from([
+            ("canary_assets".to_string(), state.assets.len() as f64),
+            ("canary_rules".to_string(), state.rules.len() as f64),
+            ("canary_hits_total".to_string(), self.hits.load(Ordering::Relaxed) as f64),
+        ])
+    }
+}
+
+fn hit_threat(asset: &CanaryAsset, rule_id: &str, event_type: &str, field: &str, value: &str, event: &serde_json::Value, timestamp: u64) -> AdvancedThreatResult {
+    let text = |name: &str| event.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    let (category, technique) = match asset.kind {
+        CanaryKind::Account => (ThreatCategory::Authentication, "T1078"),
+        CanaryKind::Host => (ThreatCategory::LateralMovement, "T1021"),
+    };
+    let mut details = attack::technique_details(&[technique.to_string()]);
+    details.insert(CANARY_ASSET_DETAIL.to_string(), asset.name.clone());
+    details.insert("canary_kind".to_string(), asset.kind.to_string());
+    details.insert("canary_rule".to_string(), rule_id.to_string());
+    details.insert("canary_field".to_string(), field.to_string());
+    if !asset.owner.is_empty() {
+        details.insert("canary_owner".to_string(), asset.owner.clone());
+    }
+    if let Some(tenant) = event.get("tenant").and_then(|v| v.as_str()) {
+        details.insert("tenant".to_string(), tenant.to_string());
+    }
+    let event_type = if event_type.is_empty() { "an" } else { event_type };
+    AdvancedThreatResult {
+        timestamp,
+        severity: ThreatSeverity::Critical,
+        category,
+        confidence: 1.0,
+        detection_method: "canary_asset".to_string(),
+        source_ip: text("source_ip"),
+        destination_ip: text("destination_ip"),
+        user_id: text("user"),
+        description: format!("Canary {} '{}' used: {} named in {} event field {}", asset.kind, asset.name, value, event_type, field),
+        signatures: vec![rule_id.to_string()],
+        details,
+        ..Default::default()
+    }
+}
+
+/// Whether `threat` is a canary asset hit, exempt from filtering and quiet hours
+pub fn is_canary_hit(threat: &AdvancedThreatResult) -> bool {
+    threat.details.contains_key(CANARY_ASSET_DETAIL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn registry(dir: &std::path::Path) -> CanaryRegistry {
+        let settings = CanaryAssetSettings {
+            enabled: true,
+            store_path: dir.join("canaries.json").to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        CanaryRegistry::open(settings).unwrap()
+    }
+
+    #[test]
+    fn test_registration_generates_rules_per_event_type_and_persists() {
+        let dir = std::env::temp_dir().join(format!("canary-assets-{}", uuid::Uuid::new_v4()));
+        let canaries = registry(&dir);
+        let asset = CanaryAsset { kind: CanaryKind::Account, name: "CORP\\svc_backup_old".to_string(), owner: "soc".to_string(), note: String::new(), registered_at: 0 };
+        let rules = canaries.register(asset.clone(), 1_000).unwrap();
+        assert_eq!(rules.len(), CanaryAssetSettings::default().templates.len());
+        assert!(rules.iter().any(|r| r.template == "kerberos" && r.fields.contains(&"service_name".to_string())));
+        assert!(canaries.register(asset, 2_000).is_err());
+        canaries.register(CanaryAsset { kind: CanaryKind::Host, name: "fin-db-legacy.corp.example".to_string(), owner: String::new(), note: String::new(), registered_at: 0 }, 1_000).unwrap();
+
+        // Hosts are not named by the account-only templates; reopened from disk the rules come back
+        let canaries = registry(&dir);
+        assert_eq!(canaries.assets()[0].registered_at, 1_000);
+        assert_eq!(canaries.rules().iter().filter(|r| r.kind == CanaryKind::Host).count(), 7);
+        assert!(canaries.remove(CanaryKind::Host, "FIN-DB-LEGACY").unwrap());
+        assert!(!canaries.remove(CanaryKind::Host, "fin-db-legacy").unwrap());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_any_use_is_a_critical_hit() {
+        let dir = std::env::temp_dir().join(format!("canary-assets-{}", uuid::Uuid::new_v4()));
+        let canaries = registry(&dir);
+        canaries.register(CanaryAsset { kind: CanaryKind::Account, name: "svc_backup_old".to_string(), owner: "soc".to_string(), note: String::new(), registered_at: 0 }, 0).unwrap();
+        canaries.register(CanaryAsset { kind: CanaryKind::Host, name: "10.9.9.9".to_string(), owner: String::new(), note: String::new(), registered_at: 0 }, 0).unwrap();
+
+        let logon = json!({ "event_type": "logon", "user": "svc_backup_old@CORP.EXAMPLE", "host": "10.9.9.9", "source_ip": "10.1.2.3" });
+        let threats = canaries.detect(&logon, 5);
+        assert_eq!(threats.len(), 2);
+        assert!(threats.iter().all(|t| t.severity == ThreatSeverity::Critical && is_canary_hit(t)));
+        assert_eq!(threats[0].details["canary_rule"], "canary_account_svc_backup_old_authentication");
+
+        // Unlisted event types use the fallback template; unrelated names never hit
+        let unknown = json!({ "event_type": "custom_audit", "target_user": "SVC_BACKUP_OLD" });
+        assert_eq!(canaries.detect(&unknown, 5)[0].details["canary_rule"], "canary_account_svc_backup_old_other");
+        assert!(canaries.detect(&json!({ "event_type": "logon", "user": "alice" }), 5).is_empty());
+        assert_eq!(canaries.get_metrics()["canary_hits_total"], 3.0);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_hits_pass_allow_lists_and_suppressions() {
+        use crate::advanced_threat_detection::{AdvancedThreatConfig, AdvancedThreatDetectionEngine};
+        use crate::suppression::{SuppressionList, SuppressionRequest, SuppressionScope, SuppressionSettings};
+
+        let dir = std::env::temp_dir().join(format!("canary-assets-{}", uuid::Uuid::new_v4()));
+        let canaries = std::sync::Arc::new(registry(&dir));
+        canaries.register(CanaryAsset { kind: CanaryKind::Account, name: "svc_backup_old".to_string(), owner: String::new(), note: String::new(), registered_at: 0 }, 0).unwrap();
+        let suppressions = std::sync::Arc::new(SuppressionList::open(SuppressionSettings {
+            store_path: dir.join("suppressions.json").to_string_lossy().into_owned(),
+            ..Default::default()
+        }).unwrap());
+        let request = SuppressionRequest { scope: SuppressionScope::Everywhere, days: 1, owner: "alice".to_string(), reason: "noisy".to_string() };
+        let rule_hit = AdvancedThreatResult { signatures: vec!["canary_account_svc_backup_old_authentication".to_string()], ..Default::default() };
+        suppressions.create(&rule_hit, "inc-1", &request, chrono::Utc::now()).unwrap();
+
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        engine.set_suppressions(suppressions);
+        engine.set_canary_assets(canaries);
+        engine.add_to_whitelist("10.1.2.3".to_string()).unwrap();
+        let threats = engine.process_event(json!({ "event_type": "logon", "user": "svc_backup_old", "source_ip": "10.1.2.3" })).await.unwrap();
+        assert_eq!(threats.len(), 1);
+        assert_eq!(threats[0].detection_method, "canary_asset");
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
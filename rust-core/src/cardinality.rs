@@ -0,0 +1,336 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::time;
+use crate::ip_net::parse_ip;
+
+/// Fixed-memory distinct counter; standard error is about `1.04 / sqrt(2^precision)`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    /// `precision` is clamped to 4..=16 (16 B to 64 KiB of registers)
+    pub fn new(precision: u8) -> Self {
+        let precision = precision.clamp(4, 16);
+        Self {
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        let hash = hash_with_seed(item, 0);
+        let index = (hash >> (64 - self.precision)) as usize;
+        let rest = (hash << self.precision) | (1 << (self.precision - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self.registers.iter().map(|r| 2f64.powi(-(*r as i32))).sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate for small cardinalities
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            raw.round() as u64
+        }
+    }
+}
+
+/// Fixed-memory frequency counter; never under-estimates
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    counters: Vec<u64>,
+}
+
+impl CountMinSketch {
+    pub fn new(width: usize, depth: usize) -> Self {
+        let (width, depth) = (width.max(1), depth.max(1));
+        Self {
+            width,
+            depth,
+            counters: vec![0; width * depth],
+        }
+    }
+
+    pub fn add<T: Hash + ?Sized>(&mut self, item: &T, count: u64) {
+        let cells: Vec<usize> = self.cells(item).collect();
+        for cell in cells {
+            self.counters[cell] = self.counters[cell].saturating_add(count);
+        }
+    }
+
+    #[cfg(test)]
+    pub fn estimate<T: Hash + ?Sized>(&self, item: &T) -> u64 {
+        self.cells(item).map(|cell| self.counters[cell]).min().unwrap_or(0)
+    }
+
+    pub fn clear(&mut self) {
+        self.counters.iter_mut().for_each(|counter| *counter = 0);
+    }
+
+    fn cells<T: Hash + ?Sized>(&self, item: &T) -> impl Iterator<Item = usize> + '_ {
+        // Kirsch-Mitzenmacher: row hashes derived from two base hashes
+        let (h1, h2) = (hash_with_seed(item, 1), hash_with_seed(item, 2));
+        (0..self.depth).map(move |row| row * self.width + (h1.wrapping_add((row as u64).wrapping_mul(h2)) % self.width as u64) as usize)
+    }
+}
+
+fn hash_with_seed<T: Hash + ?Sized>(item: &T, seed: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Cardinality metrics and fan-out detection settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CardinalitySettings {
+    pub enabled: bool,
+    /// Precision of the hourly unique source IP / user sketches
+    pub precision: u8,
+    /// Precision of the per-host destination sketches
+    pub fanout_precision: u8,
+    pub fanout_window_seconds: u64,
+    /// Distinct destinations per host and window that raise a detection
+    pub fanout_threshold: u64,
+    /// Only count public destination addresses towards fan-out
+    pub external_only: bool,
+    pub max_tracked_hosts: usize,
+    pub count_min_width: usize,
+    pub count_min_depth: usize,
+}
+
+impl Default for CardinalitySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            precision: 14,
+            fanout_precision: 10,
+            fanout_window_seconds: 600,
+            fanout_threshold: 500,
+            external_only: true,
+            max_tracked_hosts: 10_000,
+            count_min_width: 2048,
+            count_min_depth: 4,
+        }
+    }
+}
+
+/// A host crossed the fan-out threshold within the current window
+#[derive(Debug, Clone, PartialEq)]
+pub struct FanoutAlert {
+    pub host: String,
+    pub distinct_destinations: u64,
+    pub window_seconds: u64,
+}
+
+#[derive(Debug)]
+struct HourlySketches {
+    hour: u64,
+    sources: HyperLogLog,
+    users: HyperLogLog,
+    contacts: CountMinSketch,
+    previous_sources: u64,
+    previous_users: u64,
+}
+
+#[derive(Debug, Default)]
+struct FanoutWindow {
+    start: u64,
+    hosts: HashMap<String, (HyperLogLog, bool)>,
+    untracked_hosts: u64,
+}
+
+/// Bounded-memory unique counts over the event stream
+#[derive(Debug)]
+pub struct CardinalityTracker {
+    settings: CardinalitySettings,
+    hourly: Mutex<HourlySketches>,
+    fanout: Mutex<FanoutWindow>,
+}
+
+impl CardinalityTracker {
+    pub fn new(settings: CardinalitySettings) -> Self {
+        let hourly = HourlySketches {
+            hour: 0,
+            sources: HyperLogLog::new(settings.precision),
+            users: HyperLogLog::new(settings.precision),
+            contacts: CountMinSketch::new(settings.count_min_width, settings.count_min_depth),
+            previous_sources: 0,
+            previous_users: 0,
+        };
+        Self {
+            settings,
+            hourly: Mutex::new(hourly),
+            fanout: Mutex::new(FanoutWindow::default()),
+        }
+    }
+
+    pub fn observe(&self, event: &serde_json::Value) -> Option<FanoutAlert> {
+        self.observe_at(event, time::current_timestamp().unwrap_or_default())
+    }
+
+    /// Count `event` at `now`; returns an alert the first time its host crosses the fan-out threshold
+    pub fn observe_at(&self, event: &serde_json::Value, now: u64) -> Option<FanoutAlert> {
+        let field = |name: &str| event.get(name).and_then(|v| v.as_str()).filter(|v| !v.is_empty());
+        let source = field("source_ip").or_else(|| field("host"));
+        let destination = field("destination_ip");
+
+        {
+            let mut hourly = self.hourly.lock().unwrap();
+            if now / 3600 != hourly.hour {
+                hourly.previous_sources = if now / 3600 == hourly.hour + 1 { hourly.sources.estimate() } else { 0 };
+                hourly.previous_users = if now / 3600 == hourly.hour + 1 { hourly.users.estimate() } else { 0 };
+                hourly.hour = now / 3600;
+                hourly.sources = HyperLogLog::new(self.settings.precision);
+                hourly.users = HyperLogLog::new(self.settings.precision);
+                hourly.contacts.clear();
+            }
+            if let Some(source) = source {
+                hourly.sources.insert(source);
+            }
+            if let Some(user) = field("user_id") {
+                hourly.users.insert(user);
+            }
+            if let Some(destination) = destination {
+                hourly.contacts.add(destination, 1);
+            }
+        }
+
+        let (host, destination) = (source?, destination?);
+        if self.settings.external_only && !is_external(destination) {
+            return None;
+        }
+        let mut fanout = self.fanout.lock().unwrap();
+        let window = self.settings.fanout_window_seconds.max(1);
+        if now / window * window != fanout.start {
+            *fanout = FanoutWindow { start: now / window * window, ..Default::default() };
+        }
+        if !fanout.hosts.contains_key(host) && fanout.hosts.len() >= self.settings.max_tracked_hosts {
+            fanout.untracked_hosts += 1;
+            return None;
+        }
+        let (sketch, alerted) = fanout
+            .hosts
+            .entry(host.to_string())
+            .or_insert_with(|| (HyperLogLog::new(self.settings.fanout_precision), false));
+        sketch.insert(destination);
+        if *alerted {
+            return None;
+        }
+        let distinct_destinations = sketch.estimate();
+        if distinct_destinations <= self.settings.fanout_threshold {
+            return None;
+        }
+        *alerted = true;
+        Some(FanoutAlert {
+            host: host.to_string(),
+            distinct_destinations,
+            window_seconds: window,
+        })
+    }
+
+    /// Estimated events towards `destination` in the current hour
+    #[cfg(test)]
+    pub fn contacts_to(&self, destination: &str) -> u64 {
+        self.hourly.lock().unwrap().contacts.estimate(destination)
+    }
+
+    /// Estimated distinct destinations of `host` in the current fan-out window
+    #[cfg(test)]
+    pub fn fanout_of(&self, host: &str) -> u64 {
+        self.fanout.lock().unwrap().hosts.get(host).map_or(0, |(sketch, _)| sketch.estimate())
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        let hourly = self.hourly.lock().unwrap();
+        let fanout = self.fanout.lock().unwrap();
+        let max_fanout = fanout.hosts.values().map(|(sketch, _)| sketch.estimate()).max().unwrap_or(0);
+        HashMap::from([
+            ("cardinality_unique_source_ips_hour".to_string(), hourly.sources.estimate() as f64),
+            ("cardinality_unique_users_hour".to_string(), hourly.users.estimate() as f64),
+            ("cardinality_unique_source_ips_previous_hour".to_string(), hourly.previous_sources as f64),
+            ("cardinality_unique_users_previous_hour".to_string(), hourly.previous_users as f64),
+            ("cardinality_fanout_tracked_hosts".to_string(), fanout.hosts.len() as f64),
+            ("cardinality_fanout_untracked_events".to_string(), fanout.untracked_hosts as f64),
+            ("cardinality_fanout_max".to_string(), max_fanout as f64),
+        ])
+    }
+}
+
+pub(crate) fn is_external(address: &str) -> bool {
+    match parse_ip(address) {
+        Some(IpAddr::V4(ip)) => !(ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast()),
+        Some(IpAddr::V6(ip)) => !(ip.is_loopback() || ip.is_unspecified() || (ip.segments()[0] & 0xfe00) == 0xfc00 || (ip.segments()[0] & 0xffc0) == 0xfe80),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sketch_estimates_within_error_bounds() {
+        let mut hll = HyperLogLog::new(14);
+        let mut cms = CountMinSketch::new(2048, 4);
+        for i in 0..100_000u32 {
+            hll.insert(&format!("10.{}.{}.{}", i >> 16, (i >> 8) & 0xff, i & 0xff));
+            cms.add(&(i % 100), 1);
+        }
+        let estimate = hll.estimate() as f64;
+        assert!((estimate - 100_000.0).abs() / 100_000.0 < 0.03, "estimate {}", estimate);
+
+        let mut small = HyperLogLog::new(14);
+        ["a", "b", "c", "a"].iter().for_each(|v| small.insert(*v));
+        assert_eq!(small.estimate(), 3);
+
+        assert!(cms.estimate(&7u32) >= 1000);
+        assert!(cms.estimate(&7u32) < 1100);
+    }
+
+    #[test]
+    fn test_fanout_alert_fires_once_per_window() {
+        let tracker = CardinalityTracker::new(CardinalitySettings {
+            enabled: true,
+            fanout_threshold: 50,
+            ..Default::default()
+        });
+        let now = 1_700_000_000;
+        let mut alerts = Vec::new();
+        for i in 0..200u32 {
+            let event = serde_json::json!({ "source_ip": "10.0.0.5", "destination_ip": format!("203.0.{}.{}", i / 250, i % 250) });
+            alerts.extend(tracker.observe_at(&event, now));
+            // Internal destinations never count towards fan-out
+            let internal = serde_json::json!({ "source_ip": "10.0.0.6", "destination_ip": format!("10.1.0.{}", i % 250) });
+            assert!(tracker.observe_at(&internal, now).is_none());
+        }
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].host, "10.0.0.5");
+        assert!(tracker.fanout_of("10.0.0.5") > 180);
+        assert_eq!(tracker.get_metrics()["cardinality_unique_source_ips_hour"], 2.0);
+        assert_eq!(tracker.contacts_to("203.0.0.7"), 1);
+    }
+}
@@ -0,0 +1,561 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+#[cfg(feature = "response")]
+use std::sync::Arc;
+#[cfg(feature = "response")]
+use std::time::Duration;
+#[cfg(feature = "response")]
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::attack;
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::cardinality::is_external;
+#[cfg(feature = "response")]
+use crate::error_handling::SIEMResult;
+#[cfg(feature = "response")]
+use crate::incident_response::IncidentResponseEngine;
+use crate::threat_detection::{ThreatCategory, ThreatSeverity};
+
+/// `event_type` of ingested TLS handshake metadata
+pub const TLS_EVENT_TYPE: &str = "tls_handshake";
+
+/// Brand look-alikes, unexpected issuers and self-signed internal certificates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CertMonitorSettings {
+    pub enabled: bool,
+    /// Lowercase brand names whose look-alike domains are reported, e.g. `acme`
+    pub brands: Vec<String>,
+    /// Domains owned by the company; certificates for them must come from `trusted_issuers`
+    pub company_domains: Vec<String>,
+    /// Issuer substrings, matched case-insensitively; empty disables the issuer check
+    pub trusted_issuers: Vec<String>,
+    /// Domains of internal services, in addition to private addresses
+    pub internal_domains: Vec<String>,
+    /// Edits after homoglyph folding at which a label still counts as the brand; brands under 5 characters need an exact fold
+    pub max_edit_distance: usize,
+    /// Certificates remembered so each is reported once
+    pub max_tracked_certificates: usize,
+    /// Poll the crt.sh certificate transparency search for company domains and brands
+    pub ct_enabled: bool,
+    pub ct_endpoint: String,
+    pub ct_poll_interval_minutes: u64,
+    /// Certificates issued before this on the first poll are treated as history
+    pub ct_lookback_hours: u64,
+}
+
+impl Default for CertMonitorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            brands: Vec::new(),
+            company_domains: Vec::new(),
+            trusted_issuers: Vec::new(),
+            internal_domains: Vec::new(),
+            max_edit_distance: 1,
+            max_tracked_certificates: 50_000,
+            ct_enabled: false,
+            ct_endpoint: "https://crt.sh".to_string(),
+            ct_poll_interval_minutes: 60,
+            ct_lookback_hours: 24,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CertSource {
+    /// Certificate transparency log entry
+    Ct,
+    /// Certificate presented in an ingested TLS handshake
+    Tls,
+}
+
+impl CertSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CertSource::Ct => "ct",
+            CertSource::Tls => "tls",
+        }
+    }
+}
+
+/// Certificate fields shared by CT entries and TLS handshake events
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertificateInfo {
+    pub source: CertSource,
+    pub subject: String,
+    pub issuer: String,
+    /// Lowercase SNI, common name and SANs
+    pub names: Vec<String>,
+    pub serial: String,
+    pub fingerprint: String,
+    pub not_before: String,
+    pub not_after: String,
+    pub self_signed: bool,
+    /// Address of the service presenting the certificate; empty for CT entries
+    pub server_ip: String,
+    pub client_ip: String,
+}
+
+impl CertificateInfo {
+    /// `tls_handshake` events, or any event carrying `tls_issuer`
+    pub fn from_tls_event(event: &serde_json::Value) -> Option<Self> {
+        let text = |name: &str| event.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let is_tls = event.get("event_type").and_then(|v| v.as_str()) == Some(TLS_EVENT_TYPE);
+        if !is_tls && event.get("tls_issuer").is_none() {
+            return None;
+        }
+        let (subject, issuer) = (text("tls_subject"), text("tls_issuer"));
+        let mut names = vec![text("server_name")];
+        match event.get("tls_san") {
+            Some(serde_json::Value::Array(items)) => names.extend(items.iter().filter_map(|v| v.as_str()).map(str::to_string)),
+            Some(serde_json::Value::String(list)) => names.extend(list.split([',', ' ']).map(str::to_string)),
+            _ => {}
+        }
+        names.extend(common_name(&subject));
+        let self_signed = event
+            .get("tls_self_signed")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(!issuer.is_empty() && subject == issuer);
+        Some(Self {
+            source: CertSource::Tls,
+            names: normalize_names(names),
+            serial: text("tls_serial"),
+            fingerprint: text("tls_fingerprint"),
+            not_before: text("tls_not_before"),
+            not_after: text("tls_not_after"),
+            self_signed,
+            server_ip: text("destination_ip"),
+            client_ip: text("source_ip"),
+            subject,
+            issuer,
+        })
+    }
+
+    /// Identity used to report a certificate once
+    fn key(&self) -> String {
+        match self.fingerprint.is_empty() {
+            true => format!("{}|{}|{}", self.issuer, self.serial, self.names.join(",")),
+            false => self.fingerprint.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CertAlertKind {
+    LookalikeDomain,
+    UnknownIssuer,
+    SelfSignedInternal,
+}
+
+impl CertAlertKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CertAlertKind::LookalikeDomain => "lookalike_domain",
+            CertAlertKind::UnknownIssuer => "unknown_issuer",
+            CertAlertKind::SelfSignedInternal => "self_signed_internal",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertAlert {
+    pub kind: CertAlertKind,
+    pub certificate: CertificateInfo,
+    /// Brand imitated, company name covered, or internal service
+    pub matched: String,
+}
+
+impl CertAlert {
+    pub fn to_threat(&self, timestamp: u64) -> AdvancedThreatResult {
+        let cert = &self.certificate;
+        let (category, technique, description) = match self.kind {
+            CertAlertKind::LookalikeDomain => (
+                ThreatCategory::Other,
+                "T1583.001",
+                format!("Certificate for look-alike domain of brand {}: {}", self.matched, cert.names.join(", ")),
+            ),
+            CertAlertKind::UnknownIssuer => (
+                ThreatCategory::Other,
+                "T1588.004",
+                format!("Certificate for {} issued by unexpected CA {}", self.matched, cert.issuer),
+            ),
+            CertAlertKind::SelfSignedInternal => (
+                ThreatCategory::Network,
+                "T1587.003",
+                format!("Self-signed certificate on internal service {}", self.matched),
+            ),
+        };
+        let mut details = attack::technique_details(&[technique.to_string()]);
+        details.insert("cert_alert".to_string(), self.kind.as_str().to_string());
+        details.insert("cert_source".to_string(), cert.source.as_str().to_string());
+        details.insert("cert_subject".to_string(), cert.subject.clone());
+        details.insert("cert_issuer".to_string(), cert.issuer.clone());
+        details.insert("cert_names".to_string(), cert.names.join(","));
+        details.insert("cert_serial".to_string(), cert.serial.clone());
+        details.insert("cert_fingerprint".to_string(), cert.fingerprint.clone());
+        details.insert("cert_not_before".to_string(), cert.not_before.clone());
+        details.insert("cert_not_after".to_string(), cert.not_after.clone());
+        details.insert("cert_matched".to_string(), self.matched.clone());
+        AdvancedThreatResult {
+            timestamp,
+            severity: ThreatSeverity::Medium,
+            category,
+            confidence: 0.7,
+            detection_method: "cert_monitor".to_string(),
+            source_ip: cert.client_ip.clone(),
+            destination_ip: cert.server_ip.clone(),
+            description,
+            iocs: cert.names.clone(),
+            details,
+            ..Default::default()
+        }
+    }
+}
+
+/// Bounded set of certificates already reported; the oldest are forgotten first
+#[derive(Debug, Default)]
+struct SeenCertificates {
+    keys: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl SeenCertificates {
+    /// `true` the first time `key` is seen
+    fn insert(&mut self, key: String, capacity: usize) -> bool {
+        if self.keys.contains(&key) {
+            return false;
+        }
+        if self.order.len() >= capacity.max(1) {
+            if let Some(oldest) = self.order.pop_front() {
+                self.keys.remove(&oldest);
+            }
+        }
+        self.keys.insert(key.clone());
+        self.order.push_back(key);
+        true
+    }
+}
+
+/// Checks certificates from CT logs and TLS handshakes against the company's brands and domains
+#[derive(Debug)]
+pub struct CertMonitor {
+    settings: CertMonitorSettings,
+    seen: Mutex<SeenCertificates>,
+    /// Highest crt.sh entry id per query
+    #[cfg(feature = "response")]
+    ct_cursors: Mutex<HashMap<String, u64>>,
+}
+
+impl CertMonitor {
+    pub fn new(settings: CertMonitorSettings) -> Self {
+        Self {
+            settings,
+            seen: Mutex::new(SeenCertificates::default()),
+            #[cfg(feature = "response")]
+            ct_cursors: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn observe(&self, event: &serde_json::Value) -> Vec<CertAlert> {
+        CertificateInfo::from_tls_event(event).map(|cert| self.check(&cert)).unwrap_or_default()
+    }
+
+    /// Alerts for `cert`, each kind reported once per certificate
+    pub fn check(&self, cert: &CertificateInfo) -> Vec<CertAlert> {
+        let alert = |kind, matched: &str| CertAlert { kind, certificate: cert.clone(), matched: matched.to_string() };
+        let mut alerts = Vec::new();
+        if let Some((name, brand)) = cert.names.iter().find_map(|name| self.lookalike_brand(name).map(|brand| (name, brand))) {
+            alerts.push(alert(CertAlertKind::LookalikeDomain, &format!("{} ({})", brand, name)));
+        }
+        let internal_service = match cert.source {
+            CertSource::Tls if cert.self_signed => self.internal_service(cert),
+            _ => None,
+        };
+        match internal_service {
+            Some(service) => alerts.push(alert(CertAlertKind::SelfSignedInternal, &service)),
+            None => {
+                let covered = cert.names.iter().find(|name| self.is_company_name(name));
+                if let Some(name) = covered.filter(|_| !self.is_trusted_issuer(&cert.issuer)) {
+                    alerts.push(alert(CertAlertKind::UnknownIssuer, name));
+                }
+            }
+        }
+
+        let mut seen = self.seen.lock().unwrap();
+        alerts.retain(|a| seen.insert(format!("{}|{}", a.kind.as_str(), cert.key()), self.settings.max_tracked_certificates));
+        alerts
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        HashMap::from([("cert_monitor_tracked_certificates".to_string(), self.seen.lock().unwrap().order.len() as f64)])
+    }
+
+    fn is_company_name(&self, name: &str) -> bool {
+        under_any(name, &self.settings.company_domains)
+    }
+
+    fn is_trusted_issuer(&self, issuer: &str) -> bool {
+        let issuer = issuer.to_lowercase();
+        self.settings.trusted_issuers.is_empty()
+            || self.settings.trusted_issuers.iter().any(|trusted| issuer.contains(&trusted.to_lowercase()))
+    }
+
+    /// Address or name of the presenting service when it is internal
+    fn internal_service(&self, cert: &CertificateInfo) -> Option<String> {
+        let by_name = cert.names.iter().find(|name| under_any(name, &self.settings.internal_domains)).cloned();
+        let by_address = (!cert.server_ip.is_empty() && cert.server_ip.parse::<std::net::IpAddr>().is_ok() && !is_external(&cert.server_ip))
+            .then(|| cert.server_ip.clone());
+        by_name.or(by_address)
+    }
+
+    /// Brand imitated by a label of `name` outside the company's own domains
+    fn lookalike_brand(&self, name: &str) -> Option<&str> {
+        if self.is_company_name(name) {
+            return None;
+        }
+        let tokens: Vec<&str> = name.split('.').flat_map(|label| std::iter::once(label).chain(label.split('-'))).collect();
+        self.settings.brands.iter().map(String::as_str).find(|brand| {
+            let brand = brand.to_lowercase();
+            tokens.iter().any(|token| {
+                let folded = fold_homoglyphs(token);
+                let tolerance = if brand.len() >= 5 { self.settings.max_edit_distance } else { 0 };
+                token.contains(brand.as_str()) || edit_distance(&folded, &brand) <= tolerance
+            })
+        })
+    }
+}
+
+#[cfg(feature = "response")]
+impl CertMonitor {
+    /// crt.sh searches: every name under the company domains, and any name containing a brand
+    fn ct_queries(&self) -> Vec<String> {
+        let domains = self.settings.company_domains.iter().map(|d| format!("%.{}", d.trim_start_matches('.')));
+        domains.chain(self.settings.brands.iter().map(|b| format!("%{}%", b))).collect()
+    }
+
+    /// Fetch CT entries newer than the last poll of each query and raise one incident per alert
+    pub async fn poll_ct(&self, client: &reqwest::Client, incidents: &IncidentResponseEngine) -> SIEMResult<usize> {
+        let mut raised = 0;
+        for query in self.ct_queries() {
+            let entries: Vec<CtLogEntry> = client
+                .get(&self.settings.ct_endpoint)
+                .query(&[("q", query.as_str()), ("output", "json"), ("exclude", "expired")])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            let cursor = self.ct_cursors.lock().unwrap().get(&query).copied();
+            let cutoff = chrono::Utc::now().naive_utc() - chrono::Duration::hours(self.settings.ct_lookback_hours as i64);
+            let fresh = entries.iter().filter(|entry| match cursor {
+                Some(cursor) => entry.id > cursor,
+                None => entry.issued_at().is_some_and(|issued| issued >= cutoff),
+            });
+            let alerts: Vec<CertAlert> = fresh.flat_map(|entry| self.check(&entry.certificate())).collect();
+            if let Some(latest) = entries.iter().map(|entry| entry.id).max() {
+                self.ct_cursors.lock().unwrap().insert(query.clone(), latest.max(cursor.unwrap_or_default()));
+            }
+            for alert in alerts {
+                let timestamp = crate::error_handling::time::current_timestamp().unwrap_or_default();
+                incidents.process_threat(alert.to_threat(timestamp)).await?;
+                raised += 1;
+            }
+        }
+        Ok(raised)
+    }
+
+    /// Poll certificate transparency every `ct_poll_interval_minutes`; does nothing unless enabled
+    pub fn spawn_ct_poller(self: Arc<Self>, incidents: Arc<IncidentResponseEngine>) {
+        if !self.settings.enabled || !self.settings.ct_enabled || self.ct_queries().is_empty() {
+            return;
+        }
+        tokio::spawn(async move {
+            let client = match reqwest::Client::builder().timeout(Duration::from_secs(60)).build() {
+                Ok(client) => client,
+                Err(e) => return warn!("⚠️ Certificate transparency polling disabled: {}", e),
+            };
+            info!("🔏 Polling certificate transparency at {} for {} queries", self.settings.ct_endpoint, self.ct_queries().len());
+            let mut interval = tokio::time::interval(Duration::from_secs(self.settings.ct_poll_interval_minutes.max(1) * 60));
+            loop {
+                interval.tick().await;
+                match self.poll_ct(&client, &incidents).await {
+                    Ok(0) => {}
+                    Ok(raised) => info!("🔏 {} certificate transparency incidents raised", raised),
+                    Err(e) => warn!("⚠️ Certificate transparency poll failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+/// One row of the crt.sh JSON search output
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CtLogEntry {
+    pub id: u64,
+    pub issuer_name: String,
+    pub common_name: String,
+    /// Newline-separated names of the certificate
+    pub name_value: String,
+    pub serial_number: String,
+    pub not_before: String,
+    pub not_after: String,
+}
+
+impl CtLogEntry {
+    pub fn certificate(&self) -> CertificateInfo {
+        let names = std::iter::once(self.common_name.clone()).chain(self.name_value.lines().map(str::to_string)).collect();
+        CertificateInfo {
+            source: CertSource::Ct,
+            subject: format!("CN={}", self.common_name),
+            issuer: self.issuer_name.clone(),
+            names: normalize_names(names),
+            serial: self.serial_number.clone(),
+            fingerprint: String::new(),
+            not_before: self.not_before.clone(),
+            not_after: self.not_after.clone(),
+            self_signed: false,
+            server_ip: String::new(),
+            client_ip: String::new(),
+        }
+    }
+
+    fn issued_at(&self) -> Option<chrono::NaiveDateTime> {
+        chrono::NaiveDateTime::parse_from_str(&self.not_before, "%Y-%m-%dT%H:%M:%S").ok()
+    }
+}
+
+/// `name` equals one of `domains` or is a subdomain of one
+fn under_any(name: &str, domains: &[String]) -> bool {
+    domains.iter().any(|domain| {
+        let domain = domain.trim_start_matches('.').to_lowercase();
+        !domain.is_empty() && (name == domain || name.ends_with(&format!(".{}", domain)))
+    })
+}
+
+fn common_name(subject: &str) -> Option<String> {
+    subject
+        .split([',', '/'])
+        .find_map(|part| part.trim().strip_prefix("CN="))
+        .map(str::to_string)
+}
+
+/// Lowercase, wildcard-stripped, deduplicated names
+fn normalize_names(names: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for name in names {
+        let name = name.trim().trim_start_matches("*.").trim_end_matches('.').to_lowercase();
+        if !name.is_empty() && !normalized.contains(&name) {
+            normalized.push(name);
+        }
+    }
+    normalized
+}
+
+/// Undo common look-alike substitutions, e.g. `rn` for `m` and `0` for `o`
+fn fold_homoglyphs(label: &str) -> String {
+    let folded = label.to_lowercase().replace("rn", "m").replace("vv", "w");
+    folded
+        .chars()
+        .map(|c| match c {
+            '0' => 'o',
+            '1' => 'l',
+            '3' => 'e',
+            '4' => 'a',
+            '5' => 's',
+            '7' => 't',
+            other => other,
+        })
+        .collect()
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> CertMonitor {
+        CertMonitor::new(CertMonitorSettings {
+            enabled: true,
+            brands: vec!["acmebank".to_string()],
+            company_domains: vec!["acmebank.com".to_string()],
+            trusted_issuers: vec!["Let's Encrypt".to_string(), "DigiCert".to_string()],
+            internal_domains: vec!["corp.acmebank.com".to_string()],
+            ..Default::default()
+        })
+    }
+
+    fn ct_entry(id: u64, names: &str, issuer: &str) -> CtLogEntry {
+        CtLogEntry {
+            id,
+            issuer_name: issuer.to_string(),
+            common_name: names.lines().next().unwrap_or_default().to_string(),
+            name_value: names.to_string(),
+            serial_number: format!("0{}", id),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_lookalikes_and_unknown_issuers_from_ct() {
+        let monitor = monitor();
+        let kinds = |entry: CtLogEntry| monitor.check(&entry.certificate()).iter().map(|a| a.kind).collect::<Vec<_>>();
+
+        assert_eq!(kinds(ct_entry(1, "acrnebank-login.com", "C=US, O=Let's Encrypt, CN=R3")), vec![CertAlertKind::LookalikeDomain]);
+        assert_eq!(kinds(ct_entry(2, "secure.acmebamk.net", "C=US, O=Let's Encrypt, CN=R3")), vec![CertAlertKind::LookalikeDomain]);
+        assert_eq!(kinds(ct_entry(3, "www.acmebank.com\nacmebank.com", "C=US, O=DigiCert Inc")), vec![]);
+        assert_eq!(kinds(ct_entry(4, "*.acmebank.com", "C=XX, O=Cheap Certs Ltd")), vec![CertAlertKind::UnknownIssuer]);
+        assert_eq!(kinds(ct_entry(5, "example.org", "C=XX, O=Cheap Certs Ltd")), vec![]);
+        // The same certificate is reported once
+        assert_eq!(kinds(ct_entry(4, "*.acmebank.com", "C=XX, O=Cheap Certs Ltd")), vec![]);
+
+        let threat = monitor.check(&ct_entry(6, "acmebank.com.verify-account.io", "O=Let's Encrypt").certificate()).remove(0).to_threat(0);
+        assert_eq!(threat.severity, ThreatSeverity::Medium);
+        assert_eq!(threat.details["cert_names"], "acmebank.com.verify-account.io");
+        assert_eq!(threat.details["cert_issuer"], "O=Let's Encrypt");
+    }
+
+    #[test]
+    fn test_self_signed_certificates_on_internal_services() {
+        let monitor = monitor();
+        let handshake = |server_name: &str, server_ip: &str| {
+            serde_json::json!({
+                "event_type": TLS_EVENT_TYPE,
+                "source_ip": "10.0.0.5",
+                "destination_ip": server_ip,
+                "server_name": server_name,
+                "tls_subject": format!("CN={}", server_name),
+                "tls_issuer": format!("CN={}", server_name),
+                "tls_fingerprint": format!("sha256:{}", server_name),
+            })
+        };
+
+        let alerts = monitor.observe(&handshake("grafana.corp.acmebank.com", "203.0.113.10"));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!((alerts[0].kind, alerts[0].matched.as_str()), (CertAlertKind::SelfSignedInternal, "grafana.corp.acmebank.com"));
+        let alerts = monitor.observe(&handshake("printer", "192.168.1.20"));
+        assert_eq!((alerts[0].kind, alerts[0].matched.as_str()), (CertAlertKind::SelfSignedInternal, "192.168.1.20"));
+        // Self-signed on the internet is not an internal service
+        assert!(monitor.observe(&handshake("example.org", "198.51.100.7")).is_empty());
+        assert!(monitor.observe(&serde_json::json!({ "event_type": "http_request" })).is_empty());
+        assert_eq!(edit_distance("acmebnak", "acmebank"), 2);
+    }
+}
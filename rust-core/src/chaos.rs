@@ -0,0 +1,321 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use log::warn;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// Fault injection settings for resilience testing; never enable in production
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChaosSettings {
+    pub enabled: bool,
+    /// Fixed seed for reproducible runs
+    pub seed: Option<u64>,
+    /// Probability that a NATS publish fails as if the connection dropped
+    pub nats_disconnect_probability: f64,
+    pub sink_delay_probability: f64,
+    pub sink_delay_ms: u64,
+    /// Fraction of ingested events that are corrupted before detection
+    pub event_corruption_rate: f64,
+    /// Probability that an instrumented task panics at a checkpoint
+    pub task_panic_probability: f64,
+    /// Invariant: highest tolerated fraction of events lost during a run
+    pub max_event_loss_rate: f64,
+}
+
+impl Default for ChaosSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            seed: None,
+            nats_disconnect_probability: 0.0,
+            sink_delay_probability: 0.0,
+            sink_delay_ms: 250,
+            event_corruption_rate: 0.0,
+            task_panic_probability: 0.0,
+            max_event_loss_rate: 0.01,
+        }
+    }
+}
+
+/// Faults injected so far
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChaosStats {
+    pub nats_disconnects: u64,
+    pub sink_delays: u64,
+    pub events_corrupted: u64,
+    pub task_panics: u64,
+    pub task_restarts: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    nats_disconnects: AtomicU64,
+    sink_delays: AtomicU64,
+    events_corrupted: AtomicU64,
+    task_panics: AtomicU64,
+    task_restarts: AtomicU64,
+}
+
+/// Shared fault source handed to the components under test
+#[derive(Debug, Clone)]
+pub struct FaultInjector {
+    settings: ChaosSettings,
+    rng: Arc<Mutex<StdRng>>,
+    counters: Arc<Counters>,
+}
+
+impl FaultInjector {
+    pub fn new(settings: ChaosSettings) -> Self {
+        if settings.enabled {
+            warn!("⚠️ Chaos fault injection is ENABLED; do not run this build in production");
+        }
+        let rng = match settings.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self {
+            settings,
+            rng: Arc::new(Mutex::new(rng)),
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    pub fn settings(&self) -> &ChaosSettings {
+        &self.settings
+    }
+
+    fn roll(&self, probability: f64) -> bool {
+        self.settings.enabled && probability > 0.0 && self.rng.lock().unwrap().gen_bool(probability.min(1.0))
+    }
+
+    /// Fail a NATS operation as if the connection had dropped
+    pub fn nats_fault(&self) -> SIEMResult<()> {
+        if self.roll(self.settings.nats_disconnect_probability) {
+            self.counters.nats_disconnects.fetch_add(1, Ordering::Relaxed);
+            return Err(SIEMError::Other("chaos: injected NATS disconnect".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Stall a sink write
+    pub async fn delay_sink_write(&self) {
+        if self.roll(self.settings.sink_delay_probability) {
+            self.counters.sink_delays.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(Duration::from_millis(self.settings.sink_delay_ms)).await;
+        }
+    }
+
+    /// Corrupt an event in place; returns whether it was corrupted
+    pub fn corrupt_event(&self, event: &mut serde_json::Value) -> bool {
+        if !self.roll(self.settings.event_corruption_rate) {
+            return false;
+        }
+        self.counters.events_corrupted.fetch_add(1, Ordering::Relaxed);
+        let fields: Vec<String> = event.as_object().map(|o| o.keys().cloned().collect()).unwrap_or_default();
+        if fields.is_empty() {
+            *event = serde_json::Value::String("\u{fffd}".repeat(8));
+            return true;
+        }
+        let mut rng = self.rng.lock().unwrap();
+        let field = &fields[rng.gen_range(0..fields.len())];
+        let garbage = match rng.gen_range(0..3) {
+            0 => serde_json::Value::Null,
+            1 => serde_json::Value::from(rng.gen::<i64>()),
+            _ => serde_json::Value::String("\u{fffd}".repeat(4)),
+        };
+        event[field.as_str()] = garbage;
+        true
+    }
+
+    /// Panic at a checkpoint inside an instrumented task
+    pub fn maybe_panic(&self, task: &str) {
+        if self.roll(self.settings.task_panic_probability) {
+            self.counters.task_panics.fetch_add(1, Ordering::Relaxed);
+            panic!("chaos: injected panic in task '{}'", task);
+        }
+    }
+
+    pub fn stats(&self) -> ChaosStats {
+        ChaosStats {
+            nats_disconnects: self.counters.nats_disconnects.load(Ordering::Relaxed),
+            sink_delays: self.counters.sink_delays.load(Ordering::Relaxed),
+            events_corrupted: self.counters.events_corrupted.load(Ordering::Relaxed),
+            task_panics: self.counters.task_panics.load(Ordering::Relaxed),
+            task_restarts: self.counters.task_restarts.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Check that a run stayed within the configured loss bound and recovered from every panic
+    pub fn check_invariants(&self, events_sent: u64, events_delivered: u64) -> Result<(), Vec<String>> {
+        let mut violations = Vec::new();
+        if events_delivered > events_sent {
+            violations.push(format!("delivered {} events but only {} were sent", events_delivered, events_sent));
+        }
+        let lost = events_sent.saturating_sub(events_delivered);
+        let loss_rate = if events_sent == 0 { 0.0 } else { lost as f64 / events_sent as f64 };
+        if loss_rate > self.settings.max_event_loss_rate {
+            violations.push(format!(
+                "lost {} of {} events ({:.2}%), bound is {:.2}%",
+                lost,
+                events_sent,
+                loss_rate * 100.0,
+                self.settings.max_event_loss_rate * 100.0
+            ));
+        }
+        let stats = self.stats();
+        if stats.task_restarts < stats.task_panics {
+            violations.push(format!(
+                "{} injected panics but only {} task restarts",
+                stats.task_panics, stats.task_restarts
+            ));
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Run a task to completion, restarting it whenever it panics
+    pub fn spawn_supervised<F, Fut>(&self, name: &str, mut factory: F) -> tokio::task::JoinHandle<()>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let name = name.to_string();
+        let counters = self.counters.clone();
+        tokio::spawn(async move {
+            loop {
+                match tokio::spawn(factory()).await {
+                    Err(e) if e.is_panic() => {
+                        counters.task_restarts.fetch_add(1, Ordering::Relaxed);
+                        warn!("⚠️ Task '{}' panicked; restarting", name);
+                    }
+                    _ => break,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+    use crate::advanced_threat_detection::{AdvancedThreatConfig, AdvancedThreatDetectionEngine};
+
+    /// Source IPs of the detections that reached the sink
+    #[cfg(feature = "response")]
+    #[derive(Default)]
+    struct RecordingSink {
+        sources: Mutex<std::collections::HashSet<String>>,
+    }
+
+    #[cfg(feature = "response")]
+    impl crate::output_sinks::RecordSink for RecordingSink {
+        fn write<'a>(&'a self, records: &'a [crate::output_sinks::SinkRecord]) -> futures_util::future::BoxFuture<'a, SIEMResult<()>> {
+            let mut sources = self.sources.lock().unwrap();
+            for record in records {
+                if let Some(ip) = record.fields.get("source.ip").and_then(|ip| ip.as_str()) {
+                    sources.insert(ip.to_string());
+                }
+            }
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[cfg(feature = "response")]
+    #[tokio::test]
+    async fn test_chaos_run_keeps_loss_within_bound_at_the_sink() {
+        use crate::incident_response::{AlertConfig, IncidentResponseEngine, SOARConfig};
+        use crate::output_sinks::{OutputSinks, SinkKind, SinkSettings};
+
+        let injector = FaultInjector::new(ChaosSettings {
+            enabled: true,
+            seed: Some(7),
+            event_corruption_rate: 0.2,
+            sink_delay_probability: 0.2,
+            sink_delay_ms: 5,
+            task_panic_probability: 0.3,
+            max_event_loss_rate: 0.35,
+            ..Default::default()
+        });
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        engine.set_fault_injector(injector.clone());
+        let recorded = Arc::new(RecordingSink::default());
+        let settings = SinkSettings {
+            enabled: true,
+            detections: vec![SinkKind::Loki],
+            batch_size: 5,
+            flush_interval_seconds: 1,
+            ..Default::default()
+        };
+        let mut sinks = OutputSinks::with_sinks(settings, vec![(SinkKind::Loki, recorded.clone())]);
+        sinks.set_fault_injector(injector.clone());
+        let sinks = Arc::new(sinks);
+        let (detections, receiver) = tokio::sync::broadcast::channel(4096);
+        let incidents = Arc::new(IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled()));
+        let writer = sinks.clone().spawn(Some(receiver), incidents).unwrap();
+
+        // One malicious event per source, so every source should surface at the sink
+        let sent = 200u64;
+        for i in 0..sent {
+            let event = serde_json::json!({
+                "source_ip": format!("10.0.{}.{}", i / 100, i % 100),
+                "timestamp": 1_700_000_000 + i,
+                "message": "GET /?id=1 UNION SELECT password FROM users",
+            });
+            for threat in engine.process_event(event).await.unwrap_or_default() {
+                detections.send(threat).unwrap();
+            }
+        }
+        let drained = async {
+            while !detections.is_empty() || sinks.queue_samples().iter().any(|(_, sample)| sample.depth > 0) {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(30), drained).await.unwrap();
+        writer.abort();
+
+        let delivered = recorded.sources.lock().unwrap().len() as u64;
+        let stats = injector.stats();
+        assert!(stats.events_corrupted > 0 && stats.task_panics > 0 && stats.sink_delays > 0);
+        assert!(delivered < sent);
+        assert_eq!(injector.check_invariants(sent, delivered), Ok(()));
+        assert!(injector.check_invariants(sent, sent / 2).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_supervised_task_recovers_from_injected_panics() {
+        let injector = FaultInjector::new(ChaosSettings {
+            enabled: true,
+            task_panic_probability: 1.0,
+            ..Default::default()
+        });
+        let attempts = Arc::new(AtomicU32::new(0));
+
+        let (task_injector, task_attempts) = (injector.clone(), attempts.clone());
+        injector
+            .spawn_supervised("worker", move || {
+                let (injector, attempts) = (task_injector.clone(), task_attempts.clone());
+                async move {
+                    if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                        injector.maybe_panic("worker");
+                    }
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(injector.stats().task_restarts, 2);
+        assert!(injector.check_invariants(10, 10).is_ok());
+    }
+}
@@ -0,0 +1,488 @@
+//! Per-incident chat mirroring.
+//!
+//! Incidents at or above `min_severity` get their own chat space: a Slack
+//! channel created for the incident, or a thread in a fixed Microsoft Teams
+//! channel. The incident summary opens it, later timeline entries are posted
+//! as they appear, and on resolution the space is archived and its transcript
+//! link is added to the incident's timeline.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::{Incident, IncidentResponseEngine, IncidentSeverity, IncidentStatus, TimelineEntry};
+use crate::timezone::Zone;
+
+/// Timeline events written by the mirror itself, never posted back to chat
+const CHAT_OPENED: &str = "chat_opened";
+const CHAT_ARCHIVED: &str = "chat_archived";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatPlatform {
+    /// A channel per incident, archived on resolution
+    Slack,
+    /// A thread per incident in `teams_channel_id`
+    Teams,
+}
+
+/// Dedicated chat spaces for severe incidents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatMirrorSettings {
+    pub enabled: bool,
+    pub platform: ChatPlatform,
+    pub min_severity: IncidentSeverity,
+    pub sync_interval_seconds: u64,
+    /// Which incidents have a chat space and how far their timelines were posted
+    pub store_path: String,
+    /// Bot token with `channels:manage`, `channels:read` and `chat:write`
+    pub slack_token: String,
+    pub slack_api_url: String,
+    /// Channel name prefix, followed by the incident id
+    pub channel_prefix: String,
+    /// Slack user ids invited to every new channel
+    pub invite_users: Vec<String>,
+    /// Microsoft Graph token allowed to post channel messages
+    pub teams_token: String,
+    pub teams_graph_url: String,
+    pub teams_team_id: String,
+    pub teams_channel_id: String,
+}
+
+impl Default for ChatMirrorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            platform: ChatPlatform::Slack,
+            min_severity: IncidentSeverity::Critical,
+            sync_interval_seconds: 15,
+            store_path: "data/chat-mirror.json".to_string(),
+            slack_token: String::new(),
+            slack_api_url: "https://slack.com/api".to_string(),
+            channel_prefix: "inc-".to_string(),
+            invite_users: Vec::new(),
+            teams_token: String::new(),
+            teams_graph_url: "https://graph.microsoft.com/v1.0".to_string(),
+            teams_team_id: String::new(),
+            teams_channel_id: String::new(),
+        }
+    }
+}
+
+/// A channel or thread holding one incident's conversation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatThread {
+    pub id: String,
+    pub url: String,
+}
+
+pub trait ChatBackend: Send + Sync {
+    /// Create the space named `name` and post `summary` as its first message
+    fn open<'a>(&'a self, name: &'a str, summary: &'a str) -> BoxFuture<'a, SIEMResult<ChatThread>>;
+    fn post<'a>(&'a self, thread: &'a ChatThread, text: &'a str) -> BoxFuture<'a, SIEMResult<()>>;
+    /// Archive the space; returns the link to its transcript
+    fn close<'a>(&'a self, thread: &'a ChatThread) -> BoxFuture<'a, SIEMResult<String>>;
+}
+
+pub struct SlackBackend {
+    client: reqwest::Client,
+    api_url: String,
+    token: String,
+    invite_users: Vec<String>,
+}
+
+impl SlackBackend {
+    pub fn new(settings: &ChatMirrorSettings) -> SIEMResult<Self> {
+        if settings.slack_token.is_empty() {
+            return Err(SIEMError::Config("chat_mirror: slack_token is required".to_string()));
+        }
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?,
+            api_url: settings.slack_api_url.trim_end_matches('/').to_string(),
+            token: settings.slack_token.clone(),
+            invite_users: settings.invite_users.clone(),
+        })
+    }
+
+    /// Slack answers 200 with `ok: false` on failure
+    async fn call(&self, method: &str, body: serde_json::Value) -> SIEMResult<serde_json::Value> {
+        let response: serde_json::Value = self
+            .client
+            .post(format!("{}/{}", self.api_url, method))
+            .bearer_auth(&self.token)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if response["ok"].as_bool() != Some(true) {
+            return Err(SIEMError::Other(format!("Slack {} failed: {}", method, response["error"].as_str().unwrap_or("unknown error"))));
+        }
+        Ok(response)
+    }
+}
+
+impl ChatBackend for SlackBackend {
+    fn open<'a>(&'a self, name: &'a str, summary: &'a str) -> BoxFuture<'a, SIEMResult<ChatThread>> {
+        Box::pin(async move {
+            let created = self.call("conversations.create", serde_json::json!({ "name": name })).await?;
+            let channel = created["channel"]["id"].as_str().unwrap_or_default().to_string();
+            if !self.invite_users.is_empty() {
+                let invite = serde_json::json!({ "channel": channel, "users": self.invite_users.join(",") });
+                if let Err(e) = self.call("conversations.invite", invite).await {
+                    warn!("⚠️ Could not invite responders to #{}: {}", name, e);
+                }
+            }
+            let posted = self.call("chat.postMessage", serde_json::json!({ "channel": channel, "text": summary })).await?;
+            let link = serde_json::json!({ "channel": channel, "message_ts": posted["ts"] });
+            let url = self.call("chat.getPermalink", link).await?["permalink"].as_str().unwrap_or_default().to_string();
+            Ok(ChatThread { id: channel, url })
+        })
+    }
+
+    fn post<'a>(&'a self, thread: &'a ChatThread, text: &'a str) -> BoxFuture<'a, SIEMResult<()>> {
+        Box::pin(async move {
+            self.call("chat.postMessage", serde_json::json!({ "channel": thread.id, "text": text })).await?;
+            Ok(())
+        })
+    }
+
+    fn close<'a>(&'a self, thread: &'a ChatThread) -> BoxFuture<'a, SIEMResult<String>> {
+        Box::pin(async move {
+            self.call("conversations.archive", serde_json::json!({ "channel": thread.id })).await?;
+            Ok(thread.url.clone())
+        })
+    }
+}
+
+pub struct TeamsBackend {
+    client: reqwest::Client,
+    messages_url: String,
+    token: String,
+}
+
+impl TeamsBackend {
+    pub fn new(settings: &ChatMirrorSettings) -> SIEMResult<Self> {
+        if settings.teams_token.is_empty() || settings.teams_team_id.is_empty() || settings.teams_channel_id.is_empty() {
+            return Err(SIEMError::Config("chat_mirror: teams_token, teams_team_id and teams_channel_id are required".to_string()));
+        }
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?,
+            messages_url: format!(
+                "{}/teams/{}/channels/{}/messages",
+                settings.teams_graph_url.trim_end_matches('/'),
+                settings.teams_team_id,
+                settings.teams_channel_id
+            ),
+            token: settings.teams_token.clone(),
+        })
+    }
+
+    async fn send(&self, url: String, text: &str) -> SIEMResult<serde_json::Value> {
+        let body = serde_json::json!({ "body": { "contentType": "text", "content": text } });
+        Ok(self.client.post(url).bearer_auth(&self.token).json(&body).send().await?.error_for_status()?.json().await?)
+    }
+}
+
+impl ChatBackend for TeamsBackend {
+    fn open<'a>(&'a self, _name: &'a str, summary: &'a str) -> BoxFuture<'a, SIEMResult<ChatThread>> {
+        Box::pin(async move {
+            let message = self.send(self.messages_url.clone(), summary).await?;
+            Ok(ChatThread {
+                id: message["id"].as_str().unwrap_or_default().to_string(),
+                url: message["webUrl"].as_str().unwrap_or_default().to_string(),
+            })
+        })
+    }
+
+    fn post<'a>(&'a self, thread: &'a ChatThread, text: &'a str) -> BoxFuture<'a, SIEMResult<()>> {
+        Box::pin(async move {
+            self.send(format!("{}/{}/replies", self.messages_url, thread.id), text).await?;
+            Ok(())
+        })
+    }
+
+    /// Teams threads cannot be archived; the thread simply stops receiving updates
+    fn close<'a>(&'a self, thread: &'a ChatThread) -> BoxFuture<'a, SIEMResult<String>> {
+        Box::pin(async move { Ok(thread.url.clone()) })
+    }
+}
+
+/// Chat space of one incident and how much of its timeline was posted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirroredIncident {
+    pub incident_id: String,
+    pub thread: ChatThread,
+    /// Timeline entries already handled
+    pub posted: usize,
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub transcript_url: Option<String>,
+}
+
+pub struct ChatMirror {
+    settings: ChatMirrorSettings,
+    backend: Arc<dyn ChatBackend>,
+    store_path: PathBuf,
+    threads: RwLock<HashMap<String, MirroredIncident>>,
+}
+
+impl std::fmt::Debug for ChatMirror {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChatMirror").field("platform", &self.settings.platform).finish()
+    }
+}
+
+impl ChatMirror {
+    pub fn open(settings: ChatMirrorSettings) -> SIEMResult<Self> {
+        let backend: Arc<dyn ChatBackend> = match settings.platform {
+            ChatPlatform::Slack => Arc::new(SlackBackend::new(&settings)?),
+            ChatPlatform::Teams => Arc::new(TeamsBackend::new(&settings)?),
+        };
+        Self::with_backend(settings, backend)
+    }
+
+    pub fn with_backend(settings: ChatMirrorSettings, backend: Arc<dyn ChatBackend>) -> SIEMResult<Self> {
+        let store_path = PathBuf::from(&settings.store_path);
+        let threads: Vec<MirroredIncident> = match std::fs::read(&store_path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let threads = threads.into_iter().map(|t| (t.incident_id.clone(), t)).collect();
+        Ok(Self { settings, backend, store_path, threads: RwLock::new(threads) })
+    }
+
+    /// Chat space of an incident, if it has one
+    pub fn thread(&self, incident_id: &str) -> Option<MirroredIncident> {
+        self.threads.read().unwrap().get(incident_id).cloned()
+    }
+
+    /// Open spaces for new severe incidents, post new timeline entries and archive resolved ones
+    pub async fn sync_once(&self, engine: &IncidentResponseEngine) -> SIEMResult<usize> {
+        let mut updated = 0;
+        for incident in engine.get_all_incidents() {
+            let result = match self.thread(&incident.id) {
+                Some(mirrored) if mirrored.closed_at.is_some() => continue,
+                Some(mirrored) => self.catch_up(engine, &incident, mirrored).await,
+                None if incident.severity >= self.settings.min_severity && !is_finished(&incident.status) => {
+                    self.open_thread(engine, &incident).await
+                }
+                None => continue,
+            };
+            match result {
+                Ok(()) => updated += 1,
+                Err(e) => warn!("⚠️ Chat mirroring of incident {} failed: {}", incident.id, e),
+            }
+        }
+        self.save()?;
+        Ok(updated)
+    }
+
+    pub fn spawn(self: Arc<Self>, incidents: Arc<IncidentResponseEngine>) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.settings.enabled {
+            return None;
+        }
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(self.settings.sync_interval_seconds.max(1)));
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.sync_once(&incidents).await {
+                    warn!("⚠️ Chat mirroring failed: {}", e);
+                }
+            }
+        }))
+    }
+
+    async fn open_thread(&self, engine: &IncidentResponseEngine, incident: &Incident) -> SIEMResult<()> {
+        let thread = self.backend.open(&self.channel_name(&incident.id), &summary(incident)).await?;
+        info!("💬 Incident {} mirrored to {}", incident.id, thread.url);
+        let mirrored = MirroredIncident {
+            incident_id: incident.id.clone(),
+            thread: thread.clone(),
+            posted: incident.timeline.len(),
+            opened_at: Utc::now(),
+            closed_at: None,
+            transcript_url: None,
+        };
+        self.threads.write().unwrap().insert(incident.id.clone(), mirrored);
+        engine.add_timeline_entry(&incident.id, TimelineEntry::new(CHAT_OPENED, format!("Chat opened at {}", thread.url)).with_reference(thread.url))
+    }
+
+    async fn catch_up(&self, engine: &IncidentResponseEngine, incident: &Incident, mut mirrored: MirroredIncident) -> SIEMResult<()> {
+        // Each entry is recorded as posted right away, so a failure resumes where it stopped
+        let zones = engine.time_zones();
+        let zone = zones.for_threat(&incident.threat_result);
+        for entry in incident.timeline.iter().skip(mirrored.posted) {
+            if entry.event != CHAT_OPENED && entry.event != CHAT_ARCHIVED {
+                self.backend.post(&mirrored.thread, &timeline_line(entry, zone)).await?;
+            }
+            mirrored.posted += 1;
+            self.threads.write().unwrap().insert(incident.id.clone(), mirrored.clone());
+        }
+        if !is_finished(&incident.status) {
+            return Ok(());
+        }
+
+        self.backend.post(&mirrored.thread, &format!("Incident {:?}; this space is now archived.", incident.status)).await?;
+        let transcript = self.backend.close(&mirrored.thread).await?;
+        info!("💬 Incident {} chat archived", incident.id);
+        mirrored.closed_at = Some(Utc::now());
+        mirrored.transcript_url = Some(transcript.clone());
+        self.threads.write().unwrap().insert(incident.id.clone(), mirrored);
+        engine.add_timeline_entry(&incident.id, TimelineEntry::new(CHAT_ARCHIVED, format!("Chat transcript: {}", transcript)).with_reference(transcript))
+    }
+
+    /// Slack channel names: lowercase letters, digits, `-` and `_`, at most 80 characters
+    fn channel_name(&self, incident_id: &str) -> String {
+        format!("{}{}", self.settings.channel_prefix, incident_id)
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+            .take(80)
+            .collect()
+    }
+
+    fn save(&self) -> SIEMResult<()> {
+        let threads: Vec<MirroredIncident> = self.threads.read().unwrap().values().cloned().collect();
+        if let Some(dir) = self.store_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.store_path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&threads)?)?;
+        std::fs::rename(tmp, &self.store_path)?;
+        Ok(())
+    }
+}
+
+fn is_finished(status: &IncidentStatus) -> bool {
+    matches!(status, IncidentStatus::Resolved | IncidentStatus::Closed | IncidentStatus::FalsePositive)
+}
+
+fn summary(incident: &Incident) -> String {
+    let mut text = format!(
+        "🚨 [{}] {}\nIncident: {}\nStatus: {:?}\nSource: {}  Destination: {}  User: {}",
+        incident.severity, incident.title, incident.id, incident.status, incident.source_ip, incident.destination_ip, incident.user_id
+    );
+    if let Some(assignee) = &incident.assigned_to {
+        text.push_str(&format!("\nAssigned to: {}", assignee));
+    }
+    if !incident.description.is_empty() {
+        text.push_str(&format!("\n\n{}", incident.description));
+    }
+    text
+}
+
+/// Timeline entry as a chat line, timed in the incident tenant's zone
+fn timeline_line(entry: &TimelineEntry, zone: &Zone) -> String {
+    let time = zone.to_local(entry.timestamp).format("%H:%M:%S");
+    format!("[{} {}] {}: {}", time, zone.abbreviation_at(entry.timestamp), entry.event, entry.message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::incident_response::{AlertConfig, SOARConfig};
+
+    #[derive(Default)]
+    struct RecordingChat {
+        messages: Mutex<Vec<(String, String)>>,
+        archived: Mutex<Vec<String>>,
+    }
+
+    impl ChatBackend for RecordingChat {
+        fn open<'a>(&'a self, name: &'a str, summary: &'a str) -> BoxFuture<'a, SIEMResult<ChatThread>> {
+            Box::pin(async move {
+                self.messages.lock().unwrap().push((name.to_string(), summary.to_string()));
+                Ok(ChatThread { id: name.to_string(), url: format!("https://chat.example/{}", name) })
+            })
+        }
+
+        fn post<'a>(&'a self, thread: &'a ChatThread, text: &'a str) -> BoxFuture<'a, SIEMResult<()>> {
+            Box::pin(async move {
+                self.messages.lock().unwrap().push((thread.id.clone(), text.to_string()));
+                Ok(())
+            })
+        }
+
+        fn close<'a>(&'a self, thread: &'a ChatThread) -> BoxFuture<'a, SIEMResult<String>> {
+            Box::pin(async move {
+                self.archived.lock().unwrap().push(thread.id.clone());
+                Ok(format!("{}/transcript", thread.url))
+            })
+        }
+    }
+
+    fn mirror(store_path: &std::path::Path) -> (ChatMirror, Arc<RecordingChat>) {
+        let chat = Arc::new(RecordingChat::default());
+        let settings = ChatMirrorSettings { enabled: true, store_path: store_path.display().to_string(), ..Default::default() };
+        (ChatMirror::with_backend(settings, chat.clone()).unwrap(), chat)
+    }
+
+    async fn incident(engine: &IncidentResponseEngine, severity: IncidentSeverity) -> Incident {
+        let mut incident = engine.process_threat(AdvancedThreatResult::default()).await.unwrap();
+        incident.severity = severity;
+        engine.store_incident(incident.clone());
+        incident
+    }
+
+    #[tokio::test]
+    async fn test_critical_incident_is_mirrored_until_resolution() {
+        let store = std::env::temp_dir().join(format!("chat-mirror-{}.json", uuid::Uuid::new_v4()));
+        let (mirror, chat) = mirror(&store);
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let critical = incident(&engine, IncidentSeverity::Critical).await;
+        incident(&engine, IncidentSeverity::High).await;
+
+        assert_eq!(mirror.sync_once(&engine).await.unwrap(), 1);
+        let channel = format!("inc-{}", critical.id);
+        assert_eq!(chat.messages.lock().unwrap()[0].0, channel);
+        assert!(chat.messages.lock().unwrap()[0].1.contains("[Critical]"));
+
+        engine.add_incident_note(&critical.id, "Isolated FIN-WS-12".to_string()).await.unwrap();
+        mirror.sync_once(&engine).await.unwrap();
+        let last = chat.messages.lock().unwrap().last().cloned().unwrap();
+        assert_eq!(last.0, channel);
+        assert!(last.1.ends_with("note_added: Isolated FIN-WS-12"));
+
+        engine.update_incident_status(&critical.id, IncidentStatus::Resolved).await.unwrap();
+        mirror.sync_once(&engine).await.unwrap();
+        assert_eq!(*chat.archived.lock().unwrap(), vec![channel.clone()]);
+        let timeline = engine.get_incident(&critical.id).unwrap().timeline;
+        let archived = timeline.last().unwrap();
+        assert_eq!(archived.event, CHAT_ARCHIVED);
+        assert_eq!(archived.reference.as_deref(), Some(format!("https://chat.example/{}/transcript", channel).as_str()));
+
+        // Our own timeline entries are never echoed, and nothing more is posted once archived
+        let posted = chat.messages.lock().unwrap().len();
+        mirror.sync_once(&engine).await.unwrap();
+        assert_eq!(chat.messages.lock().unwrap().len(), posted);
+        assert!(chat.messages.lock().unwrap().iter().all(|(_, text)| !text.contains(CHAT_OPENED)));
+        let _ = std::fs::remove_file(store);
+    }
+
+    #[tokio::test]
+    async fn test_mirror_state_survives_restart() {
+        let store = std::env::temp_dir().join(format!("chat-mirror-{}.json", uuid::Uuid::new_v4()));
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let emergency = incident(&engine, IncidentSeverity::Emergency).await;
+        let (first, _) = mirror(&store);
+        first.sync_once(&engine).await.unwrap();
+
+        let (restarted, chat) = mirror(&store);
+        restarted.sync_once(&engine).await.unwrap();
+        assert!(chat.messages.lock().unwrap().is_empty(), "no second channel after a restart");
+        assert_eq!(restarted.thread(&emergency.id).unwrap().thread.id, format!("inc-{}", emergency.id));
+        let _ = std::fs::remove_file(store);
+    }
+}
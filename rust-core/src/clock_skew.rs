@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use dashmap::DashMap;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+/// Timestamp normalization and clock skew settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClockSkewSettings {
+    pub enabled: bool,
+    /// Learned skew below this is treated as transport latency and not corrected
+    pub skew_tolerance_seconds: u64,
+    /// Offsets beyond this are never learned as skew
+    pub max_learnable_skew_seconds: u64,
+    /// Weight of each new sample in the per-source skew average (0.0 - 1.0)
+    pub skew_smoothing: f64,
+    pub max_future_seconds: u64,
+    pub max_past_seconds: u64,
+    pub max_tracked_sources: usize,
+}
+
+impl Default for ClockSkewSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            skew_tolerance_seconds: 30,
+            max_learnable_skew_seconds: 86_400,
+            skew_smoothing: 0.1,
+            max_future_seconds: 300,
+            max_past_seconds: 7 * 86_400,
+            max_tracked_sources: 10_000,
+        }
+    }
+}
+
+/// Original and normalized time of a single event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NormalizedTimestamp {
+    pub original: Option<u64>,
+    pub normalized: u64,
+    pub received_at: u64,
+    /// Learned skew applied to this event (event clock minus receive clock)
+    pub skew_seconds: i64,
+    /// Event time falls outside the plausibility bounds even after correction
+    pub implausible: bool,
+}
+
+#[derive(Debug, Clone)]
+struct SourceClock {
+    skew: f64,
+    samples: u64,
+}
+
+/// Learns per-source clock skew and rewrites event timestamps onto the receive clock
+#[derive(Debug)]
+pub struct TimestampNormalizer {
+    settings: ClockSkewSettings,
+    sources: DashMap<String, SourceClock>,
+    normalized: AtomicU64,
+    corrected: AtomicU64,
+    implausible: AtomicU64,
+}
+
+impl TimestampNormalizer {
+    pub fn new(settings: ClockSkewSettings) -> Self {
+        Self {
+            settings,
+            sources: DashMap::new(),
+            normalized: AtomicU64::new(0),
+            corrected: AtomicU64::new(0),
+            implausible: AtomicU64::new(0),
+        }
+    }
+
+    /// Normalize `event` against the current time
+    pub fn normalize(&self, event: &mut serde_json::Value) -> NormalizedTimestamp {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        self.normalize_at(event, now)
+    }
+
+    /// Normalize `event` received at `received_at` (unix seconds), recording both timestamps on it
+    pub fn normalize_at(&self, event: &mut serde_json::Value, received_at: u64) -> NormalizedTimestamp {
+        self.normalized.fetch_add(1, Ordering::Relaxed);
+        let original = event.get("timestamp").and_then(parse_timestamp);
+        let source = event_source(event);
+
+        let result = match original {
+            Some(event_time) => {
+                let offset = event_time as i64 - received_at as i64;
+                let skew = self.learn(&source, offset);
+                let skew = if skew.unsigned_abs() >= self.settings.skew_tolerance_seconds { skew } else { 0 };
+                let corrected = (event_time as i64 - skew).max(0) as u64;
+                let implausible = corrected > received_at.saturating_add(self.settings.max_future_seconds)
+                    || corrected < received_at.saturating_sub(self.settings.max_past_seconds);
+                if skew != 0 {
+                    self.corrected.fetch_add(1, Ordering::Relaxed);
+                }
+                NormalizedTimestamp {
+                    original,
+                    normalized: if implausible { received_at } else { corrected },
+                    received_at,
+                    skew_seconds: skew,
+                    implausible,
+                }
+            }
+            None => NormalizedTimestamp {
+                original: None,
+                normalized: received_at,
+                received_at,
+                skew_seconds: 0,
+                implausible: false,
+            },
+        };
+
+        if result.implausible {
+            self.implausible.fetch_add(1, Ordering::Relaxed);
+            debug!("Implausible timestamp {:?} from {} (received {})", original, source, received_at);
+        }
+        if let Some(fields) = event.as_object_mut() {
+            if let Some(raw) = fields.get("timestamp").cloned() {
+                fields.insert("original_timestamp".to_string(), raw);
+            }
+            fields.insert("timestamp".to_string(), result.normalized.into());
+            fields.insert("received_at".to_string(), received_at.into());
+            fields.insert("clock_skew_seconds".to_string(), result.skew_seconds.into());
+            fields.insert("timestamp_implausible".to_string(), result.implausible.into());
+        }
+        result
+    }
+
+    /// Update and return the learned skew for `source`
+    fn learn(&self, source: &str, offset: i64) -> i64 {
+        let learnable = offset.unsigned_abs() <= self.settings.max_learnable_skew_seconds;
+        if let Some(mut clock) = self.sources.get_mut(source) {
+            if learnable {
+                let alpha = self.settings.skew_smoothing.clamp(0.0, 1.0);
+                clock.skew += alpha * (offset as f64 - clock.skew);
+                clock.samples += 1;
+            }
+            return clock.skew.round() as i64;
+        }
+        if !learnable || self.sources.len() >= self.settings.max_tracked_sources {
+            return 0;
+        }
+        self.sources.insert(source.to_string(), SourceClock { skew: offset as f64, samples: 1 });
+        offset
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        let max_skew = self.sources.iter().map(|clock| clock.skew.abs()).fold(0.0, f64::max);
+        let samples: u64 = self.sources.iter().map(|clock| clock.samples).sum();
+        let mut metrics = HashMap::new();
+        metrics.insert("timestamps_normalized".to_string(), self.normalized.load(Ordering::Relaxed) as f64);
+        metrics.insert("timestamps_corrected".to_string(), self.corrected.load(Ordering::Relaxed) as f64);
+        metrics.insert("timestamps_implausible".to_string(), self.implausible.load(Ordering::Relaxed) as f64);
+        metrics.insert("clock_skew_sources".to_string(), self.sources.len() as f64);
+        metrics.insert("clock_skew_samples".to_string(), samples as f64);
+        metrics.insert("clock_skew_max_abs_seconds".to_string(), max_skew);
+        metrics
+    }
+}
+
+/// Accept unix seconds, unix milliseconds or RFC 3339 strings
+fn parse_timestamp(value: &serde_json::Value) -> Option<u64> {
+    if let Some(seconds) = value.as_u64() {
+        return Some(epoch_seconds(seconds));
+    }
+    if let Some(seconds) = value.as_f64() {
+        return (seconds >= 0.0).then_some(seconds as u64);
+    }
+    value
+        .as_str()
+        .and_then(|text| chrono::DateTime::parse_from_rfc3339(text).ok())
+        .and_then(|time| u64::try_from(time.timestamp()).ok())
+}
+
+/// Unix seconds from a numeric timestamp that may be in milliseconds
+pub(crate) fn epoch_seconds(timestamp: u64) -> u64 {
+    // Anything past year 5138 in seconds is really milliseconds
+    if timestamp > 100_000_000_000 { timestamp / 1000 } else { timestamp }
+}
+
+fn event_source(event: &serde_json::Value) -> String {
+    ["source", "host", "source_ip"]
+        .iter()
+        .find_map(|field| event.get(*field).and_then(|v| v.as_str()))
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn normalizer() -> TimestampNormalizer {
+        TimestampNormalizer::new(ClockSkewSettings {
+            enabled: true,
+            skew_smoothing: 0.5,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_learned_skew_is_corrected_and_original_kept() {
+        let normalizer = normalizer();
+        // Host clock runs one hour fast
+        let mut first = json!({ "host": "web-1", "timestamp": 1_700_003_600u64 });
+        let result = normalizer.normalize_at(&mut first, 1_700_000_000);
+
+        assert_eq!(result.skew_seconds, 3600);
+        assert_eq!(first["timestamp"], 1_700_000_000u64);
+        assert_eq!(first["original_timestamp"], 1_700_003_600u64);
+
+        let mut second = json!({ "host": "web-1", "timestamp": "2023-11-14T23:13:30Z" });
+        let result = normalizer.normalize_at(&mut second, 1_700_000_010);
+        assert_eq!(result.original, Some(1_700_003_610));
+        assert_eq!(result.normalized, 1_700_000_010);
+        assert!(!result.implausible);
+    }
+
+    #[test]
+    fn test_small_offsets_are_latency_and_outliers_are_flagged() {
+        let normalizer = normalizer();
+        let mut delayed = json!({ "host": "db-1", "timestamp": 1_699_999_995u64 });
+        assert_eq!(normalizer.normalize_at(&mut delayed, 1_700_000_000).normalized, 1_699_999_995);
+
+        let mut future = json!({ "host": "db-2", "timestamp": 1_800_000_000u64 });
+        let result = normalizer.normalize_at(&mut future, 1_700_000_000);
+        assert!(result.implausible);
+        assert_eq!(result.normalized, 1_700_000_000);
+        assert_eq!(future["timestamp_implausible"], true);
+        assert_eq!(normalizer.get_metrics()["timestamps_implausible"], 1.0);
+    }
+}
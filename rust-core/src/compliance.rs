@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::sync::{Arc, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 use serde::{Deserialize, Serialize};
@@ -6,11 +6,14 @@ use log::{info, warn, error, debug};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
-use bcrypt::{hash, verify, DEFAULT_COST};
 use jsonwebtoken::{encode, decode, Header, Algorithm, Validation, EncodingKey, DecodingKey};
+use pdf_writer::{Content, Name, Pdf, Rect, Ref, Str};
 use reqwest::Client;
 
-use crate::error_handling::SIEMResult;
+use crate::elevation::{ElevationRequest, ElevationSettings, ElevationStatus};
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::memory_budget::{self, MemoryAccountant};
+use crate::password_hashing::{PasswordHashSettings, PasswordHasher, PasswordMigrationReport};
 
 /// User roles and permissions
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -223,6 +226,92 @@ pub struct ComplianceReport {
     pub findings: Vec<ComplianceFinding>,
     pub recommendations: Vec<String>,
     pub attachments: Vec<String>,
+    /// Sections contributed by scheduled jobs such as trend analysis
+    #[serde(default)]
+    pub sections: Vec<ReportSection>,
+}
+
+/// Titled block of report text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportSection {
+    pub title: String,
+    pub generated_at: DateTime<Utc>,
+    pub lines: Vec<String>,
+}
+
+impl ComplianceReport {
+    /// Summary, findings and contributed sections as a PDF
+    pub fn to_pdf(&self) -> Vec<u8> {
+        let mut summary = vec![
+            format!("Period: {} to {}", self.period_start.format("%Y-%m-%d"), self.period_end.format("%Y-%m-%d")),
+            format!("Compliance: {:.1}% of {} requirements", self.summary.compliance_percentage, self.summary.total_requirements),
+            format!(
+                "Findings: {} critical, {} high, {} medium, {} low",
+                self.summary.critical_findings, self.summary.high_findings, self.summary.medium_findings, self.summary.low_findings
+            ),
+        ];
+        summary.extend(self.findings.iter().map(|f| format!("[{:?}] {}: {}", f.severity, f.requirement_id, f.description)));
+        let mut sections = vec![ReportSection {
+            title: "Summary".to_string(),
+            generated_at: self.report_date,
+            lines: summary,
+        }];
+        sections.extend(self.sections.iter().cloned());
+        sections_pdf(&format!("{:?} compliance report", self.framework), &sections)
+    }
+}
+
+/// Render titled text sections onto A4 pages
+pub fn sections_pdf(title: &str, sections: &[ReportSection]) -> Vec<u8> {
+    const LINE: f32 = 14.0;
+    const TOP: f32 = 792.0;
+    const BOTTOM: f32 = 50.0;
+    const WRAP: usize = 95;
+
+    // (font size, bold, text); PDF base fonts only cover Latin-1
+    let mut lines: Vec<(f32, bool, String)> = vec![(16.0, true, title.to_string()), (10.0, false, String::new())];
+    for section in sections {
+        lines.push((13.0, true, section.title.clone()));
+        lines.push((8.0, false, format!("Generated {}", section.generated_at.to_rfc3339())));
+        for line in &section.lines {
+            let chars: Vec<char> = line.chars().map(|c| if c.is_ascii() && !c.is_ascii_control() { c } else { '?' }).collect();
+            for chunk in chars.chunks(WRAP) {
+                lines.push((10.0, false, chunk.iter().collect()));
+            }
+        }
+        lines.push((10.0, false, String::new()));
+    }
+
+    let per_page = ((TOP - BOTTOM) / LINE) as usize;
+    let pages: Vec<&[(f32, bool, String)]> = lines.chunks(per_page).collect();
+    let (catalog_id, tree_id, regular_id, bold_id) = (Ref::new(1), Ref::new(2), Ref::new(3), Ref::new(4));
+    let page_ids: Vec<Ref> = (0..pages.len() as i32).map(|i| Ref::new(5 + 2 * i)).collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(tree_id);
+    pdf.pages(tree_id).kids(page_ids.iter().copied()).count(pages.len() as i32);
+    pdf.type1_font(regular_id).base_font(Name(b"Helvetica"));
+    pdf.type1_font(bold_id).base_font(Name(b"Helvetica-Bold"));
+    for (page_lines, page_id) in pages.iter().zip(&page_ids) {
+        let content_id = Ref::new(page_id.get() + 1);
+        let mut page = pdf.page(*page_id);
+        page.parent(tree_id).media_box(Rect::new(0.0, 0.0, 595.0, 842.0)).contents(content_id);
+        page.resources().fonts().pair(Name(b"F1"), regular_id).pair(Name(b"F2"), bold_id);
+        drop(page);
+
+        let mut content = Content::new();
+        let mut y = TOP;
+        for (size, bold, text) in page_lines.iter() {
+            content.begin_text();
+            content.set_font(Name(if *bold { b"F2" } else { b"F1" }), *size);
+            content.next_line(50.0, y);
+            content.show(Str(text.as_bytes()));
+            content.end_text();
+            y -= LINE;
+        }
+        pdf.stream(content_id, &content.finish());
+    }
+    pdf.finish()
 }
 
 /// Compliance summary
@@ -294,9 +383,13 @@ pub struct ComplianceSecurityEngine {
     max_audit_logs: usize,
     session_timeout_minutes: u32,
     password_policy: PasswordPolicy,
+    password_hasher: PasswordHasher,
     mfa_required: bool,
     ip_whitelist_enabled: bool,
     allowed_ips: HashSet<String>,
+    report_sections: Arc<RwLock<BTreeMap<String, ReportSection>>>,
+    elevations: Arc<RwLock<HashMap<String, ElevationRequest>>>,
+    elevation_settings: ElevationSettings,
 }
 
 /// Password policy configuration
@@ -346,9 +439,67 @@ impl ComplianceSecurityEngine {
             max_audit_logs: 100000,
             session_timeout_minutes: 480, // 8 hours
             password_policy: PasswordPolicy::default(),
+            password_hasher: PasswordHasher::default(),
             mfa_required: true,
             ip_whitelist_enabled: false,
             allowed_ips: HashSet::new(),
+            report_sections: Arc::new(RwLock::new(BTreeMap::new())),
+            elevations: Arc::new(RwLock::new(HashMap::new())),
+            elevation_settings: ElevationSettings::default(),
+        }
+    }
+
+    /// Hash new passwords with `settings`; older hashes migrate at the next login
+    pub fn set_password_hashing(&mut self, settings: PasswordHashSettings) -> SIEMResult<()> {
+        self.password_hasher = PasswordHasher::new(settings)?;
+        Ok(())
+    }
+
+    /// Accounts whose password hashes are not on the configured parameters yet
+    pub fn password_migration_report(&self) -> PasswordMigrationReport {
+        let users = self.users.read().unwrap();
+        self.password_hasher
+            .migration_report(users.values().map(|user| (user.username.as_str(), user.password_hash.as_str())))
+    }
+
+    pub fn set_elevation_settings(&mut self, settings: ElevationSettings) {
+        self.elevation_settings = settings;
+    }
+
+    /// Evict the least recently active sessions, the oldest audit entries and finished
+    /// elevation requests past their limits, and report the sizes to `memory`
+    pub fn enforce_memory_limits(&self, memory: &MemoryAccountant) {
+        {
+            let mut sessions = self.sessions.write().unwrap();
+            let eviction = memory_budget::evict_hash_map(&mut sessions, memory.limit("compliance.sessions"), |session| {
+                session.last_activity.timestamp().max(0) as u64
+            });
+            let bytes = memory_budget::estimate_bytes(sessions.len(), sessions.values());
+            memory.record("compliance.sessions", sessions.len(), bytes, eviction);
+        }
+        {
+            let mut logs = self.audit_logs.write().unwrap();
+            let eviction = memory_budget::trim_window(&mut logs, memory.limit("compliance.audit_logs"));
+            let bytes = memory_budget::estimate_bytes(logs.len(), logs.iter());
+            memory.record("compliance.audit_logs", logs.len(), bytes, eviction);
+        }
+        {
+            // Pending and active requests are never evicted
+            let mut elevations = self.elevations.write().unwrap();
+            let mut finished: HashMap<String, ElevationRequest> = HashMap::new();
+            elevations.retain(|id, request| {
+                let open = matches!(request.status, ElevationStatus::Pending | ElevationStatus::Active);
+                if !open {
+                    finished.insert(id.clone(), request.clone());
+                }
+                open
+            });
+            let eviction = memory_budget::evict_hash_map(&mut finished, memory.limit("compliance.elevations"), |request| {
+                request.ended_at.or(request.decided_at).unwrap_or(request.requested_at).timestamp().max(0) as u64
+            });
+            elevations.extend(finished);
+            let bytes = memory_budget::estimate_bytes(elevations.len(), elevations.values());
+            memory.record("compliance.elevations", elevations.len(), bytes, eviction);
         }
     }
 
@@ -364,7 +515,7 @@ impl ComplianceSecurityEngine {
         
         // Start audit log processor
         tokio::spawn({
-            let audit_rx = self.audit_rx.clone();
+            let audit_rx = std::mem::replace(&mut self.audit_rx, mpsc::channel(1000).1);
             let audit_logs = self.audit_logs.clone();
             let max_logs = self.max_audit_logs;
             
@@ -382,6 +533,21 @@ impl ComplianceSecurityEngine {
                 Self::cleanup_expired_sessions(sessions, session_timeout).await;
             }
         });
+
+        // Close out lapsed elevations in the audit log
+        tokio::spawn({
+            let elevations = self.elevations.clone();
+            let audit_tx = self.audit_tx.clone();
+            let interval = self.elevation_settings.sweep_interval_seconds.max(1);
+
+            async move {
+                let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval));
+                loop {
+                    ticker.tick().await;
+                    Self::expire_elevations_in(&elevations, &audit_tx, Utc::now()).await;
+                }
+            }
+        });
         
         info!("✅ Compliance and Security Engine started successfully");
         Ok(())
@@ -402,8 +568,8 @@ impl ComplianceSecurityEngine {
             return Ok(None);
         }
 
-        let users = self.users.read().unwrap();
-        let user = users.get(username);
+        // Clone the user out so the lock is not held across the updates below
+        let user = self.users.read().unwrap().get(username).cloned();
         
         if let Some(user) = user {
             // Check if account is locked
@@ -420,7 +586,7 @@ impl ComplianceSecurityEngine {
             }
 
             // Verify password
-            if verify(password, &user.password_hash).unwrap_or(false) {
+            if self.password_hasher.verify(password, &user.password_hash) {
                 // Check if password is expired
                 if let Some(expires_at) = user.password_expires_at {
                     if Utc::now() > expires_at {
@@ -436,11 +602,15 @@ impl ComplianceSecurityEngine {
                     }
                 }
 
+                if self.password_hasher.settings().rehash_on_login && self.password_hasher.needs_rehash(&user.password_hash) {
+                    self.rehash_password(username, password).await;
+                }
+
                 // Reset failed login attempts
                 self.reset_failed_login_attempts(username).await?;
                 
                 // Create session
-                let session_id = self.create_user_session(user, ip_address).await?;
+                let session_id = self.create_user_session(&user, ip_address).await?;
                 
                 self.log_audit_event(
                     "AUTH_SUCCESS",
@@ -502,19 +672,137 @@ impl ComplianceSecurityEngine {
     pub fn check_permission(&self, user_id: &str, permission: &Permission) -> bool {
         let users = self.users.read().unwrap();
         if let Some(user) = users.get(user_id) {
-            user.permissions.contains(permission)
+            user.permissions.contains(permission) || self.elevated_permissions(&user.username).contains(permission)
         } else {
             false
         }
     }
 
+    /// Username of an active, unexpired session
+    pub fn session_user(&self, session_id: &str) -> Option<String> {
+        let sessions = self.sessions.read().unwrap();
+        sessions
+            .get(session_id)
+            .filter(|session| session.is_active && Utc::now() < session.expires_at)
+            .map(|session| session.username.clone())
+    }
+
+    /// Role permissions plus any elevation granted right now
+    pub fn effective_permissions(&self, username: &str) -> HashSet<Permission> {
+        let mut permissions = self.users.read().unwrap().get(username).map(|user| user.permissions.clone()).unwrap_or_default();
+        permissions.extend(self.elevated_permissions(username));
+        permissions
+    }
+
+    /// Ask for `requested_role` for a bounded time; it applies once an approver grants it
+    pub async fn request_elevation(&self, username: &str, requested_role: UserRole, justification: &str, duration_minutes: u32) -> SIEMResult<ElevationRequest> {
+        let settings = &self.elevation_settings;
+        let justification = justification.trim();
+        if duration_minutes == 0 || duration_minutes > settings.max_minutes {
+            return Err(SIEMError::Validation(format!("Elevation must last between 1 and {} minutes", settings.max_minutes)));
+        }
+        if justification.chars().count() < settings.min_justification_chars {
+            return Err(SIEMError::Validation(format!(
+                "Justification must be at least {} characters",
+                settings.min_justification_chars
+            )));
+        }
+        let base_role = match self.users.read().unwrap().get(username) {
+            Some(user) if user.is_active && !user.is_locked => user.role.clone(),
+            Some(_) => return Err(SIEMError::Validation(format!("{} is inactive or locked", username))),
+            None => return Err(SIEMError::Validation(format!("Unknown user: {}", username))),
+        };
+        if self.get_role_permissions(&requested_role).is_subset(&self.get_role_permissions(&base_role)) {
+            return Err(SIEMError::Validation(format!("{} grants nothing beyond {}", requested_role, base_role)));
+        }
+
+        let now = Utc::now();
+        let request = ElevationRequest {
+            id: Uuid::new_v4().to_string(),
+            username: username.to_string(),
+            base_role,
+            requested_role,
+            justification: justification.to_string(),
+            duration_minutes,
+            requested_at: now,
+            status: ElevationStatus::Pending,
+            approver: None,
+            decided_at: None,
+            decision_note: None,
+            expires_at: None,
+            ended_at: None,
+            ended_by: None,
+        };
+        {
+            let mut elevations = self.elevations.write().unwrap();
+            if elevations.values().any(|r| r.username == username && (r.status == ElevationStatus::Pending || r.grants_at(now))) {
+                return Err(SIEMError::Validation(format!("{} already has an open elevation request", username)));
+            }
+            elevations.insert(request.id.clone(), request.clone());
+        }
+
+        info!("🔑 {} requested {} for {} minutes", username, request.requested_role, duration_minutes);
+        Self::log_elevation_event(&self.audit_tx, "ELEVATION_REQUESTED", username, &request).await;
+        Ok(request)
+    }
+
+    /// Grant a pending request; the role applies from now until its duration runs out
+    pub async fn approve_elevation(&self, id: &str, approver: &str, note: Option<String>) -> SIEMResult<ElevationRequest> {
+        self.check_elevation_approver(id, approver)?;
+        let request = self.update_elevation(id, ElevationStatus::Pending, |request| request.activate(approver, note, Utc::now()))?;
+        info!("🔑 {} granted {} to {} until {:?}", approver, request.requested_role, request.username, request.expires_at);
+        Self::log_elevation_event(&self.audit_tx, "ELEVATION_APPROVED", approver, &request).await;
+        Ok(request)
+    }
+
+    pub async fn deny_elevation(&self, id: &str, approver: &str, note: Option<String>) -> SIEMResult<ElevationRequest> {
+        self.check_elevation_approver(id, approver)?;
+        let request = self.update_elevation(id, ElevationStatus::Pending, |request| {
+            request.status = ElevationStatus::Denied;
+            request.approver = Some(approver.to_string());
+            request.decided_at = Some(Utc::now());
+            request.decision_note = note;
+        })?;
+        Self::log_elevation_event(&self.audit_tx, "ELEVATION_DENIED", approver, &request).await;
+        Ok(request)
+    }
+
+    /// End an active grant early; holders may drop their own elevation, anyone
+    /// else needs ManageRoles from their standing role
+    pub async fn revoke_elevation(&self, id: &str, actor: &str) -> SIEMResult<ElevationRequest> {
+        let holder = self.elevation(id)?.username;
+        if actor != holder && !self.has_standing_permission(actor, &Permission::ManageRoles) {
+            return Err(SIEMError::Auth(format!("{} may not revoke elevations", actor)));
+        }
+        let request = self.update_elevation(id, ElevationStatus::Active, |request| {
+            request.status = ElevationStatus::Revoked;
+            request.ended_at = Some(Utc::now());
+            request.ended_by = Some(actor.to_string());
+        })?;
+        info!("🔑 {} revoked {}'s {} elevation", actor, request.username, request.requested_role);
+        Self::log_elevation_event(&self.audit_tx, "ELEVATION_REVOKED", actor, &request).await;
+        Ok(request)
+    }
+
+    /// Mark grants that ran out by `now` as expired; they stop applying at expiry either way
+    pub async fn expire_elevations(&self, now: DateTime<Utc>) -> Vec<ElevationRequest> {
+        Self::expire_elevations_in(&self.elevations, &self.audit_tx, now).await
+    }
+
+    /// All elevation requests, newest first
+    pub fn elevations(&self) -> Vec<ElevationRequest> {
+        let mut requests: Vec<_> = self.elevations.read().unwrap().values().cloned().collect();
+        requests.sort_by_key(|request| std::cmp::Reverse(request.requested_at));
+        requests
+    }
+
     /// Create user
     pub async fn create_user(&self, username: &str, email: &str, password: &str, role: UserRole) -> SIEMResult<String> {
         // Validate password against policy
         self.validate_password_policy(password)?;
         
         // Hash password
-        let password_hash = hash(password, DEFAULT_COST)?;
+        let password_hash = self.password_hasher.hash(password)?;
         
         let user_id = Uuid::new_v4().to_string();
         let now = Utc::now();
@@ -639,6 +927,8 @@ impl ComplianceSecurityEngine {
         let requirements = self.get_compliance_requirements(&framework).await?;
         let summary = self.calculate_compliance_summary(&requirements).await?;
         let findings = self.identify_compliance_findings(&requirements).await?;
+        let recommendations = self.generate_recommendations(&summary).await?;
+        let description = format!("Generated {:?} compliance report", framework);
         
         let report = ComplianceReport {
             id: Uuid::new_v4().to_string(),
@@ -650,8 +940,9 @@ impl ComplianceSecurityEngine {
             requirements,
             summary,
             findings,
-            recommendations: self.generate_recommendations(&summary).await?,
+            recommendations,
             attachments: Vec::new(),
+            sections: self.report_sections.read().unwrap().values().cloned().collect(),
         };
         
         self.log_audit_event(
@@ -660,12 +951,17 @@ impl ComplianceSecurityEngine {
             "SYSTEM",
             "SYSTEM",
             true,
-            Some(format!("Generated {} compliance report", framework)),
+            Some(description),
         ).await;
         
         Ok(report)
     }
 
+    /// Add or replace a section included in every generated report
+    pub fn set_report_section(&self, key: &str, section: ReportSection) {
+        self.report_sections.write().unwrap().insert(key.to_string(), section);
+    }
+
     /// Get audit logs
     pub async fn get_audit_logs(&self, filters: AuditLogFilters) -> SIEMResult<Vec<AuditLogEntry>> {
         let logs = self.audit_logs.read().unwrap();
@@ -707,7 +1003,7 @@ impl ComplianceSecurityEngine {
             id: "admin".to_string(),
             username: "admin".to_string(),
             email: "admin@ultra-siem.com".to_string(),
-            password_hash: hash("UltraSIEM2024!", DEFAULT_COST)?,
+            password_hash: self.password_hasher.hash("UltraSIEM2024!")?,
             role: UserRole::SuperAdmin,
             permissions: self.get_role_permissions(&UserRole::SuperAdmin),
             is_active: true,
@@ -845,7 +1141,7 @@ impl ComplianceSecurityEngine {
                 permissions.insert(Permission::ExecuteQueries);
                 permissions
             }
-            UserRole::Custom { permissions, .. } => permissions.clone(),
+            UserRole::Custom { permissions, .. } => permissions.iter().cloned().collect(),
         }
     }
 
@@ -884,7 +1180,20 @@ impl ComplianceSecurityEngine {
     }
 
     async fn log_audit_event(&self, action: &str, resource: &str, username: &str, ip_address: &str, success: bool, error_message: Option<String>) {
-        let entry = AuditLogEntry {
+        Self::send_audit(&self.audit_tx, Self::audit_entry(action, resource, username, ip_address, success, error_message)).await;
+    }
+
+    /// Audit one elevation step with the request attached, so the chain reads from the log alone
+    async fn log_elevation_event(audit_tx: &mpsc::Sender<AuditLogEntry>, action: &str, actor: &str, request: &ElevationRequest) {
+        let mut entry = Self::audit_entry(action, &request.id, actor, "SYSTEM", true, None);
+        entry.resource_type = "PRIVILEGE_ELEVATION".to_string();
+        entry.details = serde_json::to_value(request).unwrap_or_default();
+        entry.risk_level = RiskLevel::High;
+        Self::send_audit(audit_tx, entry).await;
+    }
+
+    fn audit_entry(action: &str, resource: &str, username: &str, ip_address: &str, success: bool, error_message: Option<String>) -> AuditLogEntry {
+        AuditLogEntry {
             id: Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
             user_id: username.to_string(),
@@ -905,13 +1214,86 @@ impl ComplianceSecurityEngine {
             compliance_category: ComplianceCategory::AccessControl,
             risk_level: if success { RiskLevel::Low } else { RiskLevel::High },
             data_classification: DataClassification::Internal,
-        };
-        
-        if let Err(e) = self.audit_tx.send(entry).await {
+        }
+    }
+
+    async fn send_audit(audit_tx: &mpsc::Sender<AuditLogEntry>, entry: AuditLogEntry) {
+        if let Err(e) = audit_tx.send(entry).await {
             error!("Failed to send audit log: {}", e);
         }
     }
 
+    fn elevated_permissions(&self, username: &str) -> HashSet<Permission> {
+        let now = Utc::now();
+        self.elevations
+            .read()
+            .unwrap()
+            .values()
+            .filter(|request| request.username == username && request.grants_at(now))
+            .flat_map(|request| self.get_role_permissions(&request.requested_role))
+            .collect()
+    }
+
+    fn elevation(&self, id: &str) -> SIEMResult<ElevationRequest> {
+        self.elevations
+            .read()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| SIEMError::Validation(format!("Unknown elevation request: {}", id)))
+    }
+
+    /// Permission held through the user's own role, ignoring active elevations
+    fn has_standing_permission(&self, username: &str, permission: &Permission) -> bool {
+        self.users.read().unwrap().get(username).is_some_and(|user| user.permissions.contains(permission))
+    }
+
+    /// Approvers need ManageRoles from their own role: an elevated user cannot extend
+    /// a temporary grant to others
+    fn check_elevation_approver(&self, id: &str, approver: &str) -> SIEMResult<()> {
+        if !self.has_standing_permission(approver, &Permission::ManageRoles) {
+            return Err(SIEMError::Auth(format!("{} may not decide elevation requests", approver)));
+        }
+        if self.elevation(id)?.username == approver && !self.elevation_settings.allow_self_approval {
+            return Err(SIEMError::Auth("Elevation requests need a second person to approve".to_string()));
+        }
+        Ok(())
+    }
+
+    fn update_elevation(&self, id: &str, expected: ElevationStatus, update: impl FnOnce(&mut ElevationRequest)) -> SIEMResult<ElevationRequest> {
+        let mut elevations = self.elevations.write().unwrap();
+        let request = elevations.get_mut(id).ok_or_else(|| SIEMError::Validation(format!("Unknown elevation request: {}", id)))?;
+        if request.status != expected {
+            return Err(SIEMError::Validation(format!("Elevation request {} is {:?}", id, request.status)));
+        }
+        update(request);
+        Ok(request.clone())
+    }
+
+    async fn expire_elevations_in(
+        elevations: &RwLock<HashMap<String, ElevationRequest>>,
+        audit_tx: &mpsc::Sender<AuditLogEntry>,
+        now: DateTime<Utc>,
+    ) -> Vec<ElevationRequest> {
+        let expired: Vec<ElevationRequest> = elevations
+            .write()
+            .unwrap()
+            .values_mut()
+            .filter(|request| request.status == ElevationStatus::Active && !request.grants_at(now))
+            .map(|request| {
+                request.status = ElevationStatus::Expired;
+                request.ended_at = request.expires_at;
+                request.ended_by = Some("SYSTEM".to_string());
+                request.clone()
+            })
+            .collect();
+        for request in &expired {
+            info!("🔑 {}'s {} elevation expired", request.username, request.requested_role);
+            Self::log_elevation_event(audit_tx, "ELEVATION_EXPIRED", "SYSTEM", request).await;
+        }
+        expired
+    }
+
     async fn process_audit_logs(mut audit_rx: mpsc::Receiver<AuditLogEntry>, audit_logs: Arc<RwLock<VecDeque<AuditLogEntry>>>, max_logs: usize) {
         while let Some(entry) = audit_rx.recv().await {
             let mut logs = audit_logs.write().unwrap();
@@ -942,28 +1324,46 @@ impl ComplianceSecurityEngine {
 
     fn validate_password_policy(&self, password: &str) -> SIEMResult<()> {
         if password.len() < self.password_policy.min_length as usize {
-            return Err("Password too short".into());
+            return Err(SIEMError::Validation("Password too short".to_string()));
         }
         
         if self.password_policy.require_uppercase && !password.chars().any(|c| c.is_uppercase()) {
-            return Err("Password must contain uppercase letter".into());
+            return Err(SIEMError::Validation("Password must contain uppercase letter".to_string()));
         }
         
         if self.password_policy.require_lowercase && !password.chars().any(|c| c.is_lowercase()) {
-            return Err("Password must contain lowercase letter".into());
+            return Err(SIEMError::Validation("Password must contain lowercase letter".to_string()));
         }
         
         if self.password_policy.require_numbers && !password.chars().any(|c| c.is_numeric()) {
-            return Err("Password must contain number".into());
+            return Err(SIEMError::Validation("Password must contain number".to_string()));
         }
         
         if self.password_policy.require_special_chars && !password.chars().any(|c| !c.is_alphanumeric()) {
-            return Err("Password must contain special character".into());
+            return Err(SIEMError::Validation("Password must contain special character".to_string()));
         }
         
         Ok(())
     }
 
+    /// Replace a verified password's hash with one on the current parameters
+    async fn rehash_password(&self, username: &str, password: &str) {
+        let (success, error_message) = match self.password_hasher.hash(password) {
+            Ok(password_hash) => {
+                if let Some(user) = self.users.write().unwrap().get_mut(username) {
+                    user.password_hash = password_hash;
+                    user.updated_at = Utc::now();
+                }
+                (true, None)
+            }
+            Err(e) => {
+                warn!("Failed to rehash password for {}: {}", username, e);
+                (false, Some(e.to_string()))
+            }
+        };
+        self.log_audit_event("PASSWORD_REHASHED", "USER_MANAGEMENT", username, "SYSTEM", success, error_message).await;
+    }
+
     async fn reset_failed_login_attempts(&self, username: &str) -> SIEMResult<()> {
         let mut users = self.users.write().unwrap();
         if let Some(user) = users.get_mut(username) {
@@ -1181,7 +1581,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_user_authentication() {
-        let engine = ComplianceSecurityEngine::new("test_secret".to_string());
+        let mut engine = ComplianceSecurityEngine::new("test_secret".to_string());
         engine.start().await.unwrap();
         
         // Test authentication with default admin user
@@ -1189,9 +1589,155 @@ mod tests {
         assert!(result.is_some());
     }
 
+    async fn engine_with_analyst() -> ComplianceSecurityEngine {
+        let mut engine = ComplianceSecurityEngine::new("test_secret".to_string());
+        engine.start().await.unwrap();
+        engine
+            .set_password_hashing(PasswordHashSettings { algorithm: crate::password_hashing::HashAlgorithm::Bcrypt, bcrypt_cost: 4, ..Default::default() })
+            .unwrap();
+        engine.create_user("alice", "alice@example.com", "Analyst-Pass-2024!", UserRole::SecurityAnalyst).await.unwrap();
+        engine
+    }
+
+    #[tokio::test]
+    async fn test_memory_limits_keep_open_elevations_and_recent_sessions() {
+        let engine = engine_with_analyst().await;
+        let request = engine.request_elevation("alice", UserRole::IncidentResponder, "Triage phishing wave (INC-5120)", 30).await.unwrap();
+        {
+            let mut sessions = engine.sessions.write().unwrap();
+            for i in 0..4i64 {
+                let at = Utc::now() - chrono::Duration::minutes(10 - i);
+                sessions.insert(format!("s{}", i), UserSession {
+                    session_id: format!("s{}", i),
+                    user_id: "alice".to_string(),
+                    username: "alice".to_string(),
+                    ip_address: "192.0.2.4".to_string(),
+                    user_agent: "test".to_string(),
+                    created_at: at,
+                    last_activity: at,
+                    expires_at: at + chrono::Duration::hours(8),
+                    is_active: true,
+                    permissions: HashSet::new(),
+                });
+            }
+        }
+        let limit = crate::memory_budget::StructureLimit { max_entries: 1, ttl_seconds: 0 };
+        let memory = MemoryAccountant::new(crate::memory_budget::MemorySettings {
+            limits: [("compliance.sessions".to_string(), limit), ("compliance.elevations".to_string(), limit)].into(),
+            ..Default::default()
+        })
+        .unwrap();
+        engine.enforce_memory_limits(&memory);
+
+        let sessions = engine.sessions.read().unwrap().keys().cloned().collect::<Vec<_>>();
+        assert_eq!(sessions, vec!["s3".to_string()]);
+        assert!(engine.elevations.read().unwrap().contains_key(&request.id));
+        let report = memory.report();
+        assert_eq!(report.subsystems["compliance"].structures["sessions"].evicted_over_limit_total, 3);
+        assert_eq!(report.subsystems["compliance"].structures["elevations"].evicted_over_limit_total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_elevation_grants_until_expiry_and_audits_the_chain() {
+        let engine = engine_with_analyst().await;
+        assert!(!engine.check_permission("alice", &Permission::ExecuteResponseActions));
+
+        let request = engine
+            .request_elevation("alice", UserRole::IncidentResponder, "Contain ransomware on FIN-WS-12 (INC-4411)", 60)
+            .await
+            .unwrap();
+        assert!(!engine.check_permission("alice", &Permission::ExecuteResponseActions));
+
+        let granted = engine.approve_elevation(&request.id, "admin", Some("on call".to_string())).await.unwrap();
+        assert_eq!(granted.status, ElevationStatus::Active);
+        assert!(engine.check_permission("alice", &Permission::ExecuteResponseActions));
+        assert!(engine.effective_permissions("alice").contains(&Permission::ReadSecurityData));
+
+        let expires_at = granted.expires_at.unwrap();
+        assert!(engine.expire_elevations(expires_at - chrono::Duration::seconds(1)).await.is_empty());
+        let expired = engine.expire_elevations(expires_at).await;
+        assert_eq!(expired.len(), 1);
+        assert_eq!(engine.elevations()[0].status, ElevationStatus::Expired);
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let logs = engine.audit_logs.read().unwrap();
+        let chain: Vec<_> = logs
+            .iter()
+            .filter(|log| log.resource == request.id)
+            .map(|log| (log.action.as_str(), log.details["status"].as_str().unwrap_or_default()))
+            .collect();
+        assert_eq!(chain, vec![("ELEVATION_REQUESTED", "pending"), ("ELEVATION_APPROVED", "active"), ("ELEVATION_EXPIRED", "expired")]);
+    }
+
+    #[tokio::test]
+    async fn test_elevation_needs_a_second_approver_with_manage_roles() {
+        let engine = engine_with_analyst().await;
+        engine.create_user("bob", "bob@example.com", "Analyst-Pass-2024!", UserRole::SecurityAnalyst).await.unwrap();
+
+        assert!(engine.request_elevation("alice", UserRole::SuperAdmin, "because", 60).await.is_err());
+        assert!(engine.request_elevation("alice", UserRole::ReadOnly, "Need to look at the dashboards", 60).await.is_err());
+        let request = engine
+            .request_elevation("alice", UserRole::SuperAdmin, "Rotate the compromised service accounts", 30)
+            .await
+            .unwrap();
+        assert!(engine.request_elevation("alice", UserRole::SecurityAdmin, "Second request while one is open", 30).await.is_err());
+
+        assert!(matches!(engine.approve_elevation(&request.id, "bob", None).await, Err(SIEMError::Auth(_))));
+        assert!(matches!(engine.approve_elevation(&request.id, "alice", None).await, Err(SIEMError::Auth(_))));
+        let denied = engine.deny_elevation(&request.id, "admin", Some("use the break-glass account".to_string())).await.unwrap();
+        assert_eq!((denied.status, denied.approver.as_deref()), (ElevationStatus::Denied, Some("admin")));
+        assert!(!engine.check_permission("alice", &Permission::ManageUsers));
+        assert!(engine.approve_elevation(&request.id, "admin", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_elevated_user_cannot_approve_other_elevations() {
+        let engine = engine_with_analyst().await;
+        engine.create_user("bob", "bob@example.com", "Analyst-Pass-2024!", UserRole::SecurityAnalyst).await.unwrap();
+        let bob = engine.request_elevation("bob", UserRole::SuperAdmin, "Rebuild the role matrix after the merger", 30).await.unwrap();
+        engine.approve_elevation(&bob.id, "admin", None).await.unwrap();
+        assert!(engine.check_permission("bob", &Permission::ManageRoles));
+
+        let alice = engine.request_elevation("alice", UserRole::SuperAdmin, "Rotate the compromised service accounts", 30).await.unwrap();
+        assert!(matches!(engine.approve_elevation(&alice.id, "bob", None).await, Err(SIEMError::Auth(_))));
+        assert!(matches!(engine.deny_elevation(&alice.id, "bob", None).await, Err(SIEMError::Auth(_))));
+        assert_eq!(engine.approve_elevation(&alice.id, "admin", None).await.unwrap().approver.as_deref(), Some("admin"));
+        assert!(matches!(engine.revoke_elevation(&alice.id, "bob").await, Err(SIEMError::Auth(_))));
+        assert_eq!(engine.revoke_elevation(&bob.id, "bob").await.unwrap().status, ElevationStatus::Revoked);
+        assert_eq!(engine.revoke_elevation(&alice.id, "admin").await.unwrap().ended_by.as_deref(), Some("admin"));
+    }
+
+    #[tokio::test]
+    async fn test_session_user_resolves_active_sessions_only() {
+        let engine = engine_with_analyst().await;
+        let session = engine.authenticate_user("alice", "Analyst-Pass-2024!", "127.0.0.1").await.unwrap().unwrap();
+        assert_eq!(engine.session_user(&session).as_deref(), Some("alice"));
+        engine.sessions.write().unwrap().get_mut(&session).unwrap().expires_at = Utc::now() - chrono::Duration::seconds(1);
+        assert!(engine.session_user(&session).is_none());
+        assert!(engine.session_user("unknown").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_login_migrates_bcrypt_hash() {
+        let mut engine = ComplianceSecurityEngine::new("test_secret".to_string());
+        engine.start().await.unwrap();
+        let legacy = bcrypt::hash("UltraSIEM2024!", 4).unwrap();
+        engine.users.write().unwrap().get_mut("admin").unwrap().password_hash = legacy;
+        engine
+            .set_password_hashing(PasswordHashSettings { argon2_memory_kib: 64, argon2_iterations: 1, ..Default::default() })
+            .unwrap();
+        assert_eq!(engine.password_migration_report().outdated.len(), 1);
+
+        let session = engine.authenticate_user("admin", "UltraSIEM2024!", "127.0.0.1").await.unwrap();
+        assert!(session.is_some());
+        assert!(engine.users.read().unwrap()["admin"].password_hash.starts_with("$argon2id$"));
+        assert!(engine.password_migration_report().outdated.is_empty());
+        assert!(engine.authenticate_user("admin", "UltraSIEM2024!", "127.0.0.1").await.unwrap().is_some());
+    }
+
     #[tokio::test]
     async fn test_permission_checking() {
-        let engine = ComplianceSecurityEngine::new("test_secret".to_string());
+        let mut engine = ComplianceSecurityEngine::new("test_secret".to_string());
         engine.start().await.unwrap();
         
         // Admin should have all permissions
@@ -1201,7 +1747,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_compliance_report_generation() {
-        let engine = ComplianceSecurityEngine::new("test_secret".to_string());
+        let mut engine = ComplianceSecurityEngine::new("test_secret".to_string());
         engine.start().await.unwrap();
         
         let report = engine.generate_compliance_report(
@@ -0,0 +1,397 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatConfig;
+use crate::anomaly_tuning::AnomalyTuningSettings;
+#[cfg(feature = "response")]
+use crate::agent_tasking::AgentTaskingSettings;
+use crate::aggregation::AggregationSettings;
+#[cfg(feature = "collectors")]
+use crate::backfill::BackfillSettings;
+#[cfg(feature = "chaos")]
+use crate::chaos::ChaosSettings;
+use crate::brute_force::BruteForceSettings;
+use crate::config_change::ConfigChangeSettings;
+#[cfg(feature = "response")]
+use crate::incident_archive::ArchiveSettings;
+#[cfg(feature = "response")]
+use crate::chat_mirror::ChatMirrorSettings;
+#[cfg(feature = "response")]
+use crate::digest::DigestSettings;
+#[cfg(feature = "response")]
+use crate::output_sinks::SinkSettings;
+#[cfg(feature = "response")]
+use crate::canary::CanarySettings;
+use crate::cardinality::CardinalitySettings;
+use crate::cert_monitor::CertMonitorSettings;
+use crate::honeyport::HoneyportSettings;
+use crate::canary_asset::CanaryAssetSettings;
+use crate::tls_fingerprint::TlsFingerprintSettings;
+use crate::intel_fusion::IntelFusionSettings;
+use crate::domain_baseline::DomainLearningSettings;
+use crate::clock_skew::ClockSkewSettings;
+use crate::content_audit::{AllowListSettings, ContentAuditSettings};
+use crate::config_bundle::ConfigBundleSettings;
+use crate::forensic::ForensicSettings;
+use crate::os_service::ServiceSettings;
+use crate::memory_budget::MemorySettings;
+use crate::quantum_detector::QuantumPatternSettings;
+use crate::event_time::EventTimeSettings;
+use crate::content_pack::ContentPackSettings;
+use crate::dedup::DedupSettings;
+use crate::detection_profile::DetectionProfileSettings;
+use crate::latency_budget::LatencyBudgetSettings;
+use crate::degradation::DegradationSettings;
+use crate::autoscaling::AutoscalingSettings;
+use crate::error_handling::{SIEMError, SIEMResult};
+#[cfg(feature = "response")]
+use crate::evidence::EvidenceSettings;
+#[cfg(feature = "response")]
+use crate::storage::StorageSettings;
+#[cfg(feature = "response")]
+use crate::fatigue::FatigueSettings;
+#[cfg(feature = "response")]
+use crate::fp_learning::FpLearningSettings;
+#[cfg(feature = "response")]
+use crate::response_metrics::ResponseMetricsSettings;
+#[cfg(feature = "response")]
+use crate::forwarding::ForwardingSettings;
+#[cfg(feature = "response")]
+use crate::incident_export::IncidentExportSettings;
+#[cfg(feature = "response")]
+use crate::incident_response::{AlertConfig, ResponseRule};
+use crate::parsing::PipelineConfig;
+#[cfg(feature = "compliance")]
+use crate::password_hashing::PasswordHashSettings;
+#[cfg(feature = "compliance")]
+use crate::elevation::ElevationSettings;
+#[cfg(feature = "response")]
+use crate::related::RelatedEventsSettings;
+#[cfg(feature = "response")]
+use crate::playbook::PlaybookSettings;
+#[cfg(feature = "response")]
+use crate::response_verification::ResponseVerificationSettings;
+#[cfg(feature = "api")]
+use crate::incident_cache::IncidentCacheSettings;
+#[cfg(feature = "api")]
+use crate::query_export::ExportSettings;
+#[cfg(feature = "api")]
+use crate::access_scope::DataAccessSettings;
+use crate::replay::ReplaySettings;
+#[cfg(feature = "response")]
+use crate::routing::RoutingSettings;
+#[cfg(feature = "response")]
+use crate::payload_template::WebhookTemplateSettings;
+#[cfg(feature = "response")]
+use crate::severity::SeveritySettings;
+#[cfg(feature = "response")]
+use crate::shared_state::SharedStateSettings;
+use crate::secret_scan::SecretScanSettings;
+use crate::tagging::TaggingSettings;
+use crate::text_normalization::TextNormalizationSettings;
+use crate::decoder_chain::DecoderSettings;
+use crate::snapshot::SnapshotSettings;
+use crate::suppression::SuppressionSettings;
+#[cfg(feature = "response")]
+use crate::telemetry::TelemetrySettings;
+use crate::threat_detection::SignaturePattern;
+#[cfg(feature = "response")]
+use crate::triage::TriageSettings;
+use crate::trends::TrendSettings;
+use crate::timezone::TimeZoneSettings;
+#[cfg(feature = "waf")]
+use crate::waf::WafSettings;
+use crate::web_access::WebAccessSettings;
+use crate::zig_query::ZigQuerySettings;
+
+/// Default location of the unified configuration file
+pub const DEFAULT_CONFIG_PATH: &str = "config/ultra_siem.toml";
+
+/// NATS connection settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NatsSettings {
+    pub url: String,
+    pub connect_timeout_seconds: u64,
+}
+
+impl Default for NatsSettings {
+    fn default() -> Self {
+        Self {
+            url: "nats://127.0.0.1:4222".to_string(),
+            connect_timeout_seconds: 5,
+        }
+    }
+}
+
+/// ClickHouse connection settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClickHouseSettings {
+    pub url: String,
+    pub database: String,
+    pub timeout_seconds: u64,
+}
+
+impl Default for ClickHouseSettings {
+    fn default() -> Self {
+        Self {
+            url: "http://127.0.0.1:8123".to_string(),
+            database: "siem".to_string(),
+            timeout_seconds: 5,
+        }
+    }
+}
+
+/// GeoIP enrichment settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeoIpSettings {
+    pub enabled: bool,
+    pub database_path: String,
+}
+
+impl Default for GeoIpSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            database_path: "/usr/share/GeoIP/GeoLite2-City.mmdb".to_string(),
+        }
+    }
+}
+
+/// gRPC API settings
+#[cfg(feature = "api")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrpcSettings {
+    pub enabled: bool,
+    pub listen_addr: String,
+    /// Accepted bearer tokens; an empty list disables token auth
+    pub auth_tokens: Vec<String>,
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+}
+
+#[cfg(feature = "api")]
+impl Default for GrpcSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_addr: "0.0.0.0:50051".to_string(),
+            auth_tokens: Vec::new(),
+            tls_cert_path: None,
+            tls_key_path: None,
+        }
+    }
+}
+
+/// REST API settings
+#[cfg(feature = "api")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestSettings {
+    pub enabled: bool,
+    pub listen_addr: String,
+    /// Accepted bearer tokens; an empty list disables token auth
+    pub auth_tokens: Vec<String>,
+}
+
+#[cfg(feature = "api")]
+impl Default for RestSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_addr: "0.0.0.0:8088".to_string(),
+            auth_tokens: Vec::new(),
+        }
+    }
+}
+
+/// Unified Ultra SIEM configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SiemConfig {
+    pub nats: NatsSettings,
+    pub clickhouse: ClickHouseSettings,
+    pub geoip: GeoIpSettings,
+    pub detection: AdvancedThreatConfig,
+    pub anomaly_tuning: AnomalyTuningSettings,
+    pub dedup: DedupSettings,
+    pub latency_budget: LatencyBudgetSettings,
+    pub detection_profiles: DetectionProfileSettings,
+    pub clock_skew: ClockSkewSettings,
+    pub pipelines: Vec<PipelineConfig>,
+    pub snapshot: SnapshotSettings,
+    pub degradation: DegradationSettings,
+    pub autoscaling: AutoscalingSettings,
+    pub content_packs: ContentPackSettings,
+    pub content_audit: ContentAuditSettings,
+    pub config_bundle: ConfigBundleSettings,
+    pub replay: ReplaySettings,
+    pub aggregation: AggregationSettings,
+    pub cardinality: CardinalitySettings,
+    pub brute_force: BruteForceSettings,
+    pub config_change: ConfigChangeSettings,
+    pub web_access: WebAccessSettings,
+    pub cert_monitor: CertMonitorSettings,
+    pub tls_fingerprint: TlsFingerprintSettings,
+    pub honeyport: HoneyportSettings,
+    pub canary_assets: CanaryAssetSettings,
+    pub intel_fusion: IntelFusionSettings,
+    pub domain_learning: DomainLearningSettings,
+    pub secret_scan: SecretScanSettings,
+    pub tagging: TaggingSettings,
+    pub text_normalization: TextNormalizationSettings,
+    pub decoder: DecoderSettings,
+    pub suppression: SuppressionSettings,
+    pub trends: TrendSettings,
+    pub time_zones: TimeZoneSettings,
+    pub forensic: ForensicSettings,
+    pub service: ServiceSettings,
+    pub memory: MemorySettings,
+    pub quantum: QuantumPatternSettings,
+    pub event_time: EventTimeSettings,
+    #[cfg(feature = "chaos")]
+    pub chaos: ChaosSettings,
+    #[cfg(feature = "collectors")]
+    pub backfill: BackfillSettings,
+    #[cfg(feature = "response")]
+    pub alerts: Option<AlertConfig>,
+    pub signatures: Vec<SignaturePattern>,
+    pub allow_list: AllowListSettings,
+    #[cfg(feature = "compliance")]
+    pub password_hashing: PasswordHashSettings,
+    #[cfg(feature = "compliance")]
+    pub elevation: ElevationSettings,
+    #[cfg(feature = "response")]
+    pub response_rules: Vec<ResponseRule>,
+    #[cfg(feature = "api")]
+    pub grpc: GrpcSettings,
+    #[cfg(feature = "api")]
+    pub rest: RestSettings,
+    #[cfg(feature = "api")]
+    pub data_access: DataAccessSettings,
+    #[cfg(feature = "api")]
+    pub export: ExportSettings,
+    #[cfg(feature = "api")]
+    pub incident_cache: IncidentCacheSettings,
+    #[cfg(feature = "response")]
+    pub evidence: EvidenceSettings,
+    #[cfg(feature = "response")]
+    pub storage: StorageSettings,
+    #[cfg(feature = "response")]
+    pub incident_export: IncidentExportSettings,
+    #[cfg(feature = "response")]
+    pub triage: TriageSettings,
+    #[cfg(feature = "response")]
+    pub related_events: RelatedEventsSettings,
+    #[cfg(feature = "response")]
+    pub playbooks: PlaybookSettings,
+    #[cfg(feature = "response")]
+    pub severity: SeveritySettings,
+    #[cfg(feature = "response")]
+    pub fatigue: FatigueSettings,
+    #[cfg(feature = "response")]
+    pub fp_learning: FpLearningSettings,
+    #[cfg(feature = "response")]
+    pub response_metrics: ResponseMetricsSettings,
+    #[cfg(feature = "response")]
+    pub routing: RoutingSettings,
+    #[cfg(feature = "response")]
+    pub webhook_templates: WebhookTemplateSettings,
+    #[cfg(feature = "response")]
+    pub response_verification: ResponseVerificationSettings,
+    #[cfg(feature = "response")]
+    pub forwarding: ForwardingSettings,
+    #[cfg(feature = "response")]
+    pub agents: AgentTaskingSettings,
+    #[cfg(feature = "response")]
+    pub archive: ArchiveSettings,
+    #[cfg(feature = "response")]
+    pub chat_mirror: ChatMirrorSettings,
+    #[cfg(feature = "response")]
+    pub digest: DigestSettings,
+    #[cfg(feature = "response")]
+    pub sinks: SinkSettings,
+    #[cfg(feature = "response")]
+    pub canary: CanarySettings,
+    #[cfg(feature = "response")]
+    pub shared_state: SharedStateSettings,
+    #[cfg(feature = "response")]
+    pub telemetry: TelemetrySettings,
+    #[cfg(feature = "waf")]
+    pub waf: WafSettings,
+    pub zig_query: ZigQuerySettings,
+}
+
+impl SiemConfig {
+    /// Load configuration from a TOML file and apply environment overrides
+    pub fn load<P: AsRef<Path>>(path: P) -> SIEMResult<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            SIEMError::Config(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        let mut config = Self::from_toml_str(&content)?;
+        config.apply_env_overrides();
+        Ok(config)
+    }
+
+    /// Parse configuration from a TOML string
+    pub fn from_toml_str(content: &str) -> SIEMResult<Self> {
+        toml::from_str(content).map_err(|e| SIEMError::Config(format!("Invalid TOML: {}", e)))
+    }
+
+    /// Override connection settings from `NATS_URL` / `CLICKHOUSE_URL` / `GEOIP_DB_PATH`
+    pub fn apply_env_overrides(&mut self) {
+        if let Ok(url) = std::env::var("NATS_URL") {
+            self.nats.url = url;
+        }
+        if let Ok(url) = std::env::var("CLICKHOUSE_URL") {
+            self.clickhouse.url = url;
+        }
+        if let Ok(path) = std::env::var("GEOIP_DB_PATH") {
+            self.geoip.database_path = path;
+        }
+        #[cfg(feature = "response")]
+        if let Ok(site_id) = std::env::var("ULTRA_SIEM_SITE_ID") {
+            self.forwarding.site_id = site_id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_config_uses_defaults() {
+        let config = SiemConfig::from_toml_str(
+            r#"
+            [nats]
+            url = "nats://nats:4222"
+
+            [[signatures]]
+            id = "sqli"
+            name = "SQL Injection"
+            pattern = "(?i)union\\s+select"
+            category = "SQLInjection"
+            severity = "High"
+            description = "Union based SQL injection"
+            enabled = true
+            confidence = 0.9
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.nats.url, "nats://nats:4222");
+        assert_eq!(config.clickhouse.url, "http://127.0.0.1:8123");
+        assert_eq!(config.signatures.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_toml_is_config_error() {
+        let result = SiemConfig::from_toml_str("[nats\nurl = ");
+        assert!(matches!(result, Err(SIEMError::Config(_))));
+    }
+}
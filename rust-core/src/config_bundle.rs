@@ -0,0 +1,514 @@
+//! # Configuration Bundles
+//!
+//! One signed file carrying everything that defines how an engine detects and
+//! responds: the config file with its rules, allow-lists and policies, the
+//! playbook files, installed content packs, detection profiles, suppressions,
+//! canary assets and approved domain allow-lists. Learned and observed state
+//! (baselines, tuning, honeypot history) stays behind.
+//!
+//! For disaster recovery and staging to production promotion, `export` signs a
+//! bundle with the local Ed25519 key; `import` verifies it against
+//! `trusted_keys`, previews the differences to the local files and applies all
+//! or selected items. Item paths come from the importing side's configuration,
+//! and local files the bundle lacks are reported but left in place.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use base64ct::{Base64, Encoding};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::SiemConfig;
+use crate::content_audit::{self, ContentChange, DetectionContent, CONTENT_SECTIONS};
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// Version of the bundle layout written by `export`
+pub const BUNDLE_FORMAT: u32 = 1;
+const CONFIG_ITEM: &str = "config";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigBundleSettings {
+    /// Key id written into exported bundles
+    pub key_id: String,
+    /// File holding the base64 Ed25519 seed exports are signed with
+    pub signing_key_path: String,
+    /// Key id -> base64 Ed25519 public key of environments bundles are imported from
+    pub trusted_keys: HashMap<String, String>,
+}
+
+impl Default for ConfigBundleSettings {
+    fn default() -> Self {
+        Self {
+            key_id: "local".to_string(),
+            signing_key_path: "config/bundle-signing.key".to_string(),
+            trusted_keys: HashMap::new(),
+        }
+    }
+}
+
+impl ConfigBundleSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if self.key_id.trim().is_empty() {
+            return Err(SIEMError::Validation("config_bundle.key_id must not be empty".to_string()));
+        }
+        for (key_id, key) in &self.trusted_keys {
+            decode_key(key).map_err(|e| SIEMError::Validation(format!("config_bundle.trusted_keys.{}: {}", key_id, e)))?;
+        }
+        Ok(())
+    }
+
+    /// Signing key read from `signing_key_path`; comment lines are skipped
+    pub fn signing_key(&self) -> SIEMResult<SigningKey> {
+        let text = fs::read_to_string(&self.signing_key_path)
+            .map_err(|e| SIEMError::Config(format!("Cannot read bundle signing key {}: {}", self.signing_key_path, e)))?;
+        let seed = text.lines().find(|l| !l.trim().is_empty() && !l.starts_with('#')).unwrap_or_default();
+        Ok(SigningKey::from_bytes(&decode_key(seed)?))
+    }
+}
+
+/// One file of the bundle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleItem {
+    pub sha256: String,
+    pub content: String,
+}
+
+impl BundleItem {
+    fn new(content: String) -> Self {
+        Self { sha256: sha256(&content), content }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigBundle {
+    pub format: u32,
+    pub created_at: DateTime<Utc>,
+    pub created_by: String,
+    /// `config`, `suppressions`, `playbooks/<file>`, `content_packs/<path>`, ...
+    pub items: BTreeMap<String, BundleItem>,
+}
+
+/// Distribution format: the bundle JSON plus its detached signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedConfigBundle {
+    pub key_id: String,
+    pub signature: String,
+    pub bundle: String,
+}
+
+impl SignedConfigBundle {
+    /// Check the signature against `trusted_keys` and every item's hash
+    pub fn verify(&self, trusted_keys: &HashMap<String, String>) -> SIEMResult<ConfigBundle> {
+        let key = trusted_keys
+            .get(&self.key_id)
+            .ok_or_else(|| SIEMError::Auth(format!("Config bundle signed by untrusted key {}", self.key_id)))?;
+        let invalid = || SIEMError::Auth("Config bundle signature is invalid".to_string());
+        let mut signature = [0u8; 64];
+        if Base64::decode(&self.signature, &mut signature).map_err(|_| invalid())?.len() != signature.len() {
+            return Err(invalid());
+        }
+        VerifyingKey::from_bytes(&decode_key(key)?)
+            .map_err(|_| SIEMError::Config(format!("Malformed trusted key {}", self.key_id)))?
+            .verify_strict(self.bundle.as_bytes(), &Signature::from_bytes(&signature))
+            .map_err(|_| invalid())?;
+
+        let bundle: ConfigBundle = serde_json::from_str(&self.bundle)?;
+        if bundle.format != BUNDLE_FORMAT {
+            return Err(SIEMError::Validation(format!("Unsupported config bundle format {}", bundle.format)));
+        }
+        if let Some((name, _)) = bundle.items.iter().find(|(_, item)| item.sha256 != sha256(&item.content)) {
+            return Err(SIEMError::Validation(format!("Config bundle item {} does not match its hash", name)));
+        }
+        Ok(bundle)
+    }
+}
+
+/// Where the bundle items of `config`, loaded from `config_path`, live: (item, path, is directory)
+fn locations(config: &SiemConfig, config_path: &Path) -> Vec<(&'static str, PathBuf, bool)> {
+    vec![
+        (CONFIG_ITEM, config_path.to_path_buf(), false),
+        ("detection_profiles", PathBuf::from(&config.detection_profiles.store_path), false),
+        ("suppressions", PathBuf::from(&config.suppression.store_path), false),
+        ("canary_assets", PathBuf::from(&config.canary_assets.store_path), false),
+        ("domain_baseline", PathBuf::from(&config.domain_learning.store_path), false),
+        ("content_packs", PathBuf::from(&config.content_packs.install_dir), true),
+        #[cfg(feature = "response")]
+        ("playbooks", PathBuf::from(&config.playbooks.dir), true),
+    ]
+}
+
+/// Local files of every item, keyed like bundle items
+fn local_items(config: &SiemConfig, config_path: &Path) -> SIEMResult<BTreeMap<String, PathBuf>> {
+    let mut items = BTreeMap::new();
+    for (item, path, is_dir) in locations(config, config_path) {
+        if !is_dir {
+            if path.is_file() {
+                items.insert(item.to_string(), path);
+            }
+            continue;
+        }
+        let mut pending = vec![path.clone()];
+        while let Some(dir) = pending.pop() {
+            let Ok(entries) = fs::read_dir(&dir) else { continue };
+            for entry in entries {
+                let file = entry?.path();
+                if file.is_dir() {
+                    pending.push(file);
+                } else if let Ok(relative) = file.strip_prefix(&path) {
+                    let relative: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+                    items.insert(format!("{}/{}", item, relative.join("/")), file);
+                }
+            }
+        }
+    }
+    Ok(items)
+}
+
+/// Local path of bundle item `name` under `config`
+fn item_path(config: &SiemConfig, config_path: &Path, name: &str) -> SIEMResult<PathBuf> {
+    let (group, rest) = name.split_once('/').map_or((name, None), |(group, rest)| (group, Some(rest)));
+    let unknown = || SIEMError::Validation(format!("Unknown config bundle item {}", name));
+    let (_, path, is_dir) = locations(config, config_path).into_iter().find(|(item, _, _)| *item == group).ok_or_else(unknown)?;
+    match (is_dir, rest) {
+        (false, None) => Ok(path),
+        (true, Some(rest)) if !rest.split('/').any(|part| part.is_empty() || part == "." || part == "..") => Ok(path.join(rest)),
+        _ => Err(unknown()),
+    }
+}
+
+/// Collect the local configuration into an unsigned bundle
+pub fn collect(config: &SiemConfig, config_path: &Path, actor: &str, now: DateTime<Utc>) -> SIEMResult<ConfigBundle> {
+    let mut items = BTreeMap::new();
+    for (name, path) in local_items(config, config_path)? {
+        let content = fs::read_to_string(&path).map_err(|e| SIEMError::Config(format!("Cannot bundle {}: {}", path.display(), e)))?;
+        items.insert(name, BundleItem::new(content));
+    }
+    Ok(ConfigBundle { format: BUNDLE_FORMAT, created_at: now, created_by: actor.to_string(), items })
+}
+
+/// Collect and sign the local configuration
+pub fn export(config: &SiemConfig, config_path: &Path, signing_key: &SigningKey, actor: &str) -> SIEMResult<SignedConfigBundle> {
+    let bundle = collect(config, config_path, actor, Utc::now())?;
+    info!("📦 Exporting config bundle with {} item(s)", bundle.items.len());
+    let bundle = serde_json::to_string(&bundle)?;
+    let signature = encode(&signing_key.sign(bundle.as_bytes()).to_bytes());
+    Ok(SignedConfigBundle { key_id: config.config_bundle.key_id.clone(), signature, bundle })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    Added,
+    Modified,
+    Unchanged,
+    /// Present locally but not in the bundle; never removed by `apply`
+    LocalOnly,
+}
+
+/// Difference between one bundle item and the local file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemDiff {
+    pub item: String,
+    pub status: ItemStatus,
+    /// For the config file: changed sections and detection content
+    pub changes: Vec<String>,
+}
+
+impl ItemDiff {
+    pub fn summary(&self) -> String {
+        let sign = match self.status {
+            ItemStatus::Added => "+",
+            ItemStatus::Modified => "~",
+            ItemStatus::Unchanged => "=",
+            ItemStatus::LocalOnly => "?",
+        };
+        let mut line = format!("{} {}", sign, self.item);
+        for change in &self.changes {
+            line.push_str("\n    ");
+            line.push_str(change);
+        }
+        line
+    }
+}
+
+/// Compare `bundle` with the local files of `config`
+pub fn preview(bundle: &ConfigBundle, config: &SiemConfig, config_path: &Path) -> SIEMResult<Vec<ItemDiff>> {
+    let local = local_items(config, config_path)?;
+    let mut diffs = Vec::new();
+    for (name, item) in &bundle.items {
+        let current = match local.get(name) {
+            Some(path) => Some(fs::read_to_string(path)?),
+            None => None,
+        };
+        let (status, changes) = match &current {
+            None => (ItemStatus::Added, Vec::new()),
+            Some(current) if sha256(current) == item.sha256 => (ItemStatus::Unchanged, Vec::new()),
+            Some(current) if name == CONFIG_ITEM => (ItemStatus::Modified, config_changes(current, &item.content)?),
+            Some(_) => (ItemStatus::Modified, Vec::new()),
+        };
+        diffs.push(ItemDiff { item: name.clone(), status, changes });
+    }
+    for name in local.keys().filter(|name| !bundle.items.contains_key(*name)) {
+        diffs.push(ItemDiff { item: name.clone(), status: ItemStatus::LocalOnly, changes: Vec::new() });
+    }
+    Ok(diffs)
+}
+
+/// Changed top-level sections, with detection content broken down per item
+fn config_changes(current: &str, bundled: &str) -> SIEMResult<Vec<String>> {
+    let parse = |text: &str| toml::from_str::<toml::Table>(text).map_err(|e| SIEMError::Config(format!("Invalid TOML: {}", e)));
+    let (before, after) = (parse(current)?, parse(bundled)?);
+    let sections: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let mut changes: Vec<String> = sections
+        .into_iter()
+        .filter(|section| !CONTENT_SECTIONS.contains(&section.as_str()))
+        .filter_map(|section| match (before.get(section), after.get(section)) {
+            (None, Some(_)) => Some(format!("+ [{}]", section)),
+            (Some(_), None) => Some(format!("- [{}]", section)),
+            (Some(old), Some(new)) if old != new => Some(format!("~ [{}]", section)),
+            _ => None,
+        })
+        .collect();
+    let content = |text: &str| SiemConfig::from_toml_str(text).map(|config| DetectionContent::from_config(&config, Vec::new()));
+    changes.extend(content(bundled)?.diff(&content(current)?).iter().map(ContentChange::summary));
+    Ok(changes)
+}
+
+/// What `apply` wrote
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApplyReport {
+    pub applied: Vec<String>,
+    /// Sections replaced when only part of the config file was selected
+    pub config_sections: Vec<String>,
+    /// Copy of the config file from before the import
+    pub config_backup: Option<String>,
+}
+
+/// Apply the selected items of `bundle`; no selectors applies everything.
+///
+/// A selector names an item (`suppressions`, `playbooks/ir.toml`), a group of
+/// items (`playbooks`) or one section of the config file (`config.routing`).
+/// Items other than the config file go where the config in effect after the
+/// import places them.
+pub fn apply(bundle: &ConfigBundle, config_path: &Path, selectors: &[String]) -> SIEMResult<ApplyReport> {
+    let selected = |name: &str| selectors.is_empty() || selectors.iter().any(|s| selected_by(s, name));
+    let sections: Vec<&str> = selectors.iter().filter_map(|s| s.strip_prefix("config.")).collect();
+    if let Some(unknown) = selectors
+        .iter()
+        .find(|s| !s.starts_with("config.") && !bundle.items.keys().any(|name| selected_by(s, name)))
+    {
+        return Err(SIEMError::Validation(format!("Config bundle has no item {}", unknown)));
+    }
+
+    let mut report = ApplyReport::default();
+    if let Some(bundled) = bundle.items.get(CONFIG_ITEM) {
+        let current = fs::read_to_string(config_path).ok();
+        let updated = if selected(CONFIG_ITEM) {
+            Some(bundled.content.clone())
+        } else if !sections.is_empty() {
+            report.config_sections = sections.iter().map(|s| s.to_string()).collect();
+            Some(replace_sections(current.as_deref().unwrap_or_default(), &bundled.content, &sections)?)
+        } else {
+            None
+        };
+        if let Some(updated) = updated.filter(|updated| current.as_deref() != Some(updated.as_str())) {
+            // Refuse to write a file the engine could not start from
+            SiemConfig::from_toml_str(&updated)?;
+            if let Some(current) = &current {
+                let backup = config_path.with_extension("toml.bak");
+                fs::write(&backup, current)?;
+                report.config_backup = Some(backup.display().to_string());
+            }
+            write_atomic(config_path, &updated)?;
+            report.applied.push(CONFIG_ITEM.to_string());
+        }
+    } else if !sections.is_empty() {
+        return Err(SIEMError::Validation("Config bundle has no config file".to_string()));
+    }
+
+    let config = SiemConfig::load(config_path)?;
+    for (name, item) in bundle.items.iter().filter(|(name, _)| name.as_str() != CONFIG_ITEM && selected(name)) {
+        let path = item_path(&config, config_path, name)?;
+        if fs::read_to_string(&path).is_ok_and(|current| sha256(&current) == item.sha256) {
+            continue;
+        }
+        write_atomic(&path, &item.content)?;
+        report.applied.push(name.clone());
+    }
+    info!("📦 Applied {} config bundle item(s) from {}", report.applied.len(), bundle.created_by);
+    Ok(report)
+}
+
+fn selected_by(selector: &str, name: &str) -> bool {
+    selector == name || name.strip_prefix(selector).is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// `current` with `sections` taken from `bundled`; other sections and comments stay
+fn replace_sections(current: &str, bundled: &str, sections: &[&str]) -> SIEMResult<String> {
+    let bundled: toml::Table = toml::from_str(bundled).map_err(|e| SIEMError::Config(format!("Invalid TOML: {}", e)))?;
+    let mut updated = content_audit::drop_sections(current, sections);
+    updated.push_str("\n\n# Sections imported from a config bundle\n");
+    for section in sections {
+        let Some(value) = bundled.get(*section) else { continue };
+        // An empty array is the default; written bare it would land inside the previous table
+        if value.as_array().is_some_and(|items| items.is_empty()) {
+            continue;
+        }
+        let mut table = toml::Table::new();
+        table.insert(section.to_string(), value.clone());
+        updated.push('\n');
+        updated.push_str(&toml::to_string(&table).map_err(|e| SIEMError::Config(format!("Cannot write [{}]: {}", section, e)))?);
+    }
+    Ok(updated)
+}
+
+fn write_atomic(path: &Path, content: &str) -> SIEMResult<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn sha256(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+fn encode(bytes: &[u8]) -> String {
+    let mut buf = vec![0u8; Base64::encoded_len(bytes)];
+    Base64::encode(bytes, &mut buf).map(str::to_string).unwrap_or_default()
+}
+
+fn decode_key(value: &str) -> SIEMResult<[u8; 32]> {
+    let mut key = [0u8; 32];
+    let len = Base64::decode(value.trim(), &mut key)
+        .map_err(|_| SIEMError::Config("Malformed base64 Ed25519 key".to_string()))?
+        .len();
+    if len != key.len() {
+        return Err(SIEMError::Config("Ed25519 keys are 32 bytes".to_string()));
+    }
+    Ok(key)
+}
+
+/// New signing seed and its public key, both base64
+pub fn generate_signing_key() -> (String, String) {
+    let mut seed = [0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut seed);
+    let key = SigningKey::from_bytes(&seed);
+    (encode(&seed), encode(key.verifying_key().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAGING: &str = r#"
+[nats]
+url = "nats://staging:4222"
+
+[dedup]
+# differs per environment
+enabled = true
+
+[[signatures]]
+id = "sqli"
+name = "SQL Injection"
+pattern = "(?i)union\\s+select"
+category = "SQLInjection"
+severity = "High"
+description = "Union based SQL injection"
+enabled = true
+confidence = 0.9
+"#;
+
+    /// An environment rooted at `dir` with its own config, suppressions and playbooks
+    fn environment(dir: &Path, config: &str) -> (PathBuf, SiemConfig) {
+        fs::create_dir_all(dir.join("playbooks")).unwrap();
+        let paths = format!(
+            "[suppression]\nstore_path = \"{0}/suppressions.json\"\n\n[detection_profiles]\nstore_path = \"{0}/profiles.json\"\n\n\
+             [canary_assets]\nstore_path = \"{0}/canaries.json\"\n\n[domain_learning]\nstore_path = \"{0}/domains.json\"\n\n\
+             [content_packs]\ninstall_dir = \"{0}/packs\"\n\n[playbooks]\ndir = \"{0}/playbooks\"\n",
+            dir.display()
+        );
+        let path = dir.join("ultra_siem.toml");
+        fs::write(&path, format!("{}\n{}", config, paths)).unwrap();
+        (path.clone(), SiemConfig::load(&path).unwrap())
+    }
+
+    fn keys() -> (SigningKey, HashMap<String, String>) {
+        let key = SigningKey::from_bytes(&[9u8; 32]);
+        let trusted = HashMap::from([("local".to_string(), encode(key.verifying_key().as_bytes()))]);
+        (key, trusted)
+    }
+
+    #[test]
+    fn test_signed_bundle_round_trips_and_rejects_tampering() {
+        let dir = std::env::temp_dir().join(format!("config-bundle-{}", uuid::Uuid::new_v4()));
+        let (path, config) = environment(&dir, STAGING);
+        fs::write(dir.join("suppressions.json"), "[]").unwrap();
+        fs::write(dir.join("playbooks/ir.toml"), "name = \"ir\"\n").unwrap();
+        let (key, trusted) = keys();
+
+        let signed = export(&config, &path, &key, "alice").unwrap();
+        let bundle = signed.verify(&trusted).unwrap();
+        assert_eq!(bundle.items.keys().collect::<Vec<_>>(), ["config", "playbooks/ir.toml", "suppressions"]);
+        assert_eq!(bundle.created_by, "alice");
+
+        let mut tampered = signed.clone();
+        tampered.bundle = tampered.bundle.replace("staging", "attacker");
+        assert!(matches!(tampered.verify(&trusted), Err(SIEMError::Auth(_))));
+        assert!(matches!(signed.verify(&HashMap::new()), Err(SIEMError::Auth(_))));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_preview_lists_item_and_section_changes() {
+        let dir = std::env::temp_dir().join(format!("config-bundle-{}", uuid::Uuid::new_v4()));
+        let (staging_path, staging) = environment(&dir.join("staging"), STAGING);
+        fs::write(dir.join("staging/playbooks/ir.toml"), "name = \"ir\"\n").unwrap();
+        let bundle = collect(&staging, &staging_path, "alice", Utc::now()).unwrap();
+
+        let prod = STAGING.replace("staging", "prod").replace("enabled = true\n\n[[", "enabled = false\n\n[[").replace("[[signatures]]", "[[x]]");
+        let (prod_path, prod) = environment(&dir.join("prod"), &prod);
+        fs::write(dir.join("prod/playbooks/old.toml"), "name = \"old\"\n").unwrap();
+        let diffs = preview(&bundle, &prod, &prod_path).unwrap();
+        let status: Vec<(&str, ItemStatus)> = diffs.iter().map(|d| (d.item.as_str(), d.status)).collect();
+        assert_eq!(
+            status,
+            [("config", ItemStatus::Modified), ("playbooks/ir.toml", ItemStatus::Added), ("playbooks/old.toml", ItemStatus::LocalOnly)]
+        );
+        let changes = &diffs[0].changes;
+        assert!(changes.contains(&"~ [dedup]".to_string()) && changes.contains(&"- [x]".to_string()), "{:?}", changes);
+        assert!(changes.contains(&"+ signatures/sqli".to_string()), "{:?}", changes);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_selective_apply_keeps_other_sections() {
+        let dir = std::env::temp_dir().join(format!("config-bundle-{}", uuid::Uuid::new_v4()));
+        let (staging_path, staging) = environment(&dir.join("staging"), STAGING);
+        fs::write(dir.join("staging/playbooks/ir.toml"), "name = \"ir\"\n").unwrap();
+        fs::write(dir.join("staging/suppressions.json"), "[]").unwrap();
+        let bundle = collect(&staging, &staging_path, "alice", Utc::now()).unwrap();
+
+        let (prod_path, _) = environment(&dir.join("prod"), &STAGING.replace("staging", "prod").replace("enabled = true\n\n[[", "enabled = false\n\n[["));
+        let report = apply(&bundle, &prod_path, &["config.dedup".to_string(), "playbooks".to_string()]).unwrap();
+        assert_eq!(report.applied, ["config", "playbooks/ir.toml"]);
+        assert_eq!(report.config_sections, ["dedup"]);
+
+        let text = fs::read_to_string(&prod_path).unwrap();
+        assert!(text.contains("nats://prod:4222") && text.contains("[dedup]\nenabled = true"));
+        assert!(dir.join("prod/playbooks/ir.toml").exists() && !dir.join("prod/suppressions.json").exists());
+        assert!(fs::read_to_string(prod_path.with_extension("toml.bak")).unwrap().contains("enabled = false"));
+        assert!(apply(&bundle, &prod_path, &["canary_assets".to_string()]).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
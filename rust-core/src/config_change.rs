@@ -0,0 +1,464 @@
+//! # Configuration Change Rate Detection
+//!
+//! Group Policy edits, sudoers changes and firewall rule changes are routine
+//! during planned work and rare otherwise. Each recognised change is counted
+//! per host and kind over a rolling window; a detection is raised when
+//!
+//! - the count exceeds both `min_burst` and `burst_factor` times the learned
+//!   per-window rate for that host and kind, or
+//! - a change lands outside every configured change window.
+//!
+//! Changes inside a change window only teach the baseline and raise nothing
+//! unless `alert_bursts_in_windows` is set, so planned maintenance stays quiet.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use chrono::{DateTime, Datelike, NaiveDateTime, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::attack;
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::threat_detection::{ThreatCategory, ThreatSeverity};
+use crate::timezone::Zone;
+
+/// Windows Filtering Platform / Defender Firewall rule and setting changes
+const FIREWALL_EVENT_IDS: [&str; 8] = ["4946", "4947", "4948", "4950", "2004", "2005", "2006", "2033"];
+/// Directory service object created, modified or deleted
+const DIRECTORY_EVENT_IDS: [&str; 3] = ["5136", "5137", "5141"];
+const FIREWALL_TOOLS: [&str; 6] = ["iptables", "ip6tables", "nft", "ufw", "firewall-cmd", "netsh"];
+/// Arguments of the firewall tools that change rules rather than list them
+const FIREWALL_MUTATIONS: [&str; 14] = [
+    "-A", "-I", "-D", "-R", "-F", "-P", "-X", "add", "insert", "delete", "flush", "allow", "deny", "--add-rich-rule",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigChangeKind {
+    Gpo,
+    Sudoers,
+    Firewall,
+}
+
+impl ConfigChangeKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ConfigChangeKind::Gpo => "gpo",
+            ConfigChangeKind::Sudoers => "sudoers",
+            ConfigChangeKind::Firewall => "firewall",
+        }
+    }
+
+    fn technique(&self) -> &'static str {
+        match self {
+            ConfigChangeKind::Gpo => "T1484.001",
+            ConfigChangeKind::Sudoers => "T1548.003",
+            ConfigChangeKind::Firewall => "T1562.004",
+        }
+    }
+
+    fn category(&self) -> ThreatCategory {
+        match self {
+            ConfigChangeKind::Gpo | ConfigChangeKind::Sudoers => ThreatCategory::PrivilegeEscalation,
+            ConfigChangeKind::Firewall => ThreatCategory::Evasion,
+        }
+    }
+}
+
+/// Planned time during which configuration changes are expected; empty filters match everything
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeWindow {
+    pub name: String,
+    /// Days the window opens on, e.g. `["Sat", "Sun"]`
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Local `HH:MM`; wraps past midnight when `end` < `start`
+    pub start: String,
+    pub end: String,
+    /// One-off windows: only active between these instants
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    #[serde(default)]
+    pub kinds: Vec<ConfigChangeKind>,
+}
+
+impl ChangeWindow {
+    fn times(&self) -> SIEMResult<(NaiveTime, NaiveTime)> {
+        let parse = |value: &str| {
+            NaiveTime::parse_from_str(value, "%H:%M").map_err(|_| {
+                SIEMError::Config(format!("Change window '{}': invalid time of day '{}', expected HH:MM", self.name, value))
+            })
+        };
+        Ok((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    /// Whether `change`, made at `at` and at `local` in the windows' time zone, falls in this window
+    fn covers(&self, change: &ConfigChange, at: DateTime<Utc>, local: NaiveDateTime) -> bool {
+        let Ok((start, end)) = self.times() else {
+            return false;
+        };
+        let time = local.time();
+        // After midnight in a wrapping window, the window opened the day before
+        let opened_on = match start <= end {
+            true if time >= start && time < end => local.weekday(),
+            false if time >= start => local.weekday(),
+            false if time < end => local.weekday().pred(),
+            _ => return false,
+        };
+        (self.days.is_empty() || self.days.contains(&opened_on))
+            && self.from.is_none_or(|from| at >= from)
+            && self.until.is_none_or(|until| at < until)
+            && (self.hosts.is_empty() || self.hosts.iter().any(|h| h.eq_ignore_ascii_case(&change.host)))
+            && (self.kinds.is_empty() || self.kinds.contains(&change.kind))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigChangeSettings {
+    pub enabled: bool,
+    pub window_seconds: u64,
+    /// Changes per host, kind and window below which no burst is raised
+    pub min_burst: u32,
+    /// A burst is this many times the learned per-window rate
+    pub burst_factor: f64,
+    /// Weight of each finished window in the learned rate
+    pub baseline_alpha: f64,
+    /// Zone the change windows are written in, e.g. `Europe/Berlin`; empty uses `utc_offset_minutes`
+    pub time_zone: String,
+    /// Offset of the local time zone the change windows are written in
+    pub utc_offset_minutes: i32,
+    /// Raise a detection for changes outside every change window; needs at least one window
+    pub alert_outside_windows: bool,
+    pub alert_bursts_in_windows: bool,
+    pub change_windows: Vec<ChangeWindow>,
+    pub max_tracked: usize,
+}
+
+impl Default for ConfigChangeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_seconds: 900,
+            min_burst: 5,
+            burst_factor: 3.0,
+            baseline_alpha: 0.2,
+            time_zone: String::new(),
+            utc_offset_minutes: 0,
+            alert_outside_windows: true,
+            alert_bursts_in_windows: false,
+            change_windows: Vec::new(),
+            max_tracked: 10_000,
+        }
+    }
+}
+
+impl ConfigChangeSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        for window in &self.change_windows {
+            window.times()?;
+        }
+        self.zone().map(|_| ())
+    }
+
+    fn zone(&self) -> SIEMResult<Zone> {
+        match self.time_zone.is_empty() {
+            true => Ok(Zone::fixed(self.utc_offset_minutes * 60)),
+            false => Zone::load(&self.time_zone),
+        }
+    }
+}
+
+/// One configuration change recognised in an event
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    pub kind: ConfigChangeKind,
+    pub host: String,
+    pub user: String,
+    /// What changed: the GPO, file or firewall command
+    pub target: String,
+}
+
+impl ConfigChange {
+    /// Windows directory and firewall audit events, or Linux file and command events
+    pub fn from_event(event: &serde_json::Value) -> Option<Self> {
+        let field = |name: &str| match event.get(name) {
+            Some(serde_json::Value::String(text)) => Some(text.clone()).filter(|text| !text.is_empty()),
+            Some(serde_json::Value::Number(number)) => Some(number.to_string()),
+            _ => None,
+        };
+        let event_id = field("event_id").unwrap_or_default();
+        let (kind, target) = if DIRECTORY_EVENT_IDS.contains(&event_id.as_str()) {
+            let dn = field("event_data.ObjectDN").unwrap_or_default();
+            let is_gpo = field("event_data.ObjectClass").is_some_and(|class| class.eq_ignore_ascii_case("groupPolicyContainer"))
+                || dn.to_ascii_lowercase().contains("cn=policies,cn=system");
+            if !is_gpo {
+                return None;
+            }
+            (ConfigChangeKind::Gpo, dn)
+        } else if FIREWALL_EVENT_IDS.contains(&event_id.as_str()) {
+            let rule = field("event_data.RuleName").or_else(|| field("event_data.RuleId")).unwrap_or_else(|| format!("event {}", event_id));
+            (ConfigChangeKind::Firewall, rule)
+        } else if let Some(path) = field("file_path").filter(|path| is_sudoers(path)) {
+            (ConfigChangeKind::Sudoers, path)
+        } else if let Some(command) = field("command_line").filter(|command| is_firewall_change(command)) {
+            (ConfigChangeKind::Firewall, command)
+        } else if let Some(command) = field("command_line").filter(|command| command.split_whitespace().any(is_sudoers)) {
+            (ConfigChangeKind::Sudoers, command)
+        } else {
+            return None;
+        };
+        Some(Self {
+            kind,
+            host: field("hostname").or_else(|| field("host")).unwrap_or_else(|| "unknown".to_string()),
+            user: field("user_id").or_else(|| field("event_data.SubjectUserName")).unwrap_or_default(),
+            target,
+        })
+    }
+}
+
+fn is_sudoers(path: &str) -> bool {
+    path == "/etc/sudoers" || path.starts_with("/etc/sudoers.d/")
+}
+
+fn is_firewall_change(command: &str) -> bool {
+    let mut words = command.split_whitespace();
+    let tool = words.next().map(|tool| tool.rsplit(['/', '\\']).next().unwrap_or(tool).trim_end_matches(".exe"));
+    tool.is_some_and(|tool| FIREWALL_TOOLS.contains(&tool)) && words.any(|word| FIREWALL_MUTATIONS.contains(&word))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigChangeReason {
+    /// More changes in the window than the learned rate allows
+    Burst { baseline: f64 },
+    /// No change window covered the change
+    OutsideWindow,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChangeAlert {
+    pub change: ConfigChange,
+    pub reason: ConfigChangeReason,
+    /// Changes of this kind on the host in the current window
+    pub changes: u32,
+    pub window_seconds: u64,
+}
+
+impl ConfigChangeAlert {
+    pub fn to_threat(&self, timestamp: u64) -> AdvancedThreatResult {
+        let change = &self.change;
+        let mut details = attack::technique_details(&[change.kind.technique().to_string()]);
+        details.insert("hostname".to_string(), change.host.clone());
+        details.insert("change_kind".to_string(), change.kind.as_str().to_string());
+        details.insert("change_target".to_string(), change.target.clone());
+        details.insert("changes_in_window".to_string(), self.changes.to_string());
+        details.insert("window_seconds".to_string(), self.window_seconds.to_string());
+        let (severity, confidence, description) = match &self.reason {
+            ConfigChangeReason::Burst { baseline } => {
+                details.insert("baseline_per_window".to_string(), format!("{:.2}", baseline));
+                (
+                    ThreatSeverity::High,
+                    0.75,
+                    format!(
+                        "Burst of {} {} changes on {} within {} seconds (usually {:.1})",
+                        self.changes, change.kind.as_str(), change.host, self.window_seconds, baseline
+                    ),
+                )
+            }
+            ConfigChangeReason::OutsideWindow => (
+                ThreatSeverity::Medium,
+                0.6,
+                format!("{} change on {} outside any change window: {}", change.kind.as_str(), change.host, change.target),
+            ),
+        };
+        AdvancedThreatResult {
+            timestamp,
+            severity,
+            category: change.kind.category(),
+            confidence,
+            detection_method: "config_change".to_string(),
+            user_id: change.user.clone(),
+            description,
+            details,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ChangeRate {
+    window_start: u64,
+    count: u32,
+    /// Learned changes per window that saw any; `None` until one has finished
+    baseline: Option<f64>,
+    burst_alerted: bool,
+    outside_alerted: bool,
+}
+
+/// Per host and kind change counts against a learned rate and the change windows
+#[derive(Debug)]
+pub struct ConfigChangeDetector {
+    settings: ConfigChangeSettings,
+    zone: Zone,
+    rates: Mutex<HashMap<(String, ConfigChangeKind), ChangeRate>>,
+    in_window: Mutex<u64>,
+}
+
+impl ConfigChangeDetector {
+    pub fn new(settings: ConfigChangeSettings) -> Self {
+        let zone = settings.zone().unwrap_or_else(|e| {
+            log::warn!("⚠️ Change windows fall back to UTC: {}", e);
+            Zone::utc()
+        });
+        Self {
+            settings,
+            zone,
+            rates: Mutex::new(HashMap::new()),
+            in_window: Mutex::new(0),
+        }
+    }
+
+    /// Count a change in `event` at `now`; each reason alerts at most once per host, kind and window
+    pub fn observe_at(&self, event: &serde_json::Value, now: u64) -> Option<ConfigChangeAlert> {
+        let change = ConfigChange::from_event(event)?;
+        let at = DateTime::from_timestamp(now as i64, 0).unwrap_or_default();
+        let local = self.zone.to_local(at);
+        let planned = self.settings.change_windows.iter().any(|window| window.covers(&change, at, local));
+        if planned {
+            *self.in_window.lock().unwrap() += 1;
+        }
+
+        let mut rates = self.rates.lock().unwrap();
+        let key = (change.host.clone(), change.kind);
+        if !rates.contains_key(&key) && rates.len() >= self.settings.max_tracked {
+            let stale = self.settings.window_seconds * 2;
+            rates.retain(|_, rate| now.saturating_sub(rate.window_start) <= stale);
+            if rates.len() >= self.settings.max_tracked {
+                return None;
+            }
+        }
+        let rate = rates.entry(key).or_insert_with(|| ChangeRate {
+            window_start: now,
+            count: 0,
+            baseline: None,
+            burst_alerted: false,
+            outside_alerted: false,
+        });
+        if now.saturating_sub(rate.window_start) >= self.settings.window_seconds {
+            // Idle windows are not folded in: the rate is how large a batch of changes usually is
+            let count = rate.count as f64;
+            let baseline = rate.baseline.map_or(count, |b| b + self.settings.baseline_alpha * (count - b));
+            *rate = ChangeRate { window_start: now, count: 0, baseline: Some(baseline), burst_alerted: false, outside_alerted: false };
+        }
+        rate.count += 1;
+
+        let burst = rate.count >= self.settings.min_burst
+            && rate.count as f64 > rate.baseline.unwrap_or(0.0) * self.settings.burst_factor
+            && (!planned || self.settings.alert_bursts_in_windows);
+        let reason = if burst && !rate.burst_alerted {
+            rate.burst_alerted = true;
+            ConfigChangeReason::Burst { baseline: rate.baseline.unwrap_or(0.0) }
+        } else if !planned && self.settings.alert_outside_windows && !self.settings.change_windows.is_empty() && !rate.outside_alerted {
+            rate.outside_alerted = true;
+            ConfigChangeReason::OutsideWindow
+        } else {
+            return None;
+        };
+        Some(ConfigChangeAlert {
+            change,
+            reason,
+            changes: rate.count,
+            window_seconds: self.settings.window_seconds,
+        })
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        HashMap::from([
+            ("config_change_tracked".to_string(), self.rates.lock().unwrap().len() as f64),
+            ("config_change_in_window".to_string(), *self.in_window.lock().unwrap() as f64),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn firewall_rule(host: &str) -> serde_json::Value {
+        serde_json::json!({
+            "event_id": 4947,
+            "hostname": host,
+            "event_data.RuleName": "Allow inbound 3389",
+            "event_data.SubjectUserName": "svc-deploy",
+        })
+    }
+
+    #[test]
+    fn test_changes_are_recognised() {
+        let gpo = serde_json::json!({
+            "event_id": "5136",
+            "host": "dc-01",
+            "event_data.ObjectClass": "groupPolicyContainer",
+            "event_data.ObjectDN": "CN={31B2F340-016D-11D2-945F-00C04FB984F9},CN=Policies,CN=System,DC=corp,DC=example",
+        });
+        assert_eq!(ConfigChange::from_event(&gpo).unwrap().kind, ConfigChangeKind::Gpo);
+        let user_edit = serde_json::json!({ "event_id": "5136", "event_data.ObjectClass": "user", "event_data.ObjectDN": "CN=bob,DC=corp" });
+        assert!(ConfigChange::from_event(&user_edit).is_none());
+
+        let sudoers = serde_json::json!({ "file_path": "/etc/sudoers.d/90-deploy", "hostname": "web-01", "user_id": "root" });
+        assert_eq!(ConfigChange::from_event(&sudoers).unwrap().kind, ConfigChangeKind::Sudoers);
+        let iptables = serde_json::json!({ "command_line": "/usr/sbin/iptables -I INPUT -p tcp --dport 4444 -j ACCEPT" });
+        assert_eq!(ConfigChange::from_event(&iptables).unwrap().kind, ConfigChangeKind::Firewall);
+        // Listing rules changes nothing
+        assert!(ConfigChange::from_event(&serde_json::json!({ "command_line": "iptables -L -n" })).is_none());
+
+        let threat = ConfigChangeAlert {
+            change: ConfigChange::from_event(&sudoers).unwrap(),
+            reason: ConfigChangeReason::OutsideWindow,
+            changes: 1,
+            window_seconds: 900,
+        }
+        .to_threat(1_700_000_000);
+        assert_eq!(threat.category, ThreatCategory::PrivilegeEscalation);
+        assert_eq!(attack::techniques_of(&threat), vec!["T1548.003"]);
+    }
+
+    #[test]
+    fn test_bursts_and_unplanned_changes_alert_outside_windows_only() {
+        let window: ChangeWindow = serde_json::from_value(serde_json::json!({
+            "name": "weekend maintenance", "days": ["Sat"], "start": "22:00", "end": "04:00", "kinds": ["firewall"],
+        }))
+        .unwrap();
+        let detector = ConfigChangeDetector::new(ConfigChangeSettings {
+            min_burst: 4,
+            change_windows: vec![window],
+            ..Default::default()
+        });
+        // Saturday 2023-11-18 23:00 UTC, and 01:00 the night after is still Saturday's window
+        let saturday = 1_700_348_400;
+        for i in 0..10 {
+            assert!(detector.observe_at(&firewall_rule("fw-01"), saturday + i).is_none());
+        }
+        assert!(detector.observe_at(&firewall_rule("fw-02"), saturday + 2 * 3600).is_none());
+        assert_eq!(detector.get_metrics()["config_change_in_window"], 11.0);
+
+        // Midweek: the first change is unplanned, the fourth a burst, the rest are quiet
+        let midweek = saturday + 3 * 86_400 + 15 * 3600;
+        let alerts: Vec<_> = (0..8).filter_map(|i| detector.observe_at(&firewall_rule("fw-03"), midweek + i)).collect();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].reason, ConfigChangeReason::OutsideWindow);
+        assert_eq!(alerts[1].reason, ConfigChangeReason::Burst { baseline: 0.0 });
+        assert_eq!(alerts[1].changes, 4);
+        assert_eq!(alerts[1].to_threat(midweek).severity, ThreatSeverity::High);
+
+        // fw-01 learned ten changes per window, so a repeat of that rate is no burst
+        let later = saturday + 900;
+        let repeat: Vec<_> = (0..10).filter_map(|i| detector.observe_at(&firewall_rule("fw-01"), later + 3 * 86_400 + i)).collect();
+        assert_eq!(repeat.len(), 1);
+        assert_eq!(repeat[0].reason, ConfigChangeReason::OutsideWindow);
+    }
+}
@@ -0,0 +1,534 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use log::info;
+#[cfg(feature = "response")]
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::SiemConfig;
+use crate::content_pack::{ContentPackManager, InstalledPack};
+use crate::error_handling::{SIEMError, SIEMResult};
+#[cfg(feature = "response")]
+use crate::incident_response::ResponseRule;
+use crate::parsing::PipelineConfig;
+use crate::threat_detection::SignaturePattern;
+
+const AUDIT_LOG_FILE: &str = "audit.jsonl";
+const VERSIONS_DIR: &str = "versions";
+
+/// Config sections holding detection content, rewritten on rollback
+pub(crate) const CONTENT_SECTIONS: [&str; 4] = ["signatures", "allow_list", "response_rules", "pipelines"];
+
+/// Versioning of detection content changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentAuditSettings {
+    pub enabled: bool,
+    /// Content versions and the append-only `audit.jsonl`
+    pub store_dir: String,
+    /// Receive every audit event as JSON
+    pub webhook_urls: Vec<String>,
+}
+
+impl Default for ContentAuditSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            store_dir: "data/content-audit".to_string(),
+            webhook_urls: Vec::new(),
+        }
+    }
+}
+
+/// Sources and users whose events never raise detections
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AllowListSettings {
+    pub entries: Vec<String>,
+}
+
+/// Everything that decides what is detected and how it is answered
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionContent {
+    pub signatures: Vec<SignaturePattern>,
+    pub allow_list: Vec<String>,
+    #[cfg(feature = "response")]
+    pub response_rules: Vec<ResponseRule>,
+    pub pipelines: Vec<PipelineConfig>,
+    pub content_packs: Vec<InstalledPack>,
+}
+
+impl DetectionContent {
+    pub fn from_config(config: &SiemConfig, packs: Vec<InstalledPack>) -> Self {
+        Self {
+            signatures: config.signatures.clone(),
+            allow_list: config.allow_list.entries.clone(),
+            #[cfg(feature = "response")]
+            response_rules: config.response_rules.clone(),
+            pipelines: config.pipelines.clone(),
+            content_packs: packs,
+        }
+    }
+
+    /// Content of `config` plus the installed content packs
+    pub fn current(config: &SiemConfig) -> SIEMResult<Self> {
+        let packs = ContentPackManager::open(config.content_packs.clone())?.list();
+        Ok(Self::from_config(config, packs))
+    }
+
+    /// Items of each section keyed by id, in canonical JSON
+    fn sections(&self) -> Vec<(&'static str, Vec<(String, serde_json::Value)>)> {
+        fn keyed<T: Serialize>(items: &[T], key: impl Fn(&T) -> String) -> Vec<(String, serde_json::Value)> {
+            items.iter().map(|item| (key(item), canonical(serde_json::to_value(item).unwrap_or_default()))).collect()
+        }
+        vec![
+            ("signatures", keyed(&self.signatures, |s| s.id.clone())),
+            ("allow_list", keyed(&self.allow_list, |entry| entry.clone())),
+            #[cfg(feature = "response")]
+            ("response_rules", keyed(&self.response_rules, |r| r.id.clone())),
+            ("pipelines", keyed(&self.pipelines, |p| p.name.clone())),
+            // Install time and source change on reinstall without changing the content
+            ("content_packs", keyed(&self.content_packs, |p| p.name.clone())
+                .into_iter()
+                .map(|(name, mut pack)| {
+                    if let Some(fields) = pack.as_object_mut() {
+                        fields.retain(|field, _| ["version", "sha256", "enabled", "key_id"].contains(&field.as_str()));
+                    }
+                    (name, pack)
+                })
+                .collect()),
+        ]
+    }
+
+    pub fn sha256(&self) -> String {
+        let sections: Vec<(&str, Vec<(String, serde_json::Value)>)> = self.sections();
+        format!("{:x}", Sha256::digest(serde_json::to_vec(&sections).unwrap_or_default()))
+    }
+
+    /// Per-item changes from `previous` to `self`
+    pub fn diff(&self, previous: &DetectionContent) -> Vec<ContentChange> {
+        let mut changes = Vec::new();
+        for ((section, after), (_, before)) in self.sections().into_iter().zip(previous.sections()) {
+            for (key, old) in &before {
+                match after.iter().find(|(k, _)| k == key) {
+                    None => changes.push(ContentChange::new(section, key, ChangeKind::Removed, Some(old.clone()), None)),
+                    Some((_, new)) if new != old => {
+                        changes.push(ContentChange::new(section, key, ChangeKind::Modified, Some(old.clone()), Some(new.clone())))
+                    }
+                    Some(_) => {}
+                }
+            }
+            for (key, new) in &after {
+                if !before.iter().any(|(k, _)| k == key) {
+                    changes.push(ContentChange::new(section, key, ChangeKind::Added, None, Some(new.clone())));
+                }
+            }
+        }
+        changes
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// One signature, allow-list entry, response rule, pipeline or pack that changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentChange {
+    pub section: String,
+    pub key: String,
+    pub kind: ChangeKind,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+impl ContentChange {
+    fn new(section: &str, key: &str, kind: ChangeKind, before: Option<serde_json::Value>, after: Option<serde_json::Value>) -> Self {
+        Self { section: section.to_string(), key: key.to_string(), kind, before, after }
+    }
+
+    /// `+ signatures/xss_2`, `~ pipelines/nginx-access (processors)`
+    pub fn summary(&self) -> String {
+        let sign = match self.kind {
+            ChangeKind::Added => "+",
+            ChangeKind::Removed => "-",
+            ChangeKind::Modified => "~",
+        };
+        let fields = match (&self.before, &self.after) {
+            (Some(serde_json::Value::Object(before)), Some(serde_json::Value::Object(after))) => {
+                let mut fields: Vec<&str> = after.keys().chain(before.keys()).map(String::as_str).filter(|f| before.get(*f) != after.get(*f)).collect();
+                fields.sort_unstable();
+                fields.dedup();
+                format!(" ({})", fields.join(", "))
+            }
+            _ => String::new(),
+        };
+        format!("{} {}/{}{}", sign, self.section, self.key, fields)
+    }
+}
+
+/// A recorded state of the detection content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentVersion {
+    pub version: u64,
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+    pub message: String,
+    pub sha256: String,
+    pub content: DetectionContent,
+    /// Changes from the previous version; everything is `added` in version 1
+    pub changes: Vec<ContentChange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Commit,
+    Rollback,
+}
+
+/// Line of `audit.jsonl`, also posted to the webhooks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub timestamp: DateTime<Utc>,
+    pub actor: String,
+    pub action: AuditAction,
+    pub version: u64,
+    pub previous_version: Option<u64>,
+    pub message: String,
+    pub changes: Vec<String>,
+}
+
+/// Versions detection content under `store_dir` and audits every change
+#[derive(Debug)]
+pub struct ContentAuditLog {
+    settings: ContentAuditSettings,
+    dir: PathBuf,
+}
+
+impl ContentAuditLog {
+    pub fn open(settings: ContentAuditSettings) -> SIEMResult<Self> {
+        let dir = PathBuf::from(&settings.store_dir);
+        fs::create_dir_all(dir.join(VERSIONS_DIR))?;
+        Ok(Self { settings, dir })
+    }
+
+    /// Record `content` as a new version unless it matches the latest one
+    pub fn record(&self, content: DetectionContent, author: &str, message: &str) -> SIEMResult<Option<AuditEvent>> {
+        self.record_as(content, author, message, AuditAction::Commit, Utc::now())
+    }
+
+    /// Every recorded version, oldest first
+    pub fn history(&self) -> SIEMResult<Vec<ContentVersion>> {
+        let mut versions = Vec::new();
+        for entry in fs::read_dir(self.dir.join(VERSIONS_DIR))? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("json") {
+                versions.push(serde_json::from_slice::<ContentVersion>(&fs::read(&path)?)?);
+            }
+        }
+        versions.sort_by_key(|v| v.version);
+        Ok(versions)
+    }
+
+    pub fn load(&self, version: u64) -> SIEMResult<ContentVersion> {
+        let path = self.version_path(version);
+        if !path.exists() {
+            return Err(SIEMError::Validation(format!("Content version {} does not exist", version)));
+        }
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    pub fn latest(&self) -> SIEMResult<Option<ContentVersion>> {
+        Ok(self.history()?.pop())
+    }
+
+    /// Changes between two recorded versions
+    pub fn diff(&self, from: u64, to: u64) -> SIEMResult<Vec<ContentChange>> {
+        Ok(self.load(to)?.content.diff(&self.load(from)?.content))
+    }
+
+    /// The audit trail, oldest first
+    pub fn events(&self) -> SIEMResult<Vec<AuditEvent>> {
+        let path = self.dir.join(AUDIT_LOG_FILE);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(SIEMError::from))
+            .collect()
+    }
+
+    /// Restore `version` into the content sections of `config_path` and the pack registry, recording it as a new version.
+    /// Packs uninstalled since then cannot be restored and are returned by name; a restart applies the rollback.
+    pub fn rollback(&self, version: u64, config_path: &Path, packs: &mut ContentPackManager, author: &str) -> SIEMResult<(AuditEvent, Vec<String>)> {
+        let target = self.load(version)?;
+        let text = fs::read_to_string(config_path)?;
+        let restored = replace_content_sections(&text, &target.content)?;
+        // Refuse to write a file the engine could not start from
+        SiemConfig::from_toml_str(&restored)?;
+        fs::write(config_path.with_extension("toml.bak"), &text)?;
+        fs::write(config_path, restored)?;
+
+        let mut missing = Vec::new();
+        for pack in &target.content.content_packs {
+            let installed = packs.list().into_iter().find(|p| p.name == pack.name);
+            match installed {
+                Some(installed) if installed.sha256 != pack.sha256 => missing.push(pack.name.clone()),
+                Some(_) if pack.enabled => packs.enable(&pack.name)?,
+                Some(_) => packs.disable(&pack.name)?,
+                None => missing.push(pack.name.clone()),
+            }
+        }
+        for pack in packs.list() {
+            if pack.enabled && !target.content.content_packs.iter().any(|p| p.name == pack.name) {
+                packs.disable(&pack.name)?;
+            }
+        }
+
+        let mut content = target.content.clone();
+        content.content_packs = packs.list();
+        let message = format!("Rollback to version {}", version);
+        let event = self
+            .record_as(content, author, &message, AuditAction::Rollback, Utc::now())?
+            .unwrap_or_else(|| self.audit_event(author, AuditAction::Rollback, &target, None, &message));
+        Ok((event, missing))
+    }
+
+    /// Post `event` to every configured webhook
+    #[cfg(feature = "response")]
+    pub async fn notify(&self, event: &AuditEvent) {
+        if self.settings.webhook_urls.is_empty() {
+            return;
+        }
+        let client = reqwest::Client::new();
+        for url in &self.settings.webhook_urls {
+            let sent = client
+                .post(url)
+                .json(&serde_json::json!({ "type": "content_audit", "event": event }))
+                .timeout(std::time::Duration::from_secs(10))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = sent {
+                warn!("⚠️ Content audit webhook {} failed: {}", url, e);
+            }
+        }
+    }
+
+    fn record_as(&self, content: DetectionContent, author: &str, message: &str, action: AuditAction, now: DateTime<Utc>) -> SIEMResult<Option<AuditEvent>> {
+        let latest = self.latest()?;
+        let sha256 = content.sha256();
+        if latest.as_ref().is_some_and(|v| v.sha256 == sha256) {
+            return Ok(None);
+        }
+        let changes = content.diff(&latest.as_ref().map(|v| v.content.clone()).unwrap_or_default());
+        let version = ContentVersion {
+            version: latest.as_ref().map_or(1, |v| v.version + 1),
+            author: author.to_string(),
+            created_at: now,
+            message: message.to_string(),
+            sha256,
+            content,
+            changes,
+        };
+        fs::write(self.version_path(version.version), serde_json::to_vec_pretty(&version)?)?;
+
+        let event = self.audit_event(author, action, &version, latest.map(|v| v.version), message);
+        let mut log = OpenOptions::new().create(true).append(true).open(self.dir.join(AUDIT_LOG_FILE))?;
+        writeln!(log, "{}", serde_json::to_string(&event)?)?;
+        info!("📝 Detection content version {} by {}: {} ({} changes)", version.version, author, message, version.changes.len());
+        Ok(Some(event))
+    }
+
+    fn audit_event(&self, actor: &str, action: AuditAction, version: &ContentVersion, previous: Option<u64>, message: &str) -> AuditEvent {
+        AuditEvent {
+            timestamp: version.created_at,
+            actor: actor.to_string(),
+            action,
+            version: version.version,
+            previous_version: previous,
+            message: message.to_string(),
+            changes: version.changes.iter().map(ContentChange::summary).collect(),
+        }
+    }
+
+    fn version_path(&self, version: u64) -> PathBuf {
+        self.dir.join(VERSIONS_DIR).join(format!("{:06}.json", version))
+    }
+}
+
+/// Who made a change: `ULTRA_SIEM_ACTOR`, else the login user
+pub fn current_actor() -> String {
+    std::env::var("ULTRA_SIEM_ACTOR")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Drop the content tables from a TOML document and append `content` in their place; other sections and comments stay
+fn replace_content_sections(text: &str, content: &DetectionContent) -> SIEMResult<String> {
+    let mut restored = drop_sections(text, &CONTENT_SECTIONS);
+    restored.push_str("\n\n# Detection content restored by rollback\n");
+
+    let to_toml = |value: serde_json::Value| toml::to_string(&without_nulls(value)).map_err(|e| SIEMError::Config(format!("Cannot write content as TOML: {}", e)));
+    restored.push_str(&to_toml(serde_json::json!({ "allow_list": { "entries": content.allow_list } }))?);
+    // Empty arrays are left out: a bare `key = []` after a table would land inside that table
+    if !content.signatures.is_empty() {
+        restored.push('\n');
+        restored.push_str(&to_toml(serde_json::json!({ "signatures": content.signatures }))?);
+    }
+    #[cfg(feature = "response")]
+    if !content.response_rules.is_empty() {
+        restored.push('\n');
+        restored.push_str(&to_toml(serde_json::json!({ "response_rules": content.response_rules }))?);
+    }
+    if !content.pipelines.is_empty() {
+        restored.push('\n');
+        restored.push_str(&to_toml(serde_json::json!({ "pipelines": content.pipelines }))?);
+    }
+    Ok(restored)
+}
+
+/// Drop the tables named in `sections`, with their sub-tables, from a TOML document; other sections and comments stay
+pub(crate) fn drop_sections(text: &str, sections: &[&str]) -> String {
+    let mut kept = Vec::new();
+    // Comments closing a dropped table may introduce the table after it
+    let mut comments = Vec::new();
+    let mut skipping = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('[') {
+            let name = trimmed.trim_start_matches('[').split(']').next().unwrap_or_default().trim();
+            let was_skipping = skipping;
+            skipping = sections.iter().any(|s| name == *s || name.starts_with(&format!("{}.", s)));
+            if was_skipping && !skipping {
+                kept.append(&mut comments);
+            }
+            comments.clear();
+        }
+        if !skipping {
+            kept.push(line);
+        } else if trimmed.is_empty() || trimmed.starts_with('#') {
+            comments.push(line);
+        } else {
+            comments.clear();
+        }
+    }
+    kept.join("\n").trim_end().to_string()
+}
+
+/// TOML has no null; unset options are left out instead
+pub(crate) fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(fields) => {
+            serde_json::Value::Object(fields.into_iter().filter(|(_, v)| !v.is_null()).map(|(k, v)| (k, without_nulls(v))).collect())
+        }
+        serde_json::Value::Array(items) => serde_json::Value::Array(items.into_iter().map(without_nulls).collect()),
+        other => other,
+    }
+}
+
+/// Objects with sorted keys, so equal content hashes equally whatever the map order
+fn canonical(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(fields) => {
+            let mut fields: Vec<(String, serde_json::Value)> = fields.into_iter().collect();
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            serde_json::Value::Object(fields.into_iter().map(|(k, v)| (k, canonical(v))).collect())
+        }
+        serde_json::Value::Array(items) => serde_json::Value::Array(items.into_iter().map(canonical).collect()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[nats]
+url = "nats://localhost:4222"
+
+[[signatures]]
+id = "sqli"
+name = "SQL Injection"
+pattern = "(?i)union\\s+select"
+category = "SQLInjection"
+severity = "High"
+description = "Union based SQL injection"
+enabled = true
+confidence = 0.9
+
+[allow_list]
+entries = ["10.0.0.1"]
+
+# Kept across rollbacks
+[dedup]
+enabled = true
+"#;
+
+    fn store(name: &str) -> (PathBuf, ContentAuditLog, ContentPackManager) {
+        let dir = std::env::temp_dir().join(format!("content-audit-{}-{}", name, uuid::Uuid::new_v4()));
+        let audit = ContentAuditLog::open(ContentAuditSettings { store_dir: dir.join("audit").display().to_string(), ..Default::default() }).unwrap();
+        let packs = ContentPackManager::open(crate::content_pack::ContentPackSettings {
+            install_dir: dir.join("packs").display().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        (dir, audit, packs)
+    }
+
+    #[test]
+    fn test_changes_are_versioned_with_author_and_diff() {
+        let (dir, audit, _) = store("diff");
+        let mut config = SiemConfig::from_toml_str(CONFIG).unwrap();
+        let first = audit.record(DetectionContent::from_config(&config, Vec::new()), "alice", "initial").unwrap().unwrap();
+        assert_eq!((first.version, first.previous_version), (1, None));
+        // Unchanged content records nothing
+        assert!(audit.record(DetectionContent::from_config(&config, Vec::new()), "alice", "restart").unwrap().is_none());
+
+        config.signatures[0].confidence = 0.5;
+        config.allow_list.entries.push("svc-backup".to_string());
+        let second = audit.record(DetectionContent::from_config(&config, Vec::new()), "bob", "tune sqli").unwrap().unwrap();
+        assert_eq!(second.actor, "bob");
+        assert_eq!(second.changes, vec!["~ signatures/sqli (confidence)".to_string(), "+ allow_list/svc-backup".to_string()]);
+        assert_eq!(audit.diff(1, 2).unwrap().len(), 2);
+        assert_eq!(audit.events().unwrap().len(), 2);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_rollback_rewrites_content_sections_only() {
+        let (dir, audit, mut packs) = store("rollback");
+        let config_path = dir.join("ultra_siem.toml");
+        fs::write(&config_path, CONFIG).unwrap();
+        let original = SiemConfig::load(&config_path).unwrap();
+        audit.record(DetectionContent::from_config(&original, Vec::new()), "alice", "initial").unwrap();
+
+        let edited = CONFIG.replace("\"10.0.0.1\"", "\"10.0.0.1\", \"0.0.0.0\"").replace("enabled = true\nconfidence", "enabled = false\nconfidence");
+        fs::write(&config_path, &edited).unwrap();
+        let edited = SiemConfig::load(&config_path).unwrap();
+        audit.record(DetectionContent::from_config(&edited, Vec::new()), "mallory", "loosen").unwrap();
+
+        let (event, missing) = audit.rollback(1, &config_path, &mut packs, "alice").unwrap();
+        assert_eq!((event.action, event.version, event.previous_version), (AuditAction::Rollback, 3, Some(2)));
+        assert!(missing.is_empty());
+        let restored = SiemConfig::load(&config_path).unwrap();
+        assert_eq!(restored.allow_list.entries, vec!["10.0.0.1".to_string()]);
+        assert_eq!(restored.signatures, original.signatures);
+        let text = fs::read_to_string(&config_path).unwrap();
+        assert!(text.contains("# Kept across rollbacks\n[dedup]"));
+        assert_eq!(audit.load(3).unwrap().sha256, audit.load(1).unwrap().sha256);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
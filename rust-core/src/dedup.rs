@@ -0,0 +1,239 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// What happens to an exact duplicate seen inside the window
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DedupMode {
+    /// Drop the duplicate before detection
+    Drop,
+    /// Keep the duplicate but tag its detections
+    Tag,
+}
+
+/// Ingestion de-duplication settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DedupSettings {
+    pub enabled: bool,
+    pub window_seconds: u64,
+    /// Upper bound on tracked hashes; the oldest are evicted first
+    pub max_entries: usize,
+    pub mode: DedupMode,
+    /// Top-level fields excluded from the content hash (e.g. receive timestamps)
+    pub ignore_fields: Vec<String>,
+}
+
+impl Default for DedupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_seconds: 60,
+            max_entries: 100_000,
+            mode: DedupMode::Drop,
+            ignore_fields: vec!["received_at".to_string(), "ingest_id".to_string()],
+        }
+    }
+}
+
+/// Outcome of checking a single event
+#[derive(Debug, Clone, PartialEq)]
+pub enum DedupVerdict {
+    Unique,
+    Duplicate { first_seen: u64, count: u64 },
+}
+
+#[derive(Debug)]
+struct SeenEntry {
+    first_seen: u64,
+    count: u64,
+    /// Canonical content behind the hash, compared before anything is called a duplicate
+    content: String,
+}
+
+/// SHA-256 digest of an event's canonical content
+pub type ContentHash = [u8; 32];
+
+#[derive(Debug, Default)]
+struct DedupState {
+    seen: HashMap<ContentHash, SeenEntry>,
+    order: VecDeque<(ContentHash, u64)>,
+}
+
+/// Time-windowed content-hash de-duplicator with LRU-style eviction
+#[derive(Debug)]
+pub struct EventDeduplicator {
+    settings: DedupSettings,
+    state: Mutex<DedupState>,
+    events_seen: AtomicU64,
+    duplicates: AtomicU64,
+    collisions: AtomicU64,
+}
+
+impl EventDeduplicator {
+    pub fn new(settings: DedupSettings) -> Self {
+        Self {
+            settings,
+            state: Mutex::new(DedupState::default()),
+            events_seen: AtomicU64::new(0),
+            duplicates: AtomicU64::new(0),
+            collisions: AtomicU64::new(0),
+        }
+    }
+
+    pub fn mode(&self) -> DedupMode {
+        self.settings.mode
+    }
+
+    /// Check an event against the window ending now
+    pub fn check(&self, event: &serde_json::Value) -> DedupVerdict {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        self.check_at(event, now)
+    }
+
+    /// Check an event against the window ending at `now` (unix seconds)
+    pub fn check_at(&self, event: &serde_json::Value, now: u64) -> DedupVerdict {
+        let content = self.canonical_content(event);
+        let hash = content_hash(&content);
+        self.events_seen.fetch_add(1, Ordering::Relaxed);
+
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        self.evict(&mut state, now);
+
+        if let Some(entry) = state.seen.get_mut(&hash) {
+            if entry.content != content {
+                // Same digest, different event: never drop on a collision
+                self.collisions.fetch_add(1, Ordering::Relaxed);
+                return DedupVerdict::Unique;
+            }
+            entry.count += 1;
+            self.duplicates.fetch_add(1, Ordering::Relaxed);
+            return DedupVerdict::Duplicate {
+                first_seen: entry.first_seen,
+                count: entry.count,
+            };
+        }
+
+        state.seen.insert(hash, SeenEntry { first_seen: now, count: 1, content });
+        state.order.push_back((hash, now));
+        DedupVerdict::Unique
+    }
+
+    /// SHA-256 of the event with `ignore_fields` removed
+    #[cfg(test)]
+    pub fn content_hash(&self, event: &serde_json::Value) -> ContentHash {
+        content_hash(&self.canonical_content(event))
+    }
+
+    /// The event as JSON with `ignore_fields` removed and keys in sorted order
+    fn canonical_content(&self, event: &serde_json::Value) -> String {
+        match event.as_object() {
+            Some(fields) => {
+                let kept: serde_json::Map<String, serde_json::Value> = fields
+                    .iter()
+                    .filter(|(key, _)| !self.settings.ignore_fields.iter().any(|ignored| ignored == *key))
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+                serde_json::Value::Object(kept).to_string()
+            }
+            None => event.to_string(),
+        }
+    }
+
+    fn evict(&self, state: &mut DedupState, now: u64) {
+        while let Some(&(hash, first_seen)) = state.order.front() {
+            let expired = first_seen.saturating_add(self.settings.window_seconds) <= now;
+            let over_capacity = state.order.len() >= self.settings.max_entries.max(1);
+            if !expired && !over_capacity {
+                break;
+            }
+            state.order.pop_front();
+            state.seen.remove(&hash);
+        }
+    }
+
+    /// Fraction of checked events that were duplicates
+    pub fn dedup_ratio(&self) -> f64 {
+        let seen = self.events_seen.load(Ordering::Relaxed);
+        if seen == 0 {
+            return 0.0;
+        }
+        self.duplicates.load(Ordering::Relaxed) as f64 / seen as f64
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        let tracked = self.state.lock().map(|state| state.seen.len()).unwrap_or(0);
+        let mut metrics = HashMap::new();
+        metrics.insert("dedup_events_seen".to_string(), self.events_seen.load(Ordering::Relaxed) as f64);
+        metrics.insert("dedup_duplicates".to_string(), self.duplicates.load(Ordering::Relaxed) as f64);
+        metrics.insert("dedup_ratio".to_string(), self.dedup_ratio());
+        metrics.insert("dedup_tracked_hashes".to_string(), tracked as f64);
+        metrics.insert("dedup_hash_collisions".to_string(), self.collisions.load(Ordering::Relaxed) as f64);
+        metrics
+    }
+}
+
+fn content_hash(content: &str) -> ContentHash {
+    Sha256::digest(content.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_duplicates_within_window_only() {
+        let dedup = EventDeduplicator::new(DedupSettings {
+            enabled: true,
+            window_seconds: 10,
+            ..Default::default()
+        });
+        let event = json!({ "message": "failed login", "source_ip": "10.0.0.1", "received_at": 1 });
+        let resent = json!({ "message": "failed login", "source_ip": "10.0.0.1", "received_at": 2 });
+
+        assert_eq!(dedup.check_at(&event, 100), DedupVerdict::Unique);
+        assert_eq!(dedup.check_at(&resent, 105), DedupVerdict::Duplicate { first_seen: 100, count: 2 });
+        assert_eq!(dedup.check_at(&event, 111), DedupVerdict::Unique);
+        assert!((dedup.dedup_ratio() - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest() {
+        let dedup = EventDeduplicator::new(DedupSettings {
+            enabled: true,
+            max_entries: 2,
+            ..Default::default()
+        });
+        for i in 0..3 {
+            assert_eq!(dedup.check_at(&json!({ "n": i }), 100), DedupVerdict::Unique);
+        }
+
+        assert_eq!(dedup.check_at(&json!({ "n": 0 }), 100), DedupVerdict::Unique);
+        assert_eq!(dedup.get_metrics()["dedup_tracked_hashes"], 2.0);
+    }
+
+    #[test]
+    fn test_digest_match_with_different_content_is_not_a_duplicate() {
+        let dedup = EventDeduplicator::new(DedupSettings { enabled: true, ..Default::default() });
+        let original = json!({ "message": "failed login", "source_ip": "10.0.0.1" });
+        let crafted = json!({ "message": "root shell opened", "source_ip": "10.0.0.1" });
+        assert_eq!(dedup.check_at(&original, 100), DedupVerdict::Unique);
+
+        // Force the crafted event onto the original's digest
+        {
+            let mut state = dedup.state.lock().unwrap();
+            let entry = state.seen.remove(&dedup.content_hash(&original)).unwrap();
+            state.seen.insert(dedup.content_hash(&crafted), entry);
+        }
+        assert_eq!(dedup.check_at(&crafted, 101), DedupVerdict::Unique);
+        assert_eq!(dedup.get_metrics()["dedup_hash_collisions"], 1.0);
+        assert_eq!(dedup.get_metrics()["dedup_duplicates"], 0.0);
+    }
+}
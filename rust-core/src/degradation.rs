@@ -0,0 +1,557 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use async_nats::{Client, ConnectOptions, Event};
+use log::{error, info, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, Notify};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::autoscaling::QueueSample;
+use crate::bridge_contract::{self, BridgeThreatEvent};
+#[cfg(feature = "chaos")]
+use crate::chaos::FaultInjector;
+use crate::config::ClickHouseSettings;
+use crate::config_check::clickhouse_ping;
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// Graceful degradation settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DegradationSettings {
+    /// Where detections are spooled while ClickHouse is unavailable
+    pub spool_dir: String,
+    /// Spooled detections beyond this size are dropped and counted
+    pub max_spool_bytes: u64,
+    /// Enrichment providers slower than this are skipped for the event
+    pub enrichment_timeout_ms: u64,
+    pub clickhouse_probe_interval_seconds: u64,
+}
+
+impl Default for DegradationSettings {
+    fn default() -> Self {
+        Self {
+            spool_dir: "data/spool".to_string(),
+            max_spool_bytes: 1024 * 1024 * 1024,
+            enrichment_timeout_ms: 200,
+            clickhouse_probe_interval_seconds: 15,
+        }
+    }
+}
+
+/// Subsystems with a defined degraded behaviour
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    ClickHouse,
+    Gpu,
+    Enrichment,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 3] = [Subsystem::ClickHouse, Subsystem::Gpu, Subsystem::Enrichment];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Subsystem::ClickHouse => "clickhouse",
+            Subsystem::Gpu => "gpu",
+            Subsystem::Enrichment => "enrichment",
+        }
+    }
+
+    /// The degradation matrix: what the pipeline does while this subsystem is failing
+    pub fn fallback(&self) -> DegradationMode {
+        match self {
+            Subsystem::ClickHouse => DegradationMode::SpoolToDisk,
+            Subsystem::Gpu => DegradationMode::CpuFallback,
+            Subsystem::Enrichment => DegradationMode::PartialEnrichment,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DegradationMode {
+    Normal,
+    /// Detections are written to the local spool and replayed on recovery
+    SpoolToDisk,
+    /// Detection runs on the SIMD CPU path
+    CpuFallback,
+    /// Events continue with whatever enrichment finished, tagged `enrichment_partial`
+    PartialEnrichment,
+}
+
+/// Current state of one subsystem
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubsystemStatus {
+    pub subsystem: Subsystem,
+    pub healthy: bool,
+    pub mode: DegradationMode,
+    pub reason: Option<String>,
+    /// Unix seconds of the last state change
+    pub since: u64,
+}
+
+/// Body of `/healthz`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// `ok` or `degraded`
+    pub status: String,
+    pub subsystems: Vec<SubsystemStatus>,
+    pub spooled_detections: u64,
+}
+
+/// Append-only JSON lines spool for items that could not be delivered
+#[derive(Debug)]
+pub struct DiskSpool<T = BridgeThreatEvent> {
+    path: PathBuf,
+    max_bytes: u64,
+    lock: Mutex<()>,
+    dropped: AtomicU64,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl DiskSpool {
+    pub fn open(settings: &DegradationSettings) -> SIEMResult<Self> {
+        Self::open_file(Path::new(&settings.spool_dir), "detections.jsonl", settings.max_spool_bytes)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> DiskSpool<T> {
+    pub fn open_file(dir: &Path, file_name: &str, max_bytes: u64) -> SIEMResult<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            path: dir.join(file_name),
+            max_bytes,
+            lock: Mutex::new(()),
+            dropped: AtomicU64::new(0),
+            _item: PhantomData,
+        })
+    }
+
+    pub fn push(&self, item: &T) -> SIEMResult<()> {
+        let _guard = self.lock.lock().unwrap();
+        let mut line = serde_json::to_vec(item)?;
+        line.push(b'\n');
+        if self.size_bytes() + line.len() as u64 > self.max_bytes {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return Err(SIEMError::Other(format!("Spool {} is full", self.path.display())));
+        }
+        OpenOptions::new().create(true).append(true).open(&self.path)?.write_all(&line)?;
+        Ok(())
+    }
+
+    pub fn size_bytes(&self) -> u64 {
+        fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0)
+    }
+
+    pub fn len(&self) -> u64 {
+        let _guard = self.lock.lock().unwrap();
+        File::open(&self.path).map(|f| BufReader::new(f).lines().count() as u64).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size_bytes() == 0
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Take every spooled item, leaving the spool empty
+    pub fn take_all(&self) -> SIEMResult<Vec<T>> {
+        let _guard = self.lock.lock().unwrap();
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut items = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            match serde_json::from_str(&line) {
+                Ok(item) => items.push(item),
+                Err(e) => warn!("⚠️ Discarding unreadable spool entry: {}", e),
+            }
+        }
+        fs::remove_file(&self.path)?;
+        Ok(items)
+    }
+}
+
+/// Tracks failing subsystems and applies the degradation matrix
+#[derive(Debug)]
+pub struct DegradationController {
+    settings: DegradationSettings,
+    states: RwLock<HashMap<Subsystem, SubsystemStatus>>,
+    spool: DiskSpool,
+    partial_enrichments: AtomicU64,
+    replayed: AtomicU64,
+    #[cfg(feature = "chaos")]
+    chaos: Option<FaultInjector>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl DegradationController {
+    pub fn new(settings: DegradationSettings) -> SIEMResult<Self> {
+        let since = now_secs();
+        let states = Subsystem::ALL
+            .iter()
+            .map(|subsystem| {
+                let status = SubsystemStatus {
+                    subsystem: *subsystem,
+                    healthy: true,
+                    mode: DegradationMode::Normal,
+                    reason: None,
+                    since,
+                };
+                (*subsystem, status)
+            })
+            .collect();
+        Ok(Self {
+            spool: DiskSpool::open(&settings)?,
+            settings,
+            states: RwLock::new(states),
+            partial_enrichments: AtomicU64::new(0),
+            replayed: AtomicU64::new(0),
+            #[cfg(feature = "chaos")]
+            chaos: None,
+        })
+    }
+
+    /// Drop detection publishes and panic the publisher task for resilience testing
+    #[cfg(feature = "chaos")]
+    pub fn set_fault_injector(&mut self, injector: FaultInjector) {
+        self.chaos = injector.settings().enabled.then_some(injector);
+    }
+
+    pub fn report_failure(&self, subsystem: Subsystem, reason: impl Into<String>) {
+        let reason = reason.into();
+        let mut states = self.states.write().unwrap();
+        let status = states.get_mut(&subsystem).expect("all subsystems are tracked");
+        if status.healthy {
+            warn!("⚠️ {} degraded ({:?}): {}", subsystem.name(), subsystem.fallback(), reason);
+            status.healthy = false;
+            status.mode = subsystem.fallback();
+            status.since = now_secs();
+        }
+        status.reason = Some(reason);
+    }
+
+    pub fn report_recovery(&self, subsystem: Subsystem) {
+        let mut states = self.states.write().unwrap();
+        let status = states.get_mut(&subsystem).expect("all subsystems are tracked");
+        if !status.healthy {
+            info!("✅ {} recovered", subsystem.name());
+            status.healthy = true;
+            status.mode = DegradationMode::Normal;
+            status.reason = None;
+            status.since = now_secs();
+        }
+    }
+
+    pub fn mode(&self, subsystem: Subsystem) -> DegradationMode {
+        self.states.read().unwrap()[&subsystem].mode
+    }
+
+    pub fn health(&self) -> HealthReport {
+        let mut subsystems: Vec<SubsystemStatus> = self.states.read().unwrap().values().cloned().collect();
+        subsystems.sort_by_key(|status| status.subsystem.name());
+        let degraded = subsystems.iter().any(|status| !status.healthy);
+        HealthReport {
+            status: if degraded { "degraded" } else { "ok" }.to_string(),
+            subsystems,
+            spooled_detections: self.spool.len(),
+        }
+    }
+
+    /// Spooled detections and those replayed so far, sampled as the `clickhouse_spool` queue
+    pub fn spool_sample(&self) -> QueueSample {
+        QueueSample { depth: self.spool.len(), processed: self.replayed.load(Ordering::Relaxed) }
+    }
+
+    pub fn to_metrics(&self) -> HashMap<String, f64> {
+        let mut metrics = HashMap::new();
+        for status in self.states.read().unwrap().values() {
+            let degraded = if status.healthy { 0.0 } else { 1.0 };
+            metrics.insert(format!("degraded_{}", status.subsystem.name()), degraded);
+        }
+        metrics.insert("spool_bytes".to_string(), self.spool.size_bytes() as f64);
+        metrics.insert("spool_dropped".to_string(), self.spool.dropped() as f64);
+        metrics.insert("spool_replayed".to_string(), self.replayed.load(Ordering::Relaxed) as f64);
+        metrics.insert("partial_enrichments".to_string(), self.partial_enrichments.load(Ordering::Relaxed) as f64);
+        metrics
+    }
+
+    /// Publish a detection for ClickHouse, spooling it while storage is degraded or the publish fails
+    pub async fn publish_threat(&self, client: &Client, threat: &BridgeThreatEvent) -> SIEMResult<()> {
+        if self.mode(Subsystem::ClickHouse) == DegradationMode::SpoolToDisk {
+            return self.spool.push(threat);
+        }
+        #[cfg(feature = "chaos")]
+        let published = match &self.chaos {
+            Some(chaos) => match chaos.nats_fault() {
+                Ok(()) => bridge_contract::publish_threat(client, threat).await,
+                Err(e) => Err(e),
+            },
+            None => bridge_contract::publish_threat(client, threat).await,
+        };
+        #[cfg(not(feature = "chaos"))]
+        let published = bridge_contract::publish_threat(client, threat).await;
+        if let Err(e) = published {
+            self.report_failure(Subsystem::ClickHouse, format!("publish failed: {}", e));
+            return self.spool.push(threat);
+        }
+        Ok(())
+    }
+
+    /// Re-publish spooled detections; returns how many were delivered
+    pub async fn replay_spool(&self, client: &Client) -> SIEMResult<u64> {
+        let threats = self.spool.take_all()?;
+        let mut delivered = 0;
+        for (index, threat) in threats.iter().enumerate() {
+            if let Err(e) = bridge_contract::publish_threat(client, threat).await {
+                warn!("⚠️ Spool replay interrupted: {}", e);
+                for remaining in &threats[index..] {
+                    self.spool.push(remaining)?;
+                }
+                break;
+            }
+            delivered += 1;
+        }
+        self.replayed.fetch_add(delivered, Ordering::Relaxed);
+        Ok(delivered)
+    }
+
+    /// Await an enrichment provider, giving up after the configured timeout
+    pub async fn enrich_within_deadline<T, F>(&self, provider: &str, target: &mut impl EnrichmentTarget, lookup: F) -> Option<T>
+    where
+        F: Future<Output = T>,
+    {
+        let deadline = Duration::from_millis(self.settings.enrichment_timeout_ms);
+        match tokio::time::timeout(deadline, lookup).await {
+            Ok(result) => {
+                self.report_recovery(Subsystem::Enrichment);
+                Some(result)
+            }
+            Err(_) => {
+                self.partial_enrichments.fetch_add(1, Ordering::Relaxed);
+                self.report_failure(Subsystem::Enrichment, format!("{} timed out after {:?}", provider, deadline));
+                target.mark_partial(provider);
+                None
+            }
+        }
+    }
+
+    /// Publish live detections for ClickHouse through the spool-aware path
+    pub fn spawn_detection_publisher(self: Arc<Self>, detections: broadcast::Receiver<AdvancedThreatResult>, client: Client) -> tokio::task::JoinHandle<()> {
+        let detections = Arc::new(tokio::sync::Mutex::new(detections));
+        #[cfg(feature = "chaos")]
+        if let Some(chaos) = self.chaos.clone() {
+            return chaos.spawn_supervised("detection_publisher", move || self.clone().publish_detections(detections.clone(), client.clone()));
+        }
+        tokio::spawn(self.publish_detections(detections, client))
+    }
+
+    async fn publish_detections(self: Arc<Self>, detections: Arc<tokio::sync::Mutex<broadcast::Receiver<AdvancedThreatResult>>>, client: Client) {
+        let mut detections = detections.lock().await;
+        loop {
+            #[cfg(feature = "chaos")]
+            if let Some(chaos) = &self.chaos {
+                chaos.maybe_panic("detection_publisher");
+            }
+            match detections.recv().await {
+                Ok(threat) => {
+                    if let Err(e) = self.publish_threat(&client, &BridgeThreatEvent::from(&threat)).await {
+                        error!("❌ Detection {} neither published nor spooled: {}", threat.threat_id, e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("⚠️ Detection publisher skipped {} detections", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Connect to NATS, replaying the spool every time the connection is (re-)established
+    pub async fn connect_nats(self: &Arc<Self>, url: &str) -> SIEMResult<Client> {
+        let reconnected = Arc::new(Notify::new());
+        let notify = reconnected.clone();
+        let client = ConnectOptions::new()
+            .event_callback(move |event| {
+                let notify = notify.clone();
+                async move {
+                    if matches!(event, Event::Connected) {
+                        notify.notify_one();
+                    }
+                }
+            })
+            .connect(url)
+            .await
+            .map_err(|e| SIEMError::Other(format!("NATS connection to {} failed: {}", url, e)))?;
+        let controller = self.clone();
+        let replay_client = client.clone();
+        tokio::spawn(async move {
+            loop {
+                reconnected.notified().await;
+                if controller.spool.is_empty() || controller.mode(Subsystem::ClickHouse) == DegradationMode::SpoolToDisk {
+                    continue;
+                }
+                match controller.replay_spool(&replay_client).await {
+                    Ok(count) if count > 0 => info!("📤 Replayed {} spooled detections after NATS reconnect", count),
+                    Ok(_) => {}
+                    Err(e) => warn!("⚠️ Spool replay failed: {}", e),
+                }
+            }
+        });
+        Ok(client)
+    }
+
+    /// Probe ClickHouse periodically, switching to the spool while it is down and replaying on recovery
+    pub fn spawn_clickhouse_probe(self: Arc<Self>, clickhouse: ClickHouseSettings, nats: Option<Client>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let period = Duration::from_secs(self.settings.clickhouse_probe_interval_seconds.max(1));
+            let timeout = Duration::from_secs(clickhouse.timeout_seconds);
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                match clickhouse_ping(&clickhouse.url, timeout).await {
+                    Ok(()) => {
+                        let was_degraded = self.mode(Subsystem::ClickHouse) == DegradationMode::SpoolToDisk;
+                        self.report_recovery(Subsystem::ClickHouse);
+                        if let (true, Some(client)) = (was_degraded || !self.spool.is_empty(), &nats) {
+                            match self.replay_spool(client).await {
+                                Ok(count) if count > 0 => info!("📤 Replayed {} spooled detections", count),
+                                Ok(_) => {}
+                                Err(e) => warn!("⚠️ Spool replay failed: {}", e),
+                            }
+                        }
+                    }
+                    Err(reason) => self.report_failure(Subsystem::ClickHouse, format!("{} {}", clickhouse.url, reason)),
+                }
+            }
+        })
+    }
+}
+
+/// Something an enrichment provider adds to, tagged when the provider misses its deadline
+pub trait EnrichmentTarget {
+    fn mark_partial(&mut self, provider: &str);
+}
+
+impl EnrichmentTarget for serde_json::Value {
+    fn mark_partial(&mut self, provider: &str) {
+        tag_partial_enrichment(self, provider);
+    }
+}
+
+impl EnrichmentTarget for AdvancedThreatResult {
+    fn mark_partial(&mut self, provider: &str) {
+        self.details.insert("enrichment_partial".to_string(), "true".to_string());
+        let missing = self.details.entry("enrichment_missing".to_string()).or_default();
+        if !missing.is_empty() {
+            missing.push(',');
+        }
+        missing.push_str(provider);
+    }
+}
+
+/// Mark an event as carrying incomplete enrichment
+pub fn tag_partial_enrichment(event: &mut serde_json::Value, provider: &str) {
+    let Some(fields) = event.as_object_mut() else {
+        return;
+    };
+    fields.insert("enrichment_partial".to_string(), true.into());
+    let missing = fields.entry("enrichment_missing").or_insert_with(|| serde_json::Value::Array(Vec::new()));
+    if let Some(missing) = missing.as_array_mut() {
+        missing.push(provider.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller() -> (DegradationController, String) {
+        let dir = std::env::temp_dir().join(format!("spool-{}", uuid::Uuid::new_v4())).to_string_lossy().to_string();
+        let settings = DegradationSettings {
+            spool_dir: dir.clone(),
+            enrichment_timeout_ms: 20,
+            ..Default::default()
+        };
+        (DegradationController::new(settings).unwrap(), dir)
+    }
+
+    #[test]
+    fn test_failure_applies_matrix_and_surfaces_in_health() {
+        let (controller, dir) = controller();
+        assert_eq!(controller.health().status, "ok");
+
+        controller.report_failure(Subsystem::ClickHouse, "connection refused");
+        controller.report_failure(Subsystem::Gpu, "CUDA not available");
+        assert_eq!(controller.mode(Subsystem::ClickHouse), DegradationMode::SpoolToDisk);
+        assert_eq!(controller.mode(Subsystem::Gpu), DegradationMode::CpuFallback);
+        assert_eq!(controller.health().status, "degraded");
+        assert_eq!(controller.to_metrics()["degraded_gpu"], 1.0);
+
+        let threat = BridgeThreatEvent {
+            timestamp: 1_700_000_000,
+            source_ip: "203.0.113.7".to_string(),
+            threat_type: "SQLInjection".to_string(),
+            payload: "UNION SELECT".to_string(),
+            severity: 3,
+            confidence: 0.9,
+        };
+        controller.spool.push(&threat).unwrap();
+        assert_eq!(controller.health().spooled_detections, 1);
+        assert_eq!(controller.spool.take_all().unwrap(), vec![threat]);
+        assert!(controller.spool.is_empty());
+
+        controller.report_recovery(Subsystem::ClickHouse);
+        assert_eq!(controller.mode(Subsystem::ClickHouse), DegradationMode::Normal);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_slow_enrichment_is_skipped_and_tagged() {
+        let (controller, dir) = controller();
+        let mut event = serde_json::json!({ "source_ip": "198.51.100.4" });
+
+        let fast = controller.enrich_within_deadline("geoip", &mut event, async { "NL" }).await;
+        assert_eq!(fast, Some("NL"));
+        let slow = controller
+            .enrich_within_deadline("threat_intel", &mut event, tokio::time::sleep(Duration::from_secs(5)))
+            .await;
+        assert!(slow.is_none());
+
+        assert_eq!(event["enrichment_partial"], true);
+        assert_eq!(event["enrichment_missing"], serde_json::json!(["threat_intel"]));
+        assert_eq!(controller.mode(Subsystem::Enrichment), DegradationMode::PartialEnrichment);
+
+        // Incidents carry the tag in the detection details
+        let mut threat = AdvancedThreatResult::default();
+        for provider in ["related_events", "asset_owner"] {
+            let missed = controller.enrich_within_deadline(provider, &mut threat, tokio::time::sleep(Duration::from_secs(5))).await;
+            assert!(missed.is_none());
+        }
+        assert_eq!(threat.details["enrichment_partial"], "true");
+        assert_eq!(threat.details["enrichment_missing"], "related_events,asset_owner");
+        assert_eq!(controller.to_metrics()["partial_enrichments"], 3.0);
+
+        // A provider answering in time again clears the degradation
+        controller.enrich_within_deadline("geoip", &mut event, async {}).await;
+        assert_eq!(controller.mode(Subsystem::Enrichment), DegradationMode::Normal);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
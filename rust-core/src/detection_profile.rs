@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::ip_net::{network_contains, parse_ip};
+use crate::threat_detection::ThreatCategory;
+
+/// Detail key naming the profile a detection was tuned with
+pub const PROFILE_DETAIL: &str = "detection_profile";
+
+/// Category toggles and thresholds for one kind of environment
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionProfile {
+    pub description: String,
+    pub disabled_categories: Vec<ThreatCategory>,
+    /// Detection methods whose results are dropped, e.g. `anomaly`
+    pub disabled_methods: Vec<String>,
+    /// Replaces the engine-wide false positive threshold
+    pub min_confidence: Option<f32>,
+    /// Per-category confidence thresholds, keyed by category name
+    pub category_min_confidence: HashMap<String, f32>,
+}
+
+impl DetectionProfile {
+    pub fn enables(&self, threat: &AdvancedThreatResult) -> bool {
+        !self.disabled_categories.contains(&threat.category) && !self.disabled_methods.contains(&threat.detection_method)
+    }
+
+    /// Confidence below which a detection counts as a false positive, when the profile sets one
+    pub fn min_confidence_for(&self, category: &ThreatCategory) -> Option<f32> {
+        self.category_min_confidence.get(&category.to_string()).copied().or(self.min_confidence)
+    }
+
+    fn validate(&self, name: &str) -> SIEMResult<()> {
+        for category in self.category_min_confidence.keys() {
+            serde_json::from_value::<ThreatCategory>(serde_json::Value::String(category.clone()))
+                .map_err(|_| SIEMError::Config(format!("Detection profile '{}': unknown category '{}'", name, category)))?;
+        }
+        let mut thresholds = self.min_confidence.iter().chain(self.category_min_confidence.values());
+        if thresholds.any(|t| !(0.0..=1.0).contains(t)) {
+            return Err(SIEMError::Config(format!("Detection profile '{}': confidence thresholds must be within 0..1", name)));
+        }
+        Ok(())
+    }
+}
+
+/// Sources and assets a profile applies to; any listed criterion matching is enough
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileAssignment {
+    pub profile: String,
+    /// Event `source` values, e.g. `nginx`
+    pub sources: Vec<String>,
+    /// Event `host` or `hostname` values
+    pub hosts: Vec<String>,
+    /// Addresses or CIDR ranges matched against the event's source and destination IP
+    pub networks: Vec<String>,
+}
+
+impl ProfileAssignment {
+    fn matches(&self, event: &serde_json::Value) -> bool {
+        let field = |key: &str| event.get(key).and_then(|v| v.as_str());
+        if field("source").is_some_and(|source| self.sources.iter().any(|s| s == source)) {
+            return true;
+        }
+        if ["host", "hostname"].into_iter().filter_map(field).any(|host| self.hosts.iter().any(|h| h.eq_ignore_ascii_case(host))) {
+            return true;
+        }
+        ["source_ip", "destination_ip"]
+            .into_iter()
+            .filter_map(|key| field(key).and_then(parse_ip))
+            .any(|ip| self.networks.iter().any(|network| network_contains(network, ip)))
+    }
+}
+
+/// Named profiles, where they apply and the fallback for everything else
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileSet {
+    /// Profile of events no assignment matches; empty leaves them untuned
+    pub default_profile: String,
+    pub profiles: BTreeMap<String, DetectionProfile>,
+    /// Checked in order; the first match wins
+    pub assignments: Vec<ProfileAssignment>,
+}
+
+impl ProfileSet {
+    pub fn validate(&self) -> SIEMResult<()> {
+        for (name, profile) in &self.profiles {
+            profile.validate(name)?;
+        }
+        let referenced = self.assignments.iter().map(|a| &a.profile).chain(Some(&self.default_profile).filter(|p| !p.is_empty()));
+        for profile in referenced {
+            if !self.profiles.contains_key(profile) {
+                return Err(SIEMError::Config(format!("Unknown detection profile '{}'", profile)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Per-source and per-asset detection tuning profiles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionProfileSettings {
+    pub enabled: bool,
+    /// Profiles changed at runtime are saved here and win over the configured ones
+    pub store_path: String,
+    #[serde(flatten)]
+    pub set: ProfileSet,
+}
+
+impl Default for DetectionProfileSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            store_path: "data/detection-profiles.json".to_string(),
+            set: ProfileSet::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ResolvedSet {
+    set: ProfileSet,
+    profiles: HashMap<String, Arc<DetectionProfile>>,
+}
+
+impl ResolvedSet {
+    fn new(set: ProfileSet) -> SIEMResult<Self> {
+        set.validate()?;
+        let profiles = set.profiles.iter().map(|(name, profile)| (name.clone(), Arc::new(profile.clone()))).collect();
+        Ok(Self { set, profiles })
+    }
+}
+
+/// Runtime-tunable profiles shared by the detection path and the REST API
+#[derive(Debug)]
+pub struct ProfileRegistry {
+    path: PathBuf,
+    state: RwLock<ResolvedSet>,
+}
+
+impl ProfileRegistry {
+    pub fn open(settings: &DetectionProfileSettings) -> SIEMResult<Self> {
+        let path = PathBuf::from(&settings.store_path);
+        let set = match std::fs::read(&path) {
+            Ok(raw) => serde_json::from_slice(&raw)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => settings.set.clone(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, state: RwLock::new(ResolvedSet::new(set)?) })
+    }
+
+    /// The profile `event` is tuned with, if any
+    pub fn resolve(&self, event: &serde_json::Value) -> Option<(String, Arc<DetectionProfile>)> {
+        let state = self.state.read().unwrap();
+        let name = state
+            .set
+            .assignments
+            .iter()
+            .find(|assignment| assignment.matches(event))
+            .map(|assignment| &assignment.profile)
+            .unwrap_or(&state.set.default_profile);
+        state.profiles.get(name).map(|profile| (name.clone(), profile.clone()))
+    }
+
+    pub fn profile_set(&self) -> ProfileSet {
+        self.state.read().unwrap().set.clone()
+    }
+
+    /// Replace the whole set; rejected as a whole when invalid
+    pub fn replace(&self, set: ProfileSet) -> SIEMResult<()> {
+        let resolved = ResolvedSet::new(set).map_err(|e| SIEMError::Validation(e.to_string()))?;
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&resolved.set)?)?;
+        std::fs::rename(tmp, &self.path)?;
+        info!("🎛️ Detection profiles updated: {} profiles, {} assignments", resolved.set.profiles.len(), resolved.set.assignments.len());
+        *self.state.write().unwrap() = resolved;
+        Ok(())
+    }
+
+    pub fn upsert_profile(&self, name: &str, profile: DetectionProfile) -> SIEMResult<()> {
+        let mut set = self.profile_set();
+        set.profiles.insert(name.to_string(), profile);
+        self.replace(set)
+    }
+
+    /// Remove a profile no assignment or default refers to
+    pub fn remove_profile(&self, name: &str) -> SIEMResult<()> {
+        let mut set = self.profile_set();
+        if set.profiles.remove(name).is_none() {
+            return Err(SIEMError::Validation(format!("Unknown detection profile '{}'", name)));
+        }
+        self.replace(set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings() -> DetectionProfileSettings {
+        let pci = DetectionProfile {
+            min_confidence: Some(0.3),
+            category_min_confidence: HashMap::from([("SQLInjection".to_string(), 0.1)]),
+            ..Default::default()
+        };
+        let datacenter = DetectionProfile {
+            disabled_categories: vec![ThreatCategory::InsiderThreat],
+            disabled_methods: vec!["anomaly".to_string()],
+            ..Default::default()
+        };
+        DetectionProfileSettings {
+            enabled: true,
+            store_path: std::env::temp_dir().join(format!("profiles-{}.json", uuid::Uuid::new_v4())).display().to_string(),
+            set: ProfileSet {
+                default_profile: "datacenter".to_string(),
+                profiles: BTreeMap::from([("pci".to_string(), pci), ("datacenter".to_string(), datacenter)]),
+                assignments: vec![ProfileAssignment {
+                    profile: "pci".to_string(),
+                    networks: vec!["10.20.0.0/16".to_string()],
+                    hosts: vec!["pos-01".to_string()],
+                    ..Default::default()
+                }],
+            },
+        }
+    }
+
+    #[test]
+    fn test_events_resolve_to_assigned_profiles() {
+        let registry = ProfileRegistry::open(&settings()).unwrap();
+        let name = |event: serde_json::Value| registry.resolve(&event).map(|(name, _)| name);
+        assert_eq!(name(json!({ "destination_ip": "10.20.4.1" })).as_deref(), Some("pci"));
+        assert_eq!(name(json!({ "host": "POS-01" })).as_deref(), Some("pci"));
+        assert_eq!(name(json!({ "source_ip": "10.21.0.1" })).as_deref(), Some("datacenter"));
+
+        let (_, pci) = registry.resolve(&json!({ "source_ip": "10.20.0.9" })).unwrap();
+        assert_eq!(pci.min_confidence_for(&ThreatCategory::SQLInjection), Some(0.1));
+        assert_eq!(pci.min_confidence_for(&ThreatCategory::XSS), Some(0.3));
+        let (_, datacenter) = registry.resolve(&json!({})).unwrap();
+        let insider = AdvancedThreatResult { category: ThreatCategory::InsiderThreat, ..Default::default() };
+        assert!(!datacenter.enables(&insider));
+        assert!(network_contains("::/0", "2001:db8::1".parse().unwrap()));
+        assert!(!network_contains("10.0.0.1", "10.0.0.2".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_runtime_changes_are_validated_and_persisted() {
+        let settings = settings();
+        let registry = ProfileRegistry::open(&settings).unwrap();
+        assert!(registry.remove_profile("pci").is_err());
+        let typo = DetectionProfile { category_min_confidence: HashMap::from([("SQLi".to_string(), 0.5)]), ..Default::default() };
+        assert!(matches!(registry.upsert_profile("pci", typo), Err(SIEMError::Validation(_))));
+
+        registry.replace(ProfileSet { default_profile: String::new(), assignments: Vec::new(), ..registry.profile_set() }).unwrap();
+        registry.remove_profile("pci").unwrap();
+        assert!(registry.resolve(&json!({ "host": "pos-01" })).is_none());
+
+        // The saved set wins over the configured one on restart
+        let reopened = ProfileRegistry::open(&settings).unwrap();
+        assert_eq!(reopened.profile_set().profiles.keys().collect::<Vec<_>>(), vec!["datacenter"]);
+        let _ = std::fs::remove_file(&settings.store_path);
+    }
+}
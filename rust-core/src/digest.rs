@@ -0,0 +1,621 @@
+//! # Incident Digests
+//!
+//! A daily or weekly email per tenant summarizing its open incidents, new
+//! detections by category, the riskiest entities and SLA breaches. Each
+//! tenant's digest goes out at `send_at` on its own local clock and is
+//! rendered from the subject and body templates, whose `{{ name | filter }}`
+//! placeholders take the values listed in `PLACEHOLDERS` and the filters of
+//! the webhook payload templates.
+//!
+//! Digests are throttled per tenant: one is never sent within
+//! `min_interval_hours` of the previous one, restarts included, and a failed
+//! delivery is retried no sooner than `RETRY_MINUTES` later. Mail goes through
+//! the SMTP relay of the `[alerts]` email settings.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration as StdDuration;
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::{Incident, IncidentResponseEngine, IncidentSeverity, IncidentStatus};
+use crate::payload_template::TextTemplate;
+use crate::smtp::{OutgoingMail, SmtpClient};
+use crate::timezone::{TimeZones, Zone};
+
+/// Digest key of incidents without a `tenant` detail
+pub const DEFAULT_TENANT: &str = "default";
+/// Values available to the subject and body templates
+pub const PLACEHOLDERS: [&str; 11] = [
+    "tenant", "cadence", "period_start", "period_end", "open_count", "new_count", "sla_breach_count",
+    "open_incidents", "new_detections", "top_entities", "sla_breaches",
+];
+/// Wait after a failed delivery before trying the tenant again
+const RETRY_MINUTES: i64 = 15;
+
+const DEFAULT_SUBJECT: &str = "Ultra SIEM {{ cadence }} digest for {{ tenant }}: {{ open_count }} open, {{ sla_breach_count }} SLA breaches";
+const DEFAULT_BODY: &str = "Ultra SIEM {{ cadence }} digest for {{ tenant }}
+{{ period_start }} - {{ period_end }}
+
+Open incidents ({{ open_count }}):
+{{ open_incidents }}
+
+New detections by category ({{ new_count }}):
+{{ new_detections }}
+
+Top risky entities:
+{{ top_entities }}
+
+SLA breaches ({{ sla_breach_count }}):
+{{ sla_breaches }}
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestCadence {
+    Daily,
+    Weekly,
+}
+
+impl DigestCadence {
+    fn period(self) -> Duration {
+        match self {
+            DigestCadence::Daily => Duration::days(1),
+            DigestCadence::Weekly => Duration::weeks(1),
+        }
+    }
+}
+
+impl std::fmt::Display for DigestCadence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DigestCadence::Daily => write!(f, "daily"),
+            DigestCadence::Weekly => write!(f, "weekly"),
+        }
+    }
+}
+
+/// Scheduled incident digest emails per tenant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DigestSettings {
+    pub enabled: bool,
+    pub cadence: DigestCadence,
+    /// Local `HH:MM` in the tenant's zone the digest goes out at
+    pub send_at: String,
+    /// Day of a weekly digest, e.g. `monday`
+    pub weekday: String,
+    /// Tenant to recipients; `default` covers incidents without a tenant
+    pub tenants: BTreeMap<String, Vec<String>>,
+    /// Never send a tenant two digests closer together than this
+    pub min_interval_hours: u64,
+    /// Send nothing when a tenant has no open incidents, detections or breaches
+    pub skip_empty: bool,
+    /// Open incidents listed before the rest are only counted
+    pub max_incidents: usize,
+    pub top_entities: usize,
+    pub subject_template: String,
+    pub body_template: String,
+    /// When each tenant was last sent a digest
+    pub state_path: String,
+}
+
+impl Default for DigestSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cadence: DigestCadence::Daily,
+            send_at: "08:00".to_string(),
+            weekday: "monday".to_string(),
+            tenants: BTreeMap::new(),
+            min_interval_hours: 12,
+            skip_empty: true,
+            max_incidents: 25,
+            top_entities: 10,
+            subject_template: DEFAULT_SUBJECT.to_string(),
+            body_template: DEFAULT_BODY.to_string(),
+            state_path: "data/digest-state.json".to_string(),
+        }
+    }
+}
+
+impl DigestSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        self.send_at()?;
+        self.weekday()?;
+        self.templates()?;
+        if self.subject_template.contains(['\r', '\n']) {
+            return Err(SIEMError::Validation("digest.subject_template must be a single line".to_string()));
+        }
+        if self.enabled && self.tenants.is_empty() {
+            return Err(SIEMError::Validation("digest.tenants names no recipients".to_string()));
+        }
+        if let Some((tenant, _)) = self.tenants.iter().find(|(_, recipients)| recipients.is_empty()) {
+            return Err(SIEMError::Validation(format!("digest.tenants.{} has no recipients", tenant)));
+        }
+        Ok(())
+    }
+
+    /// Compiled subject and body templates
+    fn templates(&self) -> SIEMResult<(TextTemplate, TextTemplate)> {
+        let compile = |name: &str, template: &str| {
+            TextTemplate::compile(template, &PLACEHOLDERS).map_err(|e| SIEMError::Validation(format!("digest.{}: {}", name, e)))
+        };
+        Ok((compile("subject_template", &self.subject_template)?, compile("body_template", &self.body_template)?))
+    }
+
+    fn send_at(&self) -> SIEMResult<NaiveTime> {
+        NaiveTime::parse_from_str(&self.send_at, "%H:%M")
+            .map_err(|_| SIEMError::Validation(format!("Invalid digest.send_at '{}', expected HH:MM", self.send_at)))
+    }
+
+    fn weekday(&self) -> SIEMResult<Weekday> {
+        self.weekday
+            .parse()
+            .map_err(|_| SIEMError::Validation(format!("Invalid digest.weekday '{}'", self.weekday)))
+    }
+}
+
+/// An incident as listed in a digest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestIncident {
+    pub id: String,
+    pub title: String,
+    pub severity: IncidentSeverity,
+    pub status: IncidentStatus,
+    pub created_at: DateTime<Utc>,
+    pub assigned_to: Option<String>,
+    pub sla_deadline: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl From<&Incident> for DigestIncident {
+    fn from(incident: &Incident) -> Self {
+        Self {
+            id: incident.id.clone(),
+            title: incident.title.clone(),
+            severity: incident.severity.clone(),
+            status: incident.status.clone(),
+            created_at: incident.created_at,
+            assigned_to: incident.assigned_to.clone(),
+            sla_deadline: incident.sla_deadline,
+            resolved_at: incident.resolved_at,
+        }
+    }
+}
+
+/// Source IP or user and the severity-weighted count of its new incidents
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RiskyEntity {
+    /// `ip` or `user`
+    pub kind: String,
+    pub value: String,
+    pub risk: f64,
+    pub incidents: u64,
+}
+
+/// What one tenant's digest covers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestReport {
+    pub tenant: String,
+    pub cadence: DigestCadence,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    /// Most severe first, then oldest first
+    pub open: Vec<DigestIncident>,
+    /// Incidents created in the period per detection category
+    pub new_by_category: BTreeMap<String, u64>,
+    pub top_entities: Vec<RiskyEntity>,
+    /// Open past their deadline, or resolved late in the period
+    pub sla_breaches: Vec<DigestIncident>,
+}
+
+impl DigestReport {
+    /// Digest of `tenant` for the period ending at `now`; `incidents` may span tenants
+    pub fn build(tenant: &str, incidents: &[Incident], cadence: DigestCadence, top_entities: usize, now: DateTime<Utc>) -> Self {
+        let period_start = now - cadence.period();
+        let incidents: Vec<&Incident> = incidents.iter().filter(|incident| tenant_of(incident) == tenant).collect();
+
+        let mut open: Vec<DigestIncident> = incidents.iter().filter(|i| is_open(i)).map(|i| DigestIncident::from(*i)).collect();
+        open.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.created_at.cmp(&b.created_at)));
+
+        let new: Vec<&Incident> = incidents.iter().copied().filter(|i| i.created_at >= period_start && i.created_at < now).collect();
+        let mut new_by_category = BTreeMap::new();
+        let mut entities: HashMap<(&str, &str), RiskyEntity> = HashMap::new();
+        for incident in &new {
+            *new_by_category.entry(incident.threat_result.category.to_string()).or_insert(0) += 1;
+            if incident.false_positive || incident.status == IncidentStatus::FalsePositive {
+                continue;
+            }
+            for (kind, value) in [("ip", incident.source_ip.as_str()), ("user", incident.user_id.as_str())] {
+                if value.is_empty() || value == "unknown" {
+                    continue;
+                }
+                let entity = entities.entry((kind, value)).or_insert_with(|| RiskyEntity {
+                    kind: kind.to_string(),
+                    value: value.to_string(),
+                    risk: 0.0,
+                    incidents: 0,
+                });
+                entity.risk += severity_weight(&incident.severity);
+                entity.incidents += 1;
+            }
+        }
+        let mut top: Vec<RiskyEntity> = entities.into_values().collect();
+        top.sort_by(|a, b| b.risk.total_cmp(&a.risk).then_with(|| (&a.kind, &a.value).cmp(&(&b.kind, &b.value))));
+        top.truncate(top_entities);
+
+        let mut sla_breaches: Vec<DigestIncident> = incidents
+            .iter()
+            .filter(|incident| {
+                let Some(deadline) = incident.sla_deadline else { return false };
+                match incident.resolved_at {
+                    _ if is_open(incident) => deadline <= now,
+                    Some(resolved) => resolved > deadline && resolved >= period_start,
+                    None => false,
+                }
+            })
+            .map(|incident| DigestIncident::from(*incident))
+            .collect();
+        sla_breaches.sort_by_key(|incident| incident.sla_deadline);
+
+        Self { tenant: tenant.to_string(), cadence, period_start, period_end: now, open, new_by_category, top_entities: top, sla_breaches }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.open.is_empty() && self.new_by_category.is_empty() && self.sla_breaches.is_empty()
+    }
+
+    /// Template values, times rendered in `zone`
+    pub fn values(&self, zone: &Zone, max_incidents: usize) -> HashMap<&'static str, String> {
+        let mut open_incidents = String::new();
+        for incident in self.open.iter().take(max_incidents) {
+            let assignee = incident.assigned_to.as_deref().map(|a| format!(", assigned to {}", a)).unwrap_or_default();
+            let _ = writeln!(
+                open_incidents,
+                "  - [{}] {} {} ({:?}, opened {}{})",
+                incident.severity, incident.id, incident.title, incident.status, zone.format(incident.created_at), assignee
+            );
+        }
+        if self.open.len() > max_incidents {
+            let _ = writeln!(open_incidents, "  ... and {} more", self.open.len() - max_incidents);
+        }
+
+        let mut new_detections = String::new();
+        for (category, count) in &self.new_by_category {
+            let _ = writeln!(new_detections, "  - {}: {}", category, count);
+        }
+
+        let mut top_entities = String::new();
+        for entity in &self.top_entities {
+            let _ = writeln!(top_entities, "  - {} {}: risk {:.0} over {} incidents", entity.kind, entity.value, entity.risk, entity.incidents);
+        }
+
+        let mut sla_breaches = String::new();
+        for incident in &self.sla_breaches {
+            let deadline = incident.sla_deadline.unwrap_or(self.period_end);
+            let late = incident.resolved_at.unwrap_or(self.period_end) - deadline;
+            let outcome = match incident.resolved_at {
+                Some(_) => "resolved",
+                None => "still open",
+            };
+            let _ = writeln!(
+                sla_breaches,
+                "  - [{}] {} {}: due {}, {} {}h {}m late",
+                incident.severity, incident.id, incident.title, zone.format(deadline), outcome, late.num_hours(), late.num_minutes() % 60
+            );
+        }
+
+        let section = |text: String| match text.is_empty() {
+            true => "  none".to_string(),
+            false => text.trim_end().to_string(),
+        };
+        HashMap::from([
+            ("tenant", self.tenant.clone()),
+            ("cadence", self.cadence.to_string()),
+            ("period_start", zone.format(self.period_start)),
+            ("period_end", zone.format(self.period_end)),
+            ("open_count", self.open.len().to_string()),
+            ("new_count", self.new_by_category.values().sum::<u64>().to_string()),
+            ("sla_breach_count", self.sla_breaches.len().to_string()),
+            ("open_incidents", section(open_incidents)),
+            ("new_detections", section(new_detections)),
+            ("top_entities", section(top_entities)),
+            ("sla_breaches", section(sla_breaches)),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct DigestState {
+    last_sent: BTreeMap<String, DateTime<Utc>>,
+}
+
+/// Composes and sends the tenant digests when they fall due
+#[derive(Debug)]
+pub struct Digester {
+    settings: DigestSettings,
+    send_at: NaiveTime,
+    weekday: Weekday,
+    subject: TextTemplate,
+    body: TextTemplate,
+    smtp: Option<SmtpClient>,
+    store_path: PathBuf,
+    state: Mutex<DigestState>,
+    /// Schedule start for tenants never sent a digest
+    started_at: DateTime<Utc>,
+    failed_at: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl Digester {
+    /// `smtp` may be `None` to compose digests without sending them
+    pub fn open(settings: DigestSettings, smtp: Option<SmtpClient>) -> SIEMResult<Self> {
+        settings.validate()?;
+        let store_path = PathBuf::from(&settings.state_path);
+        let state = match std::fs::read(&store_path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => DigestState::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let (subject, body) = settings.templates()?;
+        Ok(Self {
+            send_at: settings.send_at()?,
+            subject,
+            body,
+            weekday: settings.weekday()?,
+            settings,
+            smtp,
+            store_path,
+            state: Mutex::new(state),
+            started_at: Utc::now(),
+            failed_at: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn settings(&self) -> &DigestSettings {
+        &self.settings
+    }
+
+    pub fn last_sent(&self, tenant: &str) -> Option<DateTime<Utc>> {
+        self.state.lock().unwrap().last_sent.get(tenant).copied()
+    }
+
+    /// First scheduled send strictly after `after` in `zone`
+    pub fn next_run(&self, zone: &Zone, after: DateTime<Utc>) -> DateTime<Utc> {
+        let mut next = zone.next_daily(after, self.send_at);
+        if self.settings.cadence == DigestCadence::Weekly {
+            for _ in 0..7 {
+                if zone.to_local(next).weekday() == self.weekday {
+                    break;
+                }
+                next = zone.next_daily(next, self.send_at);
+            }
+        }
+        next
+    }
+
+    /// Whether `tenant` is scheduled and not throttled at `now`
+    pub fn is_due(&self, tenant: &str, zone: &Zone, now: DateTime<Utc>) -> bool {
+        if !self.settings.tenants.contains_key(tenant) {
+            return false;
+        }
+        let last_sent = self.last_sent(tenant);
+        if self.next_run(zone, last_sent.unwrap_or(self.started_at)) > now {
+            return false;
+        }
+        let throttle = Duration::hours(self.settings.min_interval_hours as i64);
+        if last_sent.is_some_and(|sent| now - sent < throttle) {
+            return false;
+        }
+        self.failed_at.lock().unwrap().get(tenant).is_none_or(|failed| now - *failed >= Duration::minutes(RETRY_MINUTES))
+    }
+
+    /// The tenant's digest mail, or `None` when it is empty and `skip_empty` is set
+    pub fn compose(&self, tenant: &str, incidents: &[Incident], zone: &Zone, now: DateTime<Utc>) -> SIEMResult<Option<OutgoingMail>> {
+        let report = DigestReport::build(tenant, incidents, self.settings.cadence, self.settings.top_entities, now);
+        if report.is_empty() && self.settings.skip_empty {
+            return Ok(None);
+        }
+        let values = serde_json::to_value(report.values(zone, self.settings.max_incidents))?;
+        Ok(Some(OutgoingMail {
+            to: self.settings.tenants.get(tenant).cloned().unwrap_or_default(),
+            subject: self.subject.render(&values)?,
+            body: self.body.render(&values)?,
+        }))
+    }
+
+    /// Record a digest as sent, or skipped as empty, at `now`
+    pub fn mark_sent(&self, tenant: &str, now: DateTime<Utc>) -> SIEMResult<()> {
+        self.failed_at.lock().unwrap().remove(tenant);
+        let state = {
+            let mut state = self.state.lock().unwrap();
+            state.last_sent.insert(tenant.to_string(), now);
+            state.clone()
+        };
+        if let Some(dir) = self.store_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.store_path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&state)?)?;
+        std::fs::rename(tmp, &self.store_path)?;
+        Ok(())
+    }
+
+    /// Send every digest due at `now`; returns how many were sent
+    pub async fn run_once(&self, engine: &IncidentResponseEngine, zones: &TimeZones, now: DateTime<Utc>) -> SIEMResult<usize> {
+        let due: Vec<&String> = self
+            .settings
+            .tenants
+            .keys()
+            .filter(|tenant| self.is_due(tenant, zones.for_tenant(Some(tenant.as_str())), now))
+            .collect();
+        if due.is_empty() {
+            return Ok(0);
+        }
+        let smtp = self.smtp.as_ref().ok_or_else(|| SIEMError::Config("digest: no SMTP relay configured".to_string()))?;
+        let incidents = engine.get_all_incidents();
+        let mut sent = 0;
+        for tenant in due {
+            let zone = zones.for_tenant(Some(tenant.as_str()));
+            let Some(mail) = self.compose(tenant, &incidents, zone, now)? else {
+                info!("📰 Nothing to report for {}, digest skipped", tenant);
+                self.mark_sent(tenant, now)?;
+                continue;
+            };
+            match smtp.send(&mail).await {
+                Ok(()) => {
+                    info!("📰 {} digest sent to {} for {}", self.settings.cadence, mail.to.join(", "), tenant);
+                    self.mark_sent(tenant, now)?;
+                    sent += 1;
+                }
+                Err(e) => {
+                    warn!("⚠️ Digest for {} not delivered, retrying in {} minutes: {}", tenant, RETRY_MINUTES, e);
+                    self.failed_at.lock().unwrap().insert(tenant.clone(), now);
+                }
+            }
+        }
+        Ok(sent)
+    }
+
+    pub fn spawn(self: Arc<Self>, incidents: Arc<IncidentResponseEngine>, zones: Arc<TimeZones>) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.settings.enabled {
+            return None;
+        }
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(StdDuration::from_secs(60));
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.run_once(&incidents, &zones, Utc::now()).await {
+                    warn!("⚠️ Incident digests failed: {}", e);
+                }
+            }
+        }))
+    }
+}
+
+fn tenant_of(incident: &Incident) -> &str {
+    incident.threat_result.details.get("tenant").map_or(DEFAULT_TENANT, String::as_str)
+}
+
+fn is_open(incident: &Incident) -> bool {
+    !incident.false_positive
+        && matches!(incident.status, IncidentStatus::Open | IncidentStatus::Investigating | IncidentStatus::Containing)
+}
+
+/// Risk points per incident, doubling with each severity level
+fn severity_weight(severity: &IncidentSeverity) -> f64 {
+    match severity {
+        IncidentSeverity::Low => 1.0,
+        IncidentSeverity::Medium => 2.0,
+        IncidentSeverity::High => 4.0,
+        IncidentSeverity::Critical => 8.0,
+        IncidentSeverity::Emergency => 16.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::threat_detection::ThreatCategory;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap()
+    }
+
+    fn incident(tenant: &str, severity: IncidentSeverity, status: IncidentStatus, created_at: DateTime<Utc>) -> Incident {
+        let mut threat = AdvancedThreatResult { category: ThreatCategory::BruteForce, ..Default::default() };
+        threat.details.insert("tenant".to_string(), tenant.to_string());
+        Incident {
+            id: format!("INC-{}", uuid::Uuid::new_v4()),
+            severity,
+            status,
+            title: "SSH brute force".to_string(),
+            source_ip: "203.0.113.7".to_string(),
+            user_id: "unknown".to_string(),
+            threat_result: threat,
+            ..Incident::fixture(created_at)
+        }
+    }
+
+    fn digester(cadence: DigestCadence) -> (Digester, PathBuf) {
+        let state_path = std::env::temp_dir().join(format!("digest-{}.json", uuid::Uuid::new_v4()));
+        let settings = DigestSettings {
+            enabled: true,
+            cadence,
+            send_at: "08:00".to_string(),
+            weekday: "monday".to_string(),
+            tenants: BTreeMap::from([("acme".to_string(), vec!["soc@acme.example".to_string()])]),
+            state_path: state_path.display().to_string(),
+            ..DigestSettings::default()
+        };
+        (Digester::open(settings, None).unwrap(), state_path)
+    }
+
+    #[test]
+    fn test_report_covers_open_new_entities_and_sla_breaches() {
+        let now = at(17, 8);
+        let mut critical = incident("acme", IncidentSeverity::Critical, IncidentStatus::Investigating, at(16, 12));
+        critical.sla_deadline = Some(at(16, 16));
+        let mut late = incident("acme", IncidentSeverity::High, IncidentStatus::Resolved, at(16, 9));
+        late.resolved_at = Some(at(16, 20));
+        late.sla_deadline = Some(at(16, 13));
+        late.user_id = "alice".to_string();
+        let old = incident("acme", IncidentSeverity::Low, IncidentStatus::Open, at(1, 9));
+        let other = incident("globex", IncidentSeverity::Emergency, IncidentStatus::Open, at(16, 10));
+
+        let report = DigestReport::build("acme", &[critical.clone(), late, old.clone(), other], DigestCadence::Daily, 5, now);
+        assert_eq!(report.open.iter().map(|i| i.id.as_str()).collect::<Vec<_>>(), vec![critical.id.as_str(), old.id.as_str()]);
+        assert_eq!(report.new_by_category, BTreeMap::from([("BruteForce".to_string(), 2)]));
+        assert_eq!((report.top_entities[0].value.as_str(), report.top_entities[0].risk), ("203.0.113.7", 12.0));
+        assert_eq!(report.top_entities[1].value, "alice");
+        assert_eq!(report.sla_breaches.len(), 2);
+
+        let values = serde_json::to_value(report.values(&Zone::fixed(2 * 3600), 1)).unwrap();
+        let body = TextTemplate::compile(DEFAULT_BODY, &PLACEHOLDERS).unwrap().render(&values).unwrap();
+        assert!(body.contains("Open incidents (2):\n  - [Critical]"));
+        assert!(body.contains("  ... and 1 more"));
+        assert!(body.contains("still open 16h 0m late"));
+        assert!(body.contains("resolved 7h 0m late"));
+        assert_eq!(TextTemplate::compile("{{ tenant | upper }}", &PLACEHOLDERS).unwrap().render(&values).unwrap(), "ACME");
+        assert!(DigestSettings { body_template: "{{ incident.id }}".to_string(), ..DigestSettings::default() }.validate().is_err());
+        assert!(DigestSettings { subject_template: "{{ tenant".to_string(), ..DigestSettings::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_weekly_schedule_in_tenant_zone() {
+        let (digester, _) = digester(DigestCadence::Weekly);
+        let berlin_summer = Zone::fixed(2 * 3600);
+        // Saturday 2026-10-17; the next Monday 08:00 at UTC+2 is 06:00 UTC
+        assert_eq!(digester.next_run(&berlin_summer, at(17, 12)), at(19, 6));
+        assert_eq!(digester.next_run(&berlin_summer, at(19, 6)), at(26, 6));
+    }
+
+    #[test]
+    fn test_digest_is_throttled_per_tenant_across_restarts() {
+        let (digester, state_path) = digester(DigestCadence::Daily);
+        let zone = Zone::utc();
+        let first = digester.next_run(&zone, Utc::now());
+        assert!(!digester.is_due("acme", &zone, first - Duration::minutes(1)));
+        assert!(digester.is_due("acme", &zone, first));
+        assert!(!digester.is_due("globex", &zone, first));
+
+        let incidents = vec![incident("acme", IncidentSeverity::High, IncidentStatus::Open, first - Duration::hours(1))];
+        let mail = digester.compose("acme", &incidents, &zone, first).unwrap().unwrap();
+        assert_eq!(mail.to, vec!["soc@acme.example"]);
+        assert!(mail.subject.contains("daily digest for acme: 1 open, 0 SLA breaches"));
+        assert!(digester.compose("acme", &[], &zone, first).unwrap().is_none());
+        digester.mark_sent("acme", first).unwrap();
+
+        let restarted = Digester::open(digester.settings().clone(), None).unwrap();
+        assert_eq!(restarted.last_sent("acme"), Some(first));
+        assert!(!restarted.is_due("acme", &zone, first + Duration::hours(23)));
+        assert!(restarted.is_due("acme", &zone, first + Duration::days(1)));
+        let _ = std::fs::remove_file(state_path);
+    }
+}
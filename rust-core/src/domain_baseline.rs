@@ -0,0 +1,511 @@
+//! # Domain Allow-list Learning
+//!
+//! New deployments start in `learning`: contacted domains are recorded per
+//! asset group for `learning_days` without raising anything. The domains
+//! enough assets of a group contacted are then proposed as that group's
+//! allow-list and the baseline waits in `review` until an analyst approves
+//! the proposal, with removals and additions. Only then does it switch to
+//! `enforcing`, where a contact outside the group's allow-list is reported.
+//!
+//! Domains are tracked by registrable domain (`cdn.example.com` counts as
+//! `example.com`), and an allow-list entry also covers its subdomains.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::attack;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::ip_net::{network_contains, parse_ip};
+use crate::threat_detection::{ThreatCategory, ThreatSeverity};
+
+/// Group of events no configured asset group matches
+pub const DEFAULT_GROUP: &str = "default";
+/// Assets remembered per domain; only whether `min_hosts` is reached matters
+const MAX_HOSTS_PER_DOMAIN: usize = 32;
+
+/// Assets sharing one allow-list; any listed criterion matching is enough
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainAssetGroup {
+    pub name: String,
+    /// Event `host` or `hostname` values
+    pub hosts: Vec<String>,
+    /// Addresses or CIDR ranges matched against the event's source IP
+    pub networks: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainLearningSettings {
+    pub enabled: bool,
+    pub learning_days: u32,
+    /// A domain is proposed once this many assets of a group contacted it
+    pub min_hosts: usize,
+    /// ...and the group contacted it at least this often
+    pub min_contacts: u64,
+    /// Checked in order; unmatched events belong to `default`
+    pub asset_groups: Vec<DomainAssetGroup>,
+    /// Event fields holding the contacted domain, first present wins
+    pub domain_fields: Vec<String>,
+    pub store_path: String,
+    /// Domains recorded per group while learning; further ones are ignored
+    pub max_domains_per_group: usize,
+    /// A deviation is reported again for the same group after this long
+    pub realert_hours: u64,
+    /// How often observations are saved and the end of learning is checked
+    pub save_interval_seconds: u64,
+}
+
+impl Default for DomainLearningSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            learning_days: 14,
+            min_hosts: 2,
+            min_contacts: 5,
+            asset_groups: Vec::new(),
+            domain_fields: ["query_name", "dns_query", "domain", "server_name", "http_host"].map(String::from).to_vec(),
+            store_path: "data/domain-baseline.json".to_string(),
+            max_domains_per_group: 50_000,
+            realert_hours: 24,
+            save_interval_seconds: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DomainMode {
+    Learning,
+    /// Learning finished; the proposal awaits approval
+    Review,
+    Enforcing,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DomainObservation {
+    pub hosts: BTreeSet<String>,
+    pub contacts: u64,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct GroupDomain {
+    pub group: String,
+    pub domain: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposedDomain {
+    pub group: String,
+    pub domain: String,
+    pub hosts: usize,
+    pub contacts: u64,
+}
+
+/// Analyst decision on a proposal
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainReview {
+    pub reviewer: String,
+    /// Proposed domains not to allow
+    pub remove: Vec<GroupDomain>,
+    /// Domains to allow that were not proposed
+    pub add: Vec<GroupDomain>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BaselineState {
+    mode: DomainMode,
+    learning_started_at: DateTime<Utc>,
+    observed: BTreeMap<String, BTreeMap<String, DomainObservation>>,
+    proposal: Vec<ProposedDomain>,
+    allow_list: BTreeMap<String, BTreeSet<String>>,
+    reviewed_by: Option<String>,
+    reviewed_at: Option<DateTime<Utc>>,
+}
+
+/// Mode, proposal and allow-list without the raw observations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainBaselineStatus {
+    pub mode: DomainMode,
+    pub learning_started_at: DateTime<Utc>,
+    pub learning_ends_at: DateTime<Utc>,
+    /// Distinct domains recorded per group
+    pub observed_domains: BTreeMap<String, usize>,
+    pub proposal: Vec<ProposedDomain>,
+    pub allow_list: BTreeMap<String, BTreeSet<String>>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+/// Contact with a domain outside the group's allow-list
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainAlert {
+    pub group: String,
+    pub domain: String,
+    pub asset: String,
+}
+
+impl DomainAlert {
+    pub fn to_threat(&self, event: &serde_json::Value, timestamp: u64) -> AdvancedThreatResult {
+        let field = |name: &str| event.get(name).and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let mut details = attack::technique_details(&["T1071".to_string()]);
+        details.insert("domain".to_string(), self.domain.clone());
+        details.insert("asset_group".to_string(), self.group.clone());
+        details.insert("hostname".to_string(), self.asset.clone());
+        AdvancedThreatResult {
+            threat_id: uuid::Uuid::new_v4().to_string(),
+            timestamp,
+            severity: ThreatSeverity::Low,
+            category: ThreatCategory::Network,
+            confidence: 0.6,
+            detection_method: "domain_baseline".to_string(),
+            source_ip: field("source_ip"),
+            destination_ip: field("destination_ip"),
+            user_id: field("user_id"),
+            description: format!("{} contacted {}, outside the learned domains of asset group {}", self.asset, self.domain, self.group),
+            iocs: vec![self.domain.clone()],
+            details,
+            ..Default::default()
+        }
+    }
+}
+
+/// Learned per-group domain allow-lists shared by the detection path and the REST API
+#[derive(Debug)]
+pub struct DomainBaseline {
+    settings: DomainLearningSettings,
+    path: PathBuf,
+    state: RwLock<BaselineState>,
+    dirty: AtomicBool,
+    alerted: Mutex<HashMap<GroupDomain, DateTime<Utc>>>,
+}
+
+impl DomainBaseline {
+    pub fn open(settings: DomainLearningSettings) -> SIEMResult<Self> {
+        let path = PathBuf::from(&settings.store_path);
+        let state = match std::fs::read(&path) {
+            Ok(raw) => serde_json::from_slice(&raw)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BaselineState {
+                mode: DomainMode::Learning,
+                learning_started_at: Utc::now(),
+                observed: BTreeMap::new(),
+                proposal: Vec::new(),
+                allow_list: BTreeMap::new(),
+                reviewed_by: None,
+                reviewed_at: None,
+            },
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { settings, path, state: RwLock::new(state), dirty: AtomicBool::new(false), alerted: Mutex::new(HashMap::new()) })
+    }
+
+    pub fn settings(&self) -> &DomainLearningSettings {
+        &self.settings
+    }
+
+    pub fn mode(&self) -> DomainMode {
+        self.state.read().unwrap().mode
+    }
+
+    pub fn status(&self) -> DomainBaselineStatus {
+        let state = self.state.read().unwrap();
+        DomainBaselineStatus {
+            mode: state.mode,
+            learning_started_at: state.learning_started_at,
+            learning_ends_at: state.learning_started_at + chrono::Duration::days(self.settings.learning_days as i64),
+            observed_domains: state.observed.iter().map(|(group, domains)| (group.clone(), domains.len())).collect(),
+            proposal: state.proposal.clone(),
+            allow_list: state.allow_list.clone(),
+            reviewed_by: state.reviewed_by.clone(),
+            reviewed_at: state.reviewed_at,
+        }
+    }
+
+    /// Record the contacted domain while learning; report it when enforcing and not allowed
+    pub fn observe(&self, event: &serde_json::Value, now: DateTime<Utc>) -> Option<DomainAlert> {
+        let field = |key: &str| event.get(key).and_then(|v| v.as_str()).filter(|v| !v.is_empty());
+        let domain = self.settings.domain_fields.iter().find_map(|key| field(key)).and_then(normalize_domain)?;
+        let asset = ["host", "hostname", "source_ip"].into_iter().find_map(field).unwrap_or("unknown").to_string();
+        let group = self.group_of(event);
+        if self.mode() == DomainMode::Enforcing {
+            return self.check(group, domain, asset, now);
+        }
+        let mut state = self.state.write().unwrap();
+        let domains = state.observed.entry(group).or_default();
+        let key = registrable_domain(&domain);
+        if !domains.contains_key(&key) && domains.len() >= self.settings.max_domains_per_group {
+            return None;
+        }
+        let observation = domains.entry(key).or_default();
+        if observation.hosts.len() < MAX_HOSTS_PER_DOMAIN {
+            observation.hosts.insert(asset);
+        }
+        observation.contacts += 1;
+        observation.first_seen.get_or_insert(now);
+        observation.last_seen = Some(now);
+        self.dirty.store(true, Ordering::Relaxed);
+        None
+    }
+
+    /// Close learning and propose every domain enough of a group's assets contacted
+    pub fn propose(&self) -> SIEMResult<Vec<ProposedDomain>> {
+        let mut state = self.state.write().unwrap();
+        if state.mode == DomainMode::Enforcing {
+            return Err(SIEMError::Validation("Domain baseline is already enforcing; restart learning first".to_string()));
+        }
+        state.proposal = state
+            .observed
+            .iter()
+            .flat_map(|(group, domains)| {
+                domains
+                    .iter()
+                    .filter(|(_, o)| o.hosts.len() >= self.settings.min_hosts && o.contacts >= self.settings.min_contacts)
+                    .map(|(domain, o)| ProposedDomain { group: group.clone(), domain: domain.clone(), hosts: o.hosts.len(), contacts: o.contacts })
+            })
+            .collect();
+        state.mode = DomainMode::Review;
+        self.save(&state)?;
+        info!("🌐 Domain learning finished: {} domains proposed for review", state.proposal.len());
+        Ok(state.proposal.clone())
+    }
+
+    /// Apply the reviewed proposal to the allow-lists and start enforcing
+    pub fn approve(&self, review: &DomainReview, now: DateTime<Utc>) -> SIEMResult<DomainBaselineStatus> {
+        if review.reviewer.trim().is_empty() {
+            return Err(SIEMError::Validation("A reviewer is required".to_string()));
+        }
+        {
+            let mut state = self.state.write().unwrap();
+            if state.mode != DomainMode::Review {
+                return Err(SIEMError::Validation(format!("Nothing to approve while {:?}", state.mode)));
+            }
+            let removed: BTreeSet<GroupDomain> = review.remove.iter().map(normalize_entry).collect();
+            let accepted: Vec<GroupDomain> = state
+                .proposal
+                .iter()
+                .map(|p| GroupDomain { group: p.group.clone(), domain: p.domain.clone() })
+                .filter(|entry| !removed.contains(entry))
+                .chain(review.add.iter().map(normalize_entry))
+                .collect();
+            for entry in accepted {
+                state.allow_list.entry(entry.group).or_default().insert(entry.domain);
+            }
+            state.mode = DomainMode::Enforcing;
+            state.proposal.clear();
+            state.observed.clear();
+            state.reviewed_by = Some(review.reviewer.clone());
+            state.reviewed_at = Some(now);
+            self.save(&state)?;
+        }
+        info!("🌐 Domain allow-lists approved by {}; enforcing", review.reviewer);
+        Ok(self.status())
+    }
+
+    /// Go back to learning from scratch; the current allow-lists are kept and extended on the next approval
+    pub fn restart_learning(&self, now: DateTime<Utc>) -> SIEMResult<()> {
+        let mut state = self.state.write().unwrap();
+        state.mode = DomainMode::Learning;
+        state.learning_started_at = now;
+        state.observed.clear();
+        state.proposal.clear();
+        self.alerted.lock().unwrap().clear();
+        self.save(&state)
+    }
+
+    /// Save pending observations and propose once learning has run its course
+    pub fn tick(&self, now: DateTime<Utc>) -> SIEMResult<()> {
+        let (mode, started) = {
+            let state = self.state.read().unwrap();
+            (state.mode, state.learning_started_at)
+        };
+        if mode == DomainMode::Learning && now >= started + chrono::Duration::days(self.settings.learning_days as i64) {
+            self.propose()?;
+        } else if self.dirty.load(Ordering::Relaxed) {
+            self.save(&self.state.read().unwrap())?;
+        }
+        Ok(())
+    }
+
+    pub fn spawn(self: Arc<Self>) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.settings.enabled {
+            return None;
+        }
+        Some(tokio::spawn(async move {
+            info!("🌐 Domain baseline {:?} across {} asset groups", self.mode(), self.settings.asset_groups.len() + 1);
+            let mut interval = tokio::time::interval(Duration::from_secs(self.settings.save_interval_seconds.max(1)));
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.tick(Utc::now()) {
+                    error!("❌ Failed to update the domain baseline: {}", e);
+                }
+            }
+        }))
+    }
+
+    fn check(&self, group: String, domain: String, asset: String, now: DateTime<Utc>) -> Option<DomainAlert> {
+        let allowed = {
+            let state = self.state.read().unwrap();
+            state.allow_list.get(&group).is_some_and(|allowed| parents(&domain).any(|parent| allowed.contains(parent)))
+        };
+        if allowed {
+            return None;
+        }
+        let key = GroupDomain { group: group.clone(), domain: registrable_domain(&domain) };
+        let mut alerted = self.alerted.lock().unwrap();
+        let realert = chrono::Duration::hours(self.settings.realert_hours as i64);
+        if alerted.get(&key).is_some_and(|last| now - *last < realert) {
+            return None;
+        }
+        alerted.insert(key, now);
+        Some(DomainAlert { group, domain, asset })
+    }
+
+    fn group_of(&self, event: &serde_json::Value) -> String {
+        let field = |key: &str| event.get(key).and_then(|v| v.as_str());
+        let host = field("host").or_else(|| field("hostname"));
+        let ip = field("source_ip").and_then(parse_ip);
+        self.settings
+            .asset_groups
+            .iter()
+            .find(|group| {
+                host.is_some_and(|host| group.hosts.iter().any(|h| h.eq_ignore_ascii_case(host)))
+                    || ip.is_some_and(|ip| group.networks.iter().any(|network| network_contains(network, ip)))
+            })
+            .map(|group| group.name.clone())
+            .unwrap_or_else(|| DEFAULT_GROUP.to_string())
+    }
+
+    fn save(&self, state: &BaselineState) -> SIEMResult<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(state)?)?;
+        std::fs::rename(tmp, &self.path)?;
+        self.dirty.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Lowercase host name without trailing dot, port or URL parts; None for IP literals
+fn normalize_domain(raw: &str) -> Option<String> {
+    let host = raw.split("://").last().unwrap_or(raw);
+    let host = host.split(['/', '?', '#']).next().unwrap_or(host);
+    let host = host.rsplit_once(':').filter(|(_, port)| port.bytes().all(|b| b.is_ascii_digit())).map_or(host, |(host, _)| host);
+    let host = host.trim().trim_end_matches('.').to_ascii_lowercase();
+    (host.contains('.') && host.parse::<IpAddr>().is_err()).then_some(host)
+}
+
+fn normalize_entry(entry: &GroupDomain) -> GroupDomain {
+    GroupDomain {
+        group: entry.group.clone(),
+        domain: normalize_domain(&entry.domain).unwrap_or_else(|| entry.domain.to_ascii_lowercase()),
+    }
+}
+
+/// `domain` and each of its parent domains, e.g. `a.b.com`, `b.com`, `com`
+fn parents(domain: &str) -> impl Iterator<Item = &str> {
+    std::iter::once(domain).chain(domain.match_indices('.').map(move |(i, _)| &domain[i + 1..]))
+}
+
+/// Last two labels, or three under common second-level registries like `co.uk`
+fn registrable_domain(domain: &str) -> String {
+    let labels: Vec<&str> = domain.split('.').collect();
+    let n = labels.len();
+    let second_level = n >= 3 && labels[n - 1].len() == 2 && ["co", "com", "net", "org", "gov", "ac", "edu"].contains(&labels[n - 2]);
+    labels[n.saturating_sub(if second_level { 3 } else { 2 })..].join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings() -> DomainLearningSettings {
+        DomainLearningSettings {
+            enabled: true,
+            min_contacts: 2,
+            asset_groups: vec![DomainAssetGroup { name: "servers".to_string(), networks: vec!["10.1.0.0/16".to_string()], ..Default::default() }],
+            store_path: std::env::temp_dir().join(format!("domain-baseline-{}.json", uuid::Uuid::new_v4())).display().to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn dns(host: &str, ip: &str, domain: &str) -> serde_json::Value {
+        json!({ "event_type": "dns", "host": host, "source_ip": ip, "query_name": domain })
+    }
+
+    #[test]
+    fn test_learns_proposes_and_enforces_per_group() {
+        let baseline = DomainBaseline::open(settings()).unwrap();
+        let now = Utc::now();
+        for (host, ip) in [("web-01", "10.1.0.5"), ("web-02", "10.1.0.6")] {
+            assert!(baseline.observe(&dns(host, ip, "updates.ubuntu.com."), now).is_none());
+            assert!(baseline.observe(&dns(host, ip, "api.stripe.com"), now).is_none());
+        }
+        baseline.observe(&dns("laptop-7", "192.168.5.20", "mirror.ubuntu.com"), now);
+        // Contacted by one asset only
+        baseline.observe(&dns("web-01", "10.1.0.5", "pastebin.com"), now);
+        baseline.observe(&dns("web-01", "10.1.0.5", "pastebin.com"), now);
+
+        // Learning ends on its own after learning_days
+        baseline.tick(now).unwrap();
+        assert_eq!(baseline.mode(), DomainMode::Learning);
+        baseline.tick(now + chrono::Duration::days(15)).unwrap();
+        let status = baseline.status();
+        assert_eq!(status.mode, DomainMode::Review);
+        let proposed: Vec<(&str, &str)> = status.proposal.iter().map(|p| (p.group.as_str(), p.domain.as_str())).collect();
+        assert_eq!(proposed, vec![("servers", "stripe.com"), ("servers", "ubuntu.com")]);
+        // Still silent until a human approves
+        assert!(baseline.observe(&dns("web-01", "10.1.0.5", "evil.example"), now).is_none());
+
+        let review = DomainReview {
+            reviewer: "alice".to_string(),
+            remove: vec![GroupDomain { group: "servers".to_string(), domain: "stripe.com".to_string() }],
+            add: vec![GroupDomain { group: DEFAULT_GROUP.to_string(), domain: "ubuntu.com".to_string() }],
+        };
+        assert_eq!(baseline.approve(&review, now).unwrap().mode, DomainMode::Enforcing);
+        assert!(baseline.observe(&dns("web-01", "10.1.0.5", "security.ubuntu.com"), now).is_none());
+        assert!(baseline.observe(&dns("laptop-7", "192.168.5.20", "archive.ubuntu.com"), now).is_none());
+
+        let alert = baseline.observe(&dns("web-02", "10.1.0.6", "api.stripe.com"), now).unwrap();
+        assert_eq!((alert.group.as_str(), alert.domain.as_str(), alert.asset.as_str()), ("servers", "api.stripe.com", "web-02"));
+        // Reported once per group and domain within realert_hours
+        assert!(baseline.observe(&dns("web-01", "10.1.0.5", "files.stripe.com"), now).is_none());
+        assert!(baseline.observe(&dns("web-01", "10.1.0.5", "files.stripe.com"), now + chrono::Duration::hours(25)).is_some());
+        let _ = std::fs::remove_file(&baseline.settings().store_path);
+    }
+
+    #[test]
+    fn test_state_survives_restart_and_approval_needs_review() {
+        let settings = settings();
+        let baseline = DomainBaseline::open(settings.clone()).unwrap();
+        let review = DomainReview { reviewer: "alice".to_string(), ..Default::default() };
+        assert!(baseline.approve(&review, Utc::now()).is_err());
+        for host in ["a", "b"] {
+            baseline.observe(&dns(host, "10.9.0.1", "https://login.microsoftonline.com:443/common"), Utc::now());
+            baseline.observe(&dns(host, "10.9.0.1", "bbc.co.uk"), Utc::now());
+        }
+        baseline.tick(Utc::now()).unwrap();
+
+        let reopened = DomainBaseline::open(settings.clone()).unwrap();
+        assert_eq!(reopened.status().observed_domains[DEFAULT_GROUP], 2);
+        let proposal = reopened.propose().unwrap();
+        assert_eq!(proposal.iter().map(|p| p.domain.as_str()).collect::<Vec<_>>(), vec!["bbc.co.uk", "microsoftonline.com"]);
+        assert!(reopened.approve(&DomainReview::default(), Utc::now()).is_err());
+        reopened.approve(&review, Utc::now()).unwrap();
+        assert_eq!(DomainBaseline::open(settings.clone()).unwrap().mode(), DomainMode::Enforcing);
+        let _ = std::fs::remove_file(&settings.store_path);
+    }
+}
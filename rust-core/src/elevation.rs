@@ -0,0 +1,74 @@
+//! Just-in-time privilege elevation.
+//!
+//! An analyst asks for a higher role for a bounded time with a justification,
+//! an approver grants or denies it, and the extra permissions lapse on their
+//! own at expiry. Every step lands in the compliance audit log with the full
+//! request attached, so the chain can be reconstructed from the log alone.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::compliance::UserRole;
+
+/// Limits on just-in-time elevation requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ElevationSettings {
+    /// Longest grant an analyst may ask for
+    pub max_minutes: u32,
+    pub min_justification_chars: usize,
+    /// Let holders of ManageRoles approve their own requests
+    pub allow_self_approval: bool,
+    /// How often lapsed grants are closed out in the audit log
+    pub sweep_interval_seconds: u64,
+}
+
+impl Default for ElevationSettings {
+    fn default() -> Self {
+        Self { max_minutes: 240, min_justification_chars: 20, allow_self_approval: false, sweep_interval_seconds: 60 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ElevationStatus {
+    Pending,
+    Active,
+    Denied,
+    Expired,
+    Revoked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElevationRequest {
+    pub id: String,
+    pub username: String,
+    pub base_role: UserRole,
+    pub requested_role: UserRole,
+    pub justification: String,
+    pub duration_minutes: u32,
+    pub requested_at: DateTime<Utc>,
+    pub status: ElevationStatus,
+    pub approver: Option<String>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub decision_note: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// When the grant was revoked or lapsed, and by whom
+    pub ended_at: Option<DateTime<Utc>>,
+    pub ended_by: Option<String>,
+}
+
+impl ElevationRequest {
+    /// Whether the grant's permissions apply at `now`, whether or not the sweep has run
+    pub fn grants_at(&self, now: DateTime<Utc>) -> bool {
+        self.status == ElevationStatus::Active && self.expires_at.is_some_and(|expires_at| now < expires_at)
+    }
+
+    pub(crate) fn activate(&mut self, approver: &str, note: Option<String>, now: DateTime<Utc>) {
+        self.status = ElevationStatus::Active;
+        self.approver = Some(approver.to_string());
+        self.decided_at = Some(now);
+        self.decision_note = note;
+        self.expires_at = Some(now + Duration::minutes(self.duration_minutes as i64));
+    }
+}
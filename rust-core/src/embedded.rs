@@ -0,0 +1,237 @@
+//! # Embedded Detection Facade
+//!
+//! This module provides `UltraSiem`, a small and stable entry point for
+//! embedding Ultra SIEM detection into other Rust applications.
+//!
+//! ## Features
+//! - Builder-style setup (`UltraSiem::builder()`)
+//! - Feed JSON events directly, no NATS connection required
+//! - Receive detections through callbacks or a broadcast stream
+//! - Detection only: no OS response actions, alerts or network side effects
+//!
+//! ## Usage
+//! ```rust
+//! use siem_rust_core::UltraSiem;
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let siem = UltraSiem::builder()
+//!     .on_detection(|threat| println!("{}: {}", threat.category, threat.description))
+//!     .build()
+//!     .await
+//!     .unwrap();
+//!
+//! let mut detections = siem.subscribe();
+//! let threats = siem
+//!     .process_event(serde_json::json!({ "message": "<script>alert(1)</script>" }))
+//!     .await
+//!     .unwrap();
+//! # let _ = (threats, detections.try_recv());
+//! # });
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use regex::Regex;
+use tokio::sync::broadcast;
+
+use crate::advanced_threat_detection::{AdvancedThreatConfig, AdvancedThreatDetectionEngine, AdvancedThreatResult};
+use crate::clock_skew::ClockSkewSettings;
+use crate::dedup::DedupSettings;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::parsing::{ParsingPipelines, PipelineConfig};
+use crate::threat_detection::SignaturePattern;
+
+type DetectionCallback = Arc<dyn Fn(&AdvancedThreatResult) + Send + Sync>;
+
+/// Builder for the embedded `UltraSiem` facade
+pub struct UltraSiemBuilder {
+    config: AdvancedThreatConfig,
+    signatures: Vec<SignaturePattern>,
+    whitelist: Vec<String>,
+    callbacks: Vec<DetectionCallback>,
+    stream_capacity: usize,
+    dedup: DedupSettings,
+    clock_skew: ClockSkewSettings,
+    pipelines: Vec<PipelineConfig>,
+}
+
+impl Default for UltraSiemBuilder {
+    fn default() -> Self {
+        Self {
+            config: AdvancedThreatConfig::default(),
+            signatures: Vec::new(),
+            whitelist: Vec::new(),
+            callbacks: Vec::new(),
+            stream_capacity: 1024,
+            dedup: DedupSettings::default(),
+            clock_skew: ClockSkewSettings::default(),
+            pipelines: Vec::new(),
+        }
+    }
+}
+
+impl UltraSiemBuilder {
+    /// Replace the detection configuration
+    pub fn config(mut self, config: AdvancedThreatConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Add a custom signature on top of the built-in set
+    pub fn signature(mut self, signature: SignaturePattern) -> Self {
+        self.signatures.push(signature);
+        self
+    }
+
+    /// Never report events containing this value (IP, user or substring)
+    pub fn whitelist(mut self, item: impl Into<String>) -> Self {
+        self.whitelist.push(item.into());
+        self
+    }
+
+    /// Invoke `callback` synchronously for every detection
+    pub fn on_detection<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&AdvancedThreatResult) + Send + Sync + 'static,
+    {
+        self.callbacks.push(Arc::new(callback));
+        self
+    }
+
+    /// Number of detections buffered per `subscribe()` receiver
+    pub fn stream_capacity(mut self, capacity: usize) -> Self {
+        self.stream_capacity = capacity.max(1);
+        self
+    }
+
+    /// De-duplicate repeated events before detection
+    pub fn dedup(mut self, settings: DedupSettings) -> Self {
+        self.dedup = settings;
+        self
+    }
+
+    /// Normalize event timestamps and correct per-source clock skew
+    pub fn clock_skew(mut self, settings: ClockSkewSettings) -> Self {
+        self.clock_skew = settings;
+        self
+    }
+
+    /// Parse events from one source with a chain of processors
+    pub fn pipeline(mut self, pipeline: PipelineConfig) -> Self {
+        self.pipelines.push(pipeline);
+        self
+    }
+
+    /// Validate custom signatures and pipelines and start the detection engine
+    pub async fn build(self) -> SIEMResult<UltraSiem> {
+        let mut engine = AdvancedThreatDetectionEngine::new(self.config);
+        engine.start().await?;
+        engine.enable_dedup(self.dedup);
+        engine.enable_timestamp_normalization(self.clock_skew);
+        engine.set_parsing_pipelines(ParsingPipelines::from_config(&self.pipelines)?);
+
+        for signature in self.signatures {
+            Regex::new(&signature.pattern).map_err(|e| {
+                SIEMError::Config(format!("Invalid signature '{}': {}", signature.id, e))
+            })?;
+            engine.add_signature(signature)?;
+        }
+        for item in self.whitelist {
+            engine.add_to_whitelist(item)?;
+        }
+
+        let (detections, _) = broadcast::channel(self.stream_capacity);
+        Ok(UltraSiem {
+            engine,
+            callbacks: self.callbacks,
+            detections,
+        })
+    }
+}
+
+/// Embedded, detection-only Ultra SIEM instance
+pub struct UltraSiem {
+    engine: AdvancedThreatDetectionEngine,
+    callbacks: Vec<DetectionCallback>,
+    detections: broadcast::Sender<AdvancedThreatResult>,
+}
+
+impl UltraSiem {
+    /// Start configuring an embedded instance
+    pub fn builder() -> UltraSiemBuilder {
+        UltraSiemBuilder::default()
+    }
+
+    /// Run detection on a single event and fan the results out to subscribers
+    pub async fn process_event(&self, event: serde_json::Value) -> SIEMResult<Vec<AdvancedThreatResult>> {
+        let threats = self.engine.process_event(event).await?;
+        for threat in &threats {
+            for callback in &self.callbacks {
+                callback(threat);
+            }
+            // No receivers is not an error for an embedded caller
+            let _ = self.detections.send(threat.clone());
+        }
+        Ok(threats)
+    }
+
+    /// Receive every future detection as a stream
+    pub fn subscribe(&self) -> broadcast::Receiver<AdvancedThreatResult> {
+        self.detections.subscribe()
+    }
+
+    /// Processing metrics of the underlying detection engine
+    pub fn metrics(&self) -> HashMap<String, f64> {
+        self.engine.get_performance_metrics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::threat_detection::{ThreatCategory, ThreatSeverity};
+
+    #[tokio::test]
+    async fn test_callback_and_stream_receive_detections() {
+        let seen = Arc::new(AtomicUsize::new(0));
+        let counter = seen.clone();
+        let siem = UltraSiem::builder()
+            .on_detection(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .build()
+            .await
+            .unwrap();
+        let mut stream = siem.subscribe();
+
+        let threats = siem
+            .process_event(serde_json::json!({ "message": "<script>alert('xss')</script>", "source_ip": "10.0.0.5" }))
+            .await
+            .unwrap();
+
+        assert!(!threats.is_empty());
+        assert_eq!(seen.load(Ordering::SeqCst), threats.len());
+        assert_eq!(stream.try_recv().unwrap().threat_id, threats[0].threat_id);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_custom_signature_is_rejected() {
+        let result = UltraSiem::builder()
+            .signature(SignaturePattern {
+                id: "broken".to_string(),
+                name: "Broken".to_string(),
+                pattern: "(unclosed".to_string(),
+                category: ThreatCategory::Other,
+                severity: ThreatSeverity::Low,
+                description: String::new(),
+                enabled: true,
+                confidence: 0.9,
+                attack_techniques: Vec::new(),
+            })
+            .build()
+            .await;
+
+        assert!(matches!(result, Err(SIEMError::Config(_))));
+    }
+}
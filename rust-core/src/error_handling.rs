@@ -12,7 +12,7 @@
 //! 
 //! ## Usage
 //! ```rust
-//! use siem_rust_core::error_handling::{SIEMResult, SIEMError};
+//! use siem_rust_core::{SIEMResult, SIEMError};
 //! 
 //! fn process_data() -> SIEMResult<String> {
 //!     // Your processing logic here
@@ -21,7 +21,6 @@
 //! ```
 
 use std::time::SystemTimeError;
-use serde_json;
 use std::io;
 use async_nats::SubscribeError;
 use async_nats::PublishError;
@@ -104,6 +103,7 @@ impl From<String> for SIEMError {
     }
 }
 
+#[cfg(any(feature = "response", feature = "compliance"))]
 impl From<reqwest::Error> for SIEMError {
     /// Convert a reqwest HTTP error to SIEMError::Other
     fn from(e: reqwest::Error) -> Self {
@@ -111,6 +111,14 @@ impl From<reqwest::Error> for SIEMError {
     }
 }
 
+#[cfg(feature = "compliance")]
+impl From<bcrypt::BcryptError> for SIEMError {
+    /// Convert a bcrypt hashing error to SIEMError::Auth
+    fn from(e: bcrypt::BcryptError) -> Self {
+        SIEMError::Auth(e.to_string())
+    }
+}
+
 /// Time utility functions with error handling
 pub mod time {
     use super::*;
@@ -122,46 +130,12 @@ pub mod time {
     /// 
     /// # Returns
     /// - `SIEMResult<u64>`: Current timestamp in seconds, or error
-    /// 
-    /// # Examples
-    /// ```rust
-    /// use siem_rust_core::error_handling::time;
-    /// 
-    /// match time::current_timestamp() {
-    ///     Ok(timestamp) => println!("Current time: {}", timestamp),
-    ///     Err(e) => eprintln!("Time error: {}", e),
-    /// }
-    /// ```
     pub fn current_timestamp() -> SIEMResult<u64> {
         Ok(std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .map_err(SIEMError::SystemTime)?
             .as_secs())
     }
-    
-    /// Get current Unix timestamp in milliseconds
-    /// 
-    /// Returns the number of milliseconds since Unix epoch (January 1, 1970).
-    /// This provides higher precision than `current_timestamp()`.
-    /// 
-    /// # Returns
-    /// - `SIEMResult<u128>`: Current timestamp in milliseconds, or error
-    /// 
-    /// # Examples
-    /// ```rust
-    /// use siem_rust_core::error_handling::time;
-    /// 
-    /// match time::current_timestamp_millis() {
-    ///     Ok(timestamp) => println!("Current time (ms): {}", timestamp),
-    ///     Err(e) => eprintln!("Time error: {}", e),
-    /// }
-    /// ```
-    pub fn current_timestamp_millis() -> SIEMResult<u128> {
-        Ok(std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map_err(SIEMError::SystemTime)?
-            .as_millis())
-    }
 } 
 
 #[cfg(test)]
@@ -181,8 +155,6 @@ mod tests {
     fn test_time_utilities() {
         let ts = time::current_timestamp();
         assert!(ts.is_ok());
-        let ts_ms = time::current_timestamp_millis();
-        assert!(ts_ms.is_ok());
     }
 
     #[test]
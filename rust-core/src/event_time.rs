@@ -0,0 +1,313 @@
+//! # Event-Time Correlation
+//!
+//! Collectors batch and lag, so events reach correlation out of order. With
+//! event time enabled correlation windows are cut by event timestamp instead of
+//! arrival order, and a watermark trailing the newest event time by
+//! `watermark_delay_seconds` decides when a window is complete. Events older
+//! than the watermark are late: they are still correlated, set aside for
+//! inspection, or dropped, per `late_events`, and counted either way.
+//!
+//! Each source keeps its own watermark, so one source with a fast clock cannot
+//! make every other source's events late. Events stamped more than
+//! `max_skew_seconds` past their receive time are rejected without moving any
+//! watermark, and millisecond timestamps are read as seconds.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use chrono::{DateTime, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::CorrelationEvent;
+use crate::clock_skew::epoch_seconds;
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// What correlation does with an event behind the watermark
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LateEventPolicy {
+    /// Correlate it into its window anyway
+    Include,
+    /// Keep it out of correlation but retain it for `late_events`
+    SideOutput,
+    /// Discard it
+    Drop,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventTimeSettings {
+    pub enabled: bool,
+    /// How far the watermark trails the newest event time
+    pub watermark_delay_seconds: u64,
+    pub late_events: LateEventPolicy,
+    /// Late events kept for inspection with the side-output policy
+    pub side_output_capacity: usize,
+    /// Events further than this past their receive time are rejected as future-dated
+    pub max_skew_seconds: u64,
+    /// Sources with their own watermark; the one furthest behind is evicted beyond this
+    pub max_tracked_sources: usize,
+}
+
+impl Default for EventTimeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            watermark_delay_seconds: 30,
+            late_events: LateEventPolicy::SideOutput,
+            side_output_capacity: 1000,
+            max_skew_seconds: 300,
+            max_tracked_sources: 10_000,
+        }
+    }
+}
+
+impl EventTimeSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if self.late_events == LateEventPolicy::SideOutput && self.side_output_capacity == 0 {
+            return Err(SIEMError::Validation(
+                "event_time.side_output_capacity must be positive with late_events = \"side_output\"".to_string(),
+            ));
+        }
+        if self.max_tracked_sources == 0 {
+            return Err(SIEMError::Validation("event_time.max_tracked_sources must be positive".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Whether an event takes part in correlation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    OnTime,
+    /// Behind the watermark by this many seconds but included by policy
+    LateIncluded(u64),
+    /// Behind the watermark and kept out of correlation
+    Rejected,
+}
+
+/// Late event set aside by the side-output policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LateEvent {
+    pub received_at: DateTime<Utc>,
+    /// Seconds the event was behind the watermark
+    pub lateness_seconds: u64,
+    pub watermark: u64,
+    pub event: CorrelationEvent,
+}
+
+/// Per-source watermarks over event timestamps and the late events they turned away
+#[derive(Debug)]
+pub struct EventTimeClock {
+    settings: EventTimeSettings,
+    /// Newest plausible event time per source
+    max_event_time: Mutex<HashMap<String, u64>>,
+    on_time: AtomicU64,
+    future_rejected: AtomicU64,
+    late_included: AtomicU64,
+    late_side_output: AtomicU64,
+    late_dropped: AtomicU64,
+    side_output: Mutex<VecDeque<LateEvent>>,
+}
+
+impl EventTimeClock {
+    pub fn new(settings: EventTimeSettings) -> Self {
+        Self {
+            settings,
+            max_event_time: Mutex::new(HashMap::new()),
+            on_time: AtomicU64::new(0),
+            future_rejected: AtomicU64::new(0),
+            late_included: AtomicU64::new(0),
+            late_side_output: AtomicU64::new(0),
+            late_dropped: AtomicU64::new(0),
+            side_output: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Event time below which windows are complete: the most advanced source watermark, 0 before any event
+    pub fn watermark(&self) -> u64 {
+        let newest = self.max_event_time.lock().unwrap().values().copied().max().unwrap_or(0);
+        newest.saturating_sub(self.settings.watermark_delay_seconds)
+    }
+
+    /// Watermark of a single source; 0 before its first event
+    #[cfg(test)]
+    pub fn source_watermark(&self, source: &str) -> u64 {
+        let newest = self.max_event_time.lock().unwrap().get(source).copied().unwrap_or(0);
+        newest.saturating_sub(self.settings.watermark_delay_seconds)
+    }
+
+    /// Advance the source's watermark with `event` and decide whether it is correlated
+    pub fn admit(&self, event: &CorrelationEvent) -> Admission {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        self.admit_at(event, now)
+    }
+
+    /// As [`admit`](Self::admit) for an event received at `received_at` (unix seconds)
+    pub fn admit_at(&self, event: &CorrelationEvent, received_at: u64) -> Admission {
+        let timestamp = epoch_seconds(event.timestamp);
+        if timestamp > received_at.saturating_add(self.settings.max_skew_seconds) {
+            self.future_rejected.fetch_add(1, Ordering::Relaxed);
+            debug!("⏱️ Event {} from {} is {}s in the future", event.id, event.source, timestamp - received_at);
+            return Admission::Rejected;
+        }
+
+        let watermark = {
+            let mut max_event_time = self.max_event_time.lock().unwrap();
+            if !max_event_time.contains_key(&event.source) && max_event_time.len() >= self.settings.max_tracked_sources {
+                let stalest = max_event_time.iter().min_by_key(|(_, time)| **time).map(|(source, _)| source.clone());
+                if let Some(source) = stalest {
+                    max_event_time.remove(&source);
+                }
+            }
+            let newest = max_event_time.entry(event.source.clone()).or_insert(0);
+            let watermark = newest.saturating_sub(self.settings.watermark_delay_seconds);
+            *newest = (*newest).max(timestamp);
+            watermark
+        };
+        if timestamp >= watermark {
+            self.on_time.fetch_add(1, Ordering::Relaxed);
+            return Admission::OnTime;
+        }
+        let lateness = watermark - timestamp;
+        debug!("⏱️ Event {} is {}s behind the watermark", event.id, lateness);
+        match self.settings.late_events {
+            LateEventPolicy::Include => {
+                self.late_included.fetch_add(1, Ordering::Relaxed);
+                Admission::LateIncluded(lateness)
+            }
+            LateEventPolicy::SideOutput => {
+                self.late_side_output.fetch_add(1, Ordering::Relaxed);
+                let mut side_output = self.side_output.lock().unwrap();
+                side_output.push_front(LateEvent {
+                    received_at: Utc::now(),
+                    lateness_seconds: lateness,
+                    watermark,
+                    event: event.clone(),
+                });
+                side_output.truncate(self.settings.side_output_capacity);
+                Admission::Rejected
+            }
+            LateEventPolicy::Drop => {
+                self.late_dropped.fetch_add(1, Ordering::Relaxed);
+                Admission::Rejected
+            }
+        }
+    }
+
+    /// Up to `limit` of the latest side-output events, newest first
+    pub fn late_events(&self, limit: usize) -> Vec<LateEvent> {
+        self.side_output.lock().unwrap().iter().take(limit).cloned().collect()
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        HashMap::from([
+            ("event_time_watermark".to_string(), self.watermark() as f64),
+            ("event_time_on_time_events".to_string(), self.on_time.load(Ordering::Relaxed) as f64),
+            ("event_time_future_rejected".to_string(), self.future_rejected.load(Ordering::Relaxed) as f64),
+            ("event_time_late_included".to_string(), self.late_included.load(Ordering::Relaxed) as f64),
+            ("event_time_late_side_output".to_string(), self.late_side_output.load(Ordering::Relaxed) as f64),
+            ("event_time_late_dropped".to_string(), self.late_dropped.load(Ordering::Relaxed) as f64),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::threat_detection::ThreatSeverity;
+
+    fn event(timestamp: u64) -> CorrelationEvent {
+        event_from("203.0.113.9", timestamp)
+    }
+
+    fn event_from(source: &str, timestamp: u64) -> CorrelationEvent {
+        CorrelationEvent {
+            id: format!("e{}", timestamp),
+            timestamp,
+            event_type: "auth_failure".to_string(),
+            source: source.to_string(),
+            target: String::new(),
+            severity: ThreatSeverity::Low,
+            confidence: 0.5,
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn clock(late_events: LateEventPolicy) -> EventTimeClock {
+        EventTimeClock::new(EventTimeSettings {
+            enabled: true,
+            watermark_delay_seconds: 10,
+            late_events,
+            side_output_capacity: 2,
+            ..EventTimeSettings::default()
+        })
+    }
+
+    #[test]
+    fn test_watermark_trails_newest_event_time() {
+        let clock = clock(LateEventPolicy::Include);
+        assert_eq!(clock.admit(&event(1_000)), Admission::OnTime);
+        // Out of order but within the delay
+        assert_eq!(clock.admit(&event(995)), Admission::OnTime);
+        assert_eq!(clock.admit(&event(1_030)), Admission::OnTime);
+        assert_eq!(clock.watermark(), 1_020);
+        assert_eq!(clock.admit(&event(1_005)), Admission::LateIncluded(15));
+        assert_eq!(clock.get_metrics()["event_time_late_included"], 1.0);
+        assert_eq!(clock.watermark(), 1_020);
+    }
+
+    #[test]
+    fn test_side_output_keeps_latest_and_drop_only_counts() {
+        let side = clock(LateEventPolicy::SideOutput);
+        side.admit(&event(2_000));
+        for timestamp in [1_900, 1_950, 1_980] {
+            assert_eq!(side.admit(&event(timestamp)), Admission::Rejected);
+        }
+        let late = side.late_events(10);
+        assert_eq!(late.len(), 2);
+        assert_eq!(late[0].event.timestamp, 1_980);
+        assert_eq!(late[0].lateness_seconds, 10);
+        assert_eq!(side.get_metrics()["event_time_late_side_output"], 3.0);
+
+        let drop = clock(LateEventPolicy::Drop);
+        drop.admit(&event(2_000));
+        assert_eq!(drop.admit(&event(1_000)), Admission::Rejected);
+        assert!(drop.late_events(10).is_empty());
+        assert_eq!(drop.get_metrics()["event_time_late_dropped"], 1.0);
+        assert!(EventTimeSettings { side_output_capacity: 0, ..EventTimeSettings::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_future_dated_event_does_not_make_later_events_late() {
+        let clock = clock(LateEventPolicy::SideOutput);
+        let now = 1_700_000_000;
+        assert_eq!(clock.admit_at(&event(now - 5), now), Admission::OnTime);
+        // A day ahead of its receive time: turned away without touching the watermark
+        assert_eq!(clock.admit_at(&event(now + 86_400), now), Admission::Rejected);
+        assert_eq!(clock.source_watermark("203.0.113.9"), now - 15);
+        assert_eq!(clock.admit_at(&event(now - 1), now + 1), Admission::OnTime);
+        assert_eq!(clock.admit_at(&event(now + 2), now + 2), Admission::OnTime);
+        assert!(clock.late_events(10).is_empty());
+        assert_eq!(clock.get_metrics()["event_time_future_rejected"], 1.0);
+
+        // Millisecond timestamps are read as seconds
+        assert_eq!(clock.admit_at(&event((now + 3) * 1000), now + 3), Admission::OnTime);
+        assert_eq!(clock.source_watermark("203.0.113.9"), now - 7);
+    }
+
+    #[test]
+    fn test_watermarks_are_kept_per_source() {
+        let clock = clock(LateEventPolicy::Drop);
+        let now = 1_700_000_000;
+        // Within the skew bound but well ahead of the other source
+        assert_eq!(clock.admit_at(&event_from("fast", now + 200), now), Admission::OnTime);
+        assert_eq!(clock.admit_at(&event_from("slow", now - 5), now), Admission::OnTime);
+        assert_eq!(clock.admit_at(&event_from("slow", now - 12), now), Admission::OnTime);
+        assert_eq!(clock.admit_at(&event_from("fast", now + 100), now), Admission::Rejected);
+        assert_eq!(clock.watermark(), now + 190);
+        assert_eq!(clock.source_watermark("slow"), now - 15);
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::{IncidentResponseEngine, TimelineEntry};
+
+/// Evidence attachment storage settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvidenceSettings {
+    pub storage_dir: String,
+    pub max_attachment_bytes: u64,
+    pub max_incident_bytes: u64,
+    pub retention_days: i64,
+}
+
+impl Default for EvidenceSettings {
+    fn default() -> Self {
+        Self {
+            storage_dir: "data/evidence".to_string(),
+            max_attachment_bytes: 25 * 1024 * 1024,
+            max_incident_bytes: 250 * 1024 * 1024,
+            retention_days: 90,
+        }
+    }
+}
+
+/// Kind of artifact attached to an incident
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EvidenceKind {
+    PcapSnippet,
+    Screenshot,
+    QuarantinedFile,
+    QueryResult,
+    /// State captured from a host, e.g. its process list or firewall rules
+    HostSnapshot,
+    Other,
+}
+
+/// Metadata of a stored attachment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub incident_id: String,
+    pub kind: EvidenceKind,
+    pub filename: String,
+    pub content_type: String,
+    pub size_bytes: u64,
+    /// SHA-256 of the stored content; `None` for reference-only attachments
+    pub sha256: Option<String>,
+    /// External location (e.g. quarantine path) for reference-only attachments
+    pub reference: Option<String>,
+    pub uploaded_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Upload request for a new attachment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewAttachment {
+    pub incident_id: String,
+    pub kind: EvidenceKind,
+    pub filename: String,
+    pub content_type: String,
+    pub uploaded_by: String,
+}
+
+/// Content-addressed attachment store with size limits and retention
+#[derive(Debug)]
+pub struct EvidenceStore {
+    settings: EvidenceSettings,
+    root: PathBuf,
+    attachments: RwLock<HashMap<String, Attachment>>,
+}
+
+impl EvidenceStore {
+    /// Open (or create) the store and load existing attachment metadata
+    pub fn open(settings: EvidenceSettings) -> SIEMResult<Self> {
+        let root = PathBuf::from(&settings.storage_dir);
+        std::fs::create_dir_all(root.join("objects"))?;
+        std::fs::create_dir_all(root.join("attachments"))?;
+
+        let mut attachments = HashMap::new();
+        for entry in std::fs::read_dir(root.join("attachments"))? {
+            let path = entry?.path();
+            match std::fs::read(&path).map_err(SIEMError::from).and_then(|raw| Ok(serde_json::from_slice::<Attachment>(&raw)?)) {
+                Ok(attachment) => {
+                    attachments.insert(attachment.id.clone(), attachment);
+                }
+                Err(e) => warn!("⚠️ Ignoring unreadable evidence metadata {}: {}", path.display(), e),
+            }
+        }
+        info!("🗂️ Evidence store opened with {} attachment(s)", attachments.len());
+
+        Ok(Self {
+            settings,
+            root,
+            attachments: RwLock::new(attachments),
+        })
+    }
+
+    /// Store `content` for an existing incident and reference it from the incident timeline
+    pub fn attach(&self, incidents: &IncidentResponseEngine, upload: NewAttachment, content: &[u8]) -> SIEMResult<Attachment> {
+        if incidents.get_incident(&upload.incident_id).is_none() {
+            return Err(SIEMError::Validation(format!("Incident {} not found", upload.incident_id)));
+        }
+        let size = content.len() as u64;
+        if size > self.settings.max_attachment_bytes {
+            return Err(SIEMError::Validation(format!(
+                "Attachment of {} bytes exceeds limit of {} bytes",
+                size, self.settings.max_attachment_bytes
+            )));
+        }
+        let used = self.incident_bytes(&upload.incident_id);
+        if used + size > self.settings.max_incident_bytes {
+            return Err(SIEMError::Validation(format!(
+                "Incident {} evidence quota of {} bytes exceeded",
+                upload.incident_id, self.settings.max_incident_bytes
+            )));
+        }
+
+        let sha256 = format!("{:x}", Sha256::digest(content));
+        let object = self.object_path(&sha256);
+        if !object.exists() {
+            if let Some(parent) = object.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&object, content)?;
+        }
+
+        let attachment = self.new_attachment(upload, size, Some(sha256), None);
+        self.record(incidents, attachment)
+    }
+
+    /// Attach an artifact kept elsewhere (e.g. a quarantined file) by reference only
+    pub fn attach_reference(
+        &self,
+        incidents: &IncidentResponseEngine,
+        upload: NewAttachment,
+        reference: String,
+        sha256: Option<String>,
+    ) -> SIEMResult<Attachment> {
+        if incidents.get_incident(&upload.incident_id).is_none() {
+            return Err(SIEMError::Validation(format!("Incident {} not found", upload.incident_id)));
+        }
+        let attachment = self.new_attachment(upload, 0, sha256, Some(reference));
+        self.record(incidents, attachment)
+    }
+
+    fn new_attachment(&self, upload: NewAttachment, size_bytes: u64, sha256: Option<String>, reference: Option<String>) -> Attachment {
+        let now = Utc::now();
+        Attachment {
+            id: Uuid::new_v4().to_string(),
+            incident_id: upload.incident_id,
+            kind: upload.kind,
+            filename: upload.filename,
+            content_type: upload.content_type,
+            size_bytes,
+            sha256,
+            reference,
+            uploaded_by: upload.uploaded_by,
+            created_at: now,
+            expires_at: now + Duration::days(self.settings.retention_days),
+        }
+    }
+
+    fn record(&self, incidents: &IncidentResponseEngine, attachment: Attachment) -> SIEMResult<Attachment> {
+        std::fs::write(self.metadata_path(&attachment.id), serde_json::to_vec_pretty(&attachment)?)?;
+        self.attachments.write().unwrap().insert(attachment.id.clone(), attachment.clone());
+        incidents.add_timeline_entry(
+            &attachment.incident_id,
+            TimelineEntry::new(
+                "evidence_attached",
+                format!("{:?} '{}' attached by {}", attachment.kind, attachment.filename, attachment.uploaded_by),
+            )
+            .with_reference(attachment.id.clone()),
+        )?;
+        Ok(attachment)
+    }
+
+    pub fn get(&self, attachment_id: &str) -> Option<Attachment> {
+        self.attachments.read().unwrap().get(attachment_id).cloned()
+    }
+
+    /// Attachments of an incident, oldest first
+    pub fn list(&self, incident_id: &str) -> Vec<Attachment> {
+        let mut attachments: Vec<Attachment> = self
+            .attachments
+            .read()
+            .unwrap()
+            .values()
+            .filter(|attachment| attachment.incident_id == incident_id)
+            .cloned()
+            .collect();
+        attachments.sort_by_key(|attachment| attachment.created_at);
+        attachments
+    }
+
+    /// Read stored content, verifying it still matches its hash
+    pub fn read_content(&self, attachment_id: &str) -> SIEMResult<Vec<u8>> {
+        let attachment = self
+            .get(attachment_id)
+            .ok_or_else(|| SIEMError::Validation(format!("Attachment {} not found", attachment_id)))?;
+        let Some(sha256) = attachment.sha256.filter(|_| attachment.reference.is_none()) else {
+            return Err(SIEMError::Validation(format!("Attachment {} is a reference without stored content", attachment_id)));
+        };
+        let content = std::fs::read(self.object_path(&sha256))?;
+        if format!("{:x}", Sha256::digest(&content)) != sha256 {
+            return Err(SIEMError::Validation(format!("Attachment {} content failed integrity check", attachment_id)));
+        }
+        Ok(content)
+    }
+
+    /// Delete attachments past their retention and note the removal on each incident
+    pub fn enforce_retention(&self, incidents: &IncidentResponseEngine, now: DateTime<Utc>) -> SIEMResult<Vec<Attachment>> {
+        let expired: Vec<Attachment> = {
+            let mut attachments = self.attachments.write().unwrap();
+            let ids: Vec<String> = attachments
+                .values()
+                .filter(|attachment| attachment.expires_at <= now)
+                .map(|attachment| attachment.id.clone())
+                .collect();
+            ids.iter().filter_map(|id| attachments.remove(id)).collect()
+        };
+
+        for attachment in &expired {
+            let _ = std::fs::remove_file(self.metadata_path(&attachment.id));
+            if let Some(sha256) = attachment.sha256.as_ref().filter(|_| attachment.reference.is_none()) {
+                // Objects are shared between identical uploads
+                let still_used = self.attachments.read().unwrap().values().any(|a| a.sha256.as_ref() == Some(sha256));
+                if !still_used {
+                    let _ = std::fs::remove_file(self.object_path(sha256));
+                }
+            }
+            let entry = TimelineEntry::new("evidence_expired", format!("'{}' removed by retention policy", attachment.filename))
+                .with_reference(attachment.id.clone());
+            if let Err(e) = incidents.add_timeline_entry(&attachment.incident_id, entry) {
+                warn!("⚠️ Could not record evidence expiry on incident {}: {}", attachment.incident_id, e);
+            }
+        }
+        Ok(expired)
+    }
+
+    fn incident_bytes(&self, incident_id: &str) -> u64 {
+        self.attachments
+            .read()
+            .unwrap()
+            .values()
+            .filter(|attachment| attachment.incident_id == incident_id)
+            .map(|attachment| attachment.size_bytes)
+            .sum()
+    }
+
+    fn object_path(&self, sha256: &str) -> PathBuf {
+        self.root.join("objects").join(&sha256[..2]).join(sha256)
+    }
+
+    fn metadata_path(&self, attachment_id: &str) -> PathBuf {
+        self.root.join("attachments").join(format!("{}.json", attachment_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::incident_response::{AlertConfig, SOARConfig};
+
+    async fn engine_with_incident() -> (IncidentResponseEngine, String) {
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let incident = engine.process_threat(AdvancedThreatResult::default()).await.unwrap();
+        (engine, incident.id)
+    }
+
+    fn upload(incident_id: &str) -> NewAttachment {
+        NewAttachment {
+            incident_id: incident_id.to_string(),
+            kind: EvidenceKind::PcapSnippet,
+            filename: "capture.pcap".to_string(),
+            content_type: "application/vnd.tcpdump.pcap".to_string(),
+            uploaded_by: "analyst".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_attach_hash_and_timeline() {
+        let (incidents, incident_id) = engine_with_incident().await;
+        let dir = std::env::temp_dir().join(format!("evidence-{}", Uuid::new_v4()));
+        let store = EvidenceStore::open(EvidenceSettings {
+            storage_dir: dir.to_string_lossy().to_string(),
+            max_attachment_bytes: 16,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let attachment = store.attach(&incidents, upload(&incident_id), b"pcap-bytes").unwrap();
+        assert_eq!(attachment.sha256.as_deref(), Some(format!("{:x}", Sha256::digest(b"pcap-bytes")).as_str()));
+        assert_eq!(store.read_content(&attachment.id).unwrap(), b"pcap-bytes");
+        assert!(store.attach(&incidents, upload(&incident_id), &[0u8; 32]).is_err());
+
+        let timeline = incidents.get_incident(&incident_id).unwrap().timeline;
+        assert_eq!(timeline.last().unwrap().event, "evidence_attached");
+        assert_eq!(timeline.last().unwrap().reference.as_deref(), Some(attachment.id.as_str()));
+
+        // Metadata survives a reopen
+        let reopened = EvidenceStore::open(EvidenceSettings {
+            storage_dir: dir.to_string_lossy().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(reopened.list(&incident_id).len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_retention_removes_expired_evidence() {
+        let (incidents, incident_id) = engine_with_incident().await;
+        let dir = std::env::temp_dir().join(format!("evidence-{}", Uuid::new_v4()));
+        let store = EvidenceStore::open(EvidenceSettings {
+            storage_dir: dir.to_string_lossy().to_string(),
+            retention_days: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        let attachment = store.attach(&incidents, upload(&incident_id), b"old").unwrap();
+
+        let removed = store.enforce_retention(&incidents, Utc::now() + Duration::days(2)).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert!(store.get(&attachment.id).is_none());
+        assert!(store.read_content(&attachment.id).is_err());
+        let timeline = incidents.get_incident(&incident_id).unwrap().timeline;
+        assert_eq!(timeline.last().unwrap().event, "evidence_expired");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
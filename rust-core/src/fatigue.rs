@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::incident_response::{Incident, IncidentStatus};
+use crate::timezone::Zone;
+
+/// Timeline events that count as an analyst acknowledging an incident
+const ACK_EVENTS: &[&str] = &["assigned", "status_changed", "note_added", "false_positive"];
+
+/// Alarm fatigue analytics settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FatigueSettings {
+    /// Rolling window the handling stats are computed over
+    pub window_hours: u64,
+    /// Flag a rising backlog when unacked incidents grow by more than this fraction per window
+    pub backlog_growth_threshold: f64,
+    /// Flag rules whose false positive rate exceeds this
+    pub high_fp_rate: f64,
+    /// Rules with fewer alerts in the window are never flagged
+    pub min_rule_alerts: u64,
+    /// How often the digest is produced; 0 disables it
+    pub digest_interval_hours: u64,
+    /// Directory the digest is written to; empty only logs it
+    pub digest_dir: String,
+}
+
+impl Default for FatigueSettings {
+    fn default() -> Self {
+        Self {
+            window_hours: 24 * 7,
+            backlog_growth_threshold: 0.25,
+            high_fp_rate: 0.5,
+            min_rule_alerts: 5,
+            digest_interval_hours: 24 * 7,
+            digest_dir: String::new(),
+        }
+    }
+}
+
+/// Alert handling stats for one analyst or rule
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HandlingStats {
+    pub alerts: u64,
+    pub acknowledged: u64,
+    pub mean_time_to_ack_seconds: f64,
+    pub false_positives: u64,
+    pub false_positive_rate: f64,
+    /// Resolved or closed without ever being assigned to an analyst
+    pub auto_closed: u64,
+}
+
+impl HandlingStats {
+    fn record(&mut self, incident: &Incident, ack_seconds: Option<f64>) {
+        self.alerts += 1;
+        if let Some(seconds) = ack_seconds {
+            self.mean_time_to_ack_seconds += (seconds - self.mean_time_to_ack_seconds) / (self.acknowledged + 1) as f64;
+            self.acknowledged += 1;
+        }
+        if incident.false_positive || incident.status == IncidentStatus::FalsePositive {
+            self.false_positives += 1;
+        }
+        if incident.assigned_to.is_none()
+            && matches!(incident.status, IncidentStatus::Resolved | IncidentStatus::Closed)
+        {
+            self.auto_closed += 1;
+        }
+        self.false_positive_rate = self.false_positives as f64 / self.alerts as f64;
+    }
+}
+
+/// A sign that analysts are being overwhelmed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FatigueIndicator {
+    RisingBacklog { previous: u64, current: u64 },
+    HighFalsePositiveRule { rule: String, false_positive_rate: f64, alerts: u64 },
+}
+
+/// Handling stats over one rolling window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FatigueReport {
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub overall: HandlingStats,
+    pub per_analyst: BTreeMap<String, HandlingStats>,
+    pub per_rule: BTreeMap<String, HandlingStats>,
+    /// Unacknowledged incidents at the end of the window and one window earlier
+    pub unacked_backlog: u64,
+    pub previous_unacked_backlog: u64,
+    pub indicators: Vec<FatigueIndicator>,
+}
+
+impl FatigueReport {
+    /// Flat gauges for the metrics endpoint
+    pub fn to_metrics(&self) -> HashMap<String, f64> {
+        let high_fp_rules = self
+            .indicators
+            .iter()
+            .filter(|indicator| matches!(indicator, FatigueIndicator::HighFalsePositiveRule { .. }))
+            .count();
+        let mut metrics = HashMap::new();
+        metrics.insert("fatigue_alerts_in_window".to_string(), self.overall.alerts as f64);
+        metrics.insert("fatigue_mean_time_to_ack_seconds".to_string(), self.overall.mean_time_to_ack_seconds);
+        metrics.insert("fatigue_false_positive_rate".to_string(), self.overall.false_positive_rate);
+        metrics.insert("fatigue_auto_closed".to_string(), self.overall.auto_closed as f64);
+        metrics.insert("fatigue_unacked_backlog".to_string(), self.unacked_backlog as f64);
+        metrics.insert("fatigue_previous_unacked_backlog".to_string(), self.previous_unacked_backlog as f64);
+        metrics.insert("fatigue_high_fp_rules".to_string(), high_fp_rules as f64);
+        metrics.insert("fatigue_indicators".to_string(), self.indicators.len() as f64);
+        metrics
+    }
+
+    /// Plain-text digest for analysts and their leads, dated in `zone`
+    pub fn render_digest(&self, zone: &Zone) -> String {
+        let mut digest = String::new();
+        let _ = writeln!(
+            digest,
+            "Ultra SIEM alert handling digest ({} - {})",
+            zone.to_local(self.window_start).format("%Y-%m-%d"),
+            zone.to_local(self.window_end).format("%Y-%m-%d")
+        );
+        let _ = writeln!(
+            digest,
+            "Alerts: {}  acknowledged: {}  mean time to ack: {:.0}s  FP rate: {:.0}%  auto-closed: {}",
+            self.overall.alerts,
+            self.overall.acknowledged,
+            self.overall.mean_time_to_ack_seconds,
+            self.overall.false_positive_rate * 100.0,
+            self.overall.auto_closed
+        );
+        let _ = writeln!(digest, "Unacknowledged backlog: {} (previous window: {})", self.unacked_backlog, self.previous_unacked_backlog);
+
+        if !self.indicators.is_empty() {
+            let _ = writeln!(digest, "\nFatigue indicators:");
+            for indicator in &self.indicators {
+                let _ = match indicator {
+                    FatigueIndicator::RisingBacklog { previous, current } => {
+                        writeln!(digest, "  - Unacknowledged backlog rose from {} to {}", previous, current)
+                    }
+                    FatigueIndicator::HighFalsePositiveRule { rule, false_positive_rate, alerts } => writeln!(
+                        digest,
+                        "  - Rule '{}' was a false positive in {:.0}% of {} alerts",
+                        rule,
+                        false_positive_rate * 100.0,
+                        alerts
+                    ),
+                };
+            }
+        }
+
+        let _ = writeln!(digest, "\nPer analyst:");
+        for (analyst, stats) in &self.per_analyst {
+            let _ = writeln!(
+                digest,
+                "  {:<24} alerts {:>5}  ack {:>7.0}s  FP {:>3.0}%",
+                analyst,
+                stats.alerts,
+                stats.mean_time_to_ack_seconds,
+                stats.false_positive_rate * 100.0
+            );
+        }
+        let _ = writeln!(digest, "\nPer rule:");
+        for (rule, stats) in &self.per_rule {
+            let _ = writeln!(
+                digest,
+                "  {:<24} alerts {:>5}  FP {:>3.0}%  auto-closed {:>4}",
+                rule,
+                stats.alerts,
+                stats.false_positive_rate * 100.0,
+                stats.auto_closed
+            );
+        }
+        digest
+    }
+}
+
+/// Computes per-analyst and per-rule handling stats from incident history
+#[derive(Debug, Clone, Default)]
+pub struct FatigueAnalyzer {
+    settings: FatigueSettings,
+}
+
+impl FatigueAnalyzer {
+    pub fn new(settings: FatigueSettings) -> Self {
+        Self { settings }
+    }
+
+    pub fn analyze(&self, incidents: &[Incident], now: DateTime<Utc>) -> FatigueReport {
+        let window = Duration::hours(self.settings.window_hours.max(1) as i64);
+        let window_start = now - window;
+
+        let mut overall = HandlingStats::default();
+        let mut per_analyst: BTreeMap<String, HandlingStats> = BTreeMap::new();
+        let mut per_rule: BTreeMap<String, HandlingStats> = BTreeMap::new();
+        for incident in incidents.iter().filter(|i| i.created_at > window_start && i.created_at <= now) {
+            let ack_seconds = acknowledged_at(incident, now)
+                .map(|at| (at - incident.created_at).num_milliseconds().max(0) as f64 / 1000.0);
+            overall.record(incident, ack_seconds);
+            per_rule.entry(rule_key(incident)).or_default().record(incident, ack_seconds);
+            if let Some(analyst) = &incident.assigned_to {
+                per_analyst.entry(analyst.clone()).or_default().record(incident, ack_seconds);
+            }
+        }
+
+        let unacked_backlog = unacked_at(incidents, now);
+        let previous_unacked_backlog = unacked_at(incidents, window_start);
+
+        let mut indicators = Vec::new();
+        let growth_limit = previous_unacked_backlog as f64 * (1.0 + self.settings.backlog_growth_threshold);
+        if unacked_backlog > 0 && unacked_backlog as f64 > growth_limit {
+            indicators.push(FatigueIndicator::RisingBacklog {
+                previous: previous_unacked_backlog,
+                current: unacked_backlog,
+            });
+        }
+        for (rule, stats) in &per_rule {
+            if stats.alerts >= self.settings.min_rule_alerts && stats.false_positive_rate > self.settings.high_fp_rate {
+                indicators.push(FatigueIndicator::HighFalsePositiveRule {
+                    rule: rule.clone(),
+                    false_positive_rate: stats.false_positive_rate,
+                    alerts: stats.alerts,
+                });
+            }
+        }
+
+        FatigueReport {
+            window_start,
+            window_end: now,
+            overall,
+            per_analyst,
+            per_rule,
+            unacked_backlog,
+            previous_unacked_backlog,
+            indicators,
+        }
+    }
+}
+
+/// Rule that raised the incident: its first signature, else the detection method
+fn rule_key(incident: &Incident) -> String {
+    incident
+        .threat_result
+        .signatures
+        .first()
+        .cloned()
+        .unwrap_or_else(|| incident.threat_result.detection_method.clone())
+}
+
+pub(crate) fn acknowledged_at(incident: &Incident, at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    incident
+        .timeline
+        .iter()
+        .filter(|entry| entry.timestamp <= at && ACK_EVENTS.contains(&entry.event.as_str()))
+        .map(|entry| entry.timestamp)
+        .min()
+}
+
+/// Incidents open at `at` that no analyst had touched yet
+fn unacked_at(incidents: &[Incident], at: DateTime<Utc>) -> u64 {
+    incidents
+        .iter()
+        .filter(|incident| incident.created_at <= at && acknowledged_at(incident, at).is_none())
+        .filter(|incident| {
+            let finished = matches!(
+                incident.status,
+                IncidentStatus::Resolved | IncidentStatus::Closed | IncidentStatus::FalsePositive
+            );
+            !(finished && incident.updated_at <= at)
+        })
+        .count() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::incident_response::TimelineEntry;
+
+    fn incident(created_at: DateTime<Utc>, rule: &str) -> Incident {
+        Incident {
+            threat_result: AdvancedThreatResult { signatures: vec![rule.to_string()], ..Default::default() },
+            ..Incident::fixture(created_at)
+        }
+    }
+
+    fn ack(incident: &mut Incident, analyst: &str, at: DateTime<Utc>) {
+        incident.assigned_to = Some(analyst.to_string());
+        let mut entry = TimelineEntry::new("assigned", format!("Assigned to {}", analyst));
+        entry.timestamp = at;
+        incident.timeline.push(entry);
+    }
+
+    #[test]
+    fn test_per_analyst_time_to_ack_and_fp_rate() {
+        let now = Utc::now();
+        let mut first = incident(now - Duration::hours(2), "sql_injection");
+        ack(&mut first, "alice", now - Duration::hours(2) + Duration::seconds(60));
+        let mut second = incident(now - Duration::hours(1), "sql_injection");
+        ack(&mut second, "alice", now - Duration::hours(1) + Duration::seconds(180));
+        second.false_positive = true;
+        second.status = IncidentStatus::FalsePositive;
+
+        let report = FatigueAnalyzer::default().analyze(&[first, second], now);
+        let alice = &report.per_analyst["alice"];
+        assert_eq!(alice.alerts, 2);
+        assert_eq!(alice.acknowledged, 2);
+        assert!((alice.mean_time_to_ack_seconds - 120.0).abs() < 1e-6);
+        assert!((report.per_rule["sql_injection"].false_positive_rate - 0.5).abs() < 1e-9);
+        assert_eq!(report.unacked_backlog, 0);
+    }
+
+    #[test]
+    fn test_flags_rising_backlog_and_noisy_rule() {
+        let now = Utc::now();
+        let settings = FatigueSettings { window_hours: 24, min_rule_alerts: 3, ..Default::default() };
+        let mut incidents: Vec<Incident> = (1..=4).map(|h| incident(now - Duration::hours(h), "port_scan")).collect();
+        for noisy in incidents.iter_mut().take(3) {
+            noisy.false_positive = true;
+            noisy.status = IncidentStatus::Closed;
+        }
+        incidents.push(incident(now - Duration::hours(30), "malware"));
+
+        let report = FatigueAnalyzer::new(settings).analyze(&incidents, now);
+        assert_eq!(report.previous_unacked_backlog, 1);
+        assert_eq!(report.unacked_backlog, 2);
+        assert!(report.indicators.contains(&FatigueIndicator::RisingBacklog { previous: 1, current: 2 }));
+        assert!(report.indicators.iter().any(|i| matches!(i, FatigueIndicator::HighFalsePositiveRule { rule, .. } if rule == "port_scan")));
+        assert_eq!(report.per_rule["port_scan"].auto_closed, 3);
+        assert!(report.render_digest(&Zone::utc()).contains("port_scan"));
+        assert_eq!(report.to_metrics()["fatigue_high_fp_rules"], 1.0);
+    }
+}
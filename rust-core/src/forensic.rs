@@ -0,0 +1,227 @@
+//! # Forensic (Read-Only) Mode
+//!
+//! For analysing historical imports or running inside an investigation lab the
+//! core must ingest and detect as usual while leaving the world untouched. In
+//! forensic mode response actions, outbound alerts and notifications, and writes
+//! to shared data (incident storage, baselines, snapshots, analyst edits) are
+//! held back. Each held-back operation is journaled instead, in memory for
+//! `/api/v1/forensic/journal` and appended as JSON lines to `journal_path`, so
+//! the investigation shows what the core would have done.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ForensicSettings {
+    pub enabled: bool,
+    /// JSON lines journal of held-back operations; empty keeps it in memory only
+    pub journal_path: String,
+    /// Held-back operations kept in memory for the REST API
+    pub max_recent: usize,
+}
+
+impl Default for ForensicSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            journal_path: "data/forensic_journal.jsonl".to_string(),
+            max_recent: 1000,
+        }
+    }
+}
+
+impl ForensicSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if self.max_recent == 0 {
+            return Err(SIEMError::Validation("forensic.max_recent must be positive".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// What kind of side effect was held back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeldBackKind {
+    /// Response action such as a firewall block or an agent command
+    ResponseAction,
+    /// Alert to email, chat, paging or SOAR
+    Alert,
+    /// Data leaving the core: forwarding, output sinks, telemetry
+    Outbound,
+    /// Write to stored incidents, baselines, snapshots or other shared data
+    Mutation,
+}
+
+/// One operation forensic mode did not perform
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeldBackOperation {
+    pub at: DateTime<Utc>,
+    pub kind: HeldBackKind,
+    /// Subsystem that would have acted, e.g. `response_action` or `storage`
+    pub component: String,
+    pub detail: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incident_id: Option<String>,
+}
+
+/// Held-back operations so far, newest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ForensicReport {
+    pub enabled: bool,
+    pub totals: BTreeMap<HeldBackKind, u64>,
+    pub recent: Vec<HeldBackOperation>,
+}
+
+/// Journal of what forensic mode held back; records nothing while disabled
+#[derive(Debug)]
+pub struct ForensicJournal {
+    settings: ForensicSettings,
+    file: Mutex<Option<File>>,
+    recent: Mutex<VecDeque<HeldBackOperation>>,
+    totals: Mutex<BTreeMap<HeldBackKind, u64>>,
+}
+
+impl ForensicJournal {
+    pub fn open(settings: ForensicSettings) -> SIEMResult<Self> {
+        settings.validate()?;
+        let file = match settings.enabled && !settings.journal_path.is_empty() {
+            true => {
+                let path = Path::new(&settings.journal_path);
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(parent)?;
+                }
+                Some(OpenOptions::new().create(true).append(true).open(path)?)
+            }
+            false => None,
+        };
+        if settings.enabled {
+            info!("🔬 Forensic mode: response actions, outbound alerts and data changes are journaled, not performed");
+        }
+        Ok(Self {
+            settings,
+            file: Mutex::new(file),
+            recent: Mutex::new(VecDeque::new()),
+            totals: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.settings.enabled
+    }
+
+    /// Journal an operation that was not performed
+    pub fn record(&self, kind: HeldBackKind, component: &str, detail: impl Into<String>, incident_id: Option<&str>) {
+        if !self.settings.enabled {
+            return;
+        }
+        let operation = HeldBackOperation {
+            at: Utc::now(),
+            kind,
+            component: component.to_string(),
+            detail: detail.into(),
+            incident_id: incident_id.map(str::to_string),
+        };
+        info!("🔬 Held back {}: {}", component, operation.detail);
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            let written = serde_json::to_string(&operation)
+                .map_err(std::io::Error::from)
+                .and_then(|line| writeln!(file, "{}", line));
+            if let Err(e) = written {
+                warn!("⚠️ Forensic journal {} not written: {}", self.settings.journal_path, e);
+            }
+        }
+        *self.totals.lock().unwrap().entry(kind).or_default() += 1;
+        let mut recent = self.recent.lock().unwrap();
+        recent.push_front(operation);
+        recent.truncate(self.settings.max_recent);
+    }
+
+    /// True when forensic mode holds back `component`, after journaling it; for subsystems skipped at startup
+    pub fn holds_back(&self, kind: HeldBackKind, component: &str, detail: &str) -> bool {
+        self.record(kind, component, detail, None);
+        self.settings.enabled
+    }
+
+    /// Totals and up to `limit` of the latest held-back operations
+    pub fn report(&self, limit: usize) -> ForensicReport {
+        ForensicReport {
+            enabled: self.settings.enabled,
+            totals: self.totals.lock().unwrap().clone(),
+            recent: self.recent.lock().unwrap().iter().take(limit).cloned().collect(),
+        }
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        let totals = self.totals.lock().unwrap();
+        let mut metrics = HashMap::from([("forensic_mode".to_string(), self.settings.enabled as u8 as f64)]);
+        for kind in [HeldBackKind::ResponseAction, HeldBackKind::Alert, HeldBackKind::Outbound, HeldBackKind::Mutation] {
+            let name = serde_json::to_value(kind).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+            metrics.insert(format!("forensic_held_back_{}_total", name), totals.get(&kind).copied().unwrap_or(0) as f64);
+        }
+        metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("forensic-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn journal(dir: &Path) -> ForensicJournal {
+        ForensicJournal::open(ForensicSettings {
+            enabled: true,
+            journal_path: dir.join("journal.jsonl").to_string_lossy().into_owned(),
+            max_recent: 2,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_records_newest_first_and_appends_journal() {
+        let dir = temp_dir();
+        let journal = journal(&dir);
+        journal.record(HeldBackKind::ResponseAction, "response_action", "BlockIP 203.0.113.7", Some("inc-1"));
+        journal.record(HeldBackKind::Alert, "alert", "High: malware", Some("inc-1"));
+        journal.record(HeldBackKind::Alert, "alert", "Low: scan", Some("inc-2"));
+
+        let report = journal.report(10);
+        assert_eq!(report.recent.len(), 2);
+        assert_eq!(report.recent[0].detail, "Low: scan");
+        assert_eq!(report.totals[&HeldBackKind::Alert], 2);
+        assert_eq!(report.totals[&HeldBackKind::ResponseAction], 1);
+
+        let lines = std::fs::read_to_string(dir.join("journal.jsonl")).unwrap();
+        assert_eq!(lines.lines().count(), 3);
+        let first: HeldBackOperation = serde_json::from_str(lines.lines().next().unwrap()).unwrap();
+        assert_eq!(first.component, "response_action");
+        assert_eq!(first.incident_id.as_deref(), Some("inc-1"));
+        assert_eq!(journal.get_metrics()["forensic_held_back_alert_total"], 2.0);
+    }
+
+    #[test]
+    fn test_disabled_journal_holds_nothing_back() {
+        let path = temp_dir().join("journal.jsonl");
+        let journal = ForensicJournal::open(ForensicSettings {
+            journal_path: path.to_string_lossy().into_owned(),
+            ..ForensicSettings::default()
+        })
+        .unwrap();
+        assert!(!journal.holds_back(HeldBackKind::Outbound, "forwarding", "site forwarding"));
+        assert!(journal.report(10).recent.is_empty());
+        assert!(!path.exists());
+        assert!(ForensicJournal::open(ForensicSettings { max_recent: 0, ..ForensicSettings::default() }).is_err());
+    }
+}
@@ -0,0 +1,586 @@
+//! # Site-to-Site Forwarding
+//!
+//! Edge cores forward detections and top-N aggregates, never raw events, to a
+//! central core. Transport is either a NATS leaf node (publishing on
+//! `ultra_siem.sites.<site>.forward`, which the leaf connection routes to the
+//! hub) or HTTPS (`POST /api/v1/forward` on the central REST API).
+//!
+//! Every envelope carries its origin site and the path of sites it crossed.
+//! Receivers drop envelopes that already passed through them, that exceed
+//! `max_hops`, or whose id was seen before. Undeliverable envelopes go to a
+//! disk spool and are retried in order once the WAN link recovers.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::aggregation::{TopNAggregator, TopNStats};
+use crate::autoscaling::QueueSample;
+use crate::degradation::DiskSpool;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::IncidentResponseEngine;
+use crate::subjects::{SchemaMode, SubjectPublisher, SUBJECT_SITE_FORWARD};
+
+/// Envelope ids remembered for duplicate suppression
+const SEEN_CAPACITY: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForwardTransportKind {
+    Nats,
+    Https,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ForwardingSettings {
+    /// Name of this site, stamped on everything it forwards
+    pub site_id: String,
+    /// Forward local detections and aggregates upstream
+    pub enabled: bool,
+    pub transport: ForwardTransportKind,
+    /// Local NATS leaf node that routes `ultra_siem.sites.>` to the hub
+    pub nats_url: String,
+    /// Base URL of the central core's REST API
+    pub central_url: String,
+    pub auth_token: String,
+    pub aggregate_interval_seconds: u64,
+    pub flush_interval_seconds: u64,
+    pub batch_size: usize,
+    /// Accept envelopes forwarded by other sites
+    pub accept: bool,
+    /// Envelopes that crossed more sites than this are dropped
+    pub max_hops: usize,
+    pub spool_dir: String,
+    pub max_spool_bytes: u64,
+}
+
+impl Default for ForwardingSettings {
+    fn default() -> Self {
+        Self {
+            site_id: "local".to_string(),
+            enabled: false,
+            transport: ForwardTransportKind::Nats,
+            nats_url: "nats://127.0.0.1:7422".to_string(),
+            central_url: String::new(),
+            auth_token: String::new(),
+            aggregate_interval_seconds: 60,
+            flush_interval_seconds: 30,
+            batch_size: 500,
+            accept: false,
+            max_hops: 4,
+            spool_dir: "data/forward-spool".to_string(),
+            max_spool_bytes: 512 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "payload", rename_all = "snake_case")]
+pub enum ForwardPayload {
+    Detection(Box<AdvancedThreatResult>),
+    Aggregate(TopNStats),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardEnvelope {
+    pub id: String,
+    pub origin_site: String,
+    /// Sites the envelope passed through, origin first
+    pub path: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub payload: ForwardPayload,
+}
+
+/// Delivers a batch of envelopes to the next site
+pub trait ForwardTransport: Send + Sync {
+    fn send<'a>(&'a self, batch: &'a [ForwardEnvelope]) -> BoxFuture<'a, SIEMResult<()>>;
+}
+
+/// Publishes envelopes to a NATS leaf node
+pub struct NatsTransport {
+    publisher: SubjectPublisher,
+    subject: String,
+}
+
+impl NatsTransport {
+    pub fn new(client: async_nats::Client, site_id: &str) -> Self {
+        Self {
+            publisher: SubjectPublisher::new(client, SchemaMode::default()),
+            subject: SUBJECT_SITE_FORWARD.replace('*', site_id),
+        }
+    }
+}
+
+impl ForwardTransport for NatsTransport {
+    fn send<'a>(&'a self, batch: &'a [ForwardEnvelope]) -> BoxFuture<'a, SIEMResult<()>> {
+        Box::pin(async move {
+            for envelope in batch {
+                self.publisher.publish(&self.subject, envelope).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Posts envelopes to the central core's REST API
+pub struct HttpsTransport {
+    client: reqwest::Client,
+    url: String,
+    auth_token: String,
+}
+
+impl HttpsTransport {
+    pub fn new(settings: &ForwardingSettings) -> SIEMResult<Self> {
+        if !settings.central_url.starts_with("https://") {
+            return Err(SIEMError::Config(format!("Forwarding central_url '{}' must use https", settings.central_url)));
+        }
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?,
+            url: format!("{}/api/v1/forward", settings.central_url.trim_end_matches('/')),
+            auth_token: settings.auth_token.clone(),
+        })
+    }
+}
+
+impl ForwardTransport for HttpsTransport {
+    fn send<'a>(&'a self, batch: &'a [ForwardEnvelope]) -> BoxFuture<'a, SIEMResult<()>> {
+        Box::pin(async move {
+            let mut request = self.client.post(&self.url).json(batch);
+            if !self.auth_token.is_empty() {
+                request = request.bearer_auth(&self.auth_token);
+            }
+            request.send().await?.error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+/// Forwards local detections and aggregates upstream, spooling during outages
+pub struct SiteForwarder {
+    settings: ForwardingSettings,
+    transport: Arc<dyn ForwardTransport>,
+    spool: DiskSpool<ForwardEnvelope>,
+    /// Serializes sends so spooled envelopes always go out before newer ones
+    sending: tokio::sync::Mutex<()>,
+    forwarded: AtomicU64,
+}
+
+impl SiteForwarder {
+    pub fn new(settings: ForwardingSettings, transport: Arc<dyn ForwardTransport>) -> SIEMResult<Self> {
+        let spool = DiskSpool::open_file(Path::new(&settings.spool_dir), "forward.jsonl", settings.max_spool_bytes)?;
+        Ok(Self {
+            settings,
+            transport,
+            spool,
+            sending: tokio::sync::Mutex::new(()),
+            forwarded: AtomicU64::new(0),
+        })
+    }
+
+    /// Build the forwarder with the configured transport
+    pub async fn connect(settings: ForwardingSettings) -> SIEMResult<Self> {
+        let transport: Arc<dyn ForwardTransport> = match settings.transport {
+            ForwardTransportKind::Nats => {
+                let client = async_nats::connect(&settings.nats_url)
+                    .await
+                    .map_err(|e| SIEMError::Other(format!("NATS leaf node {} unavailable: {}", settings.nats_url, e)))?;
+                Arc::new(NatsTransport::new(client, &settings.site_id))
+            }
+            ForwardTransportKind::Https => Arc::new(HttpsTransport::new(&settings)?),
+        };
+        Self::new(settings, transport)
+    }
+
+    pub fn site_id(&self) -> &str {
+        &self.settings.site_id
+    }
+
+    pub fn forwarded(&self) -> u64 {
+        self.forwarded.load(Ordering::Relaxed)
+    }
+
+    pub fn spooled(&self) -> u64 {
+        self.spool.len()
+    }
+
+    pub fn queue_sample(&self) -> QueueSample {
+        QueueSample { depth: self.spooled(), processed: self.forwarded() }
+    }
+
+    fn envelope(&self, payload: ForwardPayload) -> ForwardEnvelope {
+        ForwardEnvelope {
+            id: uuid::Uuid::new_v4().to_string(),
+            origin_site: self.settings.site_id.clone(),
+            path: vec![self.settings.site_id.clone()],
+            created_at: Utc::now(),
+            payload,
+        }
+    }
+
+    /// Forward a detection without its raw correlated events, tagged with the site
+    pub async fn forward_detection(&self, mut threat: AdvancedThreatResult) -> SIEMResult<()> {
+        threat.correlation_events.clear();
+        threat.details.entry("site".to_string()).or_insert_with(|| self.settings.site_id.clone());
+        self.deliver(vec![self.envelope(ForwardPayload::Detection(Box::new(threat)))]).await
+    }
+
+    pub async fn forward_aggregate(&self, stats: TopNStats) -> SIEMResult<()> {
+        self.deliver(vec![self.envelope(ForwardPayload::Aggregate(stats))]).await
+    }
+
+    /// Send now, or spool behind earlier undelivered envelopes to keep order
+    pub async fn deliver(&self, batch: Vec<ForwardEnvelope>) -> SIEMResult<()> {
+        let _sending = self.sending.lock().await;
+        if self.spool.is_empty() {
+            match self.transport.send(&batch).await {
+                Ok(()) => {
+                    self.forwarded.fetch_add(batch.len() as u64, Ordering::Relaxed);
+                    return Ok(());
+                }
+                Err(e) => warn!("⚠️ Upstream unreachable, spooling {} envelope(s): {}", batch.len(), e),
+            }
+        }
+        for envelope in &batch {
+            self.spool.push(envelope)?;
+        }
+        Ok(())
+    }
+
+    /// Retry spooled envelopes oldest first; returns how many were delivered
+    pub async fn flush_spool(&self) -> SIEMResult<usize> {
+        let _sending = self.sending.lock().await;
+        let pending = self.spool.take_all()?;
+        let mut delivered = 0;
+        for batch in pending.chunks(self.settings.batch_size.max(1)) {
+            if let Err(e) = self.transport.send(batch).await {
+                for envelope in &pending[delivered..] {
+                    self.spool.push(envelope)?;
+                }
+                warn!("⚠️ Upstream still unreachable, {} envelope(s) remain spooled: {}", pending.len() - delivered, e);
+                break;
+            }
+            delivered += batch.len();
+        }
+        self.forwarded.fetch_add(delivered as u64, Ordering::Relaxed);
+        Ok(delivered)
+    }
+
+    /// Forward detections as they arrive and aggregates on their interval
+    pub fn spawn(
+        self: Arc<Self>,
+        mut detections: Option<broadcast::Receiver<AdvancedThreatResult>>,
+        aggregator: Option<Arc<TopNAggregator>>,
+    ) {
+        tokio::spawn(async move {
+            info!("🌐 Forwarding site '{}' upstream", self.settings.site_id);
+            let mut flush = tokio::time::interval(Duration::from_secs(self.settings.flush_interval_seconds.max(1)));
+            let mut aggregate = tokio::time::interval(Duration::from_secs(self.settings.aggregate_interval_seconds.max(1)));
+            loop {
+                tokio::select! {
+                    received = async { detections.as_mut().unwrap().recv().await }, if detections.is_some() => match received {
+                        Ok(threat) => {
+                            if let Err(e) = self.forward_detection(threat).await {
+                                error!("❌ Failed to forward detection: {}", e);
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            warn!("⚠️ Forwarder skipped {} detections", skipped);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => detections = None,
+                    },
+                    _ = aggregate.tick(), if aggregator.is_some() => {
+                        let stats = aggregator.as_ref().unwrap().stats();
+                        if let Err(e) = self.forward_aggregate(stats).await {
+                            error!("❌ Failed to forward aggregates: {}", e);
+                        }
+                    }
+                    _ = flush.tick() => {
+                        if let Err(e) = self.flush_spool().await {
+                            error!("❌ Failed to flush forwarding spool: {}", e);
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveOutcome {
+    Accepted,
+    Duplicate,
+    /// The envelope already passed through this site
+    Loop,
+    HopLimit,
+}
+
+/// What the central core knows about one forwarding site
+#[derive(Debug, Clone, Serialize)]
+pub struct SiteStatus {
+    pub site_id: String,
+    pub last_seen: DateTime<Utc>,
+    pub detections: u64,
+    pub aggregates: u64,
+    pub latest_aggregate: Option<TopNStats>,
+}
+
+#[derive(Default)]
+struct SeenIds {
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+}
+
+/// Accepts envelopes from downstream sites and relays them further up when chained
+pub struct SiteReceiver {
+    site_id: String,
+    accept: bool,
+    max_hops: usize,
+    incidents: Arc<IncidentResponseEngine>,
+    upstream: Option<Arc<SiteForwarder>>,
+    seen: Mutex<SeenIds>,
+    sites: RwLock<BTreeMap<String, SiteStatus>>,
+}
+
+impl SiteReceiver {
+    pub fn new(settings: &ForwardingSettings, incidents: Arc<IncidentResponseEngine>, upstream: Option<Arc<SiteForwarder>>) -> Self {
+        Self {
+            site_id: settings.site_id.clone(),
+            accept: settings.accept,
+            max_hops: settings.max_hops,
+            incidents,
+            upstream,
+            seen: Mutex::new(SeenIds::default()),
+            sites: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Remember an envelope id; false when it was already seen
+    fn remember(&self, id: &str) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        if !seen.ids.insert(id.to_string()) {
+            return false;
+        }
+        seen.order.push_back(id.to_string());
+        if seen.order.len() > SEEN_CAPACITY {
+            if let Some(oldest) = seen.order.pop_front() {
+                seen.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    pub async fn receive(&self, mut envelope: ForwardEnvelope) -> SIEMResult<ReceiveOutcome> {
+        if !self.accept {
+            return Err(SIEMError::Validation(format!("Site '{}' does not accept forwarded events", self.site_id)));
+        }
+        if envelope.path.first() != Some(&envelope.origin_site) {
+            return Err(SIEMError::Validation(format!("Envelope {} path does not start at its origin site", envelope.id)));
+        }
+        if envelope.path.contains(&self.site_id) {
+            return Ok(ReceiveOutcome::Loop);
+        }
+        if envelope.path.len() > self.max_hops {
+            return Ok(ReceiveOutcome::HopLimit);
+        }
+        if !self.remember(&envelope.id) {
+            return Ok(ReceiveOutcome::Duplicate);
+        }
+
+        match &envelope.payload {
+            ForwardPayload::Detection(threat) => {
+                self.incidents.process_threat((**threat).clone()).await?;
+            }
+            ForwardPayload::Aggregate(_) => {}
+        }
+        {
+            let mut sites = self.sites.write().unwrap();
+            let status = sites.entry(envelope.origin_site.clone()).or_insert_with(|| SiteStatus {
+                site_id: envelope.origin_site.clone(),
+                last_seen: envelope.created_at,
+                detections: 0,
+                aggregates: 0,
+                latest_aggregate: None,
+            });
+            status.last_seen = status.last_seen.max(envelope.created_at);
+            match &envelope.payload {
+                ForwardPayload::Detection(_) => status.detections += 1,
+                ForwardPayload::Aggregate(stats) => {
+                    status.aggregates += 1;
+                    status.latest_aggregate = Some(stats.clone());
+                }
+            }
+        }
+
+        if let Some(upstream) = &self.upstream {
+            envelope.path.push(self.site_id.clone());
+            upstream.deliver(vec![envelope]).await?;
+        }
+        Ok(ReceiveOutcome::Accepted)
+    }
+
+    pub async fn receive_batch(&self, batch: Vec<ForwardEnvelope>) -> SIEMResult<Vec<ReceiveOutcome>> {
+        let mut outcomes = Vec::with_capacity(batch.len());
+        for envelope in batch {
+            outcomes.push(self.receive(envelope).await?);
+        }
+        Ok(outcomes)
+    }
+
+    pub fn sites(&self) -> Vec<SiteStatus> {
+        self.sites.read().unwrap().values().cloned().collect()
+    }
+
+    /// Receive envelopes published by leaf-node sites on `ultra_siem.sites.*.forward`
+    pub async fn spawn_nats_listener(self: Arc<Self>, client: async_nats::Client) -> SIEMResult<()> {
+        let mut subscriber = client.subscribe(SUBJECT_SITE_FORWARD).await?;
+        tokio::spawn(async move {
+            while let Some(message) = subscriber.next().await {
+                let received = match serde_json::from_slice::<ForwardEnvelope>(&message.payload) {
+                    Ok(envelope) => self.receive(envelope).await,
+                    Err(e) => Err(e.into()),
+                };
+                if let Err(e) = received {
+                    warn!("⚠️ Rejected forwarded envelope on {}: {}", message.subject, e);
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use crate::advanced_threat_detection::CorrelationEvent;
+    use crate::threat_detection::ThreatSeverity;
+    use crate::incident_response::{AlertConfig, SOARConfig};
+
+    /// Records delivered envelopes and fails while `down` is set
+    #[derive(Default)]
+    struct RecordingTransport {
+        down: AtomicBool,
+        sent: Mutex<Vec<ForwardEnvelope>>,
+    }
+
+    impl ForwardTransport for RecordingTransport {
+        fn send<'a>(&'a self, batch: &'a [ForwardEnvelope]) -> BoxFuture<'a, SIEMResult<()>> {
+            Box::pin(async move {
+                if self.down.load(Ordering::SeqCst) {
+                    return Err(SIEMError::Other("WAN down".to_string()));
+                }
+                self.sent.lock().unwrap().extend_from_slice(batch);
+                Ok(())
+            })
+        }
+    }
+
+    fn settings(site_id: &str) -> ForwardingSettings {
+        let dir = std::env::temp_dir().join(format!("forward-spool-{}", uuid::Uuid::new_v4()));
+        ForwardingSettings {
+            site_id: site_id.to_string(),
+            accept: true,
+            spool_dir: dir.to_string_lossy().to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn incidents() -> Arc<IncidentResponseEngine> {
+        Arc::new(IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled()))
+    }
+
+    #[tokio::test]
+    async fn test_store_and_forward_keeps_order_and_strips_raw_events() {
+        let transport = Arc::new(RecordingTransport::default());
+        let settings = settings("edge-1");
+        let spool_dir = settings.spool_dir.clone();
+        let forwarder = SiteForwarder::new(settings, transport.clone()).unwrap();
+
+        transport.down.store(true, Ordering::SeqCst);
+        let mut threat = AdvancedThreatResult {
+            threat_id: "t-1".to_string(),
+            correlation_events: vec![CorrelationEvent {
+                id: "raw-1".to_string(),
+                timestamp: 1700000000,
+                event_type: "auth_failure".to_string(),
+                source: "203.0.113.7".to_string(),
+                target: "10.0.0.5".to_string(),
+                severity: ThreatSeverity::Medium,
+                confidence: 0.6,
+                metadata: Default::default(),
+            }],
+            ..Default::default()
+        };
+        forwarder.forward_detection(threat.clone()).await.unwrap();
+        forwarder.forward_aggregate(TopNStats::default()).await.unwrap();
+        assert_eq!(forwarder.spooled(), 2);
+
+        // The link is back, but newer envelopes still queue behind the spool
+        transport.down.store(false, Ordering::SeqCst);
+        threat.threat_id = "t-2".to_string();
+        forwarder.forward_detection(threat).await.unwrap();
+        assert!(transport.sent.lock().unwrap().is_empty());
+        assert_eq!(forwarder.flush_spool().await.unwrap(), 3);
+        assert_eq!(forwarder.spooled(), 0);
+
+        let sent = transport.sent.lock().unwrap();
+        let ids: Vec<_> = sent
+            .iter()
+            .map(|envelope| match &envelope.payload {
+                ForwardPayload::Detection(threat) => {
+                    assert!(threat.correlation_events.is_empty());
+                    assert_eq!(threat.details["site"], "edge-1");
+                    threat.threat_id.clone()
+                }
+                ForwardPayload::Aggregate(_) => "aggregate".to_string(),
+            })
+            .collect();
+        assert_eq!(ids, vec!["t-1", "aggregate", "t-2"]);
+        assert_eq!(sent[0].path, vec!["edge-1"]);
+        let _ = std::fs::remove_dir_all(&spool_dir);
+    }
+
+    #[tokio::test]
+    async fn test_receiver_drops_loops_duplicates_and_relays_upstream() {
+        let transport = Arc::new(RecordingTransport::default());
+        let upstream_settings = settings("region-eu");
+        let spool_dir = upstream_settings.spool_dir.clone();
+        let upstream = Arc::new(SiteForwarder::new(upstream_settings.clone(), transport.clone()).unwrap());
+        let receiver = SiteReceiver::new(&upstream_settings, incidents(), Some(upstream));
+
+        let edge = SiteForwarder::new(settings("edge-1"), Arc::new(RecordingTransport::default())).unwrap();
+        let envelope = edge.envelope(ForwardPayload::Detection(Box::default()));
+        assert_eq!(receiver.receive(envelope.clone()).await.unwrap(), ReceiveOutcome::Accepted);
+        assert_eq!(receiver.receive(envelope.clone()).await.unwrap(), ReceiveOutcome::Duplicate);
+
+        // Relayed with this site appended, so it can never come back here
+        let relayed = transport.sent.lock().unwrap().clone();
+        assert_eq!(relayed.len(), 1);
+        assert_eq!(relayed[0].path, vec!["edge-1", "region-eu"]);
+        let mut looped = relayed[0].clone();
+        looped.id = "looped".to_string();
+        assert_eq!(receiver.receive(looped).await.unwrap(), ReceiveOutcome::Loop);
+
+        let mut far = edge.envelope(ForwardPayload::Aggregate(TopNStats::default()));
+        far.path.extend(["a", "b", "c", "d"].map(String::from));
+        assert_eq!(receiver.receive(far).await.unwrap(), ReceiveOutcome::HopLimit);
+
+        let sites = receiver.sites();
+        assert_eq!(sites.len(), 1);
+        assert_eq!((sites[0].site_id.as_str(), sites[0].detections), ("edge-1", 1));
+        assert_eq!(receiver.incidents.get_all_incidents().len(), 1);
+        let _ = std::fs::remove_dir_all(&spool_dir);
+    }
+}
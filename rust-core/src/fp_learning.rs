@@ -0,0 +1,306 @@
+//! # False Positive Pattern Learning
+//!
+//! When analysts mark many incidents false positive for the same reason, such
+//! as one signature fired over and over by an internal vulnerability scanner,
+//! snoozing them one at a time is slow. The incidents marked false positive
+//! over `lookback_days` are mined for shared attributes:
+//!
+//! - a signature with the same source address, host or user becomes a
+//!   candidate suppression rule,
+//! - an internal source address cleared on several signatures becomes a
+//!   candidate allow-list entry.
+//!
+//! A candidate is only proposed when enough false positives share it and
+//! nearly every incident with those attributes was one, so real detections
+//! are not tuned away. Analysts adopt a proposal, with an expiry, in one call.
+
+use std::collections::{BTreeSet, HashMap};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::cardinality::is_external;
+use crate::incident_response::{Incident, IncidentStatus};
+use crate::shared_state::SharedEntry;
+use crate::suppression::{signature_key, SuppressionRule};
+
+/// False positive incidents listed on a suggestion, newest first
+const MAX_SAMPLES: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FpLearningSettings {
+    pub enabled: bool,
+    /// Incidents created this long ago or more recently are mined
+    pub lookback_days: u64,
+    /// False positives that must share a pattern before it is proposed
+    pub min_false_positives: u64,
+    /// Share of all incidents with the pattern that must have been false positives
+    pub min_false_positive_ratio: f64,
+    /// Signatures an internal address must be cleared on before it is proposed for the allow list
+    pub allow_list_min_signatures: usize,
+    /// Expiry proposed for adopted rules
+    pub default_days: u32,
+    pub max_suggestions: usize,
+}
+
+impl Default for FpLearningSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            lookback_days: 30,
+            min_false_positives: 5,
+            min_false_positive_ratio: 0.9,
+            allow_list_min_signatures: 3,
+            default_days: 14,
+            max_suggestions: 20,
+        }
+    }
+}
+
+/// Rule a false positive pattern would become
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CandidateRule {
+    /// Snooze `signature` for `host` or `user`; the other is empty
+    Suppression { signature: String, host: String, user: String },
+    /// Allow-list an internal source address
+    AllowList { source_ip: String },
+}
+
+impl CandidateRule {
+    fn describe(&self) -> String {
+        match self {
+            CandidateRule::Suppression { signature, host, user } if user.is_empty() => format!("'{}' on {}", signature, host),
+            CandidateRule::Suppression { signature, user, .. } => format!("'{}' for {}", signature, user),
+            CandidateRule::AllowList { source_ip } => format!("allow-list {}", source_ip),
+        }
+    }
+}
+
+/// Proposed rule with the false positives behind it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleSuggestion {
+    /// Stable for the same rule, so a suggestion can be adopted by id
+    pub id: String,
+    #[serde(flatten)]
+    pub rule: CandidateRule,
+    pub false_positives: u64,
+    /// Incidents with the pattern, false positive or not
+    pub incidents: u64,
+    pub false_positive_ratio: f64,
+    /// Signatures the false positives were raised by
+    pub signatures: Vec<String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub sample_incidents: Vec<String>,
+    pub suggested_days: u32,
+}
+
+impl RuleSuggestion {
+    /// Reason recorded on the adopted rule when the analyst gives none
+    pub fn default_reason(&self) -> String {
+        format!(
+            "Learned from {} of {} incidents marked false positive: {}",
+            self.false_positives,
+            self.incidents,
+            self.rule.describe()
+        )
+    }
+}
+
+/// One-click adoption of a suggestion; the adopting caller owns the rule
+#[derive(Debug, Clone, Deserialize)]
+pub struct AdoptRequest {
+    /// `suggested_days` when absent
+    #[serde(default)]
+    pub days: Option<u32>,
+    /// `default_reason` when absent
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Rule created by adopting a suggestion
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AdoptedRule {
+    Suppression { rule: SuppressionRule },
+    AllowList { source_ip: String, entry: SharedEntry },
+}
+
+#[derive(Debug, Default)]
+struct Tally {
+    false_positives: u64,
+    incidents: u64,
+    signatures: BTreeSet<String>,
+    first_seen: Option<DateTime<Utc>>,
+    last_seen: Option<DateTime<Utc>>,
+    samples: Vec<(DateTime<Utc>, String)>,
+}
+
+/// Rules the attributes of `incident` could be covered by
+fn candidates(incident: &Incident) -> Vec<CandidateRule> {
+    let threat = &incident.threat_result;
+    let signature = signature_key(threat).to_string();
+    let source_ip = threat.source_ip.as_str();
+    let host = ["hostname", "host"].iter().filter_map(|key| threat.details.get(*key)).find(|host| !host.is_empty());
+    let suppression = |host: &str, user: &str| CandidateRule::Suppression { signature: signature.clone(), host: host.to_string(), user: user.to_string() };
+
+    let mut candidates = Vec::new();
+    if !source_ip.is_empty() {
+        candidates.push(suppression(source_ip, ""));
+        if !is_external(source_ip) {
+            candidates.push(CandidateRule::AllowList { source_ip: source_ip.to_string() });
+        }
+    }
+    if let Some(host) = host.filter(|host| *host != source_ip) {
+        candidates.push(suppression(host, ""));
+    }
+    if !threat.user_id.is_empty() {
+        candidates.push(suppression("", &threat.user_id));
+    }
+    candidates
+}
+
+/// Whether an active rule already snoozes exactly `candidate`
+fn already_suppressed(candidate: &CandidateRule, existing: &[SuppressionRule], now: DateTime<Utc>) -> bool {
+    let CandidateRule::Suppression { signature, host, user } = candidate else {
+        return false;
+    };
+    existing
+        .iter()
+        .any(|rule| rule.is_active(now) && rule.signature == *signature && rule.host == *host && rule.user == *user)
+}
+
+/// Rules proposed from the false positives among `incidents`, most false positives first
+pub fn suggest(incidents: &[Incident], existing: &[SuppressionRule], settings: &FpLearningSettings, now: DateTime<Utc>) -> Vec<RuleSuggestion> {
+    if !settings.enabled {
+        return Vec::new();
+    }
+    let since = now - Duration::days(settings.lookback_days as i64);
+    let mut tallies: HashMap<CandidateRule, Tally> = HashMap::new();
+    for incident in incidents.iter().filter(|incident| incident.created_at >= since) {
+        let false_positive = incident.false_positive || incident.status == IncidentStatus::FalsePositive;
+        for candidate in candidates(incident) {
+            let tally = tallies.entry(candidate).or_default();
+            tally.incidents += 1;
+            if !false_positive {
+                continue;
+            }
+            tally.false_positives += 1;
+            tally.signatures.insert(signature_key(&incident.threat_result).to_string());
+            tally.first_seen = Some(tally.first_seen.map_or(incident.created_at, |seen| seen.min(incident.created_at)));
+            tally.last_seen = Some(tally.last_seen.map_or(incident.created_at, |seen| seen.max(incident.created_at)));
+            tally.samples.push((incident.created_at, incident.id.clone()));
+        }
+    }
+
+    let mut suggestions: Vec<RuleSuggestion> = tallies
+        .into_iter()
+        .filter_map(|(rule, mut tally)| {
+            let ratio = tally.false_positives as f64 / tally.incidents as f64;
+            let enough_signatures = !matches!(rule, CandidateRule::AllowList { .. }) || tally.signatures.len() >= settings.allow_list_min_signatures;
+            if tally.false_positives < settings.min_false_positives.max(1)
+                || ratio < settings.min_false_positive_ratio
+                || !enough_signatures
+                || already_suppressed(&rule, existing, now)
+            {
+                return None;
+            }
+            tally.samples.sort_by(|a, b| b.cmp(a));
+            let digest = Sha256::digest(serde_json::to_vec(&rule).unwrap_or_default());
+            Some(RuleSuggestion {
+                id: format!("{:x}", digest)[..16].to_string(),
+                false_positives: tally.false_positives,
+                incidents: tally.incidents,
+                false_positive_ratio: ratio,
+                signatures: tally.signatures.into_iter().collect(),
+                first_seen: tally.first_seen?,
+                last_seen: tally.last_seen?,
+                sample_incidents: tally.samples.into_iter().take(MAX_SAMPLES).map(|(_, id)| id).collect(),
+                suggested_days: settings.default_days,
+                rule,
+            })
+        })
+        .collect();
+    suggestions.sort_by(|a, b| b.false_positives.cmp(&a.false_positives).then_with(|| a.rule.cmp(&b.rule)));
+    suggestions.truncate(settings.max_suggestions);
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+
+    fn incident(id: usize, signature: &str, source_ip: &str, false_positive: bool, now: DateTime<Utc>) -> Incident {
+        let created_at = now - Duration::hours(id as i64);
+        let mut threat_result = AdvancedThreatResult { source_ip: source_ip.to_string(), ..Default::default() };
+        threat_result.details.insert("signature_id".to_string(), signature.to_string());
+        Incident {
+            id: format!("inc-{}", id),
+            status: if false_positive { IncidentStatus::FalsePositive } else { IncidentStatus::Open },
+            source_ip: source_ip.to_string(),
+            threat_result,
+            false_positive,
+            ..Incident::fixture(created_at)
+        }
+    }
+
+    #[test]
+    fn test_scanner_false_positives_become_a_suppression_suggestion() {
+        let now = Utc::now();
+        let settings = FpLearningSettings::default();
+        // The scanner's port scans were all cleared; the one from an outside address was real
+        let mut incidents: Vec<Incident> = (0..6).map(|i| incident(i, "port_scan", "10.0.0.50", true, now)).collect();
+        incidents.push(incident(6, "port_scan", "198.51.100.7", false, now));
+        // Mixed verdicts for brute force from another host are not proposed
+        incidents.extend((7..13).map(|i| incident(i, "brute_force_1", "10.0.0.60", i % 2 == 0, now)));
+
+        let suggestions = suggest(&incidents, &[], &settings, now);
+        assert_eq!(suggestions.len(), 1);
+        let suggestion = &suggestions[0];
+        assert_eq!(
+            suggestion.rule,
+            CandidateRule::Suppression { signature: "port_scan".to_string(), host: "10.0.0.50".to_string(), user: String::new() }
+        );
+        assert_eq!((suggestion.false_positives, suggestion.incidents, suggestion.sample_incidents[0].as_str()), (6, 6, "inc-0"));
+        assert_eq!(suggestion.id, suggest(&incidents, &[], &settings, now)[0].id);
+
+        // Not proposed again once snoozed
+        let rule = SuppressionRule {
+            id: "r1".to_string(),
+            signature: "port_scan".to_string(),
+            host: "10.0.0.50".to_string(),
+            user: String::new(),
+            owner: "alice".to_string(),
+            reason: suggestion.default_reason(),
+            incident_id: "inc-0".to_string(),
+            created_at: now,
+            expires_at: now + Duration::days(14),
+            renewals: 0,
+            hits: 0,
+            last_hit: None,
+        };
+        assert!(suggest(&incidents, &[rule], &settings, now).is_empty());
+    }
+
+    #[test]
+    fn test_internal_address_cleared_across_signatures_is_proposed_for_the_allow_list() {
+        let now = Utc::now();
+        let settings = FpLearningSettings { min_false_positives: 3, ..Default::default() };
+        let incidents: Vec<Incident> = ["port_scan", "sql_injection", "xss_1", "port_scan"]
+            .iter()
+            .enumerate()
+            .map(|(i, signature)| incident(i, signature, "10.0.0.50", true, now))
+            .collect();
+        let suggestions = suggest(&incidents, &[], &settings, now);
+        let allow = suggestions.iter().find(|s| matches!(s.rule, CandidateRule::AllowList { .. })).unwrap();
+        assert_eq!(allow.signatures, vec!["port_scan", "sql_injection", "xss_1"]);
+        assert_eq!(allow.false_positives, 4);
+
+        // External addresses are never proposed for the allow list
+        let external: Vec<Incident> = (0..4).map(|i| incident(i, ["port_scan", "sql_injection", "xss_1"][i % 3], "203.0.113.4", true, now)).collect();
+        assert!(suggest(&external, &[], &settings, now).iter().all(|s| !matches!(s.rule, CandidateRule::AllowList { .. })));
+    }
+}
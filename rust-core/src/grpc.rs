@@ -0,0 +1,425 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use log::{info, warn};
+use subtle::ConstantTimeEq;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::service::Interceptor;
+use tonic::transport::{Identity, Server, ServerTlsConfig};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::advanced_threat_detection::{AdvancedThreatDetectionEngine, AdvancedThreatResult};
+use crate::config::GrpcSettings;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::forensic::HeldBackKind;
+use crate::incident_response::{Incident, IncidentResponseEngine, IncidentSeverity, IncidentStatus};
+use crate::threat_detection::ThreatSeverity;
+
+/// Generated protobuf types and service stubs (`proto/ultra_siem.proto`)
+pub mod proto {
+    tonic::include_proto!("ultra_siem.v1");
+}
+
+use proto::siem_service_server::{SiemService, SiemServiceServer};
+
+type DetectionStream = Pin<Box<dyn Stream<Item = Result<proto::Detection, Status>> + Send>>;
+
+/// gRPC front-end for event submission, detection streaming and incident management
+pub struct SiemGrpcService {
+    detector: Arc<AdvancedThreatDetectionEngine>,
+    incidents: Arc<IncidentResponseEngine>,
+    detections: broadcast::Sender<AdvancedThreatResult>,
+}
+
+impl SiemGrpcService {
+    pub fn new(detector: Arc<AdvancedThreatDetectionEngine>, incidents: Arc<IncidentResponseEngine>) -> Self {
+        let (detections, _) = broadcast::channel(10000);
+        Self { detector, incidents, detections }
+    }
+
+    /// Detections found by `AnalyzeEvents`, for consumers besides `StreamDetections`
+    pub fn subscribe_detections(&self) -> broadcast::Receiver<AdvancedThreatResult> {
+        self.detections.subscribe()
+    }
+
+    async fn handle_event(&self, event: proto::Event) -> SIEMResult<usize> {
+        let threats = self.detector.process_event(event_to_json(event)).await?;
+        for threat in &threats {
+            let _ = self.detections.send(threat.clone());
+            self.incidents.process_threat(threat.clone()).await?;
+        }
+        Ok(threats.len())
+    }
+
+    fn incident_or_not_found(&self, id: &str) -> Result<Response<proto::Incident>, Box<Status>> {
+        self.incidents
+            .get_incident(id)
+            .map(|incident| Response::new(incident_to_proto(&incident)))
+            .ok_or_else(|| Box::new(Status::not_found(format!("Incident {} not found", id))))
+    }
+
+    /// Analyst edits are journaled, not applied, in forensic mode
+    fn forensic_refusal(&self, rpc: &str, id: &str) -> Option<Status> {
+        let journal = self.incidents.forensic_journal()?;
+        journal.record(HeldBackKind::Mutation, "grpc", rpc, Some(id));
+        Some(Status::failed_precondition("Read-only forensic mode; the change was journaled, not applied"))
+    }
+}
+
+#[tonic::async_trait]
+impl SiemService for SiemGrpcService {
+    async fn submit_events(
+        &self,
+        request: Request<Streaming<proto::Event>>,
+    ) -> Result<Response<proto::SubmitEventsResponse>, Status> {
+        let mut stream = request.into_inner();
+        let mut summary = proto::SubmitEventsResponse::default();
+
+        while let Some(event) = stream.message().await? {
+            match self.handle_event(event).await {
+                Ok(detections) => {
+                    summary.accepted += 1;
+                    summary.detections += detections as u64;
+                }
+                Err(e) => {
+                    warn!("⚠️ Rejected gRPC event: {}", e);
+                    summary.rejected += 1;
+                }
+            }
+        }
+
+        Ok(Response::new(summary))
+    }
+
+    type StreamDetectionsStream = DetectionStream;
+
+    async fn stream_detections(
+        &self,
+        request: Request<proto::StreamDetectionsRequest>,
+    ) -> Result<Response<Self::StreamDetectionsStream>, Status> {
+        let min_severity = request.into_inner().min_severity;
+        let min_rank = if min_severity.is_empty() {
+            0
+        } else {
+            parse_threat_severity(&min_severity)
+                .map(|s| severity_rank(&s))
+                .ok_or_else(|| Status::invalid_argument(format!("Unknown severity: {}", min_severity)))?
+        };
+
+        let stream = BroadcastStream::new(self.detections.subscribe()).filter_map(move |item| match item {
+            Ok(threat) if severity_rank(&threat.severity) >= min_rank => Some(Ok(detection_to_proto(&threat))),
+            Ok(_) => None,
+            // Slow consumers skip missed detections rather than closing the stream
+            Err(_) => None,
+        });
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn get_incident(
+        &self,
+        request: Request<proto::GetIncidentRequest>,
+    ) -> Result<Response<proto::Incident>, Status> {
+        self.incident_or_not_found(&request.into_inner().id).map_err(|status| *status)
+    }
+
+    async fn list_incidents(
+        &self,
+        request: Request<proto::ListIncidentsRequest>,
+    ) -> Result<Response<proto::ListIncidentsResponse>, Status> {
+        let filter = request.into_inner();
+        let status = match filter.status.as_str() {
+            "" => None,
+            value => Some(parse_incident_status(value).ok_or_else(|| Status::invalid_argument(format!("Unknown status: {}", value)))?),
+        };
+        let severity = match filter.severity.as_str() {
+            "" => None,
+            value => Some(parse_incident_severity(value).ok_or_else(|| Status::invalid_argument(format!("Unknown severity: {}", value)))?),
+        };
+
+        let candidates = if filter.query.is_empty() {
+            self.incidents.get_all_incidents()
+        } else {
+            self.incidents.search_incidents(&filter.query)
+        };
+        let incidents = candidates
+            .iter()
+            .filter(|incident| status.as_ref().is_none_or(|s| &incident.status == s))
+            .filter(|incident| severity.as_ref().is_none_or(|s| &incident.severity == s))
+            .map(incident_to_proto)
+            .collect();
+
+        Ok(Response::new(proto::ListIncidentsResponse { incidents }))
+    }
+
+    async fn update_incident_status(
+        &self,
+        request: Request<proto::UpdateIncidentStatusRequest>,
+    ) -> Result<Response<proto::Incident>, Status> {
+        let request = request.into_inner();
+        let status = parse_incident_status(&request.status)
+            .ok_or_else(|| Status::invalid_argument(format!("Unknown status: {}", request.status)))?;
+        self.incident_or_not_found(&request.id).map_err(|status| *status)?;
+        if let Some(refusal) = self.forensic_refusal("UpdateIncidentStatus", &request.id) {
+            return Err(refusal);
+        }
+        self.incidents.update_incident_status(&request.id, status).await.map_err(to_status)?;
+        self.incident_or_not_found(&request.id).map_err(|status| *status)
+    }
+
+    async fn add_incident_note(
+        &self,
+        request: Request<proto::AddIncidentNoteRequest>,
+    ) -> Result<Response<proto::Incident>, Status> {
+        let request = request.into_inner();
+        self.incident_or_not_found(&request.id).map_err(|status| *status)?;
+        if let Some(refusal) = self.forensic_refusal("AddIncidentNote", &request.id) {
+            return Err(refusal);
+        }
+        self.incidents.add_incident_note(&request.id, request.note).await.map_err(to_status)?;
+        self.incident_or_not_found(&request.id).map_err(|status| *status)
+    }
+
+    async fn assign_incident(
+        &self,
+        request: Request<proto::AssignIncidentRequest>,
+    ) -> Result<Response<proto::Incident>, Status> {
+        let request = request.into_inner();
+        self.incident_or_not_found(&request.id).map_err(|status| *status)?;
+        if let Some(refusal) = self.forensic_refusal("AssignIncident", &request.id) {
+            return Err(refusal);
+        }
+        self.incidents.assign_incident(&request.id, request.assigned_to).await.map_err(to_status)?;
+        self.incident_or_not_found(&request.id).map_err(|status| *status)
+    }
+}
+
+/// Bearer token check applied to every RPC; tokens are compared in constant time
+#[derive(Debug, Clone)]
+pub struct TokenAuth {
+    tokens: Arc<Vec<String>>,
+}
+
+impl TokenAuth {
+    pub fn new(tokens: Vec<String>) -> Self {
+        Self { tokens: Arc::new(tokens) }
+    }
+}
+
+impl Interceptor for TokenAuth {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        if self.tokens.is_empty() {
+            return Ok(request);
+        }
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match token {
+            Some(token) if self.tokens.iter().any(|t| bool::from(t.as_bytes().ct_eq(token.as_bytes()))) => Ok(request),
+            Some(_) => Err(Status::unauthenticated("Invalid token")),
+            None => Err(Status::unauthenticated("Missing bearer token")),
+        }
+    }
+}
+
+/// Serve the gRPC API until the server fails
+pub async fn serve(settings: &GrpcSettings, service: SiemGrpcService) -> SIEMResult<()> {
+    let addr = settings
+        .listen_addr
+        .parse()
+        .map_err(|e| SIEMError::Config(format!("Invalid gRPC listen address {}: {}", settings.listen_addr, e)))?;
+
+    let mut builder = Server::builder();
+    match (&settings.tls_cert_path, &settings.tls_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let cert = std::fs::read(cert_path)?;
+            let key = std::fs::read(key_path)?;
+            builder = builder
+                .tls_config(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
+                .map_err(|e| SIEMError::Config(format!("Invalid gRPC TLS configuration: {}", e)))?;
+        }
+        (None, None) => warn!("⚠️ gRPC API is serving without TLS"),
+        _ => return Err(SIEMError::Config("gRPC TLS needs both tls_cert_path and tls_key_path".to_string())),
+    }
+    if settings.auth_tokens.is_empty() {
+        warn!("⚠️ gRPC API is serving without token authentication");
+    }
+
+    info!("🚀 Starting gRPC API on {}", addr);
+    builder
+        .add_service(SiemServiceServer::with_interceptor(service, TokenAuth::new(settings.auth_tokens.clone())))
+        .serve(addr)
+        .await
+        .map_err(|e| SIEMError::Other(format!("gRPC server error: {}", e)))
+}
+
+fn to_status(error: SIEMError) -> Status {
+    match error {
+        SIEMError::Validation(message) | SIEMError::Config(message) => Status::invalid_argument(message),
+        SIEMError::Auth(message) => Status::permission_denied(message),
+        other => Status::internal(other.to_string()),
+    }
+}
+
+fn event_to_json(event: proto::Event) -> serde_json::Value {
+    let mut object = serde_json::Map::new();
+    for (key, value) in event.fields {
+        object.insert(key, serde_json::Value::String(value));
+    }
+    object.insert("id".to_string(), event.id.into());
+    object.insert("timestamp".to_string(), event.timestamp.into());
+    object.insert("source".to_string(), event.source.into());
+    object.insert("source_ip".to_string(), event.source_ip.into());
+    object.insert("destination_ip".to_string(), event.destination_ip.into());
+    object.insert("user_id".to_string(), event.user_id.into());
+    object.insert("message".to_string(), event.message.into());
+    object.insert("event_type".to_string(), event.event_type.into());
+    serde_json::Value::Object(object)
+}
+
+fn detection_to_proto(threat: &AdvancedThreatResult) -> proto::Detection {
+    proto::Detection {
+        threat_id: threat.threat_id.clone(),
+        timestamp: threat.timestamp,
+        severity: threat.severity.to_string(),
+        category: threat.category.to_string(),
+        confidence: threat.confidence,
+        detection_method: threat.detection_method.clone(),
+        source_ip: threat.source_ip.clone(),
+        destination_ip: threat.destination_ip.clone(),
+        user_id: threat.user_id.clone(),
+        description: threat.description.clone(),
+        iocs: threat.iocs.clone(),
+    }
+}
+
+fn incident_to_proto(incident: &Incident) -> proto::Incident {
+    proto::Incident {
+        id: incident.id.clone(),
+        timestamp: incident.timestamp,
+        severity: incident.severity.to_string(),
+        status: format!("{:?}", incident.status),
+        title: incident.title.clone(),
+        description: incident.description.clone(),
+        source_ip: incident.source_ip.clone(),
+        destination_ip: incident.destination_ip.clone(),
+        user_id: incident.user_id.clone(),
+        threat_id: incident.threat_id.clone(),
+        assigned_to: incident.assigned_to.clone().unwrap_or_default(),
+        notes: incident.notes.clone(),
+        escalation_level: incident.escalation_level as u32,
+    }
+}
+
+fn severity_rank(severity: &ThreatSeverity) -> u8 {
+    match severity {
+        ThreatSeverity::Low => 1,
+        ThreatSeverity::Medium => 2,
+        ThreatSeverity::High => 3,
+        ThreatSeverity::Critical => 4,
+    }
+}
+
+fn parse_threat_severity(value: &str) -> Option<ThreatSeverity> {
+    match value.to_lowercase().as_str() {
+        "low" => Some(ThreatSeverity::Low),
+        "medium" => Some(ThreatSeverity::Medium),
+        "high" => Some(ThreatSeverity::High),
+        "critical" => Some(ThreatSeverity::Critical),
+        _ => None,
+    }
+}
+
+fn parse_incident_severity(value: &str) -> Option<IncidentSeverity> {
+    match value.to_lowercase().as_str() {
+        "low" => Some(IncidentSeverity::Low),
+        "medium" => Some(IncidentSeverity::Medium),
+        "high" => Some(IncidentSeverity::High),
+        "critical" => Some(IncidentSeverity::Critical),
+        "emergency" => Some(IncidentSeverity::Emergency),
+        _ => None,
+    }
+}
+
+fn parse_incident_status(value: &str) -> Option<IncidentStatus> {
+    match value.to_lowercase().as_str() {
+        "open" => Some(IncidentStatus::Open),
+        "investigating" => Some(IncidentStatus::Investigating),
+        "containing" => Some(IncidentStatus::Containing),
+        "resolved" => Some(IncidentStatus::Resolved),
+        "closed" => Some(IncidentStatus::Closed),
+        "falsepositive" | "false_positive" => Some(IncidentStatus::FalsePositive),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatConfig;
+    use crate::incident_response::{AlertConfig, SOARConfig};
+
+    fn service() -> SiemGrpcService {
+        SiemGrpcService::new(
+            Arc::new(AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default())),
+            Arc::new(IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled())),
+        )
+    }
+
+    #[test]
+    fn test_token_auth() {
+        let mut auth = TokenAuth::new(vec!["secret".to_string()]);
+
+        let mut request = Request::new(());
+        request.metadata_mut().insert("authorization", "Bearer secret".parse().unwrap());
+        assert!(auth.call(request).is_ok());
+
+        let mut request = Request::new(());
+        request.metadata_mut().insert("authorization", "Bearer wrong".parse().unwrap());
+        assert_eq!(auth.call(request).unwrap_err().code(), tonic::Code::Unauthenticated);
+
+        assert!(auth.call(Request::new(())).is_err());
+        assert!(TokenAuth::new(vec![]).call(Request::new(())).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_incident_rpcs() {
+        let service = service();
+        let threat = AdvancedThreatResult {
+            threat_id: "t-1".to_string(),
+            severity: ThreatSeverity::High,
+            source_ip: "10.0.0.9".to_string(),
+            ..Default::default()
+        };
+        let incident = service.incidents.process_threat(threat).await.unwrap();
+
+        let updated = service
+            .update_incident_status(Request::new(proto::UpdateIncidentStatusRequest {
+                id: incident.id.clone(),
+                status: "investigating".to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(updated.status, "Investigating");
+
+        let listed = service
+            .list_incidents(Request::new(proto::ListIncidentsRequest {
+                status: "Investigating".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(listed.incidents.len(), 1);
+
+        let missing = service
+            .get_incident(Request::new(proto::GetIncidentRequest { id: "nope".to_string() }))
+            .await
+            .unwrap_err();
+        assert_eq!(missing.code(), tonic::Code::NotFound);
+    }
+}
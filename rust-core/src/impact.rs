@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "compliance")]
+use crate::compliance::{ComplianceSecurityEngine, ReportSection};
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::Incident;
+
+/// Ten years; longer outages are a data entry error
+const MAX_DOWNTIME_MINUTES: u64 = 10 * 365 * 24 * 60;
+const MAX_ESTIMATED_COST: f64 = 1e12;
+
+/// Business impact of an incident, in the organization's reporting currency
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IncidentImpact {
+    pub affected_users: Option<u64>,
+    pub downtime_minutes: Option<u64>,
+    pub estimated_cost: Option<f64>,
+    /// How the estimate was reached
+    pub notes: String,
+}
+
+impl IncidentImpact {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if self.affected_users.is_none() && self.downtime_minutes.is_none() && self.estimated_cost.is_none() {
+            return Err(SIEMError::Validation("Impact needs affected_users, downtime_minutes or estimated_cost".to_string()));
+        }
+        if self.downtime_minutes.is_some_and(|minutes| minutes > MAX_DOWNTIME_MINUTES) {
+            return Err(SIEMError::Validation(format!("downtime_minutes exceeds {}", MAX_DOWNTIME_MINUTES)));
+        }
+        if let Some(cost) = self.estimated_cost {
+            if !cost.is_finite() || !(0.0..=MAX_ESTIMATED_COST).contains(&cost) {
+                return Err(SIEMError::Validation(format!("estimated_cost must be between 0 and {}", MAX_ESTIMATED_COST)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Calendar quarter of a timestamp, e.g. `2024-Q2`
+pub fn quarter_of(time: DateTime<Utc>) -> String {
+    format!("{}-Q{}", time.year(), time.month0() / 3 + 1)
+}
+
+/// Impact totals of one quarter and category; category `all` for quarter totals
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImpactRollup {
+    pub quarter: String,
+    pub category: String,
+    pub incidents: u64,
+    pub affected_users: u64,
+    pub downtime_minutes: u64,
+    pub estimated_cost: f64,
+}
+
+impl ImpactRollup {
+    fn add(&mut self, impact: &IncidentImpact) {
+        self.incidents += 1;
+        self.affected_users += impact.affected_users.unwrap_or(0);
+        self.downtime_minutes += impact.downtime_minutes.unwrap_or(0);
+        self.estimated_cost += impact.estimated_cost.unwrap_or(0.0);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpactReport {
+    pub generated_at: DateTime<Utc>,
+    /// Per quarter and category, oldest quarter first
+    pub rollups: Vec<ImpactRollup>,
+    /// Per quarter across categories, oldest first
+    pub quarters: Vec<ImpactRollup>,
+    /// Incidents, false positives excluded, with impact recorded
+    pub tracked_incidents: u64,
+    pub untracked_incidents: u64,
+}
+
+impl ImpactReport {
+    pub fn build(incidents: &[Incident], now: DateTime<Utc>) -> Self {
+        let mut rollups: BTreeMap<(String, String), ImpactRollup> = BTreeMap::new();
+        let mut quarters: BTreeMap<String, ImpactRollup> = BTreeMap::new();
+        let (mut tracked, mut untracked) = (0, 0);
+        for incident in incidents.iter().filter(|i| !i.false_positive) {
+            let Some(impact) = &incident.impact else {
+                untracked += 1;
+                continue;
+            };
+            tracked += 1;
+            let quarter = quarter_of(incident.created_at);
+            let category = format!("{:?}", incident.threat_result.category);
+            rollups
+                .entry((quarter.clone(), category.clone()))
+                .or_insert_with(|| ImpactRollup { quarter: quarter.clone(), category, ..Default::default() })
+                .add(impact);
+            quarters
+                .entry(quarter.clone())
+                .or_insert_with(|| ImpactRollup { quarter, category: "all".to_string(), ..Default::default() })
+                .add(impact);
+        }
+        Self {
+            generated_at: now,
+            rollups: rollups.into_values().collect(),
+            quarters: quarters.into_values().collect(),
+            tracked_incidents: tracked,
+            untracked_incidents: untracked,
+        }
+    }
+
+    /// Per-quarter, per-category table for compliance reports
+    #[cfg(feature = "compliance")]
+    pub fn section(&self) -> ReportSection {
+        let mut lines = vec![format!(
+            "Impact recorded for {} of {} incidents",
+            self.tracked_incidents,
+            self.tracked_incidents + self.untracked_incidents
+        )];
+        for rollup in &self.rollups {
+            lines.push(format!(
+                "  {} {}: {} incidents, {} users, {} min downtime, cost {:.2}",
+                rollup.quarter, rollup.category, rollup.incidents, rollup.affected_users, rollup.downtime_minutes, rollup.estimated_cost
+            ));
+        }
+        ReportSection {
+            title: "Incident impact by quarter".to_string(),
+            generated_at: self.generated_at,
+            lines,
+        }
+    }
+
+    /// Headline figures of the latest quarter against the one before
+    #[cfg(feature = "compliance")]
+    pub fn executive_section(&self) -> ReportSection {
+        let mut lines = Vec::new();
+        match self.quarters.last() {
+            Some(latest) => {
+                lines.push(format!(
+                    "{}: {} incidents with impact, {} users affected, {:.1} h downtime, estimated cost {:.2}",
+                    latest.quarter,
+                    latest.incidents,
+                    latest.affected_users,
+                    latest.downtime_minutes as f64 / 60.0,
+                    latest.estimated_cost
+                ));
+                if let Some(previous) = self.quarters.iter().rev().nth(1).filter(|p| p.estimated_cost > 0.0) {
+                    let change = (latest.estimated_cost - previous.estimated_cost) / previous.estimated_cost * 100.0;
+                    lines.push(format!("Cost vs {}: {:+.1}%", previous.quarter, change));
+                }
+                let mut categories: Vec<&ImpactRollup> = self.rollups.iter().filter(|r| r.quarter == latest.quarter).collect();
+                categories.sort_by(|a, b| b.estimated_cost.total_cmp(&a.estimated_cost));
+                for rollup in categories.into_iter().take(3) {
+                    lines.push(format!("  {}: cost {:.2} across {} incidents", rollup.category, rollup.estimated_cost, rollup.incidents));
+                }
+            }
+            None => lines.push("No incident impact recorded yet".to_string()),
+        }
+        ReportSection {
+            title: "Executive summary: incident impact".to_string(),
+            generated_at: self.generated_at,
+            lines,
+        }
+    }
+
+    /// Refresh the impact sections of generated compliance reports
+    #[cfg(feature = "compliance")]
+    pub fn publish(&self, compliance: &ComplianceSecurityEngine) {
+        compliance.set_report_section("impact_executive", self.executive_section());
+        compliance.set_report_section("impact_rollup", self.section());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::incident_response::{IncidentSeverity, IncidentStatus};
+    use crate::threat_detection::ThreatCategory;
+
+    fn incident(created_at: DateTime<Utc>, category: ThreatCategory, impact: Option<IncidentImpact>) -> Incident {
+        Incident {
+            severity: IncidentSeverity::High,
+            status: IncidentStatus::Resolved,
+            threat_result: AdvancedThreatResult { category, ..Default::default() },
+            impact,
+            ..Incident::fixture(created_at)
+        }
+    }
+
+    #[test]
+    fn test_impact_validation() {
+        assert!(IncidentImpact::default().validate().is_err());
+        let cost = |estimated_cost: f64| IncidentImpact { estimated_cost: Some(estimated_cost), ..Default::default() };
+        assert!(cost(25_000.0).validate().is_ok());
+        assert!(cost(-1.0).validate().is_err());
+        assert!(cost(f64::NAN).validate().is_err());
+        let downtime = IncidentImpact { downtime_minutes: Some(MAX_DOWNTIME_MINUTES + 1), ..Default::default() };
+        assert!(downtime.validate().is_err());
+    }
+
+    #[test]
+    fn test_rollups_per_quarter_and_category() {
+        let impact = |users: u64, cost: f64| {
+            Some(IncidentImpact {
+                affected_users: Some(users),
+                downtime_minutes: Some(30),
+                estimated_cost: Some(cost),
+                ..Default::default()
+            })
+        };
+        let q1 = Utc.with_ymd_and_hms(2024, 2, 10, 0, 0, 0).unwrap();
+        let q2 = Utc.with_ymd_and_hms(2024, 5, 3, 0, 0, 0).unwrap();
+        let mut false_positive = incident(q2, ThreatCategory::Malware, impact(1, 1.0));
+        false_positive.false_positive = true;
+        let incidents = vec![
+            incident(q1, ThreatCategory::Malware, impact(10, 1000.0)),
+            incident(q2, ThreatCategory::Malware, impact(5, 500.0)),
+            incident(q2, ThreatCategory::Malware, impact(7, 700.0)),
+            incident(q2, ThreatCategory::DataExfiltration, impact(100, 9000.0)),
+            incident(q2, ThreatCategory::Malware, None),
+            false_positive,
+        ];
+        let report = ImpactReport::build(&incidents, q2);
+        assert_eq!((report.tracked_incidents, report.untracked_incidents), (4, 1));
+        assert_eq!(report.quarters.iter().map(|q| q.quarter.as_str()).collect::<Vec<_>>(), vec!["2024-Q1", "2024-Q2"]);
+        assert_eq!(report.quarters[1].estimated_cost, 10_200.0);
+        assert_eq!(report.quarters[1].downtime_minutes, 90);
+        let malware = report.rollups.iter().find(|r| r.quarter == "2024-Q2" && r.category == "Malware").unwrap();
+        assert_eq!((malware.incidents, malware.affected_users), (2, 12));
+
+        #[cfg(feature = "compliance")]
+        {
+            let summary = report.executive_section();
+            assert!(summary.lines[0].starts_with("2024-Q2: 3 incidents"));
+            assert!(summary.lines[1].contains("+920.0%"));
+            assert!(summary.lines[2].contains("DataExfiltration"));
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::{Incident, IncidentResponseEngine, IncidentSeverity, IncidentStatus, TimelineEntry};
+
+const INDEX_FILE: &str = "archive-index.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColdStoreKind {
+    /// A local or mounted directory
+    File,
+    /// HTTP object store: `PUT`/`GET`/`DELETE <store_url>/<key>`
+    Http,
+}
+
+/// Archival of old resolved incidents to cold storage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveSettings {
+    pub enabled: bool,
+    /// Incidents in these states are archived this long after resolution
+    pub archive_after_days: i64,
+    pub statuses: Vec<IncidentStatus>,
+    /// Archived incidents are deleted for good after this long
+    pub retention_days: i64,
+    /// A new incident sharing a source IP, user or host with an archived one
+    /// resolved at most this long ago reopens it
+    pub reopen_window_days: i64,
+    pub interval_hours: u64,
+    pub store: ColdStoreKind,
+    /// Searchable stubs; also the object directory of the `file` store
+    pub index_dir: String,
+    pub store_dir: String,
+    pub store_url: String,
+    pub auth_token: String,
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            archive_after_days: 30,
+            statuses: vec![IncidentStatus::Resolved, IncidentStatus::Closed, IncidentStatus::FalsePositive],
+            retention_days: 730,
+            reopen_window_days: 90,
+            interval_hours: 24,
+            store: ColdStoreKind::File,
+            index_dir: "data/archive".to_string(),
+            store_dir: "data/archive/objects".to_string(),
+            store_url: String::new(),
+            auth_token: String::new(),
+        }
+    }
+}
+
+/// Object storage holding full archived incident records
+pub trait ColdStore: Send + Sync {
+    fn put<'a>(&'a self, key: &'a str, body: Vec<u8>) -> BoxFuture<'a, SIEMResult<()>>;
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, SIEMResult<Vec<u8>>>;
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, SIEMResult<()>>;
+}
+
+pub struct FileColdStore {
+    root: PathBuf,
+}
+
+impl FileColdStore {
+    pub fn open(dir: &str) -> SIEMResult<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self { root: PathBuf::from(dir) })
+    }
+}
+
+impl ColdStore for FileColdStore {
+    fn put<'a>(&'a self, key: &'a str, body: Vec<u8>) -> BoxFuture<'a, SIEMResult<()>> {
+        Box::pin(async move { Ok(tokio::fs::write(self.root.join(key), body).await?) })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, SIEMResult<Vec<u8>>> {
+        Box::pin(async move { Ok(tokio::fs::read(self.root.join(key)).await?) })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, SIEMResult<()>> {
+        Box::pin(async move {
+            match tokio::fs::remove_file(self.root.join(key)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+pub struct HttpColdStore {
+    client: reqwest::Client,
+    base_url: String,
+    auth_token: String,
+}
+
+impl HttpColdStore {
+    pub fn new(settings: &ArchiveSettings) -> SIEMResult<Self> {
+        if !settings.store_url.starts_with("http://") && !settings.store_url.starts_with("https://") {
+            return Err(SIEMError::Config(format!("Archive store_url '{}' is not an http(s) URL", settings.store_url)));
+        }
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(Duration::from_secs(60)).build()?,
+            base_url: settings.store_url.trim_end_matches('/').to_string(),
+            auth_token: settings.auth_token.clone(),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, key: &str) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, format!("{}/{}", self.base_url, key));
+        if self.auth_token.is_empty() {
+            request
+        } else {
+            request.bearer_auth(&self.auth_token)
+        }
+    }
+}
+
+impl ColdStore for HttpColdStore {
+    fn put<'a>(&'a self, key: &'a str, body: Vec<u8>) -> BoxFuture<'a, SIEMResult<()>> {
+        Box::pin(async move {
+            self.request(reqwest::Method::PUT, key).body(body).send().await?.error_for_status()?;
+            Ok(())
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, SIEMResult<Vec<u8>>> {
+        Box::pin(async move { Ok(self.request(reqwest::Method::GET, key).send().await?.error_for_status()?.bytes().await?.to_vec()) })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, SIEMResult<()>> {
+        Box::pin(async move {
+            let response = self.request(reqwest::Method::DELETE, key).send().await?;
+            if response.status() != reqwest::StatusCode::NOT_FOUND {
+                response.error_for_status()?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// What stays searchable of an archived incident
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedIncidentStub {
+    pub id: String,
+    pub title: String,
+    pub severity: IncidentSeverity,
+    pub status: IncidentStatus,
+    pub category: String,
+    pub source_ip: String,
+    pub destination_ip: String,
+    pub user_id: String,
+    pub hostname: String,
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub resolved_at: DateTime<Utc>,
+    pub archived_at: DateTime<Utc>,
+    pub object_key: String,
+    pub sha256: String,
+}
+
+impl ArchivedIncidentStub {
+    fn new(incident: &Incident, resolved_at: DateTime<Utc>, archived_at: DateTime<Utc>, sha256: String) -> Self {
+        let mut tags: Vec<String> = incident.tags.iter().cloned().collect();
+        tags.sort();
+        Self {
+            id: incident.id.clone(),
+            title: incident.title.clone(),
+            severity: incident.severity.clone(),
+            status: incident.status.clone(),
+            category: incident.threat_result.category.to_string(),
+            source_ip: incident.source_ip.clone(),
+            destination_ip: incident.destination_ip.clone(),
+            user_id: incident.user_id.clone(),
+            hostname: hostname(incident).to_string(),
+            tags,
+            created_at: incident.created_at,
+            resolved_at,
+            archived_at,
+            object_key: format!("{}.json", incident.id),
+            sha256,
+        }
+    }
+
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        [&self.id, &self.title, &self.category, &self.source_ip, &self.destination_ip, &self.user_id, &self.hostname]
+            .into_iter()
+            .chain(&self.tags)
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+
+    /// Whether `incident` involves the same source IP, user or host
+    fn shares_entity(&self, incident: &Incident) -> bool {
+        let same = |archived: &str, current: &str| !archived.is_empty() && archived == current;
+        same(&self.source_ip, &incident.source_ip) || same(&self.user_id, &incident.user_id) || same(&self.hostname, hostname(incident))
+    }
+}
+
+fn hostname(incident: &Incident) -> &str {
+    let details = &incident.threat_result.details;
+    details.get("hostname").or_else(|| details.get("host")).map(String::as_str).unwrap_or_default()
+}
+
+/// Moves old resolved incidents to cold storage and brings them back on reopen
+pub struct IncidentArchive {
+    settings: ArchiveSettings,
+    store: Arc<dyn ColdStore>,
+    stubs: RwLock<HashMap<String, ArchivedIncidentStub>>,
+    index_path: PathBuf,
+}
+
+impl std::fmt::Debug for IncidentArchive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IncidentArchive").field("settings", &self.settings).finish_non_exhaustive()
+    }
+}
+
+impl IncidentArchive {
+    /// Open the configured cold store and load the stub index
+    pub fn open(settings: ArchiveSettings) -> SIEMResult<Self> {
+        let store: Arc<dyn ColdStore> = match settings.store {
+            ColdStoreKind::File => Arc::new(FileColdStore::open(&settings.store_dir)?),
+            ColdStoreKind::Http => Arc::new(HttpColdStore::new(&settings)?),
+        };
+        Self::with_store(settings, store)
+    }
+
+    pub fn with_store(settings: ArchiveSettings, store: Arc<dyn ColdStore>) -> SIEMResult<Self> {
+        std::fs::create_dir_all(&settings.index_dir)?;
+        let index_path = PathBuf::from(&settings.index_dir).join(INDEX_FILE);
+        let stubs = match std::fs::read(&index_path) {
+            Ok(raw) => serde_json::from_slice::<Vec<ArchivedIncidentStub>>(&raw)?.into_iter().map(|s| (s.id.clone(), s)).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { settings, store, stubs: RwLock::new(stubs), index_path })
+    }
+
+    /// Archive every incident due at `now` and drop archives past retention
+    pub async fn run_once(&self, incidents: &IncidentResponseEngine, now: DateTime<Utc>) -> SIEMResult<Vec<ArchivedIncidentStub>> {
+        let due_before = now - chrono::Duration::days(self.settings.archive_after_days);
+        let mut archived = Vec::new();
+        for incident in incidents.get_all_incidents() {
+            let resolved_at = incident.resolved_at.unwrap_or(incident.updated_at);
+            if !self.settings.statuses.contains(&incident.status) || resolved_at > due_before {
+                continue;
+            }
+            let body = serde_json::to_vec(&incident)?;
+            let stub = ArchivedIncidentStub::new(&incident, resolved_at, now, format!("{:x}", Sha256::digest(&body)));
+            // The hot copy goes only once the cold copy is written
+            self.store.put(&stub.object_key, body).await?;
+            if incidents.remove_incident(&incident.id).is_some() {
+                self.stubs.write().unwrap().insert(stub.id.clone(), stub.clone());
+                archived.push(stub);
+            }
+        }
+
+        let expire_before = now - chrono::Duration::days(self.settings.retention_days);
+        let expired: Vec<ArchivedIncidentStub> = self.stubs.read().unwrap().values().filter(|s| s.archived_at <= expire_before).cloned().collect();
+        for stub in &expired {
+            self.store.delete(&stub.object_key).await?;
+            self.stubs.write().unwrap().remove(&stub.id);
+        }
+        if !archived.is_empty() || !expired.is_empty() {
+            self.save_index()?;
+            info!("🧊 Archived {} incident(s), deleted {} past retention", archived.len(), expired.len());
+        }
+        Ok(archived)
+    }
+
+    /// Archived incidents whose stub contains `query`, newest first
+    pub fn search(&self, query: &str) -> Vec<ArchivedIncidentStub> {
+        let mut found: Vec<ArchivedIncidentStub> = self.stubs.read().unwrap().values().filter(|s| s.matches(query)).cloned().collect();
+        found.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        found
+    }
+
+    pub fn stub(&self, incident_id: &str) -> Option<ArchivedIncidentStub> {
+        self.stubs.read().unwrap().get(incident_id).cloned()
+    }
+
+    /// Rehydrate an archived incident into the live store as `Open`
+    pub async fn reopen(&self, incidents: &IncidentResponseEngine, incident_id: &str, reason: &str) -> SIEMResult<Incident> {
+        let stub = self
+            .stub(incident_id)
+            .ok_or_else(|| SIEMError::Validation(format!("Incident {} is not archived", incident_id)))?;
+        let body = self.store.get(&stub.object_key).await?;
+        if format!("{:x}", Sha256::digest(&body)) != stub.sha256 {
+            return Err(SIEMError::Validation(format!("Archived incident {} failed integrity check", incident_id)));
+        }
+        let mut incident: Incident = serde_json::from_slice(&body)?;
+        let now = Utc::now();
+        incident.status = IncidentStatus::Open;
+        incident.resolved_at = None;
+        incident.updated_at = now;
+        incident.timeline.push(TimelineEntry::new("reopened", format!("Reopened from archive: {}", reason)));
+        incidents.store_incident(incident.clone());
+
+        self.stubs.write().unwrap().remove(incident_id);
+        self.save_index()?;
+        if let Err(e) = self.store.delete(&stub.object_key).await {
+            warn!("⚠️ Archived copy of reopened incident {} not deleted: {}", incident_id, e);
+        }
+        info!("♻️ Reopened archived incident {}: {}", incident_id, reason);
+        Ok(incident)
+    }
+
+    /// Reopen archived incidents whose entities re-offend in `incident` within the reopen window
+    pub async fn reopen_reoffenders(&self, incidents: &IncidentResponseEngine, incident: &Incident) -> SIEMResult<Vec<Incident>> {
+        let window_start = incident.created_at - chrono::Duration::days(self.settings.reopen_window_days);
+        let candidates: Vec<String> = self
+            .stubs
+            .read()
+            .unwrap()
+            .values()
+            .filter(|stub| stub.resolved_at >= window_start && stub.shares_entity(incident))
+            .map(|stub| stub.id.clone())
+            .collect();
+        let mut reopened = Vec::new();
+        for id in candidates {
+            let reopened_incident = self.reopen(incidents, &id, &format!("same entities seen again in incident {}", incident.id)).await?;
+            incidents.add_timeline_entry(
+                &incident.id,
+                TimelineEntry::new("related_reopened", format!("Reopened archived incident {}", id)).with_reference(id.clone()),
+            )?;
+            reopened.push(reopened_incident);
+        }
+        Ok(reopened)
+    }
+
+    /// Archive on the configured cadence
+    pub fn spawn(self: Arc<Self>, incidents: Arc<IncidentResponseEngine>) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.settings.enabled {
+            return None;
+        }
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(self.settings.interval_hours.max(1) * 3600));
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.run_once(&incidents, Utc::now()).await {
+                    warn!("⚠️ Incident archival failed: {}", e);
+                }
+            }
+        }))
+    }
+
+    fn save_index(&self) -> SIEMResult<()> {
+        let stubs: Vec<ArchivedIncidentStub> = self.stubs.read().unwrap().values().cloned().collect();
+        let tmp = self.index_path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&stubs)?)?;
+        std::fs::rename(tmp, &self.index_path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::incident_response::{AlertConfig, SOARConfig};
+
+    fn archive() -> (Arc<IncidentArchive>, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("incident-archive-{}", uuid::Uuid::new_v4()));
+        let settings = ArchiveSettings {
+            enabled: true,
+            index_dir: dir.display().to_string(),
+            store_dir: dir.join("objects").display().to_string(),
+            ..Default::default()
+        };
+        (Arc::new(IncidentArchive::open(settings).unwrap()), dir)
+    }
+
+    async fn resolved_incident(engine: &IncidentResponseEngine, source_ip: &str) -> Incident {
+        let threat = AdvancedThreatResult { source_ip: source_ip.to_string(), ..Default::default() };
+        let incident = engine.process_threat(threat).await.unwrap();
+        engine.update_incident_status(&incident.id, IncidentStatus::Resolved).await.unwrap();
+        engine.get_incident(&incident.id).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_old_resolved_incidents_move_to_cold_storage() {
+        let (archive, dir) = archive();
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let resolved = resolved_incident(&engine, "203.0.113.9").await;
+        let open = engine.process_threat(AdvancedThreatResult::default()).await.unwrap();
+
+        // Not due yet
+        assert!(archive.run_once(&engine, Utc::now()).await.unwrap().is_empty());
+        let archived = archive.run_once(&engine, Utc::now() + chrono::Duration::days(31)).await.unwrap();
+        assert_eq!(archived.len(), 1);
+        assert!(engine.get_incident(&resolved.id).is_none());
+        assert!(engine.get_incident(&open.id).is_some());
+        assert_eq!(archive.search("203.0.113").len(), 1);
+
+        // The stub index survives a restart
+        let reloaded = IncidentArchive::open(archive.settings.clone()).unwrap();
+        let restored = reloaded.reopen(&engine, &resolved.id, "analyst request").await.unwrap();
+        assert_eq!(restored.status, IncidentStatus::Open);
+        assert_eq!(restored.timeline.len(), resolved.timeline.len() + 1);
+        assert_eq!(restored.threat_result.source_ip, "203.0.113.9");
+        assert!(engine.get_incident(&resolved.id).is_some());
+        assert!(reloaded.search("203.0.113").is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_reoffending_entity_reopens_within_window() {
+        let (archive, dir) = archive();
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        engine.set_archive(archive.clone());
+        let first = resolved_incident(&engine, "198.51.100.4").await;
+        let other = resolved_incident(&engine, "198.51.100.5").await;
+        archive.run_once(&engine, Utc::now() + chrono::Duration::days(31)).await.unwrap();
+
+        let repeat = engine
+            .process_threat(AdvancedThreatResult { source_ip: "198.51.100.4".to_string(), ..Default::default() })
+            .await
+            .unwrap();
+        assert_eq!(engine.get_incident(&first.id).unwrap().status, IncidentStatus::Open);
+        assert!(engine.get_incident(&other.id).is_none());
+        assert!(engine.get_incident(&repeat.id).unwrap().timeline.iter().any(|e| e.event == "related_reopened"));
+
+        // Past retention the archived copy is gone for good
+        archive.run_once(&engine, Utc::now() + chrono::Duration::days(31 + 731)).await.unwrap();
+        assert!(archive.stub(&other.id).is_none());
+        assert!(archive.reopen(&engine, &other.id, "too late").await.is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::access_scope::DataScope;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::{Incident, IncidentSeverity, IncidentStatus, TimelineEntry};
+
+/// Incidents a bulk operation applies to; all given criteria must match
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IncidentSelector {
+    /// Explicit incident ids; every one must exist
+    pub ids: Vec<String>,
+    pub status: Option<IncidentStatus>,
+    pub severity: Option<IncidentSeverity>,
+    /// Threat category, e.g. `SQLInjection`
+    pub category: Option<String>,
+    /// Detection method of the threat, e.g. the rule that fired
+    pub detection_method: Option<String>,
+    /// Exact values of threat details, e.g. `{"signature_id": "xss_script_tag"}`
+    pub details: HashMap<String, String>,
+    pub tag: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    /// Incidents the caller may touch, set by the API from its principal and never by clients
+    #[serde(skip)]
+    pub scope: Option<DataScope>,
+}
+
+impl IncidentSelector {
+    /// No criteria besides the caller's scope
+    pub fn is_empty(&self) -> bool {
+        Self { scope: None, ..self.clone() } == Self::default()
+    }
+
+    pub fn matches(&self, incident: &Incident) -> bool {
+        (self.ids.is_empty() || self.ids.contains(&incident.id))
+            && self.status.as_ref().is_none_or(|s| &incident.status == s)
+            && self.severity.as_ref().is_none_or(|s| &incident.severity == s)
+            && self.category.as_ref().is_none_or(|c| incident.threat_result.category.to_string().eq_ignore_ascii_case(c))
+            && self.detection_method.as_ref().is_none_or(|m| &incident.threat_result.detection_method == m)
+            && self.details.iter().all(|(k, v)| incident.threat_result.details.get(k) == Some(v))
+            && self.tag.as_ref().is_none_or(|t| incident.tags.contains(t))
+            && self.created_after.is_none_or(|t| incident.created_at >= t)
+            && self.created_before.is_none_or(|t| incident.created_at < t)
+            && self.scope.as_ref().is_none_or(|scope| scope.permits_incident(incident))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum BulkOperation {
+    SetStatus { status: IncidentStatus },
+    Assign { assigned_to: String },
+    Tag {
+        #[serde(default)]
+        add: Vec<String>,
+        #[serde(default)]
+        remove: Vec<String>,
+    },
+    MarkFalsePositive { reason: String },
+}
+
+impl BulkOperation {
+    pub fn validate(&self) -> SIEMResult<()> {
+        let invalid = |reason: &str| Err(SIEMError::Validation(format!("Bulk operation: {}", reason)));
+        match self {
+            BulkOperation::Assign { assigned_to } if assigned_to.trim().is_empty() => invalid("assigned_to is empty"),
+            BulkOperation::Tag { add, remove } if add.is_empty() && remove.is_empty() => invalid("no tags to add or remove"),
+            BulkOperation::Tag { add, remove } if add.iter().chain(remove).any(|t| t.trim().is_empty()) => invalid("empty tag"),
+            BulkOperation::MarkFalsePositive { reason } if reason.trim().is_empty() => invalid("a false positive needs a reason"),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether applying the operation would change `incident`
+    pub fn changes(&self, incident: &Incident) -> bool {
+        match self {
+            BulkOperation::SetStatus { status } => &incident.status != status,
+            BulkOperation::Assign { assigned_to } => incident.assigned_to.as_ref() != Some(assigned_to),
+            BulkOperation::Tag { add, remove } => {
+                add.iter().any(|t| !incident.tags.contains(t)) || remove.iter().any(|t| incident.tags.contains(t))
+            }
+            BulkOperation::MarkFalsePositive { .. } => !incident.false_positive,
+        }
+    }
+
+    /// Apply to one incident, recording a timeline entry that references the bulk operation
+    pub fn apply(&self, incident: &mut Incident, bulk_id: &str, now: DateTime<Utc>) {
+        let (event, message) = match self {
+            BulkOperation::SetStatus { status } => {
+                incident.status = status.clone();
+                if *status == IncidentStatus::Resolved {
+                    incident.resolved_at = Some(now);
+                }
+                ("status_changed", format!("Status changed to {:?}", status))
+            }
+            BulkOperation::Assign { assigned_to } => {
+                incident.assigned_to = Some(assigned_to.clone());
+                ("assigned", format!("Assigned to {}", assigned_to))
+            }
+            BulkOperation::Tag { add, remove } => {
+                incident.tags.extend(add.iter().cloned());
+                incident.tags.retain(|t| !remove.contains(t));
+                ("tags_changed", format!("Tags +{:?} -{:?}", add, remove))
+            }
+            BulkOperation::MarkFalsePositive { reason } => {
+                incident.false_positive = true;
+                incident.status = IncidentStatus::FalsePositive;
+                incident.notes.push(format!("Marked as false positive: {}", reason));
+                ("false_positive", reason.clone())
+            }
+        };
+        let mut entry = TimelineEntry::new(event, format!("{} (bulk)", message)).with_reference(bulk_id);
+        entry.timestamp = now;
+        incident.timeline.push(entry);
+        incident.updated_at = now;
+    }
+}
+
+/// What a bulk operation would touch; `selection_id` confirms the apply step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkPreview {
+    pub matched: usize,
+    /// Matched incidents the operation would actually change
+    pub changed: usize,
+    pub incident_ids: Vec<String>,
+    pub selection_id: String,
+}
+
+impl BulkPreview {
+    /// Preview of `operation` over `incidents`, which must be all incidents of the store
+    pub fn build<'a>(selector: &IncidentSelector, operation: &BulkOperation, incidents: impl Iterator<Item = &'a Incident>) -> SIEMResult<Self> {
+        if selector.is_empty() {
+            return Err(SIEMError::Validation("Bulk selector matches every incident; give ids or a filter".to_string()));
+        }
+        operation.validate()?;
+        let mut matched: Vec<&Incident> = incidents.filter(|incident| selector.matches(incident)).collect();
+        if let Some(missing) = selector.ids.iter().find(|id| !matched.iter().any(|i| &&i.id == id)) {
+            return Err(SIEMError::Validation(format!("Incident {} not found or not matching the filter", missing)));
+        }
+        matched.sort_by(|a, b| a.id.cmp(&b.id));
+        let incident_ids: Vec<String> = matched.iter().map(|i| i.id.clone()).collect();
+        Ok(Self {
+            matched: matched.len(),
+            changed: matched.iter().filter(|i| operation.changes(i)).count(),
+            selection_id: format!("{:x}", Sha256::digest(incident_ids.join("\n").as_bytes())),
+            incident_ids,
+        })
+    }
+}
+
+/// Apply step; `confirm` is the `selection_id` returned by the preview.
+/// The acting user comes from the caller, never from the request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkRequest {
+    #[serde(default)]
+    pub selector: IncidentSelector,
+    #[serde(flatten)]
+    pub operation: BulkOperation,
+    pub confirm: String,
+}
+
+/// The one audit entry written per bulk operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkAuditEntry {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub actor: String,
+    pub operation: BulkOperation,
+    pub selector: IncidentSelector,
+    pub matched: usize,
+    pub changed: usize,
+    pub incident_ids: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::incident_response::{AlertConfig, IncidentResponseEngine, SOARConfig};
+
+    async fn engine_with(methods: &[&str]) -> IncidentResponseEngine {
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        for method in methods {
+            let threat = AdvancedThreatResult { detection_method: method.to_string(), ..Default::default() };
+            engine.process_threat(threat).await.unwrap();
+        }
+        engine
+    }
+
+    #[tokio::test]
+    async fn test_preview_then_apply_writes_one_audit_entry() {
+        let engine = engine_with(&["bad_rule", "bad_rule", "bad_rule", "good_rule"]).await;
+        let selector = IncidentSelector { detection_method: Some("bad_rule".to_string()), ..Default::default() };
+        let operation = BulkOperation::MarkFalsePositive { reason: "rule matched health checks".to_string() };
+
+        let preview = engine.preview_bulk(&selector, &operation).unwrap();
+        assert_eq!((preview.matched, preview.changed), (3, 3));
+        let audit = engine
+            .apply_bulk(BulkRequest { selector: selector.clone(), operation: operation.clone(), confirm: preview.selection_id }, "alice")
+            .unwrap();
+        assert_eq!((audit.actor.as_str(), audit.changed), ("alice", 3));
+        assert_eq!(engine.bulk_audit_log().len(), 1);
+
+        for incident in engine.get_all_incidents() {
+            let bad = incident.threat_result.detection_method == "bad_rule";
+            assert_eq!(incident.status == IncidentStatus::FalsePositive, bad);
+            assert_eq!(incident.timeline.iter().any(|e| e.reference.as_deref() == Some(audit.id.as_str())), bad);
+        }
+        // Already applied: matched but nothing left to change
+        assert_eq!(engine.preview_bulk(&selector, &operation).unwrap().changed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_apply_is_all_or_nothing() {
+        let engine = engine_with(&["bad_rule", "bad_rule"]).await;
+        let selector = IncidentSelector { detection_method: Some("bad_rule".to_string()), ..Default::default() };
+        let operation = BulkOperation::Assign { assigned_to: "bob".to_string() };
+        let preview = engine.preview_bulk(&selector, &operation).unwrap();
+
+        // A new match since the preview invalidates the confirmation
+        engine.process_threat(AdvancedThreatResult { detection_method: "bad_rule".to_string(), ..Default::default() }).await.unwrap();
+        let request = BulkRequest { selector: selector.clone(), operation, confirm: preview.selection_id };
+        assert!(matches!(engine.apply_bulk(request.clone(), " "), Err(SIEMError::Validation(_))));
+        assert!(matches!(engine.apply_bulk(request, "alice"), Err(SIEMError::Validation(_))));
+
+        let unknown = IncidentSelector { ids: vec![engine.get_all_incidents()[0].id.clone(), "missing".to_string()], ..Default::default() };
+        assert!(engine.preview_bulk(&unknown, &BulkOperation::SetStatus { status: IncidentStatus::Closed }).is_err());
+        assert!(engine.preview_bulk(&IncidentSelector::default(), &BulkOperation::SetStatus { status: IncidentStatus::Closed }).is_err());
+        assert!(engine.get_all_incidents().iter().all(|i| i.assigned_to.is_none() && i.status == IncidentStatus::Open));
+        assert!(engine.bulk_audit_log().is_empty());
+    }
+}
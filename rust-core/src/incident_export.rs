@@ -0,0 +1,369 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64ct::{Base64, Encoding};
+use chrono::{TimeZone, Utc};
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+use crate::attack::techniques_of;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::Incident;
+
+const WRAP_INFO: &[u8] = b"ultra-siem incident bundle v1";
+
+/// Sharing of exported incident bundles
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IncidentExportSettings {
+    /// Refuse plaintext exports
+    pub require_encryption: bool,
+    /// X25519 public keys (base64) every export is encrypted to, e.g. the retained IR firm
+    pub default_recipients: Vec<String>,
+}
+
+impl IncidentExportSettings {
+    /// Serialize `incidents`, encrypted to `recipients` plus the defaults; `true` when encrypted
+    pub fn export(&self, incidents: &[Incident], format: IncidentExportFormat, recipients: &[String]) -> SIEMResult<(Vec<u8>, bool)> {
+        let plaintext = export_incidents(incidents, format)?;
+        let mut all = self.default_recipients.clone();
+        for recipient in recipients {
+            if !all.contains(recipient) {
+                all.push(recipient.clone());
+            }
+        }
+        if all.is_empty() {
+            if self.require_encryption {
+                return Err(SIEMError::Validation("Plaintext incident exports are disabled; pass a recipient".to_string()));
+            }
+            return Ok((plaintext, false));
+        }
+        Ok((encrypt_bundle(&plaintext, &all)?, true))
+    }
+}
+
+/// Serialization of an incident export
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IncidentExportFormat {
+    Json,
+    Csv,
+    Stix,
+}
+
+impl IncidentExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            IncidentExportFormat::Json | IncidentExportFormat::Stix => "application/json",
+            IncidentExportFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            IncidentExportFormat::Json => "json",
+            IncidentExportFormat::Csv => "csv",
+            IncidentExportFormat::Stix => "stix.json",
+        }
+    }
+}
+
+/// Serialize `incidents` in `format`
+pub fn export_incidents(incidents: &[Incident], format: IncidentExportFormat) -> SIEMResult<Vec<u8>> {
+    match format {
+        IncidentExportFormat::Json => Ok(serde_json::to_vec_pretty(incidents)?),
+        IncidentExportFormat::Csv => Ok(incidents_csv(incidents)),
+        IncidentExportFormat::Stix => Ok(serde_json::to_vec_pretty(&stix_bundle(incidents))?),
+    }
+}
+
+fn incidents_csv(incidents: &[Incident]) -> Vec<u8> {
+    let field = |value: &str| {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    };
+    let mut out = String::from(
+        "id,created_at,severity,status,title,source_ip,destination_ip,user_id,category,detection_method,assigned_to,false_positive,attack_techniques\r\n",
+    );
+    for incident in incidents {
+        let row = [
+            incident.id.clone(),
+            incident.created_at.to_rfc3339(),
+            incident.severity.to_string(),
+            format!("{:?}", incident.status),
+            incident.title.clone(),
+            incident.source_ip.clone(),
+            incident.destination_ip.clone(),
+            incident.user_id.clone(),
+            incident.threat_result.category.to_string(),
+            incident.threat_result.detection_method.clone(),
+            incident.assigned_to.clone().unwrap_or_default(),
+            incident.false_positive.to_string(),
+            techniques_of(&incident.threat_result).join(" "),
+        ];
+        let row: Vec<String> = row.iter().map(|value| field(value)).collect();
+        out.push_str(&row.join(","));
+        out.push_str("\r\n");
+    }
+    out.into_bytes()
+}
+
+/// STIX 2.1 bundle: one `incident` per incident, linked to its addresses and ATT&CK techniques
+fn stix_bundle(incidents: &[Incident]) -> serde_json::Value {
+    let stix_id = |kind: &str| format!("{}--{}", kind, uuid::Uuid::new_v4());
+    let identity = stix_id("identity");
+    let mut objects = vec![serde_json::json!({
+        "type": "identity", "spec_version": "2.1", "id": identity,
+        "created": Utc::now().to_rfc3339(), "modified": Utc::now().to_rfc3339(),
+        "name": "Ultra SIEM", "identity_class": "system"
+    })];
+    let relate = |objects: &mut Vec<serde_json::Value>, source: &str, kind: &str, target: &str, created: &str| {
+        objects.push(serde_json::json!({
+            "type": "relationship", "spec_version": "2.1", "id": stix_id("relationship"),
+            "created": created, "modified": created, "created_by_ref": identity,
+            "relationship_type": kind, "source_ref": source, "target_ref": target
+        }));
+    };
+
+    for incident in incidents {
+        let created = incident.created_at.to_rfc3339();
+        let id = if uuid::Uuid::parse_str(&incident.id).is_ok() {
+            format!("incident--{}", incident.id)
+        } else {
+            stix_id("incident")
+        };
+        let first_seen = Utc
+            .timestamp_opt(incident.threat_result.timestamp as i64, 0)
+            .single()
+            .unwrap_or(incident.created_at);
+        objects.push(serde_json::json!({
+            "type": "incident", "spec_version": "2.1", "id": id,
+            "created": created, "modified": incident.updated_at.to_rfc3339(), "created_by_ref": identity,
+            "name": incident.title, "description": incident.description,
+            "labels": [incident.severity.to_string().to_lowercase(), format!("{:?}", incident.status).to_lowercase()],
+            "external_references": [{ "source_name": "ultra-siem", "external_id": incident.id }],
+            "x_ultra_siem_first_seen": first_seen.to_rfc3339(),
+            "x_ultra_siem_category": incident.threat_result.category.to_string(),
+            "x_ultra_siem_confidence": incident.threat_result.confidence
+        }));
+        for address in [&incident.source_ip, &incident.destination_ip] {
+            let kind = match address.parse::<std::net::IpAddr>() {
+                Ok(std::net::IpAddr::V4(_)) => "ipv4-addr",
+                Ok(std::net::IpAddr::V6(_)) => "ipv6-addr",
+                Err(_) => continue,
+            };
+            let address_id = stix_id(kind);
+            objects.push(serde_json::json!({ "type": kind, "spec_version": "2.1", "id": address_id, "value": address }));
+            relate(&mut objects, &id, "related-to", &address_id, &created);
+        }
+        for technique in techniques_of(&incident.threat_result) {
+            let pattern_id = stix_id("attack-pattern");
+            objects.push(serde_json::json!({
+                "type": "attack-pattern", "spec_version": "2.1", "id": pattern_id,
+                "created": created, "modified": created, "name": technique,
+                "external_references": [{
+                    "source_name": "mitre-attack", "external_id": technique,
+                    "url": format!("https://attack.mitre.org/techniques/{}/", technique)
+                }]
+            }));
+            relate(&mut objects, &id, "uses", &pattern_id, &created);
+        }
+    }
+    serde_json::json!({ "type": "bundle", "id": stix_id("bundle"), "objects": objects })
+}
+
+/// Data key wrapped to one recipient
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrappedKey {
+    /// First 8 bytes of SHA-256 over the recipient public key, hex
+    pub recipient: String,
+    pub ephemeral_public: String,
+    pub nonce: String,
+    pub wrapped_key: String,
+}
+
+/// AES-256-GCM encrypted bundle with its data key wrapped per X25519 recipient
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedBundle {
+    pub version: u32,
+    pub cipher: String,
+    pub recipients: Vec<WrappedKey>,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// New X25519 identity as `(secret, public)`, both base64
+pub fn generate_identity() -> (String, String) {
+    let secret = StaticSecret::random_from_rng(OsRng);
+    let public = PublicKey::from(&secret);
+    (encode(secret.as_bytes()), encode(public.as_bytes()))
+}
+
+/// Encrypt `plaintext` so that any one of `recipients` can decrypt it
+pub fn encrypt_bundle(plaintext: &[u8], recipients: &[String]) -> SIEMResult<Vec<u8>> {
+    if recipients.is_empty() {
+        return Err(SIEMError::Validation("Encrypted export needs at least one recipient".to_string()));
+    }
+    let mut data_key = [0u8; 32];
+    OsRng.fill_bytes(&mut data_key);
+    let (nonce, ciphertext) = seal(&data_key, plaintext)?;
+
+    let mut wrapped = Vec::new();
+    for recipient in recipients {
+        let recipient_key = PublicKey::from(decode_key(recipient, "recipient public key")?);
+        let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+        let ephemeral_public = PublicKey::from(&ephemeral);
+        let shared = ephemeral.diffie_hellman(&recipient_key);
+        let wrap_key = derive_wrap_key(shared.as_bytes(), &ephemeral_public, &recipient_key)?;
+        let (wrap_nonce, wrapped_key) = seal(&wrap_key, &data_key)?;
+        wrapped.push(WrappedKey {
+            recipient: key_id(&recipient_key),
+            ephemeral_public: encode(ephemeral_public.as_bytes()),
+            nonce: encode(&wrap_nonce),
+            wrapped_key: encode(&wrapped_key),
+        });
+    }
+
+    Ok(serde_json::to_vec_pretty(&EncryptedBundle {
+        version: 1,
+        cipher: "AES-256-GCM+X25519-HKDF-SHA256".to_string(),
+        recipients: wrapped,
+        nonce: encode(&nonce),
+        ciphertext: encode(&ciphertext),
+    })?)
+}
+
+/// Decrypt a bundle with the base64 X25519 secret of one of its recipients
+pub fn decrypt_bundle(bundle: &[u8], identity: &str) -> SIEMResult<Vec<u8>> {
+    let bundle: EncryptedBundle = serde_json::from_slice(bundle)?;
+    if bundle.version != 1 {
+        return Err(SIEMError::Validation(format!("Unsupported bundle version {}", bundle.version)));
+    }
+    let secret = StaticSecret::from(decode_key(identity, "identity")?);
+    let public = PublicKey::from(&secret);
+    let id = key_id(&public);
+    let wrapped = bundle
+        .recipients
+        .iter()
+        .find(|w| w.recipient == id)
+        .ok_or_else(|| SIEMError::Auth("Bundle is not encrypted to this identity".to_string()))?;
+
+    let ephemeral_public = PublicKey::from(decode_key(&wrapped.ephemeral_public, "ephemeral key")?);
+    let shared = secret.diffie_hellman(&ephemeral_public);
+    let wrap_key = derive_wrap_key(shared.as_bytes(), &ephemeral_public, &public)?;
+    let data_key = open(&wrap_key, &decode(&wrapped.nonce)?, &decode(&wrapped.wrapped_key)?)?;
+    let data_key: [u8; 32] = data_key
+        .try_into()
+        .map_err(|_| SIEMError::Validation("Malformed wrapped key".to_string()))?;
+    open(&data_key, &decode(&bundle.nonce)?, &decode(&bundle.ciphertext)?)
+}
+
+fn derive_wrap_key(shared: &[u8], ephemeral: &PublicKey, recipient: &PublicKey) -> SIEMResult<[u8; 32]> {
+    let salt = [ephemeral.as_bytes().as_slice(), recipient.as_bytes().as_slice()].concat();
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(WRAP_INFO, &mut key)
+        .map_err(|_| SIEMError::Other("HKDF expand failed".to_string()))?;
+    Ok(key)
+}
+
+fn seal(key: &[u8; 32], plaintext: &[u8]) -> SIEMResult<([u8; 12], Vec<u8>)> {
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = Aes256Gcm::new(key.into())
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| SIEMError::Other("AES-GCM encryption failed".to_string()))?;
+    Ok((nonce, ciphertext))
+}
+
+fn open(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8]) -> SIEMResult<Vec<u8>> {
+    if nonce.len() != 12 {
+        return Err(SIEMError::Validation("Malformed nonce".to_string()));
+    }
+    Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| SIEMError::Auth("Bundle decryption failed: wrong key or tampered data".to_string()))
+}
+
+fn key_id(key: &PublicKey) -> String {
+    Sha256::digest(key.as_bytes())[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn encode(bytes: &[u8]) -> String {
+    let mut buf = vec![0u8; Base64::encoded_len(bytes)];
+    Base64::encode(bytes, &mut buf).map(str::to_string).unwrap_or_default()
+}
+
+fn decode(value: &str) -> SIEMResult<Vec<u8>> {
+    let mut buf = vec![0u8; value.len()];
+    let len = Base64::decode(value.trim(), &mut buf)
+        .map_err(|_| SIEMError::Validation("Malformed base64 in bundle".to_string()))?
+        .len();
+    buf.truncate(len);
+    Ok(buf)
+}
+
+fn decode_key(value: &str, what: &str) -> SIEMResult<[u8; 32]> {
+    decode(value)?
+        .try_into()
+        .map_err(|_| SIEMError::Validation(format!("Malformed {}: expected 32 bytes", what)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::incident_response::{AlertConfig, IncidentResponseEngine, SOARConfig};
+    use crate::threat_detection::ThreatCategory;
+
+    #[test]
+    fn test_bundle_decrypts_only_for_recipients() {
+        let (alice_secret, alice_public) = generate_identity();
+        let (bob_secret, bob_public) = generate_identity();
+        let (eve_secret, _) = generate_identity();
+
+        let bundle = encrypt_bundle(b"incident bundle", &[alice_public, bob_public]).unwrap();
+        assert_eq!(decrypt_bundle(&bundle, &alice_secret).unwrap(), b"incident bundle");
+        assert_eq!(decrypt_bundle(&bundle, &bob_secret).unwrap(), b"incident bundle");
+        assert!(decrypt_bundle(&bundle, &eve_secret).is_err());
+
+        let mut tampered: EncryptedBundle = serde_json::from_slice(&bundle).unwrap();
+        let mut ciphertext = decode(&tampered.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        tampered.ciphertext = encode(&ciphertext);
+        assert!(decrypt_bundle(&serde_json::to_vec(&tampered).unwrap(), &alice_secret).is_err());
+        assert!(encrypt_bundle(b"x", &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stix_and_csv_exports() {
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let incident = engine
+            .process_threat(AdvancedThreatResult {
+                category: ThreatCategory::BruteForce,
+                source_ip: "198.51.100.23".to_string(),
+                description: "ssh, \"password\" spraying".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let stix: serde_json::Value =
+            serde_json::from_slice(&export_incidents(std::slice::from_ref(&incident), IncidentExportFormat::Stix).unwrap()).unwrap();
+        assert_eq!(stix["type"], "bundle");
+        let objects = stix["objects"].as_array().unwrap();
+        assert!(objects.iter().any(|o| o["type"] == "incident" && o["id"] == format!("incident--{}", incident.id)));
+        assert!(objects.iter().any(|o| o["type"] == "attack-pattern" && o["external_references"][0]["external_id"] == "T1110"));
+        assert!(objects.iter().any(|o| o["type"] == "ipv4-addr" && o["value"] == "198.51.100.23"));
+
+        let csv = String::from_utf8(export_incidents(&[incident], IncidentExportFormat::Csv).unwrap()).unwrap();
+        assert_eq!(csv.lines().count(), 2);
+        assert!(csv.lines().nth(1).unwrap().ends_with(",T1110"));
+    }
+}
@@ -1,15 +1,39 @@
 use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, RwLock};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, LockResult, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
 use serde::{Deserialize, Serialize};
 use log::{info, warn, error};
-use tokio::sync::{mpsc};
+use tokio::sync::{mpsc, watch};
 use uuid::Uuid;
 use reqwest::Client;
 use chrono::{DateTime, Utc};
 
-use crate::error_handling::SIEMResult;
+use crate::error_handling::{SIEMError, SIEMResult};
 use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::agent_tasking::{runs_on_agent, AgentCommandResult, AgentDispatcher};
+use crate::canary_asset;
+use crate::cardinality::is_external;
+use crate::fatigue::{FatigueAnalyzer, FatigueReport, FatigueSettings};
+use crate::forensic::{ForensicJournal, HeldBackKind};
+use crate::response_metrics::{ResponseMetricsReport, ResponseMetricsSettings};
+use crate::chat_mirror::ChatMirror;
+use crate::incident_archive::IncidentArchive;
+use crate::incident_bulk::{BulkAuditEntry, BulkOperation, BulkPreview, BulkRequest, IncidentSelector};
+use crate::impact::{ImpactReport, IncidentImpact};
+use crate::intel_fusion::INTEL_CONFIDENCE_DETAIL;
+use crate::ip_net::{self, IpNetwork};
+use crate::degradation::DegradationController;
+use crate::related::{RelatedEventsEnricher, RelatedEventsSummary};
+use crate::response_verification::{self, ResponseVerificationSettings, VerificationOutcome};
+use crate::payload_template::{WebhookTemplateSettings, WebhookTemplates};
+use crate::routing::{AlertRouter, RoutingSettings};
+use crate::severity::{SeverityExplanation, SeverityPolicyEngine, SeveritySettings};
+use crate::shared_state::{SharedList, SharedState};
+use crate::smtp::{OutgoingMail, SmtpClient, SmtpSettings};
+use crate::tagging;
+use crate::timezone::TimeZones;
+use crate::triage::{TriageScore, TriageScorer, TriageSettings};
 
 /// Incident severity levels
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -47,7 +71,14 @@ pub enum IncidentStatus {
 /// Response action types
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum ResponseAction {
+    /// Block an address or CIDR range of either family
     BlockIP { ip: String, duration_seconds: u64 },
+    /// Block the incident's source address; private ranges only with `block_private`
+    BlockSourceIP {
+        duration_seconds: u64,
+        #[serde(default)]
+        block_private: bool,
+    },
     DisableAccount { user_id: String, reason: String },
     QuarantineFile { file_path: String, hash: String },
     KillProcess { process_id: u32, reason: String },
@@ -57,6 +88,17 @@ pub enum ResponseAction {
     GrafanaAlert { dashboard_id: String, panel_id: String },
     CustomScript { script_path: String, args: Vec<String> },
     LogOnly { message: String },
+    /// Run `action` on the collector agent of `host_id`, by default the incident's host.
+    /// The result is pending until the agent reports back.
+    RunOnAgent {
+        #[serde(default)]
+        host_id: Option<String>,
+        action: Box<ResponseAction>,
+    },
+    /// Running processes of the host; collected by its agent for playbook steps
+    CollectProcessList,
+    /// Active firewall ruleset of the host; collected by its agent for playbook steps
+    SnapshotFirewallRules,
 }
 
 /// Response action result
@@ -69,6 +111,36 @@ pub struct ResponseActionResult {
     pub execution_time_ms: u64,
     pub timestamp: u64,
     pub metadata: HashMap<String, String>,
+    /// Whether the action was seen to take effect; `None` when not checked
+    #[serde(default)]
+    pub verification: Option<VerificationOutcome>,
+}
+
+/// Entry on an incident's timeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineEntry {
+    pub timestamp: DateTime<Utc>,
+    /// Machine-readable kind, e.g. `created`, `status_changed`, `evidence_attached`
+    pub event: String,
+    pub message: String,
+    /// Id of a related object such as an evidence attachment
+    pub reference: Option<String>,
+}
+
+impl TimelineEntry {
+    pub fn new(event: &str, message: impl Into<String>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            event: event.to_string(),
+            message: message.into(),
+            reference: None,
+        }
+    }
+
+    pub fn with_reference(mut self, reference: impl Into<String>) -> Self {
+        self.reference = Some(reference.into());
+        self
+    }
 }
 
 /// Incident structure
@@ -95,6 +167,56 @@ pub struct Incident {
     pub false_positive: bool,
     pub escalation_level: u8,
     pub sla_deadline: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub timeline: Vec<TimelineEntry>,
+    /// Analyst queue priority and its factor breakdown
+    #[serde(default)]
+    pub triage: Option<TriageScore>,
+    /// Policy inputs and steps that produced `severity`
+    #[serde(default)]
+    pub severity_explanation: Option<SeverityExplanation>,
+    /// Business impact recorded by analysts
+    #[serde(default)]
+    pub impact: Option<IncidentImpact>,
+    /// Recent events of the same source, user or host at creation time
+    #[serde(default)]
+    pub related_events: Option<RelatedEventsSummary>,
+}
+
+#[cfg(test)]
+impl Incident {
+    /// Open, medium-severity incident created at `created_at` with every other field empty,
+    /// for tests to override with struct update syntax
+    pub(crate) fn fixture(created_at: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            timestamp: created_at.timestamp() as u64,
+            severity: IncidentSeverity::Medium,
+            status: IncidentStatus::Open,
+            title: String::new(),
+            description: String::new(),
+            source_ip: String::new(),
+            destination_ip: String::new(),
+            user_id: String::new(),
+            threat_id: String::new(),
+            threat_result: AdvancedThreatResult::default(),
+            response_actions: Vec::new(),
+            assigned_to: None,
+            notes: Vec::new(),
+            tags: HashSet::new(),
+            created_at,
+            updated_at: created_at,
+            resolved_at: None,
+            false_positive: false,
+            escalation_level: 0,
+            sla_deadline: None,
+            timeline: Vec::new(),
+            triage: None,
+            severity_explanation: None,
+            impact: None,
+            related_events: None,
+        }
+    }
 }
 
 /// Alert configuration
@@ -121,6 +243,33 @@ pub struct AlertConfig {
     pub pagerduty_service_id: String,
 }
 
+impl AlertConfig {
+    /// Every channel off, for engines that must never page anyone
+    pub fn disabled() -> Self {
+        Self {
+            email_enabled: false,
+            email_smtp_server: String::new(),
+            email_smtp_port: 587,
+            email_username: String::new(),
+            email_password: String::new(),
+            email_from: String::new(),
+            email_to: Vec::new(),
+            webhook_enabled: false,
+            webhook_urls: Vec::new(),
+            grafana_enabled: false,
+            grafana_url: String::new(),
+            grafana_api_key: String::new(),
+            slack_enabled: false,
+            slack_webhook_url: String::new(),
+            teams_enabled: false,
+            teams_webhook_url: String::new(),
+            pagerduty_enabled: false,
+            pagerduty_api_key: String::new(),
+            pagerduty_service_id: String::new(),
+        }
+    }
+}
+
 /// Response rule configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResponseRule {
@@ -155,13 +304,77 @@ pub struct SOARConfig {
     pub custom_headers: HashMap<String, String>,
 }
 
+impl SOARConfig {
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            platform: "custom".to_string(),
+            api_url: String::new(),
+            api_key: String::new(),
+            timeout_seconds: 30,
+            retry_attempts: 0,
+            custom_headers: HashMap::new(),
+        }
+    }
+}
+
+/// Incident map whose revision advances after every write, so listings can be cached until it changes
+#[derive(Debug)]
+struct IncidentStore {
+    incidents: RwLock<HashMap<String, Incident>>,
+    revision: watch::Sender<u64>,
+}
+
+impl IncidentStore {
+    fn new() -> Self {
+        Self { incidents: RwLock::new(HashMap::new()), revision: watch::channel(0).0 }
+    }
+
+    fn read(&self) -> LockResult<RwLockReadGuard<'_, HashMap<String, Incident>>> {
+        self.incidents.read()
+    }
+
+    fn write(&self) -> LockResult<IncidentWriteGuard<'_>> {
+        let wrap = |guard| IncidentWriteGuard { guard: Some(guard), revision: &self.revision };
+        self.incidents.write().map(wrap).map_err(|poisoned| PoisonError::new(wrap(poisoned.into_inner())))
+    }
+}
+
+/// Write access to the incidents; bumps the revision once the lock is released
+struct IncidentWriteGuard<'a> {
+    guard: Option<RwLockWriteGuard<'a, HashMap<String, Incident>>>,
+    revision: &'a watch::Sender<u64>,
+}
+
+impl Deref for IncidentWriteGuard<'_> {
+    type Target = HashMap<String, Incident>;
+
+    fn deref(&self) -> &Self::Target {
+        self.guard.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for IncidentWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.guard.as_mut().unwrap()
+    }
+}
+
+impl Drop for IncidentWriteGuard<'_> {
+    fn drop(&mut self) {
+        // Readers that saw the old revision must not cache what they read under the new one
+        drop(self.guard.take());
+        self.revision.send_modify(|revision| *revision += 1);
+    }
+}
+
 /// Incident Response Engine
 #[derive(Debug)]
 pub struct IncidentResponseEngine {
     config: AlertConfig,
     soar_config: SOARConfig,
     response_rules: Arc<RwLock<HashMap<String, ResponseRule>>>,
-    incidents: Arc<RwLock<HashMap<String, Incident>>>,
+    incidents: Arc<IncidentStore>,
     blocked_ips: Arc<RwLock<HashMap<String, u64>>>,
     disabled_accounts: Arc<RwLock<HashMap<String, u64>>>,
     http_client: Client,
@@ -171,18 +384,57 @@ pub struct IncidentResponseEngine {
     response_rx: mpsc::Receiver<ResponseMessage>,
     performance_metrics: Arc<RwLock<HashMap<String, f64>>>,
     incident_counter: Arc<RwLock<u64>>,
+    triage: Arc<RwLock<TriageScorer>>,
+    severity: Arc<RwLock<SeverityPolicyEngine>>,
+    fatigue: Arc<RwLock<FatigueAnalyzer>>,
+    response_metrics: Arc<RwLock<ResponseMetricsSettings>>,
+    router: Arc<AlertRouter>,
+    time_zones: Arc<RwLock<Arc<TimeZones>>>,
+    webhook_templates: Arc<RwLock<Arc<WebhookTemplates>>>,
+    related_events: Arc<RwLock<Option<Arc<RelatedEventsEnricher>>>>,
+    degradation: Arc<RwLock<Option<Arc<DegradationController>>>>,
+    agents: Arc<RwLock<Option<Arc<AgentDispatcher>>>>,
+    bulk_audit: Arc<RwLock<Vec<BulkAuditEntry>>>,
+    archive: Arc<RwLock<Option<Arc<IncidentArchive>>>>,
+    shared_state: Arc<RwLock<Option<Arc<SharedState>>>>,
+    chat_mirror: Arc<RwLock<Option<Arc<ChatMirror>>>>,
+    verification: Arc<RwLock<ResponseVerificationSettings>>,
+    forensic: Arc<RwLock<Option<Arc<ForensicJournal>>>>,
 }
 
 /// Alert message for internal communication
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AlertMessage {
     pub id: Uuid,
     pub severity: IncidentSeverity,
     pub message: String,
     pub timestamp: DateTime<Utc>,
+    /// Threat category of the incident, e.g. `Malware`
+    pub category: String,
+    pub tenant: Option<String>,
+    /// Tags of the incident, from tagging rules and analysts
+    pub tags: Vec<String>,
+    /// Raised by a canary account or host; escalated past quiet hours
+    pub canary: bool,
 }
 
-#[derive(Debug)]
+impl AlertMessage {
+    pub fn new(severity: IncidentSeverity, message: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            severity,
+            message,
+            timestamp: Utc::now(),
+            category: String::new(),
+            tenant: None,
+            tags: Vec::new(),
+            canary: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum AlertChannel {
     Email,
     Webhook,
@@ -215,7 +467,7 @@ impl IncidentResponseEngine {
             config,
             soar_config,
             response_rules: Arc::new(RwLock::new(HashMap::new())),
-            incidents: Arc::new(RwLock::new(HashMap::new())),
+            incidents: Arc::new(IncidentStore::new()),
             blocked_ips: Arc::new(RwLock::new(HashMap::new())),
             disabled_accounts: Arc::new(RwLock::new(HashMap::new())),
             http_client,
@@ -225,9 +477,133 @@ impl IncidentResponseEngine {
             response_rx,
             performance_metrics: Arc::new(RwLock::new(HashMap::new())),
             incident_counter: Arc::new(RwLock::new(0)),
+            triage: Arc::new(RwLock::new(TriageScorer::default())),
+            severity: Arc::new(RwLock::new(SeverityPolicyEngine::default())),
+            fatigue: Arc::new(RwLock::new(FatigueAnalyzer::default())),
+            response_metrics: Arc::new(RwLock::new(ResponseMetricsSettings::default())),
+            router: Arc::new(AlertRouter::default()),
+            time_zones: Arc::new(RwLock::new(Arc::default())),
+            webhook_templates: Arc::new(RwLock::new(Arc::default())),
+            related_events: Arc::new(RwLock::new(None)),
+            degradation: Arc::new(RwLock::new(None)),
+            agents: Arc::new(RwLock::new(None)),
+            bulk_audit: Arc::new(RwLock::new(Vec::new())),
+            archive: Arc::new(RwLock::new(None)),
+            shared_state: Arc::new(RwLock::new(None)),
+            chat_mirror: Arc::new(RwLock::new(None)),
+            verification: Arc::new(RwLock::new(ResponseVerificationSettings::default())),
+            forensic: Arc::new(RwLock::new(None)),
         }
     }
 
+    /// Replace the triage formula used to prioritise new incidents
+    pub fn set_triage_settings(&self, settings: TriageSettings) {
+        *self.triage.write().unwrap() = TriageScorer::new(settings);
+    }
+
+    /// Replace the severity policies applied to new incidents
+    pub fn set_severity_settings(&self, settings: SeveritySettings) -> SIEMResult<()> {
+        *self.severity.write().unwrap() = SeverityPolicyEngine::new(settings)?;
+        Ok(())
+    }
+
+    /// Replace the alarm fatigue analytics settings
+    pub fn set_fatigue_settings(&self, settings: FatigueSettings) {
+        *self.fatigue.write().unwrap() = FatigueAnalyzer::new(settings);
+    }
+
+    /// Replace the notification routing matrix and quiet hours
+    pub fn set_routing_settings(&self, settings: RoutingSettings) -> SIEMResult<()> {
+        self.router.update(settings)
+    }
+
+    /// Replace the default, tenant and analyst time zones used for routing hours and rendered times
+    pub fn set_time_zones(&self, zones: Arc<TimeZones>) {
+        self.router.set_time_zones(zones.clone());
+        *self.time_zones.write().unwrap() = zones;
+    }
+
+    pub fn time_zones(&self) -> Arc<TimeZones> {
+        self.time_zones.read().unwrap().clone()
+    }
+
+    /// Replace the per-destination templates webhook and SOAR payloads are shaped with
+    pub fn set_webhook_templates(&self, settings: WebhookTemplateSettings) -> SIEMResult<()> {
+        *self.webhook_templates.write().unwrap() = Arc::new(WebhookTemplates::new(settings)?);
+        Ok(())
+    }
+
+    /// Replace the MTTD/MTTA/MTTR windows and SLO definitions
+    pub fn set_response_metrics_settings(&self, settings: ResponseMetricsSettings) {
+        *self.response_metrics.write().unwrap() = settings;
+    }
+
+    /// Replace the checks run after response actions to confirm they took effect
+    pub fn set_verification_settings(&self, settings: ResponseVerificationSettings) {
+        *self.verification.write().unwrap() = settings;
+    }
+
+    /// Add or replace a response rule by id
+    pub fn add_response_rule(&self, rule: ResponseRule) {
+        self.response_rules.write().unwrap().insert(rule.id.clone(), rule);
+    }
+
+    /// Attach a summary of recent related events to new incidents
+    pub fn set_related_events(&self, enricher: Arc<RelatedEventsEnricher>) {
+        *self.related_events.write().unwrap() = Some(enricher);
+    }
+
+    /// Bound enrichment lookups by the degradation deadline, tagging incidents that miss it
+    pub fn set_degradation(&self, degradation: Arc<DegradationController>) {
+        *self.degradation.write().unwrap() = Some(degradation);
+    }
+
+    /// Send `RunOnAgent` actions to remote agents through `dispatcher`
+    pub fn set_agent_dispatcher(&self, dispatcher: Arc<AgentDispatcher>) {
+        *self.agents.write().unwrap() = Some(dispatcher);
+    }
+
+    /// Reopen archived incidents when their entities re-offend
+    pub fn set_archive(&self, archive: Arc<IncidentArchive>) {
+        *self.archive.write().unwrap() = Some(archive);
+    }
+
+    pub fn archive(&self) -> Option<Arc<IncidentArchive>> {
+        self.archive.read().unwrap().clone()
+    }
+
+    /// Share blocked IPs and disabled accounts with the other cores
+    pub fn set_shared_state(&self, shared_state: Arc<SharedState>) {
+        *self.shared_state.write().unwrap() = Some(shared_state);
+    }
+
+    pub fn shared_state(&self) -> Option<Arc<SharedState>> {
+        self.shared_state.read().unwrap().clone()
+    }
+
+    /// Mirror severe incidents into their own chat channel or thread
+    pub fn set_chat_mirror(&self, mirror: Arc<ChatMirror>) {
+        *self.chat_mirror.write().unwrap() = Some(mirror);
+    }
+
+    pub fn chat_mirror(&self) -> Option<Arc<ChatMirror>> {
+        self.chat_mirror.read().unwrap().clone()
+    }
+
+    /// Journal response actions, alerts and SOAR calls instead of performing them
+    pub fn set_forensic_journal(&self, journal: Arc<ForensicJournal>) {
+        *self.forensic.write().unwrap() = Some(journal).filter(|journal| journal.enabled());
+    }
+
+    /// The forensic journal while forensic mode is on
+    pub fn forensic_journal(&self) -> Option<Arc<ForensicJournal>> {
+        self.forensic.read().unwrap().clone()
+    }
+
+    pub fn agent_dispatcher(&self) -> Option<Arc<AgentDispatcher>> {
+        self.agents.read().unwrap().clone()
+    }
+
     /// Start the incident response engine
     pub async fn start(&mut self) -> SIEMResult<()> {
         info!("🚀 Starting Ultra SIEM Incident Response Engine...");
@@ -237,8 +613,9 @@ impl IncidentResponseEngine {
         
         // Start alert processing
         let mut alert_rx = std::mem::replace(&mut self.alert_rx, tokio::sync::mpsc::channel(1000).1);
+        let router = self.router.clone();
         tokio::spawn(async move {
-            Self::process_alerts(&mut alert_rx).await;
+            Self::process_alerts(&mut alert_rx, router).await;
         });
         
         // Start response processing
@@ -256,7 +633,28 @@ impl IncidentResponseEngine {
         let start_time = std::time::Instant::now();
         
         // Create incident from threat
-        let incident = self.create_incident_from_threat(threat).await?;
+        let mut incident = self.create_incident_from_threat(threat).await?;
+        
+        // Start investigations from recent activity rather than a bare alert
+        let enricher = self.related_events.read().unwrap().clone();
+        if let Some(enricher) = enricher {
+            let lookup = enricher.summarize(&incident.threat_result, incident.created_at);
+            let degradation = self.degradation.read().unwrap().clone();
+            let summarized = match degradation {
+                Some(degradation) => {
+                    let mut threat = incident.threat_result.clone();
+                    let summarized = degradation.enrich_within_deadline("related_events", &mut threat, lookup).await;
+                    incident.threat_result = threat;
+                    summarized
+                }
+                None => Some(lookup.await),
+            };
+            match summarized {
+                Some(Ok(summary)) => incident.related_events = summary,
+                Some(Err(e)) => warn!("⚠️ Incident {} created without related events: {}", incident.id, e),
+                None => warn!("⚠️ Incident {} created without related events: lookup missed the enrichment deadline", incident.id),
+            }
+        }
         
         // Evaluate response rules
         let actions = self.evaluate_response_rules(&incident).await?;
@@ -273,6 +671,12 @@ impl IncidentResponseEngine {
             let mut incidents = self.incidents.write().unwrap();
             incidents.insert(incident.id.clone(), updated_incident.clone());
         }
+        self.dispatch_agent_actions(&updated_incident);
+        if let Some(archive) = self.archive() {
+            if let Err(e) = archive.reopen_reoffenders(self, &updated_incident).await {
+                warn!("⚠️ Archived incidents related to {} not reopened: {}", updated_incident.id, e);
+            }
+        }
         
         // Send alerts
         self.send_alerts(&updated_incident).await?;
@@ -293,12 +697,14 @@ impl IncidentResponseEngine {
         let now = Utc::now();
         
         // Determine incident severity
-        let severity = match threat.severity {
-            crate::threat_detection::ThreatSeverity::Low => IncidentSeverity::Low,
-            crate::threat_detection::ThreatSeverity::Medium => IncidentSeverity::Medium,
-            crate::threat_detection::ThreatSeverity::High => IncidentSeverity::High,
-            crate::threat_detection::ThreatSeverity::Critical => IncidentSeverity::Critical,
-        };
+        let triage = self.triage.read().unwrap().score(&threat);
+        let mut severity_explanation = self.severity.read().unwrap().evaluate(&threat, &triage);
+        // Nobody legitimately uses a canary, so no policy may rate its use below Critical
+        if canary_asset::is_canary_hit(&threat) && severity_explanation.severity < IncidentSeverity::Critical {
+            severity_explanation.severity = IncidentSeverity::Critical;
+            severity_explanation.fallback = Some("canary asset use is always Critical".to_string());
+        }
+        let severity = severity_explanation.severity.clone();
         
         // Calculate escalation level
         let escalation_level = match severity {
@@ -324,6 +730,10 @@ impl IncidentResponseEngine {
             *counter += 1;
         }
         
+        let tags = tagging::detail_tags(&threat).map(str::to_string).collect();
+        let created = TimelineEntry::new("created", format!("Created from {} detection", threat.detection_method))
+            .with_reference(threat.threat_id.clone());
+        
         Ok(Incident {
             id: incident_id,
             timestamp,
@@ -339,13 +749,18 @@ impl IncidentResponseEngine {
             response_actions: Vec::new(),
             assigned_to: None,
             notes: Vec::new(),
-            tags: HashSet::new(),
+            tags,
             created_at: now,
             updated_at: now,
             resolved_at: None,
             false_positive: false,
             escalation_level,
             sla_deadline,
+            timeline: vec![created],
+            triage: Some(triage),
+            severity_explanation: Some(severity_explanation),
+            impact: None,
+            related_events: None,
         })
     }
 
@@ -382,10 +797,20 @@ impl IncidentResponseEngine {
             let field_value = match condition.field.as_str() {
                 "severity" => incident.severity.to_string(),
                 "source_ip" => incident.source_ip.clone(),
+                "destination_ip" => incident.destination_ip.clone(),
                 "user_id" => incident.user_id.clone(),
                 "category" => incident.threat_result.category.to_string(),
                 "confidence" => incident.threat_result.confidence.to_string(),
-                _ => continue,
+                "detection_method" => incident.threat_result.detection_method.clone(),
+                // Fused multi-feed confidence of the matched indicator
+                "intel_confidence" => match incident.threat_result.details.get(INTEL_CONFIDENCE_DETAIL) {
+                    Some(confidence) => confidence.clone(),
+                    None => return false,
+                },
+                field => match field.strip_prefix("details.") {
+                    Some(key) => incident.threat_result.details.get(key).cloned().unwrap_or_default(),
+                    None => continue,
+                },
             };
             
             let condition_value = if condition.case_sensitive {
@@ -405,6 +830,10 @@ impl IncidentResponseEngine {
                 "contains" => field_value.contains(&condition_value),
                 "starts_with" => field_value.starts_with(&condition_value),
                 "ends_with" => field_value.ends_with(&condition_value),
+                // Comma-separated addresses or CIDR ranges, e.g. "10.0.0.0/8, 2001:db8::/32"
+                "in_network" => ip_net::parse_ip(&field_value).is_some_and(|ip| {
+                    condition_value.split(',').any(|network| ip_net::network_contains(network.trim(), ip))
+                }),
                 "greater_than" => {
                     if let (Ok(field_num), Ok(condition_num)) = (field_value.parse::<f64>(), condition_value.parse::<f64>()) {
                         field_num > condition_num
@@ -433,15 +862,35 @@ impl IncidentResponseEngine {
     /// Execute response actions
     async fn execute_response_actions(&self, incident: &Incident, actions: Vec<ResponseAction>) -> SIEMResult<Vec<ResponseActionResult>> {
         let mut results = Vec::new();
+        let forensic = self.forensic_journal();
         
-        for action in actions {
+        for mut action in actions {
             let start_time = std::time::Instant::now();
             let action_id = Uuid::new_v4().to_string();
+            let mut dispatched = None;
+            
+            if let Some(journal) = &forensic {
+                journal.record(HeldBackKind::ResponseAction, "response_action", format!("{:?}", action), Some(&incident.id));
+                results.push(ResponseActionResult {
+                    action_id,
+                    action_type: action,
+                    success: false,
+                    error_message: Some("held back in forensic mode".to_string()),
+                    execution_time_ms: 0,
+                    timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+                    metadata: HashMap::from([("forensic".to_string(), "held_back".to_string())]),
+                    verification: None,
+                });
+                continue;
+            }
             
             let result = match &action {
                 ResponseAction::BlockIP { ip, duration_seconds } => {
                     self.block_ip(ip, *duration_seconds).await
                 }
+                ResponseAction::BlockSourceIP { duration_seconds, block_private } => {
+                    self.block_source_ip(&incident.source_ip, *duration_seconds, *block_private).await
+                }
                 ResponseAction::DisableAccount { user_id, reason } => {
                     self.disable_account(user_id, reason).await
                 }
@@ -458,7 +907,7 @@ impl IncidentResponseEngine {
                     self.send_email(to, subject, body).await
                 }
                 ResponseAction::WebhookNotification { url, payload } => {
-                    self.send_webhook(url, payload).await
+                    self.send_webhook(url, payload, incident).await
                 }
                 ResponseAction::GrafanaAlert { dashboard_id, panel_id } => {
                     self.send_grafana_alert(dashboard_id, panel_id, incident).await
@@ -469,18 +918,41 @@ impl IncidentResponseEngine {
                 ResponseAction::LogOnly { message } => {
                     self.log_only(message).await
                 }
+                ResponseAction::RunOnAgent { host_id, action: remote } => {
+                    self.prepare_agent_action(incident, host_id.as_deref(), remote).map(|resolved| dispatched = Some(resolved))
+                }
+                ResponseAction::CollectProcessList | ResponseAction::SnapshotFirewallRules => {
+                    Err(format!("{:?} only runs on an agent from a playbook step", action).into())
+                }
             };
             
             let execution_time = start_time.elapsed().as_millis() as u64;
+            // Agent actions only succeed once the agent reports back
+            let pending = dispatched.is_some();
+            let mut metadata = HashMap::new();
+            if let Some(resolved) = dispatched {
+                action = resolved;
+                metadata.insert("agent_status".to_string(), "pending".to_string());
+            }
+            
+            let verification = match result.is_ok() && !pending {
+                true => self.verify_action(incident, &action).await,
+                false => None,
+            };
+            let mut error_message = result.as_ref().err().map(|e| e.to_string());
+            if let Some(outcome) = verification.as_ref().filter(|outcome| !outcome.verified) {
+                error_message = Some(format!("verification failed: {}", outcome.detail));
+            }
             
             let action_result = ResponseActionResult {
                 action_id,
                 action_type: action,
-                success: result.is_ok(),
-                error_message: result.err().map(|e| e.to_string()),
+                success: result.is_ok() && !pending && verification.as_ref().is_none_or(|outcome| outcome.verified),
+                error_message,
                 execution_time_ms: execution_time,
                 timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
-                metadata: HashMap::new(),
+                metadata,
+                verification,
             };
             
             results.push(action_result);
@@ -489,26 +961,191 @@ impl IncidentResponseEngine {
         Ok(results)
     }
 
+    /// Check that `action` took effect, re-running it while it has not; alerts when it never does
+    async fn verify_action(&self, incident: &Incident, action: &ResponseAction) -> Option<VerificationOutcome> {
+        let settings = self.verification.read().unwrap().clone();
+        if !settings.enabled {
+            return None;
+        }
+        let action = match action {
+            ResponseAction::BlockSourceIP { duration_seconds, .. } => ResponseAction::BlockIP {
+                ip: incident.source_ip.clone(),
+                duration_seconds: *duration_seconds,
+            },
+            action => action.clone(),
+        };
+        let outcome = response_verification::verify(
+            &settings,
+            || response_verification::check_action(&action, &settings),
+            || self.rerun_action(&action),
+        )
+        .await?;
+        
+        if outcome.verified {
+            info!("✅ Verified {:?} via {}: {}", action, outcome.method, outcome.detail);
+        } else {
+            warn!("⚠️ {:?} did not take effect after {} attempts: {}", action, outcome.attempts, outcome.detail);
+            if settings.alert_on_failure {
+                let message = format!("Response action for incident {} did not take effect: {}", incident.id, outcome.detail);
+                let mut alert_message = AlertMessage::new(IncidentSeverity::High, message);
+                alert_message.category = "ResponseVerification".to_string();
+                alert_message.tenant = incident.threat_result.details.get("tenant").cloned();
+                alert_message.tags = incident.tags.iter().cloned().collect();
+                alert_message.tags.sort();
+        alert_message.canary = canary_asset::is_canary_hit(&incident.threat_result);
+                let _ = self.alert_tx.send(alert_message).await;
+            }
+        }
+        Some(outcome)
+    }
+
+    /// Apply an action again after it failed verification
+    async fn rerun_action(&self, action: &ResponseAction) -> SIEMResult<()> {
+        match action {
+            ResponseAction::BlockIP { ip, duration_seconds } => self.block_ip(ip, *duration_seconds).await,
+            ResponseAction::DisableAccount { user_id, reason } => self.disable_account(user_id, reason).await,
+            ResponseAction::KillProcess { process_id, reason } => self.kill_process(*process_id, reason).await,
+            ResponseAction::QuarantineFile { file_path, hash } => self.quarantine_file(file_path, hash).await,
+            _ => Ok(()),
+        }
+    }
+
     /// Block IP address
     async fn block_ip(&self, ip: &str, duration_seconds: u64) -> SIEMResult<()> {
+        let target: IpNetwork = ip.parse()?;
+        let ip = &target.to_string();
         let expiry_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + duration_seconds;
         
-        {
-            let mut blocked_ips = self.blocked_ips.write().unwrap();
-            blocked_ips.insert(ip.to_string(), expiry_time);
+        self.record_blocked_ip(ip, expiry_time);
+        if let Some(shared_state) = self.shared_state() {
+            shared_state.publish(SharedList::BlockedIps, ip, expiry_time);
         }
         
         // Execute actual blocking (platform-specific)
         if cfg!(target_os = "windows") {
             self.block_ip_windows(ip).await?;
         } else {
-            self.block_ip_linux(ip).await?;
+            self.block_ip_linux(&target).await?;
         }
         
         info!("🚫 Blocked IP {} for {} seconds", ip, duration_seconds);
         Ok(())
     }
 
+    /// Block an incident's source, refusing internal addresses unless `block_private`
+    async fn block_source_ip(&self, ip: &str, duration_seconds: u64, block_private: bool) -> SIEMResult<()> {
+        Self::check_source_ip(ip, block_private)?;
+        self.block_ip(ip, duration_seconds).await
+    }
+
+    fn check_source_ip(ip: &str, block_private: bool) -> SIEMResult<IpNetwork> {
+        let Ok(target) = ip.parse::<IpNetwork>() else {
+            return Err(format!("Incident source '{}' is not an IP address or CIDR range", ip).into());
+        };
+        if !block_private && !is_external(&target.addr().to_string()) {
+            return Err(format!("Not blocking internal address {}; set block_private to allow", target).into());
+        }
+        Ok(target)
+    }
+
+    /// Resolve the target host and incident fields of an agent action before it is sent
+    fn prepare_agent_action(&self, incident: &Incident, host_id: Option<&str>, action: &ResponseAction) -> SIEMResult<ResponseAction> {
+        if self.agents.read().unwrap().is_none() {
+            return Err("Agent tasking is not enabled".to_string().into());
+        }
+        let details = &incident.threat_result.details;
+        let host = host_id
+            .or_else(|| details.get("hostname").or_else(|| details.get("host")).map(String::as_str))
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| format!("Incident {} names no host to run {:?} on", incident.id, action))?;
+        let action = match action {
+            ResponseAction::BlockSourceIP { duration_seconds, block_private } => {
+                let target = Self::check_source_ip(&incident.source_ip, *block_private)?;
+                ResponseAction::BlockIP { ip: target.to_string(), duration_seconds: *duration_seconds }
+            }
+            action if runs_on_agent(action) => action.clone(),
+            action => return Err(format!("{:?} cannot run on an agent", action).into()),
+        };
+        Ok(ResponseAction::RunOnAgent { host_id: Some(host.to_string()), action: Box::new(action) })
+    }
+
+    /// Send the pending agent actions of a stored incident; results land on the incident when agents reply
+    fn dispatch_agent_actions(&self, incident: &Incident) {
+        let Some(dispatcher) = self.agents.read().unwrap().clone() else {
+            return;
+        };
+        for result in &incident.response_actions {
+            let ResponseAction::RunOnAgent { host_id: Some(host_id), action } = &result.action_type else {
+                continue;
+            };
+            if result.metadata.get("agent_status").map(String::as_str) != Some("pending") {
+                continue;
+            }
+            let (dispatcher, incidents) = (dispatcher.clone(), self.incidents.clone());
+            let (incident_id, action_id, host_id, action) = (incident.id.clone(), result.action_id.clone(), host_id.clone(), action.clone());
+            tokio::spawn(async move {
+                let outcome = dispatcher.dispatch(&host_id, &incident_id, &action).await;
+                Self::record_agent_result(&incidents, &incident_id, &action_id, &action, outcome);
+            });
+        }
+    }
+
+    fn record_agent_result(
+        incidents: &IncidentStore,
+        incident_id: &str,
+        action_id: &str,
+        action: &ResponseAction,
+        outcome: AgentCommandResult,
+    ) {
+        let mut incidents = incidents.write().unwrap();
+        let Some(incident) = incidents.get_mut(incident_id) else {
+            warn!("⚠️ Agent result for unknown incident {}", incident_id);
+            return;
+        };
+        if let Some(result) = incident.response_actions.iter_mut().find(|r| r.action_id == action_id) {
+            result.success = outcome.success;
+            result.error_message = outcome.error_message.clone();
+            result.execution_time_ms = outcome.execution_time_ms;
+            result.timestamp = outcome.completed_at.timestamp().max(0) as u64;
+            result.metadata.insert("agent_status".to_string(), if outcome.success { "succeeded" } else { "failed" }.to_string());
+            result.metadata.insert("agent_command_id".to_string(), outcome.command_id.clone());
+        }
+        let message = match &outcome.error_message {
+            None => format!("{:?} succeeded on agent {}", action, outcome.host_id),
+            Some(error) => format!("{:?} failed on agent {}: {}", action, outcome.host_id, error),
+        };
+        incident.timeline.push(TimelineEntry::new("agent_action", message).with_reference(outcome.command_id));
+        incident.updated_at = Utc::now();
+    }
+
+    /// Run `actions` for an existing incident outside the response rules and record their results on it
+    pub async fn run_actions(&self, incident_id: &str, actions: Vec<ResponseAction>) -> SIEMResult<Vec<ResponseActionResult>> {
+        let incident = self
+            .get_incident(incident_id)
+            .ok_or_else(|| SIEMError::Validation(format!("Incident {} not found", incident_id)))?;
+        let results = self.execute_response_actions(&incident, actions).await?;
+        let mut incidents = self.incidents.write().unwrap();
+        if let Some(incident) = incidents.get_mut(incident_id) {
+            incident.response_actions.extend(results.iter().cloned());
+            incident.updated_at = Utc::now();
+        }
+        Ok(results)
+    }
+
+    /// Execute an action received by this host's agent
+    pub async fn execute_agent_action(&self, action: &ResponseAction) -> SIEMResult<()> {
+        if let Some(journal) = self.forensic_journal() {
+            journal.record(HeldBackKind::ResponseAction, "agent_action", format!("{:?}", action), None);
+            return Err(SIEMError::Validation("Agent action held back in forensic mode".to_string()));
+        }
+        match action {
+            ResponseAction::BlockIP { ip, duration_seconds } => self.block_ip(ip, *duration_seconds).await,
+            ResponseAction::KillProcess { process_id, reason } => self.kill_process(*process_id, reason).await,
+            ResponseAction::QuarantineFile { file_path, hash } => self.quarantine_file(file_path, hash).await,
+            action => Err(format!("{:?} cannot run on an agent", action).into()),
+        }
+    }
+
     /// Block IP on Windows
     async fn block_ip_windows(&self, ip: &str) -> SIEMResult<()> {
         // Use Windows Firewall or netsh
@@ -527,15 +1164,15 @@ impl IncidentResponseEngine {
     }
 
     /// Block IP on Linux
-    async fn block_ip_linux(&self, ip: &str) -> SIEMResult<()> {
-        // Use iptables
-        let output = tokio::process::Command::new("iptables")
-            .args(&["-A", "INPUT", "-s", ip, "-j", "DROP"])
+    async fn block_ip_linux(&self, target: &IpNetwork) -> SIEMResult<()> {
+        let (program, args) = linux_firewall_command("-A", target);
+        let output = tokio::process::Command::new(program)
+            .args(&args)
             .output()
             .await?;
         
         if !output.status.success() {
-            return Err(format!("Failed to block IP {}: {}", ip, String::from_utf8_lossy(&output.stderr)).into());
+            return Err(format!("Failed to block IP {}: {}", target, String::from_utf8_lossy(&output.stderr)).into());
         }
         
         Ok(())
@@ -545,9 +1182,9 @@ impl IncidentResponseEngine {
     async fn disable_account(&self, user_id: &str, reason: &str) -> SIEMResult<()> {
         let expiry_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + 3600; // 1 hour
         
-        {
-            let mut disabled_accounts = self.disabled_accounts.write().unwrap();
-            disabled_accounts.insert(user_id.to_string(), expiry_time);
+        self.record_disabled_account(user_id, expiry_time);
+        if let Some(shared_state) = self.shared_state() {
+            shared_state.publish(SharedList::DisabledAccounts, user_id, expiry_time);
         }
         
         // Execute actual account disable (platform-specific)
@@ -592,11 +1229,10 @@ impl IncidentResponseEngine {
     /// Quarantine file
     async fn quarantine_file(&self, file_path: &str, hash: &str) -> SIEMResult<()> {
         // Create quarantine directory
-        let quarantine_dir = "/tmp/ultra_siem_quarantine";
-        tokio::fs::create_dir_all(quarantine_dir).await?;
+        tokio::fs::create_dir_all(QUARANTINE_DIR).await?;
         
         // Move file to quarantine
-        let quarantine_path = format!("{}/{}", quarantine_dir, hash);
+        let quarantine_path = format!("{}/{}", QUARANTINE_DIR, hash);
         tokio::fs::rename(file_path, &quarantine_path).await?;
         
         info!("📁 Quarantined file {} to {}", file_path, quarantine_path);
@@ -638,20 +1274,17 @@ impl IncidentResponseEngine {
         if !self.config.email_enabled {
             return Ok(());
         }
-        
-        // In a real implementation, you would use a proper email library
-        // For now, we'll simulate email sending
-        info!("📧 Email alert sent to {:?}: {}", to, subject);
-        info!("Email body: {}", body);
-        
-        Ok(())
+        let mail = OutgoingMail { to: to.to_vec(), subject: subject.to_string(), body: body.to_string() };
+        SmtpClient::new(SmtpSettings::from(&self.config))?.send(&mail).await
     }
 
-    /// Send webhook notification
-    async fn send_webhook(&self, url: &str, payload: &serde_json::Value) -> SIEMResult<()> {
+    /// Send webhook notification, shaped by the destination's template if it has one
+    async fn send_webhook(&self, url: &str, payload: &serde_json::Value, incident: &Incident) -> SIEMResult<()> {
+        let templates = self.webhook_templates.read().unwrap().clone();
+        let payload = templates.shape(url, payload, incident)?;
         let response = self.http_client
             .post(url)
-            .json(payload)
+            .json(payload.as_ref())
             .send()
             .await?;
         
@@ -719,25 +1352,44 @@ impl IncidentResponseEngine {
 
     /// Send alerts for incident
     async fn send_alerts(&self, incident: &Incident) -> SIEMResult<()> {
-        let alert_message = AlertMessage {
-            id: Uuid::new_v4(),
-            severity: incident.severity.clone(),
-            message: incident.description.clone(),
-            timestamp: Utc::now(),
-        };
-        
+        let mut alert_message = AlertMessage::new(incident.severity.clone(), incident.description.clone());
+        alert_message.category = format!("{:?}", incident.threat_result.category);
+        alert_message.tenant = incident.threat_result.details.get("tenant").cloned();
+        alert_message.tags = incident.tags.iter().cloned().collect();
+        alert_message.tags.sort();
+        
+        if let Some(journal) = self.forensic_journal() {
+            let detail = format!("{} {} alert: {}", alert_message.severity, alert_message.category, alert_message.message);
+            journal.record(HeldBackKind::Alert, "alert", detail, Some(&incident.id));
+            return Ok(());
+        }
         let _ = self.alert_tx.send(alert_message).await;
         
         Ok(())
     }
 
     /// Process alerts (background task)
-    async fn process_alerts(alert_rx: &mut mpsc::Receiver<AlertMessage>) {
+    async fn process_alerts(alert_rx: &mut mpsc::Receiver<AlertMessage>, router: Arc<AlertRouter>) {
         info!("🚨 Alert processor started");
         
-        while let Some(alert) = alert_rx.recv().await {
-            // Process alert through all configured channels
-            Self::send_alert_to_channels(&alert).await;
+        let mut digest_check = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            tokio::select! {
+                alert = alert_rx.recv() => {
+                    let Some(alert) = alert else { break };
+                    // Route through the matrix; quiet hours hold non-pages for the digest
+                    let decision = router.route(&alert, Utc::now());
+                    for channel in &decision.send_now {
+                        Self::send_to_channel(channel, &alert).await;
+                    }
+                    router.hold(&alert, decision.digest);
+                }
+                _ = digest_check.tick() => {
+                    for (channel, digest) in router.take_digest(Utc::now()) {
+                        Self::send_to_channel(&channel, &digest).await;
+                    }
+                }
+            }
         }
     }
 
@@ -751,41 +1403,19 @@ impl IncidentResponseEngine {
         }
     }
 
-    /// Send alert to all configured channels
-    async fn send_alert_to_channels(alert: &AlertMessage) {
-        // Email alerts
-        if Self::should_send_email_alert(alert) {
-            if let Err(e) = Self::send_email_alert(alert).await {
-                error!("Failed to send email alert: {}", e);
-            }
-        }
-
-        // Webhook notifications
-        if Self::should_send_webhook_alert(alert) {
-            if let Err(e) = Self::send_webhook_alert(alert).await {
-                error!("Failed to send webhook alert: {}", e);
-            }
-        }
-
-        // Slack notifications
-        if Self::should_send_slack_alert(alert) {
-            if let Err(e) = Self::send_slack_alert(alert).await {
-                error!("Failed to send Slack alert: {}", e);
-            }
-        }
-
-        // Teams notifications
-        if Self::should_send_teams_alert(alert) {
-            if let Err(e) = Self::send_teams_alert(alert).await {
-                error!("Failed to send Teams alert: {}", e);
-            }
-        }
-
-        // PagerDuty notifications
-        if Self::should_send_pagerduty_alert(alert) {
-            if let Err(e) = Self::send_pagerduty_alert(alert).await {
-                error!("Failed to send PagerDuty alert: {}", e);
-            }
+    /// Send alert to a single channel
+    async fn send_to_channel(channel: &AlertChannel, alert: &AlertMessage) {
+        let result = match channel {
+            AlertChannel::Email => Self::send_email_alert(alert).await,
+            AlertChannel::Webhook => Self::send_webhook_alert(alert).await,
+            AlertChannel::Grafana => Self::send_grafana_annotation(alert).await,
+            AlertChannel::Slack => Self::send_slack_alert(alert).await,
+            AlertChannel::Teams => Self::send_teams_alert(alert).await,
+            AlertChannel::PagerDuty => Self::send_pagerduty_alert(alert).await,
+            AlertChannel::Custom { url } => Self::send_custom_alert(url, alert).await,
+        };
+        if let Err(e) = result {
+            error!("Failed to send {:?} alert: {}", channel, e);
         }
     }
 
@@ -860,27 +1490,6 @@ impl IncidentResponseEngine {
         Ok(())
     }
 
-    // Alert channel decision methods
-    fn should_send_email_alert(alert: &AlertMessage) -> bool {
-        alert.severity >= IncidentSeverity::Medium
-    }
-
-    fn should_send_webhook_alert(alert: &AlertMessage) -> bool {
-        alert.severity >= IncidentSeverity::High
-    }
-
-    fn should_send_slack_alert(alert: &AlertMessage) -> bool {
-        alert.severity >= IncidentSeverity::Medium
-    }
-
-    fn should_send_teams_alert(alert: &AlertMessage) -> bool {
-        alert.severity >= IncidentSeverity::High
-    }
-
-    fn should_send_pagerduty_alert(alert: &AlertMessage) -> bool {
-        alert.severity >= IncidentSeverity::Critical
-    }
-
     // Alert sending methods
     async fn send_email_alert(alert: &AlertMessage) -> SIEMResult<()> {
         info!("📧 Sending email alert: {}", alert.message);
@@ -894,6 +1503,18 @@ impl IncidentResponseEngine {
         Ok(())
     }
 
+    async fn send_grafana_annotation(alert: &AlertMessage) -> SIEMResult<()> {
+        info!("📊 Sending Grafana annotation: {}", alert.message);
+        // Grafana implementation would go here
+        Ok(())
+    }
+
+    async fn send_custom_alert(url: &str, alert: &AlertMessage) -> SIEMResult<()> {
+        info!("🔗 Sending custom alert to {}: {}", url, alert.message);
+        // Custom channel implementation would go here
+        Ok(())
+    }
+
     async fn send_slack_alert(alert: &AlertMessage) -> SIEMResult<()> {
         info!("💬 Sending Slack alert: {}", alert.message);
         // Slack implementation would go here
@@ -948,7 +1569,25 @@ impl IncidentResponseEngine {
 
     /// Get performance metrics
     pub fn get_performance_metrics(&self) -> HashMap<String, f64> {
-        self.performance_metrics.read().unwrap().clone()
+        let mut metrics = self.performance_metrics.read().unwrap().clone();
+        metrics.extend(self.fatigue_report(Utc::now()).to_metrics());
+        metrics.extend(self.response_metrics_report(Utc::now()).to_metrics());
+        if let Some(journal) = self.forensic_journal() {
+            metrics.extend(journal.get_metrics());
+        }
+        metrics
+    }
+
+    /// Mean response times and SLO error budgets as of `now`
+    pub fn response_metrics_report(&self, now: DateTime<Utc>) -> ResponseMetricsReport {
+        let incidents = self.get_all_incidents();
+        ResponseMetricsReport::build(&incidents, &self.response_metrics.read().unwrap(), now)
+    }
+
+    /// Alert handling stats over the rolling window ending at `now`
+    pub fn fatigue_report(&self, now: DateTime<Utc>) -> FatigueReport {
+        let incidents = self.get_all_incidents();
+        self.fatigue.read().unwrap().analyze(&incidents, now)
     }
 
     /// Get incident statistics
@@ -965,6 +1604,37 @@ impl IncidentResponseEngine {
         self.incidents.write().unwrap().insert(incident.id.clone(), incident);
     }
 
+    /// Blocked IPs and their expiry in Unix seconds
+    pub fn blocked_ips(&self) -> HashMap<String, u64> {
+        self.blocked_ips.read().unwrap().clone()
+    }
+
+    pub fn record_blocked_ip(&self, ip: &str, expiry_time: u64) {
+        self.blocked_ips.write().unwrap().insert(ip.to_string(), expiry_time);
+    }
+
+    pub fn remove_blocked_ip(&self, ip: &str) {
+        self.blocked_ips.write().unwrap().remove(ip);
+    }
+
+    /// Disabled accounts and their expiry in Unix seconds
+    pub fn disabled_accounts(&self) -> HashMap<String, u64> {
+        self.disabled_accounts.read().unwrap().clone()
+    }
+
+    pub fn record_disabled_account(&self, user_id: &str, expiry_time: u64) {
+        self.disabled_accounts.write().unwrap().insert(user_id.to_string(), expiry_time);
+    }
+
+    pub fn remove_disabled_account(&self, user_id: &str) {
+        self.disabled_accounts.write().unwrap().remove(user_id);
+    }
+
+    /// Remove an incident from the engine's internal storage
+    pub fn remove_incident(&self, incident_id: &str) -> Option<Incident> {
+        self.incidents.write().unwrap().remove(incident_id)
+    }
+
     /// Clean up expired blocks and disabled accounts
     pub async fn cleanup_expired_items(&self) -> SIEMResult<()> {
         let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
@@ -990,6 +1660,10 @@ impl IncidentResponseEngine {
         if !self.soar_config.enabled {
             return Err("SOAR integration not enabled".to_string().into());
         }
+        if let Some(journal) = self.forensic_journal() {
+            journal.record(HeldBackKind::Alert, "soar_playbook", format!("SOAR playbook '{}'", playbook_name), Some(&incident.id));
+            return Ok(());
+        }
 
         let playbook_payload = serde_json::json!({
             "playbook": playbook_name,
@@ -1002,10 +1676,13 @@ impl IncidentResponseEngine {
             }
         });
 
+        let url = format!("{}/playbooks/execute", self.soar_config.api_url);
+        let templates = self.webhook_templates.read().unwrap().clone();
+        let playbook_payload = templates.shape(&url, &playbook_payload, incident)?;
         let response = self.http_client
-            .post(&format!("{}/playbooks/execute", self.soar_config.api_url))
+            .post(&url)
             .header("Authorization", format!("Bearer {}", self.soar_config.api_key))
-            .json(&playbook_payload)
+            .json(playbook_payload.as_ref())
             .timeout(Duration::from_secs(self.soar_config.timeout_seconds))
             .send()
             .await?;
@@ -1030,6 +1707,7 @@ impl IncidentResponseEngine {
             if status_clone == IncidentStatus::Resolved {
                 incident.resolved_at = Some(Utc::now());
             }
+            incident.timeline.push(TimelineEntry::new("status_changed", format!("Status changed to {:?}", status_clone)));
             
             info!("📝 Updated incident {} status to {:?}", incident_id, status_clone);
             Ok(())
@@ -1044,6 +1722,7 @@ impl IncidentResponseEngine {
         
         if let Some(incident) = incidents.get_mut(incident_id) {
             incident.notes.push(note.clone());
+            incident.timeline.push(TimelineEntry::new("note_added", note.clone()));
             incident.updated_at = Utc::now();
             
             info!("📝 Added note to incident {}: {}", incident_id, note);
@@ -1059,6 +1738,7 @@ impl IncidentResponseEngine {
         
         if let Some(incident) = incidents.get_mut(incident_id) {
             incident.assigned_to = Some(assigned_to.clone());
+            incident.timeline.push(TimelineEntry::new("assigned", format!("Assigned to {}", assigned_to)));
             incident.updated_at = Utc::now();
             
             info!("👤 Assigned incident {} to {}", incident_id, assigned_to);
@@ -1076,6 +1756,7 @@ impl IncidentResponseEngine {
             incident.false_positive = true;
             incident.status = IncidentStatus::FalsePositive;
             incident.notes.push(format!("Marked as false positive: {}", reason));
+            incident.timeline.push(TimelineEntry::new("false_positive", reason.clone()));
             incident.updated_at = Utc::now();
             
             info!("❌ Marked incident {} as false positive: {}", incident_id, reason);
@@ -1085,6 +1766,121 @@ impl IncidentResponseEngine {
         }
     }
 
+    /// Record or replace the business impact of an incident
+    pub fn set_incident_impact(&self, incident_id: &str, impact: IncidentImpact) -> SIEMResult<Incident> {
+        impact.validate()?;
+        let mut incidents = self.incidents.write().unwrap();
+        
+        if let Some(incident) = incidents.get_mut(incident_id) {
+            let summary = format!(
+                "Impact: {} users, {} min downtime, cost {}",
+                impact.affected_users.map_or("?".to_string(), |n| n.to_string()),
+                impact.downtime_minutes.map_or("?".to_string(), |n| n.to_string()),
+                impact.estimated_cost.map_or("?".to_string(), |c| format!("{:.2}", c))
+            );
+            incident.impact = Some(impact);
+            incident.timeline.push(TimelineEntry::new("impact_updated", summary));
+            incident.updated_at = Utc::now();
+            Ok(incident.clone())
+        } else {
+            Err(format!("Incident {} not found", incident_id).into())
+        }
+    }
+
+    /// Count and list the incidents a bulk operation would touch
+    pub fn preview_bulk(&self, selector: &IncidentSelector, operation: &BulkOperation) -> SIEMResult<BulkPreview> {
+        BulkPreview::build(selector, operation, self.incidents.read().unwrap().values())
+    }
+
+    /// Apply a previewed bulk operation to every selected incident or to none, audited as `actor`.
+    /// Fails if the selection changed since the preview named by `request.confirm`.
+    pub fn apply_bulk(&self, request: BulkRequest, actor: &str) -> SIEMResult<BulkAuditEntry> {
+        if actor.trim().is_empty() {
+            return Err(SIEMError::Validation("Bulk operation needs an actor".to_string()));
+        }
+        let mut incidents = self.incidents.write().unwrap();
+        let preview = BulkPreview::build(&request.selector, &request.operation, incidents.values())?;
+        if preview.selection_id != request.confirm {
+            return Err(SIEMError::Validation(format!(
+                "Selection changed since the preview; {} incidents match now, preview again",
+                preview.matched
+            )));
+        }
+        let entry = BulkAuditEntry {
+            id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            actor: actor.to_string(),
+            operation: request.operation,
+            selector: request.selector,
+            matched: preview.matched,
+            changed: preview.changed,
+            incident_ids: preview.incident_ids,
+        };
+        for id in &entry.incident_ids {
+            if let Some(incident) = incidents.get_mut(id).filter(|incident| entry.operation.changes(incident)) {
+                entry.operation.apply(incident, &entry.id, entry.timestamp);
+            }
+        }
+        // Recorded before the incidents unlock so storage snapshots see both or neither
+        self.bulk_audit.write().unwrap().push(entry.clone());
+        drop(incidents);
+        
+        info!("📦 Bulk {:?} by {} changed {} of {} incidents ({})", entry.operation, entry.actor, entry.changed, entry.matched, entry.id);
+        Ok(entry)
+    }
+
+    /// Audit entries of applied bulk operations, oldest first
+    pub fn bulk_audit_log(&self) -> Vec<BulkAuditEntry> {
+        self.bulk_audit.read().unwrap().clone()
+    }
+
+    /// Incidents and bulk audit entries read under one lock, for the storage persister
+    pub fn storage_snapshot(&self) -> (Vec<Incident>, Vec<BulkAuditEntry>) {
+        let incidents = self.incidents.read().unwrap();
+        let entries = self.bulk_audit.read().unwrap().clone();
+        (incidents.values().cloned().collect(), entries)
+    }
+
+    /// Load incidents and bulk audit entries from storage at start-up
+    pub fn restore_incidents(&self, restored: Vec<Incident>, entries: Vec<BulkAuditEntry>) {
+        let mut incidents = self.incidents.write().unwrap();
+        for incident in restored {
+            incidents.insert(incident.id.clone(), incident);
+        }
+        let mut audit = self.bulk_audit.write().unwrap();
+        let known: HashSet<String> = audit.iter().map(|e| e.id.clone()).collect();
+        audit.extend(entries.into_iter().filter(|e| !known.contains(&e.id)));
+        audit.sort_by_key(|e| e.timestamp);
+    }
+
+    /// Impact rollups per quarter and category
+    pub fn impact_report(&self, now: DateTime<Utc>) -> ImpactReport {
+        ImpactReport::build(&self.get_all_incidents(), now)
+    }
+
+    /// Append an entry to an incident's timeline
+    pub fn add_timeline_entry(&self, incident_id: &str, entry: TimelineEntry) -> SIEMResult<()> {
+        let mut incidents = self.incidents.write().unwrap();
+        
+        if let Some(incident) = incidents.get_mut(incident_id) {
+            incident.timeline.push(entry);
+            incident.updated_at = Utc::now();
+            Ok(())
+        } else {
+            Err(format!("Incident {} not found", incident_id).into())
+        }
+    }
+
+    /// Advances after every change to any incident
+    pub fn incident_revision(&self) -> u64 {
+        *self.incidents.revision.borrow()
+    }
+
+    /// Notified with the new revision after incidents change
+    pub fn subscribe_incident_revisions(&self) -> watch::Receiver<u64> {
+        self.incidents.revision.subscribe()
+    }
+
     /// Get incident by ID
     pub fn get_incident(&self, incident_id: &str) -> Option<Incident> {
         self.incidents.read().unwrap().get(incident_id).cloned()
@@ -1104,6 +1900,37 @@ impl IncidentResponseEngine {
             .collect()
     }
 
+    /// How an incident's severity was derived; re-evaluated under the current
+    /// policies for incidents recorded without an explanation
+    pub fn explain_severity(&self, incident_id: &str) -> Option<SeverityExplanation> {
+        let incident = self.get_incident(incident_id)?;
+        incident.severity_explanation.or_else(|| {
+            let triage = incident
+                .triage
+                .unwrap_or_else(|| self.triage.read().unwrap().score(&incident.threat_result));
+            Some(self.severity.read().unwrap().evaluate(&incident.threat_result, &triage))
+        })
+    }
+
+    /// Unresolved incidents, highest triage priority first
+    pub fn get_triage_queue(&self) -> Vec<Incident> {
+        let mut queue: Vec<Incident> = self.incidents.read().unwrap()
+            .values()
+            .filter(|incident| matches!(
+                incident.status,
+                IncidentStatus::Open | IncidentStatus::Investigating | IncidentStatus::Containing
+            ))
+            .cloned()
+            .collect();
+        let priority = |incident: &Incident| incident.triage.as_ref().map_or(0.0, |score| score.priority);
+        queue.sort_by(|a, b| {
+            priority(b)
+                .total_cmp(&priority(a))
+                .then_with(|| a.created_at.cmp(&b.created_at))
+        });
+        queue
+    }
+
     /// Get incidents by severity
     pub fn get_incidents_by_severity(&self, severity: IncidentSeverity) -> Vec<Incident> {
         self.incidents.read().unwrap()
@@ -1129,6 +1956,16 @@ impl IncidentResponseEngine {
     }
 }
 
+/// Where quarantined files are moved, named by hash
+pub(crate) const QUARANTINE_DIR: &str = "/tmp/ultra_siem_quarantine";
+
+/// Netfilter command applying `operation` (`-A` add, `-C` check) to the drop rule for `target`; IPv6 ranges need ip6tables
+pub(crate) fn linux_firewall_command(operation: &str, target: &IpNetwork) -> (&'static str, Vec<String>) {
+    let program = if target.is_ipv6() { "ip6tables" } else { "iptables" };
+    let args = [operation, "INPUT", "-s", &target.to_string(), "-j", "DROP"].map(String::from).to_vec();
+    (program, args)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1195,6 +2032,99 @@ mod tests {
         let incident = engine.process_threat(threat).await.unwrap();
         assert_eq!(incident.severity, IncidentSeverity::Critical);
         assert_eq!(incident.status, IncidentStatus::Open);
+        assert!(incident.triage.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_triage_queue_orders_by_priority() {
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+
+        let low = engine
+            .process_threat(AdvancedThreatResult { confidence: 0.2, ..Default::default() })
+            .await
+            .unwrap();
+        let high = engine
+            .process_threat(AdvancedThreatResult {
+                confidence: 0.95,
+                iocs: vec!["known_c2".to_string()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let queue: Vec<String> = engine.get_triage_queue().into_iter().map(|i| i.id).collect();
+        assert_eq!(queue, vec![high.id, low.id]);
+    }
+
+    #[tokio::test]
+    async fn test_rules_condition_on_fused_intel_confidence() {
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let rule = ResponseRule {
+            id: "block_confirmed_c2".to_string(),
+            name: "Block corroborated C2".to_string(),
+            description: String::new(),
+            enabled: true,
+            conditions: vec![ResponseCondition {
+                field: "intel_confidence".to_string(),
+                operator: "greater_than".to_string(),
+                value: "0.8".to_string(),
+                case_sensitive: false,
+            }],
+            actions: vec![ResponseAction::LogOnly { message: "c2".to_string() }],
+            priority: 1,
+            cooldown_seconds: 0,
+            last_triggered: None,
+        };
+        let incident = |details: &[(&str, &str)]| {
+            let threat = AdvancedThreatResult {
+                confidence: 0.95,
+                details: details.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+                ..Default::default()
+            };
+            engine.create_incident_from_threat(threat)
+        };
+        let corroborated = incident(&[(INTEL_CONFIDENCE_DETAIL, "0.877"), ("intel_first_seen_confidence", "0.500")]).await.unwrap();
+        let single_feed = incident(&[(INTEL_CONFIDENCE_DETAIL, "0.190"), ("intel_first_seen_confidence", "0.950")]).await.unwrap();
+        let no_intel = incident(&[]).await.unwrap();
+        assert!(engine.evaluate_rule_conditions(&rule, &corroborated));
+        assert!(!engine.evaluate_rule_conditions(&rule, &single_feed));
+        assert!(!engine.evaluate_rule_conditions(&rule, &no_intel));
+    }
+
+    #[tokio::test]
+    async fn test_rules_match_source_networks_and_block_with_ip6tables() {
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let rule = ResponseRule {
+            id: "block_hostile_ranges".to_string(),
+            name: "Block hostile ranges".to_string(),
+            description: String::new(),
+            enabled: true,
+            conditions: vec![ResponseCondition {
+                field: "source_ip".to_string(),
+                operator: "in_network".to_string(),
+                value: "198.51.100.0/24, 2001:DB8:BAD::/48".to_string(),
+                case_sensitive: false,
+            }],
+            actions: vec![ResponseAction::LogOnly { message: "hostile".to_string() }],
+            priority: 1,
+            cooldown_seconds: 0,
+            last_triggered: None,
+        };
+        let incident = |source: &str| {
+            let threat = AdvancedThreatResult { source_ip: source.to_string(), confidence: 0.95, ..Default::default() };
+            engine.create_incident_from_threat(threat)
+        };
+        assert!(engine.evaluate_rule_conditions(&rule, &incident("198.51.100.77").await.unwrap()));
+        assert!(engine.evaluate_rule_conditions(&rule, &incident("2001:db8:bad:1::9").await.unwrap()));
+        assert!(!engine.evaluate_rule_conditions(&rule, &incident("2001:db8:beef::9").await.unwrap()));
+        assert!(!engine.evaluate_rule_conditions(&rule, &incident("unknown").await.unwrap()));
+
+        let target = IncidentResponseEngine::check_source_ip("2001:db8:bad::/48", false).unwrap();
+        let (program, args) = linux_firewall_command("-A", &target);
+        assert_eq!(program, "ip6tables");
+        assert_eq!(args, ["-A", "INPUT", "-s", "2001:db8:bad::/48", "-j", "DROP"]);
+        assert_eq!(linux_firewall_command("-C", &"::ffff:203.0.113.9".parse().unwrap()).0, "iptables");
+        assert!(IncidentResponseEngine::check_source_ip("fd00::1", false).is_err());
     }
 
     #[test]
@@ -0,0 +1,349 @@
+//! # Threat Intel Confidence Fusion
+//!
+//! The same indicator often arrives from several feeds, each with its own
+//! confidence. Instead of keeping whichever report came first, every feed's
+//! report is kept and combined into one effective confidence per indicator:
+//!
+//! - each report is scaled by the trust in its source and decays with a
+//!   half-life from when that source last saw the indicator,
+//! - repeated sightings by one source count as partial corroboration:
+//!   `p = 1 - (1 - e)^(1 + sighting_weight * ln(sightings))`,
+//! - independent sources combine as a noisy-OR: `1 - Π(1 - p)`.
+//!
+//! Matches expose the fused value as the `intel_confidence` detail (and the
+//! first reported value as `intel_first_seen_confidence`), so response rules
+//! can condition on `intel_confidence`.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::threat_detection::{ThreatCategory, ThreatSeverity, IOC};
+
+/// Detail holding the fused confidence of the matched indicator
+pub const INTEL_CONFIDENCE_DETAIL: &str = "intel_confidence";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IntelFusionSettings {
+    pub enabled: bool,
+    /// Trust per feed name, 0.0-1.0
+    pub source_trust: HashMap<String, f32>,
+    /// Trust for feeds not listed in `source_trust`
+    pub default_trust: f32,
+    /// A report counts half as much this long after its source last saw the indicator
+    pub half_life_hours: f64,
+    /// How much repeated sightings by one source add; 0 ignores them
+    pub sighting_weight: f64,
+    /// Matches below this fused confidence are not raised
+    pub min_confidence: f32,
+    /// JSON array of IOC records loaded at start; empty loads nothing
+    pub indicators_path: String,
+}
+
+impl Default for IntelFusionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            source_trust: HashMap::new(),
+            default_trust: 0.5,
+            half_life_hours: 24.0 * 30.0,
+            sighting_weight: 0.5,
+            min_confidence: 0.3,
+            indicators_path: String::new(),
+        }
+    }
+}
+
+/// What one feed reported about an indicator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceReport {
+    pub source: String,
+    pub confidence: f32,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub sightings: u64,
+}
+
+#[derive(Debug, Clone)]
+struct IntelEntry {
+    ioc_type: String,
+    tags: Vec<String>,
+    /// Confidence of the first report received, whatever its source
+    first_confidence: f32,
+    reports: HashMap<String, SourceReport>,
+}
+
+/// One report's share of the fused confidence
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contribution {
+    pub source: String,
+    pub confidence: f32,
+    pub trust: f32,
+    pub recency: f64,
+    pub sightings: u64,
+    /// Probability this report alone contributes after trust, recency and sightings
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FusedIndicator {
+    pub value: String,
+    pub ioc_type: String,
+    pub tags: Vec<String>,
+    pub confidence: f32,
+    pub first_seen_confidence: f32,
+    pub contributions: Vec<Contribution>,
+}
+
+impl FusedIndicator {
+    pub fn sightings(&self) -> u64 {
+        self.contributions.iter().map(|c| c.sightings).sum()
+    }
+
+    /// Detection for an event that contained this indicator
+    pub fn to_threat(&self, event: &serde_json::Value, timestamp: u64) -> AdvancedThreatResult {
+        let field = |name: &str| event.get(name).and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let sources: Vec<&str> = self.contributions.iter().map(|c| c.source.as_str()).collect();
+        let details = [
+            (INTEL_CONFIDENCE_DETAIL.to_string(), format!("{:.3}", self.confidence)),
+            ("intel_first_seen_confidence".to_string(), format!("{:.3}", self.first_seen_confidence)),
+            ("intel_sources".to_string(), sources.join(",")),
+            ("intel_sightings".to_string(), self.sightings().to_string()),
+            ("ioc_type".to_string(), self.ioc_type.clone()),
+        ];
+        AdvancedThreatResult {
+            threat_id: uuid::Uuid::new_v4().to_string(),
+            timestamp,
+            severity: match self.confidence {
+                c if c >= 0.85 => ThreatSeverity::High,
+                c if c >= 0.6 => ThreatSeverity::Medium,
+                _ => ThreatSeverity::Low,
+            },
+            category: ThreatCategory::Malware,
+            confidence: self.confidence,
+            detection_method: "threat_intel".to_string(),
+            source_ip: field("source_ip"),
+            destination_ip: field("destination_ip"),
+            user_id: field("user_id"),
+            description: format!("Known {} indicator {} reported by {}", self.ioc_type, self.value, sources.join(", ")),
+            iocs: vec![self.value.clone()],
+            details: details.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Indicators from every feed, keyed by normalized value
+#[derive(Debug)]
+pub struct IntelFusion {
+    settings: IntelFusionSettings,
+    indicators: RwLock<HashMap<String, IntelEntry>>,
+}
+
+impl IntelFusion {
+    pub fn new(settings: IntelFusionSettings) -> Self {
+        Self { settings, indicators: RwLock::new(HashMap::new()) }
+    }
+
+    pub fn settings(&self) -> &IntelFusionSettings {
+        &self.settings
+    }
+
+    pub fn len(&self) -> usize {
+        self.indicators.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Record a feed's report; a repeat from the same source is another sighting
+    pub fn observe(&self, ioc: &IOC) {
+        let value = normalize(&ioc.value);
+        if value.is_empty() {
+            return;
+        }
+        let mut indicators = self.indicators.write().unwrap();
+        let entry = indicators.entry(value).or_insert_with(|| IntelEntry {
+            ioc_type: ioc.ioc_type.clone(),
+            tags: Vec::new(),
+            first_confidence: ioc.confidence,
+            reports: HashMap::new(),
+        });
+        for tag in &ioc.tags {
+            if !entry.tags.contains(tag) {
+                entry.tags.push(tag.clone());
+            }
+        }
+        let report = entry.reports.entry(ioc.source.clone()).or_insert_with(|| SourceReport {
+            source: ioc.source.clone(),
+            confidence: ioc.confidence,
+            first_seen: ioc.first_seen,
+            last_seen: ioc.last_seen,
+            sightings: 0,
+        });
+        report.sightings += 1;
+        report.confidence = ioc.confidence.clamp(0.0, 1.0);
+        report.first_seen = report.first_seen.min(ioc.first_seen);
+        report.last_seen = report.last_seen.max(ioc.last_seen);
+    }
+
+    /// Load a JSON array of IOC records
+    pub fn load_file(&self, path: &str) -> SIEMResult<usize> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| SIEMError::Config(format!("Failed to read indicators {}: {}", path, e)))?;
+        let iocs: Vec<IOC> = serde_json::from_str(&content)?;
+        iocs.iter().for_each(|ioc| self.observe(ioc));
+        Ok(iocs.len())
+    }
+
+    /// Fused view of one indicator at `now` (Unix seconds)
+    pub fn fused(&self, value: &str, now: u64) -> Option<FusedIndicator> {
+        let value = normalize(value);
+        let indicators = self.indicators.read().unwrap();
+        let entry = indicators.get(&value)?;
+        let mut contributions: Vec<Contribution> = entry.reports.values().map(|report| self.contribution(report, now)).collect();
+        contributions.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+        let missing = contributions.iter().fold(1.0, |missing, c| missing * (1.0 - c.weight));
+        Some(FusedIndicator {
+            value,
+            ioc_type: entry.ioc_type.clone(),
+            tags: entry.tags.clone(),
+            confidence: (1.0 - missing).clamp(0.0, 1.0) as f32,
+            first_seen_confidence: entry.first_confidence,
+            contributions,
+        })
+    }
+
+    /// Indicators found in the event's string fields or message tokens, at or above `min_confidence`
+    pub fn match_event(&self, event: &serde_json::Value, now: u64) -> Vec<FusedIndicator> {
+        let Some(fields) = event.as_object() else {
+            return Vec::new();
+        };
+        let mut candidates: Vec<&str> = Vec::new();
+        for value in fields.values().filter_map(|v| v.as_str()) {
+            candidates.push(value);
+            for token in value.split(|c: char| c.is_whitespace() || "\"'<>()[]{},;=|".contains(c)) {
+                candidates.push(token);
+                // host:port
+                if let Some((host, port)) = token.rsplit_once(':') {
+                    if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) {
+                        candidates.push(host);
+                    }
+                }
+            }
+        }
+        let mut seen = std::collections::HashSet::new();
+        candidates
+            .into_iter()
+            .filter(|candidate| !candidate.is_empty() && seen.insert(normalize(candidate)))
+            .filter_map(|candidate| self.matched(candidate, now))
+            .collect()
+    }
+
+    /// `value`'s fused indicator when it reaches `min_confidence`
+    pub fn matched(&self, value: &str, now: u64) -> Option<FusedIndicator> {
+        self.fused(value, now).filter(|fused| fused.confidence >= self.settings.min_confidence)
+    }
+
+    fn contribution(&self, report: &SourceReport, now: u64) -> Contribution {
+        let trust = self.settings.source_trust.get(&report.source).copied().unwrap_or(self.settings.default_trust).clamp(0.0, 1.0);
+        let age_hours = now.saturating_sub(report.last_seen) as f64 / 3600.0;
+        let recency = if self.settings.half_life_hours > 0.0 {
+            0.5f64.powf(age_hours / self.settings.half_life_hours)
+        } else {
+            1.0
+        };
+        let evidence = (report.confidence as f64 * trust as f64 * recency).clamp(0.0, 1.0);
+        let exponent = 1.0 + self.settings.sighting_weight.max(0.0) * (report.sightings.max(1) as f64).ln();
+        Contribution {
+            source: report.source.clone(),
+            confidence: report.confidence,
+            trust,
+            recency,
+            sightings: report.sightings,
+            weight: 1.0 - (1.0 - evidence).powf(exponent),
+        }
+    }
+}
+
+fn normalize(value: &str) -> String {
+    value.trim().to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn ioc(source: &str, confidence: f32, last_seen: u64) -> IOC {
+        IOC {
+            id: format!("{}-1", source),
+            value: "198.51.100.66".to_string(),
+            ioc_type: "ip".to_string(),
+            confidence,
+            source: source.to_string(),
+            first_seen: last_seen,
+            last_seen,
+            tags: vec!["c2".to_string()],
+        }
+    }
+
+    fn fusion() -> IntelFusion {
+        IntelFusion::new(IntelFusionSettings {
+            enabled: true,
+            source_trust: [("abuseipdb".to_string(), 0.9), ("pastebin".to_string(), 0.2)].into(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_fusion_weighs_trust_recency_and_sightings() {
+        let fusion = fusion();
+        // First report comes from a low-trust source with a high claimed confidence
+        fusion.observe(&ioc("pastebin", 0.95, NOW));
+        let single = fusion.fused("198.51.100.66", NOW).unwrap();
+        assert!((single.confidence - 0.19).abs() < 1e-3, "{:?}", single);
+        assert_eq!(single.first_seen_confidence, 0.95);
+
+        // Corroboration by a trusted feed raises it above either report alone
+        fusion.observe(&ioc("abuseipdb", 0.8, NOW));
+        let corroborated = fusion.fused("198.51.100.66", NOW).unwrap();
+        assert!(corroborated.confidence > 0.72 && corroborated.confidence < 0.95, "{:?}", corroborated);
+        assert_eq!(corroborated.contributions[0].source, "abuseipdb");
+
+        // Repeat sightings add, age takes away
+        fusion.observe(&ioc("abuseipdb", 0.8, NOW));
+        assert!(fusion.fused("198.51.100.66", NOW).unwrap().confidence > corroborated.confidence);
+        let month_later = NOW + 30 * 24 * 3600;
+        assert!(fusion.fused("198.51.100.66", month_later).unwrap().confidence < corroborated.confidence);
+    }
+
+    #[test]
+    fn test_matches_expose_fused_confidence_for_response_rules() {
+        let corroborated = fusion();
+        corroborated.observe(&ioc("abuseipdb", 0.9, NOW));
+        corroborated.observe(&ioc("otx", 0.7, NOW));
+        let event = serde_json::json!({
+            "source_ip": "10.0.0.5",
+            "message": "outbound connection to 198.51.100.66:443 (beacon)",
+        });
+        let matches = corroborated.match_event(&event, NOW);
+        assert_eq!(matches.len(), 1);
+
+        let threat = matches[0].to_threat(&event, NOW);
+        let fused: f32 = threat.details[INTEL_CONFIDENCE_DETAIL].parse().unwrap();
+        assert!((fused - matches[0].confidence).abs() < 1e-3);
+        assert_eq!(threat.details["intel_first_seen_confidence"], "0.900");
+        assert_eq!(threat.details["intel_sources"], "abuseipdb,otx");
+
+        // Weak single-source reports stay below the raise threshold
+        let weak = fusion();
+        weak.observe(&ioc("pastebin", 0.5, NOW));
+        assert!(weak.match_event(&event, NOW).is_empty());
+    }
+}
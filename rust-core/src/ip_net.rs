@@ -0,0 +1,205 @@
+//! Typed addresses and CIDR ranges for detection and response
+//!
+//! Logs write peers as bare addresses, `host:port`, `[v6]:port`, with zone ids or
+//! as IPv4-mapped IPv6. Everything that compares, allow-lists or blocks addresses
+//! goes through `parse_ip` and `IpNetwork` so those spellings agree.
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::SIEMError;
+
+/// Normalized event fields holding a peer address
+pub const ADDRESS_FIELDS: [&str; 2] = ["source_ip", "destination_ip"];
+
+/// Parse an address as logs write it; IPv4-mapped IPv6 is unwrapped to IPv4
+pub fn parse_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim();
+    let ip = value
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| value.parse::<SocketAddr>().ok().map(|socket| socket.ip()))
+        .or_else(|| {
+            let bare = value.strip_prefix('[').and_then(|v| v.split_once(']')).map_or(value, |(address, _)| address);
+            bare.split_once('%').map_or(bare, |(address, _)| address).parse().ok()
+        })?;
+    Some(canonical(ip))
+}
+
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        ip => ip,
+    }
+}
+
+/// Rewrite the event's address fields to their canonical spelling, leaving non-addresses alone
+pub fn normalize_addresses(event: &mut serde_json::Value) {
+    let Some(object) = event.as_object_mut() else {
+        return;
+    };
+    for field in ADDRESS_FIELDS {
+        let Some(value) = object.get_mut(field) else {
+            continue;
+        };
+        if let Some(ip) = value.as_str().and_then(parse_ip) {
+            *value = serde_json::Value::String(ip.to_string());
+        }
+    }
+}
+
+/// Whether `network` (an address or CIDR range) contains `ip`
+pub fn network_contains(network: &str, ip: IpAddr) -> bool {
+    network.parse::<IpNetwork>().is_ok_and(|network| network.contains(ip))
+}
+
+/// An IPv4 or IPv6 range; a single address is a full-length prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNetwork {
+    /// Range of `prefix` bits around `addr`; host bits are cleared
+    pub fn new(addr: IpAddr, prefix: u8) -> Option<Self> {
+        let (addr, prefix) = match addr {
+            // Mapped prefixes count the 96-bit ::ffff:0:0 header
+            IpAddr::V6(v6) if prefix >= 96 => match v6.to_ipv4_mapped() {
+                Some(v4) => (IpAddr::V4(v4), prefix - 96),
+                None => (addr, prefix),
+            },
+            addr => (addr, prefix),
+        };
+        (prefix <= bits(addr)).then(|| Self { addr: mask(addr, prefix), prefix })
+    }
+
+    pub fn host(addr: IpAddr) -> Self {
+        let addr = canonical(addr);
+        Self { addr, prefix: bits(addr) }
+    }
+
+    /// First address of the range
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    pub fn is_ipv6(&self) -> bool {
+        self.addr.is_ipv6()
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.prefix == bits(self.addr)
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = canonical(ip);
+        ip.is_ipv6() == self.addr.is_ipv6() && mask(ip, self.prefix) == self.addr
+    }
+}
+
+fn bits(addr: IpAddr) -> u8 {
+    if addr.is_ipv4() { 32 } else { 128 }
+}
+
+fn mask(addr: IpAddr, prefix: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(v4) => {
+            let keep = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4((u32::from(v4) & keep).into())
+        }
+        IpAddr::V6(v6) => {
+            let keep = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6((u128::from(v6) & keep).into())
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = SIEMError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || SIEMError::Validation(format!("'{}' is not an IP address or CIDR range", value));
+        match value.trim().split_once('/') {
+            Some((address, prefix)) => {
+                let address = address.parse::<IpAddr>().map_err(|_| invalid())?;
+                let prefix = prefix.parse::<u8>().map_err(|_| invalid())?;
+                Self::new(address, prefix).ok_or_else(invalid)
+            }
+            None => parse_ip(value).map(Self::host).ok_or_else(invalid),
+        }
+    }
+}
+
+impl TryFrom<String> for IpNetwork {
+    type Error = SIEMError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<IpNetwork> for String {
+    fn from(network: IpNetwork) -> Self {
+        network.to_string()
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_host() {
+            write!(f, "{}", self.addr)
+        } else {
+            write!(f, "{}/{}", self.addr, self.prefix)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ip_accepts_log_spellings() {
+        assert_eq!(parse_ip("[2001:db8::1]:443"), Some("2001:db8::1".parse().unwrap()));
+        assert_eq!(parse_ip("fe80::1%eth0"), Some("fe80::1".parse().unwrap()));
+        assert_eq!(parse_ip("203.0.113.7:51234"), Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(parse_ip("::ffff:198.51.100.2"), Some("198.51.100.2".parse().unwrap()));
+        assert_eq!(parse_ip("host.example"), None);
+
+        let mut event = serde_json::json!({"source_ip": "[2001:DB8::0001]:22", "destination_ip": "web-01"});
+        normalize_addresses(&mut event);
+        assert_eq!(event["source_ip"], "2001:db8::1");
+        assert_eq!(event["destination_ip"], "web-01");
+    }
+
+    #[test]
+    fn test_network_matches_both_families() {
+        let v4: IpNetwork = "10.1.2.3/16".parse().unwrap();
+        assert_eq!(v4.to_string(), "10.1.0.0/16");
+        assert!(v4.contains("10.1.200.9".parse().unwrap()));
+        assert!(v4.contains("::ffff:10.1.0.1".parse().unwrap()));
+        assert!(!v4.contains("10.2.0.1".parse().unwrap()));
+
+        let v6: IpNetwork = "2001:db8::/32".parse().unwrap();
+        assert!(v6.is_ipv6());
+        assert!(v6.contains("2001:db8:ffff::1".parse().unwrap()));
+        assert!(!v6.contains("2001:db9::1".parse().unwrap()));
+        assert!(network_contains("::/0", "2001:db8::1".parse().unwrap()));
+        assert!(!network_contains("0.0.0.0/0", "2001:db8::1".parse().unwrap()));
+
+        let mapped: IpNetwork = "::ffff:192.0.2.0/120".parse().unwrap();
+        assert_eq!(mapped.to_string(), "192.0.2.0/24");
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        let host: IpNetwork = serde_json::from_str("\"2001:db8::5\"").unwrap();
+        assert!(host.is_host());
+        assert_eq!(serde_json::to_string(&host).unwrap(), "\"2001:db8::5\"");
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+/// Detail key listing the optional stages an event's detections ran without
+pub const SKIPPED_STAGES_DETAIL: &str = "skipped_stages";
+
+/// Weight of the newest event in the moving average latency
+const EWMA_ALPHA: f64 = 0.1;
+
+/// Per-event detection latency budget
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatencyBudgetSettings {
+    pub enabled: bool,
+    pub budget_ms: u64,
+    /// Stages that may be skipped, the first shed first
+    pub optional_stages: Vec<String>,
+    /// Events between two changes of the shed level
+    pub cooldown_events: u64,
+    /// Shed stages come back once the average latency drops below this share of the budget
+    pub recover_ratio: f64,
+}
+
+impl Default for LatencyBudgetSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            budget_ms: 50,
+            optional_stages: ["quantum", "anomaly", "behavioral", "correlation"].map(String::from).to_vec(),
+            cooldown_events: 100,
+            recover_ratio: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StageLatency {
+    pub runs: u64,
+    pub skipped: u64,
+    pub total_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    events: u64,
+    ewma_ms: f64,
+    /// Number of leading `optional_stages` currently shed
+    shed: usize,
+    since_change: u64,
+    stages: HashMap<String, StageLatency>,
+}
+
+/// Times pipeline stages and sheds optional ones while events run over budget
+#[derive(Debug)]
+pub struct LatencyBudget {
+    settings: LatencyBudgetSettings,
+    state: Mutex<BudgetState>,
+}
+
+impl LatencyBudget {
+    pub fn new(settings: LatencyBudgetSettings) -> Self {
+        Self { settings, state: Mutex::new(BudgetState::default()) }
+    }
+
+    pub fn start_event(&self) -> EventBudget<'_> {
+        EventBudget {
+            shed: self.state.lock().unwrap().shed,
+            controller: self,
+            start: Instant::now(),
+            current: None,
+            timings: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Optional stages shed for every event at the moment
+    #[cfg(test)]
+    pub fn shed_stages(&self) -> Vec<String> {
+        let shed = self.state.lock().unwrap().shed;
+        self.settings.optional_stages[..shed].to_vec()
+    }
+
+    #[cfg(test)]
+    pub fn stage_latencies(&self) -> HashMap<String, StageLatency> {
+        self.state.lock().unwrap().stages.clone()
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        let state = self.state.lock().unwrap();
+        let mut metrics = HashMap::new();
+        metrics.insert("latency_budget_ms".to_string(), self.settings.budget_ms as f64);
+        metrics.insert("latency_budget_avg_ms".to_string(), state.ewma_ms);
+        metrics.insert("latency_budget_shed_stages".to_string(), state.shed as f64);
+        for (stage, latency) in &state.stages {
+            metrics.insert(format!("stage_{}_mean_ms", stage), latency.total_ms / latency.runs.max(1) as f64);
+            metrics.insert(format!("stage_{}_max_ms", stage), latency.max_ms);
+            metrics.insert(format!("stage_{}_skipped", stage), latency.skipped as f64);
+        }
+        metrics
+    }
+
+    fn finish(&self, event: &EventBudget<'_>, elapsed: Duration) {
+        let budget_ms = self.settings.budget_ms as f64;
+        let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+        let mut state = self.state.lock().unwrap();
+        for (stage, took) in &event.timings {
+            let latency = state.stages.entry(stage.to_string()).or_default();
+            let ms = took.as_secs_f64() * 1000.0;
+            latency.runs += 1;
+            latency.total_ms += ms;
+            latency.max_ms = latency.max_ms.max(ms);
+        }
+        for stage in &event.skipped {
+            state.stages.entry(stage.to_string()).or_default().skipped += 1;
+        }
+
+        state.ewma_ms = if state.events == 0 { elapsed_ms } else { state.ewma_ms + EWMA_ALPHA * (elapsed_ms - state.ewma_ms) };
+        state.events += 1;
+        state.since_change += 1;
+        if state.since_change < self.settings.cooldown_events {
+            return;
+        }
+        if state.ewma_ms > budget_ms && state.shed < self.settings.optional_stages.len() {
+            warn!(
+                "⏱️ Detection averaging {:.1}ms against a {}ms budget; shedding stage '{}'",
+                state.ewma_ms, self.settings.budget_ms, self.settings.optional_stages[state.shed]
+            );
+            state.shed += 1;
+            state.since_change = 0;
+        } else if state.ewma_ms < budget_ms * self.settings.recover_ratio && state.shed > 0 {
+            state.shed -= 1;
+            state.since_change = 0;
+            info!("⏱️ Detection averaging {:.1}ms; restoring stage '{}'", state.ewma_ms, self.settings.optional_stages[state.shed]);
+        }
+    }
+}
+
+/// Stage timing and shedding decisions for one event
+#[derive(Debug)]
+pub struct EventBudget<'a> {
+    controller: &'a LatencyBudget,
+    start: Instant,
+    shed: usize,
+    current: Option<(&'static str, Duration)>,
+    timings: Vec<(&'static str, Duration)>,
+    skipped: Vec<&'static str>,
+}
+
+impl EventBudget<'_> {
+    /// Begin `stage`, ending the one before; false when an optional stage is shed
+    pub fn admit(&mut self, stage: &'static str) -> bool {
+        let elapsed = self.start.elapsed();
+        self.admit_at(stage, elapsed)
+    }
+
+    fn admit_at(&mut self, stage: &'static str, elapsed: Duration) -> bool {
+        self.close(elapsed);
+        let settings = &self.controller.settings;
+        if let Some(position) = settings.optional_stages.iter().position(|s| s == stage) {
+            // Shed by the controller, or this event already spent its budget
+            if position < self.shed || elapsed >= Duration::from_millis(settings.budget_ms) {
+                self.skipped.push(stage);
+                return false;
+            }
+        }
+        self.current = Some((stage, elapsed));
+        true
+    }
+
+    /// Record the event's timings; returns the stages it skipped
+    pub fn finish(self) -> Vec<&'static str> {
+        let elapsed = self.start.elapsed();
+        self.finish_at(elapsed)
+    }
+
+    fn finish_at(mut self, elapsed: Duration) -> Vec<&'static str> {
+        self.close(elapsed);
+        self.controller.finish(&self, elapsed);
+        self.skipped
+    }
+
+    fn close(&mut self, elapsed: Duration) {
+        if let Some((stage, started)) = self.current.take() {
+            self.timings.push((stage, elapsed.saturating_sub(started)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(cooldown_events: u64) -> LatencyBudget {
+        LatencyBudget::new(LatencyBudgetSettings { enabled: true, cooldown_events, ..Default::default() })
+    }
+
+    fn run_event(controller: &LatencyBudget, total_ms: u64) -> Vec<&'static str> {
+        let mut event = controller.start_event();
+        let ran: Vec<bool> = ["signature", "behavioral", "anomaly", "quantum"]
+            .into_iter()
+            .map(|stage| event.admit_at(stage, Duration::ZERO))
+            .collect();
+        assert!(ran[0], "required stages always run");
+        event.finish_at(Duration::from_millis(total_ms))
+    }
+
+    #[test]
+    fn test_event_over_budget_skips_remaining_optional_stages() {
+        let controller = budget(100);
+        let mut event = controller.start_event();
+        assert!(event.admit_at("signature", Duration::ZERO));
+        assert!(event.admit_at("behavioral", Duration::from_millis(20)));
+        assert!(!event.admit_at("anomaly", Duration::from_millis(55)));
+        assert!(event.admit_at("brute_force", Duration::from_millis(55)));
+        assert_eq!(event.finish_at(Duration::from_millis(60)), vec!["anomaly"]);
+
+        let stages = controller.stage_latencies();
+        assert!((stages["behavioral"].max_ms - 35.0).abs() < 1e-6);
+        assert_eq!((stages["anomaly"].runs, stages["anomaly"].skipped), (0, 1));
+        assert!(controller.shed_stages().is_empty());
+    }
+
+    #[test]
+    fn test_sustained_overrun_sheds_stages_in_order_and_recovers() {
+        let controller = budget(5);
+        for _ in 0..5 {
+            assert!(run_event(&controller, 80).is_empty());
+        }
+        assert_eq!(controller.shed_stages(), vec!["quantum"]);
+        for _ in 0..5 {
+            assert_eq!(run_event(&controller, 80), vec!["quantum"]);
+        }
+        assert_eq!(controller.shed_stages(), vec!["quantum", "anomaly"]);
+
+        // Load drops: the average falls under half the budget and stages come back one by one
+        for _ in 0..60 {
+            run_event(&controller, 5);
+        }
+        assert!(controller.shed_stages().is_empty());
+        assert_eq!(controller.get_metrics()["latency_budget_shed_stages"], 0.0);
+        let quantum = &controller.stage_latencies()["quantum"];
+        assert!(quantum.skipped >= 10);
+        assert_eq!(quantum.runs + quantum.skipped, 70);
+    }
+}
@@ -1,3 +1,19 @@
+//! # Ultra SIEM Rust Core
+//!
+//! ## API stability
+//! The crate follows semver for its stable surface: the crate-root
+//! re-exports and [`prelude`]. Those items are only removed or changed
+//! incompatibly in a new major version, and `tests/public_api.rs` pins them
+//! so a change to the surface has to be deliberate.
+//!
+//! The public modules are the ones the binary is built from, plus the NATS
+//! and query contracts ([`bridge_contract`], [`subjects`], [`query_builder`]);
+//! their contents may change in any minor release. Every other module is
+//! `pub(crate)`. `tests/public-api.txt` lists every public item and CI fails
+//! when the rendered API differs from it, so additions show up in review too.
+//! The placeholder types behind `UltraSIEMCore` are `#[doc(hidden)]` and not
+//! part of the stable surface.
+
 use log::{info, error};
 use serde::{Serialize, Deserialize};
 use std::collections::{HashMap, HashSet};
@@ -8,6 +24,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 // Enterprise-grade threat detection engine
 
 // Add missing type definitions
+#[cfg(all(feature = "gpu", feature = "response"))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemStats {
     pub gpu_stats: HashMap<String, GPUPerformanceProfile>,
@@ -18,39 +35,172 @@ pub struct SystemStats {
     pub active_threats: u64,
 }
 
-// Remove duplicate/conflicting type definitions:
-// Remove MLStats, QuantumStats, QuantumResult, SignatureMatch, ProcessingResult struct definitions from here.
-// Only keep the canonical definitions and re-exports.
-
-// Re-export canonical types from their modules:
-pub use crate::quantum_detector::{QuantumStats, QuantumResult};
-// Remove the SignatureMatch re-export - it's not defined in advanced_threat_detection
-// pub use crate::advanced_threat_detection::SignatureMatch;
-// If ProcessingResult is defined elsewhere, re-export it; otherwise, define it once here.
+use crate::quantum_detector::QuantumResult;
+#[cfg(all(feature = "gpu", feature = "response"))]
+use crate::quantum_detector::QuantumStats;
 
-pub mod error_handling;
-pub mod enrichment;
+pub(crate) mod error_handling;
+pub mod prelude;
+pub mod config;
+pub mod config_check;
+pub(crate) mod dedup;
+pub(crate) mod clock_skew;
+pub(crate) mod event_time;
+pub mod timezone;
+pub mod parsing;
+pub(crate) mod tagging;
+pub(crate) mod text_normalization;
+pub(crate) mod decoder_chain;
+pub(crate) mod ip_net;
+pub mod snapshot;
+pub mod degradation;
+pub mod autoscaling;
+pub mod content_pack;
+pub mod replay;
+pub mod query_builder;
+pub mod access_scope;
+pub mod aggregation;
+pub(crate) mod cardinality;
+pub mod brute_force;
+pub(crate) mod config_change;
+pub(crate) mod web_access;
+pub mod cert_monitor;
+pub(crate) mod tls_fingerprint;
+pub mod honeyport;
+pub mod canary_asset;
+pub mod anomaly_tuning;
+pub(crate) mod secret_scan;
+pub mod suppression;
+pub mod intel_fusion;
+pub mod domain_baseline;
+pub(crate) mod latency_budget;
+pub mod detection_profile;
+pub mod content_audit;
+pub mod config_bundle;
+pub mod forensic;
+pub mod os_service;
+pub mod memory_budget;
+pub mod attack;
+pub mod trends;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+#[cfg(feature = "collectors")]
+pub mod backfill;
+#[cfg(feature = "collectors")]
+pub mod evtx_import;
+// Not wired into the pipeline yet
+#[cfg(feature = "collectors")]
+#[allow(dead_code)]
+pub(crate) mod enrichment;
 pub mod threat_detection;
-pub mod real_detection;
-pub mod ml_engine;
+#[allow(dead_code)]
+pub(crate) mod real_detection;
+pub(crate) mod ml_engine;
 pub mod quantum_detector;
-pub mod gpu_engine;
+// Only the re-exported engine is used; the NVML `GpuEngine` is not wired in
+#[cfg(feature = "gpu")]
+#[allow(dead_code)]
+pub(crate) mod gpu_engine;
+#[cfg(feature = "gpu")]
 pub mod cuda_kernels;
-pub mod advanced_threat_detection;
+pub(crate) mod advanced_threat_detection;
+pub(crate) mod embedded;
+pub mod bridge_contract;
+pub mod subjects;
+// Not wired into the pipeline yet: the Zig engine has no server for it
+#[allow(dead_code)]
+pub(crate) mod zig_query;
+#[cfg(feature = "response")]
 pub mod incident_response;
+#[cfg(feature = "response")]
+pub(crate) mod response_verification;
+#[cfg(feature = "response")]
+pub mod evidence;
+#[cfg(feature = "response")]
+pub mod incident_export;
+#[cfg(feature = "response")]
+pub(crate) mod triage;
+#[cfg(feature = "response")]
+pub(crate) mod severity;
+#[cfg(feature = "response")]
+pub(crate) mod fatigue;
+#[cfg(feature = "response")]
+pub(crate) mod fp_learning;
+#[cfg(feature = "response")]
+pub(crate) mod response_metrics;
+#[cfg(feature = "response")]
+pub(crate) mod routing;
+#[cfg(feature = "response")]
+pub(crate) mod payload_template;
+#[cfg(feature = "response")]
+pub mod simulation;
+#[cfg(feature = "response")]
+pub mod forwarding;
+#[cfg(feature = "response")]
+pub mod agent_tasking;
+#[cfg(feature = "response")]
+pub(crate) mod incident_bulk;
+#[cfg(feature = "response")]
+pub mod incident_archive;
+#[cfg(feature = "response")]
+pub mod chat_mirror;
+#[cfg(feature = "response")]
+pub mod smtp;
+#[cfg(feature = "response")]
+pub(crate) mod tls;
+#[cfg(feature = "response")]
+pub mod digest;
+#[cfg(feature = "response")]
+pub mod output_sinks;
+#[cfg(feature = "response")]
+pub mod canary;
+#[cfg(feature = "response")]
+pub mod shared_state;
+#[cfg(feature = "response")]
+pub(crate) mod impact;
+#[cfg(feature = "response")]
+pub mod telemetry;
+#[cfg(feature = "response")]
+pub mod related;
+#[cfg(feature = "response")]
+pub mod playbook;
+#[cfg(feature = "response")]
+pub mod storage;
+#[cfg(feature = "response")]
+pub(crate) mod postgres;
+#[cfg(feature = "compliance")]
+pub mod compliance;
+#[cfg(feature = "compliance")]
+pub(crate) mod elevation;
+#[cfg(feature = "compliance")]
+pub(crate) mod password_hashing;
+#[cfg(feature = "api")]
+pub mod grpc;
+#[cfg(feature = "api")]
+pub mod rest_api;
+#[cfg(feature = "api")]
+pub mod incident_cache;
+#[cfg(feature = "api")]
+pub mod query_export;
+#[cfg(feature = "waf")]
+pub mod waf;
 
-pub use error_handling::*;
-pub use enrichment::*;
-pub use threat_detection::*;
-pub use real_detection::*;
-pub use ml_engine::*;
-pub use quantum_detector::*;
-pub use gpu_engine::*;
-pub use cuda_kernels::*;
-pub use advanced_threat_detection::*;
-pub use incident_response::*;
-
-pub use gpu_engine::GPUPerformanceProfile;
+// Stable crate-root API. Everything else stays reachable through its module path.
+pub use error_handling::{SIEMError, SIEMResult};
+pub use config::{SiemConfig, DEFAULT_CONFIG_PATH};
+pub use embedded::{UltraSiem, UltraSiemBuilder};
+pub use threat_detection::{ThreatSeverity, ThreatCategory, SignaturePattern};
+pub use advanced_threat_detection::{AdvancedThreatConfig, AdvancedThreatDetectionEngine, AdvancedThreatResult};
+pub use ml_engine::{MLAnomalyEngine, MLAnomalyResult};
+pub use quantum_detector::QuantumDetector;
+#[cfg(feature = "gpu")]
+pub use gpu_engine::{GPUPerformanceProfile, UniversalNvidiaGPUEngine};
+#[cfg(feature = "gpu")]
+pub use cuda_kernels::AnomalyDetectionKernel;
+#[cfg(feature = "response")]
+pub use incident_response::{
+    AlertConfig, Incident, IncidentResponseEngine, IncidentSeverity, IncidentStatus, SOARConfig,
+};
 
 /// Ultra SIEM Core Library
 /// 
@@ -63,6 +213,7 @@ pub use gpu_engine::GPUPerformanceProfile;
 /// - Error handling
 /// - Advanced threat detection
 /// - Incident response and alerting
+#[cfg(all(feature = "gpu", feature = "response"))]
 pub struct UltraSIEMCore {
     pub threat_detector: ThreatDetector,
     pub gpu_engine: UniversalNvidiaGPUEngine,
@@ -73,6 +224,7 @@ pub struct UltraSIEMCore {
     pub incident_response_engine: IncidentResponseEngine,
 }
 
+#[cfg(all(feature = "gpu", feature = "response"))]
 impl UltraSIEMCore {
     /// Create new Ultra SIEM core instance
     pub fn new() -> Self {
@@ -234,6 +386,11 @@ impl UltraSIEMCore {
                 false_positive: false,
                 escalation_level: 1,
                 sla_deadline: None,
+                timeline: vec![],
+                triage: None,
+                severity_explanation: None,
+                impact: None,
+                related_events: None,
             })
         } else {
             None
@@ -290,6 +447,7 @@ impl UltraSIEMCore {
 }
 
 /// Processed Event Result
+#[doc(hidden)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessedEvent {
     pub event: String,
@@ -302,6 +460,7 @@ pub struct ProcessedEvent {
 }
 
 /// Quantum Signal
+#[doc(hidden)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QuantumSignal {
     pub signal_type: String,
@@ -310,6 +469,7 @@ pub struct QuantumSignal {
 }
 
 /// Performance Statistics
+#[cfg(all(feature = "gpu", feature = "response"))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceStats {
     pub gpu_stats: HashMap<String, GPUPerformanceProfile>,
@@ -320,6 +480,7 @@ pub struct PerformanceStats {
 }
 
 /// ML Statistics
+#[doc(hidden)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MLStats {
     pub models_loaded: u32,
@@ -327,13 +488,14 @@ pub struct MLStats {
     pub average_inference_time_ms: f32,
 }
 
+#[cfg(all(feature = "gpu", feature = "response"))]
 impl Default for UltraSIEMCore {
     fn default() -> Self {
         Self::new()
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "gpu", feature = "response"))]
 mod tests {
     use super::*;
     
@@ -389,10 +551,12 @@ mod tests {
     }
 } 
 
-// Type stubs for missing types
+// Placeholders behind `UltraSIEMCore`; not part of the stable API
+#[doc(hidden)]
 pub struct ThreatDetector;
 impl ThreatDetector { pub fn new() -> Self { ThreatDetector } }
 
+#[doc(hidden)]
 pub struct MLEngine;
 impl MLEngine { 
     pub fn new() -> Self { MLEngine } 
@@ -406,16 +570,21 @@ impl MLEngine {
     }
 }
 
+#[doc(hidden)]
 pub struct EnrichmentEngine;
 impl EnrichmentEngine { pub fn new() -> Self { EnrichmentEngine } }
 
+#[doc(hidden)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThreatDetection;
+#[doc(hidden)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnomalyDetection;
+#[doc(hidden)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MLPrediction;
 /// Processing Result
+#[doc(hidden)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessingResult {
     pub threats: Vec<String>,
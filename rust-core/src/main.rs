@@ -12,13 +12,495 @@ use siem_rust_core::{
     QuantumDetector,
     AnomalyDetectionKernel,
 };
+use siem_rust_core::config::{SiemConfig, DEFAULT_CONFIG_PATH};
+use siem_rust_core::config_check::ConfigChecker;
+use siem_rust_core::degradation::{DegradationController, Subsystem};
+use siem_rust_core::forensic::{ForensicJournal, HeldBackKind};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize logging
     env_logger::init();
     
+    // Validate configuration and exit when invoked with --check-config [path]
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|a| a == "--check-config") {
+        let path = args.get(pos + 1).map(String::as_str).unwrap_or(DEFAULT_CONFIG_PATH);
+        let report = match SiemConfig::load(path) {
+            Ok(config) => ConfigChecker::new(config).check().await,
+            Err(e) => {
+                eprintln!("❌ [FATAL] config: {}", e);
+                std::process::exit(2);
+            }
+        };
+        println!("{}", report);
+        std::process::exit(report.exit_code());
+    }
+    
+    // Replay historical archives and exit when invoked with --backfill <dir> [--output <file>]
+    #[cfg(feature = "collectors")]
+    if let Some(pos) = args.iter().position(|a| a == "--backfill") {
+        use siem_rust_core::backfill::{BackfillImporter, BackfillSink};
+        
+        let Some(directory) = args.get(pos + 1) else {
+            eprintln!("usage: siem-rust-core --backfill <directory> [--output <detections.jsonl>]");
+            eprintln!("  events get the live parsing, content pack, normalization, decoding, tagging and threat intel");
+            eprintln!("  enrichment; stages that learn from live traffic (domain baseline, honeyport actors, anomaly");
+            eprintln!("  tuning, aggregation) are not applied to historical events");
+            std::process::exit(2);
+        };
+        let config = SiemConfig::load(DEFAULT_CONFIG_PATH)?;
+        let mut engine = siem_rust_core::AdvancedThreatDetectionEngine::new(config.detection.clone());
+        engine.start().await?;
+        engine.enable_dedup(config.dedup.clone());
+        let mut pipelines = config.pipelines.clone();
+        pipelines.extend(siem_rust_core::content_pack::ContentPackManager::open(config.content_packs.clone())?.apply(&engine)?);
+        engine.set_parsing_pipelines(siem_rust_core::parsing::ParsingPipelines::from_config(&pipelines)?);
+        engine.enable_text_normalization(config.text_normalization.clone());
+        engine.enable_decoder(config.decoder.clone())?;
+        engine.enable_tagging(config.tagging.clone())?;
+        if config.intel_fusion.enabled {
+            let intel = siem_rust_core::intel_fusion::IntelFusion::new(config.intel_fusion.clone());
+            if !config.intel_fusion.indicators_path.is_empty() {
+                intel.load_file(&config.intel_fusion.indicators_path)?;
+            }
+            engine.set_intel_fusion(std::sync::Arc::new(intel));
+        }
+        for entry in &config.allow_list.entries {
+            engine.add_to_whitelist(entry.clone())?;
+        }
+        // Timestamp normalization stays off so detections keep the original event times
+        let sink = match args.iter().position(|a| a == "--output").and_then(|i| args.get(i + 1)) {
+            Some(path) => BackfillSink::json_lines(path)?,
+            None => BackfillSink::Nats(async_nats::connect(&config.nats.url).await?),
+        };
+        let mut importer = BackfillImporter::new(config.backfill.clone(), engine, sink);
+        #[cfg(feature = "chaos")]
+        importer.set_fault_injector(siem_rust_core::chaos::FaultInjector::new(config.chaos.clone()));
+        #[cfg(feature = "chaos")]
+        let report = importer.run_supervised(directory.into()).await?;
+        #[cfg(not(feature = "chaos"))]
+        let report = importer.run(directory).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    
+    // Run an exported Windows event log through detection for an investigation:
+    // --evtx <file.evtx> --investigation <name> [--incident <id>]
+    #[cfg(feature = "collectors")]
+    if let Some(pos) = args.iter().position(|a| a == "--evtx") {
+        use siem_rust_core::evtx_import::{import_evtx_file, InvestigationContext};
+        
+        let flag = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+        let (Some(path), Some(name)) = (args.get(pos + 1), flag("--investigation")) else {
+            eprintln!("usage: siem-rust-core --evtx <file.evtx> --investigation <name> [--incident <id>]");
+            std::process::exit(2);
+        };
+        let mut investigation = InvestigationContext::new(name.as_str());
+        if let Some(incident_id) = flag("--incident") {
+            investigation = investigation.with_incident(incident_id.as_str());
+        }
+        let config = SiemConfig::load(DEFAULT_CONFIG_PATH)?;
+        let mut engine = siem_rust_core::AdvancedThreatDetectionEngine::new(config.detection.clone());
+        engine.start().await?;
+        engine.set_parsing_pipelines(siem_rust_core::parsing::ParsingPipelines::from_config(&config.pipelines)?);
+        for threat in import_evtx_file(&engine, std::path::Path::new(path), &investigation).await? {
+            println!("{}", serde_json::to_string(&threat)?);
+        }
+        return Ok(());
+    }
+    
+    // Re-run a recorded detection with per-stage traces: --replay <record id|file> [--json]
+    if let Some(pos) = args.iter().position(|a| a == "--replay") {
+        use siem_rust_core::replay::{replay, ReplayRecorder};
+        
+        let Some(record_id) = args.get(pos + 1) else {
+            eprintln!("usage: siem-rust-core --replay <record id|file> [--json]");
+            std::process::exit(2);
+        };
+        let config = SiemConfig::load(DEFAULT_CONFIG_PATH)?;
+        let record = ReplayRecorder::new(&config.replay.record_dir).load(record_id)?;
+        let mut engine = siem_rust_core::AdvancedThreatDetectionEngine::new(config.detection.clone());
+        engine.start().await?;
+        let mut pipelines = config.pipelines.clone();
+        pipelines.extend(siem_rust_core::content_pack::ContentPackManager::open(config.content_packs.clone())?.apply(&engine)?);
+        engine.set_parsing_pipelines(siem_rust_core::parsing::ParsingPipelines::from_config(&pipelines)?);
+        // Dedup and clock skew state are not recorded; drift shows up as `input drift`
+        let report = replay(&engine, &record).await?;
+        if args.iter().any(|a| a == "--json") {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print!("{}", report.render());
+        }
+        return Ok(());
+    }
+    
+    // ATT&CK coverage: --attack-coverage [--days N] [--fixtures <dir>] [--detections <file.jsonl>] [--output <layer.json>]
+    if args.iter().any(|a| a == "--attack-coverage") {
+        use siem_rust_core::attack::{load_fixtures, CoverageReport};
+        
+        let flag = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+        let days = match flag("--days").map(|d| d.parse::<u32>()) {
+            Some(Ok(days)) => days,
+            Some(Err(_)) => {
+                eprintln!("usage: siem-rust-core --attack-coverage [--days N] [--fixtures <dir>] [--detections <file.jsonl>] [--output <layer.json>]");
+                std::process::exit(2);
+            }
+            None => 30,
+        };
+        let config = SiemConfig::load(DEFAULT_CONFIG_PATH)?;
+        let mut engine = siem_rust_core::AdvancedThreatDetectionEngine::new(config.detection.clone());
+        engine.start().await?;
+        siem_rust_core::content_pack::ContentPackManager::open(config.content_packs.clone())?.apply(&engine)?;
+        engine.enable_cardinality(config.cardinality.clone());
+        engine.enable_brute_force(config.brute_force.clone());
+        engine.enable_config_changes(config.config_change.clone());
+        engine.enable_web_access(config.web_access.clone());
+        engine.enable_cert_monitor(config.cert_monitor.clone());
+        engine.enable_tls_fingerprints(config.tls_fingerprint.clone());
+        engine.enable_secret_scan(config.secret_scan.clone())?;
+        engine.enable_text_normalization(config.text_normalization.clone());
+        engine.enable_decoder(config.decoder.clone())?;
+        engine.enable_tagging(config.tagging.clone())?;
+        for entry in &config.allow_list.entries {
+            engine.add_to_whitelist(entry.clone())?;
+        }
+        let fixtures = load_fixtures(flag("--fixtures").map(String::as_str).unwrap_or("fixtures/attack"))?;
+        // Detection history: the incidents in the configured store, plus any file
+        // written by --backfill --output (one JSON object per line)
+        let mut fired = Vec::new();
+        if config.storage.backend != siem_rust_core::storage::StorageBackend::Memory {
+            let storage = siem_rust_core::storage::open_storage(config.storage.backend, &config.storage, &config.clickhouse)?;
+            fired = siem_rust_core::attack::stored_detections(storage.as_ref()).await?;
+        } else if flag("--detections").is_none() {
+            eprintln!("⚠️ storage.backend is memory: no detection history, fired counts will be 0 unless --detections is given");
+        }
+        if let Some(path) = flag("--detections") {
+            for line in std::fs::read_to_string(path)?.lines().filter(|l| !l.trim().is_empty()) {
+                fired.push(serde_json::from_str::<AdvancedThreatResult>(line)?);
+            }
+        }
+        let report = CoverageReport::build(&engine.attack_inventory(), &fixtures, &fired, days, chrono::Utc::now());
+        match flag("--output") {
+            Some(path) => {
+                std::fs::write(path, serde_json::to_vec_pretty(&report.navigator_layer())?)?;
+                print!("{}", report.render());
+                println!("Navigator layer written to {}", path);
+            }
+            None => println!("{}", serde_json::to_string_pretty(&report.navigator_layer())?),
+        }
+        return Ok(());
+    }
+    
+    // Purple-team validation: --simulate <all|technique,...> [--json]; exits 1 when a scenario fails
+    #[cfg(feature = "response")]
+    if let Some(pos) = args.iter().position(|a| a == "--simulate") {
+        use siem_rust_core::simulation::{Scenario, Simulator};
+        
+        let scenarios = match args.get(pos + 1).map(|list| Scenario::parse_list(list)) {
+            Some(Ok(scenarios)) => scenarios,
+            Some(Err(e)) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(2);
+            }
+            None => {
+                eprintln!("usage: siem-rust-core --simulate <all|T1110,T1021,T1041|brute_force,...> [--json]");
+                std::process::exit(2);
+            }
+        };
+        let config = SiemConfig::load(DEFAULT_CONFIG_PATH)?;
+        let mut engine = siem_rust_core::AdvancedThreatDetectionEngine::new(config.detection.clone());
+        engine.start().await?;
+        let mut pipelines = config.pipelines.clone();
+        pipelines.extend(siem_rust_core::content_pack::ContentPackManager::open(config.content_packs.clone())?.apply(&engine)?);
+        engine.set_parsing_pipelines(siem_rust_core::parsing::ParsingPipelines::from_config(&pipelines)?);
+        engine.enable_cardinality(config.cardinality.clone());
+        engine.enable_brute_force(config.brute_force.clone());
+        engine.enable_config_changes(config.config_change.clone());
+        engine.enable_web_access(config.web_access.clone());
+        engine.enable_cert_monitor(config.cert_monitor.clone());
+        engine.enable_tls_fingerprints(config.tls_fingerprint.clone());
+        engine.enable_secret_scan(config.secret_scan.clone())?;
+        engine.enable_text_normalization(config.text_normalization.clone());
+        engine.enable_decoder(config.decoder.clone())?;
+        engine.enable_tagging(config.tagging.clone())?;
+        for entry in &config.allow_list.entries {
+            engine.add_to_whitelist(entry.clone())?;
+        }
+        // Never started: incidents are created without response actions or alerts
+        let incidents = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let report = Simulator::new(&engine, &incidents).run(&scenarios).await?;
+        if args.iter().any(|a| a == "--json") {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print!("{}", report.render());
+        }
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+    
+    // Encrypted incident bundles: --bundle keygen | encrypt <file> --recipient <key,...> | decrypt <file> --identity <file> [--output <file>]
+    #[cfg(feature = "response")]
+    if let Some(pos) = args.iter().position(|a| a == "--bundle") {
+        use siem_rust_core::incident_export::{decrypt_bundle, encrypt_bundle, generate_identity};
+        
+        let flag = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+        let output = match (args.get(pos + 1).map(String::as_str), args.get(pos + 2)) {
+            (Some("keygen"), _) => {
+                let (secret, public) = generate_identity();
+                println!("# public key (share with exporters): {}", public);
+                println!("{}", secret);
+                return Ok(());
+            }
+            (Some("encrypt"), Some(path)) if flag("--recipient").is_some() => {
+                let recipients: Vec<String> = flag("--recipient").unwrap().split(',').map(|r| r.trim().to_string()).collect();
+                encrypt_bundle(&std::fs::read(path)?, &recipients)?
+            }
+            (Some("decrypt"), Some(path)) if flag("--identity").is_some() => {
+                let identity = std::fs::read_to_string(flag("--identity").unwrap())?;
+                let secret = identity.lines().find(|l| !l.trim().is_empty() && !l.starts_with('#')).unwrap_or_default();
+                decrypt_bundle(&std::fs::read(path)?, secret)?
+            }
+            _ => {
+                eprintln!("usage: siem-rust-core --bundle keygen | encrypt <file> --recipient <key,...> | decrypt <file> --identity <file> [--output <file>]");
+                std::process::exit(2);
+            }
+        };
+        match flag("--output") {
+            Some(path) => std::fs::write(path, output)?,
+            None => std::io::Write::write_all(&mut std::io::stdout(), &output)?,
+        }
+        return Ok(());
+    }
+    
+    // Remote response agent: --agent keygen | serve [--host-id <id>]
+    #[cfg(feature = "response")]
+    if let Some(pos) = args.iter().position(|a| a == "--agent") {
+        use siem_rust_core::agent_tasking::{generate_signing_key, AgentExecutor};
+        
+        match args.get(pos + 1).map(String::as_str) {
+            Some("keygen") => {
+                let (seed, public) = generate_signing_key();
+                println!("# public key (agents' [agents.trusted_keys]): {}", public);
+                println!("{}", seed);
+            }
+            Some("serve") => {
+                let mut config = SiemConfig::load(DEFAULT_CONFIG_PATH)?;
+                if let Some(host_id) = args.iter().position(|a| a == "--host-id").and_then(|i| args.get(i + 1)) {
+                    config.agents.host_id = host_id.clone();
+                }
+                let engine = std::sync::Arc::new(IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled()));
+                let executor = std::sync::Arc::new(AgentExecutor::new(&config.agents, engine)?);
+                executor.serve(async_nats::connect(&config.nats.url).await?).await?;
+            }
+            _ => {
+                eprintln!("usage: siem-rust-core --agent keygen | serve [--host-id <id>]");
+                std::process::exit(2);
+            }
+        }
+        return Ok(());
+    }
+    
+    // Manage content packs: --content-pack install <path|url> | enable <name> | disable <name> | uninstall <name> | list
+    if let Some(pos) = args.iter().position(|a| a == "--content-pack") {
+        use siem_rust_core::content_pack::ContentPackManager;
+        
+        let config = SiemConfig::load(DEFAULT_CONFIG_PATH)?;
+        let mut manager = ContentPackManager::open(config.content_packs.clone())?;
+        match (args.get(pos + 1).map(String::as_str), args.get(pos + 2)) {
+            (Some("install"), Some(source)) if source.starts_with("http://") || source.starts_with("https://") => {
+                println!("{}", serde_json::to_string_pretty(&manager.install_from_url(source).await?)?);
+            }
+            (Some("install"), Some(source)) => println!("{}", serde_json::to_string_pretty(&manager.install_from_path(source)?)?),
+            (Some("enable"), Some(name)) => manager.enable(name)?,
+            (Some("disable"), Some(name)) => manager.disable(name)?,
+            (Some("uninstall"), Some(name)) => {
+                manager.uninstall(name)?;
+            }
+            (Some("list"), _) => println!("{}", serde_json::to_string_pretty(&manager.list())?),
+            _ => {
+                eprintln!("usage: siem-rust-core --content-pack install <path|url> | enable <name> | disable <name> | uninstall <name> | list");
+                std::process::exit(2);
+            }
+        }
+        if config.content_audit.enabled && args.get(pos + 1).map(String::as_str) != Some("list") {
+            use siem_rust_core::content_audit::{current_actor, ContentAuditLog, DetectionContent};
+            let audit = ContentAuditLog::open(config.content_audit.clone())?;
+            let message = args[pos + 1..].join(" ");
+            if let Some(event) = audit.record(DetectionContent::from_config(&config, manager.list()), &current_actor(), &message)? {
+                #[cfg(feature = "response")]
+                audit.notify(&event).await;
+                #[cfg(not(feature = "response"))]
+                let _ = event;
+            }
+        }
+        return Ok(());
+    }
+    
+    // Detection content history: --content-audit commit | history | show <version> | diff <from> <to> | rollback <version> [--author <name>] [--message <text>]
+    if let Some(pos) = args.iter().position(|a| a == "--content-audit") {
+        use siem_rust_core::content_audit::{current_actor, ContentAuditLog, DetectionContent};
+        use siem_rust_core::content_pack::ContentPackManager;
+        
+        let flag = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+        let config = SiemConfig::load(DEFAULT_CONFIG_PATH)?;
+        let audit = ContentAuditLog::open(config.content_audit.clone())?;
+        let author = flag("--author").cloned().unwrap_or_else(current_actor);
+        let version = |i: usize| args.get(pos + i).and_then(|v| v.parse::<u64>().ok());
+        let event = match args.get(pos + 1).map(String::as_str) {
+            Some("commit") => {
+                let message = flag("--message").map(String::as_str).unwrap_or("manual commit");
+                audit.record(DetectionContent::current(&config)?, &author, message)?
+            }
+            Some("history") => {
+                for v in audit.history()? {
+                    println!("{:>4}  {}  {:<12}  {} ({} changes)", v.version, v.created_at.to_rfc3339(), v.author, v.message, v.changes.len());
+                }
+                None
+            }
+            Some("show") if version(2).is_some() => {
+                println!("{}", serde_json::to_string_pretty(&audit.load(version(2).unwrap())?)?);
+                None
+            }
+            Some("diff") if version(2).is_some() && version(3).is_some() => {
+                for change in audit.diff(version(2).unwrap(), version(3).unwrap())? {
+                    println!("{}", change.summary());
+                }
+                None
+            }
+            Some("rollback") if version(2).is_some() => {
+                let mut packs = ContentPackManager::open(config.content_packs.clone())?;
+                let (event, missing) = audit.rollback(version(2).unwrap(), std::path::Path::new(DEFAULT_CONFIG_PATH), &mut packs, &author)?;
+                for name in missing {
+                    eprintln!("⚠️ content pack {} must be reinstalled to match version {}", name, version(2).unwrap());
+                }
+                println!("rolled back to version {} as version {}; restart to apply", version(2).unwrap(), event.version);
+                Some(event)
+            }
+            _ => {
+                eprintln!("usage: siem-rust-core --content-audit commit | history | show <version> | diff <from> <to> | rollback <version> [--author <name>] [--message <text>]");
+                std::process::exit(2);
+            }
+        };
+        #[cfg(feature = "response")]
+        if let Some(event) = event {
+            audit.notify(&event).await;
+        }
+        #[cfg(not(feature = "response"))]
+        let _ = event;
+        return Ok(());
+    }
+    
+    // Signed configuration bundles: --config-bundle keygen | export [--output <file>] | import <file> [--apply] [--only <item,...>] [--config <path>]
+    if let Some(pos) = args.iter().position(|a| a == "--config-bundle") {
+        use siem_rust_core::config_bundle::{self, ItemStatus, SignedConfigBundle};
+        use siem_rust_core::content_audit::{current_actor, ContentAuditLog, DetectionContent};
+        
+        let flag = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+        let config_path = std::path::Path::new(flag("--config").map(String::as_str).unwrap_or(DEFAULT_CONFIG_PATH));
+        let config = SiemConfig::load(config_path)?;
+        match (args.get(pos + 1).map(String::as_str), args.get(pos + 2)) {
+            (Some("keygen"), _) => {
+                let (seed, public) = config_bundle::generate_signing_key();
+                println!("# public key (importers' [config_bundle.trusted_keys]): {}", public);
+                println!("{}", seed);
+            }
+            (Some("export"), _) => {
+                let signed = config_bundle::export(&config, config_path, &config.config_bundle.signing_key()?, &current_actor())?;
+                let output = serde_json::to_vec_pretty(&signed)?;
+                match flag("--output") {
+                    Some(path) => std::fs::write(path, output)?,
+                    None => std::io::Write::write_all(&mut std::io::stdout(), &output)?,
+                }
+            }
+            (Some("import"), Some(path)) => {
+                let signed: SignedConfigBundle = serde_json::from_slice(&std::fs::read(path)?)?;
+                let bundle = signed.verify(&config.config_bundle.trusted_keys)?;
+                println!("bundle by {} at {} (key {})", bundle.created_by, bundle.created_at.to_rfc3339(), signed.key_id);
+                for diff in config_bundle::preview(&bundle, &config, config_path)?.iter().filter(|d| d.status != ItemStatus::Unchanged) {
+                    println!("{}", diff.summary());
+                }
+                if !args.iter().any(|a| a == "--apply") {
+                    println!("preview only; rerun with --apply [--only <item,...>] to write");
+                    return Ok(());
+                }
+                let selectors: Vec<String> = flag("--only").map(|list| list.split(',').map(|s| s.trim().to_string()).collect()).unwrap_or_default();
+                let report = config_bundle::apply(&bundle, config_path, &selectors)?;
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                let config = SiemConfig::load(config_path)?;
+                if config.content_audit.enabled && !report.applied.is_empty() {
+                    let audit = ContentAuditLog::open(config.content_audit.clone())?;
+                    let message = format!("Config bundle import from {} ({})", bundle.created_by, report.applied.join(", "));
+                    if let Some(event) = audit.record(DetectionContent::current(&config)?, &current_actor(), &message)? {
+                        #[cfg(feature = "response")]
+                        audit.notify(&event).await;
+                        #[cfg(not(feature = "response"))]
+                        let _ = event;
+                    }
+                }
+                println!("restart to apply");
+            }
+            _ => {
+                eprintln!("usage: siem-rust-core --config-bundle keygen | export [--output <file>] | import <file> [--apply] [--only <item,...>] [--config <path>]");
+                std::process::exit(2);
+            }
+        }
+        return Ok(());
+    }
+    
+    // Incident/audit storage: --storage prepare [backend] | migrate <from> <to>
+    if let Some(pos) = args.iter().position(|a| a == "--storage") {
+        use siem_rust_core::storage::{migrate, open_storage, StorageBackend};
+        
+        let config = SiemConfig::load(DEFAULT_CONFIG_PATH)?;
+        let open = |name: &str| open_storage(StorageBackend::parse(name)?, &config.storage, &config.clickhouse);
+        match (args.get(pos + 1).map(String::as_str), args.get(pos + 2), args.get(pos + 3)) {
+            (Some("prepare"), backend, _) => {
+                let storage = match backend {
+                    Some(name) => open(name)?,
+                    None => open_storage(config.storage.backend, &config.storage, &config.clickhouse)?,
+                };
+                storage.prepare().await?;
+                println!("{} storage is ready", storage.name());
+            }
+            (Some("migrate"), Some(from), Some(to)) => {
+                let report = migrate(open(from)?.as_ref(), open(to)?.as_ref(), config.storage.migration_batch).await?;
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
+            _ => {
+                eprintln!("usage: siem-rust-core --storage prepare [memory|clickhouse|postgres] | migrate <from> <to>");
+                std::process::exit(2);
+            }
+        }
+        return Ok(());
+    }
+    
+    // Service registration: --service install | uninstall | unit; the service manager starts the core with --service run
+    // The daemon never runs on defaults: a missing or invalid file would silently
+    // drop settings such as `[forensic]`
+    let config = SiemConfig::load(DEFAULT_CONFIG_PATH)?;
+    let service_settings = config.service.clone();
+    if let Some(pos) = args.iter().position(|a| a == "--service") {
+        use siem_rust_core::os_service::{install, systemd_unit, uninstall};
+        
+        let done = match args.get(pos + 1).map(String::as_str) {
+            Some("install") => Some(install(&service_settings)?),
+            Some("uninstall") => Some(uninstall(&service_settings)?),
+            Some("unit") => Some(systemd_unit(&service_settings, &std::env::current_exe()?, &std::env::current_dir()?)),
+            Some("run") => None,
+            _ => {
+                eprintln!("usage: siem-rust-core --service install | uninstall | unit | run");
+                std::process::exit(2);
+            }
+        };
+        if let Some(done) = done {
+            println!("{}", done.trim_end());
+            return Ok(());
+        }
+    }
+    
     info!("🚀 Starting Ultra SIEM Core System...");
+    let service = siem_rust_core::os_service::ServiceHost::start(service_settings, args.iter().any(|a| a == "--service"))?;
+    service.status("Starting");
     
     // Create Ultra SIEM core instance
     let ultra_siem = UltraSIEMCore::new();
@@ -58,6 +540,77 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     let mut incident_engine = IncidentResponseEngine::new(alert_config, soar_config);
     incident_engine.start().await?;
+    incident_engine.set_triage_settings(config.triage.clone());
+    if let Err(e) = incident_engine.set_severity_settings(config.severity.clone()) {
+        log::warn!("⚠️ Keeping detection severity for incidents: {}", e);
+    }
+    incident_engine.set_fatigue_settings(config.fatigue.clone());
+    incident_engine.set_response_metrics_settings(config.response_metrics.clone());
+    if let Err(e) = incident_engine.set_routing_settings(config.routing.clone()) {
+        log::warn!("⚠️ Keeping default alert routing: {}", e);
+    }
+    incident_engine.set_verification_settings(config.response_verification.clone());
+    if let Err(e) = incident_engine.set_webhook_templates(config.webhook_templates.clone()) {
+        log::warn!("⚠️ Sending webhook payloads unshaped: {}", e);
+    }
+    let fatigue_settings = config.fatigue.clone();
+    incident_engine.add_response_rule(siem_rust_core::brute_force::block_rule(&config.brute_force));
+    
+    // Track failing subsystems and apply the degradation matrix
+    let time_zones = match siem_rust_core::timezone::TimeZones::new(&config.time_zones) {
+        Ok(zones) => std::sync::Arc::new(zones),
+        Err(e) => {
+            log::warn!("⚠️ Keeping UTC for schedules and rendered times: {}", e);
+            std::sync::Arc::default()
+        }
+    };
+    incident_engine.set_time_zones(time_zones.clone());
+    // Ingest and detect without acting on anything: --forensic or [forensic] enabled
+    let mut forensic_settings = config.forensic.clone();
+    forensic_settings.enabled |= args.iter().any(|a| a == "--forensic");
+    let forensic = std::sync::Arc::new(ForensicJournal::open(forensic_settings)?);
+    incident_engine.set_forensic_journal(forensic.clone());
+    let degradation = DegradationController::new(config.degradation.clone())?;
+    #[cfg(feature = "chaos")]
+    let degradation = {
+        let mut degradation = degradation;
+        degradation.set_fault_injector(siem_rust_core::chaos::FaultInjector::new(config.chaos.clone()));
+        degradation
+    };
+    let degradation = std::sync::Arc::new(degradation);
+    let degradation_nats = match degradation.connect_nats(&config.nats.url).await {
+        Ok(client) => Some(client),
+        Err(e) => {
+            log::warn!("⚠️ Detections will not be spooled or replayed: {}", e);
+            None
+        }
+    };
+    degradation.clone().spawn_clickhouse_probe(config.clickhouse.clone(), degradation_nats.clone());
+    incident_engine.set_degradation(degradation.clone());
+    // Per-stage queue lag and scale up/down hints; stages register as they start
+    let queues = std::sync::Arc::new(siem_rust_core::autoscaling::QueueMonitor::new(config.autoscaling.clone()));
+    {
+        let degradation = degradation.clone();
+        queues.register("clickhouse_spool", move || degradation.spool_sample());
+    }
+    if config.autoscaling.enabled {
+        queues.clone().spawn(async_nats::connect(&config.nats.url).await.ok());
+    }
+    
+    // Version detection content edited by hand since the last run
+    if config.content_audit.enabled && !forensic.holds_back(HeldBackKind::Mutation, "content_audit", "versioning hand-edited detection content") {
+        use siem_rust_core::content_audit::{current_actor, ContentAuditLog, DetectionContent};
+        let recorded = ContentAuditLog::open(config.content_audit.clone()).and_then(|audit| {
+            let event = audit.record(DetectionContent::current(&config)?, &current_actor(), "changed outside the content CLI; recorded at startup")?;
+            Ok((audit, event))
+        });
+        match recorded {
+            #[cfg(feature = "response")]
+            Ok((audit, Some(event))) => audit.notify(&event).await,
+            Ok(_) => {}
+            Err(e) => log::warn!("⚠️ Detection content not versioned: {}", e),
+        }
+    }
     
     // Check GPU availability
     let gpu_stats = ultra_siem.gpu_engine.get_gpu_stats();
@@ -65,8 +618,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         info!("🎮 GPU Detected: Utilization {:.1}%, Events/sec: {:.0}, Temp: {:.1}C", gpu_stats.gpu_utilization * 100.0, gpu_stats.throughput_events_per_sec, gpu_stats.temperature);
     } else {
         info!("💻 Using CPU-only mode (no GPU detected)");
+        degradation.report_failure(Subsystem::Gpu, "no GPU detected");
     }
     
+    // Rolling top-N statistics for dashboards
+    let aggregator = std::sync::Arc::new(siem_rust_core::aggregation::TopNAggregator::new(config.aggregation.clone()));
+    aggregator.clone().spawn_refresh_task();
+    
     // Test threat detection
     info!("🔍 Testing Threat Detection Engine...");
     
@@ -153,10 +711,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     ];
     
     for event in &quantum_test_events {
-        quantum_detector.add_pattern(event.clone());
+        quantum_detector.add_pattern(siem_rust_core::quantum_detector::QuantumPattern::new(event, event))?;
     }
     
-    let quantum_results = quantum_detector.cache.match_event("quantum_test_1");
+    let quantum_results = quantum_detector.match_event("quantum_test_1");
     info!("🔬 Quantum Detection: {} patterns matched", quantum_results.len());
     
     // Get system statistics
@@ -182,12 +740,472 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("   - Open Incidents: {}", incident_stats.get("open_incidents").unwrap_or(&0));
     info!("   - Resolved Incidents: {}", incident_stats.get("resolved_incidents").unwrap_or(&0));
     
+    let incident_engine = std::sync::Arc::new(incident_engine);
+    
+    // Load stored incidents and write later changes through to the configured backend
+    let persister = if config.storage.backend != siem_rust_core::storage::StorageBackend::Memory {
+        let storage = siem_rust_core::storage::open_storage(config.storage.backend, &config.storage, &config.clickhouse)?;
+        let persister = std::sync::Arc::new(siem_rust_core::storage::IncidentPersister::new(storage, incident_engine.clone()));
+        persister.restore().await?;
+        if forensic.holds_back(HeldBackKind::Mutation, "storage", "writing incidents through to the storage backend") {
+            None
+        } else {
+            persister.clone().spawn(std::time::Duration::from_millis(config.storage.flush_interval_ms));
+            Some(persister)
+        }
+    } else {
+        None
+    };
+
+    // Send RunOnAgent response actions to the agents of affected hosts
+    if config.agents.enabled && !forensic.holds_back(HeldBackKind::ResponseAction, "agents", "tasking collector agents") {
+        match siem_rust_core::agent_tasking::AgentDispatcher::connect(&config.agents, &config.nats.url).await {
+            Ok(dispatcher) => incident_engine.set_agent_dispatcher(std::sync::Arc::new(dispatcher)),
+            Err(e) => log::error!("❌ Agent tasking disabled: {}", e),
+        }
+    }
+
+    // Keep allow-lists, blocked IPs and disabled accounts consistent across cores
+    let shared_state = if config.shared_state.enabled
+        && !forensic.holds_back(HeldBackKind::Mutation, "shared_state", "syncing allow-lists, blocks and disabled accounts with other cores")
+    {
+        match siem_rust_core::shared_state::SharedState::connect(&config.shared_state, &config.nats.url).await {
+            Ok(shared_state) => {
+                let shared_state = std::sync::Arc::new(shared_state);
+                incident_engine.set_shared_state(shared_state.clone());
+                shared_state.clone().spawn(incident_engine.clone());
+                Some(shared_state)
+            }
+            Err(e) => {
+                log::error!("❌ Shared state disabled: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Move old resolved incidents to cold storage; re-offenders reopen them
+    if config.archive.enabled && !forensic.holds_back(HeldBackKind::Mutation, "archive", "moving resolved incidents to cold storage") {
+        match siem_rust_core::incident_archive::IncidentArchive::open(config.archive.clone()) {
+            Ok(archive) => {
+                let archive = std::sync::Arc::new(archive);
+                incident_engine.set_archive(archive.clone());
+                archive.spawn(incident_engine.clone());
+            }
+            Err(e) => log::error!("❌ Incident archive disabled: {}", e),
+        }
+    }
+
+    // Give severe incidents their own chat channel or thread
+    if config.chat_mirror.enabled && !forensic.holds_back(HeldBackKind::Alert, "chat_mirror", "mirroring severe incidents to chat") {
+        match siem_rust_core::chat_mirror::ChatMirror::open(config.chat_mirror.clone()) {
+            Ok(mirror) => {
+                let mirror = std::sync::Arc::new(mirror);
+                incident_engine.set_chat_mirror(mirror.clone());
+                mirror.spawn(incident_engine.clone());
+            }
+            Err(e) => log::error!("❌ Chat mirroring disabled: {}", e),
+        }
+    }
+
+    // Email each tenant its daily or weekly incident digest
+    if config.digest.enabled && !forensic.holds_back(HeldBackKind::Alert, "digest", "emailing incident digests") {
+        let smtp = config.alerts.as_ref().map(siem_rust_core::smtp::SmtpSettings::from).unwrap_or_default();
+        let digester = siem_rust_core::smtp::SmtpClient::new(smtp)
+            .and_then(|smtp| siem_rust_core::digest::Digester::open(config.digest.clone(), Some(smtp)));
+        match digester {
+            Ok(digester) => {
+                std::sync::Arc::new(digester).spawn(incident_engine.clone(), time_zones.clone());
+            }
+            Err(e) => log::error!("❌ Incident digests disabled: {}", e),
+        }
+    }
+
+    // Produce the alarm fatigue digest on its configured cadence
+    if fatigue_settings.digest_interval_hours > 0 {
+        let incidents = incident_engine.clone();
+        let zone = time_zones.default_zone().clone();
+        let mut ticker = time_zones.report_ticker(fatigue_settings.digest_interval_hours);
+        tokio::spawn(async move {
+            loop {
+                ticker.tick().await;
+                let now = chrono::Utc::now();
+                let digest = incidents.fatigue_report(now).render_digest(&zone);
+                info!("📬 {}", digest);
+                if !fatigue_settings.digest_dir.is_empty() {
+                    let path = std::path::Path::new(&fatigue_settings.digest_dir)
+                        .join(format!("fatigue-digest-{}.txt", zone.to_local(now).format("%Y-%m-%d")));
+                    let written = std::fs::create_dir_all(&fatigue_settings.digest_dir)
+                        .and_then(|_| std::fs::write(&path, &digest));
+                    if let Err(e) = written {
+                        log::error!("❌ Failed to write fatigue digest {}: {}", path.display(), e);
+                    }
+                }
+            }
+        });
+    }
+
+    // Raise incidents for certificates newly logged in certificate transparency
+    std::sync::Arc::new(siem_rust_core::cert_monitor::CertMonitor::new(config.cert_monitor.clone())).spawn_ct_poller(incident_engine.clone());
+
+    // Open the decoy ports and remember who touches them
+    let attackers = std::sync::Arc::new(siem_rust_core::honeyport::AttackerStore::open(config.honeyport.clone())?);
+    std::sync::Arc::new(siem_rust_core::honeyport::Honeyport::new(attackers.clone())).spawn(incident_engine.clone());
+
+    // Start the gRPC and REST APIs when enabled in the unified configuration
+    #[cfg(feature = "api")]
+    {
+        let config = config.clone();
+        let mut detections = None;
+        let mut sink_detections = None;
+        let mut stored_detections = None;
+        let mut canary_detector = None;
+        let memory = std::sync::Arc::new(siem_rust_core::memory_budget::MemoryAccountant::new(config.memory.clone())?);
+        let telemetry = std::sync::Arc::new(siem_rust_core::telemetry::TelemetryReporter::new(config.telemetry.clone()));
+        if !config.telemetry.enabled || !forensic.holds_back(HeldBackKind::Outbound, "telemetry", "submitting anonymized telemetry") {
+            telemetry.clone().spawn(incident_engine.clone());
+        }
+        let suppressions = std::sync::Arc::new(siem_rust_core::suppression::SuppressionList::open(config.suppression.clone())?);
+        let profiles = std::sync::Arc::new(siem_rust_core::detection_profile::ProfileRegistry::open(&config.detection_profiles)?);
+        let domain_baseline = std::sync::Arc::new(siem_rust_core::domain_baseline::DomainBaseline::open(config.domain_learning.clone())?);
+        let canaries = std::sync::Arc::new(siem_rust_core::canary_asset::CanaryRegistry::open(config.canary_assets.clone())?);
+        let anomaly_tuner = std::sync::Arc::new(siem_rust_core::anomaly_tuning::AnomalyTuner::open(
+            config.anomaly_tuning.clone(),
+            config.detection.anomaly_sensitivity,
+        )?);
+        if !config.domain_learning.enabled || !forensic.holds_back(HeldBackKind::Mutation, "domain_baseline", "saving the learned domain baseline") {
+            domain_baseline.clone().spawn();
+        }
+        // Review snoozes on a cadence so temporary ones don't become permanent blind spots
+        if config.suppression.report_interval_hours > 0 {
+            let (suppressions, forensic) = (suppressions.clone(), forensic.clone());
+            let zone = time_zones.default_zone().clone();
+            let mut ticker = time_zones.report_ticker(config.suppression.report_interval_hours);
+            tokio::spawn(async move {
+                let settings = suppressions.settings().clone();
+                loop {
+                    ticker.tick().await;
+                    let now = chrono::Utc::now();
+                    let report = suppressions.report(now).render(&zone);
+                    info!("🔕 {}", report);
+                    if !settings.report_dir.is_empty() {
+                        let path = std::path::Path::new(&settings.report_dir)
+                            .join(format!("suppression-report-{}.txt", zone.to_local(now).format("%Y-%m-%d")));
+                        let written = std::fs::create_dir_all(&settings.report_dir)
+                            .and_then(|_| std::fs::write(&path, &report));
+                        if let Err(e) = written {
+                            log::error!("❌ Failed to write suppression report {}: {}", path.display(), e);
+                        }
+                    }
+                    if forensic.enabled() {
+                        continue;
+                    }
+                    if let Err(e) = suppressions.prune(now) {
+                        log::error!("❌ Failed to prune expired suppressions: {}", e);
+                    }
+                }
+            });
+        }
+        #[cfg(feature = "waf")]
+        let waf_enabled = config.waf.enabled;
+        #[cfg(not(feature = "waf"))]
+        let waf_enabled = false;
+        if config.grpc.enabled || waf_enabled {
+            let mut detector = siem_rust_core::AdvancedThreatDetectionEngine::new(config.detection.clone());
+            detector.set_quantum_detector(std::sync::Arc::new(QuantumDetector::open(config.quantum.clone())?));
+            detector.start().await?;
+            detector.enable_dedup(config.dedup.clone());
+            detector.enable_timestamp_normalization(config.clock_skew.clone());
+            detector.enable_event_time(config.event_time.clone());
+            detector.enable_latency_budget(config.latency_budget.clone());
+            let mut pipelines = config.pipelines.clone();
+            match siem_rust_core::content_pack::ContentPackManager::open(config.content_packs.clone()) {
+                Ok(manager) => match manager.apply(&detector) {
+                    Ok(pack_pipelines) => pipelines.extend(pack_pipelines),
+                    Err(e) => log::warn!("⚠️ Content packs not applied: {}", e),
+                },
+                Err(e) => log::warn!("⚠️ Content pack registry unavailable: {}", e),
+            }
+            detector.set_parsing_pipelines(siem_rust_core::parsing::ParsingPipelines::from_config(&pipelines)?);
+            detector.enable_replay_recording(config.replay.clone());
+            detector.enable_cardinality(config.cardinality.clone());
+            detector.enable_brute_force(config.brute_force.clone());
+            detector.enable_config_changes(config.config_change.clone());
+            detector.enable_web_access(config.web_access.clone());
+            detector.enable_cert_monitor(config.cert_monitor.clone());
+            detector.enable_tls_fingerprints(config.tls_fingerprint.clone());
+            detector.enable_secret_scan(config.secret_scan.clone())?;
+            detector.enable_text_normalization(config.text_normalization.clone());
+            detector.enable_decoder(config.decoder.clone())?;
+            detector.enable_tagging(config.tagging.clone())?;
+            if config.intel_fusion.enabled {
+                let intel = siem_rust_core::intel_fusion::IntelFusion::new(config.intel_fusion.clone());
+                if !config.intel_fusion.indicators_path.is_empty() {
+                    match intel.load_file(&config.intel_fusion.indicators_path) {
+                        Ok(loaded) => log::info!("🧠 Loaded {} feed reports for {} indicators", loaded, intel.len()),
+                        Err(e) => log::warn!("⚠️ Threat intel indicators not loaded: {}", e),
+                    }
+                }
+                detector.set_intel_fusion(std::sync::Arc::new(intel));
+            }
+            for entry in &config.allow_list.entries {
+                detector.add_to_whitelist(entry.clone())?;
+            }
+            if config.suppression.enabled {
+                detector.set_suppressions(suppressions.clone());
+            }
+            if config.detection_profiles.enabled {
+                detector.set_detection_profiles(profiles.clone());
+            }
+            if config.domain_learning.enabled {
+                detector.set_domain_baseline(domain_baseline.clone());
+            }
+            if config.honeyport.enabled {
+                detector.set_attacker_store(attackers.clone());
+            }
+            if config.canary_assets.enabled {
+                detector.set_canary_assets(canaries.clone());
+            }
+            if config.anomaly_tuning.enabled
+                && !forensic.holds_back(HeldBackKind::Mutation, "anomaly_tuning", "tuning anomaly sensitivity from the analyzed data")
+            {
+                detector.set_anomaly_tuner(anomaly_tuner.clone());
+            }
+            if config.aggregation.enabled {
+                detector.set_aggregator(aggregator.clone());
+            }
+            detector.set_latency_histogram(telemetry.latency());
+            detector.set_memory_accountant(memory.clone());
+            #[cfg(feature = "chaos")]
+            detector.set_fault_injector(siem_rust_core::chaos::FaultInjector::new(config.chaos.clone()));
+            if let Err(e) = siem_rust_core::snapshot::restore_engine(&detector, &config.snapshot) {
+                log::warn!("⚠️ Starting without restored engine state: {}", e);
+            }
+            let detector = std::sync::Arc::new(detector);
+            {
+                let detector = detector.clone();
+                queues.register("detection", move || detector.queue_sample());
+            }
+            if let Some(shared_state) = &shared_state {
+                shared_state.attach_detector(detector.clone());
+            }
+            if !config.snapshot.enabled || !forensic.holds_back(HeldBackKind::Mutation, "snapshot", "saving engine state snapshots") {
+                siem_rust_core::snapshot::spawn_snapshot_task(detector.clone(), config.snapshot.clone());
+            }
+            canary_detector = Some(detector.clone());
+            // Score inline HTTP traffic with the same engine that serves gRPC detections
+            #[cfg(feature = "waf")]
+            if config.waf.enabled {
+                match siem_rust_core::waf::WafProxy::new(config.waf.clone(), detector.clone()) {
+                    Ok(proxy) => {
+                        let proxy = proxy.with_forensic_journal(forensic.clone());
+                        tokio::spawn(async move {
+                            if let Err(e) = siem_rust_core::waf::serve(std::sync::Arc::new(proxy)).await {
+                                log::error!("❌ WAF proxy stopped: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => log::error!("❌ WAF proxy disabled: {}", e),
+                }
+            }
+            if config.grpc.enabled {
+                let service = siem_rust_core::grpc::SiemGrpcService::new(detector, incident_engine.clone());
+                detections = Some(service.subscribe_detections());
+                sink_detections = Some(service.subscribe_detections());
+                stored_detections = Some(service.subscribe_detections());
+                let grpc_settings = config.grpc.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = siem_rust_core::grpc::serve(&grpc_settings, service).await {
+                        log::error!("❌ gRPC API stopped: {}", e);
+                    }
+                });
+            }
+        }
+        // Publish detections for ClickHouse, spooling them to disk while it is down
+        if let (Some(receiver), Some(client)) = (stored_detections, degradation_nats.clone()) {
+            degradation.clone().spawn_detection_publisher(receiver, client);
+        }
+        let clickhouse = std::sync::Arc::new(siem_rust_core::query_export::ClickHouseRowSource::new(
+            config.clickhouse.clone(),
+            config.export.fetch_timeout_seconds,
+        )?);
+        let trends = std::sync::Arc::new(siem_rust_core::trends::TrendAnalyzer::new(clickhouse.clone(), config.trends.clone()));
+        if config.related_events.enabled {
+            incident_engine.set_related_events(std::sync::Arc::new(siem_rust_core::related::RelatedEventsEnricher::new(
+                config.related_events.clone(),
+                clickhouse.clone(),
+            )));
+        }
+        trends.clone().spawn_job();
+        // Forward detections and aggregates to the central core; relay what other sites send us
+        let forwarder = if config.forwarding.enabled
+            && !forensic.holds_back(HeldBackKind::Outbound, "forwarding", "forwarding detections and aggregates to the central core")
+        {
+            match siem_rust_core::forwarding::SiteForwarder::connect(config.forwarding.clone()).await {
+                Ok(forwarder) => {
+                    let forwarder = std::sync::Arc::new(forwarder);
+                    forwarder.clone().spawn(detections, config.aggregation.enabled.then(|| aggregator.clone()));
+                    let probe = forwarder.clone();
+                    queues.register("forward_spool", move || probe.queue_sample());
+                    Some(forwarder)
+                }
+                Err(e) => {
+                    log::error!("❌ Site forwarding disabled: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        // Write detections and incidents to Loki and/or Elasticsearch next to ClickHouse
+        let sinks = if config.sinks.enabled
+            && !forensic.holds_back(HeldBackKind::Outbound, "sinks", "writing detections and incidents to output sinks")
+        {
+            match siem_rust_core::output_sinks::OutputSinks::new(config.sinks.clone()) {
+                Ok(sinks) => {
+                    #[cfg(feature = "chaos")]
+                    let sinks = {
+                        let mut sinks = sinks;
+                        sinks.set_fault_injector(siem_rust_core::chaos::FaultInjector::new(config.chaos.clone()));
+                        sinks
+                    };
+                    let sinks = std::sync::Arc::new(sinks);
+                    sinks.clone().spawn(sink_detections, incident_engine.clone());
+                    for (stage, _) in sinks.queue_samples() {
+                        let probe = sinks.clone();
+                        let name = stage.clone();
+                        queues.register(stage, move || {
+                            probe.queue_samples().into_iter().find(|(s, _)| *s == name).map(|(_, sample)| sample).unwrap_or_default()
+                        });
+                    }
+                    Some(sinks)
+                }
+                Err(e) => {
+                    log::error!("❌ Output sinks disabled: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        // Inject a benign canary event and follow it through detection, suppression and the sinks
+        if let Some(detector) = canary_detector.filter(|_| config.canary.enabled) {
+            match siem_rust_core::canary::Canary::new(config.canary.clone(), detector, sinks) {
+                Ok(canary) => {
+                    std::sync::Arc::new(canary).spawn(incident_engine.clone());
+                }
+                Err(e) => log::error!("❌ Canary disabled: {}", e),
+            }
+        }
+        let sites = std::sync::Arc::new(siem_rust_core::forwarding::SiteReceiver::new(&config.forwarding, incident_engine.clone(), forwarder));
+        if config.forwarding.accept {
+            match async_nats::connect(&config.nats.url).await {
+                Ok(client) => {
+                    if let Err(e) = sites.clone().spawn_nats_listener(client).await {
+                        log::error!("❌ Not receiving forwarded sites over NATS: {}", e);
+                    }
+                }
+                Err(e) => log::error!("❌ Not receiving forwarded sites over NATS: {}", e),
+            }
+        }
+        if config.rest.enabled {
+            let incident_cache = std::sync::Arc::new(siem_rust_core::incident_cache::IncidentCache::new(
+                config.incident_cache.clone(),
+                incident_engine.clone(),
+            )?);
+            incident_cache.clone().spawn_invalidation();
+            let evidence = std::sync::Arc::new(siem_rust_core::evidence::EvidenceStore::open(config.evidence.clone())?);
+            let playbooks = std::sync::Arc::new(siem_rust_core::playbook::PlaybookRunner::new(
+                config.playbooks.clone(),
+                incident_engine.clone(),
+                evidence.clone(),
+                Some(clickhouse.clone()),
+            )?);
+            // Users, sessions and just-in-time elevation behind `/api/v1/auth` and `/api/v1/elevations`
+            #[cfg(feature = "compliance")]
+            let compliance = {
+                let jwt_secret = std::env::var("ULTRA_SIEM_JWT_SECRET").unwrap_or_else(|_| uuid::Uuid::new_v4().to_string());
+                let mut engine = siem_rust_core::compliance::ComplianceSecurityEngine::new(jwt_secret);
+                engine.set_elevation_settings(config.elevation.clone());
+                engine.start().await?;
+                std::sync::Arc::new(engine)
+            };
+            let state = siem_rust_core::rest_api::RestState {
+                incidents: incident_engine.clone(),
+                degradation: degradation.clone(),
+                queues: queues.clone(),
+                aggregator: aggregator.clone(),
+                query: std::sync::Arc::new(siem_rust_core::query_export::QueryService::new(clickhouse, config.export.clone())),
+                trends,
+                sites,
+                telemetry,
+                suppressions,
+                profiles,
+                domain_baseline,
+                canaries,
+                anomaly_tuner,
+                playbooks,
+                incident_cache,
+                access: std::sync::Arc::new(siem_rust_core::access_scope::DataAccess::new(&config.data_access)?),
+                incident_export: config.incident_export.clone(),
+                fp_learning: config.fp_learning.clone(),
+                memory,
+                evidence,
+                #[cfg(feature = "compliance")]
+                compliance,
+            };
+            let (evidence, incidents) = (state.evidence.clone(), incident_engine.clone());
+            if !forensic.holds_back(HeldBackKind::Mutation, "evidence", "deleting attachments past retention") {
+                tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3600));
+                    loop {
+                        interval.tick().await;
+                        if let Err(e) = evidence.enforce_retention(&incidents, chrono::Utc::now()) {
+                            log::error!("❌ Evidence retention failed: {}", e);
+                        }
+                    }
+                });
+            }
+            let max_body_bytes = config.evidence.max_attachment_bytes as usize;
+            tokio::spawn(async move {
+                if let Err(e) = siem_rust_core::rest_api::serve(&config.rest, max_body_bytes, state).await {
+                    log::error!("❌ REST API stopped: {}", e);
+                }
+            });
+        }
+    }
+    
     info!("✅ Ultra SIEM Core System running successfully!");
     info!("🛡️ Ready for production deployment");
     
-    // Keep the system running
+    service.ready("Running");
+    {
+        let (incidents, degradation) = (incident_engine.clone(), degradation.clone());
+        service.clone().spawn_watchdog(move || {
+            let open = incidents.get_incidents_by_status(siem_rust_core::incident_response::IncidentStatus::Open).len();
+            format!("Running; {} open incidents; health {}", open, degradation.health().status)
+        });
+    }
+    
+    // Keep the system running until a signal or the service manager stops it
     loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
-        info!("💓 System heartbeat - All systems operational");
+        tokio::select! {
+            _ = tokio::time::sleep(tokio::time::Duration::from_secs(60)) => {
+                info!("💓 System heartbeat - All systems operational");
+            }
+            _ = service.shutdown_requested() => break,
+        }
+    }
+    
+    info!("🛑 Shutting down Ultra SIEM Core System...");
+    service.stopping();
+    if let Some(persister) = persister {
+        match persister.flush().await {
+            Ok(changes) => info!("💾 Stored {} pending incident changes", changes),
+            Err(e) => log::error!("❌ Pending incident changes not stored: {}", e),
+        }
     }
+    service.stopped(0);
+    Ok(())
 } 
\ No newline at end of file
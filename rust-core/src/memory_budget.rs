@@ -0,0 +1,335 @@
+//! # Memory Budget
+//!
+//! Profile maps, session trackers and correlation windows grow with every new
+//! user, address and event, so a long-running core slowly runs out of memory.
+//! The memory accountant gives each of these structures an entry limit and an
+//! idle TTL, evicts the least recently used entries past the limit, and keeps
+//! the estimated size of every structure for the `/debug/memory` report.
+//!
+//! Structures are named `<subsystem>.<structure>`, e.g. `behavioral.user_profiles`.
+//! TTLs are measured against the newest entry of the same structure rather than
+//! the wall clock, so replaying old data does not evict everything at once.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use dashmap::DashMap;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// Built-in limits: structure, max entries, idle TTL in seconds (0 = none)
+const DEFAULT_LIMITS: &[(&str, usize, u64)] = &[
+    ("behavioral.user_profiles", 100_000, 30 * 86_400),
+    ("behavioral.ip_profiles", 200_000, 7 * 86_400),
+    ("behavioral.sessions", 50_000, 86_400),
+    ("correlation.events", 10_000, 0),
+    ("correlation.active", 50_000, 3_600),
+    ("detection.false_positive_history", 50_000, 0),
+    ("signature.match_cache", 10_000, 0),
+    ("compliance.sessions", 10_000, 0),
+    ("compliance.audit_logs", 100_000, 0),
+    ("compliance.elevations", 10_000, 90 * 86_400),
+];
+
+/// Entries sampled per structure to estimate its size
+const SIZE_SAMPLE: usize = 32;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StructureLimit {
+    /// Entries kept before the least recently used are evicted; 0 = unbounded
+    pub max_entries: usize,
+    /// Entries idle this long are evicted; 0 = never
+    pub ttl_seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemorySettings {
+    /// Enforce limits; usage is reported either way
+    pub enabled: bool,
+    /// Seconds between sweeps, run from event processing
+    pub sweep_interval_seconds: u64,
+    /// Overrides of the built-in limits per structure
+    pub limits: BTreeMap<String, StructureLimit>,
+}
+
+impl Default for MemorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sweep_interval_seconds: 60,
+            limits: BTreeMap::new(),
+        }
+    }
+}
+
+impl MemorySettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if self.sweep_interval_seconds == 0 {
+            return Err(SIEMError::Validation("memory.sweep_interval_seconds must be positive".to_string()));
+        }
+        if let Some(name) = self.limits.keys().find(|name| !DEFAULT_LIMITS.iter().any(|(known, _, _)| known == name)) {
+            let known: Vec<&str> = DEFAULT_LIMITS.iter().map(|(known, _, _)| *known).collect();
+            return Err(SIEMError::Validation(format!(
+                "memory.limits has unknown structure {} (known: {})",
+                name,
+                known.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Configured limit of `structure`, else its built-in one
+    pub fn limit(&self, structure: &str) -> StructureLimit {
+        self.limits.get(structure).copied().unwrap_or_else(|| {
+            DEFAULT_LIMITS
+                .iter()
+                .find(|(known, _, _)| *known == structure)
+                .map(|&(_, max_entries, ttl_seconds)| StructureLimit { max_entries, ttl_seconds })
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// Entries dropped by one sweep of a structure
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Eviction {
+    pub expired: u64,
+    pub over_limit: u64,
+}
+
+/// Size and evictions of one structure
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StructureUsage {
+    pub entries: usize,
+    pub estimated_bytes: u64,
+    pub limit: StructureLimit,
+    pub evicted_expired_total: u64,
+    pub evicted_over_limit_total: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubsystemUsage {
+    pub estimated_bytes: u64,
+    pub structures: BTreeMap<String, StructureUsage>,
+}
+
+/// Usage per subsystem as of the last sweep
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryReport {
+    pub enforced: bool,
+    pub total_estimated_bytes: u64,
+    pub subsystems: BTreeMap<String, SubsystemUsage>,
+}
+
+/// Per-structure limits and the usage last reported by the engines
+#[derive(Debug)]
+pub struct MemoryAccountant {
+    settings: MemorySettings,
+    usage: Mutex<BTreeMap<String, StructureUsage>>,
+    last_sweep: AtomicU64,
+}
+
+impl MemoryAccountant {
+    pub fn new(settings: MemorySettings) -> SIEMResult<Self> {
+        settings.validate()?;
+        if settings.enabled {
+            info!("🧮 Memory budget: bounding profile maps, sessions and correlation windows");
+        }
+        Ok(Self {
+            settings,
+            usage: Mutex::new(BTreeMap::new()),
+            last_sweep: AtomicU64::new(0),
+        })
+    }
+
+    /// Limit to enforce on `structure`; unbounded while enforcement is off
+    pub fn limit(&self, structure: &str) -> StructureLimit {
+        match self.settings.enabled {
+            true => self.settings.limit(structure),
+            false => StructureLimit::default(),
+        }
+    }
+
+    /// True once per sweep interval; the caller that gets true runs the sweep
+    pub fn sweep_due(&self) -> bool {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let last = self.last_sweep.load(Ordering::Relaxed);
+        now.saturating_sub(last) >= self.settings.sweep_interval_seconds
+            && self.last_sweep.compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed).is_ok()
+    }
+
+    /// Report the size of `structure` after a sweep
+    pub fn record(&self, structure: &str, entries: usize, estimated_bytes: u64, eviction: Eviction) {
+        let mut usage = self.usage.lock().unwrap();
+        let entry = usage.entry(structure.to_string()).or_default();
+        entry.entries = entries;
+        entry.estimated_bytes = estimated_bytes;
+        entry.limit = self.limit(structure);
+        entry.evicted_expired_total += eviction.expired;
+        entry.evicted_over_limit_total += eviction.over_limit;
+    }
+
+    pub fn report(&self) -> MemoryReport {
+        let mut report = MemoryReport { enforced: self.settings.enabled, ..MemoryReport::default() };
+        for (name, usage) in self.usage.lock().unwrap().iter() {
+            let (subsystem, structure) = name.split_once('.').unwrap_or(("other", name));
+            let entry = report.subsystems.entry(subsystem.to_string()).or_default();
+            entry.estimated_bytes += usage.estimated_bytes;
+            entry.structures.insert(structure.to_string(), usage.clone());
+            report.total_estimated_bytes += usage.estimated_bytes;
+        }
+        report
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        let usage = self.usage.lock().unwrap();
+        let mut metrics = HashMap::from([(
+            "memory_estimated_bytes".to_string(),
+            usage.values().map(|u| u.estimated_bytes).sum::<u64>() as f64,
+        )]);
+        for (name, usage) in usage.iter() {
+            let name = name.replace('.', "_");
+            metrics.insert(format!("memory_{}_entries", name), usage.entries as f64);
+            metrics.insert(format!("memory_{}_bytes", name), usage.estimated_bytes as f64);
+            metrics.insert(
+                format!("memory_{}_evicted_total", name),
+                (usage.evicted_expired_total + usage.evicted_over_limit_total) as f64,
+            );
+        }
+        metrics
+    }
+}
+
+/// Evict idle entries and then the least recently used ones over the limit
+pub fn evict_map<K, V>(map: &DashMap<K, V>, limit: StructureLimit, last_used: impl Fn(&V) -> u64) -> Eviction
+where
+    K: Eq + Hash + Clone,
+{
+    let mut eviction = Eviction::default();
+    if limit.ttl_seconds > 0 {
+        let newest = map.iter().map(|e| last_used(e.value())).max().unwrap_or(0);
+        let before = map.len();
+        map.retain(|_, value| newest.saturating_sub(last_used(value)) <= limit.ttl_seconds);
+        eviction.expired = before.saturating_sub(map.len()) as u64;
+    }
+    if limit.max_entries > 0 && map.len() > limit.max_entries {
+        let mut by_age: Vec<(u64, K)> = map.iter().map(|e| (last_used(e.value()), e.key().clone())).collect();
+        by_age.sort_unstable_by_key(|(used, _)| *used);
+        let excess = by_age.len() - limit.max_entries;
+        for (_, key) in by_age.into_iter().take(excess) {
+            map.remove(&key);
+        }
+        eviction.over_limit = excess as u64;
+    }
+    eviction
+}
+
+/// Evict from a plain map, for structures behind a lock
+pub fn evict_hash_map<K, V>(map: &mut HashMap<K, V>, limit: StructureLimit, last_used: impl Fn(&V) -> u64) -> Eviction
+where
+    K: Eq + Hash + Clone,
+{
+    let mut eviction = Eviction::default();
+    if limit.ttl_seconds > 0 {
+        let newest = map.values().map(&last_used).max().unwrap_or(0);
+        let before = map.len();
+        map.retain(|_, value| newest.saturating_sub(last_used(value)) <= limit.ttl_seconds);
+        eviction.expired = (before - map.len()) as u64;
+    }
+    if limit.max_entries > 0 && map.len() > limit.max_entries {
+        let mut by_age: Vec<(u64, K)> = map.iter().map(|(key, value)| (last_used(value), key.clone())).collect();
+        by_age.sort_unstable_by_key(|(used, _)| *used);
+        let excess = by_age.len() - limit.max_entries;
+        for (_, key) in by_age.into_iter().take(excess) {
+            map.remove(&key);
+        }
+        eviction.over_limit = excess as u64;
+    }
+    eviction
+}
+
+/// Drop the oldest entries of a front-to-back ordered window over the limit
+pub fn trim_window<T>(window: &mut VecDeque<T>, limit: StructureLimit) -> Eviction {
+    let excess = match limit.max_entries {
+        0 => 0,
+        max => window.len().saturating_sub(max),
+    };
+    window.drain(..excess);
+    Eviction { expired: 0, over_limit: excess as u64 }
+}
+
+/// Estimated bytes of `len` entries, from the serialized size of a sample of them
+pub fn estimate_bytes<'a, T: Serialize + 'a>(len: usize, sample: impl Iterator<Item = &'a T>) -> u64 {
+    let (count, bytes) = sample
+        .take(SIZE_SAMPLE)
+        .map(|item| serde_json::to_vec(item).map(|v| v.len()).unwrap_or(0))
+        .fold((0usize, 0usize), |(count, bytes), size| (count + 1, bytes + size));
+    let average = bytes.checked_div(count).unwrap_or(0);
+    (len * (std::mem::size_of::<T>() + average)) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(max_entries: usize, ttl_seconds: u64) -> StructureLimit {
+        StructureLimit { max_entries, ttl_seconds }
+    }
+
+    #[test]
+    fn test_evicts_idle_then_least_recently_used() {
+        let map: DashMap<String, u64> = (0..10u64).map(|i| (format!("user-{}", i), 1_000 + i * 100)).collect();
+        // Newest is 1900: 1000..=1200 are idle for more than 650 seconds
+        let eviction = evict_map(&map, limit(4, 650), |used| *used);
+        assert_eq!(eviction, Eviction { expired: 3, over_limit: 3 });
+        let mut kept: Vec<String> = map.iter().map(|e| e.key().clone()).collect();
+        kept.sort();
+        assert_eq!(kept, vec!["user-6", "user-7", "user-8", "user-9"]);
+
+        let mut window: VecDeque<u32> = (0..5).collect();
+        assert_eq!(trim_window(&mut window, limit(2, 0)).over_limit, 3);
+        assert_eq!(window, VecDeque::from(vec![3, 4]));
+        assert_eq!(trim_window(&mut window, limit(0, 0)).over_limit, 0);
+    }
+
+    #[test]
+    fn test_report_groups_structures_by_subsystem() {
+        let accountant = MemoryAccountant::new(MemorySettings {
+            limits: BTreeMap::from([("behavioral.sessions".to_string(), limit(5, 0))]),
+            ..MemorySettings::default()
+        })
+        .unwrap();
+        assert_eq!(accountant.limit("behavioral.sessions"), limit(5, 0));
+        assert_eq!(accountant.limit("behavioral.ip_profiles"), limit(200_000, 7 * 86_400));
+
+        accountant.record("behavioral.sessions", 5, 500, Eviction { expired: 1, over_limit: 2 });
+        accountant.record("behavioral.ip_profiles", 10, 1_000, Eviction::default());
+        accountant.record("correlation.events", 3, 300, Eviction::default());
+        let report = accountant.report();
+        assert_eq!(report.total_estimated_bytes, 1_800);
+        assert_eq!(report.subsystems["behavioral"].estimated_bytes, 1_500);
+        assert_eq!(report.subsystems["behavioral"].structures["sessions"].evicted_over_limit_total, 2);
+        assert_eq!(accountant.get_metrics()["memory_behavioral_sessions_evicted_total"], 3.0);
+
+        assert!(accountant.sweep_due());
+        assert!(!accountant.sweep_due());
+    }
+
+    #[test]
+    fn test_rejects_unknown_structures_and_disabled_is_unbounded() {
+        let typo = MemorySettings {
+            limits: BTreeMap::from([("behavioral.user_profile".to_string(), limit(1, 0))]),
+            ..MemorySettings::default()
+        };
+        assert!(typo.validate().is_err());
+        let accountant = MemoryAccountant::new(MemorySettings { enabled: false, ..MemorySettings::default() }).unwrap();
+        assert_eq!(accountant.limit("behavioral.user_profiles"), StructureLimit::default());
+        assert!(estimate_bytes(10, ["abcd".to_string()].iter()) > 60);
+    }
+}
@@ -16,7 +16,7 @@
 //! 
 //! ## Usage
 //! ```rust
-//! use siem_rust_core::ml_engine::{MLAnomalyEngine, MLAnomalyResult};
+//! use siem_rust_core::{MLAnomalyEngine, MLAnomalyResult};
 //! 
 //! let engine = MLAnomalyEngine::new(10, 2.0, 0.1);
 //! engine.update_stats("cpu_usage", 85.5);
@@ -81,7 +81,7 @@ impl MLAnomalyEngine {
     /// 
     /// # Examples
     /// ```rust
-    /// use siem_rust_core::ml_engine::MLAnomalyEngine;
+    /// use siem_rust_core::MLAnomalyEngine;
     /// 
     /// let engine = MLAnomalyEngine::new(10, 2.5, 0.15);
     /// ```
@@ -112,7 +112,7 @@ impl MLAnomalyEngine {
     /// 
     /// # Examples
     /// ```rust
-    /// use siem_rust_core::ml_engine::MLAnomalyEngine;
+    /// use siem_rust_core::MLAnomalyEngine;
     /// 
     /// let engine = MLAnomalyEngine::new(5, 2.0, 0.1);
     /// engine.update_stats("cpu_usage", 75.5);
@@ -152,7 +152,7 @@ impl MLAnomalyEngine {
     /// 
     /// # Examples
     /// ```rust
-    /// use siem_rust_core::ml_engine::MLAnomalyEngine;
+    /// use siem_rust_core::MLAnomalyEngine;
     /// 
     /// let engine = MLAnomalyEngine::new(5, 2.0, 0.1);
     /// engine.update_stats("cpu_usage", 75.0);
@@ -161,6 +161,12 @@ impl MLAnomalyEngine {
     /// println!("Anomaly: {}, Score: {:.2}", result.is_anomaly, result.score);
     /// ```
     pub fn score(&self, feature: &str, value: f32) -> MLAnomalyResult {
+        self.score_with_threshold(feature, value, self.z_threshold)
+    }
+
+    /// Score a feature value against `z_threshold` instead of the engine's own,
+    /// e.g. a per-source threshold from the anomaly tuner
+    pub fn score_with_threshold(&self, feature: &str, value: f32, z_threshold: f32) -> MLAnomalyResult {
         let mean = self.baseline.get(feature).map(|v| *v.value()).unwrap_or(value);
         let std = self.stddev.get(feature).map(|v| *v.value()).unwrap_or(1.0);
         let ewma = self.ewma.get(feature).map(|v| *v.value()).unwrap_or(value);
@@ -172,7 +178,7 @@ impl MLAnomalyEngine {
         let ewma_dev = (value - ewma).abs();
         
         // Determine if anomalous
-        let is_anomaly = z.abs() > self.z_threshold || ewma_dev > self.z_threshold * std;
+        let is_anomaly = z.abs() > z_threshold || ewma_dev > z_threshold * std;
         
         // Build detailed metrics
         let mut details = HashMap::new();
@@ -203,7 +209,7 @@ impl MLAnomalyEngine {
     /// 
     /// # Examples
     /// ```rust
-    /// use siem_rust_core::ml_engine::MLAnomalyEngine;
+    /// use siem_rust_core::MLAnomalyEngine;
     /// use std::collections::HashMap;
     /// 
     /// let engine = MLAnomalyEngine::new(5, 2.0, 0.1);
@@ -219,7 +225,35 @@ impl MLAnomalyEngine {
     pub fn batch_score(&self, features: &HashMap<String, f32>) -> HashMap<String, MLAnomalyResult> {
         features.iter().map(|(k, v)| (k.clone(), self.score(k, *v))).collect()
     }
-} 
+
+    /// Copy the learned per-feature statistics, e.g. for a state snapshot
+    pub fn export_baselines(&self) -> AnomalyBaselines {
+        let collect = |map: &DashMap<String, f32>| map.iter().map(|e| (e.key().clone(), *e.value())).collect();
+        AnomalyBaselines {
+            baseline: collect(&self.baseline),
+            stddev: collect(&self.stddev),
+            ewma: collect(&self.ewma),
+        }
+    }
+
+    /// Replace the learned per-feature statistics with previously exported ones
+    pub fn restore_baselines(&self, baselines: AnomalyBaselines) {
+        for (map, values) in [(&self.baseline, baselines.baseline), (&self.stddev, baselines.stddev), (&self.ewma, baselines.ewma)] {
+            map.clear();
+            for (feature, value) in values {
+                map.insert(feature, value);
+            }
+        }
+    }
+}
+
+/// Serializable copy of the statistics learned by an `MLAnomalyEngine`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnomalyBaselines {
+    pub baseline: HashMap<String, f32>,
+    pub stddev: HashMap<String, f32>,
+    pub ewma: HashMap<String, f32>,
+}
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,582 @@
+//! # Service Manager Integration
+//!
+//! Runs the core under systemd (`Type=notify`) or as a native Windows service
+//! instead of a terminal loop. Under systemd the core reports readiness and a
+//! one-line status through `sd_notify` and pings the watchdog at half of
+//! `WatchdogSec`. The pings come from the async runtime, so a wedged runtime
+//! stops them and systemd restarts the unit. As a Windows service (started by
+//! the service control manager with `--service run`) the core answers Stop and
+//! Shutdown controls and reports its state. Either way SIGTERM, Ctrl-C or a
+//! service stop ends the heartbeat loop for a graceful shutdown.
+//!
+//! `--service install` registers the service (a systemd unit, or `sc.exe create`
+//! with restart-on-failure actions) and `--service uninstall` removes it.
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::error_handling::{SIEMError, SIEMResult};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServiceSettings {
+    /// Unit name under systemd, service name under Windows
+    pub name: String,
+    pub display_name: String,
+    pub description: String,
+    /// `WatchdogSec` of the generated unit; 0 disables the watchdog
+    pub watchdog_seconds: u64,
+    /// Delay before the service manager restarts a failed or hung core
+    pub restart_seconds: u64,
+    /// Time allowed for a graceful stop before the service manager kills the core
+    pub stop_timeout_seconds: u64,
+    /// How often the status line is refreshed when no watchdog is configured
+    pub status_interval_seconds: u64,
+}
+
+impl Default for ServiceSettings {
+    fn default() -> Self {
+        Self {
+            name: "ultra-siem".to_string(),
+            display_name: "Ultra SIEM Core".to_string(),
+            description: "Ultra SIEM detection and incident response core".to_string(),
+            watchdog_seconds: 30,
+            restart_seconds: 5,
+            stop_timeout_seconds: 30,
+            status_interval_seconds: 30,
+        }
+    }
+}
+
+impl ServiceSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        let valid_name = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+        if self.name.is_empty() || !self.name.chars().all(valid_name) {
+            return Err(SIEMError::Validation(format!("service.name {:?} must be letters, digits, '-', '_' or '.'", self.name)));
+        }
+        if self.stop_timeout_seconds == 0 || self.status_interval_seconds == 0 {
+            return Err(SIEMError::Validation("service.stop_timeout_seconds and status_interval_seconds must be positive".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Unit file that runs `exe` from `working_dir`, where the relative config and data paths resolve
+pub fn systemd_unit(settings: &ServiceSettings, exe: &Path, working_dir: &Path) -> String {
+    let mut unit = format!(
+        "[Unit]\nDescription={}\nAfter=network-online.target\nWants=network-online.target\n\n\
+         [Service]\nType=notify\nNotifyAccess=main\nExecStart={} --service run\nWorkingDirectory={}\n",
+        settings.display_name,
+        exe.display(),
+        working_dir.display(),
+    );
+    if settings.watchdog_seconds > 0 {
+        unit.push_str(&format!("WatchdogSec={}\n", settings.watchdog_seconds));
+    }
+    // on-failure covers crashes and watchdog timeouts
+    unit.push_str(&format!(
+        "Restart=on-failure\nRestartSec={}\nTimeoutStopSec={}\nKillSignal=SIGTERM\n\n[Install]\nWantedBy=multi-user.target\n",
+        settings.restart_seconds, settings.stop_timeout_seconds,
+    ));
+    unit
+}
+
+fn run(program: &str, args: &[&str]) -> SIEMResult<()> {
+    let output = Command::new(program).args(args).output()?;
+    if !output.status.success() {
+        return Err(SIEMError::Config(format!(
+            "{} {} failed: {}",
+            program,
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Register the running executable with the platform's service manager; returns what was done
+pub fn install(settings: &ServiceSettings) -> SIEMResult<String> {
+    settings.validate()?;
+    let exe = std::env::current_exe()?;
+    let working_dir = std::env::current_dir()?;
+    if cfg!(windows) {
+        let bin_path = format!("\"{}\" --service run", exe.display());
+        run("sc.exe", &["create", &settings.name, "binPath=", &bin_path, "start=", "auto", "DisplayName=", &settings.display_name])?;
+        run("sc.exe", &["description", &settings.name, &settings.description])?;
+        let delay = (settings.restart_seconds * 1000).to_string();
+        let actions = format!("restart/{0}/restart/{0}/restart/{0}", delay);
+        run("sc.exe", &["failure", &settings.name, "reset=", "86400", "actions=", &actions])?;
+        // The core reads config\ relative to its working directory, which is System32 for services
+        Ok(format!("Registered Windows service {}; run it from {} or use absolute paths in the config", settings.name, working_dir.display()))
+    } else {
+        let path = format!("/etc/systemd/system/{}.service", settings.name);
+        std::fs::write(&path, systemd_unit(settings, &exe, &working_dir))?;
+        run("systemctl", &["daemon-reload"])?;
+        run("systemctl", &["enable", &settings.name])?;
+        Ok(format!("Installed and enabled {}; start it with `systemctl start {}`", path, settings.name))
+    }
+}
+
+/// Stop and remove the registered service
+pub fn uninstall(settings: &ServiceSettings) -> SIEMResult<String> {
+    settings.validate()?;
+    if cfg!(windows) {
+        // Stopping fails when the service is not running, which is fine
+        let _ = run("sc.exe", &["stop", &settings.name]);
+        run("sc.exe", &["delete", &settings.name])?;
+        Ok(format!("Removed Windows service {}", settings.name))
+    } else {
+        let _ = run("systemctl", &["disable", "--now", &settings.name]);
+        let path = format!("/etc/systemd/system/{}.service", settings.name);
+        std::fs::remove_file(&path)?;
+        run("systemctl", &["daemon-reload"])?;
+        Ok(format!("Removed {}", path))
+    }
+}
+
+/// Watchdog ping interval: half of `WATCHDOG_USEC`, when the watchdog is meant for `own_pid`
+fn watchdog_interval(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if pid.is_some_and(|pid| pid.trim().parse::<u32>().ok() != Some(own_pid)) {
+        return None;
+    }
+    let usec = usec?.trim().parse::<u64>().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+/// `sd_notify` client for the socket systemd passes in `NOTIFY_SOCKET`
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct SystemdNotifier {
+    socket: std::os::unix::net::UnixDatagram,
+    watchdog: Option<Duration>,
+}
+
+#[cfg(unix)]
+impl SystemdNotifier {
+    /// The notifier of a `Type=notify` unit; `None` when not started by systemd
+    pub fn from_env() -> Option<Self> {
+        let address = std::env::var("NOTIFY_SOCKET").ok().filter(|a| !a.is_empty())?;
+        let watchdog = watchdog_interval(
+            std::env::var("WATCHDOG_USEC").ok().as_deref(),
+            std::env::var("WATCHDOG_PID").ok().as_deref(),
+            std::process::id(),
+        );
+        match Self::connect(&address, watchdog) {
+            Ok(notifier) => Some(notifier),
+            Err(e) => {
+                warn!("⚠️ Not notifying systemd at {}: {}", address, e);
+                None
+            }
+        }
+    }
+
+    /// Notifier for a socket path, or an abstract socket written as `@name`
+    pub fn connect(address: &str, watchdog: Option<Duration>) -> SIEMResult<Self> {
+        let socket = std::os::unix::net::UnixDatagram::unbound()?;
+        match address.strip_prefix('@') {
+            #[cfg(target_os = "linux")]
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                socket.connect_addr(&std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?)?;
+            }
+            #[cfg(not(target_os = "linux"))]
+            Some(_) => return Err(SIEMError::Config("Abstract notify sockets need Linux".to_string())),
+            None => socket.connect(address)?,
+        }
+        Ok(Self { socket, watchdog })
+    }
+
+    /// Send newline-separated `KEY=value` assignments, e.g. `READY=1`
+    pub fn notify(&self, state: &str) -> SIEMResult<()> {
+        self.socket.send(state.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn watchdog(&self) -> Option<Duration> {
+        self.watchdog
+    }
+}
+
+#[cfg(not(unix))]
+#[derive(Debug)]
+pub struct SystemdNotifier;
+
+#[cfg(not(unix))]
+impl SystemdNotifier {
+    pub fn from_env() -> Option<Self> {
+        None
+    }
+
+    pub fn notify(&self, _state: &str) -> SIEMResult<()> {
+        Ok(())
+    }
+
+    pub fn watchdog(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Status line values may not contain newlines, which separate assignments
+fn status_line(status: &str) -> String {
+    status.replace(['\n', '\r'], " ")
+}
+
+/// The service manager supervising this process, if any
+#[derive(Debug)]
+pub struct ServiceHost {
+    settings: ServiceSettings,
+    systemd: Option<SystemdNotifier>,
+    #[cfg(windows)]
+    scm: Option<windows_scm::ScmSession>,
+    shutdown: Arc<watch::Sender<bool>>,
+    started: Instant,
+}
+
+impl ServiceHost {
+    /// Attach to systemd when `NOTIFY_SOCKET` is set, or to the Windows service control
+    /// manager with `windows_service`, and turn SIGTERM/Ctrl-C into a shutdown request
+    pub fn start(settings: ServiceSettings, windows_service: bool) -> SIEMResult<Arc<Self>> {
+        settings.validate()?;
+        let (shutdown, _) = watch::channel(false);
+        let shutdown = Arc::new(shutdown);
+        #[cfg(windows)]
+        let scm = match windows_service {
+            true => Some(windows_scm::ScmSession::start(&settings, shutdown.clone())?),
+            false => None,
+        };
+        #[cfg(not(windows))]
+        if windows_service {
+            info!("🧩 Not on Windows; --service run only reports to systemd when started by it");
+        }
+        let host = Arc::new(Self {
+            systemd: SystemdNotifier::from_env(),
+            settings,
+            #[cfg(windows)]
+            scm,
+            shutdown,
+            started: Instant::now(),
+        });
+        if let Some(systemd) = &host.systemd {
+            info!("🧩 Running under systemd{}", systemd.watchdog().map(|w| format!(", watchdog ping every {:?}", w)).unwrap_or_default());
+        }
+        host.clone().spawn_signal_listener();
+        Ok(host)
+    }
+
+    /// `systemd`, `windows` or `none`
+    pub fn manager(&self) -> &'static str {
+        #[cfg(windows)]
+        if self.scm.is_some() {
+            return "windows";
+        }
+        match self.systemd {
+            Some(_) => "systemd",
+            None => "none",
+        }
+    }
+
+    fn notify(&self, state: &str) {
+        if let Some(systemd) = &self.systemd {
+            if let Err(e) = systemd.notify(state) {
+                warn!("⚠️ sd_notify {:?} failed: {}", state, e);
+            }
+        }
+    }
+
+    /// Startup finished: systemd considers the unit started, Windows shows it as running
+    pub fn ready(&self, status: &str) {
+        self.notify(&format!("READY=1\nSTATUS={}", status_line(status)));
+        #[cfg(windows)]
+        if let Some(scm) = &self.scm {
+            scm.running();
+        }
+        info!("🧩 Service ready after {:?} ({})", self.started.elapsed(), self.manager());
+    }
+
+    /// Free-form status shown by `systemctl status`
+    pub fn status(&self, status: &str) {
+        self.notify(&format!("STATUS={}", status_line(status)));
+    }
+
+    /// Refresh the status line and ping the watchdog from the runtime until shutdown
+    pub fn spawn_watchdog(self: Arc<Self>, status: impl Fn() -> String + Send + Sync + 'static) -> Option<tokio::task::JoinHandle<()>> {
+        self.systemd.as_ref()?;
+        let watchdog = self.systemd.as_ref().and_then(SystemdNotifier::watchdog);
+        let period = watchdog.unwrap_or(Duration::from_secs(self.settings.status_interval_seconds));
+        let mut shutdown = self.shutdown.subscribe();
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.changed() => break,
+                }
+                let line = status_line(&status());
+                match watchdog {
+                    Some(_) => self.notify(&format!("WATCHDOG=1\nSTATUS={}", line)),
+                    None => self.notify(&format!("STATUS={}", line)),
+                }
+            }
+        }))
+    }
+
+    fn spawn_signal_listener(self: Arc<Self>) {
+        tokio::spawn(async move {
+            #[cfg(unix)]
+            {
+                use tokio::signal::unix::{signal, SignalKind};
+                match signal(SignalKind::terminate()) {
+                    Ok(mut terminate) => {
+                        tokio::select! {
+                            _ = terminate.recv() => info!("🛑 SIGTERM received"),
+                            _ = tokio::signal::ctrl_c() => info!("🛑 Interrupt received"),
+                        }
+                    }
+                    Err(e) => {
+                        warn!("⚠️ Not handling SIGTERM: {}", e);
+                        let _ = tokio::signal::ctrl_c().await;
+                    }
+                }
+            }
+            #[cfg(not(unix))]
+            {
+                let _ = tokio::signal::ctrl_c().await;
+                info!("🛑 Interrupt received");
+            }
+            self.request_shutdown();
+        });
+    }
+
+    pub fn request_shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// Resolves once a signal or the service manager asked the core to stop
+    pub async fn shutdown_requested(&self) {
+        let mut shutdown = self.shutdown.subscribe();
+        let _ = shutdown.wait_for(|requested| *requested).await;
+    }
+
+    /// Graceful stop started; the service manager waits up to `stop_timeout_seconds`
+    pub fn stopping(&self) {
+        self.notify("STOPPING=1\nSTATUS=Stopping");
+        #[cfg(windows)]
+        if let Some(scm) = &self.scm {
+            scm.stopping(self.settings.stop_timeout_seconds);
+        }
+    }
+
+    /// Last call before the process exits; reports the exit code to the Windows SCM
+    pub fn stopped(&self, exit_code: u32) {
+        #[cfg(windows)]
+        if let Some(scm) = &self.scm {
+            scm.stopped(exit_code);
+        }
+        info!("🧩 Service stopped with exit code {} after {:?}", exit_code, self.started.elapsed());
+    }
+}
+
+/// Service control handler of a core started by the Windows service control manager
+#[cfg(windows)]
+mod windows_scm {
+    use std::sync::atomic::{AtomicIsize, Ordering};
+    use std::sync::{mpsc, Arc, Mutex, OnceLock};
+    use std::time::Duration;
+    use log::{info, warn};
+    use tokio::sync::watch;
+    use windows_sys::Win32::Foundation::{ERROR_CALL_NOT_IMPLEMENTED, NO_ERROR};
+    use windows_sys::Win32::System::Services::{
+        RegisterServiceCtrlHandlerExW, SetServiceStatus, StartServiceCtrlDispatcherW, SERVICE_ACCEPT_SHUTDOWN,
+        SERVICE_ACCEPT_STOP, SERVICE_CONTROL_INTERROGATE, SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_RUNNING,
+        SERVICE_START_PENDING, SERVICE_STATUS, SERVICE_STATUS_CURRENT_STATE, SERVICE_STOPPED, SERVICE_STOP_PENDING,
+        SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS,
+    };
+
+    use super::ServiceSettings;
+    use crate::error_handling::{SIEMError, SIEMResult};
+
+    /// The SCM calls back into plain functions, so the session state is process-wide
+    struct Scm {
+        name: Vec<u16>,
+        shutdown: Arc<watch::Sender<bool>>,
+        handle: AtomicIsize,
+        registered: Mutex<Option<mpsc::Sender<Result<(), String>>>>,
+        exit: Mutex<Option<mpsc::Receiver<()>>>,
+        stop_wait_hint_ms: u32,
+    }
+
+    static SCM: OnceLock<Scm> = OnceLock::new();
+
+    fn set_status(state: SERVICE_STATUS_CURRENT_STATE, wait_hint_ms: u32, exit_code: u32) {
+        let Some(scm) = SCM.get() else {
+            return;
+        };
+        let handle = scm.handle.load(Ordering::SeqCst);
+        if handle == 0 {
+            return;
+        }
+        let status = SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: state,
+            dwControlsAccepted: if state == SERVICE_RUNNING { SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN } else { 0 },
+            dwWin32ExitCode: exit_code,
+            dwServiceSpecificExitCode: 0,
+            dwCheckPoint: 0,
+            dwWaitHint: wait_hint_ms,
+        };
+        // SAFETY: the handle came from RegisterServiceCtrlHandlerExW and `status` outlives the call
+        if unsafe { SetServiceStatus(handle, &status) } == 0 {
+            warn!("⚠️ SetServiceStatus failed: {}", std::io::Error::last_os_error());
+        }
+    }
+
+    unsafe extern "system" fn control_handler(
+        control: u32,
+        _event_type: u32,
+        _event_data: *mut std::ffi::c_void,
+        _context: *mut std::ffi::c_void,
+    ) -> u32 {
+        match control {
+            SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+                if let Some(scm) = SCM.get() {
+                    info!("🛑 Stop requested by the service control manager");
+                    set_status(SERVICE_STOP_PENDING, scm.stop_wait_hint_ms, NO_ERROR);
+                    scm.shutdown.send_replace(true);
+                }
+                NO_ERROR
+            }
+            SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+            _ => ERROR_CALL_NOT_IMPLEMENTED,
+        }
+    }
+
+    unsafe extern "system" fn service_main(_argc: u32, _argv: *mut windows_sys::core::PWSTR) {
+        let Some(scm) = SCM.get() else {
+            return;
+        };
+        let registered = scm.registered.lock().unwrap().take();
+        // SAFETY: `name` is a NUL-terminated UTF-16 string that lives as long as the process
+        let handle = unsafe { RegisterServiceCtrlHandlerExW(scm.name.as_ptr(), Some(control_handler), std::ptr::null()) };
+        if handle == 0 {
+            if let Some(registered) = registered {
+                let _ = registered.send(Err(std::io::Error::last_os_error().to_string()));
+            }
+            return;
+        }
+        scm.handle.store(handle, Ordering::SeqCst);
+        set_status(SERVICE_START_PENDING, 60_000, NO_ERROR);
+        if let Some(registered) = registered {
+            let _ = registered.send(Ok(()));
+        }
+        // Returning ends the service; wait until the core reported SERVICE_STOPPED
+        let exit = scm.exit.lock().unwrap().take();
+        if let Some(exit) = exit {
+            let _ = exit.recv();
+        }
+    }
+
+    #[derive(Debug)]
+    pub(super) struct ScmSession {
+        exit: Mutex<Option<mpsc::Sender<()>>>,
+        dispatcher: Mutex<Option<std::thread::JoinHandle<()>>>,
+    }
+
+    impl ScmSession {
+        /// Connect to the SCM from a dispatcher thread; fails when not started as a service
+        pub(super) fn start(settings: &ServiceSettings, shutdown: Arc<watch::Sender<bool>>) -> SIEMResult<Self> {
+            let (registered_tx, registered_rx) = mpsc::channel();
+            let (exit_tx, exit_rx) = mpsc::channel();
+            let scm = Scm {
+                name: settings.name.encode_utf16().chain(std::iter::once(0)).collect(),
+                shutdown,
+                handle: AtomicIsize::new(0),
+                registered: Mutex::new(Some(registered_tx.clone())),
+                exit: Mutex::new(Some(exit_rx)),
+                stop_wait_hint_ms: (settings.stop_timeout_seconds * 1000).min(u32::MAX as u64) as u32,
+            };
+            if SCM.set(scm).is_err() {
+                return Err(SIEMError::Config("The Windows service session is already started".to_string()));
+            }
+            let dispatcher = std::thread::Builder::new().name("scm-dispatcher".to_string()).spawn(move || {
+                let scm = SCM.get().expect("set before the dispatcher starts");
+                let table = [
+                    SERVICE_TABLE_ENTRYW { lpServiceName: scm.name.as_ptr() as *mut u16, lpServiceProc: Some(service_main) },
+                    SERVICE_TABLE_ENTRYW { lpServiceName: std::ptr::null_mut(), lpServiceProc: None },
+                ];
+                // SAFETY: the table is NUL-terminated and outlives the blocking dispatcher call
+                if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+                    let _ = registered_tx.send(Err(std::io::Error::last_os_error().to_string()));
+                }
+            })?;
+            match registered_rx.recv_timeout(Duration::from_secs(30)) {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => return Err(SIEMError::Config(format!("Not started by the service control manager: {}", e))),
+                Err(_) => return Err(SIEMError::Config("The service control manager did not start the service".to_string())),
+            }
+            Ok(Self { exit: Mutex::new(Some(exit_tx)), dispatcher: Mutex::new(Some(dispatcher)) })
+        }
+
+        pub(super) fn running(&self) {
+            set_status(SERVICE_RUNNING, 0, NO_ERROR);
+        }
+
+        pub(super) fn stopping(&self, timeout_seconds: u64) {
+            set_status(SERVICE_STOP_PENDING, (timeout_seconds * 1000).min(u32::MAX as u64) as u32, NO_ERROR);
+        }
+
+        pub(super) fn stopped(&self, exit_code: u32) {
+            set_status(SERVICE_STOPPED, 0, exit_code);
+            if let Some(exit) = self.exit.lock().unwrap().take() {
+                let _ = exit.send(());
+            }
+            if let Some(dispatcher) = self.dispatcher.lock().unwrap().take() {
+                let _ = dispatcher.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog_interval_is_half_and_only_for_this_process() {
+        assert_eq!(watchdog_interval(Some("30000000"), None, 42), Some(Duration::from_secs(15)));
+        assert_eq!(watchdog_interval(Some("30000000"), Some("42"), 42), Some(Duration::from_secs(15)));
+        assert_eq!(watchdog_interval(Some("30000000"), Some("7"), 42), None);
+        assert_eq!(watchdog_interval(Some("0"), None, 42), None);
+        assert_eq!(watchdog_interval(None, None, 42), None);
+    }
+
+    #[test]
+    fn test_unit_restarts_on_watchdog_timeout() {
+        let settings = ServiceSettings::default();
+        let unit = systemd_unit(&settings, Path::new("/opt/ultra-siem/siem-rust-core"), Path::new("/opt/ultra-siem"));
+        assert!(unit.contains("Type=notify\n"));
+        assert!(unit.contains("ExecStart=/opt/ultra-siem/siem-rust-core --service run\n"));
+        assert!(unit.contains("WatchdogSec=30\n"));
+        assert!(unit.contains("Restart=on-failure\n"));
+        assert!(!systemd_unit(&ServiceSettings { watchdog_seconds: 0, ..settings }, Path::new("x"), Path::new("/")).contains("WatchdogSec"));
+        assert!(ServiceSettings { name: "ultra siem".to_string(), ..Default::default() }.validate().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_notifier_sends_state_to_socket() {
+        let path = std::env::temp_dir().join(format!("notify-{}.sock", uuid::Uuid::new_v4()));
+        let receiver = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+        let notifier = SystemdNotifier::connect(&path.to_string_lossy(), Some(Duration::from_secs(5))).unwrap();
+        notifier.notify(&format!("READY=1\nSTATUS={}", status_line("2 incidents\nok"))).unwrap();
+        let mut buffer = [0u8; 128];
+        let received = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(std::str::from_utf8(&buffer[..received]).unwrap(), "READY=1\nSTATUS=2 incidents ok");
+        assert_eq!(notifier.watchdog(), Some(Duration::from_secs(5)));
+        let _ = std::fs::remove_file(&path);
+    }
+}
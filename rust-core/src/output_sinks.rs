@@ -0,0 +1,626 @@
+//! # Loki and Elasticsearch Output Sinks
+//!
+//! Optional destinations for detections and incidents next to the ClickHouse
+//! path of the Go bridge. Each data type lists the sinks it is written to.
+//! Every record is flattened into the same field mapping whatever the sink:
+//! Loki gets it as a JSON log line under a few low-cardinality labels, and
+//! Elasticsearch/OpenSearch gets it as a bulk-indexed document.
+//!
+//! Detections are append-only: daily indices, or data streams when ILM is
+//! managed through an index template. Incidents are indexed by id into
+//! `<prefix>-incidents`, so every update replaces the previous copy. Records a
+//! sink could not take stay queued, up to `max_pending`, and are retried on the
+//! next flush.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::{DateTime, TimeZone, Utc};
+use futures_util::future::BoxFuture;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tokio::sync::broadcast;
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::autoscaling::QueueSample;
+use crate::bridge_contract::severity_code;
+#[cfg(feature = "chaos")]
+use crate::chaos::FaultInjector;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::{Incident, IncidentResponseEngine, IncidentSeverity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkKind {
+    Loki,
+    /// Elasticsearch or OpenSearch
+    Elasticsearch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataType {
+    Detections,
+    Incidents,
+}
+
+impl DataType {
+    fn as_str(self) -> &'static str {
+        match self {
+            DataType::Detections => "detections",
+            DataType::Incidents => "incidents",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LokiSettings {
+    /// Base URL; records are pushed to `<url>/loki/api/v1/push`
+    pub url: String,
+    /// Sent as `X-Scope-OrgID` for multi-tenant Loki
+    pub tenant_id: String,
+    pub auth_token: String,
+    /// Static labels on every stream, next to `data_type`, `severity` and `category`
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Default for LokiSettings {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:3100".to_string(),
+            tenant_id: String::new(),
+            auth_token: String::new(),
+            labels: BTreeMap::from([("job".to_string(), "ultra-siem".to_string())]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ElasticsearchSettings {
+    pub url: String,
+    pub index_prefix: String,
+    /// Write detections to the `<prefix>-detections` data stream instead of daily indices
+    pub data_streams: bool,
+    /// Takes precedence over `username`/`password`
+    pub api_key: String,
+    pub username: String,
+    pub password: String,
+}
+
+impl Default for ElasticsearchSettings {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:9200".to_string(),
+            index_prefix: "ultra-siem".to_string(),
+            data_streams: false,
+            api_key: String::new(),
+            username: String::new(),
+            password: String::new(),
+        }
+    }
+}
+
+/// Which sinks each data type is written to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SinkSettings {
+    pub enabled: bool,
+    pub detections: Vec<SinkKind>,
+    pub incidents: Vec<SinkKind>,
+    pub batch_size: usize,
+    pub flush_interval_seconds: u64,
+    /// Records queued per sink while it is unreachable; the oldest are dropped beyond this
+    pub max_pending: usize,
+    pub loki: LokiSettings,
+    pub elasticsearch: ElasticsearchSettings,
+}
+
+impl Default for SinkSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            detections: Vec::new(),
+            incidents: Vec::new(),
+            batch_size: 500,
+            flush_interval_seconds: 5,
+            max_pending: 50_000,
+            loki: LokiSettings::default(),
+            elasticsearch: ElasticsearchSettings::default(),
+        }
+    }
+}
+
+/// A detection or incident in the shared field mapping
+#[derive(Debug, Clone, PartialEq)]
+pub struct SinkRecord {
+    pub data_type: DataType,
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub fields: Map<String, Value>,
+}
+
+impl SinkRecord {
+    pub fn detection(threat: &AdvancedThreatResult) -> Self {
+        let timestamp = Utc.timestamp_opt(threat.timestamp as i64, 0).single().unwrap_or_else(Utc::now);
+        let mut fields = common_fields(DataType::Detections, &threat.threat_id, timestamp, threat);
+        fields.insert("severity".to_string(), json!(threat.severity.to_string()));
+        fields.insert("severity_code".to_string(), json!(severity_code(&threat.severity)));
+        Self { data_type: DataType::Detections, id: threat.threat_id.clone(), timestamp, fields }
+    }
+
+    pub fn incident(incident: &Incident) -> Self {
+        let mut fields = common_fields(DataType::Incidents, &incident.id, incident.updated_at, &incident.threat_result);
+        let mut tags: Vec<&String> = incident.tags.iter().collect();
+        tags.sort();
+        fields.insert("severity".to_string(), json!(incident.severity.to_string()));
+        fields.insert("severity_code".to_string(), json!(incident_severity_code(&incident.severity)));
+        fields.insert("message".to_string(), json!(incident.title));
+        fields.insert("incident.status".to_string(), json!(format!("{:?}", incident.status)));
+        fields.insert("incident.assigned_to".to_string(), json!(incident.assigned_to));
+        fields.insert("incident.created_at".to_string(), json!(incident.created_at.to_rfc3339()));
+        fields.insert("incident.resolved_at".to_string(), json!(incident.resolved_at.map(|t| t.to_rfc3339())));
+        fields.insert("threat.id".to_string(), json!(incident.threat_id));
+        fields.insert("tags".to_string(), json!(tags));
+        // The incident's own entity fields win over those of its detection
+        fields.insert("source.ip".to_string(), json!(incident.source_ip));
+        fields.insert("destination.ip".to_string(), json!(incident.destination_ip));
+        fields.insert("user.id".to_string(), json!(incident.user_id));
+        Self { data_type: DataType::Incidents, id: incident.id.clone(), timestamp: incident.updated_at, fields }
+    }
+}
+
+/// Fields shared by detections and incidents, taken from the detection behind them
+fn common_fields(data_type: DataType, id: &str, timestamp: DateTime<Utc>, threat: &AdvancedThreatResult) -> Map<String, Value> {
+    let details = &threat.details;
+    let host = details.get("hostname").or_else(|| details.get("host")).cloned().unwrap_or_default();
+    let mut fields = Map::new();
+    fields.insert("@timestamp".to_string(), json!(timestamp.to_rfc3339()));
+    fields.insert("data_type".to_string(), json!(data_type.as_str()));
+    fields.insert("id".to_string(), json!(id));
+    fields.insert("category".to_string(), json!(threat.category.to_string()));
+    fields.insert("confidence".to_string(), json!(threat.confidence));
+    fields.insert("message".to_string(), json!(threat.description));
+    fields.insert("detection_method".to_string(), json!(threat.detection_method));
+    fields.insert("source.ip".to_string(), json!(threat.source_ip));
+    fields.insert("destination.ip".to_string(), json!(threat.destination_ip));
+    fields.insert("user.id".to_string(), json!(threat.user_id));
+    fields.insert("host.name".to_string(), json!(host));
+    fields.insert("rule.signatures".to_string(), json!(threat.signatures));
+    fields.insert("threat.iocs".to_string(), json!(threat.iocs));
+    fields
+}
+
+/// Detection codes 1-4 extended with Emergency
+fn incident_severity_code(severity: &IncidentSeverity) -> u8 {
+    match severity {
+        IncidentSeverity::Low => 1,
+        IncidentSeverity::Medium => 2,
+        IncidentSeverity::High => 3,
+        IncidentSeverity::Critical => 4,
+        IncidentSeverity::Emergency => 5,
+    }
+}
+
+pub trait RecordSink: Send + Sync {
+    fn write<'a>(&'a self, records: &'a [SinkRecord]) -> BoxFuture<'a, SIEMResult<()>>;
+}
+
+pub struct LokiSink {
+    client: reqwest::Client,
+    settings: LokiSettings,
+}
+
+impl LokiSink {
+    pub fn new(settings: &LokiSettings) -> SIEMResult<Self> {
+        Ok(Self { client: reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?, settings: settings.clone() })
+    }
+}
+
+/// Loki push request: one stream per label set, values in nanoseconds
+pub fn loki_push_body(static_labels: &BTreeMap<String, String>, records: &[SinkRecord]) -> Value {
+    let mut streams: BTreeMap<BTreeMap<String, String>, Vec<Value>> = BTreeMap::new();
+    for record in records {
+        let mut labels = static_labels.clone();
+        labels.insert("data_type".to_string(), record.data_type.as_str().to_string());
+        for label in ["severity", "category"] {
+            labels.insert(label.to_string(), record.fields.get(label).and_then(Value::as_str).unwrap_or_default().to_string());
+        }
+        let nanos = record.timestamp.timestamp_nanos_opt().unwrap_or_default();
+        let line = Value::Object(record.fields.clone()).to_string();
+        streams.entry(labels).or_default().push(json!([nanos.to_string(), line]));
+    }
+    let streams: Vec<Value> = streams.into_iter().map(|(stream, values)| json!({ "stream": stream, "values": values })).collect();
+    json!({ "streams": streams })
+}
+
+impl RecordSink for LokiSink {
+    fn write<'a>(&'a self, records: &'a [SinkRecord]) -> BoxFuture<'a, SIEMResult<()>> {
+        Box::pin(async move {
+            let mut request = self
+                .client
+                .post(format!("{}/loki/api/v1/push", self.settings.url.trim_end_matches('/')))
+                .json(&loki_push_body(&self.settings.labels, records));
+            if !self.settings.tenant_id.is_empty() {
+                request = request.header("X-Scope-OrgID", &self.settings.tenant_id);
+            }
+            if !self.settings.auth_token.is_empty() {
+                request = request.bearer_auth(&self.settings.auth_token);
+            }
+            request.send().await?.error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+pub struct ElasticsearchSink {
+    client: reqwest::Client,
+    settings: ElasticsearchSettings,
+}
+
+impl ElasticsearchSink {
+    pub fn new(settings: &ElasticsearchSettings) -> SIEMResult<Self> {
+        Ok(Self { client: reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?, settings: settings.clone() })
+    }
+}
+
+/// NDJSON `_bulk` body: detections are created append-only, incidents indexed by id
+pub fn bulk_body(settings: &ElasticsearchSettings, records: &[SinkRecord]) -> String {
+    let mut body = String::new();
+    for record in records {
+        let action = match record.data_type {
+            DataType::Detections if settings.data_streams => {
+                json!({ "create": { "_index": format!("{}-detections", settings.index_prefix), "_id": record.id } })
+            }
+            DataType::Detections => json!({ "create": {
+                "_index": format!("{}-detections-{}", settings.index_prefix, record.timestamp.format("%Y.%m.%d")),
+                "_id": record.id,
+            } }),
+            DataType::Incidents => json!({ "index": { "_index": format!("{}-incidents", settings.index_prefix), "_id": record.id } }),
+        };
+        body.push_str(&action.to_string());
+        body.push('\n');
+        body.push_str(&Value::Object(record.fields.clone()).to_string());
+        body.push('\n');
+    }
+    body
+}
+
+impl RecordSink for ElasticsearchSink {
+    fn write<'a>(&'a self, records: &'a [SinkRecord]) -> BoxFuture<'a, SIEMResult<()>> {
+        Box::pin(async move {
+            let mut request = self
+                .client
+                .post(format!("{}/_bulk", self.settings.url.trim_end_matches('/')))
+                .header("Content-Type", "application/x-ndjson")
+                .body(bulk_body(&self.settings, records));
+            if !self.settings.api_key.is_empty() {
+                request = request.header("Authorization", format!("ApiKey {}", self.settings.api_key));
+            } else if !self.settings.username.is_empty() {
+                request = request.basic_auth(&self.settings.username, Some(&self.settings.password));
+            }
+            let response: Value = request.send().await?.error_for_status()?.json().await?;
+            if response["errors"].as_bool() != Some(true) {
+                return Ok(());
+            }
+            // A detection created twice after a retry is already stored
+            let failed: Vec<&Value> = response["items"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|item| item.as_object()?.values().next())
+                .filter(|result| result["error"].is_object() && result["status"].as_u64() != Some(409))
+                .collect();
+            match failed.first() {
+                None => Ok(()),
+                Some(first) => Err(SIEMError::Other(format!(
+                    "Elasticsearch rejected {} of {} documents: {}",
+                    failed.len(),
+                    records.len(),
+                    first["error"]["reason"].as_str().unwrap_or("unknown error")
+                ))),
+            }
+        })
+    }
+}
+
+struct SinkQueue {
+    kind: SinkKind,
+    sink: Arc<dyn RecordSink>,
+    data_types: HashSet<DataType>,
+    pending: Mutex<VecDeque<SinkRecord>>,
+    written: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Routes detections and incidents to their configured sinks
+pub struct OutputSinks {
+    settings: SinkSettings,
+    queues: Vec<SinkQueue>,
+    #[cfg(feature = "chaos")]
+    chaos: Option<FaultInjector>,
+}
+
+/// Writer task state that survives a restart of the task
+struct SinkTaskState {
+    detections: Option<broadcast::Receiver<AdvancedThreatResult>>,
+    since: DateTime<Utc>,
+}
+
+impl std::fmt::Debug for OutputSinks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputSinks").field("sinks", &self.queues.iter().map(|q| q.kind).collect::<Vec<_>>()).finish()
+    }
+}
+
+impl OutputSinks {
+    pub fn new(settings: SinkSettings) -> SIEMResult<Self> {
+        let mut sinks: Vec<(SinkKind, Arc<dyn RecordSink>)> = Vec::new();
+        let selected = |kind| settings.detections.contains(&kind) || settings.incidents.contains(&kind);
+        if selected(SinkKind::Loki) {
+            sinks.push((SinkKind::Loki, Arc::new(LokiSink::new(&settings.loki)?)));
+        }
+        if selected(SinkKind::Elasticsearch) {
+            sinks.push((SinkKind::Elasticsearch, Arc::new(ElasticsearchSink::new(&settings.elasticsearch)?)));
+        }
+        Ok(Self::with_sinks(settings, sinks))
+    }
+
+    pub fn with_sinks(settings: SinkSettings, sinks: Vec<(SinkKind, Arc<dyn RecordSink>)>) -> Self {
+        let queues = sinks
+            .into_iter()
+            .map(|(kind, sink)| {
+                let data_types = [(DataType::Detections, &settings.detections), (DataType::Incidents, &settings.incidents)]
+                    .into_iter()
+                    .filter(|(_, kinds)| kinds.contains(&kind))
+                    .map(|(data_type, _)| data_type)
+                    .collect();
+                SinkQueue {
+                    kind,
+                    sink,
+                    data_types,
+                    pending: Mutex::new(VecDeque::new()),
+                    written: AtomicU64::new(0),
+                    dropped: AtomicU64::new(0),
+                }
+            })
+            .collect();
+        Self {
+            settings,
+            queues,
+            #[cfg(feature = "chaos")]
+            chaos: None,
+        }
+    }
+
+    /// Delay batch writes and panic the writer task for resilience testing
+    #[cfg(feature = "chaos")]
+    pub fn set_fault_injector(&mut self, injector: FaultInjector) {
+        self.chaos = injector.settings().enabled.then_some(injector);
+    }
+
+    /// Queue a record for every sink its data type is routed to
+    pub fn push(&self, record: SinkRecord) {
+        for queue in self.queues.iter().filter(|q| q.data_types.contains(&record.data_type)) {
+            let mut pending = queue.pending.lock().unwrap();
+            pending.push_back(record.clone());
+            if pending.len() > self.settings.max_pending {
+                pending.pop_front();
+                queue.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Queue incidents updated after `since`; returns the newest update seen
+    pub fn collect_incidents(&self, engine: &IncidentResponseEngine, since: DateTime<Utc>) -> DateTime<Utc> {
+        let mut latest = since;
+        for incident in engine.get_all_incidents().iter().filter(|i| i.updated_at > since) {
+            latest = latest.max(incident.updated_at);
+            self.push(SinkRecord::incident(incident));
+        }
+        latest
+    }
+
+    /// Write queued records in batches; a failing sink keeps its records for the next flush
+    pub async fn flush(&self) -> usize {
+        let mut written = 0;
+        for queue in &self.queues {
+            loop {
+                let batch: Vec<SinkRecord> = {
+                    let pending = queue.pending.lock().unwrap();
+                    pending.iter().take(self.settings.batch_size.max(1)).cloned().collect()
+                };
+                if batch.is_empty() {
+                    break;
+                }
+                #[cfg(feature = "chaos")]
+                if let Some(chaos) = &self.chaos {
+                    chaos.delay_sink_write().await;
+                    chaos.maybe_panic("output_sinks");
+                }
+                if let Err(e) = queue.sink.write(&batch).await {
+                    warn!("⚠️ {:?} sink unavailable, {} records queued: {}", queue.kind, queue.pending.lock().unwrap().len(), e);
+                    break;
+                }
+                let mut pending = queue.pending.lock().unwrap();
+                let done = batch.len().min(pending.len());
+                pending.drain(..done);
+                queue.written.fetch_add(batch.len() as u64, Ordering::Relaxed);
+                written += batch.len();
+            }
+        }
+        written
+    }
+
+    /// Write one record straight to every sink it is routed to, bypassing the queues
+    pub async fn probe(&self, record: &SinkRecord) -> Vec<(SinkKind, SIEMResult<()>)> {
+        let mut results = Vec::new();
+        for queue in self.queues.iter().filter(|q| q.data_types.contains(&record.data_type)) {
+            results.push((queue.kind, queue.sink.write(std::slice::from_ref(record)).await));
+        }
+        results
+    }
+
+    /// Pending and written records per sink, as `sink_<kind>` queues
+    pub fn queue_samples(&self) -> Vec<(String, QueueSample)> {
+        self.queues
+            .iter()
+            .map(|queue| {
+                let sample = QueueSample { depth: queue.pending.lock().unwrap().len() as u64, processed: queue.written.load(Ordering::Relaxed) };
+                (format!("sink_{}", format!("{:?}", queue.kind).to_lowercase()), sample)
+            })
+            .collect()
+    }
+
+    pub fn get_metrics(&self) -> std::collections::HashMap<String, f64> {
+        let mut metrics = std::collections::HashMap::new();
+        for queue in &self.queues {
+            let name = format!("{:?}", queue.kind).to_lowercase();
+            metrics.insert(format!("sink_{}_written", name), queue.written.load(Ordering::Relaxed) as f64);
+            metrics.insert(format!("sink_{}_dropped", name), queue.dropped.load(Ordering::Relaxed) as f64);
+            metrics.insert(format!("sink_{}_pending", name), queue.pending.lock().unwrap().len() as f64);
+        }
+        metrics
+    }
+
+    /// Write detections as they arrive and changed incidents on every flush
+    pub fn spawn(
+        self: Arc<Self>,
+        detections: Option<broadcast::Receiver<AdvancedThreatResult>>,
+        incidents: Arc<IncidentResponseEngine>,
+    ) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.settings.enabled || self.queues.is_empty() {
+            return None;
+        }
+        info!("📤 Writing detections to {:?} and incidents to {:?}", self.settings.detections, self.settings.incidents);
+        let state = Arc::new(tokio::sync::Mutex::new(SinkTaskState { detections, since: Utc::now() }));
+        #[cfg(feature = "chaos")]
+        if let Some(chaos) = self.chaos.clone() {
+            return Some(chaos.spawn_supervised("output_sinks", move || self.clone().run(state.clone(), incidents.clone())));
+        }
+        Some(tokio::spawn(self.run(state, incidents)))
+    }
+
+    async fn run(self: Arc<Self>, state: Arc<tokio::sync::Mutex<SinkTaskState>>, incidents: Arc<IncidentResponseEngine>) {
+        let mut guard = state.lock().await;
+        let state = &mut *guard;
+        let mut flush = tokio::time::interval(Duration::from_secs(self.settings.flush_interval_seconds.max(1)));
+        loop {
+            tokio::select! {
+                received = async { state.detections.as_mut().unwrap().recv().await }, if state.detections.is_some() => match received {
+                    Ok(threat) => self.push(SinkRecord::detection(&threat)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => warn!("⚠️ Output sinks skipped {} detections", skipped),
+                    Err(broadcast::error::RecvError::Closed) => state.detections = None,
+                },
+                _ = flush.tick() => {
+                    if !self.settings.incidents.is_empty() {
+                        state.since = self.collect_incidents(&incidents, state.since);
+                    }
+                    self.flush().await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use crate::incident_response::{AlertConfig, SOARConfig};
+    use crate::threat_detection::ThreatSeverity;
+
+    #[derive(Default)]
+    struct RecordingSink {
+        records: Mutex<Vec<SinkRecord>>,
+        down: AtomicBool,
+    }
+
+    impl RecordSink for RecordingSink {
+        fn write<'a>(&'a self, records: &'a [SinkRecord]) -> BoxFuture<'a, SIEMResult<()>> {
+            Box::pin(async move {
+                if self.down.load(Ordering::Relaxed) {
+                    return Err(SIEMError::Other("connection refused".to_string()));
+                }
+                self.records.lock().unwrap().extend_from_slice(records);
+                Ok(())
+            })
+        }
+    }
+
+    fn threat() -> AdvancedThreatResult {
+        AdvancedThreatResult {
+            timestamp: 1_700_000_000,
+            severity: ThreatSeverity::High,
+            source_ip: "203.0.113.7".to_string(),
+            description: "UNION SELECT password FROM users".to_string(),
+            details: [("hostname".to_string(), "web-01".to_string())].into(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_records_route_per_data_type_and_survive_outages() {
+        let (loki, elastic) = (Arc::new(RecordingSink::default()), Arc::new(RecordingSink::default()));
+        let settings = SinkSettings {
+            enabled: true,
+            detections: vec![SinkKind::Loki, SinkKind::Elasticsearch],
+            incidents: vec![SinkKind::Elasticsearch],
+            batch_size: 2,
+            ..Default::default()
+        };
+        let sinks = OutputSinks::with_sinks(settings, vec![(SinkKind::Loki, loki.clone()), (SinkKind::Elasticsearch, elastic.clone())]);
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let incident = engine.process_threat(threat()).await.unwrap();
+
+        elastic.down.store(true, Ordering::Relaxed);
+        for _ in 0..3 {
+            sinks.push(SinkRecord::detection(&threat()));
+        }
+        let since = sinks.collect_incidents(&engine, incident.updated_at - chrono::Duration::seconds(1));
+        assert_eq!(since, incident.updated_at);
+        assert_eq!(sinks.flush().await, 3);
+        assert_eq!(sinks.get_metrics()["sink_elasticsearch_pending"], 4.0);
+
+        elastic.down.store(false, Ordering::Relaxed);
+        assert_eq!(sinks.flush().await, 4);
+        assert!(loki.records.lock().unwrap().iter().all(|r| r.data_type == DataType::Detections));
+        let stored = elastic.records.lock().unwrap();
+        assert_eq!(stored.iter().filter(|r| r.data_type == DataType::Incidents).count(), 1);
+
+        // Both data types share the field names of the normalized mapping
+        let detection = &stored[0].fields;
+        let incident = &stored.iter().find(|r| r.data_type == DataType::Incidents).unwrap().fields;
+        for field in ["@timestamp", "id", "severity", "severity_code", "category", "source.ip", "user.id", "host.name", "message"] {
+            assert!(detection.contains_key(field) && incident.contains_key(field), "{}", field);
+        }
+        assert_eq!(detection["host.name"], "web-01");
+        assert_eq!(detection["severity_code"], 3);
+    }
+
+    #[test]
+    fn test_wire_formats() {
+        let detection = SinkRecord::detection(&threat());
+        let labels = LokiSettings::default().labels;
+        let push = loki_push_body(&labels, &[detection.clone(), detection.clone()]);
+        let streams = push["streams"].as_array().unwrap();
+        assert_eq!(streams.len(), 1);
+        assert_eq!(streams[0]["stream"]["job"], "ultra-siem");
+        assert_eq!(streams[0]["stream"]["severity"], "High");
+        assert_eq!(streams[0]["values"][0][0], "1700000000000000000");
+
+        let mut settings = ElasticsearchSettings::default();
+        let body = bulk_body(&settings, std::slice::from_ref(&detection));
+        let lines: Vec<Value> = body.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines[0]["create"]["_index"], "ultra-siem-detections-2023.11.14");
+        assert_eq!(lines[1]["source.ip"], "203.0.113.7");
+        settings.data_streams = true;
+        let body = bulk_body(&settings, std::slice::from_ref(&detection));
+        let action: Value = serde_json::from_str(body.lines().next().unwrap()).unwrap();
+        assert_eq!(action["create"]["_index"], "ultra-siem-detections");
+        assert_eq!(action["create"]["_id"], detection.id.as_str());
+    }
+}
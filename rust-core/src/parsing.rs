@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use chrono::{DateTime, NaiveDateTime};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// Field listing the processors that failed on an event
+pub const PARSE_FAILURES_FIELD: &str = "parse_failures";
+
+const MAX_GROK_DEPTH: usize = 16;
+
+/// Per-source chain of processors
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineConfig {
+    pub name: String,
+    /// Value of the event `source` field this pipeline applies to, or `*` for any source
+    pub source: String,
+    #[serde(default)]
+    pub processors: Vec<ProcessorConfig>,
+}
+
+/// Single pipeline step, selected by `type` in the config file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProcessorConfig {
+    /// Extract fields with grok patterns; the first matching pattern wins
+    Grok {
+        #[serde(default = "default_message_field")]
+        field: String,
+        patterns: Vec<String>,
+        /// Additional named patterns usable as `%{NAME}`
+        #[serde(default)]
+        pattern_definitions: HashMap<String, String>,
+    },
+    /// Split `key=value` pairs
+    Kv {
+        #[serde(default = "default_message_field")]
+        field: String,
+        #[serde(default = "default_field_split")]
+        field_split: String,
+        #[serde(default = "default_value_split")]
+        value_split: String,
+        #[serde(default)]
+        prefix: String,
+    },
+    /// Parse a JSON string (or take a nested object) and flatten it into dotted top-level fields
+    JsonFlatten {
+        field: String,
+        #[serde(default = "default_separator")]
+        separator: String,
+        #[serde(default)]
+        prefix: String,
+    },
+    /// Parse a date into unix seconds; formats are strftime strings or `unix`, `unix_ms`, `rfc3339`, `rfc2822`
+    Date {
+        field: String,
+        formats: Vec<String>,
+        #[serde(default = "default_date_target")]
+        target: String,
+    },
+    Rename {
+        from: String,
+        to: String,
+    },
+    /// Set a field to a constant, e.g. a normalized `event_type`
+    Set {
+        field: String,
+        value: serde_json::Value,
+    },
+}
+
+fn default_message_field() -> String {
+    "message".to_string()
+}
+
+fn default_field_split() -> String {
+    " ".to_string()
+}
+
+fn default_value_split() -> String {
+    "=".to_string()
+}
+
+fn default_separator() -> String {
+    ".".to_string()
+}
+
+fn default_date_target() -> String {
+    "timestamp".to_string()
+}
+
+/// How a captured grok value is converted
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Conversion {
+    Text,
+    Int,
+    Float,
+}
+
+/// Grok expression compiled down to a single regex
+#[derive(Debug, Clone)]
+pub struct GrokPattern {
+    regex: Regex,
+    /// Capture group name -> (event field, conversion)
+    fields: Vec<(String, String, Conversion)>,
+}
+
+impl GrokPattern {
+    /// Compile `pattern` against the built-in library plus `definitions`
+    pub fn compile(pattern: &str, definitions: &HashMap<String, String>) -> SIEMResult<Self> {
+        let mut fields = Vec::new();
+        let expanded = expand(pattern, definitions, &mut fields, 0)?;
+        let regex = Regex::new(&expanded)
+            .map_err(|e| SIEMError::Config(format!("Grok pattern '{}' is not a valid regex: {}", pattern, e)))?;
+        Ok(Self { regex, fields })
+    }
+
+    /// Captured fields, or `None` when the pattern does not match
+    pub fn extract(&self, text: &str) -> Option<serde_json::Map<String, serde_json::Value>> {
+        let captures = self.regex.captures(text)?;
+        let mut extracted = serde_json::Map::new();
+        for (group, field, conversion) in &self.fields {
+            if let Some(value) = captures.name(group) {
+                extracted.insert(field.clone(), convert(value.as_str(), *conversion));
+            }
+        }
+        Some(extracted)
+    }
+}
+
+fn builtin_pattern(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "USERNAME" => r"[a-zA-Z0-9._-]+",
+        "USER" => r"%{USERNAME}",
+        "INT" => r"[+-]?[0-9]+",
+        "POSINT" => r"\b[1-9][0-9]*\b",
+        "NUMBER" | "BASE10NUM" => r"[+-]?(?:[0-9]+(?:\.[0-9]+)?|\.[0-9]+)",
+        "WORD" => r"\b\w+\b",
+        "NOTSPACE" => r"\S+",
+        "SPACE" => r"\s*",
+        "DATA" => r".*?",
+        "GREEDYDATA" => r".*",
+        "QUOTEDSTRING" | "QS" => r#""(?:[^"\\]|\\.)*""#,
+        "IPV4" => r"(?:(?:25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])\.){3}(?:25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])",
+        "IPV6" => r"[0-9A-Fa-f]{0,4}(?::[0-9A-Fa-f]{0,4}){2,7}(?:%[0-9A-Za-z]+)?",
+        "IP" => r"(?:%{IPV4}|%{IPV6})",
+        "HOSTNAME" => r"\b[0-9A-Za-z][0-9A-Za-z-]{0,62}(?:\.[0-9A-Za-z][0-9A-Za-z-]{0,62})*\.?\b",
+        "IPORHOST" => r"(?:%{IP}|%{HOSTNAME})",
+        "URIPATH" => r"(?:/[^\s?#]*)+",
+        "URIPARAM" => r"\?\S*",
+        "URIPATHPARAM" => r"%{URIPATH}(?:%{URIPARAM})?",
+        "MONTHDAY" => r"(?:0[1-9]|[12][0-9]|3[01]|[1-9])",
+        "MONTH" => r"\b[A-Za-z]{3,9}\b",
+        "YEAR" => r"[0-9]{4}",
+        "TIME" => r"[0-9]{2}:[0-9]{2}:[0-9]{2}(?:\.[0-9]+)?",
+        "HTTPDATE" => r"%{MONTHDAY}/%{MONTH}/%{YEAR}:%{TIME} [+-][0-9]{4}",
+        "SYSLOGTIMESTAMP" => r"%{MONTH} +%{MONTHDAY} %{TIME}",
+        "TIMESTAMP_ISO8601" => r"[0-9]{4}-[0-9]{2}-[0-9]{2}[T ][0-9]{2}:[0-9]{2}(?::[0-9]{2}(?:\.[0-9]+)?)?(?:Z|[+-][0-9]{2}:?[0-9]{2})?",
+        "LOGLEVEL" => r"(?i:trace|debug|info|notice|warn(?:ing)?|err(?:or)?|crit(?:ical)?|fatal|severe|emerg(?:ency)?)",
+        "SYSLOGPROG" => r"[\w._/%-]+(?:\[[0-9]+\])?",
+        _ => return None,
+    })
+}
+
+fn expand(
+    pattern: &str,
+    definitions: &HashMap<String, String>,
+    fields: &mut Vec<(String, String, Conversion)>,
+    depth: usize,
+) -> SIEMResult<String> {
+    if depth > MAX_GROK_DEPTH {
+        return Err(SIEMError::Config(format!("Grok pattern '{}' nests too deeply", pattern)));
+    }
+    let reference = Regex::new(r"%\{(\w+)(?::([\w.@-]+))?(?::(int|float))?\}").expect("valid grok reference regex");
+
+    let mut expanded = String::with_capacity(pattern.len());
+    let mut last = 0;
+    for captures in reference.captures_iter(pattern) {
+        let whole = captures.get(0).expect("group 0 always present");
+        expanded.push_str(&pattern[last..whole.start()]);
+        last = whole.end();
+
+        let name = &captures[1];
+        let definition = definitions
+            .get(name)
+            .map(String::as_str)
+            .or_else(|| builtin_pattern(name))
+            .ok_or_else(|| SIEMError::Config(format!("Unknown grok pattern %{{{}}}", name)))?;
+        // Only the outermost references capture; nested names are structural
+        let inner = expand(definition, definitions, &mut Vec::new(), depth + 1)?;
+
+        match captures.get(2).filter(|_| depth == 0) {
+            Some(field) => {
+                let group = format!("g{}", fields.len());
+                let conversion = match captures.get(3).map(|m| m.as_str()) {
+                    Some("int") => Conversion::Int,
+                    Some("float") => Conversion::Float,
+                    _ => Conversion::Text,
+                };
+                expanded.push_str(&format!("(?P<{}>{})", group, inner));
+                fields.push((group, field.as_str().to_string(), conversion));
+            }
+            None => expanded.push_str(&format!("(?:{})", inner)),
+        }
+    }
+    expanded.push_str(&pattern[last..]);
+    Ok(expanded)
+}
+
+fn convert(value: &str, conversion: Conversion) -> serde_json::Value {
+    match conversion {
+        Conversion::Int => value.parse::<i64>().map(Into::into).unwrap_or_else(|_| value.into()),
+        Conversion::Float => value.parse::<f64>().map(Into::into).unwrap_or_else(|_| value.into()),
+        Conversion::Text => value.into(),
+    }
+}
+
+/// Compiled form of `ProcessorConfig`
+#[derive(Debug, Clone)]
+enum Processor {
+    Grok { field: String, patterns: Vec<GrokPattern> },
+    Kv { field: String, field_split: String, value_split: String, prefix: String },
+    JsonFlatten { field: String, separator: String, prefix: String },
+    Date { field: String, formats: Vec<String>, target: String },
+    Rename { from: String, to: String },
+    Set { field: String, value: serde_json::Value },
+}
+
+impl Processor {
+    fn compile(config: &ProcessorConfig) -> SIEMResult<Self> {
+        Ok(match config.clone() {
+            ProcessorConfig::Grok { field, patterns, pattern_definitions } => {
+                if patterns.is_empty() {
+                    return Err(SIEMError::Config(format!("Grok processor on '{}' has no patterns", field)));
+                }
+                let patterns = patterns
+                    .iter()
+                    .map(|pattern| GrokPattern::compile(pattern, &pattern_definitions))
+                    .collect::<SIEMResult<Vec<_>>>()?;
+                Processor::Grok { field, patterns }
+            }
+            ProcessorConfig::Kv { field, field_split, value_split, prefix } => {
+                if field_split.is_empty() || value_split.is_empty() {
+                    return Err(SIEMError::Config(format!("Kv processor on '{}' needs non-empty separators", field)));
+                }
+                Processor::Kv { field, field_split, value_split, prefix }
+            }
+            ProcessorConfig::JsonFlatten { field, separator, prefix } => Processor::JsonFlatten { field, separator, prefix },
+            ProcessorConfig::Date { field, formats, target } => Processor::Date { field, formats, target },
+            ProcessorConfig::Rename { from, to } => Processor::Rename { from, to },
+            ProcessorConfig::Set { field, value } => Processor::Set { field, value },
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Processor::Grok { .. } => "grok",
+            Processor::Kv { .. } => "kv",
+            Processor::JsonFlatten { .. } => "json_flatten",
+            Processor::Date { .. } => "date",
+            Processor::Rename { .. } => "rename",
+            Processor::Set { .. } => "set",
+        }
+    }
+
+    /// Apply to `fields`; `false` means the processor could not handle the event
+    fn apply(&self, fields: &mut serde_json::Map<String, serde_json::Value>) -> bool {
+        match self {
+            Processor::Grok { field, patterns } => {
+                let Some(text) = fields.get(field).and_then(|v| v.as_str()).map(str::to_string) else {
+                    return false;
+                };
+                match patterns.iter().find_map(|pattern| pattern.extract(&text)) {
+                    Some(extracted) => {
+                        fields.extend(extracted);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Processor::Kv { field, field_split, value_split, prefix } => {
+                let Some(text) = fields.get(field).and_then(|v| v.as_str()).map(str::to_string) else {
+                    return false;
+                };
+                let mut found = false;
+                for pair in text.split(field_split.as_str()) {
+                    if let Some((key, value)) = pair.split_once(value_split.as_str()) {
+                        let key = key.trim();
+                        if key.is_empty() {
+                            continue;
+                        }
+                        let value = value.trim().trim_matches('"');
+                        fields.insert(format!("{}{}", prefix, key), value.into());
+                        found = true;
+                    }
+                }
+                found
+            }
+            Processor::JsonFlatten { field, separator, prefix } => {
+                let nested = match fields.get(field) {
+                    Some(serde_json::Value::String(text)) => match serde_json::from_str::<serde_json::Value>(text) {
+                        Ok(value @ serde_json::Value::Object(_)) => value,
+                        _ => return false,
+                    },
+                    Some(value @ serde_json::Value::Object(_)) => value.clone(),
+                    _ => return false,
+                };
+                if field != "message" {
+                    fields.remove(field);
+                }
+                flatten_into(fields, prefix, separator, &nested);
+                true
+            }
+            Processor::Date { field, formats, target } => {
+                let parsed = fields
+                    .get(field)
+                    .and_then(|value| formats.iter().find_map(|format| parse_date(value, format)));
+                match parsed {
+                    Some(seconds) => {
+                        fields.insert(target.clone(), seconds.into());
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Processor::Rename { from, to } => match fields.remove(from) {
+                Some(value) => {
+                    fields.insert(to.clone(), value);
+                    true
+                }
+                None => false,
+            },
+            Processor::Set { field, value } => {
+                fields.insert(field.clone(), value.clone());
+                true
+            }
+        }
+    }
+}
+
+fn flatten_into(
+    fields: &mut serde_json::Map<String, serde_json::Value>,
+    prefix: &str,
+    separator: &str,
+    value: &serde_json::Value,
+) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (key, child) in object {
+                let name = if prefix.is_empty() { key.clone() } else { format!("{}{}{}", prefix, separator, key) };
+                flatten_into(fields, &name, separator, child);
+            }
+        }
+        other => {
+            fields.insert(prefix.to_string(), other.clone());
+        }
+    }
+}
+
+fn parse_date(value: &serde_json::Value, format: &str) -> Option<i64> {
+    match format {
+        "unix" => value.as_i64().or_else(|| value.as_str()?.trim().parse().ok()),
+        "unix_ms" => value.as_i64().or_else(|| value.as_str()?.trim().parse().ok()).map(|ms: i64| ms / 1000),
+        "rfc3339" => DateTime::parse_from_rfc3339(value.as_str()?).ok().map(|d| d.timestamp()),
+        "rfc2822" => DateTime::parse_from_rfc2822(value.as_str()?).ok().map(|d| d.timestamp()),
+        _ => {
+            let text = value.as_str()?;
+            DateTime::parse_from_str(text, format)
+                .map(|d| d.timestamp())
+                .or_else(|_| NaiveDateTime::parse_from_str(text, format).map(|d| d.and_utc().timestamp()))
+                .ok()
+        }
+    }
+}
+
+/// Compiled parsing pipeline
+#[derive(Debug, Clone)]
+pub struct ParsingPipeline {
+    pub name: String,
+    pub source: String,
+    processors: Vec<Processor>,
+}
+
+impl ParsingPipeline {
+    pub fn compile(config: &PipelineConfig) -> SIEMResult<Self> {
+        let processors = config
+            .processors
+            .iter()
+            .map(Processor::compile)
+            .collect::<SIEMResult<Vec<_>>>()
+            .map_err(|e| SIEMError::Config(format!("Pipeline '{}': {}", config.name, e)))?;
+        Ok(Self {
+            name: config.name.clone(),
+            source: config.source.clone(),
+            processors,
+        })
+    }
+
+    /// Run every processor, recording failed ones in `parse_failures`
+    pub fn apply(&self, event: &mut serde_json::Value) {
+        let Some(fields) = event.as_object_mut() else {
+            return;
+        };
+        let mut failures = Vec::new();
+        for processor in &self.processors {
+            if !processor.apply(fields) {
+                failures.push(serde_json::Value::from(format!("{}:{}", self.name, processor.name())));
+            }
+        }
+        if !failures.is_empty() {
+            fields.insert(PARSE_FAILURES_FIELD.to_string(), failures.into());
+        }
+    }
+}
+
+/// Routes events to the pipeline configured for their `source`
+#[derive(Debug, Clone, Default)]
+pub struct ParsingPipelines {
+    pipelines: Vec<ParsingPipeline>,
+    fingerprint: String,
+}
+
+impl ParsingPipelines {
+    pub fn from_config(configs: &[PipelineConfig]) -> SIEMResult<Self> {
+        let pipelines = configs.iter().map(ParsingPipeline::compile).collect::<SIEMResult<Vec<_>>>()?;
+        let fingerprint = format!("{:x}", Sha256::digest(serde_json::to_vec(configs)?));
+        Ok(Self { pipelines, fingerprint })
+    }
+
+    /// SHA-256 of the configuration the pipelines were compiled from
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    /// Parse `event` with the first pipeline matching its source; returns the pipeline name
+    pub fn process(&self, event: &mut serde_json::Value) -> Option<String> {
+        let source = event.get("source").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let pipeline = self
+            .pipelines
+            .iter()
+            .find(|pipeline| pipeline.source == source)
+            .or_else(|| self.pipelines.iter().find(|pipeline| pipeline.source == "*"))?;
+        pipeline.apply(event);
+        Some(pipeline.name.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const NGINX_PIPELINE: &str = r#"
+        name = "nginx"
+        source = "nginx"
+
+        [[processors]]
+        type = "grok"
+        patterns = ['%{IPORHOST:client_ip} - %{USER:user} \[%{HTTPDATE:access_time}\] "%{WORD:method} %{URIPATHPARAM:path} HTTP/%{NUMBER}" %{INT:status:int}']
+
+        [[processors]]
+        type = "date"
+        field = "access_time"
+        formats = ["%d/%b/%Y:%H:%M:%S %z"]
+
+        [[processors]]
+        type = "rename"
+        from = "client_ip"
+        to = "source_ip"
+    "#;
+
+    #[test]
+    fn test_grok_date_and_rename_pipeline() {
+        let config: PipelineConfig = toml::from_str(NGINX_PIPELINE).unwrap();
+        let pipelines = ParsingPipelines::from_config(&[config]).unwrap();
+        let mut event = json!({
+            "source": "nginx",
+            "message": r#"203.0.113.7 - alice [14/Nov/2023:22:13:20 +0000] "GET /login?next=/admin HTTP/1.1" 401"#
+        });
+
+        assert_eq!(pipelines.process(&mut event).as_deref(), Some("nginx"));
+        assert_eq!(event["source_ip"], "203.0.113.7");
+        assert_eq!(event["user"], "alice");
+        assert_eq!(event["path"], "/login?next=/admin");
+        assert_eq!(event["status"], 401);
+        assert_eq!(event["timestamp"], 1_700_000_000);
+        assert!(event.get(PARSE_FAILURES_FIELD).is_none());
+    }
+
+    #[test]
+    fn test_kv_and_json_flatten_with_failures_recorded() {
+        let config = PipelineConfig {
+            name: "app".to_string(),
+            source: "*".to_string(),
+            processors: vec![
+                ProcessorConfig::Kv {
+                    field: "message".to_string(),
+                    field_split: " ".to_string(),
+                    value_split: "=".to_string(),
+                    prefix: String::new(),
+                },
+                ProcessorConfig::JsonFlatten {
+                    field: "context".to_string(),
+                    separator: ".".to_string(),
+                    prefix: "ctx".to_string(),
+                },
+                ProcessorConfig::Rename { from: "missing".to_string(), to: "other".to_string() },
+            ],
+        };
+        let pipelines = ParsingPipelines::from_config(&[config]).unwrap();
+        let mut event = json!({
+            "message": r#"user=bob action="login" result=failure"#,
+            "context": r#"{"http": {"status": 403}, "region": "eu"}"#
+        });
+
+        pipelines.process(&mut event);
+        assert_eq!(event["action"], "login");
+        assert_eq!(event["ctx.http.status"], 403);
+        assert_eq!(event["ctx.region"], "eu");
+        assert_eq!(event[PARSE_FAILURES_FIELD], json!(["app:rename"]));
+    }
+
+    #[test]
+    fn test_unknown_grok_pattern_is_config_error() {
+        let result = GrokPattern::compile("%{NOPE:field}", &HashMap::new());
+        assert!(matches!(result, Err(SIEMError::Config(_))));
+    }
+}
@@ -0,0 +1,254 @@
+//! Configurable password hashing for the compliance engine.
+//!
+//! New hashes use Argon2id (RFC 9106) in PHC string form, with bcrypt as the
+//! fallback algorithm. Stored hashes are checked against the configured
+//! parameters, so accounts are rehashed transparently at their next login and
+//! the stragglers show up in the migration report.
+
+use std::fmt::Write as _;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version, ARGON2ID_IDENT};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    Argon2id,
+    Bcrypt,
+}
+
+/// Algorithm and cost parameters for new password hashes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasswordHashSettings {
+    pub algorithm: HashAlgorithm,
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    pub bcrypt_cost: u32,
+    /// Rehash with the current parameters after a successful login on older ones
+    pub rehash_on_login: bool,
+}
+
+impl Default for PasswordHashSettings {
+    fn default() -> Self {
+        // OWASP minimums for Argon2id; bcrypt cost 12 when Argon2id is not wanted
+        Self {
+            algorithm: HashAlgorithm::Argon2id,
+            argon2_memory_kib: 19_456,
+            argon2_iterations: 2,
+            argon2_parallelism: 1,
+            bcrypt_cost: 12,
+            rehash_on_login: true,
+        }
+    }
+}
+
+/// Algorithm and parameters a stored hash was made with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "algorithm", rename_all = "snake_case")]
+pub enum HashScheme {
+    Argon2id { memory_kib: u32, iterations: u32, parallelism: u32 },
+    Bcrypt { cost: u32 },
+    Unknown,
+}
+
+impl HashScheme {
+    pub fn of(stored: &str) -> Self {
+        if let Some(hash) = PasswordHash::new(stored).ok().filter(|hash| hash.algorithm == ARGON2ID_IDENT) {
+            return match Params::try_from(&hash) {
+                Ok(params) => HashScheme::Argon2id {
+                    memory_kib: params.m_cost(),
+                    iterations: params.t_cost(),
+                    parallelism: params.p_cost(),
+                },
+                Err(_) => HashScheme::Unknown,
+            };
+        }
+        let mut parts = stored.split('$');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(""), Some("2a" | "2b" | "2x" | "2y"), Some(cost)) => {
+                cost.parse().map(|cost| HashScheme::Bcrypt { cost }).unwrap_or(HashScheme::Unknown)
+            }
+            _ => HashScheme::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for HashScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashScheme::Argon2id { memory_kib, iterations, parallelism } => {
+                write!(f, "argon2id m={},t={},p={}", memory_kib, iterations, parallelism)
+            }
+            HashScheme::Bcrypt { cost } => write!(f, "bcrypt cost {}", cost),
+            HashScheme::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Hashes and verifies passwords with the configured scheme
+#[derive(Debug, Clone, Default)]
+pub struct PasswordHasher {
+    settings: PasswordHashSettings,
+}
+
+impl PasswordHasher {
+    pub fn new(settings: PasswordHashSettings) -> SIEMResult<Self> {
+        match settings.algorithm {
+            HashAlgorithm::Argon2id => {
+                argon2id(settings.argon2_memory_kib, settings.argon2_iterations, settings.argon2_parallelism)?;
+            }
+            HashAlgorithm::Bcrypt if !(4..=31).contains(&settings.bcrypt_cost) => {
+                return Err(SIEMError::Config("password_hashing: bcrypt_cost must be between 4 and 31".to_string()));
+            }
+            HashAlgorithm::Bcrypt => {}
+        }
+        Ok(Self { settings })
+    }
+
+    pub fn settings(&self) -> &PasswordHashSettings {
+        &self.settings
+    }
+
+    /// Scheme new hashes are made with
+    pub fn target(&self) -> HashScheme {
+        match self.settings.algorithm {
+            HashAlgorithm::Argon2id => HashScheme::Argon2id {
+                memory_kib: self.settings.argon2_memory_kib,
+                iterations: self.settings.argon2_iterations,
+                parallelism: self.settings.argon2_parallelism,
+            },
+            HashAlgorithm::Bcrypt => HashScheme::Bcrypt { cost: self.settings.bcrypt_cost },
+        }
+    }
+
+    pub fn hash(&self, password: &str) -> SIEMResult<String> {
+        match self.target() {
+            HashScheme::Argon2id { memory_kib, iterations, parallelism } => {
+                let salt = SaltString::generate(&mut OsRng);
+                argon2id(memory_kib, iterations, parallelism)?
+                    .hash_password(password.as_bytes(), &salt)
+                    .map(|hash| hash.to_string())
+                    .map_err(|e| SIEMError::Other(format!("Argon2id hashing failed: {}", e)))
+            }
+            HashScheme::Bcrypt { cost } => Ok(bcrypt::hash(password, cost)?),
+            HashScheme::Unknown => unreachable!("target is always a known scheme"),
+        }
+    }
+
+    /// Check `password` against a stored hash of either algorithm; Argon2id
+    /// parameters are read from the PHC string
+    pub fn verify(&self, password: &str, stored: &str) -> bool {
+        match PasswordHash::new(stored) {
+            Ok(hash) if hash.algorithm == ARGON2ID_IDENT => Argon2::default().verify_password(password.as_bytes(), &hash).is_ok(),
+            _ => bcrypt::verify(password, stored).unwrap_or(false),
+        }
+    }
+
+    /// True when `stored` was not made with the current scheme and parameters
+    pub fn needs_rehash(&self, stored: &str) -> bool {
+        HashScheme::of(stored) != self.target()
+    }
+
+    /// Accounts whose hashes are not on the current parameters
+    pub fn migration_report<'a>(&self, accounts: impl IntoIterator<Item = (&'a str, &'a str)>) -> PasswordMigrationReport {
+        let target = self.target();
+        let mut total_accounts = 0;
+        let mut outdated: Vec<OutdatedAccount> = accounts
+            .into_iter()
+            .inspect(|_| total_accounts += 1)
+            .filter(|(_, stored)| self.needs_rehash(stored))
+            .map(|(username, stored)| OutdatedAccount { username: username.to_string(), scheme: HashScheme::of(stored) })
+            .collect();
+        outdated.sort_by(|a, b| a.username.cmp(&b.username));
+        PasswordMigrationReport { generated_at: Utc::now(), target, total_accounts, outdated }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutdatedAccount {
+    pub username: String,
+    pub scheme: HashScheme,
+}
+
+/// Accounts still hashed with older or weaker parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordMigrationReport {
+    pub generated_at: DateTime<Utc>,
+    pub target: HashScheme,
+    pub total_accounts: usize,
+    pub outdated: Vec<OutdatedAccount>,
+}
+
+impl PasswordMigrationReport {
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "Password hash migration report ({})\nTarget: {}\n{} of {} accounts still on older parameters\n",
+            self.generated_at.format("%Y-%m-%d %H:%M UTC"),
+            self.target,
+            self.outdated.len(),
+            self.total_accounts
+        );
+        for account in &self.outdated {
+            let _ = writeln!(out, "  {:<24} {}", account.username, account.scheme);
+        }
+        out
+    }
+}
+
+/// Argon2id v1.3 with the given cost; rejects parameters outside RFC 9106 limits
+fn argon2id(memory_kib: u32, iterations: u32, parallelism: u32) -> SIEMResult<Argon2<'static>> {
+    let params = Params::new(memory_kib, iterations, parallelism, None)
+        .map_err(|e| SIEMError::Config(format!("password_hashing: invalid argon2 parameters: {}", e)))?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phc_strings_and_parameter_limits() {
+        let hasher = PasswordHasher::new(PasswordHashSettings { argon2_memory_kib: 64, argon2_iterations: 1, argon2_parallelism: 2, ..Default::default() }).unwrap();
+        let stored = hasher.hash("Correct-Horse-9").unwrap();
+        assert_eq!(HashScheme::of(&stored), HashScheme::Argon2id { memory_kib: 64, iterations: 1, parallelism: 2 });
+        // Verification follows the stored parameters, not the configured ones
+        assert!(PasswordHasher::default().verify("Correct-Horse-9", &stored));
+
+        for broken in ["$argon2id$v=19$m=64,t=1,p=1$c2FsdHNhbHQ", "$argon2i$v=19$m=64,t=1,p=1$c2FsdHNhbHQ$aGFzaA", "plaintext"] {
+            assert!(!hasher.verify("Correct-Horse-9", broken));
+        }
+        assert_eq!(HashScheme::of("$argon2id$v=19$m=4,t=1,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaA"), HashScheme::Unknown);
+        assert!(PasswordHasher::new(PasswordHashSettings { argon2_memory_kib: 8, argon2_parallelism: 2, ..Default::default() }).is_err());
+        assert!(PasswordHasher::new(PasswordHashSettings { argon2_iterations: 0, ..Default::default() }).is_err());
+        assert!(PasswordHasher::new(PasswordHashSettings { algorithm: HashAlgorithm::Bcrypt, bcrypt_cost: 3, ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn test_rehash_and_migration_report_track_parameter_changes() {
+        let bcrypt_hasher = PasswordHasher::new(PasswordHashSettings { algorithm: HashAlgorithm::Bcrypt, bcrypt_cost: 4, ..Default::default() }).unwrap();
+        let old = bcrypt_hasher.hash("Correct-Horse-9").unwrap();
+        assert_eq!(HashScheme::of(&old), HashScheme::Bcrypt { cost: 4 });
+
+        let hasher = PasswordHasher::new(PasswordHashSettings { argon2_memory_kib: 64, argon2_iterations: 1, ..Default::default() }).unwrap();
+        assert!(hasher.verify("Correct-Horse-9", &old));
+        assert!(hasher.needs_rehash(&old));
+
+        let new = hasher.hash("Correct-Horse-9").unwrap();
+        assert!(new.starts_with("$argon2id$v=19$m=64,t=1,p=1$"));
+        assert!(hasher.verify("Correct-Horse-9", &new));
+        assert!(!hasher.verify("correct-horse-9", &new));
+        assert!(!hasher.needs_rehash(&new));
+
+        let report = hasher.migration_report([("bob", old.as_str()), ("alice", new.as_str()), ("carol", "plaintext")]);
+        assert_eq!(report.total_accounts, 3);
+        let outdated: Vec<_> = report.outdated.iter().map(|a| (a.username.as_str(), a.scheme.clone())).collect();
+        assert_eq!(outdated, vec![("bob", HashScheme::Bcrypt { cost: 4 }), ("carol", HashScheme::Unknown)]);
+        assert!(report.render().contains("2 of 3 accounts"));
+    }
+}
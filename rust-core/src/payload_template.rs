@@ -0,0 +1,533 @@
+//! # Webhook Payload Templates
+//!
+//! SOAR platforms and webhook receivers each expect their own JSON shape. A
+//! destination's template is a JSON document whose strings may hold
+//! `{{ path | filter }}` placeholders. They are filled from a fixed context:
+//! `incident.*` fields, the action's own `payload` and the `destination`
+//! name. Templates can read nothing else. There are no includes, loops,
+//! environment or file lookups, only a short list of pure filters.
+//!
+//! The rendered payload is checked against the destination's schema, a
+//! subset of JSON Schema, before it is sent. A payload that fails the check
+//! is not sent at all.
+//!
+//! `TextTemplate` is the same placeholder language over plain text, for
+//! callers with their own fixed set of roots such as the incident digests.
+
+use std::borrow::Cow;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::Incident;
+
+/// Roots a placeholder path may start from
+const ROOTS: [&str; 3] = ["incident", "payload", "destination"];
+/// Schema keywords understood by the payload check
+const SCHEMA_KEYWORDS: [&str; 11] = [
+    "type", "properties", "required", "additionalProperties", "items", "enum", "minLength", "maxLength", "minimum", "maximum", "description",
+];
+const SCHEMA_TYPES: [&str; 7] = ["object", "array", "string", "number", "integer", "boolean", "null"];
+
+/// Payload shaping per outbound webhook or SOAR destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookTemplateSettings {
+    /// Rendered payloads larger than this are refused
+    pub max_payload_bytes: usize,
+    pub destinations: Vec<WebhookTemplate>,
+}
+
+impl Default for WebhookTemplateSettings {
+    fn default() -> Self {
+        Self {
+            max_payload_bytes: 64 * 1024,
+            destinations: Vec::new(),
+        }
+    }
+}
+
+impl WebhookTemplateSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        WebhookTemplates::new(self.clone()).map(|_| ())
+    }
+}
+
+/// Template for requests to one destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookTemplate {
+    pub name: String,
+    /// Requests to URLs starting with this use the template; the first match wins
+    pub url_prefix: String,
+    pub template: Value,
+    /// JSON Schema subset the rendered payload must satisfy
+    #[serde(default)]
+    pub schema: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Upper,
+    Lower,
+    Trim,
+    /// The value as JSON text
+    Json,
+    /// Replaces a missing, null or empty value
+    Default(Value),
+    /// At most this many characters
+    Truncate(usize),
+}
+
+impl Filter {
+    fn parse(spec: &str) -> Result<Self, String> {
+        let (name, arg) = match spec.split_once('(') {
+            Some((name, rest)) => {
+                let arg = rest.strip_suffix(')').ok_or_else(|| format!("unclosed filter argument in '{}'", spec))?;
+                let arg: Value = serde_json::from_str(arg.trim()).map_err(|_| format!("filter argument in '{}' must be a JSON literal", spec))?;
+                (name.trim(), Some(arg))
+            }
+            None => (spec, None),
+        };
+        match (name, arg) {
+            ("upper", None) => Ok(Filter::Upper),
+            ("lower", None) => Ok(Filter::Lower),
+            ("trim", None) => Ok(Filter::Trim),
+            ("json", None) => Ok(Filter::Json),
+            ("default", Some(arg)) => Ok(Filter::Default(arg)),
+            ("truncate", Some(Value::Number(n))) if n.is_u64() => Ok(Filter::Truncate(n.as_u64().unwrap_or_default() as usize)),
+            _ => Err(format!("unknown filter '{}'", spec)),
+        }
+    }
+
+    fn apply(&self, value: Option<Value>) -> Option<Value> {
+        let text = |value: Option<Value>| value.map(|value| match value {
+            Value::String(s) => s,
+            other => other.to_string(),
+        });
+        match self {
+            Filter::Upper => text(value).map(|s| Value::String(s.to_uppercase())),
+            Filter::Lower => text(value).map(|s| Value::String(s.to_lowercase())),
+            Filter::Trim => text(value).map(|s| Value::String(s.trim().to_string())),
+            Filter::Json => value.map(|value| Value::String(value.to_string())),
+            Filter::Default(default) => match value {
+                None | Some(Value::Null) => Some(default.clone()),
+                Some(Value::String(s)) if s.is_empty() => Some(default.clone()),
+                value => value,
+            },
+            Filter::Truncate(max) => text(value).map(|s| Value::String(s.chars().take(*max).collect())),
+        }
+    }
+}
+
+/// `path | filter | filter(arg)` inside `{{ }}`
+#[derive(Debug, Clone, PartialEq)]
+struct Expr {
+    source: String,
+    path: Vec<String>,
+    filters: Vec<Filter>,
+}
+
+impl Expr {
+    fn parse(source: &str, roots: &[&str]) -> Result<Self, String> {
+        let mut parts = source.split('|').map(str::trim);
+        let path: Vec<String> = parts.next().unwrap_or_default().split('.').map(str::to_string).collect();
+        if !roots.contains(&path[0].as_str()) {
+            return Err(format!("'{}' must start with one of {}", source, roots.join(", ")));
+        }
+        if path.iter().any(|segment| segment.is_empty() || !segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')) {
+            return Err(format!("invalid path in '{}'", source));
+        }
+        let filters = parts.map(Filter::parse).collect::<Result<_, _>>()?;
+        Ok(Self { source: source.to_string(), path, filters })
+    }
+
+    fn evaluate(&self, context: &Value) -> Result<Value, String> {
+        let found = self.path.iter().try_fold(context, |value, segment| match value {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        });
+        self.filters
+            .iter()
+            .fold(found.cloned(), |value, filter| filter.apply(value))
+            .ok_or_else(|| format!("'{}' is not set; add a default filter", self.source))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Expr(Expr),
+}
+
+/// Template with its placeholders parsed once, when the settings are loaded
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(Value),
+    /// A string holding placeholders; a lone placeholder keeps its value's JSON type
+    Text(Vec<Segment>),
+    Array(Vec<Node>),
+    Object(Vec<(String, Node)>),
+}
+
+impl Node {
+    fn compile(template: &Value) -> Result<Self, String> {
+        Ok(match template {
+            Value::String(s) if s.contains("{{") => Node::Text(parse_text(s, &ROOTS)?),
+            Value::Array(items) => Node::Array(items.iter().map(Node::compile).collect::<Result<_, _>>()?),
+            Value::Object(map) => Node::Object(map.iter().map(|(k, v)| Ok((k.clone(), Node::compile(v)?))).collect::<Result<_, String>>()?),
+            literal => Node::Literal(literal.clone()),
+        })
+    }
+
+    fn render(&self, context: &Value) -> Result<Value, String> {
+        Ok(match self {
+            Node::Literal(value) => value.clone(),
+            Node::Text(segments) => match &segments[..] {
+                [Segment::Expr(expr)] => expr.evaluate(context)?,
+                segments => Value::String(render_text(segments, context)?),
+            },
+            Node::Array(items) => Value::Array(items.iter().map(|item| item.render(context)).collect::<Result<_, _>>()?),
+            Node::Object(fields) => {
+                Value::Object(fields.iter().map(|(k, v)| Ok((k.clone(), v.render(context)?))).collect::<Result<Map<_, _>, String>>()?)
+            }
+        })
+    }
+}
+
+fn render_text(segments: &[Segment], context: &Value) -> Result<String, String> {
+    let mut text = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Text(s) => text.push_str(s),
+            Segment::Expr(expr) => match expr.evaluate(context)? {
+                Value::String(s) => text.push_str(&s),
+                other => text.push_str(&other.to_string()),
+            },
+        }
+    }
+    Ok(text)
+}
+
+fn parse_text(mut rest: &str, roots: &[&str]) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    while let Some(open) = rest.find("{{") {
+        if open > 0 {
+            segments.push(Segment::Text(rest[..open].to_string()));
+        }
+        let close = rest[open..].find("}}").ok_or_else(|| format!("unclosed placeholder in '{}'", rest))? + open;
+        segments.push(Segment::Expr(Expr::parse(rest[open + 2..close].trim(), roots)?));
+        rest = &rest[close + 2..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest.to_string()));
+    }
+    Ok(segments)
+}
+
+/// Check that `schema` only uses the supported keywords
+fn check_schema(schema: &Value, at: &str) -> Result<(), String> {
+    let map = schema.as_object().ok_or_else(|| format!("schema at {} must be an object", at))?;
+    for (keyword, value) in map {
+        if !SCHEMA_KEYWORDS.contains(&keyword.as_str()) {
+            return Err(format!("unsupported schema keyword '{}' at {}", keyword, at));
+        }
+        let valid = match keyword.as_str() {
+            "type" => match value {
+                Value::String(t) => SCHEMA_TYPES.contains(&t.as_str()),
+                Value::Array(types) => types.iter().all(|t| t.as_str().is_some_and(|t| SCHEMA_TYPES.contains(&t))),
+                _ => false,
+            },
+            "properties" => match value.as_object() {
+                Some(properties) => {
+                    for (name, property) in properties {
+                        check_schema(property, &format!("{}.{}", at, name))?;
+                    }
+                    true
+                }
+                None => false,
+            },
+            "items" => {
+                check_schema(value, &format!("{}[]", at))?;
+                true
+            }
+            "required" => value.as_array().is_some_and(|names| names.iter().all(Value::is_string)),
+            "additionalProperties" => value.is_boolean(),
+            "enum" => value.is_array(),
+            "minLength" | "maxLength" => value.is_u64(),
+            "minimum" | "maximum" => value.is_number(),
+            _ => true,
+        };
+        if !valid {
+            return Err(format!("invalid '{}' at {}", keyword, at));
+        }
+    }
+    Ok(())
+}
+
+fn type_matches(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => false,
+    }
+}
+
+/// Check a rendered payload against a schema that passed `check_schema`
+fn conforms(value: &Value, schema: &Value, at: &str) -> Result<(), String> {
+    match schema.get("type") {
+        Some(Value::String(t)) if !type_matches(value, t) => return Err(format!("{} must be of type {}", at, t)),
+        Some(Value::Array(types)) if !types.iter().filter_map(Value::as_str).any(|t| type_matches(value, t)) => {
+            return Err(format!("{} has none of the allowed types", at))
+        }
+        _ => {}
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            return Err(format!("{} is not one of the allowed values", at));
+        }
+    }
+    if let Value::String(s) = value {
+        let len = s.chars().count() as u64;
+        if schema.get("minLength").and_then(Value::as_u64).is_some_and(|min| len < min) {
+            return Err(format!("{} is too short", at));
+        }
+        if schema.get("maxLength").and_then(Value::as_u64).is_some_and(|max| len > max) {
+            return Err(format!("{} is too long", at));
+        }
+    }
+    if let Some(n) = value.as_f64() {
+        if schema.get("minimum").and_then(Value::as_f64).is_some_and(|min| n < min) || schema.get("maximum").and_then(Value::as_f64).is_some_and(|max| n > max) {
+            return Err(format!("{} is out of range", at));
+        }
+    }
+    if let Value::Object(map) = value {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for name in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+            if !map.contains_key(name) {
+                return Err(format!("{}.{} is required", at, name));
+            }
+        }
+        for (name, field) in map {
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property) => conforms(field, property, &format!("{}.{}", at, name))?,
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    return Err(format!("{}.{} is not allowed", at, name))
+                }
+                None => {}
+            }
+        }
+    }
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            conforms(item, item_schema, &format!("{}[{}]", at, i))?;
+        }
+    }
+    Ok(())
+}
+
+/// Fields of an incident a template can read
+fn incident_context(incident: &Incident) -> Value {
+    let mut tags: Vec<&String> = incident.tags.iter().collect();
+    tags.sort();
+    serde_json::json!({
+        "id": incident.id,
+        "title": incident.title,
+        "description": incident.description,
+        "severity": incident.severity.to_string(),
+        "status": incident.status,
+        "category": incident.threat_result.category.to_string(),
+        "detection_method": incident.threat_result.detection_method,
+        "confidence": incident.threat_result.confidence,
+        "source_ip": incident.source_ip,
+        "destination_ip": incident.destination_ip,
+        "user_id": incident.user_id,
+        "threat_id": incident.threat_id,
+        "tenant": incident.threat_result.details.get("tenant"),
+        "assigned_to": incident.assigned_to,
+        "tags": tags,
+        "timestamp": incident.timestamp,
+        "created_at": incident.created_at.to_rfc3339(),
+        "sla_deadline": incident.sla_deadline.map(|deadline| deadline.to_rfc3339()),
+        "details": incident.threat_result.details,
+    })
+}
+
+#[derive(Debug, Clone)]
+struct CompiledTemplate {
+    name: String,
+    url_prefix: String,
+    template: Node,
+    schema: Option<Value>,
+}
+
+/// Plain-text template whose placeholders may only start from the given roots
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextTemplate {
+    segments: Vec<Segment>,
+}
+
+impl TextTemplate {
+    pub fn compile(template: &str, roots: &[&str]) -> SIEMResult<Self> {
+        let segments = parse_text(template, roots).map_err(|e| SIEMError::Validation(format!("Template: {}", e)))?;
+        Ok(Self { segments })
+    }
+
+    /// Fill the placeholders from `context`, an object keyed by root
+    pub fn render(&self, context: &Value) -> SIEMResult<String> {
+        render_text(&self.segments, context).map_err(|e| SIEMError::Validation(format!("Template: {}", e)))
+    }
+}
+
+/// Compiled destination templates, matched to requests by URL
+#[derive(Debug, Clone)]
+pub struct WebhookTemplates {
+    max_payload_bytes: usize,
+    destinations: Vec<CompiledTemplate>,
+}
+
+impl Default for WebhookTemplates {
+    fn default() -> Self {
+        Self {
+            max_payload_bytes: WebhookTemplateSettings::default().max_payload_bytes,
+            destinations: Vec::new(),
+        }
+    }
+}
+
+impl WebhookTemplates {
+    pub fn new(settings: WebhookTemplateSettings) -> SIEMResult<Self> {
+        let destinations = settings
+            .destinations
+            .into_iter()
+            .map(|destination| {
+                let invalid = |e: String| SIEMError::Config(format!("Webhook template '{}': {}", destination.name, e));
+                if destination.url_prefix.is_empty() {
+                    return Err(invalid("url_prefix must not be empty".to_string()));
+                }
+                if let Some(schema) = &destination.schema {
+                    check_schema(schema, "$").map_err(invalid)?;
+                }
+                Ok(CompiledTemplate {
+                    template: Node::compile(&destination.template).map_err(invalid)?,
+                    name: destination.name,
+                    url_prefix: destination.url_prefix,
+                    schema: destination.schema,
+                })
+            })
+            .collect::<SIEMResult<_>>()?;
+        Ok(Self { max_payload_bytes: settings.max_payload_bytes, destinations })
+    }
+
+    /// Payload to send to `url`: `payload` shaped by the destination's template, or as is without one
+    pub fn shape<'a>(&self, url: &str, payload: &'a Value, incident: &Incident) -> SIEMResult<Cow<'a, Value>> {
+        let Some(destination) = self.destinations.iter().find(|destination| url.starts_with(&destination.url_prefix)) else {
+            return Ok(Cow::Borrowed(payload));
+        };
+        let invalid = |e: String| SIEMError::Validation(format!("Webhook template '{}': {}", destination.name, e));
+        let context = serde_json::json!({
+            "incident": incident_context(incident),
+            "payload": payload,
+            "destination": destination.name,
+        });
+        let rendered = destination.template.render(&context).map_err(invalid)?;
+        let size = serde_json::to_vec(&rendered)?.len();
+        if size > self.max_payload_bytes {
+            return Err(invalid(format!("rendered payload is {} bytes, over the {} byte limit", size, self.max_payload_bytes)));
+        }
+        if let Some(schema) = &destination.schema {
+            conforms(&rendered, schema, "$").map_err(invalid)?;
+        }
+        Ok(Cow::Owned(rendered))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::incident_response::{AlertConfig, IncidentResponseEngine, SOARConfig};
+
+    async fn incident() -> Incident {
+        let engine = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let mut threat = AdvancedThreatResult { source_ip: "203.0.113.9".to_string(), detection_method: "port_scan".to_string(), ..Default::default() };
+        threat.details.insert("tenant".to_string(), "acme".to_string());
+        engine.process_threat(threat).await.unwrap()
+    }
+
+    fn templates(template: Value, schema: Option<Value>) -> SIEMResult<WebhookTemplates> {
+        WebhookTemplates::new(WebhookTemplateSettings {
+            destinations: vec![WebhookTemplate { name: "phantom".to_string(), url_prefix: "https://soar.example/".to_string(), template, schema }],
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_template_shapes_payload_per_destination() {
+        let incident = incident().await;
+        let templates = templates(
+            serde_json::json!({
+                "name": "{{ incident.title | truncate(20) }}",
+                "label": "{{ incident.severity | upper }} from {{ incident.source_ip }}",
+                "confidence": "{{ incident.confidence }}",
+                "container": { "tenant": "{{ incident.details.tenant }}", "owner": "{{ incident.assigned_to | default(\"unassigned\") }}" },
+                "original": "{{ payload | json }}",
+                "tags": ["siem", "{{ destination }}"],
+            }),
+            None,
+        )
+        .unwrap();
+        let payload = serde_json::json!({ "text": "hello" });
+
+        let shaped = templates.shape("https://soar.example/rest/container", &payload, &incident).unwrap().into_owned();
+        assert_eq!(shaped["name"].as_str().unwrap().chars().count(), incident.title.chars().take(20).count());
+        assert_eq!(shaped["label"], format!("{} from 203.0.113.9", incident.severity.to_string().to_uppercase()));
+        assert!(shaped["confidence"].is_number());
+        assert_eq!(shaped["container"], serde_json::json!({ "tenant": "acme", "owner": "unassigned" }));
+        assert_eq!(shaped["original"], "{\"text\":\"hello\"}");
+        assert_eq!(shaped["tags"], serde_json::json!(["siem", "phantom"]));
+
+        // Other destinations get the payload untouched
+        assert!(matches!(templates.shape("https://hooks.example/x", &payload, &incident).unwrap(), Cow::Borrowed(_)));
+    }
+
+    #[tokio::test]
+    async fn test_templates_cannot_reach_outside_their_context() {
+        assert!(templates(serde_json::json!("{{ env.HOME }}"), None).is_err());
+        assert!(templates(serde_json::json!("{{ incident.title | include(\"/etc/passwd\") }}"), None).is_err());
+        assert!(templates(serde_json::json!("{{ incident.title"), None).is_err());
+
+        let incident = incident().await;
+        let missing = templates(serde_json::json!({ "owner": "{{ incident.assigned_to.name }}" }), None).unwrap();
+        let err = missing.shape("https://soar.example/", &Value::Null, &incident).unwrap_err();
+        assert!(err.to_string().contains("add a default filter"));
+    }
+
+    #[tokio::test]
+    async fn test_rendered_payload_must_match_schema() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "required": ["severity", "ip"],
+            "additionalProperties": false,
+            "properties": {
+                "severity": { "type": "string", "enum": ["low", "medium", "high", "critical"] },
+                "ip": { "type": "string", "maxLength": 45 },
+            },
+        });
+        let incident = incident().await;
+        let lower = templates(serde_json::json!({ "severity": "{{ incident.severity | lower }}", "ip": "{{ incident.source_ip }}" }), Some(schema.clone())).unwrap();
+        assert!(lower.shape("https://soar.example/", &Value::Null, &incident).is_ok());
+
+        let upper = templates(serde_json::json!({ "severity": "{{ incident.severity | upper }}", "ip": "{{ incident.source_ip }}" }), Some(schema.clone())).unwrap();
+        assert!(upper.shape("https://soar.example/", &Value::Null, &incident).unwrap_err().to_string().contains("$.severity"));
+        let extra = templates(serde_json::json!({ "severity": "low", "ip": "x", "note": "{{ incident.id }}" }), Some(schema)).unwrap();
+        assert!(extra.shape("https://soar.example/", &Value::Null, &incident).is_err());
+
+        assert!(templates(Value::Null, Some(serde_json::json!({ "$ref": "http://evil.example/schema" }))).is_err());
+    }
+}
@@ -0,0 +1,624 @@
+//! Investigation playbooks: named sequences of typed steps run against an incident.
+//! Every step produces a structured JSON artifact that is attached to the incident
+//! as evidence, and each run is recorded on the incident timeline.
+//!
+//! Playbooks are declared inline under `[[playbooks.playbooks]]` or as one
+//! `*.toml` / `*.json` file per playbook in `playbooks.dir`:
+//!
+//! ```toml
+//! name = "compromised-account"
+//! description = "Scope a suspected account takeover"
+//!
+//! [[steps]]
+//! step = "pull_auth_events"
+//! hours = 24
+//!
+//! [[steps]]
+//! step = "collect_process_list"
+//! continue_on_error = true
+//!
+//! [[steps]]
+//! step = "notify_manager"
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::agent_tasking::AgentDispatcher;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::evidence::{EvidenceKind, EvidenceStore, NewAttachment};
+use crate::incident_response::{Incident, IncidentResponseEngine, ResponseAction, TimelineEntry};
+use crate::related::{RelatedEventSource, RelatedQuery};
+
+fn default_hours() -> u64 {
+    24
+}
+
+/// One step of the library; `step` selects the function in playbook files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum PlaybookStep {
+    /// Running processes from the agent on `host`, by default the incident's host
+    CollectProcessList {
+        #[serde(default)]
+        host: Option<String>,
+    },
+    /// Authentication events of `user` (default: the incident's user) over the last `hours`
+    PullAuthEvents {
+        #[serde(default)]
+        user: Option<String>,
+        #[serde(default = "default_hours")]
+        hours: u64,
+    },
+    /// Active firewall ruleset from the agent on `host`, by default the incident's host
+    SnapshotFirewallRules {
+        #[serde(default)]
+        host: Option<String>,
+    },
+    /// Email the manager of `user` (default: the incident's user) about the incident
+    NotifyManager {
+        #[serde(default)]
+        user: Option<String>,
+    },
+}
+
+impl PlaybookStep {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CollectProcessList { .. } => "collect_process_list",
+            Self::PullAuthEvents { .. } => "pull_auth_events",
+            Self::SnapshotFirewallRules { .. } => "snapshot_firewall_rules",
+            Self::NotifyManager { .. } => "notify_manager",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybookStepSpec {
+    #[serde(flatten)]
+    pub step: PlaybookStep,
+    /// Keep running the remaining steps when this one fails
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Playbook {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub steps: Vec<PlaybookStepSpec>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaybookSettings {
+    pub enabled: bool,
+    /// Directory of `*.toml` / `*.json` playbook files, loaded next to the inline ones
+    pub dir: String,
+    pub playbooks: Vec<Playbook>,
+    /// Event types `pull_auth_events` returns
+    pub auth_event_types: Vec<String>,
+    /// Cap on events per `pull_auth_events` artifact
+    pub max_events: usize,
+    /// User to manager email address, for `notify_manager`
+    pub managers: BTreeMap<String, String>,
+}
+
+impl Default for PlaybookSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: "config/playbooks".to_string(),
+            playbooks: Vec::new(),
+            auth_event_types: ["authentication", "login", "logon", "kerberos", "ssh"].map(String::from).to_vec(),
+            max_events: 1000,
+            managers: BTreeMap::new(),
+        }
+    }
+}
+
+impl PlaybookSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if self.max_events == 0 {
+            return Err(SIEMError::Validation("playbooks.max_events must be positive".to_string()));
+        }
+        if let Some((user, email)) = self.managers.iter().find(|(_, email)| !email.contains('@')) {
+            return Err(SIEMError::Validation(format!("playbooks.managers.{}: '{}' is not an email address", user, email)));
+        }
+        validate_playbooks(&self.playbooks)
+    }
+}
+
+fn validate_playbooks(playbooks: &[Playbook]) -> SIEMResult<()> {
+    let mut names = HashSet::new();
+    for playbook in playbooks {
+        if playbook.name.trim().is_empty() {
+            return Err(SIEMError::Validation("Playbook names must not be empty".to_string()));
+        }
+        if !names.insert(playbook.name.as_str()) {
+            return Err(SIEMError::Validation(format!("Duplicate playbook '{}'", playbook.name)));
+        }
+        if playbook.steps.is_empty() {
+            return Err(SIEMError::Validation(format!("Playbook '{}' has no steps", playbook.name)));
+        }
+        for spec in &playbook.steps {
+            if let PlaybookStep::PullAuthEvents { hours, .. } = spec.step {
+                if !(1..=720).contains(&hours) {
+                    return Err(SIEMError::Validation(format!(
+                        "Playbook '{}': pull_auth_events hours must be between 1 and 720",
+                        playbook.name
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Outcome of one step of a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepOutcome {
+    pub step: String,
+    pub success: bool,
+    /// Artifact summary on success, the error otherwise
+    pub detail: String,
+    /// Evidence attachment holding the artifact
+    pub attachment_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybookRun {
+    pub id: String,
+    pub playbook: String,
+    pub incident_id: String,
+    pub actor: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub steps: Vec<StepOutcome>,
+}
+
+impl PlaybookRun {
+    pub fn success(&self) -> bool {
+        self.steps.iter().all(|step| step.success)
+    }
+}
+
+/// Runs playbooks against incidents and attaches their artifacts
+pub struct PlaybookRunner {
+    settings: PlaybookSettings,
+    playbooks: BTreeMap<String, Playbook>,
+    incidents: Arc<IncidentResponseEngine>,
+    evidence: Arc<EvidenceStore>,
+    events: Option<Arc<dyn RelatedEventSource>>,
+}
+
+impl PlaybookRunner {
+    /// Load the inline playbooks and those in `settings.dir`
+    pub fn new(
+        settings: PlaybookSettings,
+        incidents: Arc<IncidentResponseEngine>,
+        evidence: Arc<EvidenceStore>,
+        events: Option<Arc<dyn RelatedEventSource>>,
+    ) -> SIEMResult<Self> {
+        let mut all = settings.playbooks.clone();
+        if settings.enabled {
+            all.extend(load_dir(Path::new(&settings.dir))?);
+        }
+        validate_playbooks(&all)?;
+        info!("📒 Loaded {} playbook(s)", all.len());
+        Ok(Self {
+            playbooks: all.into_iter().map(|p| (p.name.clone(), p)).collect(),
+            settings,
+            incidents,
+            evidence,
+            events,
+        })
+    }
+
+    pub fn playbooks(&self) -> Vec<Playbook> {
+        self.playbooks.values().cloned().collect()
+    }
+
+    /// Run playbook `name` against an incident, attaching each step's artifact
+    pub async fn run(&self, name: &str, incident_id: &str, actor: &str) -> SIEMResult<PlaybookRun> {
+        if !self.settings.enabled {
+            return Err(SIEMError::Validation("Playbooks are disabled".to_string()));
+        }
+        let playbook = self
+            .playbooks
+            .get(name)
+            .ok_or_else(|| SIEMError::Validation(format!("Playbook '{}' not found", name)))?;
+        let incident = self
+            .incidents
+            .get_incident(incident_id)
+            .ok_or_else(|| SIEMError::Validation(format!("Incident {} not found", incident_id)))?;
+
+        let mut run = PlaybookRun {
+            id: Uuid::new_v4().to_string(),
+            playbook: playbook.name.clone(),
+            incident_id: incident.id.clone(),
+            actor: actor.to_string(),
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            steps: Vec::new(),
+        };
+        let mut halted = false;
+        for spec in &playbook.steps {
+            let step = spec.step.name();
+            if halted {
+                run.steps.push(StepOutcome { step: step.to_string(), success: false, detail: "skipped after a failed step".to_string(), attachment_id: None });
+                continue;
+            }
+            let outcome = match self.execute(&spec.step, &incident).await {
+                Ok((kind, artifact)) => self.attach(&run, step, kind, &artifact).map(|attachment_id| StepOutcome {
+                    step: step.to_string(),
+                    success: true,
+                    detail: summarize(&artifact),
+                    attachment_id: Some(attachment_id),
+                }),
+                Err(e) => Err(e),
+            };
+            run.steps.push(outcome.unwrap_or_else(|e| {
+                warn!("⚠️ Playbook '{}' step {} failed on {}: {}", playbook.name, step, incident.id, e);
+                halted = !spec.continue_on_error;
+                StepOutcome { step: step.to_string(), success: false, detail: e.to_string(), attachment_id: None }
+            }));
+        }
+        run.finished_at = Utc::now();
+
+        let succeeded = run.steps.iter().filter(|s| s.success).count();
+        let message = format!("Playbook '{}' run by {}: {}/{} steps succeeded", run.playbook, actor, succeeded, run.steps.len());
+        self.incidents.add_timeline_entry(&incident.id, TimelineEntry::new("playbook_run", message).with_reference(run.id.clone()))?;
+        Ok(run)
+    }
+
+    async fn execute(&self, step: &PlaybookStep, incident: &Incident) -> SIEMResult<(EvidenceKind, Value)> {
+        match step {
+            PlaybookStep::CollectProcessList { host } => {
+                let host = resolve_host(host.as_deref(), incident)?;
+                let output = collect_from_agent(&*self.agents()?, &host, &incident.id, &ResponseAction::CollectProcessList).await?;
+                Ok((EvidenceKind::HostSnapshot, output))
+            }
+            PlaybookStep::SnapshotFirewallRules { host } => {
+                let host = resolve_host(host.as_deref(), incident)?;
+                let output = collect_from_agent(&*self.agents()?, &host, &incident.id, &ResponseAction::SnapshotFirewallRules).await?;
+                Ok((EvidenceKind::HostSnapshot, output))
+            }
+            PlaybookStep::PullAuthEvents { user, hours } => {
+                let source = self
+                    .events
+                    .as_deref()
+                    .ok_or_else(|| SIEMError::Validation("No event store configured for pull_auth_events".to_string()))?;
+                let user = resolve_user(user.as_deref(), incident)?;
+                let artifact = pull_auth_events(source, &user, *hours, &self.settings.auth_event_types, self.settings.max_events, Utc::now()).await?;
+                Ok((EvidenceKind::QueryResult, artifact))
+            }
+            PlaybookStep::NotifyManager { user } => {
+                let user = resolve_user(user.as_deref(), incident)?;
+                Ok((EvidenceKind::Other, notify_manager(&self.incidents, incident, &user, &self.settings.managers).await?))
+            }
+        }
+    }
+
+    fn agents(&self) -> SIEMResult<Arc<AgentDispatcher>> {
+        self.incidents
+            .agent_dispatcher()
+            .ok_or_else(|| SIEMError::Validation("Agent tasking is not configured".to_string()))
+    }
+
+    fn attach(&self, run: &PlaybookRun, step: &str, kind: EvidenceKind, data: &Value) -> SIEMResult<String> {
+        let artifact = json!({
+            "playbook": run.playbook,
+            "run_id": run.id,
+            "step": step,
+            "collected_at": Utc::now(),
+            "data": data,
+        });
+        let upload = NewAttachment {
+            incident_id: run.incident_id.clone(),
+            kind,
+            filename: format!("{}-{}-{}.json", run.playbook, step, &run.id[..8]),
+            content_type: "application/json".to_string(),
+            uploaded_by: format!("playbook:{}", run.actor),
+        };
+        Ok(self.evidence.attach(&self.incidents, upload, &serde_json::to_vec_pretty(&artifact)?)?.id)
+    }
+}
+
+fn load_dir(dir: &Path) -> SIEMResult<Vec<Playbook>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut paths: Vec<_> = std::fs::read_dir(dir)?.filter_map(|entry| entry.ok().map(|e| e.path())).collect();
+    paths.sort();
+    let mut playbooks = Vec::new();
+    for path in paths {
+        let raw = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") | Some("json") => std::fs::read_to_string(&path)?,
+            _ => continue,
+        };
+        let parsed = if path.extension().is_some_and(|e| e == "json") {
+            serde_json::from_str::<Playbook>(&raw).map_err(|e| e.to_string())
+        } else {
+            toml::from_str::<Playbook>(&raw).map_err(|e| e.to_string())
+        };
+        playbooks.push(parsed.map_err(|e| SIEMError::Config(format!("Playbook {}: {}", path.display(), e)))?);
+    }
+    Ok(playbooks)
+}
+
+fn resolve_host(host: Option<&str>, incident: &Incident) -> SIEMResult<String> {
+    let details = &incident.threat_result.details;
+    host.or_else(|| details.get("hostname").or_else(|| details.get("host")).map(String::as_str))
+        .filter(|h| !h.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| SIEMError::Validation(format!("Incident {} names no host", incident.id)))
+}
+
+fn resolve_user(user: Option<&str>, incident: &Incident) -> SIEMResult<String> {
+    user.or(Some(incident.user_id.as_str()))
+        .filter(|u| !u.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| SIEMError::Validation(format!("Incident {} names no user", incident.id)))
+}
+
+fn summarize(artifact: &Value) -> String {
+    if let Some(processes) = artifact.get("processes").and_then(Value::as_array) {
+        format!("{} process(es)", processes.len())
+    } else if let Some(events) = artifact.get("events").and_then(Value::as_array) {
+        format!("{} event(s)", events.len())
+    } else if let Some(source) = artifact.get("source").and_then(Value::as_str) {
+        format!("ruleset from {}", source)
+    } else if let Some(manager) = artifact.get("manager").and_then(Value::as_str) {
+        format!("notified {}", manager)
+    } else {
+        "done".to_string()
+    }
+}
+
+/// Run a collection action on `host`'s agent and return what it collected
+pub async fn collect_from_agent(agents: &AgentDispatcher, host: &str, incident_id: &str, action: &ResponseAction) -> SIEMResult<Value> {
+    let result = agents.dispatch(host, incident_id, action).await;
+    if !result.success {
+        return Err(SIEMError::Other(result.error_message.unwrap_or_else(|| format!("{:?} failed on {}", action, host))));
+    }
+    let mut output = result.output.ok_or_else(|| SIEMError::Other(format!("Agent {} returned no output", host)))?;
+    if let Value::Object(fields) = &mut output {
+        fields.insert("host".to_string(), Value::String(host.to_string()));
+    }
+    Ok(output)
+}
+
+/// Authentication events of `user` in the `hours` before `now`, newest first
+pub async fn pull_auth_events(
+    source: &dyn RelatedEventSource,
+    user: &str,
+    hours: u64,
+    event_types: &[String],
+    limit: usize,
+    now: DateTime<Utc>,
+) -> SIEMResult<Value> {
+    let query = RelatedQuery {
+        since: now - Duration::hours(hours as i64),
+        until: now,
+        source_ip: None,
+        user: Some(user.to_string()),
+        hostname: None,
+    };
+    let events = source.events(&query, event_types, limit).await?;
+    Ok(json!({
+        "user": user,
+        "since": query.since,
+        "until": query.until,
+        "truncated": events.len() >= limit,
+        "events": events,
+    }))
+}
+
+/// Email the manager of `user` about `incident` and record the action on it
+pub async fn notify_manager(
+    incidents: &IncidentResponseEngine,
+    incident: &Incident,
+    user: &str,
+    managers: &BTreeMap<String, String>,
+) -> SIEMResult<Value> {
+    let manager = managers
+        .get(user)
+        .ok_or_else(|| SIEMError::Validation(format!("No manager configured for user {}", user)))?;
+    let subject = format!("[Ultra SIEM] {:?} incident involving {}", incident.severity, user);
+    let body = format!(
+        "{} is affected by incident {}: {}\n\n{}\n\nThe security team is investigating and may contact you.",
+        user, incident.id, incident.title, incident.description
+    );
+    let action = ResponseAction::SendEmail { to: vec![manager.clone()], subject: subject.clone(), body };
+    let result = incidents
+        .run_actions(&incident.id, vec![action])
+        .await?
+        .pop()
+        .ok_or_else(|| SIEMError::Other("Notification was not executed".to_string()))?;
+    if !result.success {
+        return Err(SIEMError::Other(result.error_message.unwrap_or_else(|| "Notification failed".to_string())));
+    }
+    Ok(json!({ "user": user, "manager": manager, "subject": subject, "action_id": result.action_id }))
+}
+
+/// Run a collection action on this host, called by its agent
+pub async fn collect_local(action: &ResponseAction) -> SIEMResult<Value> {
+    match action {
+        ResponseAction::CollectProcessList => {
+            let processes = if cfg!(target_os = "windows") {
+                parse_tasklist(&command_output("tasklist", &["/fo", "csv", "/nh"]).await?)
+            } else {
+                parse_ps(&command_output("ps", &["-eo", "pid=,ppid=,user=,args="]).await?)
+            };
+            Ok(json!({ "processes": processes }))
+        }
+        ResponseAction::SnapshotFirewallRules => {
+            let sources: &[(&str, &[&str])] = if cfg!(target_os = "windows") {
+                &[("netsh", &["advfirewall", "firewall", "show", "rule", "name=all"])]
+            } else {
+                &[("nft", &["list", "ruleset"]), ("iptables-save", &[])]
+            };
+            let mut last_error = None;
+            for (program, args) in sources {
+                match command_output(program, args).await {
+                    Ok(ruleset) => return Ok(json!({ "source": program, "ruleset": ruleset })),
+                    Err(e) => last_error = Some(e),
+                }
+            }
+            Err(last_error.unwrap_or_else(|| SIEMError::Other("No firewall tool available".to_string())))
+        }
+        action => Err(format!("{:?} is not a collection", action).into()),
+    }
+}
+
+async fn command_output(program: &str, args: &[&str]) -> SIEMResult<String> {
+    let output = tokio::process::Command::new(program).args(args).output().await?;
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parse `ps -eo pid=,ppid=,user=,args=`
+fn parse_ps(output: &str) -> Vec<Value> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid: u32 = fields.next()?.parse().ok()?;
+            let ppid: u32 = fields.next()?.parse().ok()?;
+            let user = fields.next()?;
+            let command_line = fields.collect::<Vec<_>>().join(" ");
+            let name = command_line.split_whitespace().next().unwrap_or_default().rsplit('/').next().unwrap_or_default();
+            Some(json!({ "pid": pid, "ppid": ppid, "user": user, "name": name, "command_line": command_line }))
+        })
+        .collect()
+}
+
+/// Parse `tasklist /fo csv /nh`: `"name","pid","session","session#","mem usage"`
+fn parse_tasklist(output: &str) -> Vec<Value> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.trim().trim_matches('"').split("\",\"").collect();
+            let pid: u32 = fields.get(1)?.parse().ok()?;
+            Some(json!({ "pid": pid, "name": fields[0], "session": fields.get(2).copied().unwrap_or_default() }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::evidence::EvidenceSettings;
+    use crate::incident_response::{AlertConfig, SOARConfig};
+    use crate::related::{MemoryEventSource, RelatedEventRow};
+
+    fn settings() -> PlaybookSettings {
+        let playbook: Playbook = toml::from_str(
+            r#"
+            name = "compromised-account"
+
+            [[steps]]
+            step = "pull_auth_events"
+            hours = 24
+
+            [[steps]]
+            step = "collect_process_list"
+            continue_on_error = true
+
+            [[steps]]
+            step = "notify_manager"
+
+            [[steps]]
+            step = "snapshot_firewall_rules"
+            "#,
+        )
+        .unwrap();
+        PlaybookSettings {
+            enabled: true,
+            dir: String::new(),
+            playbooks: vec![playbook],
+            managers: BTreeMap::from([("jdoe".to_string(), "boss@example.com".to_string())]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_playbook_definition_parses_and_validates() {
+        let settings = settings();
+        settings.validate().unwrap();
+        let steps = &settings.playbooks[0].steps;
+        assert_eq!(steps[0].step, PlaybookStep::PullAuthEvents { user: None, hours: 24 });
+        assert!(steps[1].continue_on_error && !steps[0].continue_on_error);
+
+        let mut invalid = settings.clone();
+        invalid.playbooks.push(invalid.playbooks[0].clone());
+        assert!(invalid.validate().is_err());
+        let mut invalid = settings;
+        invalid.playbooks[0].steps[0].step = PlaybookStep::PullAuthEvents { user: None, hours: 0 };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_process_listings_parse() {
+        let ps = parse_ps("    1     0 root     /sbin/init splash\n  812     1 jdoe     /usr/bin/python3 -m http.server\n");
+        assert_eq!(ps.len(), 2);
+        assert_eq!(ps[1]["pid"], 812);
+        assert_eq!(ps[1]["name"], "python3");
+        assert_eq!(ps[1]["command_line"], "/usr/bin/python3 -m http.server");
+
+        let tasklist = parse_tasklist("\"svchost.exe\",\"1044\",\"Services\",\"0\",\"12,340 K\"\r\n");
+        assert_eq!(tasklist[0]["pid"], 1044);
+        assert_eq!(tasklist[0]["name"], "svchost.exe");
+    }
+
+    #[tokio::test]
+    async fn test_run_attaches_artifacts_and_records_timeline() {
+        let dir = std::env::temp_dir().join(format!("playbook-evidence-{}", Uuid::new_v4()));
+        let evidence = Arc::new(EvidenceStore::open(EvidenceSettings { storage_dir: dir.display().to_string(), ..Default::default() }).unwrap());
+        let incidents = Arc::new(IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled()));
+        let now = Utc::now();
+        let event = |minutes: i64, event_type: &str| RelatedEventRow {
+            timestamp: now - Duration::minutes(minutes),
+            event_type: event_type.to_string(),
+            user: "jdoe".to_string(),
+            ..Default::default()
+        };
+        let events = MemoryEventSource::new(vec![event(10, "authentication"), event(20, "process"), event(60 * 30, "authentication")]);
+        let runner = PlaybookRunner::new(settings(), incidents.clone(), evidence.clone(), Some(Arc::new(events))).unwrap();
+
+        let mut threat = AdvancedThreatResult { user_id: "jdoe".to_string(), ..Default::default() };
+        threat.details.insert("hostname".to_string(), "wks-0317".to_string());
+        let incident = incidents.process_threat(threat).await.unwrap();
+        let run = runner.run("compromised-account", &incident.id, "analyst").await.unwrap();
+
+        let outcomes: Vec<(&str, bool)> = run.steps.iter().map(|s| (s.step.as_str(), s.success)).collect();
+        assert_eq!(
+            outcomes,
+            [("pull_auth_events", true), ("collect_process_list", false), ("notify_manager", true), ("snapshot_firewall_rules", false)]
+        );
+        // Without agent tasking both host collections fail; the process list is allowed to
+        assert!(run.steps[1].detail.contains("Agent tasking"));
+
+        let auth = evidence.read_content(run.steps[0].attachment_id.as_ref().unwrap()).unwrap();
+        let auth: Value = serde_json::from_slice(&auth).unwrap();
+        assert_eq!(auth["data"]["events"].as_array().unwrap().len(), 1);
+        assert_eq!(evidence.list(&incident.id).len(), 2);
+
+        let incident = incidents.get_incident(&incident.id).unwrap();
+        assert!(incident.timeline.iter().any(|e| e.event == "playbook_run" && e.reference.as_deref() == Some(run.id.as_str())));
+        assert!(matches!(&incident.response_actions.last().unwrap().action_type, ResponseAction::SendEmail { to, .. } if to == &["boss@example.com"]));
+        assert!(runner.run("missing", &incident.id, "analyst").await.is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
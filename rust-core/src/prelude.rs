@@ -0,0 +1,27 @@
+//! # Prelude
+//!
+//! The types most embedders need, for a single glob import:
+//!
+//! ```rust
+//! use siem_rust_core::prelude::*;
+//!
+//! let config = SiemConfig::default();
+//! assert!(config.event_time.validate().is_ok());
+//! ```
+//!
+//! Everything here is part of the stable API: it is only removed or changed
+//! incompatibly in a new major version, and `tests/public_api.rs` fails when
+//! the list changes by accident.
+
+pub use crate::error_handling::{SIEMError, SIEMResult};
+pub use crate::config::{SiemConfig, DEFAULT_CONFIG_PATH};
+pub use crate::config_check::{ConfigChecker, DiagnosticReport, DiagnosticSeverity};
+pub use crate::embedded::{UltraSiem, UltraSiemBuilder};
+pub use crate::threat_detection::{SignaturePattern, ThreatCategory, ThreatSeverity};
+pub use crate::advanced_threat_detection::{AdvancedThreatConfig, AdvancedThreatDetectionEngine, AdvancedThreatResult};
+pub use crate::ml_engine::{MLAnomalyEngine, MLAnomalyResult};
+pub use crate::quantum_detector::{QuantumDetector, QuantumPattern};
+#[cfg(feature = "response")]
+pub use crate::incident_response::{
+    AlertConfig, Incident, IncidentResponseEngine, IncidentSeverity, IncidentStatus, SOARConfig,
+};
@@ -1,6 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use log::info;
 use crossbeam_channel::{bounded, Receiver, Sender};
@@ -8,57 +8,177 @@ use dashmap::DashMap;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 
-#[derive(Debug, Clone)]
-pub struct QuantumPatternCache {
-    pub patterns: Arc<DashMap<String, String>>,
-    pub quantum_state: Arc<DashMap<String, String>>,
-    pub match_count: Arc<AtomicU64>,
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// Shortest term a pattern may contain; shorter ones match nearly every event
+const MIN_TERM_LEN: usize = 3;
+const MAX_PATTERN_LEN: usize = 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuantumPatternSettings {
+    /// Patterns added through the API and enable flags of the others; empty keeps them in memory only
+    pub store_path: String,
+    pub max_patterns: usize,
 }
 
-impl QuantumPatternCache {
-    pub fn new() -> Self {
+impl Default for QuantumPatternSettings {
+    fn default() -> Self {
         Self {
-            patterns: Arc::new(DashMap::new()),
-            quantum_state: Arc::new(DashMap::new()),
-            match_count: Arc::new(AtomicU64::new(0)),
+            store_path: "data/quantum_patterns.json".to_string(),
+            max_patterns: 10_000,
         }
     }
+}
 
-    pub fn add_pattern(&self, name: String, pattern: String) {
-        self.patterns.insert(name, pattern); // Store pattern string, not boolean
+impl QuantumPatternSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if self.max_patterns == 0 {
+            return Err(SIEMError::Validation("quantum.max_patterns must be positive".to_string()));
+        }
+        Ok(())
     }
+}
 
-    pub fn match_event(&self, event: &str) -> Vec<String> {
-        let mut matches = Vec::new();
-        for refmulti in self.patterns.iter() {
-            let name = refmulti.key();
-            let pattern = refmulti.value();
-            if event.contains(pattern) {
-                matches.push(name.clone());
-                self.match_count.fetch_add(1, Ordering::Relaxed);
-            }
+/// Where a pattern came from; only API patterns are stored in full, the others are reloaded at startup
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternOrigin {
+    Builtin,
+    ContentPack(String),
+    #[default]
+    Api,
+}
+
+/// Pattern matched when an event contains any of its `|`-separated terms, ignoring case
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuantumPattern {
+    pub id: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub origin: PatternOrigin,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+impl QuantumPattern {
+    pub fn new(id: &str, pattern: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            pattern: pattern.to_string(),
+            description: String::new(),
+            enabled: true,
+            origin: PatternOrigin::Api,
         }
-        matches
     }
+
+    pub fn validate(&self) -> SIEMResult<()> {
+        if self.id.is_empty() || self.id.len() > 128
+            || !self.id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+        {
+            return Err(SIEMError::Validation(format!("Invalid quantum pattern id '{}'", self.id)));
+        }
+        if self.pattern.len() > MAX_PATTERN_LEN {
+            return Err(SIEMError::Validation(format!(
+                "Quantum pattern {} is longer than {} bytes",
+                self.id, MAX_PATTERN_LEN
+            )));
+        }
+        if let Some(term) = self.pattern.split('|').find(|term| term.trim().chars().count() < MIN_TERM_LEN) {
+            return Err(SIEMError::Validation(format!(
+                "Quantum pattern {} has term '{}' shorter than {} characters",
+                self.id,
+                term.trim(),
+                MIN_TERM_LEN
+            )));
+        }
+        Ok(())
+    }
+
+    fn terms(&self) -> Vec<String> {
+        self.pattern.split('|').map(|term| term.trim().to_lowercase()).collect()
+    }
+}
+
+/// Matches of one pattern since startup
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PatternStats {
+    pub matches: u64,
+    pub last_matched: Option<DateTime<Utc>>,
+}
+
+/// A pattern with its match statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatternInfo {
+    #[serde(flatten)]
+    pub pattern: QuantumPattern,
+    pub stats: PatternStats,
+}
+
+#[derive(Debug, Clone)]
+struct LoadedPattern {
+    pattern: QuantumPattern,
+    terms: Vec<String>,
+    stats: PatternStats,
+}
+
+/// Contents of `store_path`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredPatterns {
+    patterns: Vec<QuantumPattern>,
+    /// Built-in and content-pack patterns switched off through the API
+    disabled: BTreeSet<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct QuantumDetector {
-    pub cache: QuantumPatternCache,
+    settings: QuantumPatternSettings,
+    patterns: Arc<DashMap<String, LoadedPattern>>,
+    disabled: Arc<std::sync::Mutex<BTreeSet<String>>>,
+    quantum_state: Arc<DashMap<String, String>>,
+    events_processed: Arc<AtomicU64>,
     pub tx: Sender<String>,
     pub rx: Receiver<String>,
 }
 
 impl QuantumDetector {
+    /// Detector without a pattern store
     pub fn new() -> Self {
         let (tx, rx) = bounded(1000);
         QuantumDetector {
-            cache: QuantumPatternCache::new(),
+            settings: QuantumPatternSettings { store_path: String::new(), ..QuantumPatternSettings::default() },
+            patterns: Arc::new(DashMap::new()),
+            disabled: Arc::new(std::sync::Mutex::new(BTreeSet::new())),
+            quantum_state: Arc::new(DashMap::new()),
+            events_processed: Arc::new(AtomicU64::new(0)),
             tx,
             rx,
         }
     }
 
+    /// Detector with the patterns and enable flags stored at `store_path`
+    pub fn open(settings: QuantumPatternSettings) -> SIEMResult<Self> {
+        settings.validate()?;
+        let stored: StoredPatterns = match std::fs::read(&settings.store_path) {
+            Ok(raw) => serde_json::from_slice(&raw)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound || settings.store_path.is_empty() => StoredPatterns::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let detector = Self { settings, ..Self::new() };
+        *detector.disabled.lock().unwrap() = stored.disabled;
+        for pattern in stored.patterns {
+            pattern.validate()?;
+            detector.insert(QuantumPattern { origin: PatternOrigin::Api, ..pattern });
+        }
+        Ok(detector)
+    }
+
     pub fn process_event(&self, event: &str) -> QuantumResult {
         // Process event with quantum-inspired algorithms
         let mut result = QuantumResult {
@@ -70,11 +190,9 @@ impl QuantumDetector {
         };
 
         // Check patterns
-        for entry in self.cache.patterns.iter() {
-            if event.contains(entry.key()) {
-                result.patterns_matched.push(entry.key().clone());
-                result.confidence += 0.3;
-            }
+        for id in self.match_event(event) {
+            result.patterns_matched.push(id);
+            result.confidence += 0.3;
         }
 
         result.detected = result.confidence > 0.5;
@@ -88,21 +206,114 @@ impl QuantumDetector {
         events.iter().map(|event| self.process_event(event)).collect()
     }
 
+    /// Ids of the enabled patterns `event` matches, counted in their statistics
+    pub fn match_event(&self, event: &str) -> Vec<String> {
+        self.events_processed.fetch_add(1, Ordering::Relaxed);
+        let event = event.to_lowercase();
+        let mut matches = Vec::new();
+        for mut entry in self.patterns.iter_mut() {
+            if entry.pattern.enabled && entry.terms.iter().any(|term| event.contains(term.as_str())) {
+                entry.stats.matches += 1;
+                entry.stats.last_matched = Some(Utc::now());
+                matches.push(entry.key().clone());
+            }
+        }
+        matches.sort();
+        matches
+    }
+
+    /// Add a pattern through the API; it is stored and survives restarts
+    pub fn add_pattern(&self, pattern: QuantumPattern) -> SIEMResult<()> {
+        pattern.validate()?;
+        if self.patterns.contains_key(&pattern.id) {
+            return Err(SIEMError::Validation(format!("Quantum pattern {} already exists", pattern.id)));
+        }
+        if self.patterns.len() >= self.settings.max_patterns {
+            return Err(SIEMError::Validation(format!("Quantum pattern limit of {} reached", self.settings.max_patterns)));
+        }
+        info!("🔬 Added quantum pattern {}: {}", pattern.id, pattern.pattern);
+        self.insert(QuantumPattern { origin: PatternOrigin::Api, ..pattern });
+        self.save()
+    }
+
+    /// Load a built-in or content-pack pattern, replacing one with the same id; not stored
+    pub fn install_pattern(&self, pattern: QuantumPattern) -> SIEMResult<()> {
+        pattern.validate()?;
+        let enabled = pattern.enabled && !self.disabled.lock().unwrap().contains(&pattern.id);
+        self.insert(QuantumPattern { enabled, ..pattern });
+        Ok(())
+    }
+
+    /// Replace the terms and description of a pattern, keeping its origin and statistics
+    pub fn update_pattern(&self, pattern: QuantumPattern) -> SIEMResult<()> {
+        pattern.validate()?;
+        {
+            let mut entry = self.patterns.get_mut(&pattern.id).ok_or_else(|| not_found(&pattern.id))?;
+            if entry.pattern.origin != PatternOrigin::Api {
+                return Err(SIEMError::Validation(format!(
+                    "Quantum pattern {} is not managed through the API; it can only be enabled or disabled",
+                    pattern.id
+                )));
+            }
+            entry.terms = pattern.terms();
+            entry.pattern = QuantumPattern { origin: PatternOrigin::Api, ..pattern };
+        }
+        self.save()
+    }
+
+    /// Delete a pattern; whether it existed
+    pub fn remove_pattern(&self, id: &str) -> SIEMResult<bool> {
+        let removed = self.patterns.remove(id).is_some();
+        let forgotten = self.disabled.lock().unwrap().remove(id);
+        if removed || forgotten {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Switch a pattern on or off; remembered for built-in and content-pack patterns too
+    pub fn set_pattern_enabled(&self, id: &str, enabled: bool) -> SIEMResult<()> {
+        let origin = {
+            let mut entry = self.patterns.get_mut(id).ok_or_else(|| not_found(id))?;
+            entry.pattern.enabled = enabled;
+            entry.pattern.origin.clone()
+        };
+        if origin != PatternOrigin::Api {
+            let mut disabled = self.disabled.lock().unwrap();
+            match enabled {
+                true => disabled.remove(id),
+                false => disabled.insert(id.to_string()),
+            };
+        }
+        info!("🔬 Quantum pattern {} {}", id, if enabled { "enabled" } else { "disabled" });
+        self.save()
+    }
+
+    pub fn pattern(&self, id: &str) -> Option<PatternInfo> {
+        self.patterns.get(id).map(|entry| PatternInfo { pattern: entry.pattern.clone(), stats: entry.stats.clone() })
+    }
+
+    /// Every pattern with its statistics, by id
+    pub fn patterns(&self) -> Vec<PatternInfo> {
+        let mut patterns: Vec<PatternInfo> = self
+            .patterns
+            .iter()
+            .map(|entry| PatternInfo { pattern: entry.pattern.clone(), stats: entry.stats.clone() })
+            .collect();
+        patterns.sort_by(|a, b| a.pattern.id.cmp(&b.pattern.id));
+        patterns
+    }
+
     pub fn get_quantum_stats(&self) -> QuantumStats {
         QuantumStats {
-            total_events_processed: self.cache.patterns.len() as u64,
-            patterns_loaded: self.cache.patterns.len() as u64,
-            quantum_states: self.cache.quantum_state.len() as u64,
+            total_events_processed: self.events_processed.load(Ordering::Relaxed),
+            patterns_loaded: self.patterns.len() as u64,
+            quantum_states: self.quantum_state.len() as u64,
             avg_processing_time_ns: 1000,
             last_updated: Utc::now(),
         }
     }
 
-    pub fn add_pattern(&self, pattern: String) {
-        self.cache.patterns.insert(pattern.clone(), pattern.clone());
-        info!("🔬 Added quantum pattern: {}", pattern);
-    }
-
     pub fn get_stats(&self) -> QuantumStats {
         self.get_quantum_stats()
     }
@@ -114,6 +325,38 @@ impl QuantumDetector {
         }
         results
     }
+
+    fn insert(&self, pattern: QuantumPattern) {
+        let stats = self.patterns.get(&pattern.id).map(|entry| entry.stats.clone()).unwrap_or_default();
+        let terms = pattern.terms();
+        self.patterns.insert(pattern.id.clone(), LoadedPattern { pattern, terms, stats });
+    }
+
+    fn save(&self) -> SIEMResult<()> {
+        if self.settings.store_path.is_empty() {
+            return Ok(());
+        }
+        let mut patterns: Vec<QuantumPattern> = self
+            .patterns
+            .iter()
+            .filter(|entry| entry.pattern.origin == PatternOrigin::Api)
+            .map(|entry| entry.pattern.clone())
+            .collect();
+        patterns.sort_by(|a, b| a.id.cmp(&b.id));
+        let stored = StoredPatterns { patterns, disabled: self.disabled.lock().unwrap().clone() };
+        let path = PathBuf::from(&self.settings.store_path);
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&stored)?)?;
+        std::fs::rename(tmp, &path)?;
+        Ok(())
+    }
+}
+
+fn not_found(id: &str) -> SIEMError {
+    SIEMError::Validation(format!("Quantum pattern {} not found", id))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -132,4 +375,70 @@ pub struct QuantumStats {
     pub quantum_states: u64,
     pub avg_processing_time_ns: u64,
     pub last_updated: chrono::DateTime<Utc>,
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> QuantumPatternSettings {
+        let dir = std::env::temp_dir().join(format!("quantum-{}", uuid::Uuid::new_v4()));
+        QuantumPatternSettings { store_path: dir.join("patterns.json").to_string_lossy().into_owned(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_patterns_match_any_term_and_count_matches() {
+        let detector = QuantumDetector::new();
+        detector
+            .install_pattern(QuantumPattern { origin: PatternOrigin::Builtin, ..QuantumPattern::new("lateral_movement", "psexec|wmic|schtasks") })
+            .unwrap();
+        detector.add_pattern(QuantumPattern::new("credential_dump", "mimikatz|lsass")).unwrap();
+
+        assert_eq!(detector.match_event("PsExec.exe \\\\FIN-WS-12 -s cmd"), vec!["lateral_movement"]);
+        assert_eq!(detector.process_event("procdump -ma lsass.exe via WMIC").patterns_matched, vec!["credential_dump", "lateral_movement"]);
+        let stats = detector.pattern("lateral_movement").unwrap().stats;
+        assert_eq!(stats.matches, 2);
+        assert!(stats.last_matched.is_some());
+
+        detector.set_pattern_enabled("lateral_movement", false).unwrap();
+        assert!(detector.match_event("wmic process call create").is_empty());
+        assert_eq!(detector.get_stats().total_events_processed, 3);
+    }
+
+    #[test]
+    fn test_rejects_invalid_and_non_api_edits() {
+        let detector = QuantumDetector::new();
+        assert!(detector.add_pattern(QuantumPattern::new("bad id", "mimikatz")).is_err());
+        assert!(detector.add_pattern(QuantumPattern::new("short_term", "mimikatz|ab")).is_err());
+        detector.add_pattern(QuantumPattern::new("dump", "mimikatz")).unwrap();
+        assert!(detector.add_pattern(QuantumPattern::new("dump", "lsass")).is_err());
+        assert!(detector.update_pattern(QuantumPattern::new("missing", "lsass")).is_err());
+
+        detector
+            .install_pattern(QuantumPattern { origin: PatternOrigin::ContentPack("apt".to_string()), ..QuantumPattern::new("apt.beacon", "beacon") })
+            .unwrap();
+        assert!(detector.update_pattern(QuantumPattern::new("apt.beacon", "cobalt")).is_err());
+        assert!(detector.remove_pattern("apt.beacon").unwrap());
+        assert!(!detector.remove_pattern("apt.beacon").unwrap());
+    }
+
+    #[test]
+    fn test_api_patterns_and_disabled_flags_persist() {
+        let settings = settings();
+        let detector = QuantumDetector::open(settings.clone()).unwrap();
+        detector.add_pattern(QuantumPattern::new("dump", "mimikatz")).unwrap();
+        detector.update_pattern(QuantumPattern { description: "Credential dumping".to_string(), ..QuantumPattern::new("dump", "mimikatz|lsass") }).unwrap();
+        detector.install_pattern(QuantumPattern { origin: PatternOrigin::Builtin, ..QuantumPattern::new("persistence", "startup|registry") }).unwrap();
+        detector.set_pattern_enabled("persistence", false).unwrap();
+
+        let reopened = QuantumDetector::open(settings.clone()).unwrap();
+        let dump = reopened.pattern("dump").unwrap().pattern;
+        assert_eq!(dump.pattern, "mimikatz|lsass");
+        assert_eq!(dump.description, "Credential dumping");
+        // Built-ins are not stored, only their flag, applied when they are installed again
+        assert!(reopened.pattern("persistence").is_none());
+        reopened.install_pattern(QuantumPattern { origin: PatternOrigin::Builtin, ..QuantumPattern::new("persistence", "startup|registry") }).unwrap();
+        assert!(!reopened.pattern("persistence").unwrap().pattern.enabled);
+        let _ = std::fs::remove_dir_all(std::path::Path::new(&settings.store_path).parent().unwrap());
+    }
+}
@@ -0,0 +1,528 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::access_scope::DataScope;
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// Time range and filters of the query API's filter language
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryFilter {
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub source_ip: Option<String>,
+    pub severity_min: Option<u8>,
+    /// Rows the caller may read, set by the API from its principal and never by clients
+    #[serde(skip)]
+    pub scope: Option<DataScope>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeBucket {
+    Minute,
+    Hour,
+    Day,
+    /// Weeks start on Monday
+    Week,
+    Month,
+}
+
+/// Column expression; identifiers are validated, never free-form SQL
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Column(String),
+    Bucket(String, TimeBucket),
+    ToString(Box<Expr>),
+    Count,
+    Uniq(String),
+    Sum(String),
+    Min(String),
+    Max(String),
+}
+
+impl Expr {
+    pub fn column(name: &str) -> Self {
+        Expr::Column(name.to_string())
+    }
+
+    fn render(&self) -> SIEMResult<String> {
+        Ok(match self {
+            Expr::Column(c) => identifier(c)?.to_string(),
+            Expr::Bucket(c, TimeBucket::Minute) => format!("toStartOfMinute({})", identifier(c)?),
+            Expr::Bucket(c, TimeBucket::Hour) => format!("toStartOfHour({})", identifier(c)?),
+            Expr::Bucket(c, TimeBucket::Day) => format!("toStartOfDay({})", identifier(c)?),
+            Expr::Bucket(c, TimeBucket::Week) => format!("toStartOfWeek({}, 1)", identifier(c)?),
+            Expr::Bucket(c, TimeBucket::Month) => format!("toStartOfMonth({})", identifier(c)?),
+            Expr::ToString(inner) => format!("toString({})", inner.render()?),
+            Expr::Count => "count()".to_string(),
+            Expr::Uniq(c) => format!("uniq({})", identifier(c)?),
+            Expr::Sum(c) => format!("sum({})", identifier(c)?),
+            Expr::Min(c) => format!("min({})", identifier(c)?),
+            Expr::Max(c) => format!("max({})", identifier(c)?),
+        })
+    }
+}
+
+/// Bound query parameter with its ClickHouse type
+#[derive(Debug, Clone, PartialEq)]
+pub enum Param {
+    String(String),
+    UInt8(u8),
+    UInt64(u64),
+    Int64(i64),
+    Float64(f64),
+    Date(NaiveDate),
+    /// Any format accepted by `parseDateTimeBestEffort`
+    DateTime(String),
+}
+
+impl Param {
+    fn placeholder(&self, name: &str) -> String {
+        match self {
+            Param::String(_) => format!("{{{}:String}}", name),
+            Param::UInt8(_) => format!("{{{}:UInt8}}", name),
+            Param::UInt64(_) => format!("{{{}:UInt64}}", name),
+            Param::Int64(_) => format!("{{{}:Int64}}", name),
+            Param::Float64(_) => format!("{{{}:Float64}}", name),
+            Param::Date(_) => format!("{{{}:Date}}", name),
+            Param::DateTime(_) => format!("parseDateTimeBestEffort({{{}:String}})", name),
+        }
+    }
+
+    fn value(&self) -> String {
+        match self {
+            Param::String(s) | Param::DateTime(s) => s.clone(),
+            Param::UInt8(v) => v.to_string(),
+            Param::UInt64(v) => v.to_string(),
+            Param::Int64(v) => v.to_string(),
+            Param::Float64(v) => v.to_string(),
+            Param::Date(d) => d.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn sql(&self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+}
+
+/// SQL with `{name:Type}` placeholders and the HTTP parameters binding them
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuiltQuery {
+    pub sql: String,
+    pub params: Vec<(String, String)>,
+}
+
+/// Typed SELECT builder; every value is sent as a bound parameter
+#[derive(Debug, Clone)]
+pub struct QueryBuilder {
+    database: String,
+    table: String,
+    columns: Vec<(Expr, Option<String>)>,
+    conditions: Vec<String>,
+    params: Vec<Param>,
+    group_by: Vec<Expr>,
+    order_by: Vec<(Expr, bool)>,
+    limit_by: Option<(usize, Vec<Expr>)>,
+    limit: Option<usize>,
+    format: Option<String>,
+    settings: Vec<(String, String)>,
+    error: Option<String>,
+}
+
+impl QueryBuilder {
+    pub fn new(database: &str, table: &str) -> Self {
+        let mut builder = Self {
+            database: database.to_string(),
+            table: table.to_string(),
+            columns: Vec::new(),
+            conditions: Vec::new(),
+            params: Vec::new(),
+            group_by: Vec::new(),
+            order_by: Vec::new(),
+            limit_by: None,
+            limit: None,
+            format: None,
+            settings: Vec::new(),
+            error: None,
+        };
+        builder.check(identifier(database).and(identifier(table)).map(|_| ()));
+        builder
+    }
+
+    fn check(&mut self, result: SIEMResult<()>) {
+        if let (Err(e), None) = (result, &self.error) {
+            self.error = Some(e.to_string());
+        }
+    }
+
+    fn bind(&mut self, param: Param) -> String {
+        let placeholder = param.placeholder(&format!("p{}", self.params.len()));
+        self.params.push(param);
+        placeholder
+    }
+
+    /// Select `expr`, optionally `AS alias`; `*` when nothing is selected
+    pub fn select(mut self, expr: Expr, alias: Option<&str>) -> Self {
+        if let Some(alias) = alias {
+            self.check(identifier(alias).map(|_| ()));
+        }
+        self.columns.push((expr, alias.map(String::from)));
+        self
+    }
+
+    pub fn filter(mut self, expr: Expr, op: Op, value: Param) -> Self {
+        match expr.render() {
+            Ok(lhs) => {
+                let rhs = self.bind(value);
+                self.conditions.push(format!("{} {} {}", lhs, op.sql(), rhs));
+            }
+            Err(e) => self.check(Err(e)),
+        }
+        self
+    }
+
+    /// `column IN (...)`; an empty list matches nothing
+    pub fn filter_in(mut self, expr: Expr, values: Vec<Param>) -> Self {
+        match expr.render() {
+            Ok(_) if values.is_empty() => self.conditions.push("0".to_string()),
+            Ok(lhs) => {
+                let placeholders: Vec<String> = values.into_iter().map(|v| self.bind(v)).collect();
+                self.conditions.push(format!("{} IN ({})", lhs, placeholders.join(", ")));
+            }
+            Err(e) => self.check(Err(e)),
+        }
+        self
+    }
+
+    /// `hasAny(column, [...])` on an array column; an empty list matches nothing
+    pub fn filter_has_any(mut self, expr: Expr, values: Vec<Param>) -> Self {
+        match expr.render() {
+            Ok(_) if values.is_empty() => self.conditions.push("0".to_string()),
+            Ok(lhs) => {
+                let placeholders: Vec<String> = values.into_iter().map(|v| self.bind(v)).collect();
+                self.conditions.push(format!("hasAny({}, [{}])", lhs, placeholders.join(", ")));
+            }
+            Err(e) => self.check(Err(e)),
+        }
+        self
+    }
+
+    /// `(a = x OR b = y ...)`; an empty list matches nothing
+    pub fn filter_any(mut self, alternatives: Vec<(Expr, Op, Param)>) -> Self {
+        if alternatives.is_empty() {
+            self.conditions.push("0".to_string());
+            return self;
+        }
+        let mut terms = Vec::new();
+        for (expr, op, value) in alternatives {
+            match expr.render() {
+                Ok(lhs) => {
+                    let rhs = self.bind(value);
+                    terms.push(format!("{} {} {}", lhs, op.sql(), rhs));
+                }
+                Err(e) => self.check(Err(e)),
+            }
+        }
+        self.conditions.push(format!("({})", terms.join(" OR ")));
+        self
+    }
+
+    /// Keyset condition `(a, b, ...) > (x, y, ...)` for cursor paging
+    pub fn after(mut self, exprs: Vec<Expr>, values: Vec<Param>) -> Self {
+        if exprs.len() != values.len() || exprs.is_empty() {
+            self.check(Err(SIEMError::Validation("Keyset columns and values differ in length".to_string())));
+            return self;
+        }
+        match exprs.iter().map(Expr::render).collect::<SIEMResult<Vec<_>>>() {
+            Ok(lhs) => {
+                let rhs: Vec<String> = values.into_iter().map(|v| self.bind(v)).collect();
+                self.conditions.push(format!("({}) > ({})", lhs.join(", "), rhs.join(", ")));
+            }
+            Err(e) => self.check(Err(e)),
+        }
+        self
+    }
+
+    /// Translate the query API filter language on a `timestamp`/`source_ip`/`severity` table
+    pub fn apply(mut self, filter: &QueryFilter) -> Self {
+        if let Some(since) = &filter.since {
+            self = self.filter(Expr::column("timestamp"), Op::Ge, Param::DateTime(since.clone()));
+        }
+        if let Some(until) = &filter.until {
+            self = self.filter(Expr::column("timestamp"), Op::Lt, Param::DateTime(until.clone()));
+        }
+        if let Some(source_ip) = &filter.source_ip {
+            self = self.filter(Expr::column("source_ip"), Op::Eq, Param::String(source_ip.clone()));
+        }
+        if let Some(severity) = filter.severity_min {
+            self = self.filter(Expr::column("severity"), Op::Ge, Param::UInt8(severity));
+        }
+        if let Some(scope) = &filter.scope {
+            self = self.scope(scope);
+        }
+        self
+    }
+
+    /// Row-level access predicates on `tenant`, `tags` and `classification`; unrestricted dimensions add none
+    pub fn scope(mut self, scope: &DataScope) -> Self {
+        let params = |values: &[String]| values.iter().cloned().map(Param::String).collect();
+        if !scope.tenants.is_empty() {
+            self = self.filter_in(Expr::column("tenant"), params(&scope.tenants));
+        }
+        if !scope.tags.is_empty() {
+            self = self.filter_has_any(Expr::column("tags"), params(&scope.tags));
+        }
+        if !scope.classifications.is_empty() {
+            self = self.filter_in(Expr::column("classification"), params(&scope.classifications));
+        }
+        self
+    }
+
+    pub fn group_by(mut self, expr: Expr) -> Self {
+        self.group_by.push(expr);
+        self
+    }
+
+    pub fn order_by(mut self, expr: Expr, descending: bool) -> Self {
+        self.order_by.push((expr, descending));
+        self
+    }
+
+    /// Keep at most `limit` rows per distinct `by` values
+    pub fn limit_by(mut self, limit: usize, by: Vec<Expr>) -> Self {
+        self.limit_by = Some((limit, by));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn format(mut self, format: &str) -> Self {
+        self.check(identifier(format).map(|_| ()));
+        self.format = Some(format.to_string());
+        self
+    }
+
+    /// Per-query ClickHouse setting, sent as an HTTP parameter
+    pub fn setting(mut self, name: &str, value: &str) -> Self {
+        self.check(identifier(name).map(|_| ()));
+        self.settings.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn build(self) -> SIEMResult<BuiltQuery> {
+        if let Some(error) = self.error {
+            return Err(SIEMError::Validation(error));
+        }
+        let render_list = |exprs: &[Expr]| -> SIEMResult<String> {
+            Ok(exprs.iter().map(Expr::render).collect::<SIEMResult<Vec<_>>>()?.join(", "))
+        };
+        let columns = if self.columns.is_empty() {
+            "*".to_string()
+        } else {
+            self.columns
+                .iter()
+                .map(|(expr, alias)| Ok(match alias {
+                    Some(alias) => format!("{} AS {}", expr.render()?, alias),
+                    None => expr.render()?,
+                }))
+                .collect::<SIEMResult<Vec<_>>>()?
+                .join(", ")
+        };
+
+        let mut sql = format!("SELECT {} FROM {}.{}", columns, self.database, self.table);
+        if !self.conditions.is_empty() {
+            sql.push_str(&format!(" WHERE {}", self.conditions.join(" AND ")));
+        }
+        if !self.group_by.is_empty() {
+            sql.push_str(&format!(" GROUP BY {}", render_list(&self.group_by)?));
+        }
+        if !self.order_by.is_empty() {
+            let order = self
+                .order_by
+                .iter()
+                .map(|(expr, desc)| Ok(format!("{}{}", expr.render()?, if *desc { " DESC" } else { "" })))
+                .collect::<SIEMResult<Vec<_>>>()?;
+            sql.push_str(&format!(" ORDER BY {}", order.join(", ")));
+        }
+        if let Some((limit, by)) = &self.limit_by {
+            sql.push_str(&format!(" LIMIT {} BY {}", limit, render_list(by)?));
+        }
+        if let Some(limit) = self.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        if let Some(format) = &self.format {
+            sql.push_str(&format!(" FORMAT {}", format));
+        }
+
+        let params = self
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| (format!("param_p{}", i), param.value()))
+            .chain(self.settings)
+            .collect();
+        Ok(BuiltQuery { sql, params })
+    }
+}
+
+/// Named analyst filter with optional bucketing and per-group limits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub name: String,
+    pub table: String,
+    #[serde(default)]
+    pub filter: QueryFilter,
+    /// Count rows per bucket of `timestamp` instead of returning them
+    pub bucket: Option<TimeBucket>,
+    #[serde(default)]
+    pub group_by: Vec<String>,
+    /// Rows kept per `group_by` combination
+    pub limit_by: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+impl SavedSearch {
+    pub fn to_query(&self, database: &str) -> SIEMResult<BuiltQuery> {
+        let mut builder = QueryBuilder::new(database, &self.table).apply(&self.filter);
+        let groups: Vec<Expr> = self.group_by.iter().map(|c| Expr::column(c)).collect();
+        if let Some(bucket) = self.bucket {
+            builder = builder.select(Expr::Bucket("timestamp".to_string(), bucket), Some("bucket"));
+            for group in &groups {
+                builder = builder.select(group.clone(), None);
+            }
+            builder = builder.select(Expr::Count, Some("count")).group_by(Expr::column("bucket"));
+            for group in &groups {
+                builder = builder.group_by(group.clone());
+            }
+            builder = builder.order_by(Expr::column("bucket"), false);
+        } else {
+            builder = builder.order_by(Expr::column("timestamp"), true);
+        }
+        if let Some(limit) = self.limit_by {
+            if groups.is_empty() {
+                return Err(SIEMError::Validation(format!("Saved search '{}' has limit_by without group_by", self.name)));
+            }
+            builder = builder.limit_by(limit, groups);
+        }
+        if let Some(limit) = self.limit {
+            builder = builder.limit(limit);
+        }
+        builder.format("JSONEachRow").build()
+    }
+}
+
+/// Accept plain ClickHouse identifiers only
+fn identifier(name: &str) -> SIEMResult<&str> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(name)
+    } else {
+        Err(SIEMError::Validation(format!("Invalid identifier '{}'", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_search_builds_bucketed_parameterized_sql() {
+        let search = SavedSearch {
+            name: "noisy sources".to_string(),
+            table: "threats".to_string(),
+            filter: QueryFilter {
+                since: Some("2024-05-01".to_string()),
+                source_ip: Some("203.0.113.7' OR 1=1 --".to_string()),
+                severity_min: Some(3),
+                ..Default::default()
+            },
+            bucket: Some(TimeBucket::Hour),
+            group_by: vec!["threat_type".to_string()],
+            limit_by: Some(5),
+            limit: Some(100),
+        };
+        let query = search.to_query("siem").unwrap();
+        assert_eq!(
+            query.sql,
+            "SELECT toStartOfHour(timestamp) AS bucket, threat_type, count() AS count FROM siem.threats \
+             WHERE timestamp >= parseDateTimeBestEffort({p0:String}) AND source_ip = {p1:String} AND severity >= {p2:UInt8} \
+             GROUP BY bucket, threat_type ORDER BY bucket LIMIT 5 BY threat_type LIMIT 100 FORMAT JSONEachRow"
+        );
+        // Values only ever travel as parameters
+        assert!(!query.sql.contains("OR 1=1"));
+        assert_eq!(query.params[1], ("param_p1".to_string(), "203.0.113.7' OR 1=1 --".to_string()));
+    }
+
+    #[test]
+    fn test_scope_is_injected_as_bound_predicates() {
+        let filter = QueryFilter {
+            severity_min: Some(2),
+            scope: Some(DataScope {
+                tenants: vec!["acme".to_string()],
+                tags: vec!["pci".to_string(), "hr".to_string()],
+                classifications: Vec::new(),
+            }),
+            ..Default::default()
+        };
+        let query = QueryBuilder::new("siem", "threats").apply(&filter).build().unwrap();
+        assert_eq!(
+            query.sql,
+            "SELECT * FROM siem.threats WHERE severity >= {p0:UInt8} AND tenant IN ({p1:String}) AND hasAny(tags, [{p2:String}, {p3:String}])"
+        );
+        assert_eq!(query.params[1], ("param_p1".to_string(), "acme".to_string()));
+
+        // Clients cannot widen or set their own scope through the filter language
+        let parsed: QueryFilter = serde_json::from_value(serde_json::json!({ "scope": { "tenants": [] } })).unwrap();
+        assert!(parsed.scope.is_none());
+    }
+
+    #[test]
+    fn test_invalid_identifiers_are_rejected() {
+        assert!(QueryBuilder::new("siem", "threats; DROP TABLE x").build().is_err());
+        let bad_column = QueryBuilder::new("siem", "threats").filter(Expr::column("severity OR 1"), Op::Eq, Param::UInt8(1));
+        assert!(bad_column.build().is_err());
+        let bad_group = SavedSearch {
+            name: "x".to_string(),
+            table: "threats".to_string(),
+            filter: QueryFilter::default(),
+            bucket: None,
+            group_by: vec!["source_ip) --".to_string()],
+            limit_by: Some(1),
+            limit: None,
+        };
+        assert!(bad_group.to_query("siem").is_err());
+
+        let keyset = QueryBuilder::new("siem", "events")
+            .after(vec![Expr::column("timestamp"), Expr::ToString(Box::new(Expr::column("id")))], vec![
+                Param::DateTime("2024-05-01 10:00:00".to_string()),
+                Param::String("7".to_string()),
+            ])
+            .build()
+            .unwrap();
+        assert!(keyset.sql.ends_with("WHERE (timestamp, toString(id)) > (parseDateTimeBestEffort({p0:String}), {p1:String})"));
+    }
+}
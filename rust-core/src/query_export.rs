@@ -0,0 +1,441 @@
+use std::sync::Arc;
+use base64ct::{Base64UrlUnpadded, Encoding};
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::config::ClickHouseSettings;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::query_builder::{Expr, Param, QueryBuilder};
+
+/// One exported row, keyed by column name
+pub type Row = serde_json::Map<String, serde_json::Value>;
+
+/// Paging and streaming limits of the query API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportSettings {
+    pub default_page_size: usize,
+    pub max_page_size: usize,
+    /// Rows fetched and written per streamed chunk
+    pub chunk_rows: usize,
+    /// Encoded chunks buffered ahead of a slow client before fetching pauses
+    pub buffered_chunks: usize,
+    /// Per-page ClickHouse request timeout
+    pub fetch_timeout_seconds: u64,
+    pub tables: Vec<String>,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            default_page_size: 1000,
+            max_page_size: 10_000,
+            chunk_rows: 5000,
+            buffered_chunks: 4,
+            fetch_timeout_seconds: 60,
+            tables: vec!["threats".to_string(), "events".to_string()],
+        }
+    }
+}
+
+/// Position after the last row of a page, ordered by `(timestamp, id)`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryCursor {
+    pub timestamp: String,
+    pub id: String,
+}
+
+impl QueryCursor {
+    fn from_row(row: &Row) -> Option<Self> {
+        let text = |value: &serde_json::Value| match value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        Some(Self {
+            timestamp: text(row.get("timestamp")?),
+            id: text(row.get("id")?),
+        })
+    }
+
+    /// Opaque URL-safe token handed to clients
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        let mut buf = vec![0u8; Base64UrlUnpadded::encoded_len(&json)];
+        Base64UrlUnpadded::encode(&json, &mut buf).map(str::to_string).unwrap_or_default()
+    }
+
+    pub fn decode(token: &str) -> SIEMResult<Self> {
+        let malformed = || SIEMError::Validation("Malformed query cursor".to_string());
+        let mut buf = vec![0u8; token.len()];
+        let bytes = Base64UrlUnpadded::decode(token, &mut buf).map_err(|_| malformed())?;
+        serde_json::from_slice(bytes).map_err(|_| malformed())
+    }
+}
+
+pub use crate::query_builder::QueryFilter;
+
+/// Backend that returns rows after a cursor in `(timestamp, id)` order
+pub trait RowSource: Send + Sync {
+    fn fetch<'a>(
+        &'a self,
+        table: &'a str,
+        filter: &'a QueryFilter,
+        after: Option<&'a QueryCursor>,
+        limit: usize,
+    ) -> BoxFuture<'a, SIEMResult<Vec<Row>>>;
+}
+
+/// Keyset-paged reads from ClickHouse over its HTTP interface
+pub struct ClickHouseRowSource {
+    client: reqwest::Client,
+    settings: ClickHouseSettings,
+}
+
+impl ClickHouseRowSource {
+    pub fn new(settings: ClickHouseSettings, fetch_timeout_seconds: u64) -> SIEMResult<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(fetch_timeout_seconds.max(1)))
+            .build()
+            .map_err(|e| SIEMError::Config(format!("ClickHouse client: {}", e)))?;
+        Ok(Self { client, settings })
+    }
+
+    /// Next page after `after` in `(timestamp, id)` order
+    fn build_query(
+        &self,
+        table: &str,
+        filter: &QueryFilter,
+        after: Option<&QueryCursor>,
+        limit: usize,
+    ) -> SIEMResult<(String, Vec<(String, String)>)> {
+        let id = || Expr::ToString(Box::new(Expr::column("id")));
+        let mut builder = QueryBuilder::new(&self.settings.database, table).apply(filter);
+        if let Some(cursor) = after {
+            builder = builder.after(
+                vec![Expr::column("timestamp"), id()],
+                vec![Param::DateTime(cursor.timestamp.clone()), Param::String(cursor.id.clone())],
+            );
+        }
+        let query = builder
+            .order_by(Expr::column("timestamp"), false)
+            .order_by(id(), false)
+            .limit(limit)
+            .format("JSONEachRow")
+            .build()?;
+        Ok((query.sql, query.params))
+    }
+
+    pub(crate) fn database(&self) -> &str {
+        &self.settings.database
+    }
+
+    /// Run a `FORMAT JSONEachRow` query and parse one row per line
+    pub(crate) async fn query_rows(&self, sql: String, params: &[(String, String)]) -> SIEMResult<Vec<Row>> {
+        let response = self
+            .client
+            .post(&self.settings.url)
+            .query(params)
+            .body(sql)
+            .send()
+            .await
+            .map_err(|e| SIEMError::Database(format!("ClickHouse query failed: {}", e)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(SIEMError::Database(format!("ClickHouse returned {}: {}", status, body.trim())));
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| SIEMError::Database(format!("ClickHouse read failed: {}", e)))?;
+        body.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(SIEMError::from))
+            .collect()
+    }
+}
+
+impl RowSource for ClickHouseRowSource {
+    fn fetch<'a>(
+        &'a self,
+        table: &'a str,
+        filter: &'a QueryFilter,
+        after: Option<&'a QueryCursor>,
+        limit: usize,
+    ) -> BoxFuture<'a, SIEMResult<Vec<Row>>> {
+        Box::pin(async move {
+            let (sql, params) = self.build_query(table, filter, after, limit)?;
+            self.query_rows(sql, &params).await
+        })
+    }
+}
+
+/// Rows held in memory, e.g. for small result sets and tests
+#[derive(Debug, Clone, Default)]
+pub struct MemoryRowSource {
+    rows: Vec<Row>,
+}
+
+impl MemoryRowSource {
+    pub fn new(mut rows: Vec<Row>) -> Self {
+        rows.sort_by_key(|row| QueryCursor::from_row(row).map(|c| (c.timestamp, c.id)));
+        Self { rows }
+    }
+}
+
+impl RowSource for MemoryRowSource {
+    fn fetch<'a>(
+        &'a self,
+        _table: &'a str,
+        filter: &'a QueryFilter,
+        after: Option<&'a QueryCursor>,
+        limit: usize,
+    ) -> BoxFuture<'a, SIEMResult<Vec<Row>>> {
+        let rows = self
+            .rows
+            .iter()
+            .filter(|row| {
+                let Some(key) = QueryCursor::from_row(row) else { return false };
+                after.is_none_or(|after| (&key.timestamp, &key.id) > (&after.timestamp, &after.id))
+                    && filter.since.as_ref().is_none_or(|since| &key.timestamp >= since)
+                    && filter.until.as_ref().is_none_or(|until| &key.timestamp < until)
+                    && filter.source_ip.as_ref().is_none_or(|ip| row.get("source_ip").and_then(|v| v.as_str()) == Some(ip))
+                    && filter.scope.as_ref().is_none_or(|scope| scope.permits_row(row))
+            })
+            .take(limit)
+            .cloned()
+            .collect();
+        Box::pin(async move { Ok(rows) })
+    }
+}
+
+/// One page of rows and the cursor to continue from, if more may follow
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryPage {
+    pub rows: Vec<Row>,
+    pub next_cursor: Option<String>,
+}
+
+/// Output format of a streamed export
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+/// Cursor-paged queries and streaming exports over a row source
+pub struct QueryService {
+    source: Arc<dyn RowSource>,
+    settings: ExportSettings,
+}
+
+impl QueryService {
+    pub fn new(source: Arc<dyn RowSource>, settings: ExportSettings) -> Self {
+        Self { source, settings }
+    }
+
+    fn check_table(&self, table: &str) -> SIEMResult<()> {
+        if self.settings.tables.iter().any(|t| t == table) {
+            Ok(())
+        } else {
+            Err(SIEMError::Validation(format!("Table '{}' is not queryable", table)))
+        }
+    }
+
+    pub async fn page(
+        &self,
+        table: &str,
+        filter: &QueryFilter,
+        cursor: Option<&str>,
+        limit: Option<usize>,
+    ) -> SIEMResult<QueryPage> {
+        self.check_table(table)?;
+        let after = cursor.map(QueryCursor::decode).transpose()?;
+        let limit = limit.unwrap_or(self.settings.default_page_size).clamp(1, self.settings.max_page_size);
+        let rows = self.source.fetch(table, filter, after.as_ref(), limit).await?;
+        let next_cursor = if rows.len() == limit {
+            rows.last().and_then(QueryCursor::from_row).map(|c| c.encode())
+        } else {
+            None
+        };
+        Ok(QueryPage { rows, next_cursor })
+    }
+
+    /// Stream every matching row as encoded chunks; fetching pauses while the buffer is full
+    pub fn export(
+        &self,
+        table: &str,
+        filter: QueryFilter,
+        format: ExportFormat,
+    ) -> SIEMResult<ReceiverStream<SIEMResult<Bytes>>> {
+        self.check_table(table)?;
+        let (tx, rx) = mpsc::channel(self.settings.buffered_chunks.max(1));
+        let source = self.source.clone();
+        let table = table.to_string();
+        let chunk_rows = self.settings.chunk_rows.max(1);
+
+        tokio::spawn(async move {
+            let mut after: Option<QueryCursor> = None;
+            let mut columns: Option<Vec<String>> = None;
+            loop {
+                let rows = match source.fetch(&table, &filter, after.as_ref(), chunk_rows).await {
+                    Ok(rows) => rows,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+                if rows.is_empty() {
+                    return;
+                }
+                let chunk = match format {
+                    ExportFormat::Ndjson => encode_ndjson(&rows),
+                    ExportFormat::Csv => {
+                        let header = columns.is_none();
+                        let columns = columns.get_or_insert_with(|| rows[0].keys().cloned().collect());
+                        encode_csv(columns, &rows, header)
+                    }
+                };
+                // The receiver is gone once the client disconnects
+                if tx.send(Ok(Bytes::from(chunk))).await.is_err() {
+                    return;
+                }
+                if rows.len() < chunk_rows {
+                    return;
+                }
+                after = rows.last().and_then(QueryCursor::from_row);
+                if after.is_none() {
+                    return;
+                }
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+fn encode_ndjson(rows: &[Row]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for row in rows {
+        if serde_json::to_writer(&mut out, row).is_ok() {
+            out.push(b'\n');
+        }
+    }
+    out
+}
+
+fn encode_csv(columns: &[String], rows: &[Row], header: bool) -> Vec<u8> {
+    let mut out = String::new();
+    let mut write_record = |fields: Vec<String>| {
+        let quoted: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        out.push_str(&quoted.join(","));
+        out.push_str("\r\n");
+    };
+    if header {
+        write_record(columns.to_vec());
+    }
+    for row in rows {
+        write_record(
+            columns
+                .iter()
+                .map(|column| match row.get(column) {
+                    None | Some(serde_json::Value::Null) => String::new(),
+                    Some(serde_json::Value::String(s)) => s.clone(),
+                    Some(other) => other.to_string(),
+                })
+                .collect(),
+        );
+    }
+    out.into_bytes()
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn rows(count: usize) -> Vec<Row> {
+        (0..count)
+            .map(|i| {
+                let value = serde_json::json!({
+                    "id": format!("{:04}", i),
+                    "timestamp": format!("2024-05-01 10:{:02}:00", i % 60),
+                    "source_ip": "10.0.0.1",
+                    "message": "login failed, \"admin\"",
+                });
+                value.as_object().unwrap().clone()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_cursor_pages_cover_every_row_once() {
+        let service = QueryService::new(Arc::new(MemoryRowSource::new(rows(25))), ExportSettings::default());
+        let mut seen = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = service.page("threats", &QueryFilter::default(), cursor.as_deref(), Some(10)).await.unwrap();
+            seen.extend(page.rows.iter().map(|row| row["id"].as_str().unwrap().to_string()));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(seen.len(), 25);
+        seen.dedup();
+        assert_eq!(seen.len(), 25);
+        assert!(service.page("users", &QueryFilter::default(), None, None).await.is_err());
+        assert!(service.page("threats", &QueryFilter::default(), Some("not-a-cursor!"), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_streams_chunks_with_single_csv_header() {
+        let settings = ExportSettings {
+            chunk_rows: 4,
+            buffered_chunks: 1,
+            ..Default::default()
+        };
+        let service = QueryService::new(Arc::new(MemoryRowSource::new(rows(10))), settings);
+
+        let chunks: Vec<Bytes> = service
+            .export("threats", QueryFilter::default(), ExportFormat::Csv)
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 3);
+        let csv = String::from_utf8(chunks.concat()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], "id,message,source_ip,timestamp");
+        assert!(lines[1].contains("\"login failed, \"\"admin\"\"\""));
+
+        let ndjson: Vec<Bytes> = service
+            .export("events", QueryFilter::default(), ExportFormat::Ndjson)
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(String::from_utf8(ndjson.concat()).unwrap().lines().count(), 10);
+    }
+}
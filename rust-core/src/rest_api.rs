@@ -0,0 +1,1559 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use axum::body::{Bytes, StreamBody};
+use axum::extract::{DefaultBodyLimit, Path, Query, State};
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post, put};
+use axum::{Extension, Json, Router};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use crate::access_scope::{AccessDenial, Caller, DataAccess};
+use crate::aggregation::{TopNAggregator, TopNStats};
+use crate::anomaly_tuning::{AnomalyTuner, SensitivityReport};
+use crate::autoscaling::{QueueMonitor, ScalingHint};
+use crate::canary_asset::{self, CanaryAsset, CanaryKind, CanaryRegistry, CanaryRule};
+#[cfg(feature = "compliance")]
+use crate::compliance::{ComplianceSecurityEngine, UserRole};
+use crate::config::RestSettings;
+use crate::degradation::{DegradationController, HealthReport};
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::evidence::{Attachment, EvidenceKind, EvidenceStore, NewAttachment};
+use crate::fatigue::FatigueReport;
+use crate::forensic::{ForensicReport, HeldBackKind};
+use crate::memory_budget::{MemoryAccountant, MemoryReport};
+use crate::fp_learning::{AdoptRequest, AdoptedRule, CandidateRule, FpLearningSettings, RuleSuggestion};
+use crate::detection_profile::{DetectionProfile, ProfileRegistry, ProfileSet};
+use crate::domain_baseline::{DomainBaseline, DomainBaselineStatus, DomainReview, ProposedDomain};
+#[cfg(feature = "compliance")]
+use crate::elevation::ElevationRequest;
+use crate::forwarding::{ForwardEnvelope, ReceiveOutcome, SiteReceiver, SiteStatus};
+use crate::incident_export::{IncidentExportFormat, IncidentExportSettings};
+use crate::impact::IncidentImpact;
+use crate::incident_cache::{etag_matches, IncidentCache};
+use crate::chat_mirror::MirroredIncident;
+use crate::incident_archive::{ArchivedIncidentStub, IncidentArchive};
+use crate::incident_bulk::{BulkAuditEntry, BulkOperation, BulkPreview, BulkRequest, IncidentSelector};
+use crate::incident_response::{Incident, IncidentResponseEngine, IncidentSeverity, IncidentStatus, TimelineEntry};
+use crate::playbook::{Playbook, PlaybookRun, PlaybookRunner};
+use crate::severity::SeverityExplanation;
+use crate::shared_state::{SharedEntry, SharedList, SharedState};
+use crate::suppression::{SuppressionList, SuppressionReport, SuppressionRequest, SuppressionRule};
+use crate::telemetry::{TelemetryReport, TelemetryReporter};
+use crate::query_export::{ExportFormat, QueryFilter, QueryPage, QueryService};
+use crate::trends::{Granularity, TrendAnalyzer, TrendReport};
+
+/// Shared state of the REST handlers
+#[derive(Clone)]
+pub struct RestState {
+    pub incidents: Arc<IncidentResponseEngine>,
+    pub evidence: Arc<EvidenceStore>,
+    pub degradation: Arc<DegradationController>,
+    pub queues: Arc<QueueMonitor>,
+    pub aggregator: Arc<TopNAggregator>,
+    pub query: Arc<QueryService>,
+    pub trends: Arc<TrendAnalyzer>,
+    pub incident_export: IncidentExportSettings,
+    pub fp_learning: FpLearningSettings,
+    pub sites: Arc<SiteReceiver>,
+    pub telemetry: Arc<TelemetryReporter>,
+    pub suppressions: Arc<SuppressionList>,
+    pub profiles: Arc<ProfileRegistry>,
+    pub domain_baseline: Arc<DomainBaseline>,
+    pub canaries: Arc<CanaryRegistry>,
+    pub anomaly_tuner: Arc<AnomalyTuner>,
+    pub playbooks: Arc<PlaybookRunner>,
+    pub incident_cache: Arc<IncidentCache>,
+    pub access: Arc<DataAccess>,
+    pub memory: Arc<MemoryAccountant>,
+    /// Users, sessions and just-in-time elevation
+    #[cfg(feature = "compliance")]
+    pub compliance: Arc<ComplianceSecurityEngine>,
+}
+
+/// JSON error body with an HTTP status
+pub struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn not_found(what: &str, id: &str) -> Self {
+        ApiError(StatusCode::NOT_FOUND, format!("{} {} not found", what, id))
+    }
+}
+
+impl From<SIEMError> for ApiError {
+    fn from(error: SIEMError) -> Self {
+        let status = match error {
+            SIEMError::Validation(_) => StatusCode::BAD_REQUEST,
+            SIEMError::Auth(_) => StatusCode::UNAUTHORIZED,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError(status, error.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+/// Build the `/api/v1` router; token auth is off only without `auth_tokens` and scoped principals
+pub fn router(state: RestState, mut auth_tokens: Vec<String>, max_body_bytes: usize) -> Router {
+    auth_tokens.extend(state.access.tokens());
+    let routes = Router::new()
+        .route("/api/v1/metrics", get(get_metrics))
+        .route("/api/v1/scaling", get(get_scaling_hint))
+        .route("/api/v1/analytics/fatigue", get(get_fatigue_report))
+        .route("/api/v1/analytics/trends", get(get_trends))
+        .route("/api/v1/analytics/impact", get(get_impact_report))
+        .route("/api/v1/analytics/response-metrics", get(get_response_metrics))
+        .route("/api/v1/stats/top", get(get_top_stats))
+        .route("/api/v1/triage/queue", get(get_triage_queue))
+        .route("/api/v1/query/:table", get(query_page))
+        .route("/api/v1/query/:table/export", get(export_query))
+        .route("/api/v1/incidents", get(list_incidents))
+        .route("/api/v1/incidents/export", get(export_incidents))
+        .route("/api/v1/incidents/bulk", post(apply_bulk))
+        .route("/api/v1/incidents/bulk/preview", post(preview_bulk))
+        .route("/api/v1/incidents/bulk/audit", get(get_bulk_audit))
+        .route("/api/v1/incidents/:id", get(get_incident))
+        .route("/api/v1/incidents/:id/timeline", get(get_timeline))
+        .route("/api/v1/incidents/:id/severity", get(explain_severity))
+        .route("/api/v1/incidents/:id/impact", put(set_impact))
+        .route("/api/v1/incidents/:id/suppress", post(suppress_incident))
+        .route("/api/v1/incidents/:id/chat", get(get_incident_chat))
+        .route("/api/v1/incidents/:id/playbooks/:name", post(run_playbook))
+        .route("/api/v1/playbooks", get(list_playbooks))
+        .route("/api/v1/suppressions", get(list_suppressions))
+        .route("/api/v1/suppressions/report", get(get_suppression_report))
+        .route("/api/v1/suppressions/suggestions", get(get_suppression_suggestions))
+        .route("/api/v1/suppressions/suggestions/:id/adopt", post(adopt_suppression_suggestion))
+        .route("/api/v1/suppressions/:id", delete(expire_suppression))
+        .route("/api/v1/shared-state", get(get_shared_state))
+        .route("/api/v1/shared-state/:list/:value", put(put_shared_entry).delete(delete_shared_entry))
+        .route("/api/v1/detection/profiles", get(get_profiles).put(replace_profiles))
+        .route("/api/v1/detection/profiles/:name", put(put_profile).delete(delete_profile))
+        .route("/api/v1/detection/domain-baseline", get(get_domain_baseline))
+        .route("/api/v1/detection/domain-baseline/propose", post(propose_domain_baseline))
+        .route("/api/v1/detection/domain-baseline/approve", post(approve_domain_baseline))
+        .route("/api/v1/detection/domain-baseline/relearn", post(relearn_domain_baseline))
+        .route("/api/v1/detection/anomaly-sensitivity", get(get_anomaly_sensitivity))
+        .route("/api/v1/canaries", get(list_canaries).post(register_canary))
+        .route("/api/v1/canaries/rules", get(list_canary_rules))
+        .route("/api/v1/canaries/:kind/:name", delete(retire_canary))
+        .route("/api/v1/incidents/:id/attachments", get(list_attachments).post(upload_attachment))
+        .route("/api/v1/archive/incidents", get(search_archive))
+        .route("/api/v1/archive/incidents/:id/reopen", post(reopen_archived))
+        .route("/api/v1/attachments/:id", get(get_attachment))
+        .route("/api/v1/attachments/:id/content", get(download_attachment))
+        .route("/api/v1/forward", post(receive_forwarded))
+        .route("/api/v1/sites", get(list_sites))
+        .route("/api/v1/telemetry/preview", get(preview_telemetry))
+        .route("/api/v1/access/denials", get(list_access_denials))
+        .route("/api/v1/forensic/journal", get(get_forensic_journal))
+        .route("/debug/memory", get(get_memory_report));
+    #[cfg(feature = "compliance")]
+    let routes = routes
+        .route("/api/v1/auth/login", post(login))
+        .route("/api/v1/elevations", get(list_elevations).post(request_elevation))
+        .route("/api/v1/elevations/:id/approve", post(approve_elevation))
+        .route("/api/v1/elevations/:id/deny", post(deny_elevation))
+        .route("/api/v1/elevations/:id/revoke", post(revoke_elevation));
+    routes
+        .layer(middleware::from_fn_with_state(state.incidents.clone(), read_only_guard))
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .layer(middleware::from_fn_with_state(state.access.clone(), scope_request))
+        .layer(middleware::from_fn_with_state(Arc::new(auth_tokens), require_token))
+        // Registered after the auth layer so load balancers can probe without a token
+        .route("/healthz", get(healthz))
+        .with_state(state)
+}
+
+/// Serve the REST API until the listener fails
+pub async fn serve(settings: &RestSettings, max_body_bytes: usize, state: RestState) -> SIEMResult<()> {
+    let addr: SocketAddr = settings
+        .listen_addr
+        .parse()
+        .map_err(|e| SIEMError::Config(format!("Invalid REST listen address {}: {}", settings.listen_addr, e)))?;
+    if settings.auth_tokens.is_empty() && state.access.tokens().is_empty() {
+        warn!("⚠️ REST API is serving without token authentication");
+    }
+
+    info!("🚀 Starting REST API on {}", addr);
+    axum::Server::bind(&addr)
+        .serve(router(state, settings.auth_tokens.clone(), max_body_bytes).into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .map_err(|e| SIEMError::Other(format!("REST server error: {}", e)))
+}
+
+async fn require_token<B>(
+    State(tokens): State<Arc<Vec<String>>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if tokens.is_empty() {
+        return next.run(request).await;
+    }
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match presented {
+        Some(token) if tokens.iter().any(|t| bool::from(t.as_bytes().ct_eq(token.as_bytes()))) => next.run(request).await,
+        _ => ApiError(StatusCode::UNAUTHORIZED, "Missing or invalid bearer token".to_string()).into_response(),
+    }
+}
+
+/// Requests that change nothing despite their method: previews, and detections forwarded in for analysis
+fn read_only_request(method: &axum::http::Method, path: &str) -> bool {
+    matches!(method.as_str(), "GET" | "HEAD" | "OPTIONS")
+        || path == "/api/v1/incidents/bulk/preview"
+        || path == "/api/v1/forward"
+        || path == "/api/v1/auth/login"
+}
+
+/// Refuse and journal changes to incidents, detection content and shared state in forensic mode
+async fn read_only_guard<B>(
+    State(incidents): State<Arc<IncidentResponseEngine>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(journal) = incidents.forensic_journal() else {
+        return next.run(request).await;
+    };
+    if read_only_request(request.method(), request.uri().path()) {
+        return next.run(request).await;
+    }
+    journal.record(HeldBackKind::Mutation, "rest_api", format!("{} {}", request.method(), request.uri().path()), None);
+    ApiError(StatusCode::FORBIDDEN, "Read-only forensic mode; the change was journaled, not applied".to_string()).into_response()
+}
+
+/// Routes that apply the caller's data scope; scoped callers are refused everywhere else
+fn scope_aware(path: &str) -> bool {
+    path.starts_with("/api/v1/query/")
+        || path.starts_with("/api/v1/attachments/")
+        || path == "/api/v1/triage/queue"
+        || path.starts_with("/api/v1/suppressions/suggestions")
+        || (path.starts_with("/api/v1/incidents") && path != "/api/v1/incidents/bulk/audit")
+}
+
+/// Resolve the bearer token to a `Caller` extension for the handlers
+async fn scope_request<B>(
+    State(access): State<Arc<DataAccess>>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let caller = access.caller(token);
+    if caller.scope.is_some() && !scope_aware(request.uri().path()) {
+        access.deny(&caller, request.method().as_str(), request.uri().path());
+        return ApiError(StatusCode::FORBIDDEN, "Outside the caller's data scope".to_string()).into_response();
+    }
+    request.extensions_mut().insert(caller);
+    next.run(request).await
+}
+
+/// The incident if the caller's scope covers it; out-of-scope incidents look missing and are audited
+fn scoped_incident(state: &RestState, caller: &Caller, id: &str, action: &str) -> ApiResult<Incident> {
+    let incident = state.incidents.get_incident(id).ok_or_else(|| ApiError::not_found("Incident", id))?;
+    if !caller.permits_incident(&incident) {
+        state.access.deny(caller, action, &format!("incident {}", id));
+        return Err(ApiError::not_found("Incident", id));
+    }
+    Ok(incident)
+}
+
+/// The attachment if the caller may read its incident
+fn scoped_attachment(state: &RestState, caller: &Caller, id: &str) -> ApiResult<Attachment> {
+    let attachment = state.evidence.get(id).ok_or_else(|| ApiError::not_found("Attachment", id))?;
+    if caller.scope.is_some() {
+        scoped_incident(state, caller, &attachment.incident_id, "read")
+            .map_err(|_| ApiError::not_found("Attachment", id))?;
+    }
+    Ok(attachment)
+}
+
+/// Restrict `selector` to the caller's scope, auditing explicitly named incidents outside it
+fn scope_selector(state: &RestState, caller: &Caller, selector: &mut IncidentSelector, action: &str) {
+    if caller.scope.is_none() {
+        return;
+    }
+    for id in &selector.ids {
+        if state.incidents.get_incident(id).is_some_and(|incident| !caller.permits_incident(&incident)) {
+            state.access.deny(caller, action, &format!("incident {}", id));
+        }
+    }
+    selector.scope = caller.scope.clone();
+}
+
+async fn healthz(State(state): State<RestState>) -> Json<HealthReport> {
+    Json(state.degradation.health())
+}
+
+async fn get_metrics(State(state): State<RestState>) -> Json<HashMap<String, f64>> {
+    let mut metrics = state.incidents.get_performance_metrics();
+    metrics.extend(state.degradation.to_metrics());
+    metrics.extend(state.queues.get_metrics());
+    metrics.extend(state.incident_cache.get_metrics());
+    metrics.extend(state.access.get_metrics());
+    metrics.extend(state.canaries.get_metrics());
+    metrics.extend(state.anomaly_tuner.get_metrics());
+    Json(metrics)
+}
+
+/// Latest queue lag and scaling hint, sampled on demand before the first interval
+async fn get_scaling_hint(State(state): State<RestState>) -> Json<ScalingHint> {
+    Json(state.queues.latest().unwrap_or_else(|| state.queues.sample()))
+}
+
+async fn get_fatigue_report(State(state): State<RestState>) -> Json<FatigueReport> {
+    Json(state.incidents.fatigue_report(chrono::Utc::now()))
+}
+
+#[derive(Deserialize)]
+struct TrendParams {
+    granularity: Option<Granularity>,
+}
+
+/// Latest scheduled report, computed on demand before the first run
+async fn get_trends(State(state): State<RestState>, Query(params): Query<TrendParams>) -> ApiResult<Json<TrendReport>> {
+    let granularity = params.granularity.unwrap_or(Granularity::Weekly);
+    match state.trends.latest(granularity) {
+        Some(report) => Ok(Json(report)),
+        None => Ok(Json(state.trends.run_once(granularity, chrono::Utc::now()).await?)),
+    }
+}
+
+#[derive(Deserialize)]
+struct ImpactParams {
+    format: Option<String>,
+}
+
+/// Quarterly impact rollups as JSON, or with `format=pdf` as the executive summary PDF
+async fn get_impact_report(State(state): State<RestState>, Query(params): Query<ImpactParams>) -> ApiResult<Response> {
+    let report = state.incidents.impact_report(chrono::Utc::now());
+    match params.format.as_deref() {
+        None | Some("json") => Ok(Json(report).into_response()),
+        #[cfg(feature = "compliance")]
+        Some("pdf") => {
+            let response = state.incidents.response_metrics_report(report.generated_at);
+            let pdf = crate::compliance::sections_pdf(
+                "Executive security summary",
+                &[report.executive_section(), response.executive_section(), report.section()],
+            );
+            let headers = [
+                (header::CONTENT_TYPE, "application/pdf".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"impact-{}.pdf\"", crate::impact::quarter_of(report.generated_at))),
+            ];
+            Ok((headers, pdf).into_response())
+        }
+        Some(other) => Err(ApiError(StatusCode::BAD_REQUEST, format!("Unsupported impact report format '{}'", other))),
+    }
+}
+
+/// MTTD/MTTA/MTTR and SLO error budgets as JSON, or with `format=pdf` as a report
+async fn get_response_metrics(State(state): State<RestState>, Query(params): Query<ImpactParams>) -> ApiResult<Response> {
+    let report = state.incidents.response_metrics_report(chrono::Utc::now());
+    match params.format.as_deref() {
+        None | Some("json") => Ok(Json(report).into_response()),
+        #[cfg(feature = "compliance")]
+        Some("pdf") => {
+            let pdf = crate::compliance::sections_pdf("Incident response metrics", &[report.executive_section(), report.section()]);
+            let headers = [
+                (header::CONTENT_TYPE, "application/pdf".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"response-metrics-{}.pdf\"", report.generated_at.format("%Y-%m-%d"))),
+            ];
+            Ok((headers, pdf).into_response())
+        }
+        Some(other) => Err(ApiError(StatusCode::BAD_REQUEST, format!("Unsupported response metrics format '{}'", other))),
+    }
+}
+
+async fn get_top_stats(State(state): State<RestState>) -> Json<TopNStats> {
+    Json(state.aggregator.stats())
+}
+
+#[derive(Deserialize)]
+struct PageParams {
+    cursor: Option<String>,
+    limit: Option<usize>,
+    #[serde(flatten)]
+    filter: QueryFilter,
+}
+
+async fn query_page(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Path(table): Path<String>,
+    Query(mut params): Query<PageParams>,
+) -> ApiResult<Json<QueryPage>> {
+    params.filter.scope = caller.scope;
+    Ok(Json(state.query.page(&table, &params.filter, params.cursor.as_deref(), params.limit).await?))
+}
+
+#[derive(Deserialize)]
+struct ExportParams {
+    format: ExportFormat,
+    #[serde(flatten)]
+    filter: QueryFilter,
+}
+
+async fn export_query(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Path(table): Path<String>,
+    Query(mut params): Query<ExportParams>,
+) -> ApiResult<Response> {
+    params.filter.scope = caller.scope;
+    let stream = state.query.export(&table, params.filter, params.format)?;
+    let extension = match params.format {
+        ExportFormat::Csv => "csv",
+        ExportFormat::Ndjson => "ndjson",
+    };
+    let headers = [
+        (header::CONTENT_TYPE, params.format.content_type().to_string()),
+        (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.{}\"", table, extension)),
+    ];
+    Ok((headers, StreamBody::new(stream)).into_response())
+}
+
+/// JSON listing served through the incident cache; `304` when `If-None-Match` names the current body
+async fn cached_listing<T, F>(state: &RestState, headers: &HeaderMap, key: &str, list: F) -> ApiResult<Response>
+where
+    T: serde::Serialize,
+    F: FnOnce() -> T,
+{
+    let cached = state.incident_cache.get_or_render(key, || Ok(serde_json::to_vec(&list())?)).await?;
+    let fresh = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| etag_matches(value, &cached.etag));
+    // Clients may keep listings but must revalidate before each use
+    let validators = [(header::ETAG, cached.etag.clone()), (header::CACHE_CONTROL, "private, no-cache".to_string())];
+    if fresh {
+        return Ok((StatusCode::NOT_MODIFIED, validators).into_response());
+    }
+    let content_type = [(header::CONTENT_TYPE, "application/json".to_string())];
+    Ok((validators, content_type, cached.body).into_response())
+}
+
+#[derive(Deserialize)]
+struct IncidentListParams {
+    status: Option<IncidentStatus>,
+    severity: Option<IncidentSeverity>,
+    /// Threat category, e.g. `SQLInjection`
+    category: Option<String>,
+    tag: Option<String>,
+    limit: Option<usize>,
+}
+
+/// Incidents matching the filters, newest first
+async fn list_incidents(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    headers: HeaderMap,
+    Query(params): Query<IncidentListParams>,
+) -> ApiResult<Response> {
+    let key = format!(
+        "incidents:{}?status={:?}&severity={:?}&category={:?}&tag={:?}&limit={:?}",
+        caller.name, params.status, params.severity, params.category, params.tag, params.limit
+    );
+    let selector = IncidentSelector {
+        status: params.status,
+        severity: params.severity,
+        category: params.category,
+        tag: params.tag,
+        scope: caller.scope,
+        ..Default::default()
+    };
+    cached_listing(&state, &headers, &key, || {
+        let mut incidents: Vec<Incident> = state.incidents.get_all_incidents().into_iter().filter(|i| selector.matches(i)).collect();
+        incidents.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+        incidents.truncate(params.limit.unwrap_or(usize::MAX));
+        incidents
+    })
+    .await
+}
+
+async fn get_triage_queue(State(state): State<RestState>, Extension(caller): Extension<Caller>, headers: HeaderMap) -> ApiResult<Response> {
+    let key = format!("triage_queue:{}", caller.name);
+    cached_listing(&state, &headers, &key, || {
+        let mut queue = state.incidents.get_triage_queue();
+        queue.retain(|incident| caller.permits_incident(incident));
+        queue
+    })
+    .await
+}
+
+async fn get_incident(State(state): State<RestState>, Extension(caller): Extension<Caller>, Path(id): Path<String>) -> ApiResult<Json<Incident>> {
+    scoped_incident(&state, &caller, &id, "read").map(Json)
+}
+
+#[derive(Deserialize)]
+struct BulkPreviewRequest {
+    #[serde(default)]
+    selector: IncidentSelector,
+    #[serde(flatten)]
+    operation: BulkOperation,
+}
+
+async fn preview_bulk(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Json(mut request): Json<BulkPreviewRequest>,
+) -> ApiResult<Json<BulkPreview>> {
+    scope_selector(&state, &caller, &mut request.selector, "bulk_preview");
+    Ok(Json(state.incidents.preview_bulk(&request.selector, &request.operation)?))
+}
+
+async fn apply_bulk(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Json(mut request): Json<BulkRequest>,
+) -> ApiResult<Json<BulkAuditEntry>> {
+    scope_selector(&state, &caller, &mut request.selector, "bulk_apply");
+    Ok(Json(state.incidents.apply_bulk(request, &caller.name)?))
+}
+
+async fn get_bulk_audit(State(state): State<RestState>) -> Json<Vec<BulkAuditEntry>> {
+    Json(state.incidents.bulk_audit_log())
+}
+
+async fn list_access_denials(State(state): State<RestState>) -> Json<Vec<AccessDenial>> {
+    Json(state.access.denials())
+}
+
+/// Header carrying the compliance session from `POST /api/v1/auth/login`
+#[cfg(feature = "compliance")]
+pub const SESSION_HEADER: &str = "x-ultra-siem-session";
+
+/// The compliance user acting on this request; the bearer token only admits the client
+#[cfg(feature = "compliance")]
+fn session_user(state: &RestState, headers: &HeaderMap) -> ApiResult<String> {
+    headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|session| state.compliance.session_user(session))
+        .ok_or_else(|| ApiError(StatusCode::UNAUTHORIZED, "Missing or expired session".to_string()))
+}
+
+#[cfg(feature = "compliance")]
+#[derive(Deserialize)]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+#[cfg(feature = "compliance")]
+async fn login(
+    State(state): State<RestState>,
+    connection: Option<axum::extract::ConnectInfo<SocketAddr>>,
+    Json(login): Json<LoginRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let ip = connection.map(|info| info.0.ip().to_string()).unwrap_or_default();
+    match state.compliance.authenticate_user(&login.username, &login.password, &ip).await? {
+        Some(session) => Ok(Json(serde_json::json!({ "session": session, "header": SESSION_HEADER }))),
+        None => Err(ApiError(StatusCode::UNAUTHORIZED, "Invalid credentials".to_string())),
+    }
+}
+
+#[cfg(feature = "compliance")]
+async fn list_elevations(State(state): State<RestState>, headers: HeaderMap) -> ApiResult<Json<Vec<ElevationRequest>>> {
+    let user = session_user(&state, &headers)?;
+    let mut requests = state.compliance.elevations();
+    if !state.compliance.check_permission(&user, &crate::compliance::Permission::ManageRoles) {
+        requests.retain(|request| request.username == user);
+    }
+    Ok(Json(requests))
+}
+
+#[cfg(feature = "compliance")]
+#[derive(Deserialize)]
+struct ElevationBody {
+    requested_role: UserRole,
+    justification: String,
+    duration_minutes: u32,
+}
+
+#[cfg(feature = "compliance")]
+async fn request_elevation(
+    State(state): State<RestState>,
+    headers: HeaderMap,
+    Json(body): Json<ElevationBody>,
+) -> ApiResult<Json<ElevationRequest>> {
+    let user = session_user(&state, &headers)?;
+    Ok(Json(state.compliance.request_elevation(&user, body.requested_role, &body.justification, body.duration_minutes).await?))
+}
+
+#[cfg(feature = "compliance")]
+#[derive(Deserialize, Default)]
+struct ElevationDecision {
+    #[serde(default)]
+    note: Option<String>,
+}
+
+#[cfg(feature = "compliance")]
+async fn approve_elevation(
+    State(state): State<RestState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    decision: Option<Json<ElevationDecision>>,
+) -> ApiResult<Json<ElevationRequest>> {
+    let approver = session_user(&state, &headers)?;
+    let note = decision.map(|Json(decision)| decision).unwrap_or_default().note;
+    Ok(Json(state.compliance.approve_elevation(&id, &approver, note).await?))
+}
+
+#[cfg(feature = "compliance")]
+async fn deny_elevation(
+    State(state): State<RestState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    decision: Option<Json<ElevationDecision>>,
+) -> ApiResult<Json<ElevationRequest>> {
+    let approver = session_user(&state, &headers)?;
+    let note = decision.map(|Json(decision)| decision).unwrap_or_default().note;
+    Ok(Json(state.compliance.deny_elevation(&id, &approver, note).await?))
+}
+
+#[cfg(feature = "compliance")]
+async fn revoke_elevation(State(state): State<RestState>, headers: HeaderMap, Path(id): Path<String>) -> ApiResult<Json<ElevationRequest>> {
+    let actor = session_user(&state, &headers)?;
+    Ok(Json(state.compliance.revoke_elevation(&id, &actor).await?))
+}
+
+fn incident_archive(state: &RestState) -> ApiResult<Arc<IncidentArchive>> {
+    state
+        .incidents
+        .archive()
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, "Incident archive is not enabled".to_string()))
+}
+
+#[derive(Deserialize)]
+struct ArchiveSearchParams {
+    #[serde(default)]
+    q: String,
+}
+
+async fn search_archive(State(state): State<RestState>, Query(params): Query<ArchiveSearchParams>) -> ApiResult<Json<Vec<ArchivedIncidentStub>>> {
+    Ok(Json(incident_archive(&state)?.search(&params.q)))
+}
+
+#[derive(Deserialize)]
+struct ReopenRequest {
+    reason: String,
+}
+
+async fn reopen_archived(State(state): State<RestState>, Path(id): Path<String>, Json(request): Json<ReopenRequest>) -> ApiResult<Json<Incident>> {
+    let archive = incident_archive(&state)?;
+    if archive.stub(&id).is_none() {
+        return Err(ApiError::not_found("Archived incident", &id));
+    }
+    Ok(Json(archive.reopen(&state.incidents, &id, &request.reason).await?))
+}
+
+async fn get_incident_chat(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<MirroredIncident>> {
+    if caller.scope.is_some() {
+        scoped_incident(&state, &caller, &id, "read")?;
+    }
+    let mirror = state
+        .incidents
+        .chat_mirror()
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, "Chat mirroring is not enabled".to_string()))?;
+    mirror.thread(&id).map(Json).ok_or_else(|| ApiError::not_found("Incident chat", &id))
+}
+
+#[derive(Deserialize)]
+struct IncidentExportParams {
+    format: IncidentExportFormat,
+    /// Comma-separated incident ids; all incidents when absent
+    ids: Option<String>,
+    /// Comma-separated base64 X25519 public keys to encrypt to
+    recipients: Option<String>,
+}
+
+async fn export_incidents(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Query(params): Query<IncidentExportParams>,
+) -> ApiResult<Response> {
+    let split = |list: &Option<String>| -> Vec<String> {
+        list.iter().flat_map(|l| l.split(',')).map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect()
+    };
+    let incidents = match split(&params.ids) {
+        ids if ids.is_empty() => {
+            let mut incidents = state.incidents.get_all_incidents();
+            incidents.retain(|incident| caller.permits_incident(incident));
+            incidents
+        }
+        ids => ids
+            .iter()
+            .map(|id| scoped_incident(&state, &caller, id, "export"))
+            .collect::<ApiResult<Vec<_>>>()?,
+    };
+    let (body, encrypted) = state.incident_export.export(&incidents, params.format, &split(&params.recipients))?;
+    let filename = format!("incidents-{}.{}", chrono::Utc::now().format("%Y%m%dT%H%M%S"), params.format.extension());
+    let (content_type, filename) = if encrypted {
+        ("application/json".to_string(), format!("{}.enc", filename))
+    } else {
+        (params.format.content_type().to_string(), filename)
+    };
+    let headers = [
+        (header::CONTENT_TYPE, content_type),
+        (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+    ];
+    Ok((headers, body).into_response())
+}
+
+/// Zone to render times in: `tz` if given, else the analyst's `user` zone, else the incident tenant's
+#[derive(Debug, Default, Deserialize)]
+struct LocalTimeQuery {
+    tz: Option<String>,
+    user: Option<String>,
+}
+
+/// Timeline entry with its time on the reader's local clock
+#[derive(Debug, Serialize)]
+struct LocalTimelineEntry {
+    #[serde(flatten)]
+    entry: TimelineEntry,
+    local_time: String,
+}
+
+async fn get_timeline(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<String>,
+    Query(query): Query<LocalTimeQuery>,
+) -> ApiResult<Json<Vec<LocalTimelineEntry>>> {
+    let incident = scoped_incident(&state, &caller, &id, "read")?;
+    let zones = state.incidents.time_zones();
+    let zone = match (&query.tz, &query.user) {
+        (Some(tz), _) => Arc::new(crate::timezone::Zone::load(tz).map_err(|e| SIEMError::Validation(e.to_string()))?),
+        (None, Some(user)) => zones.for_user(user).clone(),
+        (None, None) => zones.for_threat(&incident.threat_result).clone(),
+    };
+    Ok(Json(
+        incident
+            .timeline
+            .into_iter()
+            .map(|entry| LocalTimelineEntry { local_time: zone.format(entry.timestamp), entry })
+            .collect(),
+    ))
+}
+
+async fn explain_severity(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<SeverityExplanation>> {
+    scoped_incident(&state, &caller, &id, "read")?;
+    state
+        .incidents
+        .explain_severity(&id)
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("Incident", &id))
+}
+
+async fn set_impact(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<String>,
+    Json(impact): Json<IncidentImpact>,
+) -> ApiResult<Json<Incident>> {
+    scoped_incident(&state, &caller, &id, "set_impact")?;
+    Ok(Json(state.incidents.set_incident_impact(&id, impact)?))
+}
+
+async fn suppress_incident(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<String>,
+    Json(request): Json<SuppressionRequest>,
+) -> ApiResult<Json<SuppressionRule>> {
+    let incident = scoped_incident(&state, &caller, &id, "suppress")?;
+    let request = SuppressionRequest { owner: caller.name.clone(), ..request };
+    if canary_asset::is_canary_hit(&incident.threat_result) {
+        return Err(ApiError(StatusCode::BAD_REQUEST, "Canary asset hits cannot be suppressed; retire the canary instead".to_string()));
+    }
+    let rule = state.suppressions.create(&incident.threat_result, &id, &request, chrono::Utc::now())?;
+    let until = state.incidents.time_zones().for_threat(&incident.threat_result).format(rule.expires_at);
+    let message = format!("{} suppressed '{}' until {}: {}", rule.owner, rule.signature, until, rule.reason);
+    state.incidents.add_timeline_entry(&id, TimelineEntry::new("suppressed", message).with_reference(rule.id.clone()))?;
+    Ok(Json(rule))
+}
+
+async fn list_suppressions(State(state): State<RestState>) -> Json<Vec<SuppressionRule>> {
+    Json(state.suppressions.rules())
+}
+
+async fn get_suppression_report(State(state): State<RestState>) -> Json<SuppressionReport> {
+    Json(state.suppressions.report(chrono::Utc::now()))
+}
+
+async fn expire_suppression(State(state): State<RestState>, Path(id): Path<String>) -> ApiResult<Json<SuppressionRule>> {
+    if !state.suppressions.rules().iter().any(|rule| rule.id == id) {
+        return Err(ApiError::not_found("Suppression", &id));
+    }
+    Ok(Json(state.suppressions.expire(&id, chrono::Utc::now())?))
+}
+
+/// Rules learned from the caller's false positives, less those already snoozed or allow-listed
+fn suppression_suggestions(state: &RestState, caller: &Caller) -> Vec<RuleSuggestion> {
+    let mut incidents = state.incidents.get_all_incidents();
+    incidents.retain(|incident| caller.permits_incident(incident));
+    let mut suggestions = crate::fp_learning::suggest(&incidents, &state.suppressions.rules(), &state.fp_learning, chrono::Utc::now());
+    if let Some(shared) = state.incidents.shared_state() {
+        let allowed = shared.snapshot().remove(&SharedList::AllowList).unwrap_or_default();
+        suggestions.retain(|suggestion| !matches!(&suggestion.rule, CandidateRule::AllowList { source_ip } if allowed.contains_key(source_ip)));
+    }
+    suggestions
+}
+
+async fn get_suppression_suggestions(State(state): State<RestState>, Extension(caller): Extension<Caller>) -> Json<Vec<RuleSuggestion>> {
+    Json(suppression_suggestions(&state, &caller))
+}
+
+async fn adopt_suppression_suggestion(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<String>,
+    Json(request): Json<AdoptRequest>,
+) -> ApiResult<Json<AdoptedRule>> {
+    let suggestion = suppression_suggestions(&state, &caller)
+        .into_iter()
+        .find(|suggestion| suggestion.id == id)
+        .ok_or_else(|| ApiError::not_found("Suggestion", &id))?;
+    let days = request.days.unwrap_or(suggestion.suggested_days);
+    let reason = request.reason.clone().unwrap_or_else(|| suggestion.default_reason());
+    let latest = suggestion.sample_incidents.first().cloned().unwrap_or_default();
+    let now = chrono::Utc::now();
+    let (adopted, message) = match &suggestion.rule {
+        CandidateRule::Suppression { signature, host, user } => {
+            let snooze = SuppressionRequest { scope: Default::default(), days, owner: caller.name.clone(), reason };
+            let rule = state.suppressions.create_scoped(signature, host, user, &latest, &snooze, now)?;
+            let until = state.incidents.time_zones().default_zone().format(rule.expires_at);
+            let message = format!("{} adopted learned suppression of '{}' until {}: {}", rule.owner, rule.signature, until, rule.reason);
+            (AdoptedRule::Suppression { rule }, message)
+        }
+        CandidateRule::AllowList { source_ip } => {
+            let max_days = state.suppressions.settings().max_days;
+            if caller.name.trim().is_empty() || days == 0 || days > max_days {
+                return Err(SIEMError::Validation(format!("An allow-list entry needs an owner and lasts 1 to {} days", max_days)).into());
+            }
+            let entry = shared_state(&state)?.put(SharedList::AllowList, source_ip, Some(u64::from(days) * 86_400)).await?;
+            let message = format!("{} allow-listed {} for {} days: {}", caller.name, source_ip, days, reason);
+            (AdoptedRule::AllowList { source_ip: source_ip.clone(), entry }, message)
+        }
+    };
+    info!("🧹 {}", message);
+    if !latest.is_empty() {
+        state.incidents.add_timeline_entry(&latest, TimelineEntry::new("suppressed", message).with_reference(suggestion.id))?;
+    }
+    Ok(Json(adopted))
+}
+
+fn shared_state(state: &RestState) -> ApiResult<Arc<SharedState>> {
+    state
+        .incidents
+        .shared_state()
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, "Shared state is not enabled".to_string()))
+}
+
+async fn get_shared_state(State(state): State<RestState>) -> ApiResult<Json<BTreeMap<SharedList, BTreeMap<String, SharedEntry>>>> {
+    Ok(Json(shared_state(&state)?.snapshot()))
+}
+
+#[derive(Deserialize)]
+struct SharedEntryRequest {
+    /// Never expires when absent
+    ttl_seconds: Option<u64>,
+}
+
+async fn put_shared_entry(
+    State(state): State<RestState>,
+    Path((list, value)): Path<(SharedList, String)>,
+    Json(request): Json<SharedEntryRequest>,
+) -> ApiResult<Json<SharedEntry>> {
+    Ok(Json(shared_state(&state)?.put(list, &value, request.ttl_seconds).await?))
+}
+
+async fn delete_shared_entry(State(state): State<RestState>, Path((list, value)): Path<(SharedList, String)>) -> ApiResult<StatusCode> {
+    shared_state(&state)?.remove(list, &value).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_profiles(State(state): State<RestState>) -> Json<ProfileSet> {
+    Json(state.profiles.profile_set())
+}
+
+async fn replace_profiles(State(state): State<RestState>, Json(set): Json<ProfileSet>) -> ApiResult<Json<ProfileSet>> {
+    state.profiles.replace(set)?;
+    Ok(Json(state.profiles.profile_set()))
+}
+
+async fn put_profile(State(state): State<RestState>, Path(name): Path<String>, Json(profile): Json<DetectionProfile>) -> ApiResult<Json<ProfileSet>> {
+    state.profiles.upsert_profile(&name, profile)?;
+    Ok(Json(state.profiles.profile_set()))
+}
+
+async fn delete_profile(State(state): State<RestState>, Path(name): Path<String>) -> ApiResult<Json<ProfileSet>> {
+    if !state.profiles.profile_set().profiles.contains_key(&name) {
+        return Err(ApiError::not_found("Detection profile", &name));
+    }
+    state.profiles.remove_profile(&name)?;
+    Ok(Json(state.profiles.profile_set()))
+}
+
+/// Effective anomaly threshold and last-day alert count per data source and metric
+async fn get_anomaly_sensitivity(State(state): State<RestState>) -> Json<Vec<SensitivityReport>> {
+    let now = crate::error_handling::time::current_timestamp().unwrap_or_default();
+    Json(state.anomaly_tuner.report(now))
+}
+
+async fn list_canaries(State(state): State<RestState>) -> Json<Vec<CanaryAsset>> {
+    Json(state.canaries.assets())
+}
+
+/// Register a canary account or host; returns the rules generated for it
+async fn register_canary(State(state): State<RestState>, Json(asset): Json<CanaryAsset>) -> ApiResult<(StatusCode, Json<Vec<CanaryRule>>)> {
+    let now = crate::error_handling::time::current_timestamp().unwrap_or_default();
+    Ok((StatusCode::CREATED, Json(state.canaries.register(asset, now)?)))
+}
+
+async fn list_canary_rules(State(state): State<RestState>) -> Json<Vec<CanaryRule>> {
+    Json(state.canaries.rules())
+}
+
+async fn retire_canary(State(state): State<RestState>, Path((kind, name)): Path<(String, String)>) -> ApiResult<StatusCode> {
+    let kind: CanaryKind = kind.parse()?;
+    if !state.canaries.remove(kind, &name)? {
+        return Err(ApiError::not_found(&format!("Canary {}", kind), &name));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_playbooks(State(state): State<RestState>) -> Json<Vec<Playbook>> {
+    Json(state.playbooks.playbooks())
+}
+
+/// Run a playbook against an incident; step artifacts are attached to it as evidence
+async fn run_playbook(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Path((id, name)): Path<(String, String)>,
+) -> ApiResult<Json<PlaybookRun>> {
+    scoped_incident(&state, &caller, &id, "run_playbook")?;
+    if !state.playbooks.playbooks().iter().any(|playbook| playbook.name == name) {
+        return Err(ApiError::not_found("Playbook", &name));
+    }
+    Ok(Json(state.playbooks.run(&name, &id, &caller.name).await?))
+}
+
+async fn get_domain_baseline(State(state): State<RestState>) -> Json<DomainBaselineStatus> {
+    Json(state.domain_baseline.status())
+}
+
+/// End learning early and propose the allow-lists for review
+async fn propose_domain_baseline(State(state): State<RestState>) -> ApiResult<Json<Vec<ProposedDomain>>> {
+    Ok(Json(state.domain_baseline.propose()?))
+}
+
+async fn approve_domain_baseline(State(state): State<RestState>, Json(review): Json<DomainReview>) -> ApiResult<Json<DomainBaselineStatus>> {
+    Ok(Json(state.domain_baseline.approve(&review, chrono::Utc::now())?))
+}
+
+async fn relearn_domain_baseline(State(state): State<RestState>) -> ApiResult<Json<DomainBaselineStatus>> {
+    state.domain_baseline.restart_learning(chrono::Utc::now())?;
+    Ok(Json(state.domain_baseline.status()))
+}
+
+async fn list_attachments(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Vec<Attachment>>> {
+    scoped_incident(&state, &caller, &id, "read")?;
+    Ok(Json(state.evidence.list(&id)))
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadParams {
+    kind: EvidenceKind,
+    filename: String,
+    uploaded_by: Option<String>,
+}
+
+async fn upload_attachment(
+    State(state): State<RestState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<String>,
+    Query(params): Query<UploadParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<Attachment>)> {
+    scoped_incident(&state, &caller, &id, "attach")?;
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let upload = NewAttachment {
+        incident_id: id,
+        kind: params.kind,
+        filename: params.filename,
+        content_type,
+        uploaded_by: params.uploaded_by.unwrap_or_else(|| "api".to_string()),
+    };
+    let attachment = state.evidence.attach(&state.incidents, upload, &body)?;
+    Ok((StatusCode::CREATED, Json(attachment)))
+}
+
+async fn get_attachment(State(state): State<RestState>, Extension(caller): Extension<Caller>, Path(id): Path<String>) -> ApiResult<Json<Attachment>> {
+    scoped_attachment(&state, &caller, &id).map(Json)
+}
+
+async fn download_attachment(State(state): State<RestState>, Extension(caller): Extension<Caller>, Path(id): Path<String>) -> ApiResult<Response> {
+    let attachment = scoped_attachment(&state, &caller, &id)?;
+    let content = state.evidence.read_content(&id)?;
+    let disposition = format!("attachment; filename=\"{}\"", attachment.filename.replace(['"', '\\'], "_"));
+    let headers = [
+        (header::CONTENT_TYPE, attachment.content_type),
+        (header::CONTENT_DISPOSITION, disposition),
+        (header::HeaderName::from_static("x-content-sha256"), attachment.sha256.unwrap_or_default()),
+    ];
+    Ok((headers, content).into_response())
+}
+
+/// Envelopes posted by downstream sites over HTTPS
+async fn receive_forwarded(
+    State(state): State<RestState>,
+    Json(batch): Json<Vec<ForwardEnvelope>>,
+) -> ApiResult<Json<Vec<ReceiveOutcome>>> {
+    Ok(Json(state.sites.receive_batch(batch).await?))
+}
+
+async fn list_sites(State(state): State<RestState>) -> Json<Vec<SiteStatus>> {
+    Json(state.sites.sites())
+}
+
+#[derive(Deserialize)]
+struct ForensicJournalParams {
+    limit: Option<usize>,
+}
+
+/// What forensic mode held back, newest first; empty while it is off
+async fn get_forensic_journal(State(state): State<RestState>, Query(params): Query<ForensicJournalParams>) -> Json<ForensicReport> {
+    let report = state.incidents.forensic_journal().map(|journal| journal.report(params.limit.unwrap_or(100)));
+    Json(report.unwrap_or_default())
+}
+
+/// Estimated memory per subsystem and structure as of the last sweep
+async fn get_memory_report(State(state): State<RestState>) -> Json<MemoryReport> {
+    Json(state.memory.report())
+}
+
+/// Exactly what the next telemetry submission would send
+async fn preview_telemetry(State(state): State<RestState>) -> Json<TelemetryReport> {
+    Json(state.telemetry.preview(&state.incidents.get_all_incidents(), chrono::Utc::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::evidence::EvidenceSettings;
+    use crate::incident_response::{AlertConfig, SOARConfig};
+    use crate::query_export::MemoryRowSource;
+    use crate::trends::MemoryTrendSource;
+
+    /// Compliance engine with the default admin and the analyst `alice`
+    #[cfg(feature = "compliance")]
+    async fn compliance() -> ComplianceSecurityEngine {
+        let mut engine = ComplianceSecurityEngine::new("test_secret".to_string());
+        engine
+            .set_password_hashing(crate::password_hashing::PasswordHashSettings {
+                algorithm: crate::password_hashing::HashAlgorithm::Bcrypt,
+                bcrypt_cost: 4,
+                ..Default::default()
+            })
+            .unwrap();
+        engine.start().await.unwrap();
+        engine.create_user("alice", "alice@example.com", "Analyst-Pass-2024!", UserRole::SecurityAnalyst).await.unwrap();
+        engine
+    }
+
+    async fn state() -> (RestState, String, std::path::PathBuf) {
+        let incidents = Arc::new(IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled()));
+        let incident = incidents.process_threat(AdvancedThreatResult::default()).await.unwrap();
+        let dir = std::env::temp_dir().join(format!("rest-evidence-{}", uuid::Uuid::new_v4()));
+        let evidence = Arc::new(
+            EvidenceStore::open(EvidenceSettings {
+                storage_dir: dir.to_string_lossy().to_string(),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let degradation = DegradationController::new(crate::degradation::DegradationSettings {
+            spool_dir: dir.join("spool").to_string_lossy().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        let rows = (0..3)
+            .map(|i| {
+                let row = serde_json::json!({ "id": i.to_string(), "timestamp": "2024-05-01 10:00:00", "message": "x" });
+                row.as_object().unwrap().clone()
+            })
+            .collect();
+        let state = RestState {
+            sites: Arc::new(SiteReceiver::new(&Default::default(), incidents.clone(), None)),
+            telemetry: Arc::new(TelemetryReporter::new(crate::telemetry::TelemetrySettings {
+                install_id_path: dir.join("telemetry-id").to_string_lossy().to_string(),
+                ..Default::default()
+            })),
+            suppressions: Arc::new(
+                SuppressionList::open(crate::suppression::SuppressionSettings {
+                    store_path: dir.join("suppressions.json").to_string_lossy().to_string(),
+                    ..Default::default()
+                })
+                .unwrap(),
+            ),
+            profiles: Arc::new(
+                ProfileRegistry::open(&crate::detection_profile::DetectionProfileSettings {
+                    store_path: dir.join("profiles.json").to_string_lossy().to_string(),
+                    ..Default::default()
+                })
+                .unwrap(),
+            ),
+            domain_baseline: Arc::new(
+                DomainBaseline::open(crate::domain_baseline::DomainLearningSettings {
+                    store_path: dir.join("domain-baseline.json").to_string_lossy().to_string(),
+                    ..Default::default()
+                })
+                .unwrap(),
+            ),
+            canaries: Arc::new(
+                CanaryRegistry::open(crate::canary_asset::CanaryAssetSettings {
+                    store_path: dir.join("canaries.json").to_string_lossy().to_string(),
+                    ..Default::default()
+                })
+                .unwrap(),
+            ),
+            anomaly_tuner: Arc::new(
+                AnomalyTuner::open(
+                    crate::anomaly_tuning::AnomalyTuningSettings {
+                        store_path: dir.join("anomaly-tuning.json").to_string_lossy().to_string(),
+                        ..Default::default()
+                    },
+                    2.0,
+                )
+                .unwrap(),
+            ),
+            incident_cache: Arc::new(IncidentCache::new(Default::default(), incidents.clone()).unwrap()),
+            access: Arc::new(DataAccess::new(&Default::default()).unwrap()),
+            memory: Arc::new(MemoryAccountant::new(Default::default()).unwrap()),
+            #[cfg(feature = "compliance")]
+            compliance: Arc::new(compliance().await),
+            playbooks: Arc::new(PlaybookRunner::new(Default::default(), incidents.clone(), evidence.clone(), None).unwrap()),
+            incidents,
+            evidence,
+            degradation: Arc::new(degradation),
+            queues: Arc::new(QueueMonitor::new(Default::default())),
+            aggregator: Arc::new(TopNAggregator::new(Default::default())),
+            query: Arc::new(QueryService::new(Arc::new(MemoryRowSource::new(rows)), Default::default())),
+            trends: Arc::new(TrendAnalyzer::new(Arc::new(MemoryTrendSource::default()), Default::default())),
+            incident_export: IncidentExportSettings {
+                require_encryption: true,
+                default_recipients: Vec::new(),
+            },
+            fp_learning: FpLearningSettings::default(),
+        };
+        (state, incident.id, dir)
+    }
+
+    #[tokio::test]
+    async fn test_upload_and_download_attachment() {
+        let (state, incident_id, dir) = state().await;
+        let app = router(state, vec!["secret".to_string()], 1024);
+
+        let upload = Request::post(format!(
+            "/api/v1/incidents/{}/attachments?kind=Screenshot&filename=screen.png&uploaded_by=alice",
+            incident_id
+        ))
+        .header(header::AUTHORIZATION, "Bearer secret")
+        .header(header::CONTENT_TYPE, "image/png")
+        .body(Body::from("png-bytes"))
+        .unwrap();
+        let response = app.clone().oneshot(upload).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let attachment: Attachment = serde_json::from_slice(&body).unwrap();
+
+        let download = Request::get(format!("/api/v1/attachments/{}/content", attachment.id))
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(download).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+        assert_eq!(hyper::body::to_bytes(response.into_body()).await.unwrap(), "png-bytes");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_close_requires_preview() {
+        let (state, incident_id, dir) = state().await;
+        let incidents = state.incidents.clone();
+        let app = router(state, Vec::new(), 4096);
+        let post = |uri: &str, body: serde_json::Value| {
+            Request::post(uri).header(header::CONTENT_TYPE, "application/json").body(Body::from(body.to_string())).unwrap()
+        };
+
+        let selector = serde_json::json!({ "ids": [incident_id] });
+        let response = app
+            .clone()
+            .oneshot(post("/api/v1/incidents/bulk/preview", serde_json::json!({ "selector": selector, "operation": "set_status", "status": "Closed" })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let preview: BulkPreview = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(preview.matched, 1);
+
+        let apply = |confirm: &str| {
+            post("/api/v1/incidents/bulk", serde_json::json!({
+                "selector": selector, "operation": "set_status", "status": "Closed", "actor": "mallory", "confirm": confirm
+            }))
+        };
+        assert_eq!(app.clone().oneshot(apply("stale")).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        let response = app.oneshot(apply(&preview.selection_id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // The audit names the authenticated caller, not the actor claimed in the body
+        let entry: BulkAuditEntry = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(entry.actor, "service");
+        assert_eq!(incidents.get_incident(&incident_id).unwrap().status, crate::incident_response::IncidentStatus::Closed);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_suppress_from_incident() {
+        let (state, incident_id, dir) = state().await;
+        let (incidents, suppressions) = (state.incidents.clone(), state.suppressions.clone());
+        let app = router(state, Vec::new(), 4096);
+        let suppress = |days: u32| {
+            let body = serde_json::json!({ "scope": "everywhere", "days": days, "owner": "mallory", "reason": "noisy during migration" });
+            Request::post(format!("/api/v1/incidents/{}/suppress", incident_id))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        assert_eq!(app.clone().oneshot(suppress(365)).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        let response = app.clone().oneshot(suppress(7)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let rule: SuppressionRule = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(rule.owner, "service");
+        let incident = incidents.get_incident(&incident_id).unwrap();
+        assert!(suppressions.suppresses(&incident.threat_result, chrono::Utc::now()));
+        assert!(incident.timeline.iter().any(|e| e.event == "suppressed" && e.reference.as_deref() == Some(rule.id.as_str())));
+
+        let timeline = Request::get(format!("/api/v1/incidents/{}/timeline?tz=%2B02:00", incident_id)).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(timeline).await.unwrap();
+        let entries: Vec<serde_json::Value> = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        let suppressed = entries.iter().find(|e| e["event"] == "suppressed").unwrap();
+        assert!(suppressed["local_time"].as_str().unwrap().ends_with(" +02:00"));
+        assert!(suppressed["message"].as_str().unwrap().contains(&format!("until {}", rule.expires_at.format("%Y-%m-%d %H:%M UTC"))));
+
+        let expire = Request::delete(format!("/api/v1/suppressions/{}", rule.id)).body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(expire).await.unwrap().status(), StatusCode::OK);
+        assert!(!suppressions.suppresses(&incident.threat_result, chrono::Utc::now()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_adopt_suggestion_learned_from_false_positives() {
+        let (state, _, dir) = state().await;
+        let (incidents, suppressions) = (state.incidents.clone(), state.suppressions.clone());
+        for _ in 0..5 {
+            let scan = AdvancedThreatResult { source_ip: "10.0.0.50".to_string(), detection_method: "port_scan".to_string(), ..Default::default() };
+            let incident = incidents.process_threat(scan).await.unwrap();
+            incidents.mark_false_positive(&incident.id, "authorised scanner".to_string()).await.unwrap();
+        }
+        let app = router(state, Vec::new(), 4096);
+
+        let response = app.clone().oneshot(Request::get("/api/v1/suppressions/suggestions").body(Body::empty()).unwrap()).await.unwrap();
+        let suggestions: Vec<RuleSuggestion> = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        let adopt = Request::post(format!("/api/v1/suppressions/suggestions/{}/adopt", suggestions[0].id))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::json!({ "owner": "mallory", "days": 7 }).to_string()))
+            .unwrap();
+        assert_eq!(app.clone().oneshot(adopt).await.unwrap().status(), StatusCode::OK);
+        let rule = suppressions.rules().pop().unwrap();
+        assert_eq!((rule.signature.as_str(), rule.host.as_str(), rule.owner.as_str()), ("port_scan", "10.0.0.50", "service"));
+        assert_eq!(rule.incident_id, suggestions[0].sample_incidents[0]);
+
+        // Adopted suggestions are no longer proposed
+        let response = app.oneshot(Request::get("/api/v1/suppressions/suggestions").body(Body::empty()).unwrap()).await.unwrap();
+        let remaining: Vec<RuleSuggestion> = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert!(remaining.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_export_streams_ndjson() {
+        let (state, _, dir) = state().await;
+        let app = router(state, Vec::new(), 1024);
+
+        let request = Request::get("/api/v1/query/threats/export?format=ndjson").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(String::from_utf8(body.to_vec()).unwrap().lines().count(), 3);
+
+        let request = Request::get("/api/v1/query/secrets/export?format=csv").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_incident_export_requires_recipient_and_decrypts() {
+        let (state, incident_id, dir) = state().await;
+        let app = router(state, Vec::new(), 1024);
+        let (secret, public) = crate::incident_export::generate_identity();
+
+        let plain = Request::get("/api/v1/incidents/export?format=stix").body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(plain).await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+        let uri = format!(
+            "/api/v1/incidents/export?format=json&ids={}&recipients={}",
+            incident_id,
+            public.replace('+', "%2B").replace('/', "%2F").replace('=', "%3D")
+        );
+        let response = app.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let plaintext = crate::incident_export::decrypt_bundle(&body, &secret).unwrap();
+        let incidents: Vec<Incident> = serde_json::from_slice(&plaintext).unwrap();
+        assert_eq!(incidents[0].id, incident_id);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_token_are_rejected() {
+        let (state, incident_id, dir) = state().await;
+        let app = router(state, vec!["secret".to_string()], 1024);
+
+        let request = Request::get(format!("/api/v1/incidents/{}", incident_id)).body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        let health = Request::get("/healthz").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(health).await.unwrap().status(), StatusCode::OK);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_scoped_principal_sees_only_its_tenant() {
+        let (mut state, other_id, dir) = state().await;
+        let mut threat = AdvancedThreatResult { source_ip: "10.1.1.1".to_string(), ..Default::default() };
+        threat.details.insert("tenant".to_string(), "acme".to_string());
+        let acme = state.incidents.process_threat(threat).await.unwrap();
+        // False positives outside acme teach nothing to acme's analysts
+        for _ in 0..5 {
+            let scan = AdvancedThreatResult { source_ip: "10.0.0.50".to_string(), detection_method: "port_scan".to_string(), ..Default::default() };
+            let incident = state.incidents.process_threat(scan).await.unwrap();
+            state.incidents.mark_false_positive(&incident.id, "authorised scanner".to_string()).await.unwrap();
+        }
+        state.access = Arc::new(
+            DataAccess::new(&crate::access_scope::DataAccessSettings {
+                enabled: true,
+                roles: [("acme_analyst".to_string(), crate::access_scope::DataScope { tenants: vec!["acme".to_string()], ..Default::default() })].into(),
+                principals: vec![crate::access_scope::Principal {
+                    name: "alice".to_string(),
+                    token: "alice-token-0123456789".to_string(),
+                    roles: vec!["acme_analyst".to_string()],
+                }],
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let app = router(state, vec!["secret".to_string()], 1024);
+        let get = |uri: String, token: &str| {
+            Request::get(uri).header(header::AUTHORIZATION, format!("Bearer {}", token)).body(Body::empty()).unwrap()
+        };
+        let alice = "alice-token-0123456789";
+
+        let response = app.clone().oneshot(get("/api/v1/incidents".to_string(), alice)).await.unwrap();
+        let listed: Vec<Incident> = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(listed.iter().map(|i| i.id.as_str()).collect::<Vec<_>>(), vec![acme.id.as_str()]);
+        let response = app.clone().oneshot(get(format!("/api/v1/incidents/{}", other_id), alice)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app.clone().oneshot(get(format!("/api/v1/incidents/{}", other_id), "secret")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Query rows without the tenant column value are filtered by the injected predicate
+        let response = app.clone().oneshot(get("/api/v1/query/threats".to_string(), alice)).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(page["rows"].as_array().unwrap().len(), 0);
+        let response = app.clone().oneshot(get("/api/v1/suppressions".to_string(), alice)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let suggestions = |token: &str| {
+            let app = app.clone();
+            let request = get("/api/v1/suppressions/suggestions".to_string(), token);
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                serde_json::from_slice::<Vec<RuleSuggestion>>(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap()
+            }
+        };
+        assert!(suggestions(alice).await.is_empty());
+        let learned = suggestions("secret").await;
+        assert_eq!(learned.len(), 1);
+        let adopt = Request::post(format!("/api/v1/suppressions/suggestions/{}/adopt", learned[0].id))
+            .header(header::AUTHORIZATION, format!("Bearer {}", alice))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::json!({ "days": 7 }).to_string()))
+            .unwrap();
+        assert_eq!(app.clone().oneshot(adopt).await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        let response = app.oneshot(get("/api/v1/access/denials".to_string(), "secret")).await.unwrap();
+        let denials: Vec<AccessDenial> = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(denials.len(), 2);
+        assert_eq!((denials[0].principal.as_str(), denials[0].resource.clone()), ("alice", format!("incident {}", other_id)));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_forensic_mode_refuses_and_journals_changes() {
+        let (state, incident_id, dir) = state().await;
+        let journal = crate::forensic::ForensicJournal::open(crate::forensic::ForensicSettings {
+            enabled: true,
+            journal_path: String::new(),
+            ..Default::default()
+        })
+        .unwrap();
+        state.incidents.set_forensic_journal(Arc::new(journal));
+        let incidents = state.incidents.clone();
+        let app = router(state, Vec::new(), 4096);
+
+        let impact = Request::put(format!("/api/v1/incidents/{}/impact", incident_id))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::json!({ "affected_users": 10, "notes": "" }).to_string()))
+            .unwrap();
+        assert_eq!(app.clone().oneshot(impact).await.unwrap().status(), StatusCode::FORBIDDEN);
+        assert!(incidents.get_incident(&incident_id).unwrap().impact.is_none());
+        let read = Request::get(format!("/api/v1/incidents/{}", incident_id)).body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(read).await.unwrap().status(), StatusCode::OK);
+
+        // New incidents still open, but their alerts are journaled instead of sent
+        incidents.process_threat(AdvancedThreatResult::default()).await.unwrap();
+        let response = app.oneshot(Request::get("/api/v1/forensic/journal").body(Body::empty()).unwrap()).await.unwrap();
+        let report: ForensicReport = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert!(report.enabled);
+        assert_eq!(report.totals[&HeldBackKind::Mutation], 1);
+        assert_eq!(report.totals[&HeldBackKind::Alert], 1);
+        assert_eq!(report.recent[1].detail, format!("PUT /api/v1/incidents/{}/impact", incident_id));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_incident_listing_revalidates_with_etag() {
+        let (state, incident_id, dir) = state().await;
+        let incidents = state.incidents.clone();
+        let app = router(state, Vec::new(), 1024);
+        let list = |etag: Option<&str>| {
+            let request = Request::get("/api/v1/incidents?status=Open");
+            match etag {
+                Some(etag) => request.header(header::IF_NONE_MATCH, etag),
+                None => request,
+            }
+            .body(Body::empty())
+            .unwrap()
+        };
+
+        let response = app.clone().oneshot(list(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+        let listed: Vec<Incident> = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(listed.len(), 1);
+
+        let response = app.clone().oneshot(list(Some(&etag))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(hyper::body::to_bytes(response.into_body()).await.unwrap().is_empty());
+
+        // A mutation invalidates the cached listing at once
+        incidents.update_incident_status(&incident_id, IncidentStatus::Resolved).await.unwrap();
+        let response = app.oneshot(list(Some(&etag))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[header::ETAG], etag.as_str());
+        assert_eq!(hyper::body::to_bytes(response.into_body()).await.unwrap(), "[]");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "compliance")]
+    #[tokio::test]
+    async fn test_elevation_workflow_over_rest() {
+        let (state, _, dir) = state().await;
+        let app = router(state, Vec::new(), 4096);
+        let post = |uri: String, session: Option<&str>, body: serde_json::Value| {
+            let mut request = Request::post(uri).header(header::CONTENT_TYPE, "application/json");
+            if let Some(session) = session {
+                request = request.header(SESSION_HEADER, session);
+            }
+            request.body(Body::from(body.to_string())).unwrap()
+        };
+        let call = |request: Request<Body>| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap_or_default())
+            }
+        };
+        let login = |username: &str, password: &str| {
+            post("/api/v1/auth/login".to_string(), None, serde_json::json!({ "username": username, "password": password }))
+        };
+
+        assert_eq!(call(login("alice", "wrong")).await.0, StatusCode::UNAUTHORIZED);
+        let alice = call(login("alice", "Analyst-Pass-2024!")).await.1["session"].as_str().unwrap().to_string();
+        let admin = call(login("admin", "UltraSIEM2024!")).await.1["session"].as_str().unwrap().to_string();
+
+        let ask = serde_json::json!({
+            "requested_role": "IncidentResponder",
+            "justification": "Contain ransomware on FIN-WS-12 (INC-4411)",
+            "duration_minutes": 60
+        });
+        assert_eq!(call(post("/api/v1/elevations".to_string(), None, ask.clone())).await.0, StatusCode::UNAUTHORIZED);
+        let (status, request) = call(post("/api/v1/elevations".to_string(), Some(&alice), ask)).await;
+        assert_eq!((status, request["status"].as_str()), (StatusCode::OK, Some("pending")));
+        let id = request["id"].as_str().unwrap();
+
+        let approve = format!("/api/v1/elevations/{}/approve", id);
+        assert_eq!(call(post(approve.clone(), Some(&alice), serde_json::json!({}))).await.0, StatusCode::UNAUTHORIZED);
+        let (status, granted) = call(post(approve, Some(&admin), serde_json::json!({ "note": "on call" }))).await;
+        assert_eq!((status, granted["status"].as_str(), granted["approver"].as_str()), (StatusCode::OK, Some("active"), Some("admin")));
+
+        let list = Request::get("/api/v1/elevations").header(SESSION_HEADER, alice.as_str()).body(Body::empty()).unwrap();
+        assert_eq!(call(list).await.1.as_array().unwrap().len(), 1);
+        let (status, revoked) = call(post(format!("/api/v1/elevations/{}/revoke", id), Some(&alice), serde_json::json!({}))).await;
+        assert_eq!((status, revoked["status"].as_str()), (StatusCode::OK, Some("revoked")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
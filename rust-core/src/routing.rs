@@ -0,0 +1,355 @@
+use std::sync::{Arc, Mutex, RwLock};
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::{AlertChannel, AlertMessage, IncidentSeverity};
+use crate::timezone::TimeZones;
+
+/// Local time-of-day range as `HH:MM`; wraps past midnight when `end` < `start`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeWindow {
+    pub start: String,
+    pub end: String,
+}
+
+impl TimeWindow {
+    fn parse(&self) -> SIEMResult<(NaiveTime, NaiveTime)> {
+        let parse = |value: &str| {
+            NaiveTime::parse_from_str(value, "%H:%M")
+                .map_err(|_| SIEMError::Config(format!("Invalid time of day '{}', expected HH:MM", value)))
+        };
+        Ok((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    fn contains(&self, time: NaiveTime) -> bool {
+        match self.parse() {
+            Ok((start, end)) if start <= end => time >= start && time < end,
+            Ok((start, end)) => time >= start || time < end,
+            Err(_) => false,
+        }
+    }
+}
+
+/// Hours during which only pages at or above `page_min_severity` go out immediately
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuietHours {
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub window: TimeWindow,
+    pub page_min_severity: IncidentSeverity,
+}
+
+impl Default for QuietHours {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: TimeWindow {
+                start: "22:00".to_string(),
+                end: "07:00".to_string(),
+            },
+            page_min_severity: IncidentSeverity::Critical,
+        }
+    }
+}
+
+/// One row of the routing matrix; empty filters match everything
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingRule {
+    pub min_severity: IncidentSeverity,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub tenants: Vec<String>,
+    /// Matches alerts carrying any of these tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub hours: Option<TimeWindow>,
+    pub channels: Vec<AlertChannel>,
+}
+
+impl RoutingRule {
+    fn new(min_severity: IncidentSeverity, channel: AlertChannel) -> Self {
+        Self {
+            min_severity,
+            categories: Vec::new(),
+            tenants: Vec::new(),
+            tags: Vec::new(),
+            hours: None,
+            channels: vec![channel],
+        }
+    }
+
+    fn matches(&self, alert: &AlertMessage, local_time: NaiveTime) -> bool {
+        let tenant = alert.tenant.as_deref().unwrap_or_default();
+        alert.severity >= self.min_severity
+            && (self.categories.is_empty() || self.categories.iter().any(|c| c.eq_ignore_ascii_case(&alert.category)))
+            && (self.tenants.is_empty() || self.tenants.iter().any(|t| t == tenant))
+            && (self.tags.is_empty() || self.tags.iter().any(|t| alert.tags.contains(t)))
+            && self.hours.as_ref().is_none_or(|hours| hours.contains(local_time))
+    }
+}
+
+/// Severity/category/tenant/time-of-day routing of alerts to channels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoutingSettings {
+    /// Offset of the local time zone used for `hours` and quiet hours while no
+    /// `[time_zones]` are configured; otherwise the alert's tenant zone applies
+    pub utc_offset_minutes: i32,
+    pub quiet_hours: QuietHours,
+    pub rules: Vec<RoutingRule>,
+    /// Escalation for canary account and host hits: sent at once on top of the
+    /// matrix, quiet hours or not
+    pub canary_channels: Vec<AlertChannel>,
+}
+
+impl Default for RoutingSettings {
+    fn default() -> Self {
+        Self {
+            utc_offset_minutes: 0,
+            quiet_hours: QuietHours::default(),
+            rules: vec![
+                RoutingRule::new(IncidentSeverity::Medium, AlertChannel::Email),
+                RoutingRule::new(IncidentSeverity::High, AlertChannel::Webhook),
+                RoutingRule::new(IncidentSeverity::Medium, AlertChannel::Slack),
+                RoutingRule::new(IncidentSeverity::High, AlertChannel::Teams),
+                RoutingRule::new(IncidentSeverity::Critical, AlertChannel::PagerDuty),
+            ],
+            canary_channels: vec![AlertChannel::PagerDuty],
+        }
+    }
+}
+
+impl RoutingSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        self.quiet_hours.window.parse()?;
+        for rule in &self.rules {
+            if let Some(hours) = &rule.hours {
+                hours.parse()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Channels an alert goes to now, and those held for the morning digest
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RouteDecision {
+    pub send_now: Vec<AlertChannel>,
+    pub digest: Vec<AlertChannel>,
+}
+
+#[derive(Debug, Clone)]
+struct HeldAlert {
+    tenant: Option<String>,
+    severity: IncidentSeverity,
+    message: String,
+    channels: Vec<AlertChannel>,
+}
+
+/// Applies the routing matrix and holds quiet-hours alerts until they end
+#[derive(Debug, Default)]
+pub struct AlertRouter {
+    settings: RwLock<RoutingSettings>,
+    zones: RwLock<Arc<TimeZones>>,
+    held: Mutex<Vec<HeldAlert>>,
+}
+
+impl AlertRouter {
+    #[cfg(test)]
+    pub fn new(settings: RoutingSettings) -> SIEMResult<Self> {
+        settings.validate()?;
+        Ok(Self {
+            settings: RwLock::new(settings),
+            zones: RwLock::new(Arc::default()),
+            held: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn update(&self, settings: RoutingSettings) -> SIEMResult<()> {
+        settings.validate()?;
+        *self.settings.write().unwrap() = settings;
+        Ok(())
+    }
+
+    pub fn set_time_zones(&self, zones: Arc<TimeZones>) {
+        *self.zones.write().unwrap() = zones;
+    }
+
+    /// Time of day for `tenant`: its zone when zones are configured, else the fixed offset
+    fn local_time(&self, settings: &RoutingSettings, tenant: Option<&str>, now: DateTime<Utc>) -> NaiveTime {
+        let zones = self.zones.read().unwrap();
+        match zones.is_configured() {
+            true => zones.for_tenant(tenant).to_local(now).time(),
+            false => (now + Duration::minutes(settings.utc_offset_minutes as i64)).time(),
+        }
+    }
+
+    pub fn route(&self, alert: &AlertMessage, now: DateTime<Utc>) -> RouteDecision {
+        let settings = self.settings.read().unwrap();
+        let local_time = self.local_time(&settings, alert.tenant.as_deref(), now);
+        let mut channels: Vec<AlertChannel> = Vec::new();
+        for rule in settings.rules.iter().filter(|rule| rule.matches(alert, local_time)) {
+            for channel in &rule.channels {
+                if !channels.contains(channel) {
+                    channels.push(channel.clone());
+                }
+            }
+        }
+
+        if alert.canary {
+            for channel in &settings.canary_channels {
+                if !channels.contains(channel) {
+                    channels.push(channel.clone());
+                }
+            }
+            return RouteDecision { send_now: channels, digest: Vec::new() };
+        }
+
+        let quiet = &settings.quiet_hours;
+        if quiet.enabled && quiet.window.contains(local_time) && alert.severity < quiet.page_min_severity {
+            RouteDecision { send_now: Vec::new(), digest: channels }
+        } else {
+            RouteDecision { send_now: channels, digest: Vec::new() }
+        }
+    }
+
+    /// Hold an alert for the digest sent once quiet hours end
+    pub fn hold(&self, alert: &AlertMessage, channels: Vec<AlertChannel>) {
+        if channels.is_empty() {
+            return;
+        }
+        self.held.lock().unwrap().push(HeldAlert {
+            tenant: alert.tenant.clone(),
+            severity: alert.severity.clone(),
+            message: alert.message.clone(),
+            channels,
+        });
+    }
+
+    /// Digest message per channel of the alerts whose tenant's quiet hours are over
+    pub fn take_digest(&self, now: DateTime<Utc>) -> Vec<(AlertChannel, AlertMessage)> {
+        let held = {
+            let settings = self.settings.read().unwrap();
+            let quiet = &settings.quiet_hours;
+            let (still_quiet, held): (Vec<HeldAlert>, Vec<HeldAlert>) = std::mem::take(&mut *self.held.lock().unwrap())
+                .into_iter()
+                .partition(|alert| quiet.enabled && quiet.window.contains(self.local_time(&settings, alert.tenant.as_deref(), now)));
+            self.held.lock().unwrap().extend(still_quiet);
+            held
+        };
+        if held.is_empty() {
+            return Vec::new();
+        }
+
+        let mut channels: Vec<AlertChannel> = Vec::new();
+        for alert in &held {
+            for channel in &alert.channels {
+                if !channels.contains(channel) {
+                    channels.push(channel.clone());
+                }
+            }
+        }
+        channels
+            .into_iter()
+            .map(|channel| {
+                let alerts: Vec<&HeldAlert> = held.iter().filter(|alert| alert.channels.contains(&channel)).collect();
+                let mut message = format!("Digest of {} alerts held during quiet hours:", alerts.len());
+                for alert in &alerts {
+                    message.push_str(&format!("\n- [{}] {}", alert.severity, alert.message));
+                }
+                let severity = alerts.iter().map(|alert| alert.severity.clone()).max().unwrap_or(IncidentSeverity::Low);
+                (channel, AlertMessage::new(severity, message))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 5, hour, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn test_default_matrix_matches_previous_thresholds() {
+        let router = AlertRouter::new(RoutingSettings::default()).unwrap();
+        let route = |severity| router.route(&AlertMessage::new(severity, "x".to_string()), at(12)).send_now;
+
+        assert!(route(IncidentSeverity::Low).is_empty());
+        assert_eq!(route(IncidentSeverity::Medium), vec![AlertChannel::Email, AlertChannel::Slack]);
+        assert_eq!(route(IncidentSeverity::High).len(), 4);
+        assert!(route(IncidentSeverity::Critical).contains(&AlertChannel::PagerDuty));
+    }
+
+    #[test]
+    fn test_quiet_hours_digest_everything_but_pages() {
+        let mut settings = RoutingSettings {
+            utc_offset_minutes: 60,
+            ..Default::default()
+        };
+        settings.quiet_hours.enabled = true;
+        settings.rules.push(RoutingRule {
+            min_severity: IncidentSeverity::Low,
+            categories: vec!["Malware".to_string()],
+            tenants: vec!["acme".to_string()],
+            tags: Vec::new(),
+            hours: None,
+            channels: vec![AlertChannel::Custom { url: "https://soc.acme.example/hook".to_string() }],
+        });
+        let router = AlertRouter::new(settings).unwrap();
+
+        // 22:30 UTC is 23:30 local, inside 22:00-07:00
+        let mut high = AlertMessage::new(IncidentSeverity::High, "lateral movement".to_string());
+        high.category = "Malware".to_string();
+        high.tenant = Some("acme".to_string());
+        let decision = router.route(&high, at(22));
+        assert!(decision.send_now.is_empty());
+        assert_eq!(decision.digest.len(), 5);
+        router.hold(&high, decision.digest);
+
+        let critical = AlertMessage::new(IncidentSeverity::Critical, "ransomware".to_string());
+        assert!(router.route(&critical, at(22)).send_now.contains(&AlertChannel::PagerDuty));
+
+        // Canary hits page even when a policy would hold their severity
+        let mut canary = AlertMessage::new(IncidentSeverity::Low, "canary account used".to_string());
+        canary.canary = true;
+        assert_eq!(router.route(&canary, at(22)).send_now, vec![AlertChannel::PagerDuty]);
+
+        assert!(router.take_digest(at(3)).is_empty());
+        let digest = router.take_digest(at(7));
+        assert_eq!(digest.len(), 5);
+        assert!(digest[0].1.message.contains("lateral movement"));
+        assert!(router.take_digest(at(8)).is_empty());
+    }
+
+    #[test]
+    fn test_quiet_hours_follow_the_tenant_zone() {
+        let mut settings = RoutingSettings::default();
+        settings.quiet_hours.enabled = true;
+        let router = AlertRouter::new(settings).unwrap();
+        let zones = crate::timezone::TimeZoneSettings {
+            tenants: [("apac".to_string(), "+08:00".to_string())].into(),
+            ..Default::default()
+        };
+        router.set_time_zones(Arc::new(TimeZones::new(&zones).unwrap()));
+
+        // 14:30 UTC is 22:30 in the APAC tenant's zone but mid-afternoon in UTC
+        let mut apac = AlertMessage::new(IncidentSeverity::High, "beaconing".to_string());
+        apac.tenant = Some("apac".to_string());
+        let decision = router.route(&apac, at(14));
+        assert!(decision.send_now.is_empty());
+        router.hold(&apac, decision.digest);
+        assert!(router.route(&AlertMessage::new(IncidentSeverity::High, "beaconing".to_string()), at(14)).digest.is_empty());
+
+        // 23:30 UTC is 07:30 the next morning for APAC
+        assert!(router.take_digest(at(18)).is_empty());
+        assert_eq!(router.take_digest(at(23)).len(), 4);
+    }
+}
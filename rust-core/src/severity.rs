@@ -0,0 +1,424 @@
+use std::collections::{BTreeMap, HashMap};
+use evalexpr::{build_operator_tree, ContextWithMutableFunctions, ContextWithMutableVariables, Function, HashMapContext, Node, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::bridge_contract::severity_code;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::IncidentSeverity;
+use crate::tagging::TAGS_FIELD;
+use crate::threat_detection::ThreatSeverity;
+use crate::triage::TriageScore;
+
+/// Score cut-offs of a policy; scores below `medium` are Low
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeverityThresholds {
+    pub emergency: Option<f64>,
+    pub critical: f64,
+    pub high: f64,
+    pub medium: f64,
+}
+
+impl Default for SeverityThresholds {
+    fn default() -> Self {
+        Self {
+            emergency: None,
+            critical: 0.85,
+            high: 0.65,
+            medium: 0.4,
+        }
+    }
+}
+
+/// Named sub-expression, visible to later terms and the policy expression
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityTerm {
+    pub name: String,
+    pub expression: String,
+}
+
+/// Expression over the incident inputs that yields its severity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeverityPolicy {
+    /// Evaluates to a score mapped through `thresholds`, or to a severity name;
+    /// `has_tag("prod")` and `detail("env")` read the detection's tags and details
+    pub expression: String,
+    pub terms: Vec<SeverityTerm>,
+    pub thresholds: SeverityThresholds,
+    /// Exposed as `category_weight` for detections of the category
+    pub category_weights: HashMap<String, f64>,
+}
+
+impl Default for SeverityPolicy {
+    fn default() -> Self {
+        Self {
+            expression: "0.45 * confidence + 0.25 * asset_criticality + 0.15 * entity_risk + 0.15 * intel_match + category_weight"
+                .to_string(),
+            terms: Vec::new(),
+            thresholds: SeverityThresholds::default(),
+            category_weights: HashMap::new(),
+        }
+    }
+}
+
+/// Incident severity policy settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeveritySettings {
+    /// Off keeps incident severity equal to the detection severity
+    pub enabled: bool,
+    pub policy: SeverityPolicy,
+    /// Policies keyed by the `tenant` detail of the detection
+    pub tenants: HashMap<String, SeverityPolicy>,
+}
+
+/// Value of one evaluated term
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeverityTermValue {
+    pub name: String,
+    pub expression: String,
+    pub value: serde_json::Value,
+}
+
+/// How an incident's severity was derived
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeverityExplanation {
+    /// `default`, or the tenant whose policy applied
+    pub policy: String,
+    pub expression: String,
+    pub inputs: BTreeMap<String, serde_json::Value>,
+    pub terms: Vec<SeverityTermValue>,
+    pub result: Option<serde_json::Value>,
+    pub detected: IncidentSeverity,
+    pub severity: IncidentSeverity,
+    /// Why the detection severity was kept, if it was
+    pub fallback: Option<String>,
+}
+
+#[derive(Debug)]
+struct CompiledPolicy {
+    source: SeverityPolicy,
+    terms: Vec<Node>,
+    expression: Node,
+}
+
+impl CompiledPolicy {
+    fn compile(name: &str, source: SeverityPolicy) -> SIEMResult<Self> {
+        let invalid = |what: &str, e: &dyn std::fmt::Display| SIEMError::Config(format!("Severity policy '{}' {}: {}", name, what, e));
+        let t = &source.thresholds;
+        if !(t.medium <= t.high && t.high <= t.critical && t.emergency.is_none_or(|e| t.critical <= e)) {
+            return Err(invalid("thresholds", &"must increase from medium to emergency"));
+        }
+        let terms = source
+            .terms
+            .iter()
+            .map(|term| build_operator_tree(&term.expression).map_err(|e| invalid(&format!("term '{}'", term.name), &e)))
+            .collect::<SIEMResult<Vec<_>>>()?;
+        let expression = build_operator_tree(&source.expression).map_err(|e| invalid("expression", &e))?;
+        let policy = Self { source, terms, expression };
+
+        // Dry run on neutral inputs to catch unknown variables and non-severity results
+        let (_, _, result) = policy.evaluate(&neutral_inputs(), &HashMap::new());
+        result.map_err(|e| invalid("evaluation", &e))?;
+        Ok(policy)
+    }
+
+    fn evaluate(
+        &self,
+        inputs: &BTreeMap<String, Value>,
+        details: &HashMap<String, String>,
+    ) -> (Vec<SeverityTermValue>, Option<Value>, Result<IncidentSeverity, String>) {
+        let mut context = HashMapContext::new();
+        for (name, value) in inputs {
+            if let Err(e) = context.set_value(name.clone(), value.clone()) {
+                return (Vec::new(), None, Err(e.to_string()));
+            }
+        }
+        let tags: Vec<String> = details
+            .get(TAGS_FIELD)
+            .map(|tags| tags.split(',').map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty()).collect())
+            .unwrap_or_default();
+        let details = details.clone();
+        let functions = [
+            ("has_tag", Function::new(move |tag| Ok(Value::Boolean(tags.contains(&tag.as_string()?))))),
+            ("detail", Function::new(move |name| Ok(Value::String(details.get(&name.as_string()?).cloned().unwrap_or_default())))),
+        ];
+        for (name, function) in functions {
+            if let Err(e) = context.set_function(name.to_string(), function) {
+                return (Vec::new(), None, Err(e.to_string()));
+            }
+        }
+        let mut terms = Vec::new();
+        for (term, node) in self.source.terms.iter().zip(&self.terms) {
+            let value = match node.eval_with_context(&context) {
+                Ok(value) => value,
+                Err(e) => return (terms, None, Err(format!("term '{}': {}", term.name, e))),
+            };
+            terms.push(SeverityTermValue {
+                name: term.name.clone(),
+                expression: term.expression.clone(),
+                value: to_json(&value),
+            });
+            if let Err(e) = context.set_value(term.name.clone(), value) {
+                return (terms, None, Err(e.to_string()));
+            }
+        }
+        match self.expression.eval_with_context(&context) {
+            Ok(value) => {
+                let severity = self.severity_of(&value);
+                (terms, Some(value), severity)
+            }
+            Err(e) => (terms, None, Err(e.to_string())),
+        }
+    }
+
+    fn severity_of(&self, value: &Value) -> Result<IncidentSeverity, String> {
+        let t = &self.source.thresholds;
+        match value {
+            Value::Float(_) | Value::Int(_) => {
+                let score = value.as_number().map_err(|e| e.to_string())?;
+                Ok(match score {
+                    s if t.emergency.is_some_and(|e| s >= e) => IncidentSeverity::Emergency,
+                    s if s >= t.critical => IncidentSeverity::Critical,
+                    s if s >= t.high => IncidentSeverity::High,
+                    s if s >= t.medium => IncidentSeverity::Medium,
+                    _ => IncidentSeverity::Low,
+                })
+            }
+            Value::String(name) => match name.to_ascii_lowercase().as_str() {
+                "low" => Ok(IncidentSeverity::Low),
+                "medium" => Ok(IncidentSeverity::Medium),
+                "high" => Ok(IncidentSeverity::High),
+                "critical" => Ok(IncidentSeverity::Critical),
+                "emergency" => Ok(IncidentSeverity::Emergency),
+                _ => Err(format!("'{}' is not a severity", name)),
+            },
+            other => Err(format!("expected a score or severity name, got {}", other)),
+        }
+    }
+}
+
+/// Computes incident severity from the configured default and per-tenant policies
+#[derive(Debug)]
+pub struct SeverityPolicyEngine {
+    enabled: bool,
+    policy: CompiledPolicy,
+    tenants: HashMap<String, CompiledPolicy>,
+}
+
+impl Default for SeverityPolicyEngine {
+    fn default() -> Self {
+        Self::new(SeveritySettings::default()).expect("built-in severity policy compiles")
+    }
+}
+
+impl SeverityPolicyEngine {
+    pub fn new(settings: SeveritySettings) -> SIEMResult<Self> {
+        let tenants = settings
+            .tenants
+            .into_iter()
+            .map(|(tenant, policy)| CompiledPolicy::compile(&tenant, policy).map(|compiled| (tenant, compiled)))
+            .collect::<SIEMResult<HashMap<_, _>>>()?;
+        Ok(Self {
+            enabled: settings.enabled,
+            policy: CompiledPolicy::compile("default", settings.policy)?,
+            tenants,
+        })
+    }
+
+    /// Severity of a new incident with the inputs and steps that produced it
+    pub fn evaluate(&self, threat: &AdvancedThreatResult, triage: &TriageScore) -> SeverityExplanation {
+        let detected = detected_severity(&threat.severity);
+        let (policy_name, policy) = match threat.details.get("tenant").and_then(|t| self.tenants.get_key_value(t)) {
+            Some((tenant, policy)) => (tenant.clone(), policy),
+            None => ("default".to_string(), &self.policy),
+        };
+        let factor = |name: &str| triage.factors.iter().find(|f| f.name == name).map_or(0.0, |f| f.value);
+        let category = threat.category.to_string();
+        let inputs = BTreeMap::from([
+            ("confidence".to_string(), Value::Float((threat.confidence as f64).clamp(0.0, 1.0))),
+            ("asset_criticality".to_string(), Value::Float(factor("asset_criticality"))),
+            ("entity_risk".to_string(), Value::Float(factor("entity_risk"))),
+            ("intel_match".to_string(), Value::Float(factor("intel_match"))),
+            ("category_weight".to_string(), Value::Float(policy.source.category_weights.get(&category).copied().unwrap_or(0.0))),
+            ("detected_severity".to_string(), Value::Int(severity_code(&threat.severity) as i64)),
+            ("category".to_string(), Value::String(category)),
+        ]);
+
+        let mut explanation = SeverityExplanation {
+            policy: policy_name,
+            expression: policy.source.expression.clone(),
+            inputs: inputs.iter().map(|(name, value)| (name.clone(), to_json(value))).collect(),
+            terms: Vec::new(),
+            result: None,
+            detected: detected.clone(),
+            severity: detected,
+            fallback: None,
+        };
+        if !self.enabled {
+            explanation.fallback = Some("severity policy disabled".to_string());
+            return explanation;
+        }
+        let (terms, result, severity) = policy.evaluate(&inputs, &threat.details);
+        explanation.terms = terms;
+        explanation.result = result.as_ref().map(to_json);
+        match severity {
+            Ok(severity) => explanation.severity = severity,
+            Err(e) => explanation.fallback = Some(e),
+        }
+        explanation
+    }
+}
+
+fn detected_severity(severity: &ThreatSeverity) -> IncidentSeverity {
+    match severity {
+        ThreatSeverity::Low => IncidentSeverity::Low,
+        ThreatSeverity::Medium => IncidentSeverity::Medium,
+        ThreatSeverity::High => IncidentSeverity::High,
+        ThreatSeverity::Critical => IncidentSeverity::Critical,
+    }
+}
+
+fn neutral_inputs() -> BTreeMap<String, Value> {
+    ["confidence", "asset_criticality", "entity_risk", "intel_match", "category_weight"]
+        .into_iter()
+        .map(|name| (name.to_string(), Value::Float(0.0)))
+        .chain([
+            ("detected_severity".to_string(), Value::Int(1)),
+            ("category".to_string(), Value::String(String::new())),
+        ])
+        .collect()
+}
+
+fn to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::String(s) => serde_json::json!(s),
+        Value::Float(f) => serde_json::json!(f),
+        Value::Int(i) => serde_json::json!(i),
+        Value::Boolean(b) => serde_json::json!(b),
+        Value::Tuple(items) => serde_json::Value::Array(items.iter().map(to_json).collect()),
+        Value::Empty => serde_json::Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::threat_detection::ThreatCategory;
+    use crate::triage::{TriageScorer, TriageSettings};
+
+    fn threat(category: ThreatCategory, confidence: f32, tenant: Option<&str>) -> AdvancedThreatResult {
+        let mut threat = AdvancedThreatResult {
+            category,
+            confidence,
+            severity: ThreatSeverity::Medium,
+            destination_ip: "10.0.0.10".to_string(),
+            ..Default::default()
+        };
+        if let Some(tenant) = tenant {
+            threat.details.insert("tenant".to_string(), tenant.to_string());
+        }
+        threat
+    }
+
+    #[test]
+    fn test_default_and_tenant_policies_explain_their_result() {
+        let mut triage = TriageSettings::default();
+        triage.asset_criticality.insert("10.0.0.10".to_string(), 1.0);
+        let scorer = TriageScorer::new(triage);
+        let acme = SeverityPolicy {
+            terms: vec![SeverityTerm {
+                name: "exposure".to_string(),
+                expression: "max(asset_criticality, entity_risk)".to_string(),
+            }],
+            expression: "if(category == \"DataExfiltration\", \"emergency\", if(exposure > 0.8, \"critical\", \"low\"))".to_string(),
+            ..Default::default()
+        };
+        let engine = SeverityPolicyEngine::new(SeveritySettings {
+            enabled: true,
+            policy: SeverityPolicy {
+                category_weights: HashMap::from([("DataExfiltration".to_string(), 0.3)]),
+                ..Default::default()
+            },
+            tenants: HashMap::from([("acme".to_string(), acme)]),
+        })
+        .unwrap();
+
+        // 0.45 * 0.9 + 0.25 * 1.0 + 0.3 = 0.955
+        let exfiltration = threat(ThreatCategory::DataExfiltration, 0.9, None);
+        let explained = engine.evaluate(&exfiltration, &scorer.score(&exfiltration));
+        assert_eq!(explained.policy, "default");
+        assert_eq!(explained.detected, IncidentSeverity::Medium);
+        assert_eq!(explained.severity, IncidentSeverity::Critical);
+        assert_eq!(explained.inputs["category_weight"], serde_json::json!(0.3));
+        assert!((explained.result.unwrap().as_f64().unwrap() - 0.955).abs() < 1e-6);
+
+        let malware = threat(ThreatCategory::Malware, 0.1, Some("acme"));
+        let explained = engine.evaluate(&malware, &scorer.score(&malware));
+        assert_eq!(explained.policy, "acme");
+        assert_eq!(explained.terms[0].value, serde_json::json!(1.0));
+        assert_eq!(explained.severity, IncidentSeverity::Critical);
+        assert!(explained.fallback.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_policies_read_tags_and_derived_fields() {
+        let policy = SeverityPolicy {
+            expression: "if(has_tag(\"prod\") && detail(\"business_unit\") == \"finance\", \"critical\", \"low\")".to_string(),
+            ..Default::default()
+        };
+        let engine = SeverityPolicyEngine::new(SeveritySettings { enabled: true, policy, ..Default::default() }).unwrap();
+        let scorer = TriageScorer::new(TriageSettings::default());
+
+        let mut tagged = threat(ThreatCategory::Malware, 0.5, None);
+        tagged.details.insert(TAGS_FIELD.to_string(), "edr,prod".to_string());
+        tagged.details.insert("business_unit".to_string(), "finance".to_string());
+        assert_eq!(engine.evaluate(&tagged, &scorer.score(&tagged)).severity, IncidentSeverity::Critical);
+        let untagged = threat(ThreatCategory::Malware, 0.5, None);
+        assert_eq!(engine.evaluate(&untagged, &scorer.score(&untagged)).severity, IncidentSeverity::Low);
+
+        use crate::incident_response::{AlertConfig, IncidentResponseEngine, SOARConfig};
+        let incidents = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let incident = incidents.process_threat(tagged).await.unwrap();
+        assert!(incident.tags.contains("prod") && incident.tags.contains("edr"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_policies_are_rejected_and_incidents_keep_explanations() {
+        let bad_variable = SeverityPolicy {
+            expression: "confidance * 2".to_string(),
+            ..Default::default()
+        };
+        let settings = SeveritySettings {
+            enabled: true,
+            tenants: HashMap::from([("acme".to_string(), bad_variable)]),
+            ..Default::default()
+        };
+        assert!(SeverityPolicyEngine::new(settings).is_err());
+        let bad_name = SeverityPolicy {
+            expression: "\"severe\"".to_string(),
+            ..Default::default()
+        };
+        assert!(SeverityPolicyEngine::new(SeveritySettings { policy: bad_name, ..Default::default() }).is_err());
+
+        use crate::incident_response::{AlertConfig, IncidentResponseEngine, SOARConfig};
+        let incidents = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        let quiet = incidents.process_threat(threat(ThreatCategory::Malware, 0.1, None)).await.unwrap();
+        assert_eq!(quiet.severity, IncidentSeverity::Medium);
+
+        incidents
+            .set_severity_settings(SeveritySettings {
+                enabled: true,
+                ..Default::default()
+            })
+            .unwrap();
+        let incident = incidents.process_threat(threat(ThreatCategory::Malware, 0.1, None)).await.unwrap();
+        assert_eq!(incident.severity, IncidentSeverity::Low);
+        let explained = incidents.explain_severity(&incident.id).unwrap();
+        assert_eq!(explained.severity, IncidentSeverity::Low);
+        assert_eq!(explained.policy, "default");
+        assert_eq!(incidents.explain_severity(&quiet.id).unwrap().fallback.as_deref(), Some("severity policy disabled"));
+    }
+}
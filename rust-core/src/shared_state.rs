@@ -0,0 +1,440 @@
+//! # Distributed Allow-List and Block-List
+//!
+//! Cores sharing a deployment keep allow-list entries, blocked IPs and
+//! disabled accounts in one JetStream key-value bucket. Every core watches the
+//! bucket: the initial replay fills its local caches and later puts and
+//! deletes invalidate them, so a block made on one core is visible to all of
+//! them within a round trip. Entries carry their own expiry; expired ones are
+//! ignored on arrival and purged from the bucket by whichever core sees them.
+//!
+//! The bucket sits behind [`StateBackend`]; another store (e.g. Redis) only
+//! has to provide put, delete and a watch with initial replay.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::advanced_threat_detection::AdvancedThreatDetectionEngine;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::IncidentResponseEngine;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SharedStateSettings {
+    pub enabled: bool,
+    /// JetStream key-value bucket, created on first use
+    pub bucket: String,
+    pub replicas: usize,
+    /// Name stamped on the entries this core writes; a random id when empty
+    pub instance_id: String,
+    pub purge_interval_seconds: u64,
+}
+
+impl Default for SharedStateSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bucket: "ultra_siem_state".to_string(),
+            replicas: 1,
+            instance_id: String::new(),
+            purge_interval_seconds: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SharedList {
+    AllowList,
+    BlockedIps,
+    DisabledAccounts,
+}
+
+impl SharedList {
+    const ALL: [SharedList; 3] = [SharedList::AllowList, SharedList::BlockedIps, SharedList::DisabledAccounts];
+
+    fn prefix(&self) -> &'static str {
+        match self {
+            SharedList::AllowList => "allow",
+            SharedList::BlockedIps => "block",
+            SharedList::DisabledAccounts => "account",
+        }
+    }
+
+    /// Bucket key; values are hex encoded since KV keys can't hold `:` or `@`
+    fn key(&self, value: &str) -> String {
+        let hex: String = value.bytes().map(|b| format!("{:02x}", b)).collect();
+        format!("{}.{}", self.prefix(), hex)
+    }
+
+    fn parse_key(key: &str) -> Option<(SharedList, String)> {
+        let (prefix, hex) = key.split_once('.')?;
+        let list = Self::ALL.into_iter().find(|list| list.prefix() == prefix)?;
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+            .collect::<Option<Vec<u8>>>()?;
+        Some((list, String::from_utf8(bytes).ok()?))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SharedEntry {
+    /// Unix seconds; never expires when absent
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    /// Instance that wrote the entry
+    pub origin: String,
+    pub updated_at: u64,
+}
+
+impl SharedEntry {
+    fn expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expiry| expiry <= now)
+    }
+}
+
+/// A put (`Some`) or delete (`None`) of one raw bucket key
+pub type RawChange = (String, Option<Vec<u8>>);
+
+/// Key-value store holding the shared lists
+pub trait StateBackend: Send + Sync {
+    fn put<'a>(&'a self, key: &'a str, value: Vec<u8>) -> BoxFuture<'a, SIEMResult<()>>;
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, SIEMResult<()>>;
+    /// Every current entry, then every later change
+    fn watch(&self) -> BoxFuture<'_, SIEMResult<mpsc::Receiver<RawChange>>>;
+}
+
+pub struct JetStreamBackend {
+    store: async_nats::jetstream::kv::Store,
+}
+
+impl JetStreamBackend {
+    pub async fn connect(client: async_nats::Client, settings: &SharedStateSettings) -> SIEMResult<Self> {
+        let jetstream = async_nats::jetstream::new(client);
+        let store = match jetstream.get_key_value(&settings.bucket).await {
+            Ok(store) => store,
+            Err(_) => jetstream
+                .create_key_value(async_nats::jetstream::kv::Config {
+                    bucket: settings.bucket.clone(),
+                    description: "Ultra SIEM allow-list, blocked IPs and disabled accounts".to_string(),
+                    history: 1,
+                    num_replicas: settings.replicas.max(1),
+                    storage: async_nats::jetstream::stream::StorageType::File,
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| SIEMError::Other(format!("Key-value bucket {} unavailable: {}", settings.bucket, e)))?,
+        };
+        Ok(Self { store })
+    }
+}
+
+impl StateBackend for JetStreamBackend {
+    fn put<'a>(&'a self, key: &'a str, value: Vec<u8>) -> BoxFuture<'a, SIEMResult<()>> {
+        Box::pin(async move {
+            self.store.put(key, value.into()).await.map_err(|e| SIEMError::Other(format!("Shared state put {}: {}", key, e)))?;
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, SIEMResult<()>> {
+        Box::pin(async move { self.store.delete(key).await.map_err(|e| SIEMError::Other(format!("Shared state delete {}: {}", key, e))) })
+    }
+
+    fn watch(&self) -> BoxFuture<'_, SIEMResult<mpsc::Receiver<RawChange>>> {
+        Box::pin(async move {
+            let store = self.store.clone();
+            let (tx, rx) = mpsc::channel(1024);
+            tokio::spawn(async move {
+                // A lost watch is re-created; the replay of current entries is idempotent
+                loop {
+                    match store.watch_with_history(">").await {
+                        Ok(mut entries) => {
+                            while let Some(entry) = entries.next().await {
+                                let change = match entry {
+                                    Ok(entry) => match entry.operation {
+                                        async_nats::jetstream::kv::Operation::Put => (entry.key, Some(entry.value.to_vec())),
+                                        _ => (entry.key, None),
+                                    },
+                                    Err(e) => {
+                                        warn!("⚠️ Shared state watch error: {}", e);
+                                        continue;
+                                    }
+                                };
+                                if tx.send(change).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                        Err(e) => warn!("⚠️ Shared state watch unavailable: {}", e),
+                    }
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            });
+            Ok(rx)
+        })
+    }
+}
+
+/// Shared lists with a local cache kept current by the backend's watch
+pub struct SharedState {
+    instance_id: String,
+    purge_interval: Duration,
+    backend: Arc<dyn StateBackend>,
+    entries: RwLock<BTreeMap<SharedList, BTreeMap<String, SharedEntry>>>,
+    detector: RwLock<Option<Arc<AdvancedThreatDetectionEngine>>>,
+}
+
+impl std::fmt::Debug for SharedState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedState").field("instance_id", &self.instance_id).finish_non_exhaustive()
+    }
+}
+
+impl SharedState {
+    pub fn new(settings: &SharedStateSettings, backend: Arc<dyn StateBackend>) -> Self {
+        let instance_id = if settings.instance_id.is_empty() { uuid::Uuid::new_v4().to_string() } else { settings.instance_id.clone() };
+        Self {
+            instance_id,
+            purge_interval: Duration::from_secs(settings.purge_interval_seconds.max(1)),
+            backend,
+            entries: RwLock::new(BTreeMap::new()),
+            detector: RwLock::new(None),
+        }
+    }
+
+    pub async fn connect(settings: &SharedStateSettings, nats_url: &str) -> SIEMResult<Self> {
+        let client = async_nats::connect(nats_url)
+            .await
+            .map_err(|e| SIEMError::Other(format!("NATS {} unavailable: {}", nats_url, e)))?;
+        Ok(Self::new(settings, Arc::new(JetStreamBackend::connect(client, settings).await?)))
+    }
+
+    /// Keep the detection allow-list in step; current entries are applied right away
+    pub fn attach_detector(&self, detector: Arc<AdvancedThreatDetectionEngine>) {
+        for value in self.snapshot().remove(&SharedList::AllowList).unwrap_or_default().into_keys() {
+            let _ = detector.add_to_whitelist(value);
+        }
+        *self.detector.write().unwrap() = Some(detector);
+    }
+
+    /// Write an entry for every core; `ttl_seconds` of `None` never expires
+    pub async fn put(&self, list: SharedList, value: &str, ttl_seconds: Option<u64>) -> SIEMResult<SharedEntry> {
+        if value.trim().is_empty() {
+            return Err(SIEMError::Validation("Shared list values can't be empty".to_string()));
+        }
+        let now = unix_now();
+        let entry = SharedEntry { expires_at: ttl_seconds.map(|ttl| now + ttl), origin: self.instance_id.clone(), updated_at: now };
+        self.backend.put(&list.key(value), serde_json::to_vec(&entry)?).await?;
+        Ok(entry)
+    }
+
+    pub async fn remove(&self, list: SharedList, value: &str) -> SIEMResult<()> {
+        self.backend.delete(&list.key(value)).await
+    }
+
+    /// Write in the background, for callers on the response path
+    pub fn publish(self: &Arc<Self>, list: SharedList, value: &str, expires_at: u64) {
+        let (state, value) = (self.clone(), value.to_string());
+        tokio::spawn(async move {
+            let ttl = expires_at.saturating_sub(unix_now());
+            if let Err(e) = state.put(list, &value, Some(ttl)).await {
+                warn!("⚠️ {:?} entry {} not shared with other cores: {}", list, value, e);
+            }
+        });
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<SharedList, BTreeMap<String, SharedEntry>> {
+        self.entries.read().unwrap().clone()
+    }
+
+    /// Apply the bucket's changes to the local caches until the watch ends
+    pub fn spawn(self: Arc<Self>, incidents: Arc<IncidentResponseEngine>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut changes = match self.backend.watch().await {
+                Ok(changes) => changes,
+                Err(e) => {
+                    warn!("⚠️ Shared state not watched: {}", e);
+                    return;
+                }
+            };
+            let mut purge = tokio::time::interval(self.purge_interval);
+            info!("🔗 Shared allow/block lists synchronized as {}", self.instance_id);
+            loop {
+                tokio::select! {
+                    change = changes.recv() => match change {
+                        Some((key, value)) => self.apply(&incidents, &key, value.as_deref()),
+                        None => return,
+                    },
+                    _ = purge.tick() => {
+                        if let Err(e) = self.purge_expired(unix_now()).await {
+                            warn!("⚠️ Expired shared entries not purged: {}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    fn apply(&self, incidents: &IncidentResponseEngine, key: &str, value: Option<&[u8]>) {
+        let Some((list, item)) = SharedList::parse_key(key) else {
+            return;
+        };
+        let entry = value.and_then(|raw| serde_json::from_slice::<SharedEntry>(raw).ok());
+        let entry = match entry {
+            Some(entry) if entry.expired(unix_now()) => {
+                let (backend, key) = (self.backend.clone(), key.to_string());
+                tokio::spawn(async move { backend.delete(&key).await });
+                None
+            }
+            entry => entry,
+        };
+        let detector = self.detector.read().unwrap().clone();
+        match (&entry, list) {
+            (Some(_), SharedList::AllowList) => {
+                if let Some(detector) = detector {
+                    let _ = detector.add_to_whitelist(item.clone());
+                }
+            }
+            (None, SharedList::AllowList) => {
+                if let Some(detector) = detector {
+                    let _ = detector.remove_from_whitelist(&item);
+                }
+            }
+            (Some(entry), SharedList::BlockedIps) => incidents.record_blocked_ip(&item, entry.expires_at.unwrap_or(u64::MAX)),
+            (None, SharedList::BlockedIps) => incidents.remove_blocked_ip(&item),
+            (Some(entry), SharedList::DisabledAccounts) => incidents.record_disabled_account(&item, entry.expires_at.unwrap_or(u64::MAX)),
+            (None, SharedList::DisabledAccounts) => incidents.remove_disabled_account(&item),
+        }
+        let mut entries = self.entries.write().unwrap();
+        let cached = entries.entry(list).or_default();
+        match entry {
+            Some(entry) => cached.insert(item, entry),
+            None => cached.remove(&item),
+        };
+    }
+
+    async fn purge_expired(&self, now: u64) -> SIEMResult<()> {
+        let expired: Vec<(SharedList, String)> = self
+            .snapshot()
+            .into_iter()
+            .flat_map(|(list, entries)| entries.into_iter().filter(|(_, entry)| entry.expired(now)).map(move |(item, _)| (list, item)))
+            .collect();
+        for (list, item) in expired {
+            self.remove(list, &item).await?;
+        }
+        Ok(())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatConfig;
+    use crate::incident_response::{AlertConfig, SOARConfig};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// In-process bucket shared by several [`SharedState`]s, standing in for JetStream in tests
+    #[derive(Default)]
+    struct MemoryBackend {
+        entries: Mutex<HashMap<String, Vec<u8>>>,
+        watchers: Mutex<Vec<mpsc::Sender<RawChange>>>,
+    }
+
+    impl MemoryBackend {
+        fn notify(&self, change: RawChange) {
+            self.watchers.lock().unwrap().retain(|watcher| watcher.try_send(change.clone()).is_ok());
+        }
+    }
+
+    impl StateBackend for MemoryBackend {
+        fn put<'a>(&'a self, key: &'a str, value: Vec<u8>) -> BoxFuture<'a, SIEMResult<()>> {
+            self.entries.lock().unwrap().insert(key.to_string(), value.clone());
+            self.notify((key.to_string(), Some(value)));
+            Box::pin(async { Ok(()) })
+        }
+
+        fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, SIEMResult<()>> {
+            self.entries.lock().unwrap().remove(key);
+            self.notify((key.to_string(), None));
+            Box::pin(async { Ok(()) })
+        }
+
+        fn watch(&self) -> BoxFuture<'_, SIEMResult<mpsc::Receiver<RawChange>>> {
+            let (tx, rx) = mpsc::channel(1024);
+            for (key, value) in self.entries.lock().unwrap().iter() {
+                let _ = tx.try_send((key.clone(), Some(value.clone())));
+            }
+            self.watchers.lock().unwrap().push(tx);
+            Box::pin(async { Ok(rx) })
+        }
+    }
+
+    fn core(backend: &Arc<MemoryBackend>, name: &str) -> (Arc<SharedState>, Arc<IncidentResponseEngine>) {
+        let settings = SharedStateSettings { enabled: true, instance_id: name.to_string(), ..Default::default() };
+        let state = Arc::new(SharedState::new(&settings, backend.clone()));
+        let incidents = Arc::new(IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled()));
+        incidents.set_shared_state(state.clone());
+        state.clone().spawn(incidents.clone());
+        (state, incidents)
+    }
+
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    #[test]
+    fn test_keys_round_trip_any_value() {
+        for value in ["2001:db8::1", "alice@corp.example", "10.0.0.1"] {
+            let key = SharedList::DisabledAccounts.key(value);
+            assert!(key.chars().all(|c| c.is_ascii_alphanumeric() || c == '.'));
+            assert_eq!(SharedList::parse_key(&key), Some((SharedList::DisabledAccounts, value.to_string())));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_changes_on_one_core_reach_the_others() {
+        let backend = Arc::new(MemoryBackend::default());
+        let (first, first_incidents) = core(&backend, "core-a");
+        first.put(SharedList::BlockedIps, "203.0.113.7", Some(3600)).await.unwrap();
+        first.put(SharedList::AllowList, "198.51.100.1", None).await.unwrap();
+        settle().await;
+
+        // A core joining later replays the current entries
+        let (second, second_incidents) = core(&backend, "core-b");
+        let mut detector = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        detector.start().await.unwrap();
+        let detector = Arc::new(detector);
+        settle().await;
+        second.attach_detector(detector.clone());
+        assert!(second_incidents.blocked_ips().contains_key("203.0.113.7"));
+        assert_eq!(second.snapshot()[&SharedList::AllowList]["198.51.100.1"].origin, "core-a");
+        let allowed = serde_json::json!({ "source_ip": "198.51.100.1", "message": "UNION SELECT * FROM users" });
+        assert!(detector.process_event(allowed.clone()).await.unwrap().is_empty());
+
+        // Unblocks and allow-list removals invalidate every cache
+        second.remove(SharedList::BlockedIps, "203.0.113.7").await.unwrap();
+        second.remove(SharedList::AllowList, "198.51.100.1").await.unwrap();
+        settle().await;
+        assert!(first_incidents.blocked_ips().is_empty());
+        assert!(!detector.process_event(allowed).await.unwrap().is_empty());
+
+        // Expired entries are dropped on arrival and purged from the bucket
+        let stale = SharedEntry { expires_at: Some(1), origin: "core-a".to_string(), updated_at: 0 };
+        backend.put(&SharedList::DisabledAccounts.key("mallory"), serde_json::to_vec(&stale).unwrap()).await.unwrap();
+        settle().await;
+        assert!(second_incidents.disabled_accounts().is_empty());
+        assert!(backend.entries.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatDetectionEngine;
+use crate::attack::techniques_of;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::{IncidentResponseEngine, IncidentSeverity};
+
+/// Event field marking synthetic events with the run that produced them
+pub const SIMULATION_FIELD: &str = "simulation_id";
+
+/// Scripted attack sequence for one ATT&CK technique
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scenario {
+    BruteForce,
+    LateralMovement,
+    Exfiltration,
+}
+
+impl Scenario {
+    pub const ALL: [Scenario; 3] = [Scenario::BruteForce, Scenario::LateralMovement, Scenario::Exfiltration];
+
+    pub fn technique(&self) -> &'static str {
+        match self {
+            Scenario::BruteForce => "T1110",
+            Scenario::LateralMovement => "T1021",
+            Scenario::Exfiltration => "T1041",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scenario::BruteForce => "brute_force",
+            Scenario::LateralMovement => "lateral_movement",
+            Scenario::Exfiltration => "exfiltration",
+        }
+    }
+
+    /// Lowest incident severity the scenario's detections should be rated
+    pub fn min_severity(&self) -> IncidentSeverity {
+        match self {
+            Scenario::BruteForce => IncidentSeverity::Medium,
+            Scenario::LateralMovement | Scenario::Exfiltration => IncidentSeverity::High,
+        }
+    }
+
+    /// Parse a comma-separated list of technique ids or scenario names, or `all`
+    pub fn parse_list(list: &str) -> SIEMResult<Vec<Scenario>> {
+        if list.trim().eq_ignore_ascii_case("all") {
+            return Ok(Scenario::ALL.to_vec());
+        }
+        list.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| {
+                Scenario::ALL
+                    .into_iter()
+                    .find(|s| s.technique().eq_ignore_ascii_case(item) || s.name().eq_ignore_ascii_case(item))
+                    .ok_or_else(|| SIEMError::Validation(format!("Unknown simulation scenario '{}'", item)))
+            })
+            .collect()
+    }
+
+    /// Synthetic events starting at `start`, tagged with `run_id`
+    pub fn events(&self, run_id: &str, start: u64) -> Vec<serde_json::Value> {
+        let tag = |mut event: serde_json::Value| {
+            event[SIMULATION_FIELD] = serde_json::json!(run_id);
+            event
+        };
+        match self {
+            // Password spraying burst against sshd: ~10 guesses a second
+            Scenario::BruteForce => (0..30u64)
+                .map(|i| {
+                    let user = ["root", "admin", "oracle", "ubuntu", "deploy"][i as usize % 5];
+                    tag(serde_json::json!({
+                        "timestamp": start + i / 10,
+                        "event_type": "login_failed",
+                        "source_ip": "198.51.100.23",
+                        "destination_ip": "10.20.0.4",
+                        "user_id": user,
+                        "host": "bastion-01",
+                        "message": format!("sshd[{}]: failed login for {} from 198.51.100.23 port {} ssh2", 4100 + i, user, 50000 + i),
+                    }))
+                })
+                .collect(),
+            // One workstation opening SMB sessions to most of its subnet
+            Scenario::LateralMovement => (0..600u64)
+                .map(|i| {
+                    tag(serde_json::json!({
+                        "timestamp": start + i / 20,
+                        "event_type": "network_connection",
+                        "source_ip": "10.20.3.17",
+                        "destination_ip": format!("10.20.{}.{}", 4 + i / 250, 1 + i % 250),
+                        "destination_port": 445,
+                        "user_id": "svc_backup",
+                        "host": "wks-0317",
+                        "message": format!("smb session setup from 10.20.3.17 to 10.20.{}.{} as svc_backup", 4 + i / 250, 1 + i % 250),
+                    }))
+                })
+                .collect(),
+            // Bulk pull of finance documents in one second, then off-host
+            Scenario::Exfiltration => (0..12u64)
+                .map(|i| {
+                    tag(serde_json::json!({
+                        "timestamp": start,
+                        "event_type": "file_download",
+                        "source_ip": "10.20.3.17",
+                        "destination_ip": "203.0.113.80",
+                        "user_id": "j.doe",
+                        "host": "fileserver-02",
+                        "bytes_transferred": 48_000_000 + i * 1_024,
+                        "message": format!("sftp get /finance/ledger-2024-q{}-{:02}.xlsx", 1 + i % 4, i),
+                    }))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Outcome of one scenario
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioResult {
+    pub scenario: Scenario,
+    pub technique: String,
+    pub events: usize,
+    /// All detections the scenario produced
+    pub detections: usize,
+    /// Detections attributed to the expected technique
+    pub matching_detections: usize,
+    /// Stored incidents carrying the technique at `min_severity` or above
+    pub incidents: usize,
+    /// Why matching detections did not become the expected incidents
+    #[serde(default)]
+    pub problems: Vec<String>,
+    pub passed: bool,
+}
+
+/// Pass/fail validation of a simulation run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationReport {
+    pub run_id: String,
+    pub started_at: DateTime<Utc>,
+    pub results: Vec<ScenarioResult>,
+}
+
+impl SimulationReport {
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|r| r.passed)
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = format!("Simulation {} ({})\n", self.run_id, self.started_at.to_rfc3339());
+        for result in &self.results {
+            out.push_str(&format!(
+                "{:<5} {:<18} {:<6} events={:<4} detections={:<4} matching={:<4} incidents={}\n",
+                if result.passed { "PASS" } else { "FAIL" },
+                result.scenario.name(),
+                result.technique,
+                result.events,
+                result.detections,
+                result.matching_detections,
+                result.incidents
+            ));
+            for problem in &result.problems {
+                out.push_str(&format!("      {}\n", problem));
+            }
+        }
+        let passed = self.results.iter().filter(|r| r.passed).count();
+        out.push_str(&format!("{}/{} scenarios passed\n", passed, self.results.len()));
+        out
+    }
+}
+
+/// Feeds scenarios through the detection pipeline and into incident creation
+pub struct Simulator<'a> {
+    engine: &'a AdvancedThreatDetectionEngine,
+    incidents: &'a IncidentResponseEngine,
+}
+
+impl<'a> Simulator<'a> {
+    /// `incidents` should not be started, so no response actions or alerts fire
+    pub fn new(engine: &'a AdvancedThreatDetectionEngine, incidents: &'a IncidentResponseEngine) -> Self {
+        Self { engine, incidents }
+    }
+
+    pub async fn run(&self, scenarios: &[Scenario]) -> SIEMResult<SimulationReport> {
+        let run_id = uuid::Uuid::new_v4().to_string();
+        let started_at = Utc::now();
+        let mut results = Vec::new();
+        for scenario in scenarios {
+            let events = scenario.events(&run_id, started_at.timestamp().max(0) as u64);
+            let (mut detections, mut matching, mut incidents) = (0, 0, 0);
+            let mut problems = Vec::new();
+            for event in &events {
+                for threat in self.engine.process_event(event.clone()).await? {
+                    detections += 1;
+                    if techniques_of(&threat).iter().any(|t| t == scenario.technique()) {
+                        matching += 1;
+                        let id = self.incidents.process_threat(threat).await?.id;
+                        match self.check_incident(&id, scenario) {
+                            Ok(()) => incidents += 1,
+                            Err(problem) => problems.push(problem),
+                        }
+                    }
+                }
+            }
+            results.push(ScenarioResult {
+                scenario: *scenario,
+                technique: scenario.technique().to_string(),
+                events: events.len(),
+                detections,
+                matching_detections: matching,
+                incidents,
+                passed: matching > 0 && problems.is_empty(),
+                problems,
+            });
+        }
+        Ok(SimulationReport { run_id, started_at, results })
+    }
+
+    /// Read the incident back from the engine and hold it to the scenario's expectations
+    fn check_incident(&self, id: &str, scenario: &Scenario) -> Result<(), String> {
+        let incident = self.incidents.get_incident(id).ok_or_else(|| format!("incident {} was not stored", id))?;
+        if !techniques_of(&incident.threat_result).iter().any(|t| t == scenario.technique()) {
+            return Err(format!("incident {} lost technique {}", id, scenario.technique()));
+        }
+        if incident.severity < scenario.min_severity() {
+            return Err(format!("incident {} rated {}, expected at least {}", id, incident.severity, scenario.min_severity()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatConfig;
+    use crate::cardinality::CardinalitySettings;
+    use crate::incident_response::{AlertConfig, SOARConfig};
+    use crate::severity::{SeverityPolicy, SeveritySettings};
+
+    #[test]
+    fn test_parse_scenarios_by_technique_or_name() {
+        assert_eq!(Scenario::parse_list("T1110, exfiltration").unwrap(), vec![Scenario::BruteForce, Scenario::Exfiltration]);
+        assert_eq!(Scenario::parse_list("all").unwrap().len(), 3);
+        assert!(Scenario::parse_list("T9999").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scenarios_fire_expected_detections_end_to_end() {
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        let incidents = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+
+        // Without internal fan-out tracking lateral movement goes unseen
+        let report = Simulator::new(&engine, &incidents).run(&Scenario::ALL).await.unwrap();
+        let outcome = |scenario| report.results.iter().find(|r| r.scenario == scenario).unwrap().passed;
+        assert!(outcome(Scenario::BruteForce), "{}", report.render());
+        assert!(outcome(Scenario::Exfiltration), "{}", report.render());
+        assert!(!outcome(Scenario::LateralMovement));
+        assert!(!report.passed());
+
+        engine.enable_cardinality(CardinalitySettings {
+            enabled: true,
+            external_only: false,
+            ..Default::default()
+        });
+        let report = Simulator::new(&engine, &incidents).run(&[Scenario::LateralMovement]).await.unwrap();
+        assert!(report.passed(), "{}", report.render());
+        assert!(incidents.get_all_incidents().len() >= 3);
+    }
+
+    #[tokio::test]
+    async fn test_detections_rated_below_the_expected_severity_fail() {
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        let incidents = IncidentResponseEngine::new(AlertConfig::disabled(), SOARConfig::disabled());
+        incidents
+            .set_severity_settings(SeveritySettings {
+                enabled: true,
+                policy: SeverityPolicy { expression: "\"low\"".to_string(), ..Default::default() },
+                ..Default::default()
+            })
+            .unwrap();
+
+        let report = Simulator::new(&engine, &incidents).run(&[Scenario::Exfiltration]).await.unwrap();
+        let result = &report.results[0];
+        assert!(result.matching_detections > 0);
+        assert_eq!(result.incidents, 0);
+        assert!(!report.passed());
+        assert!(report.render().contains("rated Low, expected at least High"), "{}", report.render());
+    }
+}
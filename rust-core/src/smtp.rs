@@ -0,0 +1,423 @@
+//! # SMTP Connector
+//!
+//! Mail delivery to a relay over just enough of SMTP (RFC 5321): EHLO,
+//! STARTTLS (RFC 3207) or implicit TLS, AUTH PLAIN when credentials are
+//! configured, one envelope per message and a plain-text UTF-8 body. Replies
+//! are checked at every step, so a refused recipient or a rejected message is
+//! an error, not a silent drop.
+//!
+//! Credentials are only ever sent over TLS: a relay that does not offer
+//! STARTTLS is an error, and `tls = "none"` is refused when a username is set.
+
+use std::time::Duration;
+use base64ct::{Base64, Encoding};
+use chrono::Utc;
+use log::info;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::incident_response::AlertConfig;
+use crate::tls::TlsClient;
+
+/// How the connection to the relay is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+    /// Upgrade with STARTTLS after EHLO; fail if the relay does not offer it
+    StartTls,
+    /// TLS from the first byte (SMTPS, usually port 465)
+    Implicit,
+    /// Plaintext, only for relays on a trusted network and without credentials
+    None,
+}
+
+/// Relay connection and sender
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmtpSettings {
+    pub server: String,
+    pub port: u16,
+    /// Empty skips AUTH
+    pub username: String,
+    pub password: String,
+    /// `alerts@example.com` or `Ultra SIEM <alerts@example.com>`
+    pub from: String,
+    /// Name announced in EHLO
+    pub helo_name: String,
+    pub timeout_seconds: u64,
+    pub tls: SmtpTls,
+    /// PEM CA bundle trusted in addition to the system roots
+    pub ca_file: String,
+}
+
+impl Default for SmtpSettings {
+    fn default() -> Self {
+        Self {
+            server: String::new(),
+            port: 25,
+            username: String::new(),
+            password: String::new(),
+            from: String::new(),
+            helo_name: "ultra-siem".to_string(),
+            timeout_seconds: 30,
+            tls: SmtpTls::StartTls,
+            ca_file: String::new(),
+        }
+    }
+}
+
+impl From<&AlertConfig> for SmtpSettings {
+    fn from(alerts: &AlertConfig) -> Self {
+        Self {
+            server: alerts.email_smtp_server.clone(),
+            port: alerts.email_smtp_port,
+            username: alerts.email_username.clone(),
+            password: alerts.email_password.clone(),
+            from: alerts.email_from.clone(),
+            tls: if alerts.email_smtp_port == 465 { SmtpTls::Implicit } else { SmtpTls::StartTls },
+            ..Self::default()
+        }
+    }
+}
+
+impl SmtpSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if self.server.is_empty() {
+            return Err(SIEMError::Validation("SMTP server is not set".to_string()));
+        }
+        check_address(envelope_address(&self.from))?;
+        if self.from.contains(['\r', '\n']) || self.helo_name.contains(char::is_whitespace) {
+            return Err(SIEMError::Validation("SMTP from and helo_name must be a single line".to_string()));
+        }
+        if self.timeout_seconds == 0 {
+            return Err(SIEMError::Validation("SMTP timeout_seconds must be positive".to_string()));
+        }
+        if self.tls == SmtpTls::None && !self.username.is_empty() {
+            return Err(SIEMError::Validation("SMTP credentials are only sent over TLS; set tls to start_tls or implicit".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Plain-text message to one or more recipients
+#[derive(Debug, Clone)]
+pub struct OutgoingMail {
+    pub to: Vec<String>,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers mail to the configured relay, one connection per message
+#[derive(Debug, Clone)]
+pub struct SmtpClient {
+    settings: SmtpSettings,
+    tls: Option<TlsClient>,
+}
+
+impl SmtpClient {
+    pub fn new(settings: SmtpSettings) -> SIEMResult<Self> {
+        settings.validate()?;
+        let tls = match settings.tls {
+            SmtpTls::None => None,
+            _ => Some(TlsClient::new(&settings.ca_file)?),
+        };
+        Ok(Self { settings, tls })
+    }
+
+    pub async fn send(&self, mail: &OutgoingMail) -> SIEMResult<()> {
+        if mail.to.is_empty() {
+            return Err(SIEMError::Validation("Mail has no recipients".to_string()));
+        }
+        for address in &mail.to {
+            check_address(address)?;
+        }
+        if mail.subject.contains(['\r', '\n']) {
+            return Err(SIEMError::Validation("Mail subject must be a single line".to_string()));
+        }
+        let timeout = Duration::from_secs(self.settings.timeout_seconds);
+        tokio::time::timeout(timeout, self.deliver(mail))
+            .await
+            .map_err(|_| SIEMError::Other(format!("SMTP delivery to {} timed out", self.settings.server)))??;
+        info!("📧 Mail \"{}\" delivered to {:?}", mail.subject, mail.to);
+        Ok(())
+    }
+
+    async fn deliver(&self, mail: &OutgoingMail) -> SIEMResult<()> {
+        let settings = &self.settings;
+        let stream = TcpStream::connect((settings.server.as_str(), settings.port)).await?;
+        let ehlo = format!("EHLO {}", settings.helo_name);
+        match (&self.tls, settings.tls) {
+            (Some(tls), SmtpTls::Implicit) => {
+                let mut session = Session::new(tls.connect(stream, &settings.server).await?);
+                session.expect(&[220]).await?;
+                session.command(&ehlo, &[250]).await?;
+                self.transact(session, mail).await
+            }
+            (Some(tls), SmtpTls::StartTls) => {
+                let mut session = Session::new(stream);
+                session.expect(&[220]).await?;
+                let extensions = session.command(&ehlo, &[250]).await?;
+                if !extensions.iter().any(|extension| extension.eq_ignore_ascii_case("STARTTLS")) {
+                    return Err(SIEMError::Auth(format!("SMTP relay {} does not offer STARTTLS", settings.server)));
+                }
+                session.command("STARTTLS", &[220]).await?;
+                let mut session = Session::new(tls.connect(session.stream.into_inner(), &settings.server).await?);
+                session.command(&ehlo, &[250]).await?;
+                self.transact(session, mail).await
+            }
+            _ => {
+                let mut session = Session::new(stream);
+                session.expect(&[220]).await?;
+                session.command(&ehlo, &[250]).await?;
+                self.transact(session, mail).await
+            }
+        }
+    }
+
+    /// Authenticate if configured, then send one envelope on an established session
+    async fn transact<S: AsyncRead + AsyncWrite + Unpin>(&self, mut session: Session<S>, mail: &OutgoingMail) -> SIEMResult<()> {
+        let settings = &self.settings;
+        if !settings.username.is_empty() {
+            if settings.tls == SmtpTls::None {
+                return Err(SIEMError::Auth("Refusing to send SMTP credentials without TLS".to_string()));
+            }
+            let credentials = format!("\0{}\0{}", settings.username, settings.password);
+            session.command(&format!("AUTH PLAIN {}", Base64::encode_string(credentials.as_bytes())), &[235]).await?;
+        }
+        session.command(&format!("MAIL FROM:<{}>", envelope_address(&settings.from)), &[250]).await?;
+        for address in &mail.to {
+            session.command(&format!("RCPT TO:<{}>", address), &[250, 251]).await?;
+        }
+        session.command("DATA", &[354]).await?;
+        session.stream.write_all(self.message(mail).as_bytes()).await?;
+        session.command(".", &[250]).await?;
+        // The message is accepted; a failed QUIT changes nothing
+        let _ = session.command("QUIT", &[221]).await;
+        Ok(())
+    }
+
+    /// Headers and dot-stuffed CRLF body, without the terminating dot
+    fn message(&self, mail: &OutgoingMail) -> String {
+        let now = Utc::now();
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\nMIME-Version: 1.0\r\n\
+             Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+            self.settings.from,
+            mail.to.join(", "),
+            encode_header(&mail.subject),
+            now.to_rfc2822(),
+            uuid::Uuid::new_v4(),
+            self.settings.helo_name,
+        );
+        for line in mail.body.lines() {
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message
+    }
+}
+
+struct Session<S> {
+    stream: BufStream<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Session<S> {
+    fn new(stream: S) -> Self {
+        Self { stream: BufStream::new(stream) }
+    }
+
+    /// Send one command; returns the text of each reply line
+    async fn command(&mut self, line: &str, accepted: &[u16]) -> SIEMResult<Vec<String>> {
+        self.stream.write_all(format!("{}\r\n", line).as_bytes()).await?;
+        self.stream.flush().await?;
+        self.expect(accepted).await.map_err(|e| match line.starts_with("AUTH ") {
+            true => SIEMError::Auth(format!("SMTP authentication failed: {}", e)),
+            false => e,
+        })
+    }
+
+    /// Read a possibly multi-line reply and check its code
+    async fn expect(&mut self, accepted: &[u16]) -> SIEMResult<Vec<String>> {
+        let mut reply = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                return Err(SIEMError::Other("SMTP server closed the connection".to_string()));
+            }
+            let code: u16 = line.get(..3).and_then(|code| code.parse().ok()).ok_or_else(|| {
+                SIEMError::Other(format!("Malformed SMTP reply: {}", line.trim_end()))
+            })?;
+            reply.push(line.get(4..).unwrap_or_default().trim_end().to_string());
+            if line.as_bytes().get(3) == Some(&b'-') {
+                continue;
+            }
+            return match accepted.contains(&code) {
+                true => Ok(reply),
+                false => Err(SIEMError::Other(format!("SMTP server refused: {}", line.trim_end()))),
+            };
+        }
+    }
+}
+
+/// Address part of `Display Name <address>`
+fn envelope_address(from: &str) -> &str {
+    match (from.rfind('<'), from.ends_with('>')) {
+        (Some(start), true) => &from[start + 1..from.len() - 1],
+        _ => from,
+    }
+}
+
+fn check_address(address: &str) -> SIEMResult<()> {
+    let valid = address.split_once('@').is_some_and(|(local, domain)| !local.is_empty() && !domain.is_empty())
+        && !address.contains(|c: char| c.is_whitespace() || c.is_control() || matches!(c, '<' | '>' | ','));
+    match valid {
+        true => Ok(()),
+        false => Err(SIEMError::Validation(format!("Invalid mail address '{}'", address))),
+    }
+}
+
+/// RFC 2047 encoded-word for non-ASCII header text
+fn encode_header(text: &str) -> String {
+    match text.is_ascii() {
+        true => text.to_string(),
+        false => format!("=?UTF-8?B?{}?=", Base64::encode_string(text.as_bytes())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::testing;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    /// Answer one command per reply, capturing DATA up to the terminating dot
+    async fn script<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut BufStream<S>, replies: &[&str], received: &mut String) {
+        for reply in replies {
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            received.push_str(&line);
+            if line == "DATA\r\n" {
+                stream.write_all(b"354 go ahead\r\n").await.unwrap();
+                stream.flush().await.unwrap();
+                loop {
+                    let mut data = String::new();
+                    stream.read_line(&mut data).await.unwrap();
+                    received.push_str(&data);
+                    if data == ".\r\n" {
+                        break;
+                    }
+                }
+            }
+            stream.write_all(reply.as_bytes()).await.unwrap();
+            stream.flush().await.unwrap();
+        }
+    }
+
+    /// Scripted relay; `starttls` upgrades after the plaintext replies. Returns everything the client sent
+    async fn relay(
+        plaintext: &'static [&'static str],
+        starttls: Option<&'static [&'static str]>,
+    ) -> (u16, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut stream = BufStream::new(socket);
+            let mut received = String::new();
+            stream.write_all(b"220 relay.example.com ESMTP\r\n").await.unwrap();
+            stream.flush().await.unwrap();
+            script(&mut stream, plaintext, &mut received).await;
+            match starttls {
+                Some(replies) => {
+                    let mut stream = BufStream::new(testing::acceptor().accept(stream.into_inner()).await.unwrap());
+                    received.push_str("-- tls --\r\n");
+                    script(&mut stream, replies, &mut received).await;
+                    let _ = stream.read_to_string(&mut received).await;
+                }
+                None => {
+                    let _ = stream.read_to_string(&mut received).await;
+                }
+            }
+            received
+        });
+        (port, handle)
+    }
+
+    fn client(port: u16, username: &str, tls: SmtpTls) -> SmtpClient {
+        SmtpClient::new(SmtpSettings {
+            server: "127.0.0.1".to_string(),
+            port,
+            username: username.to_string(),
+            password: "s3cret".to_string(),
+            from: "Ultra SIEM <siem@example.com>".to_string(),
+            tls,
+            ca_file: testing::CA_FILE.to_string(),
+            ..SmtpSettings::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_delivers_with_auth_over_starttls_and_dot_stuffing() {
+        let (port, relay) = relay(
+            &["250-relay.example.com\r\n250 STARTTLS\r\n", "220 ready\r\n"],
+            Some(&[
+                "250-relay.example.com\r\n250 AUTH PLAIN\r\n",
+                "235 ok\r\n",
+                "250 ok\r\n",
+                "250 ok\r\n",
+                "250 queued\r\n",
+                "221 bye\r\n",
+            ]),
+        )
+        .await;
+        let mail = OutgoingMail {
+            to: vec!["soc@acme.example".to_string()],
+            subject: "Daily digest — acme".to_string(),
+            body: "3 open incidents\n.hidden line".to_string(),
+        };
+        client(port, "siem", SmtpTls::StartTls).send(&mail).await.unwrap();
+
+        let sent = relay.await.unwrap();
+        let (plaintext, encrypted) = sent.split_once("-- tls --\r\n").unwrap();
+        assert_eq!(plaintext, "EHLO ultra-siem\r\nSTARTTLS\r\n");
+        assert!(encrypted.contains(&format!("AUTH PLAIN {}\r\n", Base64::encode_string(b"\0siem\0s3cret"))));
+        assert!(encrypted.contains("MAIL FROM:<siem@example.com>\r\n"));
+        assert!(encrypted.contains("RCPT TO:<soc@acme.example>\r\n"));
+        assert!(encrypted.contains("Subject: =?UTF-8?B?"));
+        assert!(encrypted.contains("\r\n3 open incidents\r\n..hidden line\r\n.\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_credentials_are_never_sent_without_tls() {
+        let (port, relay) = relay(&["250-relay.example.com\r\n250 AUTH PLAIN\r\n"], None).await;
+        let mail = OutgoingMail { to: vec!["soc@acme.example".to_string()], subject: "x".to_string(), body: String::new() };
+        let error = client(port, "siem", SmtpTls::StartTls).send(&mail).await.unwrap_err();
+        assert!(error.to_string().contains("does not offer STARTTLS"));
+        assert!(!relay.await.unwrap().contains("AUTH"));
+
+        let plaintext = SmtpSettings { server: "relay".to_string(), from: "siem@example.com".to_string(), ..SmtpSettings::default() };
+        assert!(SmtpSettings { username: "siem".to_string(), tls: SmtpTls::None, ..plaintext.clone() }.validate().is_err());
+        assert!(SmtpSettings { tls: SmtpTls::None, ..plaintext }.validate().is_ok());
+        let alerts = AlertConfig { email_smtp_port: 465, ..AlertConfig::disabled() };
+        assert_eq!(SmtpSettings::from(&alerts).tls, SmtpTls::Implicit);
+    }
+
+    #[tokio::test]
+    async fn test_refused_recipient_and_bad_addresses_fail() {
+        let (port, _relay) = relay(&["250 relay.example.com\r\n", "250 ok\r\n", "550 no such user\r\n"], None).await;
+        let mail = OutgoingMail { to: vec!["nobody@acme.example".to_string()], subject: "x".to_string(), body: String::new() };
+        let error = client(port, "", SmtpTls::None).send(&mail).await.unwrap_err();
+        assert!(error.to_string().contains("550 no such user"));
+
+        let injected = OutgoingMail { to: vec!["a@b.example>\r\nRCPT TO:<x@y".to_string()], ..mail.clone() };
+        assert!(client(port, "", SmtpTls::None).send(&injected).await.is_err());
+        assert!(SmtpSettings { from: "not-an-address".to_string(), server: "relay".to_string(), ..SmtpSettings::default() }.validate().is_err());
+    }
+}
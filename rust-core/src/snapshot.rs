@@ -0,0 +1,195 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::advanced_threat_detection::{AdvancedThreatDetectionEngine, BehavioralState, CorrelationState, EntityRiskState};
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// Snapshot layout version; snapshots written by another version are not restored
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// Periodic engine state snapshot settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotSettings {
+    pub enabled: bool,
+    pub path: String,
+    pub interval_seconds: u64,
+    pub restore_on_startup: bool,
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "data/state/engine-state.snapshot".to_string(),
+            interval_seconds: 300,
+            restore_on_startup: true,
+        }
+    }
+}
+
+/// Detection state that would otherwise be lost on restart
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EngineState {
+    pub behavioral: BehavioralState,
+    pub entity_risk: EntityRiskState,
+    pub correlation: CorrelationState,
+}
+
+/// First line of a snapshot file; the serialized state follows it
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotHeader {
+    version: u32,
+    created_at: u64,
+    length: u64,
+    sha256: String,
+}
+
+/// Versioned, checksummed engine state on disk
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    path: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self { path: path.as_ref().to_path_buf() }
+    }
+
+    /// Write `state`, replacing the previous snapshot atomically; returns the bytes written
+    pub fn save(&self, state: &EngineState) -> SIEMResult<u64> {
+        let body = serde_json::to_vec(state)?;
+        let header = SnapshotHeader {
+            version: SNAPSHOT_FORMAT_VERSION,
+            created_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            length: body.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(&body)),
+        };
+        let mut content = serde_json::to_vec(&header)?;
+        content.push(b'\n');
+        content.extend_from_slice(&body);
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let partial = self.path.with_extension("partial");
+        fs::write(&partial, &content)?;
+        fs::rename(&partial, &self.path)?;
+        Ok(content.len() as u64)
+    }
+
+    /// Read the last snapshot; `None` when none has been written yet
+    pub fn load(&self) -> SIEMResult<Option<EngineState>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read(&self.path)?;
+        let invalid = |reason: &str| SIEMError::Validation(format!("Snapshot {}: {}", self.path.display(), reason));
+
+        let split = content.iter().position(|b| *b == b'\n').ok_or_else(|| invalid("missing header"))?;
+        let header: SnapshotHeader = serde_json::from_slice(&content[..split]).map_err(|_| invalid("unreadable header"))?;
+        if header.version != SNAPSHOT_FORMAT_VERSION {
+            return Err(invalid(&format!(
+                "format version {} is not supported (expected {})",
+                header.version, SNAPSHOT_FORMAT_VERSION
+            )));
+        }
+        let body = &content[split + 1..];
+        if body.len() as u64 != header.length || format!("{:x}", Sha256::digest(body)) != header.sha256 {
+            return Err(invalid("checksum mismatch"));
+        }
+        Ok(Some(serde_json::from_slice(body)?))
+    }
+}
+
+/// Restore `engine` from the configured snapshot; returns whether state was restored
+pub fn restore_engine(engine: &AdvancedThreatDetectionEngine, settings: &SnapshotSettings) -> SIEMResult<bool> {
+    if !settings.enabled || !settings.restore_on_startup {
+        return Ok(false);
+    }
+    match SnapshotStore::new(&settings.path).load()? {
+        Some(state) => {
+            engine.restore_state(state);
+            info!("♻️ Restored engine state from {}", settings.path);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Snapshot `engine` every `interval_seconds` until the task is aborted
+pub fn spawn_snapshot_task(engine: Arc<AdvancedThreatDetectionEngine>, settings: SnapshotSettings) -> Option<tokio::task::JoinHandle<()>> {
+    if !settings.enabled || settings.interval_seconds == 0 {
+        return None;
+    }
+    let store = SnapshotStore::new(&settings.path);
+    Some(tokio::spawn(async move {
+        let period = Duration::from_secs(settings.interval_seconds);
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            interval.tick().await;
+            let state = engine.export_state();
+            let store = store.clone();
+            match tokio::task::spawn_blocking(move || store.save(&state)).await {
+                Ok(Ok(bytes)) => info!("💾 Engine state snapshot written ({} bytes)", bytes),
+                Ok(Err(e)) => error!("❌ Engine state snapshot failed: {}", e),
+                Err(e) => warn!("⚠️ Engine state snapshot task panicked: {}", e),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatConfig;
+
+    fn snapshot_path() -> PathBuf {
+        std::env::temp_dir().join(format!("engine-state-{}", uuid::Uuid::new_v4())).join("engine.snapshot")
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip_restores_state() {
+        let mut engine = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        engine.start().await.unwrap();
+        for i in 0..5u64 {
+            let event = serde_json::json!({
+                "user_id": "alice", "source_ip": "198.51.100.7", "action": "login",
+                "timestamp": 1_700_000_000 + i, "message": "login ok"
+            });
+            engine.process_event(event).await.unwrap();
+        }
+        let before = serde_json::to_value(engine.export_state()).unwrap();
+
+        let path = snapshot_path();
+        let store = SnapshotStore::new(&path);
+        store.save(&engine.export_state()).unwrap();
+
+        let restored = AdvancedThreatDetectionEngine::new(AdvancedThreatConfig::default());
+        restored.restore_state(store.load().unwrap().unwrap());
+        assert_eq!(serde_json::to_value(restored.export_state()).unwrap(), before);
+        assert!(before["behavioral"]["user_profiles"]["alice"].is_object());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_corrupted_snapshot_is_rejected() {
+        let path = snapshot_path();
+        let store = SnapshotStore::new(&path);
+        assert!(store.load().unwrap().is_none());
+
+        store.save(&EngineState::default()).unwrap();
+        let mut content = fs::read(&path).unwrap();
+        let last = content.len() - 2;
+        content[last] ^= 0x01;
+        fs::write(&path, content).unwrap();
+
+        assert!(matches!(store.load(), Err(SIEMError::Validation(_))));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,372 @@
+//! # NATS Subject Hierarchy
+//!
+//! Every subject the core publishes on lives under `ultra_siem.` and has a
+//! versioned payload schema. Publish through `SubjectPublisher`, which checks
+//! the payload against the subject's schema and tags the message with
+//! `Ultra-Siem-Schema` / `Ultra-Siem-Schema-Version` headers.
+//!
+//! | Subject                          | Schema                    | Payload                              |
+//! |----------------------------------|---------------------------|--------------------------------------|
+//! | `ultra_siem.threats`             | `bridge.threat_event` v1  | `BridgeThreatEvent`, Go bridge input |
+//! | `ultra_siem.events`              | `bridge.event` v1         | Collector events, Go bridge and core input |
+//! | `ultra_siem.system`              | `bridge.system_event` v1  | `BridgeSystemEvent`, Go bridge input |
+//! | `ultra_siem.platform.<os>.events`| `platform.event` v1       | Collector events per OS family       |
+//! | `ultra_siem.supervisor.status`   | `supervisor.status` v1    | Service supervisor heartbeat         |
+//! | `ultra_siem.sites.<site>.forward`| `site.forward` v1         | `ForwardEnvelope` from an edge site  |
+//! | `ultra_siem.agents.<host>.commands`| `agent.command` v1      | `SignedAgentCommand`, request/reply  |
+//! | `ultra_siem.scaling.hints`       | `scaling.hint` v1         | Queue lag and scale up/down hint     |
+//!
+//! Subscribers may use NATS wildcards (`ultra_siem.platform.*.events`,
+//! `ultra_siem.>`). The flat `threats.*`, `platform.*` and `supervisor.status`
+//! subjects of the standalone demo binaries are not part of this contract.
+//!
+//! `fixtures/bridge/v1/subjects.json` maps each bridge subject to a golden
+//! payload; the Go bridge tests decode the same files into the struct its
+//! handler for that subject expects.
+//!
+//! Validation fails fast in debug builds (`SchemaMode::Strict`); release
+//! builds log and still publish (`SchemaMode::Warn`).
+
+use async_nats::Client;
+use log::warn;
+use serde::Serialize;
+
+use crate::bridge_contract::{contract_headers, SUBJECT_EVENTS, SUBJECT_SYSTEM, SUBJECT_THREATS};
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// Per-OS collector events; `*` is the OS family, e.g. `linux`
+pub const SUBJECT_PLATFORM_EVENTS: &str = "ultra_siem.platform.*.events";
+pub const SUBJECT_SUPERVISOR_STATUS: &str = "ultra_siem.supervisor.status";
+
+/// Site-to-site forwarding; `*` is the sending site id
+pub const SUBJECT_SITE_FORWARD: &str = "ultra_siem.sites.*.forward";
+
+/// Signed response actions for the agent of one host; `*` is the host id
+pub const SUBJECT_AGENT_COMMANDS: &str = "ultra_siem.agents.*.commands";
+
+/// Queue lag and scale up/down hints for the supervisor or an autoscaler adapter
+pub const SUBJECT_SCALING_HINTS: &str = "ultra_siem.scaling.hints";
+
+/// Header naming the payload schema of a message
+pub const HEADER_SCHEMA: &str = "Ultra-Siem-Schema";
+pub const HEADER_SCHEMA_VERSION: &str = "Ultra-Siem-Schema-Version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Object,
+    Array,
+}
+
+impl FieldType {
+    fn matches(&self, value: &serde_json::Value) -> bool {
+        match self {
+            FieldType::String => value.is_string(),
+            FieldType::Integer => value.is_i64() || value.is_u64(),
+            FieldType::Number => value.is_number(),
+            FieldType::Boolean => value.is_boolean(),
+            FieldType::Object => value.is_object(),
+            FieldType::Array => value.is_array(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct FieldSpec {
+    pub name: &'static str,
+    pub field_type: FieldType,
+    pub required: bool,
+}
+
+const fn required(name: &'static str, field_type: FieldType) -> FieldSpec {
+    FieldSpec { name, field_type, required: true }
+}
+
+const fn optional(name: &'static str, field_type: FieldType) -> FieldSpec {
+    FieldSpec { name, field_type, required: false }
+}
+
+/// Payload schema of one subject pattern
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SubjectSchema {
+    pub subject: &'static str,
+    pub name: &'static str,
+    pub version: u32,
+    pub description: &'static str,
+    pub fields: &'static [FieldSpec],
+    /// Whether fields beyond `fields` are accepted
+    pub additional_fields: bool,
+}
+
+pub const SCHEMAS: &[SubjectSchema] = &[
+    SubjectSchema {
+        subject: SUBJECT_THREATS,
+        name: "bridge.threat_event",
+        version: 1,
+        description: "Detected threat for the Go bridge and ClickHouse `threats` table",
+        fields: &[
+            required("timestamp", FieldType::Integer),
+            required("source_ip", FieldType::String),
+            required("threat_type", FieldType::String),
+            required("payload", FieldType::String),
+            required("severity", FieldType::Integer),
+            required("confidence", FieldType::Number),
+        ],
+        additional_fields: false,
+    },
+    SubjectSchema {
+        subject: SUBJECT_EVENTS,
+        name: "bridge.event",
+        version: 1,
+        description: "Collector event in the Go `UltraSIEMEvent` shape",
+        fields: &[
+            required("id", FieldType::String),
+            required("timestamp", FieldType::Integer),
+            required("event_type", FieldType::String),
+            optional("source_ip", FieldType::String),
+            optional("severity", FieldType::Integer),
+            optional("message", FieldType::String),
+            optional("metadata", FieldType::Object),
+        ],
+        additional_fields: true,
+    },
+    SubjectSchema {
+        subject: SUBJECT_SYSTEM,
+        name: "bridge.system_event",
+        version: 1,
+        description: "System event for the Go bridge",
+        fields: &[
+            required("timestamp", FieldType::Integer),
+            required("event_type", FieldType::String),
+            required("source", FieldType::String),
+            required("message", FieldType::String),
+            required("severity", FieldType::Integer),
+        ],
+        additional_fields: false,
+    },
+    SubjectSchema {
+        subject: SUBJECT_PLATFORM_EVENTS,
+        name: "platform.event",
+        version: 1,
+        description: "Collector event from one OS family",
+        fields: &[
+            required("timestamp", FieldType::Integer),
+            required("platform", FieldType::String),
+            required("event_type", FieldType::String),
+            optional("severity", FieldType::String),
+            optional("process_info", FieldType::Object),
+            optional("network_connection", FieldType::Object),
+        ],
+        additional_fields: true,
+    },
+    SubjectSchema {
+        subject: SUBJECT_SUPERVISOR_STATUS,
+        name: "supervisor.status",
+        version: 1,
+        description: "Service supervisor heartbeat",
+        fields: &[
+            required("timestamp", FieldType::Integer),
+            required("total_services", FieldType::Integer),
+            required("running_services", FieldType::Integer),
+            required("failed_services", FieldType::Integer),
+            required("total_restarts", FieldType::Integer),
+            optional("uptime_ns", FieldType::Integer),
+        ],
+        additional_fields: false,
+    },
+    SubjectSchema {
+        subject: SUBJECT_SITE_FORWARD,
+        name: "site.forward",
+        version: 1,
+        description: "Detection or aggregate forwarded by an edge site",
+        fields: &[
+            required("id", FieldType::String),
+            required("origin_site", FieldType::String),
+            required("path", FieldType::Array),
+            required("created_at", FieldType::String),
+            required("kind", FieldType::String),
+            required("payload", FieldType::Object),
+        ],
+        additional_fields: false,
+    },
+    SubjectSchema {
+        subject: SUBJECT_AGENT_COMMANDS,
+        name: "agent.command",
+        version: 1,
+        description: "Signed response action for a remote agent; the reply is its execution result",
+        fields: &[
+            required("command", FieldType::String),
+            required("signature", FieldType::String),
+        ],
+        additional_fields: false,
+    },
+    SubjectSchema {
+        subject: SUBJECT_SCALING_HINTS,
+        name: "scaling.hint",
+        version: 1,
+        description: "Per-stage queue depth, lag and rate with the derived scaling hint of one core instance",
+        fields: &[
+            required("timestamp", FieldType::Integer),
+            required("instance", FieldType::String),
+            required("hint", FieldType::String),
+            required("current_instances", FieldType::Integer),
+            required("desired_instances", FieldType::Integer),
+            required("lag_seconds", FieldType::Number),
+            required("reason", FieldType::String),
+            required("stages", FieldType::Array),
+        ],
+        additional_fields: false,
+    },
+];
+
+/// Whether a concrete subject matches a pattern with `*` and trailing `>` wildcards
+pub fn subject_matches(pattern: &str, subject: &str) -> bool {
+    let mut tokens = subject.split('.');
+    for expected in pattern.split('.') {
+        match (expected, tokens.next()) {
+            (">", Some(_)) => return true,
+            ("*", Some(token)) if !token.is_empty() => {}
+            (expected, Some(token)) if expected == token => {}
+            _ => return false,
+        }
+    }
+    tokens.next().is_none()
+}
+
+pub fn schema_for(subject: &str) -> Option<&'static SubjectSchema> {
+    SCHEMAS.iter().find(|schema| subject_matches(schema.subject, subject))
+}
+
+/// Check a payload against the schema of `subject`
+pub fn validate(subject: &str, payload: &serde_json::Value) -> SIEMResult<&'static SubjectSchema> {
+    let schema = schema_for(subject).ok_or_else(|| SIEMError::Validation(format!("No schema for subject '{}'", subject)))?;
+    let invalid = |reason: String| SIEMError::Validation(format!("{} v{} on '{}': {}", schema.name, schema.version, subject, reason));
+    let object = payload.as_object().ok_or_else(|| invalid("payload is not an object".to_string()))?;
+    for field in schema.fields {
+        match object.get(field.name) {
+            Some(value) if !field.field_type.matches(value) => {
+                return Err(invalid(format!("field '{}' is not of type {:?}", field.name, field.field_type)));
+            }
+            None if field.required => return Err(invalid(format!("missing field '{}'", field.name))),
+            _ => {}
+        }
+    }
+    if !schema.additional_fields {
+        if let Some(unknown) = object.keys().find(|key| !schema.fields.iter().any(|f| f.name == key.as_str())) {
+            return Err(invalid(format!("unknown field '{}'", unknown)));
+        }
+    }
+    Ok(schema)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaMode {
+    /// Refuse to publish payloads that do not match their schema
+    Strict,
+    /// Log mismatches and publish anyway
+    Warn,
+}
+
+impl Default for SchemaMode {
+    fn default() -> Self {
+        if cfg!(debug_assertions) {
+            SchemaMode::Strict
+        } else {
+            SchemaMode::Warn
+        }
+    }
+}
+
+/// Publishes JSON payloads after validating them against their subject schema
+#[derive(Clone)]
+pub struct SubjectPublisher {
+    client: Client,
+    mode: SchemaMode,
+}
+
+impl SubjectPublisher {
+    pub fn new(client: Client, mode: SchemaMode) -> Self {
+        Self { client, mode }
+    }
+
+    pub async fn publish<T: Serialize>(&self, subject: &str, payload: &T) -> SIEMResult<()> {
+        let bytes = serde_json::to_vec(payload)?;
+        let mut headers = contract_headers();
+        match validate(subject, &serde_json::from_slice(&bytes)?) {
+            Ok(schema) => {
+                headers.insert(HEADER_SCHEMA, schema.name);
+                headers.insert(HEADER_SCHEMA_VERSION, schema.version.to_string().as_str());
+            }
+            Err(e) if self.mode == SchemaMode::Strict => return Err(e),
+            Err(e) => warn!("⚠️ Publishing unvalidated payload: {}", e),
+        }
+        self.client.publish_with_headers(subject.to_string(), headers, bytes.into()).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge_contract::BridgeThreatEvent;
+
+    #[test]
+    fn test_wildcard_subjects_resolve_to_schemas() {
+        assert!(subject_matches("ultra_siem.platform.*.events", "ultra_siem.platform.linux.events"));
+        assert!(!subject_matches("ultra_siem.platform.*.events", "ultra_siem.platform.events"));
+        assert!(subject_matches("ultra_siem.>", "ultra_siem.supervisor.status"));
+        assert!(!subject_matches("ultra_siem.threats", "ultra_siem.threats.extra"));
+        assert_eq!(schema_for("ultra_siem.platform.windows.events").unwrap().name, "platform.event");
+        assert!(schema_for("threats.detected").is_none());
+    }
+
+    #[test]
+    fn test_payloads_are_checked_against_subject_schema() {
+        let threat = BridgeThreatEvent {
+            timestamp: 1700000000,
+            source_ip: "203.0.113.7".to_string(),
+            threat_type: "SQLInjection".to_string(),
+            payload: "UNION SELECT".to_string(),
+            severity: 3,
+            confidence: 0.9,
+        };
+        let payload = serde_json::to_value(&threat).unwrap();
+        assert_eq!(validate(SUBJECT_THREATS, &payload).unwrap().version, 1);
+        // A threat on the system event subject has the wrong shape
+        assert!(validate(SUBJECT_SYSTEM, &payload).is_err());
+
+        let mut extra = payload.clone();
+        extra["tenant"] = serde_json::json!("acme");
+        assert!(validate(SUBJECT_THREATS, &extra).is_err());
+        let mut mistyped = payload;
+        mistyped["severity"] = serde_json::json!("High");
+        assert!(validate(SUBJECT_THREATS, &mistyped).unwrap_err().to_string().contains("severity"));
+
+        let status = serde_json::json!({
+            "timestamp": 1700000000, "total_services": 4, "running_services": 3,
+            "failed_services": 1, "total_restarts": 2
+        });
+        assert!(validate(SUBJECT_SUPERVISOR_STATUS, &status).is_ok());
+        assert!(validate("ultra_siem.unknown", &status).is_err());
+        assert_eq!(SchemaMode::default() == SchemaMode::Strict, cfg!(debug_assertions));
+    }
+
+    #[test]
+    fn test_subject_fixtures_match_their_schemas() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/bridge/v1");
+        let fixtures: std::collections::BTreeMap<String, String> =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("subjects.json")).unwrap()).unwrap();
+        let load = |name: &str| -> serde_json::Value { serde_json::from_str(&std::fs::read_to_string(dir.join(name)).unwrap()).unwrap() };
+
+        assert_eq!(fixtures.keys().map(String::as_str).collect::<Vec<_>>(), [SUBJECT_EVENTS, SUBJECT_SYSTEM, SUBJECT_THREATS]);
+        for (subject, fixture) in &fixtures {
+            assert!(validate(subject, &load(fixture)).is_ok(), "{} does not match the schema of {}", fixture, subject);
+        }
+        // System events are not collector events; they have their own subject
+        assert!(validate(SUBJECT_EVENTS, &load(&fixtures[SUBJECT_SYSTEM])).is_err());
+    }
+}
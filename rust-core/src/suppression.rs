@@ -0,0 +1,416 @@
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::timezone::Zone;
+
+/// Analyst suppression ("snooze") rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SuppressionSettings {
+    pub enabled: bool,
+    pub store_path: String,
+    /// Longest snooze an analyst may set; longer blind spots belong in the allow list
+    pub max_days: u32,
+    /// Expired rules are kept this long so renewals and the report can see them
+    pub keep_expired_days: u32,
+    /// A scope snoozed this many times in a row is reported as a standing blind spot
+    pub renewal_warn: u32,
+    /// How often the report is produced; 0 disables it
+    pub report_interval_hours: u64,
+    /// Directory the report is written to; empty only logs it
+    pub report_dir: String,
+}
+
+impl Default for SuppressionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            store_path: "data/suppressions.json".to_string(),
+            max_days: 30,
+            keep_expired_days: 90,
+            renewal_warn: 3,
+            report_interval_hours: 24 * 7,
+            report_dir: String::new(),
+        }
+    }
+}
+
+/// Where a rule created from an incident applies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuppressionScope {
+    /// The incident's signature on the incident's host
+    #[default]
+    Host,
+    /// The incident's signature for the incident's user
+    User,
+    /// The incident's signature everywhere
+    Everywhere,
+}
+
+/// Snooze request raised from an incident
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuppressionRequest {
+    #[serde(default)]
+    pub scope: SuppressionScope,
+    pub days: u32,
+    /// Filled from the authenticated caller over the REST API; a client-sent value is ignored
+    #[serde(default)]
+    pub owner: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SuppressionRule {
+    pub id: String,
+    /// Signature id, first matched signature or detection method of the threat
+    pub signature: String,
+    /// Hostname or IP address; any host when empty
+    pub host: String,
+    /// Any user when empty
+    pub user: String,
+    pub owner: String,
+    pub reason: String,
+    /// Incident the rule was created from
+    pub incident_id: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Earlier rules for the same signature, host and user this one follows on from
+    pub renewals: u32,
+    pub hits: u64,
+    pub last_hit: Option<DateTime<Utc>>,
+}
+
+impl SuppressionRule {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        now < self.expires_at
+    }
+
+    pub fn matches(&self, threat: &AdvancedThreatResult) -> bool {
+        signature_key(threat) == self.signature
+            && (self.host.is_empty() || host_keys(threat).any(|host| host == self.host))
+            && (self.user.is_empty() || threat.user_id == self.user)
+    }
+
+    fn same_scope(&self, other: &SuppressionRule) -> bool {
+        self.signature == other.signature && self.host == other.host && self.user == other.user
+    }
+
+    fn describe(&self) -> String {
+        let mut scope = format!("'{}'", self.signature);
+        if !self.host.is_empty() {
+            let _ = write!(scope, " on {}", self.host);
+        }
+        if !self.user.is_empty() {
+            let _ = write!(scope, " for {}", self.user);
+        }
+        scope
+    }
+}
+
+/// What a suppression rule keys on: the signature id, the first signature, or the detection method
+pub fn signature_key(threat: &AdvancedThreatResult) -> &str {
+    threat
+        .details
+        .get("signature_id")
+        .or_else(|| threat.signatures.first())
+        .unwrap_or(&threat.detection_method)
+}
+
+fn host_keys(threat: &AdvancedThreatResult) -> impl Iterator<Item = &str> {
+    ["hostname", "host"]
+        .into_iter()
+        .filter_map(|key| threat.details.get(key).map(String::as_str))
+        .chain([threat.source_ip.as_str(), threat.destination_ip.as_str()])
+        .filter(|host| !host.is_empty())
+}
+
+/// Suppression rules shared by the detection path and the REST API
+#[derive(Debug)]
+pub struct SuppressionList {
+    settings: SuppressionSettings,
+    rules: RwLock<Vec<SuppressionRule>>,
+    path: PathBuf,
+}
+
+impl SuppressionList {
+    pub fn open(settings: SuppressionSettings) -> SIEMResult<Self> {
+        let path = PathBuf::from(&settings.store_path);
+        let rules = match std::fs::read(&path) {
+            Ok(raw) => serde_json::from_slice(&raw)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { settings, rules: RwLock::new(rules), path })
+    }
+
+    pub fn settings(&self) -> &SuppressionSettings {
+        &self.settings
+    }
+
+    /// Snooze the signature of `threat` as asked in `request`
+    pub fn create(&self, threat: &AdvancedThreatResult, incident_id: &str, request: &SuppressionRequest, now: DateTime<Utc>) -> SIEMResult<SuppressionRule> {
+        let host = match request.scope {
+            SuppressionScope::Host => host_keys(threat).next().unwrap_or_default().to_string(),
+            _ => String::new(),
+        };
+        let user = match request.scope {
+            SuppressionScope::User => threat.user_id.clone(),
+            _ => String::new(),
+        };
+        if (request.scope == SuppressionScope::Host && host.is_empty()) || (request.scope == SuppressionScope::User && user.is_empty()) {
+            return Err(SIEMError::Validation(format!("Incident {} has no {:?} to scope the suppression to", incident_id, request.scope)));
+        }
+        self.create_scoped(signature_key(threat), &host, &user, incident_id, request, now)
+    }
+
+    /// Snooze `signature` on `host` and for `user`, each any when empty; `request.scope` is not consulted
+    pub fn create_scoped(
+        &self,
+        signature: &str,
+        host: &str,
+        user: &str,
+        incident_id: &str,
+        request: &SuppressionRequest,
+        now: DateTime<Utc>,
+    ) -> SIEMResult<SuppressionRule> {
+        if request.owner.trim().is_empty() || request.reason.trim().is_empty() {
+            return Err(SIEMError::Validation("A suppression needs an owner and a reason".to_string()));
+        }
+        if request.days == 0 || request.days > self.settings.max_days {
+            return Err(SIEMError::Validation(format!("Suppressions last 1 to {} days", self.settings.max_days)));
+        }
+
+        let mut rule = SuppressionRule {
+            id: Uuid::new_v4().to_string(),
+            signature: signature.to_string(),
+            host: host.to_string(),
+            user: user.to_string(),
+            owner: request.owner.clone(),
+            reason: request.reason.clone(),
+            incident_id: incident_id.to_string(),
+            created_at: now,
+            expires_at: now + Duration::days(request.days.into()),
+            renewals: 0,
+            hits: 0,
+            last_hit: None,
+        };
+        let mut rules = self.rules.write().unwrap();
+        if let Some(previous) = rules.iter().filter(|r| r.same_scope(&rule)).max_by_key(|r| r.created_at) {
+            rule.renewals = previous.renewals + 1;
+        }
+        rules.push(rule.clone());
+        drop(rules);
+        self.save()?;
+        info!("🔕 {} snoozed {} until {}: {}", rule.owner, rule.describe(), rule.expires_at.format("%Y-%m-%d %H:%M"), rule.reason);
+        Ok(rule)
+    }
+
+    /// Whether an active rule covers `threat`; counts the hit
+    pub fn suppresses(&self, threat: &AdvancedThreatResult, now: DateTime<Utc>) -> bool {
+        let matched = self.rules.read().unwrap().iter().position(|r| r.is_active(now) && r.matches(threat));
+        if let Some(index) = matched {
+            if let Some(rule) = self.rules.write().unwrap().get_mut(index) {
+                rule.hits += 1;
+                rule.last_hit = Some(now);
+            }
+        }
+        matched.is_some()
+    }
+
+    pub fn rules(&self) -> Vec<SuppressionRule> {
+        self.rules.read().unwrap().clone()
+    }
+
+    /// End a rule now; it stays listed until pruned
+    pub fn expire(&self, id: &str, now: DateTime<Utc>) -> SIEMResult<SuppressionRule> {
+        let rule = {
+            let mut rules = self.rules.write().unwrap();
+            let rule = rules
+                .iter_mut()
+                .find(|r| r.id == id)
+                .ok_or_else(|| SIEMError::Validation(format!("Suppression {} not found", id)))?;
+            rule.expires_at = rule.expires_at.min(now);
+            rule.clone()
+        };
+        self.save()?;
+        Ok(rule)
+    }
+
+    /// Rules active, expiring within a week, expired and repeatedly renewed as of `now`
+    pub fn report(&self, now: DateTime<Utc>) -> SuppressionReport {
+        let period_start = now - Duration::hours(self.settings.report_interval_hours.max(1) as i64);
+        let rules = self.rules.read().unwrap();
+        let mut report = SuppressionReport { period_start, generated_at: now, ..Default::default() };
+        for rule in rules.iter() {
+            if rule.is_active(now) {
+                report.active.push(rule.clone());
+                if rule.expires_at <= now + Duration::days(7) {
+                    report.expiring_soon.push(rule.clone());
+                }
+                if rule.renewals + 1 >= self.settings.renewal_warn {
+                    report.standing.push(rule.clone());
+                }
+            } else if rule.expires_at > period_start {
+                report.expired.push(rule.clone());
+            }
+        }
+        report
+    }
+
+    /// Drop rules expired longer than `keep_expired_days` ago and persist hit counts
+    pub fn prune(&self, now: DateTime<Utc>) -> SIEMResult<usize> {
+        let keep_after = now - Duration::days(self.settings.keep_expired_days.into());
+        let removed = {
+            let mut rules = self.rules.write().unwrap();
+            let before = rules.len();
+            rules.retain(|r| r.expires_at > keep_after);
+            before - rules.len()
+        };
+        self.save()?;
+        Ok(removed)
+    }
+
+    fn save(&self) -> SIEMResult<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&*self.rules.read().unwrap())?)?;
+        std::fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Periodic review of snoozes so temporary ones don't become permanent
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SuppressionReport {
+    pub period_start: DateTime<Utc>,
+    pub generated_at: DateTime<Utc>,
+    pub active: Vec<SuppressionRule>,
+    pub expiring_soon: Vec<SuppressionRule>,
+    /// Expired since `period_start`
+    pub expired: Vec<SuppressionRule>,
+    /// Active rules whose scope has been snoozed `renewal_warn` times or more in a row
+    pub standing: Vec<SuppressionRule>,
+}
+
+impl SuppressionReport {
+    /// Plain-text report, dated in `zone`
+    pub fn render(&self, zone: &Zone) -> String {
+        let mut report = String::new();
+        let _ = writeln!(
+            report,
+            "Ultra SIEM suppression report ({} - {})",
+            zone.to_local(self.period_start).format("%Y-%m-%d"),
+            zone.to_local(self.generated_at).format("%Y-%m-%d")
+        );
+        let _ = writeln!(
+            report,
+            "Active: {}  expiring within 7 days: {}  expired: {}  standing blind spots: {}",
+            self.active.len(),
+            self.expiring_soon.len(),
+            self.expired.len(),
+            self.standing.len()
+        );
+        let sections = [
+            ("Standing blind spots (renew or fix the rule)", &self.standing),
+            ("Active", &self.active),
+            ("Expired", &self.expired),
+        ];
+        for (title, rules) in sections {
+            if rules.is_empty() {
+                continue;
+            }
+            let _ = writeln!(report, "\n{}:", title);
+            for rule in rules {
+                let _ = writeln!(
+                    report,
+                    "  - {} by {} until {} ({} hits, {} renewals): {}",
+                    rule.describe(),
+                    rule.owner,
+                    zone.to_local(rule.expires_at).format("%Y-%m-%d"),
+                    rule.hits,
+                    rule.renewals,
+                    rule.reason
+                );
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list() -> (SuppressionList, PathBuf) {
+        let path = std::env::temp_dir().join(format!("suppressions-{}.json", Uuid::new_v4()));
+        let settings = SuppressionSettings { store_path: path.display().to_string(), ..Default::default() };
+        (SuppressionList::open(settings).unwrap(), path)
+    }
+
+    fn threat(signature: &str, hostname: &str) -> AdvancedThreatResult {
+        let mut threat = AdvancedThreatResult { source_ip: "10.0.0.5".to_string(), ..Default::default() };
+        threat.details.insert("signature_id".to_string(), signature.to_string());
+        threat.details.insert("hostname".to_string(), hostname.to_string());
+        threat
+    }
+
+    fn request(days: u32) -> SuppressionRequest {
+        SuppressionRequest { scope: SuppressionScope::Host, days, owner: "alice".to_string(), reason: "patch window".to_string() }
+    }
+
+    #[test]
+    fn test_snooze_applies_to_signature_on_host_until_expiry() {
+        let (list, path) = list();
+        let now = Utc::now();
+        let rule = list.create(&threat("xss_script_tag", "web-01"), "inc-1", &request(7), now).unwrap();
+        assert_eq!((rule.signature.as_str(), rule.host.as_str()), ("xss_script_tag", "web-01"));
+
+        assert!(list.suppresses(&threat("xss_script_tag", "web-01"), now));
+        assert!(!list.suppresses(&threat("xss_script_tag", "web-02"), now));
+        assert!(!list.suppresses(&threat("sqli_union", "web-01"), now));
+        assert!(!list.suppresses(&threat("xss_script_tag", "web-01"), now + Duration::days(8)));
+
+        assert!(list.create(&threat("x", "h"), "inc-1", &request(31), now).is_err());
+        assert!(list.create(&threat("x", "h"), "inc-1", &SuppressionRequest { reason: " ".to_string(), ..request(1) }, now).is_err());
+
+        // Rules survive a restart; hit counts are flushed on prune
+        list.prune(now).unwrap();
+        let reopened = SuppressionList::open(list.settings().clone()).unwrap();
+        assert_eq!(reopened.rules()[0].hits, 1);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_report_flags_repeated_snoozes() {
+        let (list, path) = list();
+        let start = Utc::now();
+        let noisy = threat("ssh_brute_force", "bastion");
+        for week in 0..3 {
+            list.create(&noisy, "inc-2", &request(7), start + Duration::days(7 * week)).unwrap();
+        }
+        let other = list.create(&threat("port_scan", "scanner"), "inc-3", &request(2), start + Duration::days(14)).unwrap();
+
+        let report = list.report(start + Duration::days(15));
+        assert_eq!(report.active.len(), 2);
+        assert_eq!(report.standing.len(), 1);
+        assert_eq!(report.standing[0].renewals, 2);
+        assert_eq!(report.expiring_soon.len(), 2);
+        assert!(report.render(&Zone::utc()).contains("'ssh_brute_force' on bastion by alice"));
+
+        list.expire(&other.id, start + Duration::days(15)).unwrap();
+        let report = list.report(start + Duration::days(15));
+        assert_eq!((report.active.len(), report.expired.len()), (1, 2));
+        assert_eq!(list.prune(start + Duration::days(200)).unwrap(), 4);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,447 @@
+//! # Tagging Rules
+//!
+//! Config-defined data shaping, applied once an event is normalized. A
+//! rule whose conditions all hold adds tags and derived fields to the
+//! event. Examples: `env = "prod"` for addresses in `10.1.0.0/16`, or a
+//! `business_unit` taken from the hostname prefix. Rules run in order and
+//! see the fields set by earlier ones.
+//!
+//! Detections raised from the event carry the same tags and fields in their
+//! details. That makes them usable by routing rules (`tags`), severity
+//! policies (`has_tag("prod")`, `detail("env")`), incident tags and stored
+//! threats without code changes.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::ip_net::{network_contains, parse_ip, IpNetwork};
+
+/// Event field and detail key holding the tags, comma-separated in details
+pub const TAGS_FIELD: &str = "tags";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagOperator {
+    Equals,
+    Contains,
+    StartsWith,
+    EndsWith,
+    /// Address inside a CIDR range
+    InCidr,
+    /// Regular expression match
+    Matches,
+    /// Field present with a non-empty value; `value` is ignored
+    Exists,
+}
+
+/// One value or a list of them; a condition holds when any matches
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Default for OneOrMany {
+    fn default() -> Self {
+        OneOrMany::Many(Vec::new())
+    }
+}
+
+impl OneOrMany {
+    fn values(&self) -> Vec<String> {
+        match self {
+            OneOrMany::One(value) => vec![value.clone()],
+            OneOrMany::Many(values) => values.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagCondition {
+    /// Top-level field, or a dotted path into nested objects
+    pub field: String,
+    pub operator: TagOperator,
+    #[serde(default)]
+    pub value: OneOrMany,
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+/// How a derived field gets its value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DerivedValue {
+    Constant(String),
+    /// `regex` over the `from` field, expanded into `value` (`$1`, `${name}`)
+    Extract {
+        from: String,
+        regex: String,
+        #[serde(default = "first_group")]
+        value: String,
+    },
+    /// Value of the longest key of `prefixes` the `from` field starts with
+    Prefix {
+        from: String,
+        prefixes: BTreeMap<String, String>,
+    },
+}
+
+fn first_group() -> String {
+    "$1".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaggingRule {
+    pub name: String,
+    /// All must hold; an empty list matches every event
+    #[serde(default)]
+    pub when: Vec<TagCondition>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub set: BTreeMap<String, DerivedValue>,
+    /// Replace fields the event already has; by default they are kept
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaggingSettings {
+    pub enabled: bool,
+    pub rules: Vec<TaggingRule>,
+}
+
+impl Default for TaggingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl TaggingSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        TaggingEngine::new(self.clone()).map(|_| ())
+    }
+}
+
+/// Field as text: strings as is, numbers and booleans formatted
+fn field_text(event: &Value, field: &str) -> Option<String> {
+    let value = event.get(field).or_else(|| event.pointer(&format!("/{}", field.replace('.', "/"))))?;
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+struct CompiledCondition {
+    field: String,
+    operator: TagOperator,
+    values: Vec<String>,
+    regexes: Vec<Regex>,
+    case_sensitive: bool,
+}
+
+impl CompiledCondition {
+    fn compile(rule: &str, condition: &TagCondition) -> SIEMResult<Self> {
+        let invalid = |what: String| SIEMError::Config(format!("Tagging rule '{}': {}", rule, what));
+        let values = condition.value.values();
+        if values.is_empty() && condition.operator != TagOperator::Exists {
+            return Err(invalid(format!("condition on '{}' needs a value", condition.field)));
+        }
+        let regexes = match condition.operator {
+            TagOperator::Matches => values
+                .iter()
+                .map(|pattern| {
+                    regex::RegexBuilder::new(pattern)
+                        .case_insensitive(!condition.case_sensitive)
+                        .build()
+                        .map_err(|e| invalid(format!("invalid regex '{}': {}", pattern, e)))
+                })
+                .collect::<SIEMResult<_>>()?,
+            _ => Vec::new(),
+        };
+        if condition.operator == TagOperator::InCidr {
+            if let Some(bad) = values.iter().find(|network| network.parse::<IpNetwork>().is_err()) {
+                return Err(invalid(format!("invalid network '{}'", bad)));
+            }
+        }
+        let values = match condition.case_sensitive {
+            true => values,
+            false => values.into_iter().map(|value| value.to_lowercase()).collect(),
+        };
+        Ok(Self { field: condition.field.clone(), operator: condition.operator, values, regexes, case_sensitive: condition.case_sensitive })
+    }
+
+    fn holds(&self, event: &Value) -> bool {
+        let Some(text) = field_text(event, &self.field) else {
+            return false;
+        };
+        let text = match self.case_sensitive {
+            true => text,
+            false => text.to_lowercase(),
+        };
+        let any = |test: &dyn Fn(&str) -> bool| self.values.iter().any(|value| test(value));
+        match self.operator {
+            TagOperator::Equals => any(&|value| text == value),
+            TagOperator::Contains => any(&|value| text.contains(value)),
+            TagOperator::StartsWith => any(&|value| text.starts_with(value)),
+            TagOperator::EndsWith => any(&|value| text.ends_with(value)),
+            TagOperator::InCidr => parse_ip(&text).is_some_and(|ip| any(&|network| network_contains(network, ip))),
+            TagOperator::Matches => self.regexes.iter().any(|regex| regex.is_match(&text)),
+            TagOperator::Exists => !text.is_empty(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum CompiledValue {
+    Constant(String),
+    Extract { from: String, regex: Regex, value: String },
+    /// Longest prefix first
+    Prefix { from: String, prefixes: Vec<(String, String)> },
+}
+
+impl CompiledValue {
+    fn compile(rule: &str, field: &str, value: &DerivedValue) -> SIEMResult<Self> {
+        Ok(match value {
+            DerivedValue::Constant(value) => CompiledValue::Constant(value.clone()),
+            DerivedValue::Extract { from, regex, value } => CompiledValue::Extract {
+                from: from.clone(),
+                regex: Regex::new(regex)
+                    .map_err(|e| SIEMError::Config(format!("Tagging rule '{}': invalid regex for '{}': {}", rule, field, e)))?,
+                value: value.clone(),
+            },
+            DerivedValue::Prefix { from, prefixes } => {
+                let mut prefixes: Vec<(String, String)> = prefixes.iter().map(|(p, v)| (p.to_lowercase(), v.clone())).collect();
+                prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+                CompiledValue::Prefix { from: from.clone(), prefixes }
+            }
+        })
+    }
+
+    fn derive(&self, event: &Value) -> Option<String> {
+        match self {
+            CompiledValue::Constant(value) => Some(value.clone()),
+            CompiledValue::Extract { from, regex, value } => {
+                let text = field_text(event, from)?;
+                let captures = regex.captures(&text)?;
+                let mut derived = String::new();
+                captures.expand(value, &mut derived);
+                Some(derived).filter(|derived| !derived.is_empty())
+            }
+            CompiledValue::Prefix { from, prefixes } => {
+                let text = field_text(event, from)?.to_lowercase();
+                prefixes.iter().find(|(prefix, _)| text.starts_with(prefix.as_str())).map(|(_, value)| value.clone())
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CompiledRule {
+    when: Vec<CompiledCondition>,
+    tags: Vec<String>,
+    set: Vec<(String, CompiledValue)>,
+    overwrite: bool,
+}
+
+/// Tags and fields a set of rules added to one event
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tagged {
+    pub tags: Vec<String>,
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Tagged {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.fields.is_empty()
+    }
+
+    /// Carry the tags and derived fields onto a detection raised from the event
+    pub fn annotate(&self, threat: &mut AdvancedThreatResult) {
+        for (field, value) in &self.fields {
+            threat.details.insert(field.clone(), value.clone());
+        }
+        if !self.tags.is_empty() {
+            let mut tags: Vec<String> = detail_tags(threat).map(str::to_string).collect();
+            for tag in &self.tags {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+            threat.details.insert(TAGS_FIELD.to_string(), tags.join(","));
+        }
+    }
+}
+
+/// Tags carried in a detection's details
+pub fn detail_tags(threat: &AdvancedThreatResult) -> impl Iterator<Item = &str> {
+    threat.details.get(TAGS_FIELD).into_iter().flat_map(|tags| tags.split(',')).map(str::trim).filter(|tag| !tag.is_empty())
+}
+
+/// Compiled tagging rules
+#[derive(Debug)]
+pub struct TaggingEngine {
+    rules: Vec<CompiledRule>,
+    tagged_events: AtomicU64,
+    rule_hits: HashMap<String, AtomicU64>,
+    names: Vec<String>,
+}
+
+impl TaggingEngine {
+    pub fn new(settings: TaggingSettings) -> SIEMResult<Self> {
+        let rules = settings
+            .rules
+            .iter()
+            .map(|rule| {
+                Ok(CompiledRule {
+                    when: rule.when.iter().map(|condition| CompiledCondition::compile(&rule.name, condition)).collect::<SIEMResult<_>>()?,
+                    tags: rule.tags.clone(),
+                    set: rule
+                        .set
+                        .iter()
+                        .map(|(field, value)| Ok((field.clone(), CompiledValue::compile(&rule.name, field, value)?)))
+                        .collect::<SIEMResult<_>>()?,
+                    overwrite: rule.overwrite,
+                })
+            })
+            .collect::<SIEMResult<_>>()?;
+        let names: Vec<String> = settings.rules.iter().map(|rule| rule.name.clone()).collect();
+        Ok(Self {
+            rules,
+            tagged_events: AtomicU64::new(0),
+            rule_hits: names.iter().map(|name| (name.clone(), AtomicU64::new(0))).collect(),
+            names,
+        })
+    }
+
+    /// Run every rule over `event`, writing tags and derived fields onto it
+    pub fn apply(&self, event: &mut Value) -> Tagged {
+        let mut tagged = Tagged::default();
+        if !event.is_object() {
+            return tagged;
+        }
+        for (rule, name) in self.rules.iter().zip(&self.names) {
+            if !rule.when.iter().all(|condition| condition.holds(event)) {
+                continue;
+            }
+            if let Some(hits) = self.rule_hits.get(name) {
+                hits.fetch_add(1, Ordering::Relaxed);
+            }
+            for tag in &rule.tags {
+                if !tagged.tags.contains(tag) {
+                    tagged.tags.push(tag.clone());
+                }
+            }
+            for (field, value) in &rule.set {
+                if !rule.overwrite && event.get(field).is_some_and(|existing| !existing.is_null()) {
+                    continue;
+                }
+                if let Some(derived) = value.derive(event) {
+                    event[field.as_str()] = Value::String(derived.clone());
+                    tagged.fields.insert(field.clone(), derived);
+                }
+            }
+        }
+        if !tagged.tags.is_empty() {
+            let mut tags: Vec<Value> = match event.get(TAGS_FIELD) {
+                Some(Value::Array(existing)) => existing.clone(),
+                Some(Value::String(existing)) => existing.split(',').map(|tag| Value::String(tag.trim().to_string())).collect(),
+                _ => Vec::new(),
+            };
+            for tag in &tagged.tags {
+                if !tags.iter().any(|existing| existing.as_str() == Some(tag)) {
+                    tags.push(Value::String(tag.clone()));
+                }
+            }
+            event[TAGS_FIELD] = Value::Array(tags);
+        }
+        if !tagged.is_empty() {
+            self.tagged_events.fetch_add(1, Ordering::Relaxed);
+        }
+        tagged
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        let mut metrics = HashMap::new();
+        metrics.insert("tagging_events_tagged".to_string(), self.tagged_events.load(Ordering::Relaxed) as f64);
+        for (name, hits) in &self.rule_hits {
+            metrics.insert(format!("tagging_rule_{}_hits", name), hits.load(Ordering::Relaxed) as f64);
+        }
+        metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(rules: serde_json::Value) -> SIEMResult<TaggingEngine> {
+        TaggingEngine::new(TaggingSettings { enabled: true, rules: serde_json::from_value(rules).unwrap() })
+    }
+
+    #[test]
+    fn test_rules_add_tags_and_derived_fields() {
+        let engine = engine(serde_json::json!([
+            {
+                "name": "prod_network",
+                "when": [{ "field": "source_ip", "operator": "in_cidr", "value": ["10.1.0.0/16", "10.9.0.0/16"] }],
+                "tags": ["prod"],
+                "set": { "env": "prod" },
+            },
+            {
+                "name": "business_unit",
+                "set": {
+                    "business_unit": { "from": "hostname", "prefixes": { "fin": "finance", "fin-trade": "trading", "eng": "engineering" } },
+                    "site": { "from": "hostname", "regex": "-([a-z]{3})\\d+$" },
+                },
+            },
+            {
+                "name": "pci",
+                "when": [{ "field": "env", "operator": "equals", "value": "PROD" }, { "field": "business_unit", "operator": "equals", "value": ["finance", "trading"] }],
+                "tags": ["pci"],
+            },
+        ]))
+        .unwrap();
+
+        let mut event = serde_json::json!({ "source_ip": "10.1.4.20", "hostname": "FIN-TRADE-ams01", "tags": ["edr"] });
+        let tagged = engine.apply(&mut event);
+        assert_eq!(tagged.tags, vec!["prod", "pci"]);
+        assert_eq!(event["env"], "prod");
+        assert_eq!(event["business_unit"], "trading");
+        assert_eq!(event["site"], "ams");
+        assert_eq!(event["tags"], serde_json::json!(["edr", "prod", "pci"]));
+
+        let mut threat = AdvancedThreatResult::default();
+        threat.details.insert(TAGS_FIELD.to_string(), "honeyport".to_string());
+        tagged.annotate(&mut threat);
+        assert_eq!(threat.details["business_unit"], "trading");
+        assert_eq!(detail_tags(&threat).collect::<Vec<_>>(), vec!["honeyport", "prod", "pci"]);
+
+        let mut other = serde_json::json!({ "source_ip": "192.0.2.1", "hostname": "web-01", "env": "staging" });
+        let tagged = engine.apply(&mut other);
+        assert!(tagged.is_empty());
+        assert_eq!(other["env"], "staging");
+        assert_eq!(engine.get_metrics()["tagging_events_tagged"], 1.0);
+        assert_eq!(engine.get_metrics()["tagging_rule_pci_hits"], 1.0);
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        assert!(engine(serde_json::json!([{ "name": "bad", "when": [{ "field": "source_ip", "operator": "in_cidr", "value": "10.1.0.0/33" }] }])).is_err());
+        assert!(engine(serde_json::json!([{ "name": "bad", "when": [{ "field": "message", "operator": "matches", "value": "(" }] }])).is_err());
+        assert!(engine(serde_json::json!([{ "name": "bad", "when": [{ "field": "message", "operator": "equals" }] }])).is_err());
+        assert!(engine(serde_json::json!([{ "name": "bad", "set": { "x": { "from": "host", "regex": "[" } } }])).is_err());
+    }
+}
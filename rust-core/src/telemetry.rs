@@ -0,0 +1,315 @@
+//! # Opt-in Telemetry
+//!
+//! When enabled, periodically posts aggregated detection efficacy statistics
+//! to `endpoint` so maintainers can tune the default rules. Reports contain
+//! hit counts and false positive ratios per shipped rule (custom rules are
+//! folded into one `custom` bucket), pipeline latency percentiles and a random
+//! install id. Never payloads, addresses, users or hostnames.
+//!
+//! `TelemetryReporter::preview` builds exactly the report that would be sent;
+//! it is served at `GET /api/v1/telemetry/preview` whether or not telemetry is enabled.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, DurationRound, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::fatigue::{FatigueAnalyzer, FatigueSettings};
+use crate::incident_response::{Incident, IncidentResponseEngine};
+
+/// Bumped whenever a field is added to or removed from `TelemetryReport`
+pub const TELEMETRY_SCHEMA_VERSION: u32 = 1;
+
+/// Bucket used for rules outside `shared_rules`
+pub const CUSTOM_RULES: &str = "custom";
+
+/// Upper bounds of the latency histogram buckets in milliseconds
+const LATENCY_BOUNDS_MS: [f64; 14] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, f64::INFINITY];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetrySettings {
+    pub enabled: bool,
+    pub endpoint: String,
+    pub interval_hours: u64,
+    /// Rolling window the rule statistics cover
+    pub window_hours: u64,
+    /// Where the random install id is kept between restarts
+    pub install_id_path: String,
+    /// Rules reported by id; all others are summed under `custom`
+    pub shared_rules: Vec<String>,
+}
+
+impl Default for TelemetrySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            interval_hours: 24,
+            window_hours: 24 * 7,
+            install_id_path: "data/telemetry-id".to_string(),
+            // Rules shipped with the engine
+            shared_rules: ["sql_injection_1", "xss_1", "brute_force_1", "malware_1", "brute_force_attack", "data_exfiltration"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+/// Lock-free fixed-bucket histogram of pipeline latencies
+#[derive(Debug, Default)]
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BOUNDS_MS.len()],
+}
+
+impl LatencyHistogram {
+    pub fn observe(&self, elapsed: Duration) {
+        let ms = elapsed.as_secs_f64() * 1000.0;
+        let bucket = LATENCY_BOUNDS_MS.iter().position(|bound| ms <= *bound).unwrap_or(LATENCY_BOUNDS_MS.len() - 1);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Upper bound of the bucket holding quantile `q`; the last finite bound for the overflow bucket
+    pub fn percentile(&self, q: f64) -> f64 {
+        let counts: Vec<u64> = self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return 0.0;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (count, bound) in counts.iter().zip(LATENCY_BOUNDS_MS) {
+            seen += count;
+            if seen >= rank {
+                return if bound.is_finite() { bound } else { LATENCY_BOUNDS_MS[LATENCY_BOUNDS_MS.len() - 2] };
+            }
+        }
+        0.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleEfficacy {
+    pub rule: String,
+    pub hits: u64,
+    pub false_positives: u64,
+    pub false_positive_rate: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyPercentiles {
+    pub samples: u64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// Everything a telemetry submission contains
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TelemetryReport {
+    pub schema_version: u32,
+    pub install_id: String,
+    pub engine_version: String,
+    /// Truncated to the hour
+    pub generated_at: DateTime<Utc>,
+    pub window_hours: u64,
+    pub incidents: u64,
+    pub false_positive_rate: f64,
+    pub rules: Vec<RuleEfficacy>,
+    pub latency: LatencyPercentiles,
+}
+
+pub struct TelemetryReporter {
+    settings: TelemetrySettings,
+    install_id: String,
+    latency: Arc<LatencyHistogram>,
+}
+
+impl TelemetryReporter {
+    pub fn new(settings: TelemetrySettings) -> Self {
+        let install_id = load_install_id(Path::new(&settings.install_id_path)).unwrap_or_else(|e| {
+            warn!("⚠️ Using a temporary telemetry install id: {}", e);
+            uuid::Uuid::new_v4().to_string()
+        });
+        Self {
+            settings,
+            install_id,
+            latency: Arc::new(LatencyHistogram::default()),
+        }
+    }
+
+    /// Histogram the detection engine records pipeline latencies into
+    pub fn latency(&self) -> Arc<LatencyHistogram> {
+        self.latency.clone()
+    }
+
+    /// The report that the next submission would send
+    pub fn preview(&self, incidents: &[Incident], now: DateTime<Utc>) -> TelemetryReport {
+        let fatigue = FatigueAnalyzer::new(FatigueSettings {
+            window_hours: self.settings.window_hours,
+            ..Default::default()
+        })
+        .analyze(incidents, now);
+
+        let mut rules: BTreeMap<&str, RuleEfficacy> = BTreeMap::new();
+        for (rule, stats) in &fatigue.per_rule {
+            let name = match self.settings.shared_rules.iter().find(|shared| *shared == rule) {
+                Some(shared) => shared.as_str(),
+                None => CUSTOM_RULES,
+            };
+            let entry = rules.entry(name).or_insert_with(|| RuleEfficacy {
+                rule: name.to_string(),
+                hits: 0,
+                false_positives: 0,
+                false_positive_rate: 0.0,
+            });
+            entry.hits += stats.alerts;
+            entry.false_positives += stats.false_positives;
+            entry.false_positive_rate = entry.false_positives as f64 / entry.hits.max(1) as f64;
+        }
+
+        TelemetryReport {
+            schema_version: TELEMETRY_SCHEMA_VERSION,
+            install_id: self.install_id.clone(),
+            engine_version: env!("CARGO_PKG_VERSION").to_string(),
+            generated_at: now.duration_trunc(chrono::Duration::hours(1)).unwrap_or(now),
+            window_hours: self.settings.window_hours,
+            incidents: fatigue.overall.alerts,
+            false_positive_rate: fatigue.overall.false_positive_rate,
+            rules: rules.into_values().collect(),
+            latency: LatencyPercentiles {
+                samples: self.latency.count(),
+                p50_ms: self.latency.percentile(0.5),
+                p90_ms: self.latency.percentile(0.9),
+                p99_ms: self.latency.percentile(0.99),
+            },
+        }
+    }
+
+    pub async fn send(&self, report: &TelemetryReport) -> SIEMResult<()> {
+        if self.settings.endpoint.is_empty() {
+            return Err(SIEMError::Config("Telemetry endpoint is not configured".to_string()));
+        }
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?
+            .post(&self.settings.endpoint)
+            .json(report)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Submit a report every `interval_hours`; does nothing unless opted in
+    pub fn spawn(self: Arc<Self>, incidents: Arc<IncidentResponseEngine>) {
+        if !self.settings.enabled || self.settings.endpoint.is_empty() {
+            info!("📊 Telemetry is off; preview it at /api/v1/telemetry/preview");
+            return;
+        }
+        tokio::spawn(async move {
+            info!("📊 Sending anonymized telemetry to {}", self.settings.endpoint);
+            let period = Duration::from_secs(self.settings.interval_hours.max(1) * 3600);
+            let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            loop {
+                interval.tick().await;
+                let report = self.preview(&incidents.get_all_incidents(), Utc::now());
+                if let Err(e) = self.send(&report).await {
+                    warn!("⚠️ Telemetry submission failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+fn load_install_id(path: &Path) -> SIEMResult<String> {
+    if let Ok(id) = std::fs::read_to_string(path) {
+        if uuid::Uuid::parse_str(id.trim()).is_ok() {
+            return Ok(id.trim().to_string());
+        }
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, &id)?;
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatResult;
+    use crate::incident_response::IncidentStatus;
+
+    fn incident(rule: &str, false_positive: bool, created_at: DateTime<Utc>) -> Incident {
+        Incident {
+            status: if false_positive { IncidentStatus::FalsePositive } else { IncidentStatus::Open },
+            title: "Secret title".to_string(),
+            description: "UNION SELECT password FROM users".to_string(),
+            source_ip: "203.0.113.7".to_string(),
+            destination_ip: "10.0.0.5".to_string(),
+            user_id: "alice".to_string(),
+            threat_result: AdvancedThreatResult { signatures: vec![rule.to_string()], ..Default::default() },
+            false_positive,
+            ..Incident::fixture(created_at)
+        }
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let histogram = LatencyHistogram::default();
+        assert_eq!(histogram.percentile(0.5), 0.0);
+        for _ in 0..90 {
+            histogram.observe(Duration::from_micros(800));
+        }
+        for _ in 0..10 {
+            histogram.observe(Duration::from_millis(40));
+        }
+        histogram.observe(Duration::from_secs(5));
+        assert_eq!(histogram.count(), 101);
+        assert_eq!(histogram.percentile(0.5), 1.0);
+        assert_eq!(histogram.percentile(0.95), 50.0);
+        assert_eq!(histogram.percentile(1.0), 1000.0);
+    }
+
+    #[test]
+    fn test_preview_is_aggregated_and_anonymized() {
+        let dir = std::env::temp_dir().join(format!("telemetry-{}", uuid::Uuid::new_v4()));
+        let settings = TelemetrySettings {
+            install_id_path: dir.join("id").to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let reporter = TelemetryReporter::new(settings.clone());
+        reporter.latency().observe(Duration::from_millis(3));
+        let now = Utc::now();
+        let incidents = vec![
+            incident("sql_injection_1", false, now),
+            incident("sql_injection_1", true, now),
+            incident("acme_internal_rule", true, now),
+            incident("acme_other_rule", false, now),
+        ];
+        let report = reporter.preview(&incidents, now);
+        assert_eq!(report.incidents, 4);
+        assert_eq!(report.rules.iter().map(|r| (r.rule.as_str(), r.hits)).collect::<Vec<_>>(), vec![(CUSTOM_RULES, 2), ("sql_injection_1", 2)]);
+        assert_eq!(report.rules[1].false_positive_rate, 0.5);
+        assert_eq!(report.latency.p50_ms, 5.0);
+
+        let sent = serde_json::to_string(&report).unwrap();
+        for private in ["acme", "203.0.113.7", "10.0.0.5", "alice", "UNION", "Secret"] {
+            assert!(!sent.contains(private), "{} leaked into telemetry", private);
+        }
+        // The install id is stable across restarts
+        assert_eq!(TelemetryReporter::new(settings).preview(&[], now).install_id, report.install_id);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::{Deserialize, Serialize};
+
+use crate::attack;
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::error_handling::{SIEMError, SIEMResult};
+use crate::threat_detection::{ThreatCategory, ThreatSeverity};
+
+/// Longest original value kept in detection details
+const MAX_ORIGINAL_CHARS: usize = 256;
+
+/// Unicode normalization of event text before signature matching
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextNormalizationSettings {
+    pub enabled: bool,
+    /// Event fields rewritten in place; dotted paths reach nested objects
+    pub fields: Vec<String>,
+    /// Fold compatibility forms (fullwidth, mathematical, circled, ligatures, odd spaces) as NFKC does
+    pub compatibility: bool,
+    /// Map Cyrillic and Greek look-alikes to Latin inside Latin words and all-look-alike words
+    pub fold_homoglyphs: bool,
+    /// Drop zero-width, bidi control and other invisible characters
+    pub strip_invisible: bool,
+    /// Suspicious changes in one event (invisible characters, look-alikes, folded forms other
+    /// than spaces) that raise an evasion detection; 0 disables the signal
+    pub evasion_min_changes: usize,
+    /// Also raise it for any word mixing Latin with Cyrillic or Greek letters
+    pub flag_mixed_scripts: bool,
+}
+
+impl Default for TextNormalizationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            fields: vec!["message".to_string(), "command_line".to_string(), "url".to_string()],
+            compatibility: true,
+            fold_homoglyphs: true,
+            strip_invisible: true,
+            evasion_min_changes: 3,
+            flag_mixed_scripts: true,
+        }
+    }
+}
+
+impl TextNormalizationSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        if self.enabled && self.fields.iter().any(|f| f.trim().is_empty()) {
+            return Err(SIEMError::Validation("text_normalization.fields must not contain empty names".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Other,
+}
+
+fn script(c: char) -> Script {
+    match c {
+        'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' => Script::Latin,
+        '\u{0400}'..='\u{052F}' => Script::Cyrillic,
+        '\u{0370}'..='\u{03FF}' => Script::Greek,
+        _ => Script::Other,
+    }
+}
+
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}' | '\u{034F}' | '\u{061C}' | '\u{180E}' | '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}' | '\u{2066}'..='\u{2069}' | '\u{FE00}'..='\u{FE0F}' | '\u{FEFF}'
+    )
+}
+
+/// Combining marks stacked on a letter, e.g. to break up a keyword
+fn is_combining(c: char) -> bool {
+    matches!(c, '\u{0300}'..='\u{036F}' | '\u{20D0}'..='\u{20FF}')
+}
+
+fn is_space(c: char) -> bool {
+    matches!(c, '\u{00A0}' | '\u{1680}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}')
+}
+
+fn offset(c: char, base: u32, to: u8) -> char {
+    char::from(to + (c as u32 - base) as u8)
+}
+
+/// Compatibility decomposition (a subset of NFKC) of the forms used to dodge ASCII matching
+fn compatibility(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{FB00}' => "ff",
+        '\u{FB01}' => "fi",
+        '\u{FB02}' => "fl",
+        '\u{FB03}' => "ffi",
+        '\u{FB04}' => "ffl",
+        '\u{FB05}' | '\u{FB06}' => "st",
+        '\u{2024}' => ".",
+        '\u{2025}' => "..",
+        '\u{2026}' => "...",
+        '\u{2474}'..='\u{247C}' => ["(1)", "(2)", "(3)", "(4)", "(5)", "(6)", "(7)", "(8)", "(9)"][c as usize - 0x2474],
+        _ => return None,
+    })
+}
+
+/// Single-character compatibility mappings
+fn compatibility_char(c: char) -> Option<char> {
+    Some(match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFF01 + 0x21)?,
+        // Mathematical alphanumerics: 13 alphabets of 52 letters, then 5 digit sets
+        '\u{1D400}'..='\u{1D6A3}' => {
+            let index = (c as u32 - 0x1D400) % 52;
+            if index < 26 { char::from(b'A' + index as u8) } else { char::from(b'a' + (index - 26) as u8) }
+        }
+        '\u{1D7CE}'..='\u{1D7FF}' => char::from(b'0' + ((c as u32 - 0x1D7CE) % 10) as u8),
+        '\u{24B6}'..='\u{24CF}' => offset(c, 0x24B6, b'A'),
+        '\u{24D0}'..='\u{24E9}' => offset(c, 0x24D0, b'a'),
+        '\u{2460}'..='\u{2468}' => offset(c, 0x2460, b'1'),
+        '\u{2070}' | '\u{2080}' => '0',
+        '\u{00B9}' | '\u{2081}' => '1',
+        '\u{00B2}' | '\u{2082}' => '2',
+        '\u{00B3}' | '\u{2083}' => '3',
+        '\u{2074}'..='\u{2079}' => offset(c, 0x2074, b'4'),
+        '\u{2084}'..='\u{2089}' => offset(c, 0x2084, b'4'),
+        '\u{2071}' | '\u{2139}' => 'i',
+        '\u{207F}' => 'n',
+        '\u{2102}' => 'C',
+        '\u{210A}' => 'g',
+        '\u{210B}'..='\u{210D}' => 'H',
+        '\u{210E}' => 'h',
+        '\u{2110}' | '\u{2111}' => 'I',
+        '\u{2112}' => 'L',
+        '\u{2113}' => 'l',
+        '\u{2115}' => 'N',
+        '\u{2119}' => 'P',
+        '\u{211A}' => 'Q',
+        '\u{211B}'..='\u{211D}' => 'R',
+        '\u{2124}' | '\u{2128}' => 'Z',
+        '\u{212A}' => 'K',
+        '\u{212B}' => 'Å',
+        '\u{212C}' => 'B',
+        '\u{212D}' => 'C',
+        '\u{212F}' | '\u{2147}' => 'e',
+        '\u{2130}' => 'E',
+        '\u{2131}' => 'F',
+        '\u{2133}' => 'M',
+        '\u{2134}' => 'o',
+        '\u{2145}' => 'D',
+        '\u{2146}' => 'd',
+        '\u{2148}' => 'i',
+        '\u{2149}' => 'j',
+        '\u{FE63}' => '-',
+        '\u{FE68}' => '\\',
+        '\u{2215}' | '\u{2044}' => '/',
+        _ => return None,
+    })
+}
+
+/// Cyrillic and Greek letters drawn like a Latin one
+fn homoglyph(c: char) -> Option<char> {
+    Some(match c {
+        'а' | 'α' => 'a',
+        'ь' => 'b',
+        'с' | 'ϲ' => 'c',
+        'ԁ' => 'd',
+        'е' | 'ε' => 'e',
+        'һ' => 'h',
+        'і' | 'ι' | 'ı' => 'i',
+        'ј' | 'ϳ' => 'j',
+        'κ' => 'k',
+        'ӏ' => 'l',
+        'п' => 'n',
+        'о' | 'ο' | 'σ' => 'o',
+        'р' | 'ρ' => 'p',
+        'ԛ' => 'q',
+        'г' => 'r',
+        'ѕ' => 's',
+        'τ' => 't',
+        'υ' => 'u',
+        'ν' | 'ѵ' => 'v',
+        'ԝ' | 'ω' => 'w',
+        'х' | 'χ' => 'x',
+        'у' | 'γ' => 'y',
+        'А' | 'Α' => 'A',
+        'В' | 'Β' => 'B',
+        'С' | 'Ϲ' => 'C',
+        'Е' | 'Ε' => 'E',
+        'Н' | 'Η' => 'H',
+        'І' | 'Ι' => 'I',
+        'Ј' => 'J',
+        'К' | 'Κ' => 'K',
+        'М' | 'Μ' => 'M',
+        'Ν' => 'N',
+        'О' | 'Ο' => 'O',
+        'Р' | 'Ρ' => 'P',
+        'Ѕ' => 'S',
+        'Т' | 'Τ' => 'T',
+        'Х' | 'Χ' => 'X',
+        'У' | 'Υ' => 'Y',
+        'Ζ' => 'Z',
+        _ => return None,
+    })
+}
+
+/// Changes made to one field
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FieldNormalization {
+    pub field: String,
+    pub invisible: usize,
+    /// Folded compatibility forms and combining marks, excluding space variants
+    pub compatibility: usize,
+    pub spaces: usize,
+    pub homoglyphs: usize,
+    /// Words that mixed Latin with Cyrillic or Greek letters, as written
+    pub mixed_script_words: Vec<String>,
+    pub original: String,
+}
+
+impl FieldNormalization {
+    /// Changes that ordinary text does not need
+    pub fn suspicious_changes(&self) -> usize {
+        self.invisible + self.compatibility + self.homoglyphs
+    }
+}
+
+/// Normalize `text`, returning the rewritten text and what changed
+pub fn normalize_text(settings: &TextNormalizationSettings, text: &str) -> (String, FieldNormalization) {
+    let mut changes = FieldNormalization::default();
+    let mut folded = String::with_capacity(text.len());
+    let mut previous: Option<char> = None;
+    for c in text.chars() {
+        if settings.strip_invisible && is_invisible(c) {
+            changes.invisible += 1;
+            continue;
+        }
+        if settings.compatibility {
+            if is_combining(c) && previous.is_some_and(|p| p.is_ascii_alphanumeric()) {
+                changes.compatibility += 1;
+                continue;
+            }
+            if is_space(c) {
+                changes.spaces += 1;
+                folded.push(' ');
+                previous = Some(' ');
+                continue;
+            }
+            if let Some(mapped) = compatibility_char(c) {
+                changes.compatibility += 1;
+                folded.push(mapped);
+                previous = Some(mapped);
+                continue;
+            }
+            if let Some(mapped) = compatibility(c) {
+                changes.compatibility += 1;
+                folded.push_str(mapped);
+                previous = mapped.chars().last();
+                continue;
+            }
+        }
+        folded.push(c);
+        previous = Some(c);
+    }
+    if !settings.fold_homoglyphs && !settings.flag_mixed_scripts {
+        return (folded, changes);
+    }
+
+    let mut normalized = String::with_capacity(folded.len());
+    let mut word = String::new();
+    let flush = |word: &mut String, normalized: &mut String, changes: &mut FieldNormalization| {
+        let latin = word.chars().any(|c| script(c) == Script::Latin);
+        let foreign = word.chars().any(|c| matches!(script(c), Script::Cyrillic | Script::Greek));
+        let mixed = latin && foreign;
+        // Whole words of look-alikes ("рауРаӏ") are folded too; short ones are often real words
+        let lookalike = foreign && word.chars().count() >= 3
+            && word.chars().all(|c| !c.is_alphabetic() || script(c) == Script::Latin || homoglyph(c).is_some());
+        if mixed {
+            changes.mixed_script_words.push(word.clone());
+        }
+        if settings.fold_homoglyphs && (mixed || lookalike) {
+            for c in word.chars() {
+                match homoglyph(c) {
+                    Some(latin) => {
+                        changes.homoglyphs += 1;
+                        normalized.push(latin);
+                    }
+                    None => normalized.push(c),
+                }
+            }
+        } else {
+            normalized.push_str(word);
+        }
+        word.clear();
+    };
+    for c in folded.chars() {
+        if c.is_alphanumeric() {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut normalized, &mut changes);
+            normalized.push(c);
+        }
+    }
+    flush(&mut word, &mut normalized, &mut changes);
+    (normalized, changes)
+}
+
+/// What normalization changed in one event
+#[derive(Debug, Clone, Default)]
+pub struct NormalizationReport {
+    pub fields: Vec<FieldNormalization>,
+}
+
+impl NormalizationReport {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn suspicious_changes(&self) -> usize {
+        self.fields.iter().map(FieldNormalization::suspicious_changes).sum()
+    }
+
+    pub fn mixed_script_words(&self) -> impl Iterator<Item = &String> {
+        self.fields.iter().flat_map(|f| f.mixed_script_words.iter())
+    }
+
+    /// Heavy enough rewriting to suggest the text was crafted to slip past signatures
+    pub fn is_evasion(&self, settings: &TextNormalizationSettings) -> bool {
+        (settings.evasion_min_changes > 0 && self.suspicious_changes() >= settings.evasion_min_changes)
+            || (settings.flag_mixed_scripts && self.mixed_script_words().next().is_some())
+    }
+
+    pub fn to_threat(&self, event: &serde_json::Value, timestamp: u64) -> AdvancedThreatResult {
+        let sum = |count: fn(&FieldNormalization) -> usize| self.fields.iter().map(count).sum::<usize>();
+        let mut details = attack::technique_details(&["T1027".to_string()]);
+        details.insert("normalized_fields".to_string(), self.fields.iter().map(|f| f.field.as_str()).collect::<Vec<_>>().join(","));
+        details.insert("invisible_characters".to_string(), sum(|f| f.invisible).to_string());
+        details.insert("compatibility_forms".to_string(), sum(|f| f.compatibility).to_string());
+        details.insert("homoglyphs".to_string(), sum(|f| f.homoglyphs).to_string());
+        for field in &self.fields {
+            details.insert(format!("original_{}", field.field), field.original.chars().take(MAX_ORIGINAL_CHARS).collect());
+        }
+        let mixed: Vec<String> = self.mixed_script_words().cloned().collect();
+        let text = |name: &str| event.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        AdvancedThreatResult {
+            timestamp,
+            severity: ThreatSeverity::Medium,
+            category: ThreatCategory::Evasion,
+            confidence: if mixed.is_empty() { 0.6 } else { 0.75 },
+            detection_method: "unicode_evasion".to_string(),
+            source_ip: text("source_ip"),
+            destination_ip: text("destination_ip"),
+            user_id: text("user_id"),
+            description: format!(
+                "Unicode obfuscation in {}: {} invisible, {} look-alike and {} compatibility characters{}",
+                details["normalized_fields"],
+                details["invisible_characters"],
+                details["homoglyphs"],
+                details["compatibility_forms"],
+                if mixed.is_empty() { String::new() } else { format!(", mixed-script words {:?}", mixed) },
+            ),
+            iocs: mixed,
+            details,
+            ..Default::default()
+        }
+    }
+}
+
+/// Rewrites configured event fields to a matchable form and counts what it changed
+#[derive(Debug)]
+pub struct TextNormalizer {
+    settings: TextNormalizationSettings,
+    normalized_events: AtomicU64,
+    evasion_signals: AtomicU64,
+}
+
+impl TextNormalizer {
+    pub fn new(settings: TextNormalizationSettings) -> Self {
+        Self { settings, normalized_events: AtomicU64::new(0), evasion_signals: AtomicU64::new(0) }
+    }
+
+    pub fn settings(&self) -> &TextNormalizationSettings {
+        &self.settings
+    }
+
+    /// Normalize the configured fields of `event` in place
+    pub fn apply(&self, event: &mut serde_json::Value) -> NormalizationReport {
+        let mut report = NormalizationReport::default();
+        for field in &self.settings.fields {
+            let pointer = format!("/{}", field.replace('.', "/"));
+            let Some(serde_json::Value::String(text)) = event.pointer_mut(&pointer) else {
+                continue;
+            };
+            if text.is_ascii() {
+                continue;
+            }
+            let (normalized, mut changes) = normalize_text(&self.settings, text);
+            if normalized != *text || !changes.mixed_script_words.is_empty() {
+                changes.field = field.clone();
+                changes.original = std::mem::replace(text, normalized);
+                report.fields.push(changes);
+            }
+        }
+        if !report.is_empty() {
+            self.normalized_events.fetch_add(1, Ordering::Relaxed);
+        }
+        report
+    }
+
+    /// The evasion detection for `report`, when it crosses the configured thresholds
+    pub fn evasion(&self, event: &serde_json::Value, report: &NormalizationReport, timestamp: u64) -> Option<AdvancedThreatResult> {
+        if !report.is_evasion(&self.settings) {
+            return None;
+        }
+        self.evasion_signals.fetch_add(1, Ordering::Relaxed);
+        Some(report.to_threat(event, timestamp))
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        HashMap::from([
+            ("text_normalization_events_normalized".to_string(), self.normalized_events.load(Ordering::Relaxed) as f64),
+            ("text_normalization_evasion_signals".to_string(), self.evasion_signals.load(Ordering::Relaxed) as f64),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_folds_fullwidth_invisible_and_homoglyphs() {
+        let settings = TextNormalizationSettings::default();
+        // Fullwidth "SELECT", a zero-width space inside "union", Cyrillic "о" and "е" in "powershell"
+        let (text, changes) = normalize_text(&settings, "ＳＥＬＥＣＴ * FROM users un\u{200B}ion; pоwеrshell -enc");
+        assert_eq!(text, "SELECT * FROM users union; powershell -enc");
+        assert_eq!(changes.compatibility, 6);
+        assert_eq!(changes.invisible, 1);
+        assert_eq!(changes.homoglyphs, 2);
+        assert_eq!(changes.mixed_script_words, vec!["pоwеrshell".to_string()]);
+
+        let (text, _) = normalize_text(&settings, "𝐜𝐦𝐝.exe ﬁle\u{00A0}x s\u{0336}elect");
+        assert_eq!(text, "cmd.exe file x select");
+    }
+
+    #[test]
+    fn test_leaves_genuine_foreign_text_alone() {
+        let settings = TextNormalizationSettings::default();
+        let message = "Пользователь вошёл в систему: ошибка пароля, Ελληνικά δεδομένα";
+        let (text, changes) = normalize_text(&settings, message);
+        assert_eq!(text, message);
+        assert_eq!(changes.suspicious_changes(), 0);
+        assert!(changes.mixed_script_words.is_empty());
+
+        // A whole word spelled in look-alikes is folded without being mixed-script
+        let (text, changes) = normalize_text(&settings, "login to рауреаӏ now");
+        assert_eq!(text, "login to paypeal now");
+        assert_eq!(changes.homoglyphs, 7);
+    }
+
+    #[test]
+    fn test_rewrites_event_fields_and_signals_evasion() {
+        let normalizer = TextNormalizer::new(TextNormalizationSettings::default());
+        let mut event = json!({
+            "message": "cmd /c who\u{200D}ami",
+            "request": {"url": "/search?q=ＤＲＯＰ table"},
+            "source_ip": "10.0.0.9",
+        });
+        let settings = TextNormalizationSettings { fields: vec!["message".to_string(), "request.url".to_string()], ..Default::default() };
+        let normalizer_nested = TextNormalizer::new(settings);
+        let report = normalizer_nested.apply(&mut event);
+        assert_eq!(event["message"], "cmd /c whoami");
+        assert_eq!(event["request"]["url"], "/search?q=DROP table");
+        assert_eq!(report.suspicious_changes(), 5);
+        let threat = normalizer_nested.evasion(&event, &report, 1).unwrap();
+        assert_eq!(threat.category, ThreatCategory::Evasion);
+        assert_eq!(threat.details["original_message"], "cmd /c who\u{200D}ami");
+        assert_eq!(threat.source_ip, "10.0.0.9");
+
+        // One non-breaking space is normalized but is not evasion
+        let mut event = json!({"message": "disk\u{00A0}full"});
+        let report = normalizer.apply(&mut event);
+        assert_eq!(event["message"], "disk full");
+        assert!(normalizer.evasion(&event, &report, 1).is_none());
+        assert_eq!(normalizer.get_metrics()["text_normalization_events_normalized"], 1.0);
+    }
+}
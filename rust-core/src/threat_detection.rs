@@ -5,6 +5,8 @@ use std::fmt;
 use serde::{Deserialize, Serialize};
 use log::{info, error, debug};
 use crate::error_handling::{SIEMResult, time};
+use crate::bridge_contract::{self, BridgeThreatEvent};
+use crate::degradation::DegradationController;
 use futures_util::StreamExt;
 use async_nats::Client;
 use uuid::Uuid;
@@ -99,6 +101,9 @@ pub struct SignaturePattern {
     pub description: String,
     pub enabled: bool,
     pub confidence: f32,
+    /// MITRE ATT&CK technique ids, e.g. `T1110`
+    #[serde(default)]
+    pub attack_techniques: Vec<String>,
 }
 
 /// Behavioral context for anomaly detection
@@ -181,6 +186,7 @@ pub struct ThreatDetectionEngine {
     stats: Arc<RwLock<DetectionStats>>,
     whitelist: Arc<RwLock<HashSet<String>>>,
     performance_metrics: Arc<RwLock<HashMap<String, f64>>>,
+    degradation: Arc<RwLock<Option<Arc<DegradationController>>>>,
 }
 
 impl ThreatDetectionEngine {
@@ -205,9 +211,15 @@ impl ThreatDetectionEngine {
             })),
             whitelist: Arc::new(RwLock::new(HashSet::new())),
             performance_metrics: Arc::new(RwLock::new(HashMap::new())),
+            degradation: Arc::new(RwLock::new(None)),
         }
     }
 
+    /// Spool threats to disk instead of dropping them while ClickHouse is degraded
+    pub fn set_degradation(&self, degradation: Arc<DegradationController>) {
+        *self.degradation.write().unwrap() = Some(degradation);
+    }
+
     /// Start the threat detection engine
     pub async fn start(&self) -> SIEMResult<()> {
         info!("🚀 Starting Ultra SIEM Threat Detection Engine...");
@@ -235,6 +247,7 @@ impl ThreatDetectionEngine {
                 category: ThreatCategory::SQLInjection,
                 severity: ThreatSeverity::High,
                 confidence: 0.85,
+                attack_techniques: vec!["T1190".to_string()],
                 description: "Detects common SQL injection patterns".to_string(),
                 enabled: true,
             },
@@ -245,6 +258,7 @@ impl ThreatDetectionEngine {
                 category: ThreatCategory::XSS,
                 severity: ThreatSeverity::High,
                 confidence: 0.90,
+                attack_techniques: vec!["T1189".to_string()],
                 description: "Detects XSS attack patterns".to_string(),
                 enabled: true,
             },
@@ -255,6 +269,7 @@ impl ThreatDetectionEngine {
                 category: ThreatCategory::BruteForce,
                 severity: ThreatSeverity::Medium,
                 confidence: 0.75,
+                attack_techniques: vec!["T1110".to_string()],
                 description: "Detects brute force attack patterns".to_string(),
                 enabled: true,
             },
@@ -290,7 +305,7 @@ impl ThreatDetectionEngine {
     async fn process_events(&self) -> SIEMResult<()> {
         info!("📡 Subscribing to Ultra SIEM events...");
         
-        let mut sub = self.nats_client.subscribe("ultra_siem.events").await?;
+        let mut sub = self.nats_client.subscribe(bridge_contract::SUBJECT_EVENTS).await?;
         
         info!("🔄 Starting event processing loop...");
         
@@ -556,9 +571,11 @@ impl ThreatDetectionEngine {
 
     /// Publish threat to NATS
     async fn publish_threat(&self, threat: &ThreatEvent) -> SIEMResult<()> {
-        let serialized = serde_json::to_string(threat)?;
-        self.nats_client.publish("ultra_siem.threats", serialized.into()).await?;
-        Ok(())
+        let degradation = self.degradation.read().unwrap().clone();
+        match degradation {
+            Some(degradation) => degradation.publish_threat(&self.nats_client, &BridgeThreatEvent::from(threat)).await,
+            None => bridge_contract::publish_threat(&self.nats_client, &BridgeThreatEvent::from(threat)).await,
+        }
     }
 
     /// Update threat statistics
@@ -677,6 +694,7 @@ mod tests {
             category: ThreatCategory::Other,
             severity: ThreatSeverity::Low,
             confidence: 0.8,
+            attack_techniques: Vec::new(),
             description: "Test signature".to_string(),
             enabled: true,
         };
@@ -0,0 +1,653 @@
+//! # Time Zones
+//!
+//! Quiet hours, change windows, scheduled reports and rendered timestamps
+//! are all local to somebody. A zone is `UTC`, a fixed offset such as
+//! `+05:30`, or an IANA name like `Europe/Berlin` read from the system tz
+//! database (`$TZDIR`, else `/usr/share/zoneinfo`). The settings name a
+//! default zone with overrides per tenant and per analyst.
+//!
+//! Turning a local wall-clock time back into UTC is DST-safe: a time that
+//! occurs twice when clocks go back resolves to its first occurrence, and a
+//! time skipped when they go forward lands just after the gap. A job set for
+//! 02:30 every day therefore neither runs twice nor gets lost.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use chrono::{DateTime, Datelike, Days, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::error_handling::{SIEMError, SIEMResult};
+
+const DEFAULT_ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+/// Default, per-tenant and per-analyst time zones
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeZoneSettings {
+    /// Zone for everything without a more specific one
+    pub default: String,
+    /// Tenant name to zone, used for routing hours, quiet hours and incident timelines
+    pub tenants: BTreeMap<String, String>,
+    /// Analyst name to zone, used for timestamps rendered for them
+    pub users: BTreeMap<String, String>,
+    /// Local `HH:MM` in the default zone that scheduled digests and reports go out at,
+    /// every whole number of days of their interval; empty counts the interval from startup
+    pub report_time: String,
+}
+
+impl Default for TimeZoneSettings {
+    fn default() -> Self {
+        Self {
+            default: "UTC".to_string(),
+            tenants: BTreeMap::new(),
+            users: BTreeMap::new(),
+            report_time: String::new(),
+        }
+    }
+}
+
+impl TimeZoneSettings {
+    pub fn validate(&self) -> SIEMResult<()> {
+        TimeZones::new(self).map(|_| ())
+    }
+
+    fn report_time(&self) -> SIEMResult<Option<NaiveTime>> {
+        if self.report_time.is_empty() {
+            return Ok(None);
+        }
+        NaiveTime::parse_from_str(&self.report_time, "%H:%M")
+            .map(Some)
+            .map_err(|_| SIEMError::Config(format!("Invalid report time '{}', expected HH:MM", self.report_time)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct LocalType {
+    /// Seconds east of UTC
+    offset: i32,
+    is_dst: bool,
+    abbreviation: String,
+}
+
+/// Day of the year a POSIX TZ rule switches on
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RuleDay {
+    /// `Mm.w.d`: weekday `d` (0 = Sunday) of week `w` of month `m`, week 5 being the last
+    MonthWeekday { month: u32, week: u32, weekday: u32 },
+    /// `Jn`: day 1 to 365, never counting February 29
+    Julian(u32),
+    /// `n`: day 0 to 365, counting February 29
+    Ordinal(u32),
+}
+
+impl RuleDay {
+    fn date(&self, year: i32) -> Option<NaiveDate> {
+        match *self {
+            RuleDay::MonthWeekday { month, week, weekday } => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let first_weekday = first.weekday().num_days_from_sunday();
+                let mut day = 1 + (weekday + 7 - first_weekday) % 7 + (week - 1) * 7;
+                while NaiveDate::from_ymd_opt(year, month, day).is_none() {
+                    day -= 7;
+                }
+                NaiveDate::from_ymd_opt(year, month, day)
+            }
+            RuleDay::Julian(day) => {
+                let leap = NaiveDate::from_ymd_opt(year, 2, 29).is_some();
+                let skip = if leap && day >= 60 { day } else { day - 1 };
+                NaiveDate::from_ymd_opt(year, 1, 1)?.checked_add_days(Days::new(skip as u64))
+            }
+            RuleDay::Ordinal(day) => NaiveDate::from_ymd_opt(year, 1, 1)?.checked_add_days(Days::new(day as u64)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DstRule {
+    dst: LocalType,
+    start: RuleDay,
+    /// Seconds after local midnight, in standard time
+    start_time: i32,
+    end: RuleDay,
+    /// Seconds after local midnight, in daylight time
+    end_time: i32,
+}
+
+/// POSIX TZ string such as `CET-1CEST,M3.5.0,M10.5.0/3`, used past the last listed transition
+#[derive(Debug, Clone, PartialEq)]
+struct PosixRule {
+    std: LocalType,
+    dst: Option<DstRule>,
+}
+
+impl PosixRule {
+    fn parse(spec: &str) -> Option<Self> {
+        let mut rest = spec;
+        let std_name = take_abbreviation(&mut rest)?;
+        let std_offset = -take_time(&mut rest)?;
+        let std = LocalType { offset: std_offset, is_dst: false, abbreviation: std_name };
+        if rest.is_empty() {
+            return Some(Self { std, dst: None });
+        }
+
+        let dst_name = take_abbreviation(&mut rest)?;
+        let dst_offset = match rest.starts_with(',') || rest.is_empty() {
+            true => std_offset + 3600,
+            false => -take_time(&mut rest)?,
+        };
+        // Without rules, POSIX leaves the dates to the implementation; the US ones are the usual choice
+        let rules = match rest.strip_prefix(',') {
+            Some(rules) => rules,
+            None if rest.is_empty() => "M3.2.0,M11.1.0",
+            None => return None,
+        };
+        let (start, end) = rules.split_once(',')?;
+        let (start, start_time) = parse_rule_day(start)?;
+        let (end, end_time) = parse_rule_day(end)?;
+        let dst = LocalType { offset: dst_offset, is_dst: true, abbreviation: dst_name };
+        Some(Self { std, dst: Some(DstRule { dst, start, start_time, end, end_time }) })
+    }
+
+    fn local_type(&self, utc: i64) -> &LocalType {
+        let Some(rule) = &self.dst else {
+            return &self.std;
+        };
+        let year = DateTime::from_timestamp(utc + self.std.offset as i64, 0).unwrap_or_default().year();
+        let instant = |day: RuleDay, time: i32, offset: i32| {
+            day.date(year).map(|date| date.and_time(NaiveTime::MIN).and_utc().timestamp() + time as i64 - offset as i64)
+        };
+        let (Some(start), Some(end)) = (
+            instant(rule.start, rule.start_time, self.std.offset),
+            instant(rule.end, rule.end_time, rule.dst.offset),
+        ) else {
+            return &self.std;
+        };
+        // Southern-hemisphere rules start daylight time late in the year and end it early the next
+        let in_dst = match start < end {
+            true => utc >= start && utc < end,
+            false => utc >= start || utc < end,
+        };
+        if in_dst {
+            &rule.dst
+        } else {
+            &self.std
+        }
+    }
+}
+
+fn take_abbreviation(rest: &mut &str) -> Option<String> {
+    let (name, remainder) = match rest.strip_prefix('<') {
+        Some(quoted) => {
+            let end = quoted.find('>')?;
+            (&quoted[..end], &quoted[end + 1..])
+        }
+        None => {
+            let end = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        }
+    };
+    if name.len() < 3 {
+        return None;
+    }
+    *rest = remainder;
+    Some(name.to_string())
+}
+
+/// `[+-]h[h][:mm[:ss]]` as seconds; hours run to 167 in rule times
+fn take_time(rest: &mut &str) -> Option<i32> {
+    let (sign, unsigned) = match rest.as_bytes().first() {
+        Some(b'-') => (-1, &rest[1..]),
+        Some(b'+') => (1, &rest[1..]),
+        _ => (1, *rest),
+    };
+    let end = unsigned.find(|c: char| !c.is_ascii_digit() && c != ':').unwrap_or(unsigned.len());
+    let mut parts = unsigned[..end].split(':');
+    let hours: i32 = parts.next()?.parse().ok()?;
+    let minutes: i32 = parts.next().map_or(Some(0), |m| m.parse().ok())?;
+    let seconds: i32 = parts.next().map_or(Some(0), |s| s.parse().ok())?;
+    if hours > 167 || minutes > 59 || seconds > 59 || parts.next().is_some() {
+        return None;
+    }
+    *rest = &unsigned[end..];
+    Some(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+fn parse_rule_day(rule: &str) -> Option<(RuleDay, i32)> {
+    let (day, time) = match rule.split_once('/') {
+        Some((day, mut time)) => {
+            let seconds = take_time(&mut time)?;
+            if !time.is_empty() {
+                return None;
+            }
+            (day, seconds)
+        }
+        None => (rule, 2 * 3600),
+    };
+    let day = if let Some(spec) = day.strip_prefix('M') {
+        let fields: Vec<u32> = spec.split('.').map(|field| field.parse().ok()).collect::<Option<_>>()?;
+        match fields[..] {
+            [month @ 1..=12, week @ 1..=5, weekday @ 0..=6] => RuleDay::MonthWeekday { month, week, weekday },
+            _ => return None,
+        }
+    } else if let Some(day) = day.strip_prefix('J') {
+        match day.parse().ok()? {
+            day @ 1..=365 => RuleDay::Julian(day),
+            _ => return None,
+        }
+    } else {
+        match day.parse().ok()? {
+            day @ 0..=365 => RuleDay::Ordinal(day),
+            _ => return None,
+        }
+    };
+    Some((day, time))
+}
+
+/// A time zone: UTC offsets over time, with their abbreviations
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    name: String,
+    /// Instants the offset changes at, ascending
+    transitions: Vec<i64>,
+    /// Index into `types` in force from each transition on
+    transition_types: Vec<usize>,
+    types: Vec<LocalType>,
+    /// Offsets past the last transition, or for all time when there are none
+    rule: Option<PosixRule>,
+}
+
+impl Zone {
+    pub fn utc() -> Self {
+        Self::fixed(0)
+    }
+
+    /// Zone at a constant offset, named like `+05:30`
+    pub fn fixed(offset_seconds: i32) -> Self {
+        let name = match offset_seconds {
+            0 => "UTC".to_string(),
+            offset => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                format!("{}{:02}:{:02}", sign, offset.abs() / 3600, offset.abs() % 3600 / 60)
+            }
+        };
+        Self {
+            transitions: Vec::new(),
+            transition_types: Vec::new(),
+            types: vec![LocalType { offset: offset_seconds, is_dst: false, abbreviation: name.clone() }],
+            name,
+            rule: None,
+        }
+    }
+
+    /// Zone following a POSIX TZ string such as `EST5EDT,M3.2.0,M11.1.0` for all time
+    pub fn posix(name: &str, spec: &str) -> SIEMResult<Self> {
+        let rule = PosixRule::parse(spec).ok_or_else(|| SIEMError::Config(format!("Invalid POSIX time zone '{}'", spec)))?;
+        Ok(Self {
+            name: name.to_string(),
+            transitions: Vec::new(),
+            transition_types: Vec::new(),
+            types: vec![rule.std.clone()],
+            rule: Some(rule),
+        })
+    }
+
+    /// Zone from the contents of a compiled tz database (TZif) file
+    pub fn from_tzif(name: &str, data: &[u8]) -> SIEMResult<Self> {
+        parse_tzif(name, data).ok_or_else(|| SIEMError::Config(format!("Time zone '{}' is not a valid TZif file", name)))
+    }
+
+    /// `UTC`, a fixed offset like `+05:30` or `-08`, or an IANA name from the system tz database
+    pub fn load(name: &str) -> SIEMResult<Self> {
+        let name = name.trim();
+        if name.is_empty() || name.eq_ignore_ascii_case("utc") || name == "Z" {
+            return Ok(Self::utc());
+        }
+        if name.starts_with('+') || name.starts_with('-') {
+            return parse_fixed(name)
+                .map(Self::fixed)
+                .ok_or_else(|| SIEMError::Config(format!("Invalid UTC offset '{}', expected +HH:MM", name)));
+        }
+        let valid = name.split('/').all(|part| {
+            !part.is_empty() && part != ".." && part != "." && part.chars().all(|c| c.is_ascii_alphanumeric() || "_-+".contains(c))
+        });
+        if !valid {
+            return Err(SIEMError::Config(format!("Invalid time zone name '{}'", name)));
+        }
+        let dir = std::env::var_os("TZDIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from(DEFAULT_ZONEINFO_DIR));
+        let data = std::fs::read(dir.join(name)).map_err(|e| SIEMError::Config(format!("Unknown time zone '{}': {}", name, e)))?;
+        Self::from_tzif(name, &data)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn local_type(&self, utc: i64) -> &LocalType {
+        let standard = || self.types.iter().find(|t| !t.is_dst).unwrap_or(&self.types[0]);
+        match self.transitions.partition_point(|&at| at <= utc) {
+            0 if self.transitions.is_empty() => self.rule.as_ref().map_or_else(standard, |rule| rule.local_type(utc)),
+            0 => standard(),
+            n if n == self.transitions.len() && self.rule.is_some() => self.rule.as_ref().map_or_else(standard, |rule| rule.local_type(utc)),
+            n => &self.types[self.transition_types[n - 1]],
+        }
+    }
+
+    pub fn offset_at(&self, utc: DateTime<Utc>) -> FixedOffset {
+        FixedOffset::east_opt(self.local_type(utc.timestamp()).offset).unwrap_or(FixedOffset::east_opt(0).unwrap())
+    }
+
+    /// Abbreviation in force at `utc`, e.g. `CEST`
+    pub fn abbreviation_at(&self, utc: DateTime<Utc>) -> &str {
+        &self.local_type(utc.timestamp()).abbreviation
+    }
+
+    pub fn is_dst_at(&self, utc: DateTime<Utc>) -> bool {
+        self.local_type(utc.timestamp()).is_dst
+    }
+
+    pub fn to_local(&self, utc: DateTime<Utc>) -> NaiveDateTime {
+        (utc + Duration::seconds(self.local_type(utc.timestamp()).offset as i64)).naive_utc()
+    }
+
+    /// The UTC instant `local` names: the first of two when clocks go back, and
+    /// the same distance past the gap when clocks go forward over it
+    pub fn from_local(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        let wall = local.and_utc().timestamp();
+        let before = self.local_type(wall - 86_400).offset as i64;
+        let after = self.local_type(wall + 86_400).offset as i64;
+        let utc = [wall - before.max(after), wall - before.min(after)]
+            .into_iter()
+            .find(|&utc| self.local_type(utc).offset as i64 == wall - utc)
+            .unwrap_or(wall - before);
+        DateTime::from_timestamp(utc, 0).unwrap_or_default()
+    }
+
+    /// `YYYY-MM-DD HH:MM ABBR` in this zone
+    pub fn format(&self, utc: DateTime<Utc>) -> String {
+        format!("{} {}", self.to_local(utc).format("%Y-%m-%d %H:%M"), self.abbreviation_at(utc))
+    }
+
+    /// Next instant after `after` at which the local clock reads `at`
+    pub fn next_daily(&self, after: DateTime<Utc>, at: NaiveTime) -> DateTime<Utc> {
+        let today = self.to_local(after).date();
+        (0..=2)
+            .filter_map(|days| today.checked_add_days(Days::new(days)))
+            .map(|date| self.from_local(date.and_time(at)))
+            .find(|&next| next > after)
+            .unwrap_or(after + Duration::days(1))
+    }
+}
+
+fn parse_fixed(name: &str) -> Option<i32> {
+    let mut rest = name;
+    let offset = take_time(&mut rest)?;
+    (rest.is_empty() && offset.abs() < 24 * 3600).then_some(offset)
+}
+
+fn parse_tzif(name: &str, data: &[u8]) -> Option<Zone> {
+    struct Counts {
+        isut: usize,
+        isstd: usize,
+        leap: usize,
+        time: usize,
+        types: usize,
+        chars: usize,
+    }
+    fn header(data: &[u8], at: usize) -> Option<(u8, Counts)> {
+        let header = data.get(at..at + 44)?;
+        if &header[..4] != b"TZif" {
+            return None;
+        }
+        let count = |i: usize| u32::from_be_bytes(header[20 + i * 4..24 + i * 4].try_into().unwrap()) as usize;
+        Some((header[4], Counts { isut: count(0), isstd: count(1), leap: count(2), time: count(3), types: count(4), chars: count(5) }))
+    }
+    fn block_len(counts: &Counts, time_size: usize) -> usize {
+        counts.time * (time_size + 1) + counts.types * 6 + counts.chars + counts.leap * (time_size + 4) + counts.isstd + counts.isut
+    }
+
+    let (version, v1) = header(data, 0)?;
+    // Version 2 and later repeat the data with 64-bit times after the 32-bit block, then a POSIX footer
+    let (counts, time_size, start) = match version {
+        0 => (v1, 4, 44),
+        _ => {
+            let v2_start = 44 + block_len(&v1, 4);
+            (header(data, v2_start)?.1, 8, v2_start + 44)
+        }
+    };
+
+    let mut at = start;
+    let mut take = |len: usize| {
+        let bytes = data.get(at..at + len);
+        at += len;
+        bytes
+    };
+    let transitions: Vec<i64> = take(counts.time * time_size)?
+        .chunks(time_size)
+        .map(|chunk| match time_size {
+            8 => i64::from_be_bytes(chunk.try_into().unwrap()),
+            _ => i32::from_be_bytes(chunk.try_into().unwrap()) as i64,
+        })
+        .collect();
+    let transition_types: Vec<usize> = take(counts.time)?.iter().map(|&index| index as usize).collect();
+    let raw_types = take(counts.types * 6)?;
+    let chars = take(counts.chars)?;
+    take(counts.leap * (time_size + 4) + counts.isstd + counts.isut)?;
+    let end = at;
+
+    let types: Vec<LocalType> = raw_types
+        .chunks(6)
+        .map(|chunk| {
+            let start = (chunk[5] as usize).min(chars.len());
+            let len = chars[start..].iter().position(|&c| c == 0).unwrap_or(chars.len() - start);
+            LocalType {
+                offset: i32::from_be_bytes(chunk[..4].try_into().unwrap()),
+                is_dst: chunk[4] != 0,
+                abbreviation: String::from_utf8_lossy(&chars[start..start + len]).into_owned(),
+            }
+        })
+        .collect();
+    if types.is_empty() || transition_types.iter().any(|&index| index >= types.len()) {
+        return None;
+    }
+
+    let rule = match version {
+        0 => None,
+        _ => data
+            .get(end..)
+            .and_then(|footer| footer.strip_prefix(b"\n"))
+            .and_then(|footer| footer.split(|&c| c == b'\n').next())
+            .and_then(|footer| std::str::from_utf8(footer).ok())
+            .filter(|footer| !footer.is_empty())
+            .and_then(PosixRule::parse),
+    };
+    Some(Zone { name: name.to_string(), transitions, transition_types, types, rule })
+}
+
+/// The zones configured for the deployment, its tenants and its analysts
+#[derive(Debug, Clone)]
+pub struct TimeZones {
+    default: Arc<Zone>,
+    tenants: HashMap<String, Arc<Zone>>,
+    users: HashMap<String, Arc<Zone>>,
+    report_time: Option<NaiveTime>,
+}
+
+impl Default for TimeZones {
+    fn default() -> Self {
+        Self {
+            default: Arc::new(Zone::utc()),
+            tenants: HashMap::new(),
+            users: HashMap::new(),
+            report_time: None,
+        }
+    }
+}
+
+impl TimeZones {
+    pub fn new(settings: &TimeZoneSettings) -> SIEMResult<Self> {
+        let load = |zones: &BTreeMap<String, String>| {
+            zones
+                .iter()
+                .map(|(owner, zone)| Ok((owner.clone(), Arc::new(Zone::load(zone)?))))
+                .collect::<SIEMResult<HashMap<_, _>>>()
+        };
+        Ok(Self {
+            default: Arc::new(Zone::load(&settings.default)?),
+            tenants: load(&settings.tenants)?,
+            users: load(&settings.users)?,
+            report_time: settings.report_time()?,
+        })
+    }
+
+    /// Whether any zone other than plain UTC is configured
+    pub fn is_configured(&self) -> bool {
+        self.default.name() != "UTC" || !self.tenants.is_empty() || !self.users.is_empty()
+    }
+
+    pub fn default_zone(&self) -> &Arc<Zone> {
+        &self.default
+    }
+
+    pub fn for_tenant(&self, tenant: Option<&str>) -> &Arc<Zone> {
+        tenant.and_then(|tenant| self.tenants.get(tenant)).unwrap_or(&self.default)
+    }
+
+    pub fn for_user(&self, user: &str) -> &Arc<Zone> {
+        self.users.get(user).unwrap_or(&self.default)
+    }
+
+    /// Zone of the tenant a detection belongs to
+    pub fn for_threat(&self, threat: &AdvancedThreatResult) -> &Arc<Zone> {
+        self.for_tenant(threat.details.get("tenant").map(String::as_str))
+    }
+
+    /// Ticker for a job every `interval_hours`, aligned to `report_time` in the default zone when set
+    pub fn report_ticker(&self, interval_hours: u64) -> ReportTicker {
+        let period = std::time::Duration::from_secs(interval_hours.max(1) * 3600);
+        match self.report_time {
+            Some(at) => {
+                let every_days = interval_hours.div_ceil(24).max(1);
+                let next = self.default.next_daily(Utc::now(), at);
+                ReportTicker::Local { zone: self.default.clone(), at, every_days, next }
+            }
+            None => ReportTicker::Interval(tokio::time::interval_at(tokio::time::Instant::now() + period, period)),
+        }
+    }
+}
+
+/// Wakes a scheduled job on a fixed interval or at the same local time of day
+#[derive(Debug)]
+pub enum ReportTicker {
+    Interval(tokio::time::Interval),
+    /// Every `every_days` days at `at` on the local clock, whatever the UTC offset that day
+    Local { zone: Arc<Zone>, at: NaiveTime, every_days: u64, next: DateTime<Utc> },
+}
+
+impl ReportTicker {
+    pub async fn tick(&mut self) {
+        match self {
+            ReportTicker::Interval(interval) => {
+                interval.tick().await;
+            }
+            ReportTicker::Local { zone, at, every_days, next } => {
+                tokio::time::sleep((*next - Utc::now()).to_std().unwrap_or_default()).await;
+                let date = zone.to_local(*next).date() + Days::new(*every_days);
+                *next = zone.from_local(date.and_time(*at));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn test_posix_rule_follows_daylight_saving() {
+        let berlin = Zone::posix("Europe/Berlin", "CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        let winter = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2024, 7, 15, 12, 0, 0).unwrap();
+        assert_eq!(berlin.format(winter), "2024-01-15 13:00 CET");
+        assert_eq!(berlin.format(summer), "2024-07-15 14:00 CEST");
+        // Clocks went forward at 01:00 UTC on the last Sunday of March
+        assert!(!berlin.is_dst_at(Utc.with_ymd_and_hms(2024, 3, 31, 0, 59, 0).unwrap()));
+        assert!(berlin.is_dst_at(Utc.with_ymd_and_hms(2024, 3, 31, 1, 0, 0).unwrap()));
+
+        let sydney = Zone::posix("Australia/Sydney", "AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert!(sydney.is_dst_at(Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap()));
+        assert!(!sydney.is_dst_at(Utc.with_ymd_and_hms(2024, 7, 15, 0, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_local_times_in_gaps_and_overlaps_resolve_once() {
+        let berlin = Zone::posix("Europe/Berlin", "CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        // 02:30 never happens on 2024-03-31; it lands half an hour after the jump
+        assert_eq!(berlin.from_local(local(2024, 3, 31, 2, 30)), Utc.with_ymd_and_hms(2024, 3, 31, 1, 30, 0).unwrap());
+        // 02:30 happens twice on 2024-10-27; the first, still in CEST, wins
+        assert_eq!(berlin.from_local(local(2024, 10, 27, 2, 30)), Utc.with_ymd_and_hms(2024, 10, 27, 0, 30, 0).unwrap());
+
+        // A daily 02:30 job runs exactly once on each changeover day
+        let at = NaiveTime::from_hms_opt(2, 30, 0).unwrap();
+        let mut next = Utc.with_ymd_and_hms(2024, 10, 25, 12, 0, 0).unwrap();
+        let runs: Vec<_> = (0..3)
+            .map(|_| {
+                next = berlin.next_daily(next, at);
+                berlin.to_local(next).date()
+            })
+            .collect();
+        assert_eq!(runs, vec![local(2024, 10, 26, 0, 0).date(), local(2024, 10, 27, 0, 0).date(), local(2024, 10, 28, 0, 0).date()]);
+    }
+
+    #[test]
+    fn test_tzif_and_tenant_resolution() {
+        // Version 2 file with one transition into daylight time and a POSIX footer
+        let mut data = Vec::new();
+        let header = |data: &mut Vec<u8>, version: u8, time: u32| {
+            data.extend_from_slice(b"TZif");
+            data.push(version);
+            data.extend_from_slice(&[0; 15]);
+            for count in [0, 0, 0, time, 2, 8] {
+                data.extend_from_slice(&u32::to_be_bytes(count));
+            }
+        };
+        let types = |data: &mut Vec<u8>| {
+            data.extend_from_slice(&(-5 * 3600i32).to_be_bytes());
+            data.extend_from_slice(&[0, 0]);
+            data.extend_from_slice(&(-4 * 3600i32).to_be_bytes());
+            data.extend_from_slice(&[1, 4]);
+            data.extend_from_slice(b"EST\0EDT\0");
+        };
+        header(&mut data, b'2', 0);
+        types(&mut data);
+        header(&mut data, b'2', 1);
+        let spring = Utc.with_ymd_and_hms(2024, 3, 10, 7, 0, 0).unwrap();
+        data.extend_from_slice(&spring.timestamp().to_be_bytes());
+        data.push(1);
+        types(&mut data);
+        data.extend_from_slice(b"\nEST5EDT,M3.2.0,M11.1.0\n");
+
+        let new_york = Zone::from_tzif("America/New_York", &data).unwrap();
+        assert_eq!(new_york.format(spring - Duration::hours(1)), "2024-03-10 01:00 EST");
+        assert_eq!(new_york.format(spring), "2024-03-10 03:00 EDT");
+        // Past the last transition the footer rule applies
+        assert_eq!(new_york.format(Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap()), "2030-01-01 07:00 EST");
+        assert!(Zone::from_tzif("broken", &data[..50]).is_err());
+
+        let settings = TimeZoneSettings {
+            tenants: BTreeMap::from([("apac".to_string(), "+08:00".to_string())]),
+            ..Default::default()
+        };
+        let zones = TimeZones::new(&settings).unwrap();
+        assert_eq!(zones.for_tenant(Some("apac")).name(), "+08:00");
+        assert_eq!(zones.for_tenant(Some("emea")).name(), "UTC");
+        assert!(Zone::load("../etc/passwd").is_err());
+        assert!(TimeZones::new(&TimeZoneSettings { report_time: "25:00".to_string(), ..Default::default() }).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! # Client TLS
+//!
+//! TLS for the connectors that speak their wire protocol directly: SMTP,
+//! Postgres and Redis. Servers are verified against the system roots plus an
+//! optional PEM bundle (`ca_file`), e.g. a private CA; there is no switch to
+//! skip verification.
+
+use std::io::BufReader;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore, ServerName};
+use tokio_rustls::TlsConnector;
+
+use crate::error_handling::{SIEMError, SIEMResult};
+
+pub type TlsStream = tokio_rustls::client::TlsStream<TcpStream>;
+
+/// A plain or TLS connection, for protocols that decide after connecting
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Transport for S {}
+
+/// Verifying TLS client shared by every connection of one connector
+#[derive(Clone)]
+pub struct TlsClient {
+    connector: TlsConnector,
+}
+
+impl std::fmt::Debug for TlsClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsClient").finish_non_exhaustive()
+    }
+}
+
+impl TlsClient {
+    /// Trust the system roots plus the certificates in `ca_file` (empty for none)
+    pub fn new(ca_file: &str) -> SIEMResult<Self> {
+        let mut roots = RootCertStore::empty();
+        // Unreadable system stores are not fatal when a CA file is given
+        for certificate in rustls_native_certs::load_native_certs().unwrap_or_default() {
+            let _ = roots.add(&rustls::Certificate(certificate.0));
+        }
+        if !ca_file.is_empty() {
+            let file = std::fs::File::open(ca_file)
+                .map_err(|e| SIEMError::Config(format!("Cannot read CA file {}: {}", ca_file, e)))?;
+            let certificates = rustls_pemfile::certs(&mut BufReader::new(file))
+                .map_err(|e| SIEMError::Config(format!("Invalid CA file {}: {}", ca_file, e)))?;
+            let (added, _) = roots.add_parsable_certificates(&certificates);
+            if added == 0 {
+                return Err(SIEMError::Config(format!("CA file {} holds no certificates", ca_file)));
+            }
+        }
+        if roots.is_empty() {
+            return Err(SIEMError::Config("No trusted CA certificates for TLS; set a CA file".to_string()));
+        }
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(Self { connector: TlsConnector::from(Arc::new(config)) })
+    }
+
+    /// Handshake over `stream`, verifying the certificate against `server`
+    pub async fn connect(&self, stream: TcpStream, server: &str) -> SIEMResult<TlsStream> {
+        let name = ServerName::try_from(server)
+            .map_err(|_| SIEMError::Config(format!("'{}' is not a valid TLS server name", server)))?;
+        self.connector
+            .connect(name, stream)
+            .await
+            .map_err(|e| SIEMError::Auth(format!("TLS handshake with {} failed: {}", server, e)))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    //! Test CA and a `localhost` / `127.0.0.1` server certificate it signed
+    use super::*;
+    use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+    use tokio_rustls::TlsAcceptor;
+
+    pub const CA_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/tls/ca.pem");
+
+    pub fn acceptor() -> TlsAcceptor {
+        let certificates = include_bytes!("../fixtures/tls/server.pem");
+        let key = include_bytes!("../fixtures/tls/server-key.pem");
+        let chain = rustls_pemfile::certs(&mut &certificates[..]).unwrap().into_iter().map(Certificate).collect();
+        let key = PrivateKey(rustls_pemfile::pkcs8_private_keys(&mut &key[..]).unwrap().remove(0));
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .unwrap();
+        TlsAcceptor::from(Arc::new(config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_verifies_server_against_ca_file() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for _ in 0..2 {
+                let (socket, _) = listener.accept().await.unwrap();
+                if let Ok(mut stream) = testing::acceptor().accept(socket).await {
+                    stream.write_all(b"hello").await.unwrap();
+                    stream.shutdown().await.unwrap();
+                }
+            }
+        });
+
+        let client = TlsClient::new(testing::CA_FILE).unwrap();
+        let mut stream = client.connect(TcpStream::connect(address).await.unwrap(), "127.0.0.1").await.unwrap();
+        let mut greeting = String::new();
+        stream.read_to_string(&mut greeting).await.unwrap();
+        assert_eq!(greeting, "hello");
+
+        // Certificate does not cover this name
+        let error = client.connect(TcpStream::connect(address).await.unwrap(), "siem.example.com").await.unwrap_err();
+        assert!(matches!(error, SIEMError::Auth(_)));
+        assert!(TlsClient::new("/nonexistent/ca.pem").is_err());
+    }
+}
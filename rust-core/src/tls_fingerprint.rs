@@ -0,0 +1,470 @@
+//! # TLS Client and Server Fingerprints
+//!
+//! Handshake metadata is reduced to JA3 (client hello) and JA3S (server hello)
+//! fingerprints, which are written back onto the event so they can be hunted
+//! on. A fingerprint is reported when it is on the configured known-malicious
+//! list or reported by a threat intel feed as a `ja3`/`ja3s` indicator, and
+//! when a fingerprint rarely seen across all traffic reaches a critical server.
+//!
+//! Client hello fields: `tls_version`, `tls_ciphers`, `tls_extensions`,
+//! `tls_curves`, `tls_point_formats`; server hello fields: `tls_server_version`,
+//! `tls_server_cipher`, `tls_server_extensions`. Lists may be arrays or
+//! `-`/`,`-separated strings of decimal or `0x` values. Sensors that already
+//! compute the hashes can send `ja3`/`ja3s` directly.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::attack;
+use crate::intel_fusion::{IntelFusion, INTEL_CONFIDENCE_DETAIL};
+use crate::ip_net;
+use crate::threat_detection::{ThreatCategory, ThreatSeverity};
+
+/// Servers remembered per fingerprint so a rare one is reported once per server
+const MAX_SERVERS_PER_FINGERPRINT: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsFingerprintSettings {
+    pub enabled: bool,
+    /// JA3 or JA3S hash -> what it is known for, e.g. `"Cobalt Strike beacon"`
+    pub known_malicious: HashMap<String, String>,
+    /// Addresses or CIDR ranges of servers where rare client fingerprints are reported
+    pub critical_servers: Vec<String>,
+    /// A fingerprint seen at most this often across all traffic counts as rare
+    pub rare_max_sightings: u64,
+    /// Fingerprints are only counted, not judged rare, for this long after start
+    pub warmup_hours: u64,
+    /// Fingerprints counted; beyond it new ones are not tracked
+    pub max_tracked_fingerprints: usize,
+}
+
+impl Default for TlsFingerprintSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            known_malicious: HashMap::new(),
+            critical_servers: Vec::new(),
+            rare_max_sightings: 3,
+            warmup_hours: 24,
+            max_tracked_fingerprints: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FingerprintKind {
+    Ja3,
+    Ja3s,
+}
+
+impl FingerprintKind {
+    /// Event field holding the hash; also the intel indicator type
+    pub fn field(&self) -> &'static str {
+        match self {
+            FingerprintKind::Ja3 => "ja3",
+            FingerprintKind::Ja3s => "ja3s",
+        }
+    }
+
+    /// Event field holding the string the hash was computed from
+    pub fn string_field(&self) -> &'static str {
+        match self {
+            FingerprintKind::Ja3 => "ja3_string",
+            FingerprintKind::Ja3s => "ja3s_string",
+        }
+    }
+}
+
+impl fmt::Display for FingerprintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FingerprintKind::Ja3 => write!(f, "JA3"),
+            FingerprintKind::Ja3s => write!(f, "JA3S"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsFingerprint {
+    pub kind: FingerprintKind,
+    /// Lowercase MD5 hex
+    pub hash: String,
+    /// Empty when the sensor sent only the hash
+    pub text: String,
+}
+
+impl TlsFingerprint {
+    /// JA3 and JA3S of the event's handshake metadata
+    pub fn from_event(event: &serde_json::Value) -> Vec<Self> {
+        [FingerprintKind::Ja3, FingerprintKind::Ja3s]
+            .into_iter()
+            .filter_map(|kind| Self::compute(event, kind).or_else(|| Self::supplied(event, kind)))
+            .collect()
+    }
+
+    fn compute(event: &serde_json::Value, kind: FingerprintKind) -> Option<Self> {
+        let text = match kind {
+            // SSLVersion,Ciphers,Extensions,EllipticCurves,EllipticCurvePointFormats
+            FingerprintKind::Ja3 => {
+                let version = tls_version(event.get("tls_version")?)?;
+                let ciphers = numbers(event.get("tls_ciphers")?)?;
+                let list = |name: &str| event.get(name).and_then(numbers).unwrap_or_default();
+                format!(
+                    "{},{},{},{},{}",
+                    version,
+                    join(&ciphers),
+                    join(&list("tls_extensions")),
+                    join(&list("tls_curves")),
+                    join(&list("tls_point_formats")),
+                )
+            }
+            // SSLVersion,Cipher,Extensions
+            FingerprintKind::Ja3s => {
+                let version = tls_version(event.get("tls_server_version")?)?;
+                let cipher = numbers(event.get("tls_server_cipher")?)?.first().copied()?;
+                let extensions = event.get("tls_server_extensions").and_then(numbers).unwrap_or_default();
+                format!("{},{},{}", version, cipher, join(&extensions))
+            }
+        };
+        Some(Self { kind, hash: md5_hex(text.as_bytes()), text })
+    }
+
+    fn supplied(event: &serde_json::Value, kind: FingerprintKind) -> Option<Self> {
+        let hash = event.get(kind.field())?.as_str()?.trim().to_ascii_lowercase();
+        let is_md5 = hash.len() == 32 && hash.bytes().all(|b| b.is_ascii_hexdigit());
+        is_md5.then(|| Self { kind, hash, text: String::new() })
+    }
+}
+
+/// Numbers of a list field; GREASE values (RFC 8701) are dropped as JA3 requires
+fn numbers(value: &serde_json::Value) -> Option<Vec<u32>> {
+    let parse = |item: &str| {
+        let item = item.trim();
+        match item.strip_prefix("0x").or_else(|| item.strip_prefix("0X")) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => item.parse().ok(),
+        }
+    };
+    let values: Option<Vec<u32>> = match value {
+        serde_json::Value::Array(items) => items
+            .iter()
+            .map(|item| item.as_u64().map(|n| n as u32).or_else(|| item.as_str().and_then(parse)))
+            .collect(),
+        serde_json::Value::String(list) if list.trim().is_empty() => Some(Vec::new()),
+        serde_json::Value::String(list) => list.split(['-', ',', ' ']).filter(|item| !item.is_empty()).map(parse).collect(),
+        serde_json::Value::Number(n) => n.as_u64().map(|n| vec![n as u32]),
+        _ => None,
+    };
+    values.map(|values| values.into_iter().filter(|v| !is_grease(*v)).collect())
+}
+
+fn is_grease(value: u32) -> bool {
+    value <= 0xffff && value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+/// Wire version number; protocol names are accepted too
+fn tls_version(value: &serde_json::Value) -> Option<u32> {
+    let named = value.as_str().and_then(|name| {
+        match name.to_ascii_lowercase().replace([' ', '_'], "").as_str() {
+            "sslv3" | "ssl3.0" => Some(0x0300),
+            "tlsv1" | "tls1.0" | "tlsv1.0" => Some(0x0301),
+            "tlsv1.1" | "tls1.1" => Some(0x0302),
+            "tlsv1.2" | "tls1.2" => Some(0x0303),
+            "tlsv1.3" | "tls1.3" => Some(0x0304),
+            _ => None,
+        }
+    });
+    named.or_else(|| numbers(value).and_then(|v| v.first().copied()))
+}
+
+fn join(values: &[u32]) -> String {
+    values.iter().map(u32::to_string).collect::<Vec<_>>().join("-")
+}
+
+/// RFC 1321 MD5, which JA3 and JA3S are defined over
+fn md5_hex(data: &[u8]) -> String {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let constants: Vec<u32> = (0..64).map(|i| ((i as f64 + 1.0).sin().abs() * 4_294_967_296.0) as u32).collect();
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    for block in message.chunks_exact(64) {
+        let words: Vec<u32> = block.chunks_exact(4).map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]])).collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f.wrapping_add(a).wrapping_add(constants[i]).wrapping_add(words[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[(i / 16) * 4 + i % 4]));
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+    state.iter().flat_map(|word| word.to_le_bytes()).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FingerprintAlertKind {
+    /// On the configured list (`source = "config"`) or a feed indicator (`source = "intel"`)
+    KnownMalicious { label: String, source: String, intel_confidence: Option<f32> },
+    /// Seen `sightings` times across all traffic when it reached a critical server
+    RareOnCriticalServer { sightings: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FingerprintAlert {
+    pub kind: FingerprintAlertKind,
+    pub fingerprint: TlsFingerprint,
+    pub client: String,
+    pub server: String,
+}
+
+impl FingerprintAlert {
+    pub fn to_threat(&self, event: &serde_json::Value, timestamp: u64) -> AdvancedThreatResult {
+        let field = |name: &str| event.get(name).and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let fingerprint = &self.fingerprint;
+        let (severity, category, confidence, technique, description) = match &self.kind {
+            FingerprintAlertKind::KnownMalicious { label, intel_confidence, .. } => (
+                ThreatSeverity::High,
+                ThreatCategory::Malware,
+                intel_confidence.unwrap_or(0.9),
+                "T1071.001",
+                format!("{} fingerprint {} between {} and {} matches known-malicious {}", fingerprint.kind, fingerprint.hash, self.client, self.server, label),
+            ),
+            FingerprintAlertKind::RareOnCriticalServer { sightings } => (
+                ThreatSeverity::Medium,
+                ThreatCategory::Network,
+                0.5,
+                "T1573",
+                format!("Rare {} fingerprint {} (seen {} times) from {} on critical server {}", fingerprint.kind, fingerprint.hash, sightings, self.client, self.server),
+            ),
+        };
+        let mut details = attack::technique_details(&[technique.to_string()]);
+        details.insert(fingerprint.kind.field().to_string(), fingerprint.hash.clone());
+        if !fingerprint.text.is_empty() {
+            details.insert(fingerprint.kind.string_field().to_string(), fingerprint.text.clone());
+        }
+        match &self.kind {
+            FingerprintAlertKind::KnownMalicious { label, source, intel_confidence } => {
+                details.insert("tls_fingerprint_alert".to_string(), "known_malicious".to_string());
+                details.insert("fingerprint_label".to_string(), label.clone());
+                details.insert("fingerprint_source".to_string(), source.clone());
+                if let Some(confidence) = intel_confidence {
+                    details.insert(INTEL_CONFIDENCE_DETAIL.to_string(), format!("{:.3}", confidence));
+                }
+            }
+            FingerprintAlertKind::RareOnCriticalServer { sightings } => {
+                details.insert("tls_fingerprint_alert".to_string(), "rare_on_critical_server".to_string());
+                details.insert("fingerprint_sightings".to_string(), sightings.to_string());
+            }
+        }
+        AdvancedThreatResult {
+            timestamp,
+            severity,
+            category,
+            confidence,
+            detection_method: "tls_fingerprint".to_string(),
+            source_ip: self.client.clone(),
+            destination_ip: self.server.clone(),
+            user_id: field("user_id"),
+            description,
+            iocs: vec![fingerprint.hash.clone()],
+            details,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct FingerprintStats {
+    sightings: u64,
+    /// Critical servers it was already reported on
+    reported: Vec<String>,
+}
+
+/// Annotates events with their fingerprints and checks them against intel and traffic-wide rarity
+#[derive(Debug)]
+pub struct TlsFingerprinter {
+    settings: TlsFingerprintSettings,
+    known_malicious: HashMap<String, String>,
+    started: u64,
+    stats: Mutex<HashMap<(FingerprintKind, String), FingerprintStats>>,
+}
+
+impl TlsFingerprinter {
+    pub fn new(settings: TlsFingerprintSettings, now: u64) -> Self {
+        let known_malicious = settings.known_malicious.iter().map(|(hash, label)| (hash.trim().to_ascii_lowercase(), label.clone())).collect();
+        Self { settings, known_malicious, started: now, stats: Mutex::new(HashMap::new()) }
+    }
+
+    /// Write the event's fingerprints onto it; returns them
+    pub fn annotate(&self, event: &mut serde_json::Value) -> Vec<TlsFingerprint> {
+        let fingerprints = TlsFingerprint::from_event(event);
+        if let Some(object) = event.as_object_mut() {
+            for fingerprint in &fingerprints {
+                object.insert(fingerprint.kind.field().to_string(), fingerprint.hash.clone().into());
+                if !fingerprint.text.is_empty() {
+                    object.insert(fingerprint.kind.string_field().to_string(), fingerprint.text.clone().into());
+                }
+            }
+        }
+        fingerprints
+    }
+
+    /// Count the event's fingerprints and report malicious ones and rare ones on critical servers
+    pub fn observe(&self, event: &serde_json::Value, intel: Option<&IntelFusion>, now: u64) -> Vec<FingerprintAlert> {
+        let field = |name: &str| event.get(name).and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let (client, server) = (field("source_ip"), field("destination_ip"));
+        let critical = ip_net::parse_ip(&server)
+            .is_some_and(|ip| self.settings.critical_servers.iter().any(|network| ip_net::network_contains(network, ip)));
+        let warmed_up = now >= self.started + self.settings.warmup_hours * 3600;
+
+        let mut alerts = Vec::new();
+        let mut stats = self.stats.lock().unwrap();
+        for fingerprint in TlsFingerprint::from_event(event) {
+            let alert = |kind| FingerprintAlert { kind, fingerprint: fingerprint.clone(), client: client.clone(), server: server.clone() };
+            if let Some(kind) = self.known_malicious(&fingerprint, intel, now) {
+                alerts.push(alert(kind));
+            }
+
+            let key = (fingerprint.kind, fingerprint.hash.clone());
+            if !stats.contains_key(&key) && stats.len() >= self.settings.max_tracked_fingerprints {
+                continue;
+            }
+            let entry = stats.entry(key).or_default();
+            entry.sightings += 1;
+            let rare = warmed_up && entry.sightings <= self.settings.rare_max_sightings;
+            if critical && rare && !entry.reported.contains(&server) && entry.reported.len() < MAX_SERVERS_PER_FINGERPRINT {
+                entry.reported.push(server.clone());
+                alerts.push(alert(FingerprintAlertKind::RareOnCriticalServer { sightings: entry.sightings }));
+            }
+        }
+        alerts
+    }
+
+    fn known_malicious(&self, fingerprint: &TlsFingerprint, intel: Option<&IntelFusion>, now: u64) -> Option<FingerprintAlertKind> {
+        if let Some(label) = self.known_malicious.get(&fingerprint.hash) {
+            return Some(FingerprintAlertKind::KnownMalicious { label: label.clone(), source: "config".to_string(), intel_confidence: None });
+        }
+        let indicator = intel?.matched(&fingerprint.hash, now).filter(|indicator| is_fingerprint_indicator(&indicator.ioc_type))?;
+        let label = match indicator.tags.is_empty() {
+            true => format!("{} indicator", indicator.ioc_type),
+            false => indicator.tags.join(", "),
+        };
+        Some(FingerprintAlertKind::KnownMalicious { label, source: "intel".to_string(), intel_confidence: Some(indicator.confidence) })
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        HashMap::from([("tls_fingerprints_tracked".to_string(), self.stats.lock().unwrap().len() as f64)])
+    }
+}
+
+/// Intel indicator types reported by the fingerprint stage rather than as plain intel matches
+pub fn is_fingerprint_indicator(ioc_type: &str) -> bool {
+    ioc_type.eq_ignore_ascii_case("ja3") || ioc_type.eq_ignore_ascii_case("ja3s")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::intel_fusion::IntelFusionSettings;
+    use crate::threat_detection::IOC;
+
+    fn handshake(client: &str, server: &str) -> serde_json::Value {
+        json!({
+            "event_type": "tls_handshake",
+            "source_ip": client,
+            "destination_ip": server,
+            "tls_version": 771,
+            "tls_ciphers": "0x0a0a-4865-4866-4867-49195",
+            "tls_extensions": [2570, 0, 23, 65281, 10, 11],
+            "tls_curves": "29,23,24",
+            "tls_point_formats": [0],
+            "tls_server_version": "TLSv1.2",
+            "tls_server_cipher": 49199,
+            "tls_server_extensions": "65281-0-11",
+        })
+    }
+
+    #[test]
+    fn test_fingerprints_follow_ja3_definition() {
+        assert_eq!(md5_hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5_hex(b"The quick brown fox jumps over the lazy dog"), "9e107d9d372bb6826bd81d3542a419d6");
+
+        let fingerprinter = TlsFingerprinter::new(TlsFingerprintSettings { enabled: true, ..Default::default() }, 0);
+        let mut event = handshake("198.51.100.7", "10.0.0.5");
+        let fingerprints = fingerprinter.annotate(&mut event);
+        assert_eq!(fingerprints.len(), 2);
+        // GREASE values 0x0a0a and 2570 are left out
+        assert_eq!(event["ja3_string"], "771,4865-4866-4867-49195,0-23-65281-10-11,29-23-24,0");
+        assert_eq!(event["ja3"], md5_hex(b"771,4865-4866-4867-49195,0-23-65281-10-11,29-23-24,0"));
+        assert_eq!(event["ja3s_string"], "771,49199,65281-0-11");
+
+        let supplied = TlsFingerprint::from_event(&json!({ "ja3": "E7D705A3286E19EA42F587B344EE6865", "ja3s": "n/a" }));
+        assert_eq!(supplied, vec![TlsFingerprint { kind: FingerprintKind::Ja3, hash: "e7d705a3286e19ea42f587b344ee6865".to_string(), text: String::new() }]);
+    }
+
+    #[test]
+    fn test_reports_malicious_and_rare_fingerprints() {
+        let ja3 = TlsFingerprint::from_event(&handshake("", ""))[0].hash.clone();
+        let ja3s = TlsFingerprint::from_event(&handshake("", ""))[1].hash.clone();
+        let intel = IntelFusion::new(IntelFusionSettings { enabled: true, ..Default::default() });
+        intel.observe(&IOC {
+            id: "1".to_string(),
+            value: ja3s.clone(),
+            ioc_type: "ja3s".to_string(),
+            confidence: 0.9,
+            source: "abuse.ch".to_string(),
+            first_seen: 1_000,
+            last_seen: 1_000,
+            tags: vec!["Cobalt Strike".to_string()],
+        });
+        let settings = TlsFingerprintSettings {
+            enabled: true,
+            known_malicious: HashMap::from([(ja3.to_uppercase(), "Trickbot".to_string())]),
+            critical_servers: vec!["10.0.0.0/24".to_string(), "2001:db8::/64".to_string()],
+            rare_max_sightings: 2,
+            warmup_hours: 1,
+            ..Default::default()
+        };
+        let fingerprinter = TlsFingerprinter::new(settings, 1_000);
+
+        let alerts = fingerprinter.observe(&handshake("198.51.100.7", "10.0.0.5"), Some(&intel), 1_000);
+        let kinds: Vec<&FingerprintAlertKind> = alerts.iter().map(|alert| &alert.kind).collect();
+        assert_eq!(kinds.len(), 2, "still warming up, so only intel matches: {:?}", kinds);
+        let threat = alerts[1].to_threat(&json!({}), 1_000);
+        assert_eq!(threat.details["fingerprint_source"], "intel");
+        assert_eq!(threat.details["fingerprint_label"], "Cobalt Strike");
+        assert!(threat.details.contains_key(INTEL_CONFIDENCE_DETAIL));
+        assert_eq!(alerts[0].to_threat(&json!({}), 1_000).details["fingerprint_label"], "Trickbot");
+
+        // Seen twice before: the third sighting is no longer rare
+        let quiet = TlsFingerprinter::new(TlsFingerprintSettings { known_malicious: HashMap::new(), ..fingerprinter.settings.clone() }, 1_000);
+        let later = 1_000 + 3_600;
+        let rare = quiet.observe(&handshake("203.0.113.9", "2001:db8::10"), None, later);
+        assert_eq!(rare.len(), 2);
+        assert!(rare.iter().all(|alert| alert.kind == FingerprintAlertKind::RareOnCriticalServer { sightings: 1 }));
+        assert!(quiet.observe(&handshake("203.0.113.9", "2001:db8::10"), None, later).is_empty(), "reported once per server");
+        assert!(quiet.observe(&handshake("203.0.113.9", "10.9.0.1"), None, later).is_empty(), "not a critical server");
+        assert!(quiet.observe(&handshake("203.0.113.9", "10.0.0.6"), None, later).is_empty(), "no longer rare");
+    }
+}
@@ -0,0 +1,538 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use futures_util::future::BoxFuture;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "compliance")]
+use crate::compliance::{sections_pdf, ComplianceSecurityEngine, ReportSection};
+use crate::error_handling::{SIEMError, SIEMResult};
+#[cfg(feature = "api")]
+use crate::query_builder::{Expr, Op, Param, QueryBuilder, TimeBucket};
+#[cfg(feature = "api")]
+use crate::query_export::ClickHouseRowSource;
+
+/// Periods before the recent window used when no seasonal history exists
+const TRAILING_PERIODS: usize = 8;
+
+/// Long-term trend analysis settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrendSettings {
+    pub enabled: bool,
+    pub interval_hours: u64,
+    /// Completed weeks analysed; more than 53 enables year-over-year baselines
+    pub weekly_periods: usize,
+    /// Completed months analysed; more than 13 enables year-over-year baselines
+    pub monthly_periods: usize,
+    /// Consecutive recent periods that must all exceed the norm
+    pub sustain_periods: usize,
+    pub threshold_sigma: f64,
+    /// Minimum ratio of the recent mean to the baseline mean
+    pub min_increase_ratio: f64,
+    /// Recent means below this are never reported
+    pub min_count: u64,
+    pub top_sources: usize,
+    /// Asset group name to source addresses; a trailing `*` matches a prefix
+    pub asset_groups: HashMap<String, Vec<String>>,
+    /// Directory for scheduled PDF report sections; empty disables
+    pub pdf_dir: String,
+}
+
+impl Default for TrendSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_hours: 24,
+            weekly_periods: 60,
+            monthly_periods: 26,
+            sustain_periods: 3,
+            threshold_sigma: 2.0,
+            min_increase_ratio: 1.5,
+            min_count: 10,
+            top_sources: 10,
+            asset_groups: HashMap::new(),
+            pdf_dir: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    Weekly,
+    Monthly,
+}
+
+impl Granularity {
+    pub const ALL: [Granularity; 2] = [Granularity::Weekly, Granularity::Monthly];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Granularity::Weekly => "weekly",
+            Granularity::Monthly => "monthly",
+        }
+    }
+
+    /// First day of the period containing `date`; weeks start on Monday
+    pub fn period_start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Granularity::Weekly => date - chrono::Duration::days(date.weekday().num_days_from_monday() as i64),
+            Granularity::Monthly => date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    fn previous(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Granularity::Weekly => start - chrono::Duration::days(7),
+            Granularity::Monthly => self.period_start(start - chrono::Duration::days(1)),
+        }
+    }
+
+    /// Periods per year
+    fn season(&self) -> usize {
+        match self {
+            Granularity::Weekly => 52,
+            Granularity::Monthly => 12,
+        }
+    }
+
+    /// The last `count` completed periods before `now`, oldest first
+    pub fn completed_periods(&self, now: DateTime<Utc>, count: usize) -> Vec<NaiveDate> {
+        let mut start = self.period_start(now.date_naive());
+        let mut periods: Vec<NaiveDate> = (0..count)
+            .map(|_| {
+                start = self.previous(start);
+                start
+            })
+            .collect();
+        periods.reverse();
+        periods
+    }
+}
+
+/// Threat count for one period, category and source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrendCount {
+    pub period_start: NaiveDate,
+    pub category: String,
+    pub source_ip: String,
+    pub count: u64,
+}
+
+/// Persistent store of historical threat counts
+pub trait TrendSource: Send + Sync {
+    /// Counts bucketed by `granularity` for periods starting on or after `since`
+    fn counts<'a>(&'a self, granularity: Granularity, since: NaiveDate) -> BoxFuture<'a, SIEMResult<Vec<TrendCount>>>;
+}
+
+#[cfg(feature = "api")]
+impl TrendSource for ClickHouseRowSource {
+    fn counts<'a>(&'a self, granularity: Granularity, since: NaiveDate) -> BoxFuture<'a, SIEMResult<Vec<TrendCount>>> {
+        Box::pin(async move {
+            let bucket = match granularity {
+                Granularity::Weekly => TimeBucket::Week,
+                Granularity::Monthly => TimeBucket::Month,
+            };
+            let query = QueryBuilder::new(self.database(), "threats")
+                .select(Expr::ToString(Box::new(Expr::Bucket("timestamp".to_string(), bucket))), Some("period_start"))
+                .select(Expr::column("threat_type"), Some("category"))
+                .select(Expr::column("source_ip"), None)
+                .select(Expr::Count, Some("count"))
+                .filter(Expr::column("timestamp"), Op::Ge, Param::Date(since))
+                .group_by(Expr::column("period_start"))
+                .group_by(Expr::column("category"))
+                .group_by(Expr::column("source_ip"))
+                .format("JSONEachRow")
+                .setting("output_format_json_quote_64bit_integers", "0")
+                .build()?;
+            self.query_rows(query.sql, &query.params)
+                .await?
+                .into_iter()
+                .map(|row| serde_json::from_value(serde_json::Value::Object(row)).map_err(SIEMError::from))
+                .collect()
+        })
+    }
+}
+
+/// Counts held in memory at any resolution, e.g. for tests and imported history
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTrendSource {
+    counts: Vec<TrendCount>,
+}
+
+impl MemoryTrendSource {
+    pub fn new(counts: Vec<TrendCount>) -> Self {
+        Self { counts }
+    }
+}
+
+impl TrendSource for MemoryTrendSource {
+    fn counts<'a>(&'a self, granularity: Granularity, since: NaiveDate) -> BoxFuture<'a, SIEMResult<Vec<TrendCount>>> {
+        let counts = self
+            .counts
+            .iter()
+            .filter(|c| c.period_start >= since)
+            .map(|c| TrendCount {
+                period_start: granularity.period_start(c.period_start),
+                ..c.clone()
+            })
+            .collect();
+        Box::pin(async move { Ok(counts) })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendDimension {
+    Category,
+    Source,
+    AssetGroup,
+}
+
+/// Counts per period for one dimension value, aligned with `TrendReport::periods`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendSeries {
+    pub dimension: TrendDimension,
+    pub value: String,
+    pub counts: Vec<u64>,
+}
+
+/// Sustained rise above the seasonal or trailing norm
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendIncrease {
+    pub dimension: TrendDimension,
+    pub value: String,
+    /// Compared with the same periods a year earlier rather than the trailing periods
+    pub seasonal: bool,
+    pub baseline_mean: f64,
+    pub baseline_std: f64,
+    pub recent: Vec<u64>,
+    pub ratio: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendReport {
+    pub generated_at: DateTime<Utc>,
+    pub granularity: Granularity,
+    /// Completed periods only; the current partial period is excluded
+    pub periods: Vec<NaiveDate>,
+    pub series: Vec<TrendSeries>,
+    pub increases: Vec<TrendIncrease>,
+}
+
+impl TrendReport {
+    pub fn build(granularity: Granularity, counts: &[TrendCount], settings: &TrendSettings, now: DateTime<Utc>) -> Self {
+        let periods = granularity.completed_periods(now, period_count(granularity, settings));
+        let index: HashMap<NaiveDate, usize> = periods.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+
+        let mut series: BTreeMap<(TrendDimension, String), Vec<u64>> = BTreeMap::new();
+        let mut sources: HashMap<String, Vec<u64>> = HashMap::new();
+        let add = |counts: &mut Vec<u64>, i: usize, count: u64| {
+            counts.resize(periods.len(), 0);
+            counts[i] += count;
+        };
+        for c in counts {
+            let Some(&i) = index.get(&granularity.period_start(c.period_start)) else {
+                continue;
+            };
+            add(series.entry((TrendDimension::Category, c.category.clone())).or_default(), i, c.count);
+            add(sources.entry(c.source_ip.clone()).or_default(), i, c.count);
+            for (group, members) in &settings.asset_groups {
+                if members.iter().any(|m| member_matches(m, &c.source_ip)) {
+                    add(series.entry((TrendDimension::AssetGroup, group.clone())).or_default(), i, c.count);
+                }
+            }
+        }
+        let mut sources: Vec<(String, Vec<u64>)> = sources.into_iter().collect();
+        sources.sort_by(|a, b| b.1.iter().sum::<u64>().cmp(&a.1.iter().sum::<u64>()).then_with(|| a.0.cmp(&b.0)));
+        for (source, counts) in sources.into_iter().take(settings.top_sources) {
+            series.insert((TrendDimension::Source, source), counts);
+        }
+
+        let series: Vec<TrendSeries> = series
+            .into_iter()
+            .map(|((dimension, value), counts)| TrendSeries { dimension, value, counts })
+            .collect();
+        let mut increases: Vec<TrendIncrease> = series
+            .iter()
+            .filter_map(|s| sustained_increase(granularity, s, settings))
+            .collect();
+        increases.sort_by(|a, b| b.ratio.total_cmp(&a.ratio));
+
+        Self {
+            generated_at: now,
+            granularity,
+            periods,
+            series,
+            increases,
+        }
+    }
+
+    /// Report text for compliance reports
+    #[cfg(feature = "compliance")]
+    pub fn section(&self) -> ReportSection {
+        let mut lines = Vec::new();
+        if let (Some(first), Some(last)) = (self.periods.first(), self.periods.last()) {
+            lines.push(format!("{} {} periods analysed, {} to {}", self.periods.len(), self.granularity.name(), first, last));
+        }
+        lines.push(format!("Sustained increases: {}", self.increases.len()));
+        for increase in &self.increases {
+            lines.push(format!(
+                "  {:?} {}: recent {:?} vs {} baseline {:.1} (sd {:.1}), x{:.1}",
+                increase.dimension,
+                increase.value,
+                increase.recent,
+                if increase.seasonal { "seasonal" } else { "trailing" },
+                increase.baseline_mean,
+                increase.baseline_std,
+                increase.ratio
+            ));
+        }
+        lines.push("Latest period by category:".to_string());
+        let mut latest: Vec<(&str, u64)> = self
+            .series
+            .iter()
+            .filter(|s| s.dimension == TrendDimension::Category)
+            .map(|s| (s.value.as_str(), s.counts.last().copied().unwrap_or(0)))
+            .collect();
+        latest.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        lines.extend(latest.into_iter().map(|(category, count)| format!("  {}: {}", category, count)));
+        let title = match self.granularity {
+            Granularity::Weekly => "Weekly threat trends",
+            Granularity::Monthly => "Monthly threat trends",
+        };
+        ReportSection {
+            title: title.to_string(),
+            generated_at: self.generated_at,
+            lines,
+        }
+    }
+}
+
+fn period_count(granularity: Granularity, settings: &TrendSettings) -> usize {
+    let configured = match granularity {
+        Granularity::Weekly => settings.weekly_periods,
+        Granularity::Monthly => settings.monthly_periods,
+    };
+    configured.max(settings.sustain_periods.max(1) + 1)
+}
+
+fn member_matches(member: &str, source_ip: &str) -> bool {
+    match member.strip_suffix('*') {
+        Some(prefix) => source_ip.starts_with(prefix),
+        None => source_ip == member,
+    }
+}
+
+fn sustained_increase(granularity: Granularity, series: &TrendSeries, settings: &TrendSettings) -> Option<TrendIncrease> {
+    let counts = &series.counts;
+    let recent_len = settings.sustain_periods.max(1);
+    if counts.len() <= recent_len {
+        return None;
+    }
+    let first_recent = counts.len() - recent_len;
+    let recent = counts[first_recent..].to_vec();
+
+    // Same periods a year earlier, widened by one either side for holiday drift
+    let seasonal: Vec<f64> = (first_recent..counts.len())
+        .filter_map(|i| i.checked_sub(granularity.season() + 1))
+        .flat_map(|i| counts[i..=i + 2].iter().map(|c| *c as f64))
+        .collect();
+    let seasonal_ok = seasonal.len() == recent_len * 3 && seasonal.iter().sum::<f64>() > 0.0;
+    let baseline: Vec<f64> = if seasonal_ok {
+        seasonal
+    } else {
+        counts[first_recent.saturating_sub(TRAILING_PERIODS)..first_recent].iter().map(|c| *c as f64).collect()
+    };
+
+    let mean = baseline.iter().sum::<f64>() / baseline.len() as f64;
+    let std = (baseline.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / baseline.len() as f64).sqrt();
+    let threshold = mean + settings.threshold_sigma * std;
+    let recent_mean = recent.iter().sum::<u64>() as f64 / recent_len as f64;
+    let ratio = recent_mean / mean.max(1.0);
+    let sustained = recent.iter().all(|c| *c as f64 > threshold)
+        && recent_mean >= settings.min_count as f64
+        && ratio >= settings.min_increase_ratio;
+    sustained.then(|| TrendIncrease {
+        dimension: series.dimension,
+        value: series.value.clone(),
+        seasonal: seasonal_ok,
+        baseline_mean: mean,
+        baseline_std: std,
+        recent,
+        ratio,
+    })
+}
+
+/// Scheduled trend job holding the latest report per granularity
+pub struct TrendAnalyzer {
+    source: Arc<dyn TrendSource>,
+    settings: TrendSettings,
+    latest: RwLock<HashMap<Granularity, TrendReport>>,
+    #[cfg(feature = "compliance")]
+    compliance: Option<Arc<ComplianceSecurityEngine>>,
+}
+
+impl TrendAnalyzer {
+    pub fn new(source: Arc<dyn TrendSource>, settings: TrendSettings) -> Self {
+        Self {
+            source,
+            settings,
+            latest: RwLock::new(HashMap::new()),
+            #[cfg(feature = "compliance")]
+            compliance: None,
+        }
+    }
+
+    /// Publish each scheduled run as a section of generated compliance reports
+    #[cfg(feature = "compliance")]
+    pub fn with_compliance(mut self, engine: Arc<ComplianceSecurityEngine>) -> Self {
+        self.compliance = Some(engine);
+        self
+    }
+
+    pub async fn run_once(&self, granularity: Granularity, now: DateTime<Utc>) -> SIEMResult<TrendReport> {
+        let periods = granularity.completed_periods(now, period_count(granularity, &self.settings));
+        let since = periods.first().copied().unwrap_or_else(|| now.date_naive());
+        let counts = self.source.counts(granularity, since).await?;
+        let report = TrendReport::build(granularity, &counts, &self.settings, now);
+        self.latest.write().unwrap().insert(granularity, report.clone());
+        Ok(report)
+    }
+
+    pub fn latest(&self, granularity: Granularity) -> Option<TrendReport> {
+        self.latest.read().unwrap().get(&granularity).cloned()
+    }
+
+    /// Recompute both granularities every `interval_hours`
+    pub fn spawn_job(self: Arc<Self>) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.settings.enabled {
+            return None;
+        }
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(self.settings.interval_hours.max(1) * 3600));
+            loop {
+                interval.tick().await;
+                for granularity in Granularity::ALL {
+                    match self.run_once(granularity, Utc::now()).await {
+                        Ok(report) => {
+                            info!("{} trend analysis: {} sustained increases", granularity.name(), report.increases.len());
+                            self.publish(&report);
+                        }
+                        Err(e) => warn!("{} trend analysis failed: {}", granularity.name(), e),
+                    }
+                }
+            }
+        }))
+    }
+
+    #[cfg(feature = "compliance")]
+    fn publish(&self, report: &TrendReport) {
+        let section = report.section();
+        if let Some(engine) = &self.compliance {
+            engine.set_report_section(&format!("trends_{}", report.granularity.name()), section.clone());
+        }
+        if !self.settings.pdf_dir.is_empty() {
+            let path = std::path::Path::new(&self.settings.pdf_dir).join(format!(
+                "trends-{}-{}.pdf",
+                report.granularity.name(),
+                report.generated_at.format("%Y%m%d")
+            ));
+            let title = section.title.clone();
+            let written = std::fs::create_dir_all(&self.settings.pdf_dir)
+                .and_then(|_| std::fs::write(&path, sections_pdf(&title, &[section])));
+            if let Err(e) = written {
+                warn!("Failed to write trend report {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "compliance"))]
+    fn publish(&self, _report: &TrendReport) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn history(granularity: Granularity, now: DateTime<Utc>, category: &str, source_ip: &str, values: &[u64]) -> Vec<TrendCount> {
+        granularity
+            .completed_periods(now, values.len())
+            .into_iter()
+            .zip(values)
+            .map(|(period_start, count)| TrendCount {
+                period_start,
+                category: category.to_string(),
+                source_ip: source_ip.to_string(),
+                count: *count,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_weekly_sustained_increase_by_category_source_and_group() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 18, 12, 0, 0).unwrap();
+        let mut steady: Vec<u64> = (0..20).map(|i| 10 + i % 3).collect();
+        let mut counts = history(Granularity::Weekly, now, "SQLInjection", "203.0.113.9", &steady);
+        steady.truncate(17);
+        steady.extend([40, 45, 52]);
+        counts.extend(history(Granularity::Weekly, now, "BruteForce", "10.20.3.17", &steady));
+        // The current partial week never counts
+        counts.push(TrendCount {
+            period_start: now.date_naive(),
+            category: "SQLInjection".to_string(),
+            source_ip: "203.0.113.9".to_string(),
+            count: 10_000,
+        });
+
+        let settings = TrendSettings {
+            asset_groups: HashMap::from([("workstations".to_string(), vec!["10.20.*".to_string()])]),
+            ..Default::default()
+        };
+        let analyzer = TrendAnalyzer::new(Arc::new(MemoryTrendSource::new(counts)), settings);
+        let report = analyzer.run_once(Granularity::Weekly, now).await.unwrap();
+
+        assert_eq!(report.periods.len(), 60);
+        assert!(*report.periods.last().unwrap() < now.date_naive());
+        let flagged: Vec<(TrendDimension, &str)> = report.increases.iter().map(|i| (i.dimension, i.value.as_str())).collect();
+        assert!(flagged.contains(&(TrendDimension::Category, "BruteForce")));
+        assert!(flagged.contains(&(TrendDimension::Source, "10.20.3.17")));
+        assert!(flagged.contains(&(TrendDimension::AssetGroup, "workstations")));
+        assert_eq!(flagged.len(), 3);
+        assert!(report.increases.iter().all(|i| !i.seasonal && i.recent == vec![40, 45, 52]));
+        assert_eq!(analyzer.latest(Granularity::Weekly).unwrap().increases.len(), 3);
+        assert!(analyzer.latest(Granularity::Monthly).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_monthly_seasonal_norm_suppresses_expected_peaks() {
+        let now = Utc.with_ymd_and_hms(2026, 1, 15, 0, 0, 0).unwrap();
+        // Feb 2024 .. Dec 2025; phishing peaks September to January every year
+        let months = Granularity::Monthly.completed_periods(now, 23);
+        let seasonal: Vec<u64> = months.iter().map(|m| if m.month() >= 9 || m.month() == 1 { 40 } else { 10 }).collect();
+        let mut rising = seasonal.clone();
+        let last = rising.len() - 3;
+        rising[last..].copy_from_slice(&[90, 95, 110]);
+        let mut counts = history(Granularity::Monthly, now, "Phishing", "198.51.100.4", &seasonal);
+        counts.extend(history(Granularity::Monthly, now, "Ransomware", "198.51.100.5", &rising));
+
+        let analyzer = TrendAnalyzer::new(Arc::new(MemoryTrendSource::new(counts)), TrendSettings::default());
+        let report = analyzer.run_once(Granularity::Monthly, now).await.unwrap();
+        let categories: Vec<&TrendIncrease> =
+            report.increases.iter().filter(|i| i.dimension == TrendDimension::Category).collect();
+        assert_eq!(categories.len(), 1);
+        assert_eq!(categories[0].value, "Ransomware");
+        assert!(categories[0].seasonal);
+
+        #[cfg(feature = "compliance")]
+        {
+            let section = report.section();
+            assert!(section.lines.iter().any(|l| l.contains("Ransomware")));
+            assert!(sections_pdf(&section.title, std::slice::from_ref(&section)).starts_with(b"%PDF"));
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::advanced_threat_detection::AdvancedThreatResult;
+
+/// Weights of the triage formula; they are normalized by their sum
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriageWeights {
+    pub confidence: f64,
+    pub entity_risk: f64,
+    pub asset_criticality: f64,
+    pub intel_match: f64,
+}
+
+impl Default for TriageWeights {
+    fn default() -> Self {
+        Self {
+            confidence: 0.35,
+            entity_risk: 0.25,
+            asset_criticality: 0.25,
+            intel_match: 0.15,
+        }
+    }
+}
+
+/// Triage scoring settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriageSettings {
+    pub weights: TriageWeights,
+    /// Criticality (0.0 - 1.0) of known assets, keyed by IP, host name or user id
+    pub asset_criticality: HashMap<String, f64>,
+    pub default_asset_criticality: f64,
+}
+
+impl Default for TriageSettings {
+    fn default() -> Self {
+        Self {
+            weights: TriageWeights::default(),
+            asset_criticality: HashMap::new(),
+            default_asset_criticality: 0.3,
+        }
+    }
+}
+
+/// One input of the triage formula
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriageFactor {
+    pub name: String,
+    /// Factor value in 0.0 - 1.0
+    pub value: f64,
+    pub weight: f64,
+    /// Points this factor adds to the priority
+    pub contribution: f64,
+}
+
+/// Priority (0 - 100) with its factor breakdown
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriageScore {
+    pub priority: f64,
+    pub factors: Vec<TriageFactor>,
+}
+
+/// Ranks incidents by a weighted combination of detection and context signals
+#[derive(Debug, Clone, Default)]
+pub struct TriageScorer {
+    settings: TriageSettings,
+}
+
+impl TriageScorer {
+    pub fn new(settings: TriageSettings) -> Self {
+        Self { settings }
+    }
+
+    pub fn score(&self, threat: &AdvancedThreatResult) -> TriageScore {
+        let weights = &self.settings.weights;
+        let inputs = [
+            ("confidence", threat.confidence as f64, weights.confidence),
+            ("entity_risk", entity_risk(threat), weights.entity_risk),
+            ("asset_criticality", self.asset_criticality(threat), weights.asset_criticality),
+            ("intel_match", intel_match(threat), weights.intel_match),
+        ];
+        let total_weight: f64 = inputs.iter().map(|(_, _, weight)| weight.max(0.0)).sum();
+
+        let factors: Vec<TriageFactor> = inputs
+            .iter()
+            .map(|(name, value, weight)| {
+                let value = value.clamp(0.0, 1.0);
+                let weight = weight.max(0.0);
+                let contribution = if total_weight > 0.0 { 100.0 * value * weight / total_weight } else { 0.0 };
+                TriageFactor {
+                    name: name.to_string(),
+                    value,
+                    weight,
+                    contribution,
+                }
+            })
+            .collect();
+
+        TriageScore {
+            priority: factors.iter().map(|factor| factor.contribution).sum(),
+            factors,
+        }
+    }
+
+    fn asset_criticality(&self, threat: &AdvancedThreatResult) -> f64 {
+        let host = threat.details.get("host").map(String::as_str).unwrap_or_default();
+        [threat.destination_ip.as_str(), threat.source_ip.as_str(), threat.user_id.as_str(), host]
+            .iter()
+            .filter_map(|key| self.settings.asset_criticality.get(*key))
+            .copied()
+            .fold(None, |max: Option<f64>, value| Some(max.map_or(value, |m| m.max(value))))
+            .unwrap_or(self.settings.default_asset_criticality)
+    }
+}
+
+/// Behavioral risk of the involved entity, or an upstream `entity_risk` detail
+fn entity_risk(threat: &AdvancedThreatResult) -> f64 {
+    let behavioral = threat.behavioral_context.as_ref().map(|context| context.risk_score as f64);
+    let reported = threat.details.get("entity_risk").and_then(|value| value.parse::<f64>().ok());
+    behavioral.into_iter().chain(reported).fold(0.0, f64::max)
+}
+
+fn intel_match(threat: &AdvancedThreatResult) -> f64 {
+    let flagged = threat.details.get("intel_match").map(|value| value == "true").unwrap_or(false);
+    if flagged || !threat.iocs.is_empty() {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_factor_breakdown_sums_to_priority() {
+        let mut settings = TriageSettings::default();
+        settings.asset_criticality.insert("10.0.0.10".to_string(), 1.0);
+        let scorer = TriageScorer::new(settings);
+
+        let threat = AdvancedThreatResult {
+            confidence: 0.8,
+            destination_ip: "10.0.0.10".to_string(),
+            iocs: vec!["malware_hash_1".to_string()],
+            ..Default::default()
+        };
+        let score = scorer.score(&threat);
+
+        assert_eq!(score.factors.len(), 4);
+        let sum: f64 = score.factors.iter().map(|f| f.contribution).sum();
+        assert!((score.priority - sum).abs() < 1e-9);
+        // 0.35*0.8 + 0.25*0 + 0.25*1 + 0.15*1 = 0.68
+        assert!((score.priority - 68.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_critical_asset_outranks_default_asset() {
+        let mut settings = TriageSettings::default();
+        settings.asset_criticality.insert("db-prod".to_string(), 0.9);
+        let scorer = TriageScorer::new(settings);
+
+        let mut critical = AdvancedThreatResult { confidence: 0.5, ..Default::default() };
+        critical.details.insert("host".to_string(), "db-prod".to_string());
+        let ordinary = AdvancedThreatResult { confidence: 0.5, ..Default::default() };
+
+        assert!(scorer.score(&critical).priority > scorer.score(&ordinary).priority);
+    }
+}
@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use bytes::{Bytes, BytesMut};
+use hyper::body::HttpBody;
+use hyper::client::HttpConnector;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Client, Request, Response, StatusCode, Uri};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::advanced_threat_detection::{AdvancedThreatDetectionEngine, AdvancedThreatResult};
+use crate::error_handling::{time, SIEMError, SIEMResult};
+use crate::forensic::{ForensicJournal, HeldBackKind};
+use crate::threat_detection::ThreatSeverity;
+use crate::web_access::{self, HTTP_EVENT_TYPE};
+
+/// Cookie carrying the answer to a challenge page
+pub const CHALLENGE_COOKIE: &str = "ultra_siem_challenge";
+
+/// Connection-scoped headers that a proxy must not forward
+const HOP_BY_HOP_HEADERS: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// What the proxy does with a request whose detections reach a severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WafAction {
+    /// Forward and only record the detections
+    Log,
+    /// Forward once the client has answered a JavaScript cookie challenge
+    Challenge,
+    /// Reject with 403
+    Block,
+}
+
+/// Action per detection severity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WafActions {
+    pub low: WafAction,
+    pub medium: WafAction,
+    pub high: WafAction,
+    pub critical: WafAction,
+}
+
+impl Default for WafActions {
+    fn default() -> Self {
+        Self {
+            low: WafAction::Log,
+            medium: WafAction::Challenge,
+            high: WafAction::Block,
+            critical: WafAction::Block,
+        }
+    }
+}
+
+impl WafActions {
+    fn for_severity(&self, severity: &ThreatSeverity) -> WafAction {
+        match severity {
+            ThreatSeverity::Low => self.low,
+            ThreatSeverity::Medium => self.medium,
+            ThreatSeverity::High => self.high,
+            ThreatSeverity::Critical => self.critical,
+        }
+    }
+}
+
+/// Inline reverse-proxy inspection in front of a plain-HTTP application
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WafSettings {
+    pub enabled: bool,
+    pub listen_addr: String,
+    /// Base URL of the protected application, e.g. `http://127.0.0.1:8080`
+    pub upstream: String,
+    pub actions: WafActions,
+    /// Detections below this confidence are only logged
+    pub min_confidence: f32,
+    /// Larger request bodies are rejected with 413
+    pub max_body_bytes: usize,
+    /// Leading part of the body included in the inspected message
+    pub inspect_body_bytes: usize,
+    /// Take the client address from `X-Forwarded-For`; only behind a trusted load balancer
+    pub trust_forwarded_for: bool,
+    /// Key for challenge cookies; empty generates one per process, so restarts re-challenge clients
+    pub challenge_secret: String,
+}
+
+impl Default for WafSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_addr: "0.0.0.0:8090".to_string(),
+            upstream: "http://127.0.0.1:8080".to_string(),
+            actions: WafActions::default(),
+            min_confidence: 0.7,
+            max_body_bytes: 1024 * 1024,
+            inspect_body_bytes: 8192,
+            trust_forwarded_for: false,
+            challenge_secret: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct WafCounters {
+    requests: AtomicU64,
+    logged: AtomicU64,
+    challenged: AtomicU64,
+    blocked: AtomicU64,
+    upstream_errors: AtomicU64,
+}
+
+/// Scores proxied requests with the detection engine and applies the configured action
+pub struct WafProxy {
+    settings: WafSettings,
+    engine: Arc<AdvancedThreatDetectionEngine>,
+    client: Client<HttpConnector>,
+    upstream: Uri,
+    challenge_secret: String,
+    counters: WafCounters,
+    forensic: Option<Arc<ForensicJournal>>,
+}
+
+impl WafProxy {
+    pub fn new(settings: WafSettings, engine: Arc<AdvancedThreatDetectionEngine>) -> SIEMResult<Self> {
+        let upstream: Uri = settings
+            .upstream
+            .parse()
+            .map_err(|e| SIEMError::Config(format!("Invalid WAF upstream {}: {}", settings.upstream, e)))?;
+        if upstream.scheme_str() != Some("http") || upstream.authority().is_none() {
+            return Err(SIEMError::Config(format!("WAF upstream must be an http:// URL, got {}", settings.upstream)));
+        }
+        let challenge_secret = if settings.challenge_secret.is_empty() {
+            uuid::Uuid::new_v4().to_string()
+        } else {
+            settings.challenge_secret.clone()
+        };
+        Ok(Self {
+            settings,
+            engine,
+            client: Client::new(),
+            upstream,
+            challenge_secret,
+            counters: WafCounters::default(),
+            forensic: None,
+        })
+    }
+
+    /// Journal blocks and challenges instead of enforcing them while forensic mode is on
+    pub fn with_forensic_journal(mut self, journal: Arc<ForensicJournal>) -> Self {
+        self.forensic = Some(journal).filter(|journal| journal.enabled());
+        self
+    }
+
+    /// Strongest action over the detections of one request
+    pub fn decide(&self, threats: &[AdvancedThreatResult]) -> WafAction {
+        threats
+            .iter()
+            .map(|threat| match threat.confidence >= self.settings.min_confidence {
+                true => self.settings.actions.for_severity(&threat.severity),
+                false => WafAction::Log,
+            })
+            .max()
+            .unwrap_or(WafAction::Log)
+    }
+
+    pub async fn handle(&self, remote: SocketAddr, request: Request<Body>) -> Response<Body> {
+        self.counters.requests.fetch_add(1, Ordering::Relaxed);
+        let client_ip = self.client_ip(remote, request.headers());
+        let (mut parts, body) = request.into_parts();
+        let body = match read_body(body, self.settings.max_body_bytes).await {
+            Ok(body) => body,
+            Err(status) => return plain_response(status, "Request body rejected"),
+        };
+
+        let event = self.request_event(&client_ip, &parts, &body);
+        let threats = match self.engine.process_event(event).await {
+            Ok(threats) => threats,
+            Err(e) => {
+                // Fail open: an engine error must not take the protected application down
+                warn!("⚠️ WAF inspection failed for {}: {}", client_ip, e);
+                Vec::new()
+            }
+        };
+        let mut action = self.decide(&threats);
+        if action == WafAction::Challenge && self.has_passed_challenge(&client_ip, &parts.headers) {
+            action = WafAction::Log;
+        }
+        let reference = threats.first().map(|t| t.threat_id.clone()).unwrap_or_default();
+        if let Some(journal) = self.forensic.as_ref().filter(|_| action != WafAction::Log) {
+            let detail = format!("{:?} {} {} from {} ({})", action, parts.method, parts.uri, client_ip, reference);
+            journal.record(HeldBackKind::ResponseAction, "waf", detail, None);
+            action = WafAction::Log;
+        }
+        match action {
+            WafAction::Block => {
+                self.counters.blocked.fetch_add(1, Ordering::Relaxed);
+                warn!("🛑 WAF blocked {} {} from {} ({})", parts.method, parts.uri, client_ip, reference);
+                return plain_response(StatusCode::FORBIDDEN, &format!("Request blocked (reference {})", reference));
+            }
+            WafAction::Challenge => {
+                self.counters.challenged.fetch_add(1, Ordering::Relaxed);
+                info!("🧩 WAF challenged {} {} from {} ({})", parts.method, parts.uri, client_ip, reference);
+                return self.challenge_response(&client_ip);
+            }
+            WafAction::Log if !threats.is_empty() => {
+                self.counters.logged.fetch_add(1, Ordering::Relaxed);
+                info!("📝 WAF passed {} {} from {} with {} detections ({})", parts.method, parts.uri, client_ip, threats.len(), reference);
+            }
+            WafAction::Log => {}
+        }
+
+        parts.uri = match self.upstream_uri(&parts.uri) {
+            Ok(uri) => uri,
+            Err(e) => return plain_response(StatusCode::BAD_REQUEST, &e.to_string()),
+        };
+        strip_hop_by_hop(&mut parts.headers);
+        append_forwarded_for(&mut parts.headers, &remote);
+        match self.client.request(Request::from_parts(parts, Body::from(body))).await {
+            Ok(mut response) => {
+                strip_hop_by_hop(response.headers_mut());
+                response
+            }
+            Err(e) => {
+                self.counters.upstream_errors.fetch_add(1, Ordering::Relaxed);
+                warn!("⚠️ WAF upstream {} failed: {}", self.settings.upstream, e);
+                plain_response(StatusCode::BAD_GATEWAY, "Upstream unavailable")
+            }
+        }
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed) as f64;
+        HashMap::from([
+            ("waf_requests".to_string(), load(&self.counters.requests)),
+            ("waf_logged".to_string(), load(&self.counters.logged)),
+            ("waf_challenged".to_string(), load(&self.counters.challenged)),
+            ("waf_blocked".to_string(), load(&self.counters.blocked)),
+            ("waf_upstream_errors".to_string(), load(&self.counters.upstream_errors)),
+        ])
+    }
+
+    fn client_ip(&self, remote: SocketAddr, headers: &HeaderMap) -> String {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .map(|ip| ip.trim().to_string())
+            .filter(|ip| !ip.is_empty());
+        match forwarded {
+            Some(ip) if self.settings.trust_forwarded_for => ip,
+            _ => remote.ip().to_string(),
+        }
+    }
+
+    /// Normalized `http_request` event; signatures match the decoded request line and body in `message`
+    fn request_event(&self, client_ip: &str, parts: &hyper::http::request::Parts, body: &Bytes) -> serde_json::Value {
+        let header = |name: header::HeaderName| parts.headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+        let url = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+        let inspected = &body[..body.len().min(self.settings.inspect_body_bytes)];
+        let mut message = format!("{} {}", parts.method, web_access::percent_decode(url));
+        if !inspected.is_empty() {
+            message.push(' ');
+            message.push_str(&web_access::percent_decode(&String::from_utf8_lossy(inspected).replace('+', " ")));
+        }
+        serde_json::json!({
+            "timestamp": time::current_timestamp().unwrap_or_default(),
+            "source": "waf",
+            "event_type": HTTP_EVENT_TYPE,
+            "source_ip": client_ip,
+            "host": header(header::HOST),
+            "http_method": parts.method.as_str(),
+            "url": url,
+            "user_agent": header(header::USER_AGENT),
+            "message": message,
+        })
+    }
+
+    fn upstream_uri(&self, uri: &Uri) -> Result<Uri, hyper::http::Error> {
+        let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+        Uri::builder()
+            .scheme("http")
+            .authority(self.upstream.authority().map(|a| a.as_str()).unwrap_or_default())
+            .path_and_query(format!("{}{}", self.upstream.path().trim_end_matches('/'), path))
+            .build()
+    }
+
+    fn challenge_token(&self, client_ip: &str) -> String {
+        let digest = Sha256::digest(format!("{}|{}", self.challenge_secret, client_ip).as_bytes());
+        digest.iter().take(16).map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn has_passed_challenge(&self, client_ip: &str, headers: &HeaderMap) -> bool {
+        let expected = format!("{}={}", CHALLENGE_COOKIE, self.challenge_token(client_ip));
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .any(|cookie| cookie.trim() == expected)
+    }
+
+    /// Page that sets the challenge cookie from script and reloads; clients without JavaScript stay stuck
+    fn challenge_response(&self, client_ip: &str) -> Response<Body> {
+        let page = format!(
+            "<!doctype html><html><head><title>Checking your browser</title></head><body>\
+             <noscript>JavaScript is required to continue.</noscript>\
+             <script>document.cookie=\"{}={}; path=/; SameSite=Lax\";location.reload();</script>\
+             </body></html>",
+            CHALLENGE_COOKIE,
+            self.challenge_token(client_ip)
+        );
+        let mut response = Response::new(Body::from(page));
+        *response.status_mut() = StatusCode::FORBIDDEN;
+        response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("text/html; charset=utf-8"));
+        response.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        response
+    }
+}
+
+/// Serve the inspection proxy until the listener fails
+pub async fn serve(proxy: Arc<WafProxy>) -> SIEMResult<()> {
+    let addr: SocketAddr = proxy
+        .settings
+        .listen_addr
+        .parse()
+        .map_err(|e| SIEMError::Config(format!("Invalid WAF listen address {}: {}", proxy.settings.listen_addr, e)))?;
+    info!("🚀 Starting WAF inspection proxy on {} for {}", addr, proxy.settings.upstream);
+    let make_service = make_service_fn(move |connection: &AddrStream| {
+        let (proxy, remote) = (proxy.clone(), connection.remote_addr());
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let proxy = proxy.clone();
+                async move { Ok::<_, Infallible>(proxy.handle(remote, request).await) }
+            }))
+        }
+    });
+    hyper::Server::try_bind(&addr)
+        .map_err(|e| SIEMError::Other(format!("Failed to bind WAF proxy on {}: {}", addr, e)))?
+        .serve(make_service)
+        .await
+        .map_err(|e| SIEMError::Other(format!("WAF proxy error: {}", e)))
+}
+
+async fn read_body(mut body: Body, limit: usize) -> Result<Bytes, StatusCode> {
+    let mut buffer = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| StatusCode::BAD_REQUEST)?;
+        if buffer.len() + chunk.len() > limit {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(buffer.freeze())
+}
+
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(name);
+    }
+}
+
+fn append_forwarded_for(headers: &mut HeaderMap, remote: &SocketAddr) {
+    let chain = match headers.get("x-forwarded-for").and_then(|v| v.to_str().ok()) {
+        Some(existing) => format!("{}, {}", existing, remote.ip()),
+        None => remote.ip().to_string(),
+    };
+    if let Ok(value) = HeaderValue::from_str(&chain) {
+        headers.insert("x-forwarded-for", value);
+    }
+}
+
+fn plain_response(status: StatusCode, text: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(text.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain; charset=utf-8"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_threat_detection::AdvancedThreatConfig;
+
+    async fn spawn_upstream() -> SocketAddr {
+        let make_service = make_service_fn(|_: &AddrStream| async {
+            Ok::<_, Infallible>(service_fn(|request: Request<Body>| async move {
+                let forwarded = request.headers().get("x-forwarded-for").cloned();
+                let body = format!("upstream {} xff={:?}", request.uri(), forwarded);
+                Ok::<_, Infallible>(Response::new(Body::from(body)))
+            }))
+        });
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    async fn proxy(settings: WafSettings) -> WafProxy {
+        let config = AdvancedThreatConfig { behavioral_enabled: false, anomaly_enabled: false, ..Default::default() };
+        let mut engine = AdvancedThreatDetectionEngine::new(config);
+        engine.start().await.unwrap();
+        WafProxy::new(settings, Arc::new(engine)).unwrap()
+    }
+
+    async fn send(proxy: &WafProxy, uri: &str, cookie: Option<String>) -> (StatusCode, String) {
+        let mut request = Request::get(uri);
+        if let Some(cookie) = cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+        let response = proxy.handle("203.0.113.7:50000".parse().unwrap(), request.body(Body::empty()).unwrap()).await;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_clean_requests_pass_and_attacks_are_blocked() {
+        let upstream = spawn_upstream().await;
+        let proxy = proxy(WafSettings { upstream: format!("http://{}", upstream), ..Default::default() }).await;
+
+        let (status, body) = send(&proxy, "/products?page=2", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "upstream /products?page=2 xff=Some(\"203.0.113.7\")");
+
+        // Percent-encoded injection is decoded before signature matching
+        let (status, body) = send(&proxy, "/search?q=1%20UNION%20SELECT%20password%20FROM%20users", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body.starts_with("Request blocked"));
+        assert_eq!(proxy.get_metrics()["waf_blocked"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_challenge_passes_once_cookie_is_presented() {
+        let upstream = spawn_upstream().await;
+        let actions = WafActions { high: WafAction::Challenge, ..Default::default() };
+        let proxy = proxy(WafSettings { upstream: format!("http://{}", upstream), actions, ..Default::default() }).await;
+
+        let uri = "/comment?text=%3Cscript%3Ealert(1)%3C/script%3E";
+        let (status, page) = send(&proxy, uri, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let token = proxy.challenge_token("203.0.113.7");
+        assert!(page.contains(&format!("{}={}", CHALLENGE_COOKIE, token)));
+
+        let (status, body) = send(&proxy, uri, Some(format!("theme=dark; {}={}", CHALLENGE_COOKIE, token))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with("upstream /comment"));
+        assert_eq!(proxy.get_metrics()["waf_challenged"], 1.0);
+        assert_eq!(proxy.get_metrics()["waf_logged"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_forensic_mode_forwards_instead_of_blocking() {
+        use crate::forensic::ForensicSettings;
+
+        let upstream = spawn_upstream().await;
+        let journal = Arc::new(ForensicJournal::open(ForensicSettings { enabled: true, journal_path: String::new(), ..Default::default() }).unwrap());
+        let actions = WafActions { medium: WafAction::Block, high: WafAction::Challenge, ..Default::default() };
+        let proxy = proxy(WafSettings { upstream: format!("http://{}", upstream), actions, ..Default::default() })
+            .await
+            .with_forensic_journal(journal.clone());
+
+        let (status, body) = send(&proxy, "/search?q=1%20UNION%20SELECT%20password%20FROM%20users", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with("upstream /search"));
+        let (status, _) = send(&proxy, "/comment?text=%3Cscript%3Ealert(1)%3C/script%3E", None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let metrics = proxy.get_metrics();
+        assert_eq!((metrics["waf_blocked"], metrics["waf_challenged"], metrics["waf_logged"]), (0.0, 0.0, 2.0));
+        let held_back = journal.report(10);
+        assert_eq!(held_back.totals[&HeldBackKind::ResponseAction], 2);
+        assert!(held_back.recent.iter().all(|op| op.component == "waf"));
+    }
+}
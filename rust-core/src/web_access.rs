@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::attack;
+use crate::advanced_threat_detection::AdvancedThreatResult;
+use crate::content_pack::{ContentPack, ContentPackManifest};
+use crate::error_handling::time;
+use crate::parsing::{PipelineConfig, ProcessorConfig};
+use crate::threat_detection::{ThreatCategory, ThreatSeverity};
+
+/// Name of the built-in pack shipping the access log parsers
+pub const PACK_NAME: &str = "web-access-logs";
+/// `event_type` of parsed access log lines
+pub const HTTP_EVENT_TYPE: &str = "http_request";
+
+/// Apache/Nginx `combined` format; the referrer and user agent are optional (`common` format)
+const ACCESS_LOG_PATTERN: &str = r#"^%{IPORHOST:source_ip} %{NOTSPACE:ident} %{NOTSPACE:http_user} \[%{HTTPDATE:http_time}\] "%{WORD:http_method} %{NOTSPACE:url}(?: HTTP/%{NUMBER:http_version})?" %{INT:http_status:int} (?:%{INT:bytes:int}|-)(?: "%{DATA:referrer}" "%{DATA:user_agent}")?"#;
+
+/// Access log detections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebAccessSettings {
+    pub enabled: bool,
+    pub window_seconds: u64,
+    /// 404 responses per source and window that raise a detection
+    pub not_found_threshold: u32,
+    /// Path prefixes of login endpoints, matched case-insensitively without the query string
+    pub login_paths: Vec<String>,
+    /// Responses to a login POST counted as failed; form logins often re-render with 200
+    pub login_failure_statuses: Vec<u16>,
+    /// Failed login POSTs per source and window that raise a detection
+    pub login_threshold: u32,
+    /// Lowercase user-agent substrings of known scanners
+    pub scanner_user_agents: Vec<String>,
+    pub max_tracked_sources: usize,
+}
+
+impl Default for WebAccessSettings {
+    fn default() -> Self {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        Self {
+            enabled: true,
+            window_seconds: 300,
+            not_found_threshold: 100,
+            login_paths: strings(&["/login", "/signin", "/wp-login.php", "/user/login", "/api/login", "/auth/login", "/session"]),
+            login_failure_statuses: vec![200, 401, 403],
+            login_threshold: 20,
+            scanner_user_agents: strings(&[
+                "sqlmap", "nikto", "nmap", "masscan", "zgrab", "gobuster", "dirbuster", "wpscan", "nuclei", "acunetix", "nessus",
+                "openvas", "ffuf", "feroxbuster", "wfuzz", "whatweb", "jaeles",
+            ]),
+            max_tracked_sources: 10_000,
+        }
+    }
+}
+
+/// Fields of one access log line
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    pub source_ip: String,
+    pub method: String,
+    pub url: String,
+    pub status: u16,
+    pub user_agent: String,
+}
+
+impl HttpRequest {
+    /// Events parsed by the access log pipelines, or any event carrying `url` and `http_status`
+    pub fn from_event(event: &serde_json::Value) -> Option<Self> {
+        let text = |name: &str| event.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let status = match event.get("http_status")? {
+            serde_json::Value::Number(number) => number.as_u64()?,
+            serde_json::Value::String(text) => text.parse().ok()?,
+            _ => return None,
+        };
+        Some(Self {
+            source_ip: text("source_ip"),
+            method: text("http_method").to_uppercase(),
+            url: event.get("url")?.as_str()?.to_string(),
+            status: u16::try_from(status).ok()?,
+            user_agent: text("user_agent"),
+        })
+    }
+
+    /// Lowercase path with the query string removed and percent-encoding undone
+    fn path(&self) -> String {
+        let path = self.url.split(['?', '#']).next().unwrap_or_default();
+        percent_decode(path).to_lowercase()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebAlertKind {
+    ScannerUserAgent,
+    NotFoundBurst,
+    CredentialStuffing,
+    PathTraversal,
+}
+
+impl WebAlertKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebAlertKind::ScannerUserAgent => "scanner_user_agent",
+            WebAlertKind::NotFoundBurst => "not_found_burst",
+            WebAlertKind::CredentialStuffing => "credential_stuffing",
+            WebAlertKind::PathTraversal => "path_traversal",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebAlert {
+    pub kind: WebAlertKind,
+    pub source_ip: String,
+    /// Requests counted towards the alert
+    pub count: u32,
+    /// User agent or URL that triggered it
+    pub evidence: String,
+}
+
+impl WebAlert {
+    pub fn to_threat(&self, timestamp: u64, window_seconds: u64) -> AdvancedThreatResult {
+        let (severity, category, technique, description) = match self.kind {
+            WebAlertKind::ScannerUserAgent => (
+                ThreatSeverity::Medium,
+                ThreatCategory::Network,
+                "T1595.002",
+                format!("Web vulnerability scanner from {}: {}", self.source_ip, self.evidence),
+            ),
+            WebAlertKind::NotFoundBurst => (
+                ThreatSeverity::Medium,
+                ThreatCategory::Network,
+                "T1595.003",
+                format!("{} not-found responses to {} within {} seconds", self.count, self.source_ip, window_seconds),
+            ),
+            WebAlertKind::CredentialStuffing => (
+                ThreatSeverity::High,
+                ThreatCategory::BruteForce,
+                "T1110.004",
+                format!("{} failed login requests from {} within {} seconds on {}", self.count, self.source_ip, window_seconds, self.evidence),
+            ),
+            WebAlertKind::PathTraversal => (
+                ThreatSeverity::High,
+                ThreatCategory::Other,
+                "T1190",
+                format!("Path traversal attempt from {}: {}", self.source_ip, self.evidence),
+            ),
+        };
+        let mut details = attack::technique_details(&[technique.to_string()]);
+        details.insert("web_alert".to_string(), self.kind.as_str().to_string());
+        details.insert("request_count".to_string(), self.count.to_string());
+        details.insert("evidence".to_string(), self.evidence.clone());
+        AdvancedThreatResult {
+            timestamp,
+            severity,
+            category,
+            confidence: 0.8,
+            detection_method: "web_access".to_string(),
+            source_ip: self.source_ip.clone(),
+            description,
+            iocs: vec![self.evidence.clone()],
+            details,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counter {
+    start: u64,
+    count: u32,
+    alerted: bool,
+}
+
+impl Counter {
+    /// Count one request; `true` exactly when the window reaches `threshold`
+    fn hit(&mut self, now: u64, window_seconds: u64, threshold: u32) -> bool {
+        if self.count == 0 || now.saturating_sub(self.start) > window_seconds {
+            *self = Counter { start: now, ..Default::default() };
+        }
+        self.count += 1;
+        if self.alerted || self.count < threshold {
+            return false;
+        }
+        self.alerted = true;
+        true
+    }
+}
+
+#[derive(Debug, Default)]
+struct SourceState {
+    last_seen: u64,
+    not_found: Counter,
+    logins: Counter,
+    scanner_alerted_at: Option<u64>,
+}
+
+/// Scanner, 404 burst, credential stuffing and path traversal detection over access logs
+#[derive(Debug)]
+pub struct WebAccessDetector {
+    settings: WebAccessSettings,
+    sources: Mutex<HashMap<String, SourceState>>,
+}
+
+impl WebAccessDetector {
+    pub fn new(settings: WebAccessSettings) -> Self {
+        Self {
+            settings,
+            sources: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn settings(&self) -> &WebAccessSettings {
+        &self.settings
+    }
+
+    pub fn observe(&self, event: &serde_json::Value) -> Vec<WebAlert> {
+        self.observe_at(event, time::current_timestamp().unwrap_or_default())
+    }
+
+    pub fn observe_at(&self, event: &serde_json::Value, now: u64) -> Vec<WebAlert> {
+        let Some(request) = HttpRequest::from_event(event) else {
+            return Vec::new();
+        };
+        let path = request.path();
+        let alert = |kind, count, evidence: &str| WebAlert {
+            kind,
+            source_ip: request.source_ip.clone(),
+            count,
+            evidence: evidence.to_string(),
+        };
+        let mut alerts = Vec::new();
+        if is_path_traversal(&request.url) {
+            alerts.push(alert(WebAlertKind::PathTraversal, 1, &request.url));
+        }
+        if request.source_ip.is_empty() {
+            return alerts;
+        }
+
+        let window = self.settings.window_seconds;
+        let mut sources = self.sources.lock().unwrap();
+        if !sources.contains_key(&request.source_ip) && sources.len() >= self.settings.max_tracked_sources {
+            sources.retain(|_, state| now.saturating_sub(state.last_seen) <= window);
+            if sources.len() >= self.settings.max_tracked_sources {
+                return alerts;
+            }
+        }
+        let state = sources.entry(request.source_ip.clone()).or_default();
+        state.last_seen = now;
+
+        let user_agent = request.user_agent.to_lowercase();
+        if let Some(scanner) = self.settings.scanner_user_agents.iter().find(|s| user_agent.contains(s.as_str())) {
+            if state.scanner_alerted_at.is_none_or(|at| now.saturating_sub(at) > window) {
+                state.scanner_alerted_at = Some(now);
+                alerts.push(alert(WebAlertKind::ScannerUserAgent, 1, scanner));
+            }
+        }
+        if request.status == 404 && state.not_found.hit(now, window, self.settings.not_found_threshold) {
+            alerts.push(alert(WebAlertKind::NotFoundBurst, state.not_found.count, &path));
+        }
+        let is_login = request.method == "POST" && self.settings.login_paths.iter().any(|p| path.starts_with(&p.to_lowercase()));
+        if is_login
+            && self.settings.login_failure_statuses.contains(&request.status)
+            && state.logins.hit(now, window, self.settings.login_threshold)
+        {
+            alerts.push(alert(WebAlertKind::CredentialStuffing, state.logins.count, &path));
+        }
+        alerts
+    }
+
+    pub fn get_metrics(&self) -> HashMap<String, f64> {
+        HashMap::from([("web_access_tracked_sources".to_string(), self.sources.lock().unwrap().len() as f64)])
+    }
+}
+
+/// `../` style escapes, also percent- and double-encoded, and well-known traversal targets
+pub fn is_path_traversal(url: &str) -> bool {
+    let decoded = percent_decode(&percent_decode(url)).to_lowercase().replace('\\', "/");
+    ["../", "/..;", "/etc/passwd", "/etc/shadow", "/proc/self/environ", "win.ini", "boot.ini"]
+        .iter()
+        .any(|marker| decoded.contains(marker))
+        || decoded.ends_with("/..")
+}
+
+pub(crate) fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn access_log_pipeline(source: &str) -> PipelineConfig {
+    PipelineConfig {
+        name: format!("{}-access", source),
+        source: source.to_string(),
+        processors: vec![
+            ProcessorConfig::Grok {
+                field: "message".to_string(),
+                patterns: vec![ACCESS_LOG_PATTERN.to_string()],
+                pattern_definitions: HashMap::new(),
+            },
+            ProcessorConfig::Date {
+                field: "http_time".to_string(),
+                formats: vec!["%d/%b/%Y:%H:%M:%S %z".to_string()],
+                target: "timestamp".to_string(),
+            },
+            ProcessorConfig::Set {
+                field: "event_type".to_string(),
+                value: HTTP_EVENT_TYPE.into(),
+            },
+        ],
+    }
+}
+
+/// Built-in pack with the Apache and Nginx access log parsers
+pub fn content_pack() -> ContentPack {
+    ContentPack {
+        manifest: ContentPackManifest {
+            name: PACK_NAME.to_string(),
+            version: "1.0.0".to_string(),
+            publisher: "ultra-siem".to_string(),
+            description: "Apache/Nginx combined access log parsing into url, http_status and user_agent fields".to_string(),
+        },
+        signatures: Vec::new(),
+        correlation_rules: Vec::new(),
+        quantum_patterns: Vec::new(),
+        pipelines: vec![access_log_pipeline("nginx"), access_log_pipeline("apache")],
+        dashboards: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::ParsingPipelines;
+
+    fn parse(source: &str, line: &str) -> serde_json::Value {
+        let pipelines = ParsingPipelines::from_config(&content_pack().pipelines).unwrap();
+        let mut event = serde_json::json!({ "source": source, "message": line });
+        pipelines.process(&mut event);
+        event
+    }
+
+    #[test]
+    fn test_combined_log_parsing_traversal_and_scanner() {
+        let event = parse(
+            "nginx",
+            r#"203.0.113.9 - - [16/Oct/2026:10:00:00 +0000] "GET /static/..%252f..%252fetc/passwd HTTP/1.1" 404 153 "-" "Mozilla/5.0 (compatible; Nmap Scripting Engine)""#,
+        );
+        assert_eq!(event["event_type"], HTTP_EVENT_TYPE);
+        assert_eq!(event["http_status"], 404);
+        assert_eq!(event["timestamp"], 1_792_144_800);
+        assert!(event.get("parse_failures").is_none());
+
+        let detector = WebAccessDetector::new(WebAccessSettings::default());
+        let kinds: Vec<WebAlertKind> = detector.observe_at(&event, 0).iter().map(|a| a.kind).collect();
+        assert_eq!(kinds, vec![WebAlertKind::PathTraversal, WebAlertKind::ScannerUserAgent]);
+        // The scanner is reported once per window
+        assert!(detector.observe_at(&event, 10).iter().all(|a| a.kind != WebAlertKind::ScannerUserAgent));
+
+        // Apache common format has no user agent
+        let common = parse("apache", r#"198.51.100.2 - alice [16/Oct/2026:10:00:00 +0000] "GET /index.html HTTP/1.0" 200 2326"#);
+        assert_eq!((common["url"].as_str(), common["http_status"].as_u64()), (Some("/index.html"), Some(200)));
+        assert!(!is_path_traversal("/docs/intro..html"));
+    }
+
+    #[test]
+    fn test_not_found_burst_and_credential_stuffing() {
+        let settings = WebAccessSettings { not_found_threshold: 5, login_threshold: 3, ..Default::default() };
+        let detector = WebAccessDetector::new(settings);
+        let request = |method: &str, url: &str, status: u16| {
+            serde_json::json!({ "source_ip": "192.0.2.50", "http_method": method, "url": url, "http_status": status, "user_agent": "curl/8.0" })
+        };
+        let mut alerts = Vec::new();
+        for i in 0..10 {
+            alerts.extend(detector.observe_at(&request("GET", &format!("/backup{}.zip", i), 404), 100 + i));
+        }
+        assert_eq!(alerts.iter().map(|a| (a.kind, a.count)).collect::<Vec<_>>(), vec![(WebAlertKind::NotFoundBurst, 5)]);
+
+        // Successful logins redirect and are not counted
+        assert!(detector.observe_at(&request("POST", "/Login?next=/", 302), 120).is_empty());
+        let stuffing: Vec<WebAlert> = (0..3).flat_map(|i| detector.observe_at(&request("POST", "/login", 401), 121 + i)).collect();
+        assert_eq!(stuffing.len(), 1);
+        assert_eq!(stuffing[0].kind, WebAlertKind::CredentialStuffing);
+        assert_eq!(stuffing[0].to_threat(123, 300).details["web_alert"], "credential_stuffing");
+    }
+}
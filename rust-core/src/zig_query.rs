@@ -0,0 +1,238 @@
+//! # Zig Query Engine Client
+//!
+//! Client side of a planned IPC protocol for offloading analytical queries to
+//! the Zig engine in `zig-query/`: length-prefixed JSON frames over a Unix
+//! socket, falling back to a local query when the engine is disabled or does not
+//! answer. The Zig side does not serve this protocol yet, so nothing in the core
+//! routes queries through it; the client and its framing are kept, and tested
+//! against an in-process stand-in, so the engine can be wired in once it does.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use log::{debug, warn};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::error_handling::{SIEMError, SIEMResult};
+
+/// Zig query engine IPC settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZigQuerySettings {
+    pub enabled: bool,
+    pub socket_path: String,
+    pub timeout_ms: u64,
+    pub max_frame_bytes: usize,
+}
+
+impl Default for ZigQuerySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            socket_path: "/tmp/ultra-siem-zig-query.sock".to_string(),
+            timeout_ms: 2000,
+            max_frame_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Analytical query sent to the Zig engine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZigQueryRequest {
+    pub id: String,
+    pub query: String,
+    pub params: serde_json::Value,
+}
+
+/// Response frame returned by the Zig engine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZigQueryResponse {
+    pub id: String,
+    pub ok: bool,
+    #[serde(default)]
+    pub result: serde_json::Value,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Where a query was finally executed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum QueryBackend {
+    Zig,
+    Local,
+}
+
+/// Query result with the backend that produced it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryOutcome {
+    pub backend: QueryBackend,
+    pub result: serde_json::Value,
+    pub execution_time_ms: f64,
+}
+
+/// IPC client that offloads heavy analytical queries to the Zig engine
+pub struct ZigQueryClient {
+    settings: ZigQuerySettings,
+}
+
+impl ZigQueryClient {
+    pub fn new(settings: ZigQuerySettings) -> Self {
+        Self { settings }
+    }
+
+    /// Run the query on the Zig engine, falling back to `local` when it is disabled or fails
+    pub async fn execute<F>(&self, query: &str, params: serde_json::Value, local: F) -> SIEMResult<QueryOutcome>
+    where
+        F: Future<Output = SIEMResult<serde_json::Value>>,
+    {
+        let start = Instant::now();
+        if self.settings.enabled {
+            match self.query_remote(query, params.clone()).await {
+                Ok(result) => {
+                    return Ok(QueryOutcome {
+                        backend: QueryBackend::Zig,
+                        result,
+                        execution_time_ms: start.elapsed().as_secs_f64() * 1000.0,
+                    })
+                }
+                Err(e) => warn!("⚠️ Zig query engine unavailable, executing locally: {}", e),
+            }
+        }
+
+        let result = local.await?;
+        Ok(QueryOutcome {
+            backend: QueryBackend::Local,
+            result,
+            execution_time_ms: start.elapsed().as_secs_f64() * 1000.0,
+        })
+    }
+
+    /// Send a single query over the IPC socket, without fallback
+    pub async fn query_remote(&self, query: &str, params: serde_json::Value) -> SIEMResult<serde_json::Value> {
+        let request = ZigQueryRequest {
+            id: Uuid::new_v4().to_string(),
+            query: query.to_string(),
+            params,
+        };
+        let timeout = Duration::from_millis(self.settings.timeout_ms);
+        let response = tokio::time::timeout(timeout, self.round_trip(&request))
+            .await
+            .map_err(|_| SIEMError::Performance(format!("Zig query timed out after {}ms", self.settings.timeout_ms)))??;
+
+        if response.id != request.id {
+            return Err(SIEMError::Other(format!(
+                "Zig query response id mismatch: expected {}, got {}",
+                request.id, response.id
+            )));
+        }
+        if !response.ok {
+            return Err(SIEMError::Other(format!(
+                "Zig query failed: {}",
+                response.error.unwrap_or_else(|| "unknown error".to_string())
+            )));
+        }
+        debug!("Zig query {} completed", request.id);
+        Ok(response.result)
+    }
+
+    #[cfg(unix)]
+    async fn round_trip(&self, request: &ZigQueryRequest) -> SIEMResult<ZigQueryResponse> {
+        let mut stream = tokio::net::UnixStream::connect(&self.settings.socket_path).await?;
+        write_frame(&mut stream, &serde_json::to_vec(request)?).await?;
+        let frame = read_frame(&mut stream, self.settings.max_frame_bytes).await?;
+        Ok(serde_json::from_slice(&frame)?)
+    }
+
+    #[cfg(not(unix))]
+    async fn round_trip(&self, _request: &ZigQueryRequest) -> SIEMResult<ZigQueryResponse> {
+        Err(SIEMError::Config("Zig query IPC requires Unix domain sockets".to_string()))
+    }
+}
+
+/// Write a length-prefixed frame (u32 big-endian length, then payload)
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, payload: &[u8]) -> SIEMResult<()> {
+    let length = u32::try_from(payload.len())
+        .map_err(|_| SIEMError::Validation(format!("Frame of {} bytes is too large", payload.len())))?;
+    writer.write_all(&length.to_be_bytes()).await?;
+    writer.write_all(payload).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Read a length-prefixed frame, rejecting frames above `max_bytes`
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R, max_bytes: usize) -> SIEMResult<Vec<u8>> {
+    let mut header = [0u8; 4];
+    reader.read_exact(&mut header).await?;
+    let length = u32::from_be_bytes(header) as usize;
+    if length > max_bytes {
+        return Err(SIEMError::Validation(format!(
+            "Frame of {} bytes exceeds limit of {} bytes",
+            length, max_bytes
+        )));
+    }
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload).await?;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn local_count() -> SIEMResult<serde_json::Value> {
+        Ok(serde_json::json!({ "count": 0 }))
+    }
+
+    #[tokio::test]
+    async fn test_frame_round_trip_and_limit() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        write_frame(&mut client, b"{\"id\":\"1\"}").await.unwrap();
+        assert_eq!(read_frame(&mut server, 1024).await.unwrap(), b"{\"id\":\"1\"}");
+
+        write_frame(&mut client, &[0u8; 64]).await.unwrap();
+        assert!(matches!(read_frame(&mut server, 16).await, Err(SIEMError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_to_local_when_socket_missing() {
+        let client = ZigQueryClient::new(ZigQuerySettings {
+            enabled: true,
+            socket_path: "/nonexistent/ultra-siem-zig.sock".to_string(),
+            ..Default::default()
+        });
+
+        let outcome = client.execute("SELECT count()", serde_json::Value::Null, local_count()).await.unwrap();
+        assert_eq!(outcome.backend, QueryBackend::Local);
+        assert_eq!(outcome.result["count"], 0);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_zig_engine_round_trip() {
+        let socket_path = std::env::temp_dir().join(format!("zig-query-{}.sock", Uuid::new_v4()));
+        let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let request: ZigQueryRequest = serde_json::from_slice(&read_frame(&mut stream, 1024).await.unwrap()).unwrap();
+            let response = ZigQueryResponse {
+                id: request.id,
+                ok: true,
+                result: serde_json::json!({ "count": 42 }),
+                error: None,
+            };
+            write_frame(&mut stream, &serde_json::to_vec(&response).unwrap()).await.unwrap();
+        });
+
+        let client = ZigQueryClient::new(ZigQuerySettings {
+            enabled: true,
+            socket_path: socket_path.to_string_lossy().to_string(),
+            ..Default::default()
+        });
+        let outcome = client.execute("SELECT count()", serde_json::Value::Null, local_count()).await.unwrap();
+        let _ = std::fs::remove_file(&socket_path);
+
+        assert_eq!(outcome.backend, QueryBackend::Zig);
+        assert_eq!(outcome.result["count"], 42);
+    }
+}
@@ -0,0 +1,532 @@
+//! Pins the stable public API: the crate-root re-exports and the prelude.
+//!
+//! These tests fail to compile when an item of the stable surface is removed,
+//! renamed or moved. Update them only together with a deliberate API change,
+//! and bump the major version when the change is incompatible.
+
+use std::any::type_name;
+
+/// Names of the stable types, reached through `$path`
+macro_rules! surface {
+    ($path:ident) => {{
+        #[allow(unused_mut)]
+        let mut names = vec![
+            type_name::<$path::SIEMError>(),
+            type_name::<$path::SIEMResult<()>>(),
+            type_name::<$path::SiemConfig>(),
+            type_name::<$path::UltraSiem>(),
+            type_name::<$path::UltraSiemBuilder>(),
+            type_name::<$path::SignaturePattern>(),
+            type_name::<$path::ThreatCategory>(),
+            type_name::<$path::ThreatSeverity>(),
+            type_name::<$path::AdvancedThreatConfig>(),
+            type_name::<$path::AdvancedThreatDetectionEngine>(),
+            type_name::<$path::AdvancedThreatResult>(),
+            type_name::<$path::MLAnomalyEngine>(),
+            type_name::<$path::MLAnomalyResult>(),
+            type_name::<$path::QuantumDetector>(),
+        ];
+        #[cfg(feature = "response")]
+        names.extend([
+            type_name::<$path::AlertConfig>(),
+            type_name::<$path::Incident>(),
+            type_name::<$path::IncidentResponseEngine>(),
+            type_name::<$path::IncidentSeverity>(),
+            type_name::<$path::IncidentStatus>(),
+            type_name::<$path::SOARConfig>(),
+        ]);
+        names
+    }};
+}
+
+#[test]
+fn test_crate_root_and_prelude_expose_the_same_types() {
+    use siem_rust_core::prelude;
+    assert_eq!(surface!(siem_rust_core), surface!(prelude));
+    assert_eq!(siem_rust_core::DEFAULT_CONFIG_PATH, prelude::DEFAULT_CONFIG_PATH);
+}
+
+/// Crate-root items that are not in the prelude
+#[test]
+fn test_crate_root_only_items() {
+    #[allow(unused_mut)]
+    let mut names: Vec<&str> = Vec::new();
+    #[cfg(feature = "gpu")]
+    names.extend([
+        type_name::<siem_rust_core::GPUPerformanceProfile>(),
+        type_name::<siem_rust_core::UniversalNvidiaGPUEngine>(),
+        type_name::<siem_rust_core::AnomalyDetectionKernel>(),
+    ]);
+    #[cfg(all(feature = "gpu", feature = "response"))]
+    names.extend([
+        type_name::<siem_rust_core::UltraSIEMCore>(),
+        type_name::<siem_rust_core::SystemStats>(),
+        type_name::<siem_rust_core::PerformanceStats>(),
+    ]);
+    assert!(names.iter().all(|name| name.starts_with("siem_rust_core::")));
+}
+
+#[test]
+fn test_prelude_only_items() {
+    use siem_rust_core::prelude::*;
+
+    let mut config = SiemConfig::default();
+    config.event_time.side_output_capacity = 0;
+    let report: DiagnosticReport = ConfigChecker::new(config).check_static();
+    assert!(report.has_fatal());
+    assert!(report.diagnostics.iter().any(|d| d.severity == DiagnosticSeverity::Fatal && d.component == "event_time"));
+
+    let pattern = QuantumPattern::new("web_shell", "cmd.exe|powershell");
+    assert!(pattern.validate().is_ok());
+}
+
+#[tokio::test]
+async fn test_embedded_facade_detects_through_the_stable_api() {
+    use siem_rust_core::prelude::*;
+
+    let siem: SIEMResult<UltraSiem> = UltraSiem::builder().build().await;
+    let threats = siem
+        .unwrap()
+        .process_event(serde_json::json!({ "message": "<script>alert(1)</script>" }))
+        .await
+        .unwrap();
+    assert!(threats.iter().all(|threat: &AdvancedThreatResult| threat.confidence >= 0.0));
+}
+
+/// Every public item, crate-root re-exports and module paths alike, must match
+/// `tests/public-api.txt`. The items come from the rustdoc JSON of the default
+/// features, which only a nightly toolchain emits, so the check runs when
+/// `PUBLIC_API_JSON` points at it, as in CI:
+///
+/// ```text
+/// cargo +nightly-2026-05-19 rustdoc --lib -- -Z unstable-options --output-format json
+/// PUBLIC_API_JSON=target/doc/siem_rust_core.json cargo test --test public_api
+/// ```
+///
+/// After a deliberate API change, rerun with `UPDATE_PUBLIC_API=1` and commit the snapshot.
+#[test]
+fn test_public_items_match_snapshot() {
+    const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/public-api.txt");
+
+    let Ok(json_path) = std::env::var("PUBLIC_API_JSON") else {
+        eprintln!("PUBLIC_API_JSON not set; public API snapshot not checked");
+        return;
+    };
+    let json = std::fs::read_to_string(&json_path).unwrap_or_else(|e| panic!("{}: {}", json_path, e));
+    let krate: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        krate["format_version"],
+        snapshot::FORMAT_VERSION,
+        "rustdoc JSON format changed; build it with the nightly pinned in CI"
+    );
+    let items = snapshot::public_items(&krate);
+
+    if std::env::var_os("UPDATE_PUBLIC_API").is_some() {
+        let text: String = items.iter().map(|line| format!("{}\n", line)).collect();
+        std::fs::write(SNAPSHOT, text).unwrap();
+        return;
+    }
+    let pinned: std::collections::BTreeSet<String> =
+        std::fs::read_to_string(SNAPSHOT).unwrap().lines().map(str::to_string).collect();
+    let added: Vec<_> = items.difference(&pinned).collect();
+    let removed: Vec<_> = pinned.difference(&items).collect();
+    assert!(
+        added.is_empty() && removed.is_empty(),
+        "public API differs from tests/public-api.txt\nadded:\n{:#?}\nremoved:\n{:#?}",
+        added,
+        removed
+    );
+}
+
+/// Renders rustdoc JSON into one sorted line per public item, in the spirit of `cargo public-api`
+mod snapshot {
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+
+    /// rustdoc JSON format the renderer understands
+    pub const FORMAT_VERSION: u64 = 57;
+
+    pub fn public_items(krate: &Value) -> BTreeSet<String> {
+        let mut renderer = Renderer { index: &krate["index"], lines: BTreeSet::new(), modules: Vec::new() };
+        if let Some(root) = renderer.get(&krate["root"]) {
+            renderer.item(root, "", None);
+        }
+        renderer.lines
+    }
+
+    struct Renderer<'a> {
+        index: &'a Value,
+        lines: BTreeSet<String>,
+        /// Modules being rendered, so re-export cycles terminate
+        modules: Vec<String>,
+    }
+
+    impl<'a> Renderer<'a> {
+        fn get(&self, id: &Value) -> Option<&'a Value> {
+            self.index.get(id.to_string())
+        }
+
+        fn public(item: &Value) -> bool {
+            item["visibility"] == "public"
+        }
+
+        /// Render `item` as `parent::name`, where `name` overrides its own name for re-exports
+        fn item(&mut self, item: &'a Value, parent: &str, name: Option<&str>) {
+            let Some((kind, inner)) = item["inner"].as_object().and_then(|inner| inner.iter().next()) else {
+                return;
+            };
+            let name = name.or(item["name"].as_str()).unwrap_or_default();
+            let path = if parent.is_empty() { name.to_string() } else { format!("{}::{}", parent, name) };
+            match kind.as_str() {
+                "module" => {
+                    self.lines.insert(format!("pub mod {}", path));
+                    let id = item["id"].to_string();
+                    if self.modules.contains(&id) {
+                        return;
+                    }
+                    self.modules.push(id);
+                    for child in inner["items"].as_array().into_iter().flatten() {
+                        if let Some(child) = self.get(child).filter(|child| Self::public(child)) {
+                            self.item(child, &path, None);
+                        }
+                    }
+                    self.modules.pop();
+                }
+                "use" => match self.get(&inner["id"]) {
+                    Some(target) if inner["is_glob"] == true => {
+                        for child in target["inner"]["module"]["items"].as_array().into_iter().flatten() {
+                            if let Some(child) = self.get(child).filter(|child| Self::public(child)) {
+                                self.item(child, parent, None);
+                            }
+                        }
+                    }
+                    Some(target) => self.item(target, parent, inner["name"].as_str()),
+                    None => {
+                        let alias = inner["name"].as_str().unwrap_or_default();
+                        self.lines.insert(format!("pub use {}::{} = {}", parent, alias, inner["source"].as_str().unwrap_or_default()));
+                    }
+                },
+                "struct" => {
+                    self.lines.insert(format!("pub struct {}{}{}", path, generics(&inner["generics"]), where_clause(&inner["generics"])));
+                    let kind = &inner["kind"];
+                    for field in kind["plain"]["fields"].as_array().into_iter().flatten() {
+                        if let Some(field) = self.get(field).filter(|field| Self::public(field)) {
+                            let name = field["name"].as_str().unwrap_or_default();
+                            self.lines.insert(format!("pub {}::{}: {}", path, name, ty(&field["inner"]["struct_field"])));
+                        }
+                    }
+                    for (position, field) in kind["tuple"].as_array().into_iter().flatten().enumerate() {
+                        if let Some(field) = self.get(field) {
+                            self.lines.insert(format!("pub {}::{}: {}", path, position, ty(&field["inner"]["struct_field"])));
+                        }
+                    }
+                    self.impls(&inner["impls"], &path);
+                }
+                "enum" => {
+                    self.lines.insert(format!("pub enum {}{}{}", path, generics(&inner["generics"]), where_clause(&inner["generics"])));
+                    for variant in inner["variants"].as_array().into_iter().flatten() {
+                        if let Some(variant) = self.get(variant) {
+                            let name = variant["name"].as_str().unwrap_or_default();
+                            let fields = self.variant_fields(&variant["inner"]["variant"]["kind"]);
+                            self.lines.insert(format!("pub {}::{}{}", path, name, fields));
+                        }
+                    }
+                    self.impls(&inner["impls"], &path);
+                }
+                "function" => {
+                    self.lines.insert(format!("pub {}", function(&path, inner)));
+                }
+                "trait" => {
+                    let bounds = bounds(&inner["bounds"]);
+                    let supertraits = if bounds.is_empty() { String::new() } else { format!(": {}", bounds) };
+                    self.lines.insert(format!("pub trait {}{}{}{}", path, generics(&inner["generics"]), supertraits, where_clause(&inner["generics"])));
+                    for member in inner["items"].as_array().into_iter().flatten() {
+                        if let Some(member) = self.get(member) {
+                            self.member(member, &path, "");
+                        }
+                    }
+                }
+                "constant" => {
+                    self.lines.insert(format!("pub const {}: {}", path, ty(&inner["type"])));
+                }
+                "static" => {
+                    self.lines.insert(format!("pub static {}: {}", path, ty(&inner["type"])));
+                }
+                "type_alias" => {
+                    self.lines.insert(format!("pub type {}{} = {}", path, generics(&inner["generics"]), ty(&inner["type"])));
+                }
+                other => {
+                    self.lines.insert(format!("pub {} {}", other, path));
+                }
+            }
+        }
+
+        /// Trait and inherent impls written in the crate; auto-trait and blanket impls are left out
+        fn impls(&mut self, impls: &Value, path: &str) {
+            for id in impls.as_array().into_iter().flatten() {
+                let Some(block) = self.get(id).map(|block| &block["inner"]["impl"]) else { continue };
+                if block["is_synthetic"] == true || !block["blanket_impl"].is_null() {
+                    continue;
+                }
+                let negative = if block["is_negative"] == true { "!" } else { "" };
+                match block["trait"].is_null() {
+                    true => {
+                        for member in block["items"].as_array().into_iter().flatten() {
+                            if let Some(member) = self.get(member).filter(|member| Self::public(member)) {
+                                self.member(member, path, "pub ");
+                            }
+                        }
+                    }
+                    false => {
+                        self.lines.insert(format!(
+                            "impl{} {}{} for {}{}",
+                            generics(&block["generics"]),
+                            negative,
+                            path_type(&block["trait"]),
+                            path,
+                            where_clause(&block["generics"])
+                        ));
+                    }
+                }
+            }
+        }
+
+        /// Associated function, constant or type of a trait or impl
+        fn member(&mut self, member: &Value, owner: &str, visibility: &str) {
+            let name = member["name"].as_str().unwrap_or_default();
+            let path = format!("{}::{}", owner, name);
+            let inner = &member["inner"];
+            let line = if let Some(function_inner) = inner.get("function") {
+                function(&path, function_inner)
+            } else if let Some(constant) = inner.get("assoc_const") {
+                format!("const {}: {}", path, ty(&constant["type"]))
+            } else if let Some(assoc) = inner.get("assoc_type") {
+                let bounds = bounds(&assoc["bounds"]);
+                match assoc["type"].is_null() {
+                    true if bounds.is_empty() => format!("type {}", path),
+                    true => format!("type {}: {}", path, bounds),
+                    false => format!("type {} = {}", path, ty(&assoc["type"])),
+                }
+            } else {
+                return;
+            };
+            self.lines.insert(format!("{}{}", visibility, line));
+        }
+
+        fn variant_fields(&self, kind: &Value) -> String {
+            if let Some(fields) = kind["tuple"].as_array() {
+                let types: Vec<String> = fields
+                    .iter()
+                    .map(|field| self.get(field).map_or("_".to_string(), |field| ty(&field["inner"]["struct_field"])))
+                    .collect();
+                return format!("({})", types.join(", "));
+            }
+            if let Some(fields) = kind["struct"]["fields"].as_array() {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .filter_map(|field| self.get(field))
+                    .map(|field| format!("{}: {}", field["name"].as_str().unwrap_or_default(), ty(&field["inner"]["struct_field"])))
+                    .collect();
+                return format!(" {{ {} }}", fields.join(", "));
+            }
+            String::new()
+        }
+    }
+
+    fn function(path: &str, function: &Value) -> String {
+        let header = &function["header"];
+        let mut qualifiers = String::new();
+        for (flag, word) in [("is_const", "const "), ("is_async", "async "), ("is_unsafe", "unsafe ")] {
+            if header[flag] == true {
+                qualifiers.push_str(word);
+            }
+        }
+        let sig = &function["sig"];
+        let inputs: Vec<String> = sig["inputs"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|input| format!("{}: {}", input[0].as_str().unwrap_or_default(), ty(&input[1])))
+            .collect();
+        let output = match sig["output"].is_null() {
+            true => String::new(),
+            false => format!(" -> {}", ty(&sig["output"])),
+        };
+        let generic_params = &function["generics"];
+        format!("{}fn {}{}({}){}{}", qualifiers, path, generics(generic_params), inputs.join(", "), output, where_clause(generic_params))
+    }
+
+    fn generics(generics: &Value) -> String {
+        let params: Vec<String> = generics["params"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|param| {
+                let name = param["name"].as_str().unwrap_or_default();
+                let kind = &param["kind"];
+                if kind.get("lifetime").is_some() {
+                    return Some(name.to_string());
+                }
+                if let Some(kind) = kind.get("type") {
+                    if kind["is_synthetic"] == true {
+                        return None;
+                    }
+                    let bounds = bounds(&kind["bounds"]);
+                    return Some(if bounds.is_empty() { name.to_string() } else { format!("{}: {}", name, bounds) });
+                }
+                kind.get("const").map(|kind| format!("const {}: {}", name, ty(&kind["type"])))
+            })
+            .collect();
+        match params.is_empty() {
+            true => String::new(),
+            false => format!("<{}>", params.join(", ")),
+        }
+    }
+
+    fn where_clause(generics: &Value) -> String {
+        let predicates: Vec<String> = generics["where_predicates"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|predicate| {
+                if let Some(bound) = predicate.get("bound_predicate") {
+                    format!("{}: {}", ty(&bound["type"]), bounds(&bound["bounds"]))
+                } else if let Some(lifetime) = predicate.get("lifetime_predicate") {
+                    let outlives: Vec<&str> = lifetime["outlives"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+                    format!("{}: {}", lifetime["lifetime"].as_str().unwrap_or_default(), outlives.join(" + "))
+                } else {
+                    let equality = &predicate["eq_predicate"];
+                    format!("{} == {}", ty(&equality["lhs"]), term(&equality["rhs"]))
+                }
+            })
+            .collect();
+        match predicates.is_empty() {
+            true => String::new(),
+            false => format!(" where {}", predicates.join(", ")),
+        }
+    }
+
+    fn bounds(bounds: &Value) -> String {
+        let bounds: Vec<String> = bounds
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|bound| {
+                if let Some(trait_bound) = bound.get("trait_bound") {
+                    let maybe = if trait_bound["modifier"] == "maybe" { "?" } else { "" };
+                    format!("{}{}", maybe, path_type(&trait_bound["trait"]))
+                } else if let Some(outlives) = bound["outlives"].as_str() {
+                    outlives.to_string()
+                } else {
+                    "use<..>".to_string()
+                }
+            })
+            .collect();
+        bounds.join(" + ")
+    }
+
+    /// A path by its last segment, so moving an import does not count as an API change
+    fn path_type(path: &Value) -> String {
+        let name = path["path"].as_str().unwrap_or_default();
+        let name = name.rsplit("::").next().unwrap_or(name);
+        format!("{}{}", name, generic_args(&path["args"]))
+    }
+
+    fn generic_args(args: &Value) -> String {
+        if let Some(angle) = args.get("angle_bracketed") {
+            let mut rendered: Vec<String> = angle["args"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|arg| match arg {
+                    Value::String(infer) if infer == "infer" => "_".to_string(),
+                    arg if arg.get("lifetime").is_some() => arg["lifetime"].as_str().unwrap_or_default().to_string(),
+                    arg if arg.get("type").is_some() => ty(&arg["type"]),
+                    arg => arg["const"]["expr"].as_str().unwrap_or("_").to_string(),
+                })
+                .collect();
+            for constraint in angle["constraints"].as_array().into_iter().flatten() {
+                let name = format!("{}{}", constraint["name"].as_str().unwrap_or_default(), generic_args(&constraint["args"]));
+                let binding = &constraint["binding"];
+                rendered.push(match binding.get("equality") {
+                    Some(equality) => format!("{} = {}", name, term(equality)),
+                    None => format!("{}: {}", name, bounds(&binding["constraint"])),
+                });
+            }
+            return match rendered.is_empty() {
+                true => String::new(),
+                false => format!("<{}>", rendered.join(", ")),
+            };
+        }
+        if let Some(parenthesized) = args.get("parenthesized") {
+            let inputs: Vec<String> = parenthesized["inputs"].as_array().into_iter().flatten().map(ty).collect();
+            let output = match parenthesized["output"].is_null() {
+                true => String::new(),
+                false => format!(" -> {}", ty(&parenthesized["output"])),
+            };
+            return format!("({}){}", inputs.join(", "), output);
+        }
+        String::new()
+    }
+
+    fn term(term: &Value) -> String {
+        match term.get("type") {
+            Some(t) => ty(t),
+            None => term["constant"]["expr"].as_str().unwrap_or("_").to_string(),
+        }
+    }
+
+    fn ty(t: &Value) -> String {
+        let Some((kind, inner)) = t.as_object().and_then(|t| t.iter().next()) else {
+            return match t.as_str() {
+                Some("infer") => "_".to_string(),
+                _ => "?".to_string(),
+            };
+        };
+        match kind.as_str() {
+            "resolved_path" => path_type(inner),
+            "generic" | "primitive" => inner.as_str().unwrap_or_default().to_string(),
+            "dyn_trait" => {
+                let mut traits: Vec<String> = inner["traits"].as_array().into_iter().flatten().map(|poly| path_type(&poly["trait"])).collect();
+                if let Some(lifetime) = inner["lifetime"].as_str() {
+                    traits.push(lifetime.to_string());
+                }
+                format!("dyn {}", traits.join(" + "))
+            }
+            "borrowed_ref" => {
+                let lifetime = inner["lifetime"].as_str().map(|lifetime| format!("{} ", lifetime)).unwrap_or_default();
+                let mutability = if inner["is_mutable"] == true { "mut " } else { "" };
+                format!("&{}{}{}", lifetime, mutability, ty(&inner["type"]))
+            }
+            "raw_pointer" => format!("*{} {}", if inner["is_mutable"] == true { "mut" } else { "const" }, ty(&inner["type"])),
+            "tuple" => {
+                let types: Vec<String> = inner.as_array().into_iter().flatten().map(ty).collect();
+                match types.len() {
+                    1 => format!("({},)", types[0]),
+                    _ => format!("({})", types.join(", ")),
+                }
+            }
+            "slice" => format!("[{}]", ty(inner)),
+            "array" => format!("[{}; {}]", ty(&inner["type"]), inner["len"].as_str().unwrap_or("_")),
+            "impl_trait" => format!("impl {}", bounds(inner)),
+            "pat" => ty(&inner["type"]),
+            "function_pointer" => {
+                let sig = &inner["sig"];
+                let inputs: Vec<String> = sig["inputs"].as_array().into_iter().flatten().map(|input| ty(&input[1])).collect();
+                let output = match sig["output"].is_null() {
+                    true => String::new(),
+                    false => format!(" -> {}", ty(&sig["output"])),
+                };
+                format!("fn({}){}", inputs.join(", "), output)
+            }
+            "qualified_path" => {
+                let name = format!("{}{}", inner["name"].as_str().unwrap_or_default(), generic_args(&inner["args"]));
+                match inner["trait"].is_null() {
+                    true => format!("{}::{}", ty(&inner["self_type"]), name),
+                    false => format!("<{} as {}>::{}", ty(&inner["self_type"]), path_type(&inner["trait"]), name),
+                }
+            }
+            _ => "?".to_string(),
+        }
+    }
+}